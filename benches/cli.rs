@@ -0,0 +1,36 @@
+//! End to end benchmarks of the command line, on the files of
+//! `experiment_assets`. Run with `cargo bench --features bench`.
+//!
+//! The completion latency needs a running server, it is measured by the
+//! `bench` subcommand.
+
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use criterion::{Criterion, criterion_group, criterion_main};
+
+const ASSETS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/experiment_assets");
+
+fn run(args: &[&str]) {
+    Command::new(env!("CARGO_BIN_EXE_neocmakelsp-fast"))
+        .args(args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+}
+
+fn format(c: &mut Criterion) {
+    let file = Path::new(ASSETS).join("nheko.cmake");
+    let file = file.to_str().unwrap();
+    c.bench_function("format nheko.cmake", |b| b.iter(|| run(&["format", file])));
+}
+
+fn lint(c: &mut Criterion) {
+    c.bench_function("lint experiment_assets", |b| {
+        b.iter(|| run(&["lint", ASSETS]));
+    });
+}
+
+criterion_group!(benches, format, lint);
+criterion_main!(benches);
@@ -0,0 +1,138 @@
+//! Insert the terminator of a block, such as `endif()`, when a line opening
+//! one is ended and the block has none yet.
+
+use std::sync::LazyLock;
+
+use tower_lsp::lsp_types::{Position, Range, TextEdit};
+use tree_sitter::{Node, Point};
+
+use crate::CMakeNodeKinds;
+use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+static OPENER_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(r"(?i)^(?<indent>\s*)(?<keyword>if|foreach|while|function|macro|block)\s*\(")
+        .unwrap()
+});
+
+/// The command opening each block, the block and its terminator
+const BLOCKS: &[(&str, &str, &str)] = &[
+    (
+        CMakeNodeKinds::IF_COMMAND,
+        CMakeNodeKinds::IF_CONDITION,
+        CMakeNodeKinds::ENDIF_COMMAND,
+    ),
+    (
+        CMakeNodeKinds::FOREACH_COMMAND,
+        CMakeNodeKinds::FOREACH_LOOP,
+        CMakeNodeKinds::ENDFOREACH_COMMAND,
+    ),
+    (
+        CMakeNodeKinds::WHILE_COMMAND,
+        CMakeNodeKinds::WHILE_LOOP,
+        CMakeNodeKinds::ENDWHILE_COMMAND,
+    ),
+    (
+        CMakeNodeKinds::FUNCTION_COMMAND,
+        CMakeNodeKinds::FUNCTION_DEF,
+        CMakeNodeKinds::ENDFUNCTION_COMMAND,
+    ),
+    (
+        CMakeNodeKinds::MACRO_COMMAND,
+        CMakeNodeKinds::MACRO_DEF,
+        CMakeNodeKinds::ENDMACRO_COMMAND,
+    ),
+    (
+        CMakeNodeKinds::BLOCK_COMMAND,
+        CMakeNodeKinds::BLOCK_DEF,
+        CMakeNodeKinds::ENDBLOCK_COMMAND,
+    ),
+];
+
+/// Whether the block opened by `command` has its terminator
+fn is_terminated(command: Node) -> bool {
+    let Some((_, block_kind, end_kind)) =
+        BLOCKS.iter().find(|(kind, _, _)| *kind == command.kind())
+    else {
+        return true;
+    };
+    let Some(block) = command.parent().filter(|node| node.kind() == *block_kind) else {
+        return false;
+    };
+    let mut cursor = block.walk();
+    block
+        .children(&mut cursor)
+        .any(|child| child.kind() == *end_kind && !child.is_missing() && !child.has_error())
+}
+
+/// The edit adding the terminator after the line `position` is on, when the
+/// line before it opens a block without one
+pub fn close_block(source: &str, position: Position) -> Option<Vec<TextEdit>> {
+    let row = position.line as usize;
+    let lines: Vec<&str> = source.lines().collect();
+    let opener = lines.get(row.checked_sub(1)?)?;
+    let caps = OPENER_REGEX.captures(opener)?;
+    let indent = &caps["indent"];
+    let keyword = &caps["keyword"];
+
+    let mut parse = tree_sitter::Parser::new();
+    parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+    let tree = parse.parse(source, None)?;
+    let point = Point {
+        row: row - 1,
+        column: indent.len(),
+    };
+    let mut node = tree.root_node().descendant_for_point_range(point, point)?;
+    while !BLOCKS.iter().any(|(kind, _, _)| *kind == node.kind()) {
+        node = node.parent()?;
+    }
+    // The command goes on, or misses its `)`
+    if node.end_position().row != row - 1 || node.has_error() {
+        return None;
+    }
+    if is_terminated(node) {
+        return None;
+    }
+
+    let terminator = format!("end{keyword}()");
+    let terminator = if keyword.chars().all(|c| c.is_ascii_uppercase()) {
+        terminator.to_uppercase()
+    } else {
+        terminator.to_lowercase()
+    };
+    let end = Position {
+        line: row as u32,
+        character: lines.get(row).map_or(0, |line| line.len() as u32),
+    };
+    Some(vec![TextEdit {
+        range: Range { start: end, end },
+        new_text: format!("\n{indent}{terminator}"),
+    }])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(source: &str, line: u32) -> Option<String> {
+        let edits = close_block(source, Position { line, character: 2 })?;
+        Some(edits[0].new_text.clone())
+    }
+
+    #[test]
+    fn test_close_block() {
+        assert_eq!(close("if(A)\n  \n", 1).as_deref(), Some("\nendif()"));
+        assert_eq!(
+            close(
+                "function(f)\n  FOREACH(x IN LISTS y)\n    \nendfunction()\n",
+                2
+            )
+            .as_deref(),
+            Some("\n  ENDFOREACH()")
+        );
+        // Already closed
+        assert_eq!(close("if(A)\n  \nendif()\n", 1), None);
+        // Not an opener, or an unfinished one
+        assert_eq!(close("set(A b)\n  \n", 1), None);
+        assert_eq!(close("if(A AND\n  \n", 1), None);
+    }
+}
@@ -0,0 +1,187 @@
+//! Lint baseline: the diagnostics a legacy project already has, which are not reported.
+//!
+//! A diagnostic is recorded with its file, rule, message and the text of its line,
+//! not the line number, so editing the code above it does not bring it back.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{LazyLock, PoisonError, RwLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{self, CONFIG};
+use crate::gammar::ErrorInformation;
+use crate::utils::module_path;
+
+const BASELINE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BaselineEntry {
+    /// Path of the file, relative to the project
+    pub path: String,
+    /// Id of the rule, empty for the output of cmake-lint
+    pub rule: String,
+    pub message: String,
+    /// The trimmed line the diagnostic starts on
+    pub line: String,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Baseline {
+    pub version: u32,
+    pub entries: Vec<BaselineEntry>,
+}
+
+static BASELINE: LazyLock<RwLock<Option<Baseline>>> =
+    LazyLock::new(|| RwLock::new(load_configured()));
+
+/// The baseline file of the config. A relative path is left by a user config,
+/// and names a file of the project.
+fn load_configured() -> Option<Baseline> {
+    let configured = CONFIG.baseline.as_ref()?;
+    let path = match config::project_root() {
+        Some(root) => root.join(configured),
+        None => configured.clone(),
+    };
+    match Baseline::load(&path) {
+        Ok(baseline) => Some(baseline),
+        Err(err) => {
+            tracing::warn!("Cannot read the baseline {}: {err}", path.display());
+            None
+        }
+    }
+}
+
+/// Replace the baseline in use, `None` to report everything
+pub fn set_baseline(baseline: Option<Baseline>) {
+    *BASELINE.write().unwrap_or_else(PoisonError::into_inner) = baseline;
+}
+
+/// How the baseline names `local_path`
+pub fn path_key(local_path: &Path) -> String {
+    let root = module_path::source_dir().or_else(|| std::env::current_dir().ok());
+    let relative = root
+        .and_then(|root| local_path.strip_prefix(root).ok().map(Path::to_path_buf))
+        .unwrap_or_else(|| local_path.to_path_buf());
+    relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn entry_of(path: &str, source: &[&str], error: &ErrorInformation) -> BaselineEntry {
+    BaselineEntry {
+        path: path.to_string(),
+        rule: error
+            .rule
+            .map(|rule| rule.id.to_string())
+            .unwrap_or_default(),
+        message: error.message.clone(),
+        line: source
+            .get(error.start_point.row)
+            .map(|line| line.trim().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+impl Baseline {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let baseline: Self = serde_json::from_str(&content)?;
+        anyhow::ensure!(
+            baseline.version == BASELINE_VERSION,
+            "unsupported baseline version {}",
+            baseline.version
+        );
+        Ok(baseline)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)? + "\n")?;
+        Ok(())
+    }
+
+    /// Record the diagnostics of `local_path`
+    pub fn record(&mut self, local_path: &Path, source: &str, errors: &[ErrorInformation]) {
+        let path = path_key(local_path);
+        let lines: Vec<&str> = source.lines().collect();
+        self.version = BASELINE_VERSION;
+        self.entries
+            .extend(errors.iter().map(|error| entry_of(&path, &lines, error)));
+    }
+
+    /// Drop the diagnostics recorded in the baseline. A diagnostic recorded once
+    /// hides a single one, so copying a bad line is still reported.
+    pub fn filter(&self, local_path: &Path, source: &[&str], errors: &mut Vec<ErrorInformation>) {
+        let path = path_key(local_path);
+        let mut known: HashMap<&BaselineEntry, usize> = HashMap::new();
+        for entry in self.entries.iter().filter(|entry| entry.path == path) {
+            *known.entry(entry).or_default() += 1;
+        }
+        if known.is_empty() {
+            return;
+        }
+        errors.retain(|error| {
+            let entry = entry_of(&path, source, error);
+            match known.get_mut(&entry) {
+                Some(count) if *count > 0 => {
+                    *count -= 1;
+                    false
+                }
+                _ => true,
+            }
+        });
+    }
+}
+
+/// Drop the diagnostics recorded in the baseline in use
+pub fn apply_baseline(local_path: &Path, source: &[&str], errors: &mut Vec<ErrorInformation>) {
+    if let Some(baseline) = BASELINE
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_ref()
+    {
+        baseline.filter(local_path, source, errors);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::DiagnosticSeverity;
+    use tree_sitter::Point;
+
+    use super::*;
+    use crate::rules;
+
+    fn error(row: usize, message: &str) -> ErrorInformation {
+        ErrorInformation {
+            start_point: Point { row, column: 0 },
+            end_point: Point { row, column: 4 },
+            message: message.to_string(),
+            severity: Some(DiagnosticSeverity::WARNING),
+            rule: Some(&rules::UNQUOTED_COMPARISON),
+        }
+    }
+
+    #[test]
+    fn test_baseline_filter() {
+        let local_path = Path::new("/project/CMakeLists.txt");
+        let old_source = "if(${A} STREQUAL b)\nendif()\n";
+        let mut baseline = Baseline::default();
+        baseline.record(local_path, old_source, &[error(0, "quote it")]);
+
+        // The line moved, and was copied
+        let new_source = "set(X y)\nif(${A} STREQUAL b)\nendif()\nif(${A} STREQUAL b)\nendif()\n";
+        let lines: Vec<&str> = new_source.lines().collect();
+        let mut errors = vec![error(1, "quote it"), error(3, "quote it")];
+        baseline.filter(local_path, &lines, &mut errors);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].start_point.row, 3);
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("baseline.json");
+        baseline.save(&file).unwrap();
+        assert_eq!(Baseline::load(&file).unwrap(), baseline);
+    }
+}
@@ -0,0 +1,414 @@
+//! The `bench` subcommand: the latency of the completion, and the time taken to
+//! index and to format a workspace.
+//!
+//! The results can be kept in a baseline, so a change making the server slower
+//! on the same workspace fails the command.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tower_lsp::jsonrpc::{Request, Response};
+use tower_lsp::lsp_types::{
+    CompletionParams, DidOpenTextDocumentParams, InitializeParams, InitializedParams, Position,
+    TextDocumentIdentifier, TextDocumentItem, TextDocumentPositionParams, Uri, WorkspaceFolder,
+};
+use tower_lsp::{ClientSocket, LanguageServer, LspService};
+use tower_service::Service;
+
+use crate::{Backend, formatting, languageserver, lint, scansubs};
+
+/// The positions completed in each file, so large workspaces stay quick
+const MAX_POSITIONS: usize = 50;
+
+/// Slowdowns below this are noise, whatever the threshold
+const MIN_REGRESSION_MS: f64 = 1.0;
+
+#[derive(Debug)]
+pub struct BenchOptions {
+    /// Times each measurement is repeated
+    pub iterations: usize,
+    /// Print the report as JSON
+    pub json: bool,
+    /// Compare with this report, written when it does not exist
+    pub baseline: Option<PathBuf>,
+    /// Write the baseline even if it exists
+    pub update_baseline: bool,
+    /// Percentage of slowdown from the baseline reported as a regression
+    pub threshold: f64,
+}
+
+/// The distribution of the durations of an operation, in milliseconds
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Latency {
+    pub samples: usize,
+    pub mean_ms: f64,
+    pub median_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+}
+
+impl Latency {
+    fn from_samples(mut samples: Vec<Duration>) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+        samples.sort();
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        let quantile = |q: f64| ms(samples[((samples.len() - 1) as f64 * q).round() as usize]);
+        Self {
+            samples: samples.len(),
+            mean_ms: ms(samples.iter().sum()) / samples.len() as f64,
+            median_ms: quantile(0.5),
+            p95_ms: quantile(0.95),
+            max_ms: ms(samples[samples.len() - 1]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BenchReport {
+    /// The CMake files of the workspace
+    pub files: usize,
+    pub lines: usize,
+    /// One sample per completion request
+    pub completion: Latency,
+    /// One sample per iteration, indexing from the top level CMakeLists.txt
+    pub indexing: Latency,
+    /// The files reached by the indexing
+    pub indexed_files: usize,
+    /// One sample per iteration, formatting every file
+    pub formatting: Latency,
+}
+
+/// The positions completed in `source`: at the end of the name of each
+/// command, and after its opening parenthesis
+fn completion_positions(source: &str) -> Vec<Position> {
+    source
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim_start().starts_with('#'))
+        .filter_map(|(row, line)| {
+            let open = line.find('(')?;
+            let name = line[..open].trim_end();
+            if name.trim_start().is_empty() {
+                return None;
+            }
+            let row = row as u32;
+            Some([
+                Position::new(row, name.len() as u32),
+                Position::new(row, open as u32 + 1),
+            ])
+        })
+        .flatten()
+        .take(MAX_POSITIONS)
+        .collect()
+}
+
+async fn bench_completion(
+    root: &Path,
+    sources: &[(PathBuf, String)],
+    iterations: usize,
+) -> Result<Latency> {
+    let (mut service, socket) = languageserver::service();
+    tokio::spawn(answer_client(socket));
+    let initialize = InitializeParams {
+        workspace_folders: Some(vec![WorkspaceFolder {
+            uri: Uri::from_file_path(root).unwrap(),
+            name: "bench".to_string(),
+        }]),
+        ..Default::default()
+    };
+    call(
+        &mut service,
+        Request::build("initialize")
+            .id(0)
+            .params(serde_json::to_value(initialize)?)
+            .finish(),
+    )
+    .await
+    .context("Failed to initialize the server")?;
+    call(
+        &mut service,
+        Request::build("initialized")
+            .params(serde_json::to_value(InitializedParams {})?)
+            .finish(),
+    )
+    .await
+    .context("Failed to initialize the server")?;
+    let backend = service.inner();
+
+    let mut samples = vec![];
+    for (path, source) in sources {
+        let uri = Uri::from_file_path(path).unwrap();
+        backend
+            .did_open(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem {
+                    uri: uri.clone(),
+                    language_id: "cmake".to_string(),
+                    version: 0,
+                    text: source.clone(),
+                },
+            })
+            .await;
+        let positions = completion_positions(source);
+        for _ in 0..iterations {
+            for position in &positions {
+                let params = CompletionParams {
+                    text_document_position: TextDocumentPositionParams {
+                        text_document: TextDocumentIdentifier { uri: uri.clone() },
+                        position: *position,
+                    },
+                    work_done_progress_params: Default::default(),
+                    partial_result_params: Default::default(),
+                    context: None,
+                };
+                let started = Instant::now();
+                let _ = backend.completion(params).await;
+                samples.push(started.elapsed());
+            }
+        }
+    }
+    Ok(Latency::from_samples(samples))
+}
+
+/// Send `request` through the service, so the server goes through the same
+/// states as with an editor
+async fn call(service: &mut LspService<Backend>, request: Request) -> Result<Option<Response>> {
+    std::future::poll_fn(|cx| service.poll_ready(cx)).await?;
+    Ok(service.call(request).await?)
+}
+
+/// Play the editor: read the messages of the server, and answer its requests
+/// with null. Nothing reading them blocks the server at its first message.
+async fn answer_client(socket: ClientSocket) {
+    let (mut requests, mut responses) = socket.split();
+    while let Some(request) = requests.next().await {
+        if let Some(id) = request.id() {
+            let response = Response::from_ok(id.clone(), Value::Null);
+            if responses.send(response).await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// Measure the workspace at `root`
+async fn measure(root: &Path, iterations: usize) -> Result<BenchReport> {
+    let root = std::path::absolute(root)?;
+    let sources: Vec<(PathBuf, String)> = lint::collect_files(std::slice::from_ref(&root))
+        .into_iter()
+        .filter_map(|path| {
+            let source = std::fs::read_to_string(&path).ok()?;
+            Some((path, source))
+        })
+        .collect();
+    anyhow::ensure!(
+        !sources.is_empty(),
+        "No CMake file found in {}",
+        root.display()
+    );
+
+    let mut report = BenchReport {
+        files: sources.len(),
+        lines: sources
+            .iter()
+            .map(|(_, source)| source.lines().count())
+            .sum(),
+        ..Default::default()
+    };
+
+    let mut samples = vec![];
+    for _ in 0..iterations {
+        let started = Instant::now();
+        report.indexed_files = scansubs::scan_all(&root, true).await;
+        samples.push(started.elapsed());
+    }
+    report.indexing = Latency::from_samples(samples);
+
+    let mut samples = vec![];
+    for _ in 0..iterations {
+        let started = Instant::now();
+        for (path, source) in &sources {
+            let _ = formatting::get_format_cli(Some(path), source, 2, true, false);
+        }
+        samples.push(started.elapsed());
+    }
+    report.formatting = Latency::from_samples(samples);
+
+    report.completion = bench_completion(&root, &sources, iterations).await?;
+    Ok(report)
+}
+
+/// The measurements of `report` slower than in `baseline` by more than
+/// `threshold` percent
+fn regressions(baseline: &BenchReport, report: &BenchReport, threshold: f64) -> Vec<String> {
+    let metrics = [
+        (
+            "completion median",
+            baseline.completion.median_ms,
+            report.completion.median_ms,
+        ),
+        (
+            "completion p95",
+            baseline.completion.p95_ms,
+            report.completion.p95_ms,
+        ),
+        (
+            "indexing",
+            baseline.indexing.median_ms,
+            report.indexing.median_ms,
+        ),
+        (
+            "formatting",
+            baseline.formatting.median_ms,
+            report.formatting.median_ms,
+        ),
+    ];
+    metrics
+        .into_iter()
+        .filter(|(_, before, after)| {
+            after - before >= MIN_REGRESSION_MS && *after > before * (1.0 + threshold / 100.0)
+        })
+        .map(|(name, before, after)| {
+            format!("{name}: {after:.2} ms, {before:.2} ms in the baseline")
+        })
+        .collect()
+}
+
+fn print_report(report: &BenchReport) {
+    let per_second = |count: usize, ms: f64| {
+        if ms > 0.0 {
+            count as f64 * 1000.0 / ms
+        } else {
+            0.0
+        }
+    };
+    println!("{} file(s), {} line(s)", report.files, report.lines);
+    println!(
+        "completion: {} request(s), median {:.2} ms, p95 {:.2} ms, max {:.2} ms",
+        report.completion.samples,
+        report.completion.median_ms,
+        report.completion.p95_ms,
+        report.completion.max_ms
+    );
+    println!(
+        "indexing: {} file(s) in {:.2} ms ({:.0} files/s)",
+        report.indexed_files,
+        report.indexing.median_ms,
+        per_second(report.indexed_files, report.indexing.median_ms)
+    );
+    println!(
+        "formatting: {:.2} ms ({:.0} lines/s)",
+        report.formatting.median_ms,
+        per_second(report.lines, report.formatting.median_ms)
+    );
+}
+
+/// Measure the workspace at `path`, and print the report.
+/// Returns whether a measurement regressed from the baseline.
+pub async fn bench_path(path: &Path, options: BenchOptions) -> Result<bool> {
+    let report = measure(path, options.iterations.max(1)).await?;
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_report(&report);
+    }
+    let Some(baseline_path) = options.baseline else {
+        return Ok(false);
+    };
+    if options.update_baseline || !baseline_path.exists() {
+        std::fs::write(
+            &baseline_path,
+            serde_json::to_string_pretty(&report)? + "\n",
+        )?;
+        eprintln!("Baseline written to {}", baseline_path.display());
+        return Ok(false);
+    }
+    let baseline: BenchReport = serde_json::from_str(&std::fs::read_to_string(&baseline_path)?)
+        .with_context(|| format!("Failed to read the baseline {}", baseline_path.display()))?;
+    let regressions = regressions(&baseline, &report, options.threshold);
+    for regression in &regressions {
+        eprintln!("Regression: {regression}");
+    }
+    Ok(!regressions.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency() {
+        let samples = (1..=20).map(Duration::from_millis).collect();
+        let latency = Latency::from_samples(samples);
+        assert_eq!(latency.samples, 20);
+        assert_eq!(latency.median_ms, 11.0);
+        assert_eq!(latency.p95_ms, 19.0);
+        assert_eq!(latency.max_ms, 20.0);
+        assert_eq!(Latency::from_samples(vec![]), Latency::default());
+    }
+
+    #[test]
+    fn test_completion_positions() {
+        let positions =
+            completion_positions("# set(A)\nproject(demo)\n  add_library(core STATIC\n  a.c)\n");
+        assert_eq!(
+            positions,
+            vec![
+                Position::new(1, 7),
+                Position::new(1, 8),
+                Position::new(2, 13),
+                Position::new(2, 14),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_regressions() {
+        let mut baseline = BenchReport::default();
+        baseline.completion.median_ms = 2.0;
+        baseline.indexing.median_ms = 100.0;
+        let mut report = baseline.clone();
+        report.completion.median_ms = 2.5;
+        report.indexing.median_ms = 150.0;
+        assert_eq!(
+            regressions(&baseline, &report, 20.0),
+            vec!["indexing: 150.00 ms, 100.00 ms in the baseline"]
+        );
+        assert!(regressions(&baseline, &report, 60.0).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_bench_path() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("CMakeLists.txt"),
+            "cmake_minimum_required(VERSION 3.20)\nproject(bench)\nadd_executable(app main.cpp)\n",
+        )
+        .unwrap();
+        let baseline = dir.path().join("baseline.json");
+        let options = BenchOptions {
+            iterations: 1,
+            json: true,
+            baseline: Some(baseline.clone()),
+            update_baseline: false,
+            threshold: 20.0,
+        };
+        let regressed =
+            tokio::time::timeout(Duration::from_secs(60), bench_path(dir.path(), options))
+                .await
+                .expect("the bench does not finish")
+                .unwrap();
+        assert!(!regressed);
+        let report: BenchReport =
+            serde_json::from_str(&std::fs::read_to_string(baseline).unwrap()).unwrap();
+        assert_eq!(report.files, 1);
+        assert_eq!(report.completion.samples, 6);
+    }
+}
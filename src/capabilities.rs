@@ -0,0 +1,135 @@
+//! The `capabilities` subcommand: what this build of the server provides.
+//!
+//! Editor extensions read the JSON report to adapt their UI to the installed
+//! binary, and users to check that a feature or a lint rule is there.
+
+use std::fmt;
+
+use serde::Serialize;
+use tower_lsp::lsp_types::ServerCapabilities;
+use tower_lsp::lsp_types::notification::Notification;
+
+use crate::indexed::Indexed;
+use crate::languageserver::config::Config;
+use crate::languageserver::{self, CUSTOM_REQUESTS, LINT_COMMAND};
+use crate::lint;
+use crate::rules::RULES;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleReport {
+    pub id: &'static str,
+    pub name: &'static str,
+    /// The default severity, like `warning`
+    pub severity: &'static str,
+    pub category: String,
+    pub url: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CapabilityReport {
+    pub name: &'static str,
+    pub version: &'static str,
+    /// The capabilities sent to the editor with the default initialization options
+    pub capabilities: ServerCapabilities,
+    /// The methods of the requests beside the ones of the protocol
+    pub custom_requests: Vec<&'static str>,
+    pub custom_notifications: Vec<&'static str>,
+    /// The commands of `workspace/executeCommand`
+    pub commands: Vec<&'static str>,
+    pub rules: Vec<RuleReport>,
+}
+
+pub fn capability_report() -> CapabilityReport {
+    CapabilityReport {
+        name: env!("CARGO_PKG_NAME"),
+        version: env!("CARGO_PKG_VERSION"),
+        capabilities: languageserver::server_capabilities(&Config::default()),
+        custom_requests: CUSTOM_REQUESTS.to_vec(),
+        custom_notifications: vec![Indexed::METHOD],
+        commands: vec![LINT_COMMAND],
+        rules: RULES
+            .iter()
+            .map(|rule| RuleReport {
+                id: rule.id,
+                name: rule.name,
+                severity: lint::severity_name(Some(rule.severity)),
+                category: rule.category.to_string(),
+                url: rule.url(),
+            })
+            .collect(),
+    }
+}
+
+/// The names of the capabilities the server provides, like `hoverProvider`
+fn provided(capabilities: &ServerCapabilities) -> Vec<String> {
+    let Ok(serde_json::Value::Object(fields)) = serde_json::to_value(capabilities) else {
+        return vec![];
+    };
+    fields
+        .into_iter()
+        .filter(|(_, value)| !value.is_null() && *value != serde_json::Value::Bool(false))
+        .map(|(name, _)| name)
+        .collect()
+}
+
+impl fmt::Display for CapabilityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} {}", self.name, self.version)?;
+        writeln!(f, "\nCapabilities:")?;
+        for capability in provided(&self.capabilities) {
+            writeln!(f, "  {capability}")?;
+        }
+        writeln!(f, "\nCustom requests:")?;
+        for method in &self.custom_requests {
+            writeln!(f, "  {method}")?;
+        }
+        writeln!(f, "\nCustom notifications:")?;
+        for method in &self.custom_notifications {
+            writeln!(f, "  {method}")?;
+        }
+        writeln!(f, "\nCommands:")?;
+        for command in &self.commands {
+            writeln!(f, "  {command}")?;
+        }
+        writeln!(f, "\nLint rules:")?;
+        for rule in &self.rules {
+            writeln!(
+                f,
+                "  {} {} ({}, {})",
+                rule.id, rule.name, rule.severity, rule.category
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capability_report() {
+        let report = capability_report();
+        assert_eq!(report.rules.len(), RULES.len());
+        assert!(report.capabilities.hover_provider.is_some());
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(json["rules"][0]["id"], "N001");
+        assert_eq!(json["rules"][0]["severity"], "error");
+        assert_eq!(json["customNotifications"][0], "neocmake/indexed");
+        assert!(report.custom_requests.contains(&"neocmake/status"));
+        assert!(report.custom_requests.contains(&"neocmake/formatWorkspace"));
+        assert!(json["capabilities"]["completionProvider"].is_object());
+
+        let names = provided(&report.capabilities);
+        assert!(names.contains(&"hoverProvider".to_string()));
+        assert!(names.contains(&"inlayHintProvider".to_string()));
+        assert!(
+            report
+                .to_string()
+                .contains("N401 command-case (hint, style)")
+        );
+    }
+}
@@ -4,6 +4,8 @@ use clap::builder::Styles;
 use clap::builder::styling::{AnsiColor, Effects};
 use clap::{Parser, Subcommand, ValueHint};
 
+use crate::scaffold::Language;
+
 const STYLES: Styles = Styles::styled()
     .header(
         AnsiColor::Yellow
@@ -46,12 +48,103 @@ pub(crate) enum Command {
     /// Format a CMake file.
     Format {
         /// Files to format.
-        #[arg(required = true)]
+        #[arg(required_unless_present = "stdin")]
         files: Vec<PathBuf>,
 
         /// Write the formatted contents to the file.
         #[arg(short, long, short_alias = 'o', alias = "override")]
         inplace: bool,
+
+        /// Format the content of stdin to stdout.
+        #[arg(long, conflicts_with_all = ["files", "inplace"])]
+        stdin: bool,
+
+        /// Path of the content of stdin, for its config files.
+        #[arg(long, requires = "stdin", value_hint = ValueHint::FilePath)]
+        stdin_filename: Option<PathBuf>,
+    },
+
+    /// Check CMake files, and exit with an error code when errors are found.
+    Lint {
+        /// Files or directories to check.
+        #[arg(default_value = ".", value_hint = ValueHint::AnyPath)]
+        paths: Vec<PathBuf>,
+
+        /// Apply the fixes which keep what the files do.
+        #[arg(long)]
+        fix: bool,
+
+        /// Report only the problems missing from this baseline, written when it does not exist.
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        baseline: Option<PathBuf>,
+
+        /// Write the baseline even if it exists.
+        #[arg(long, requires = "baseline")]
+        update_baseline: bool,
+    },
+
+    /// Measure the completion latency, and the indexing and formatting times of a workspace.
+    Bench {
+        /// Root directory of the workspace.
+        #[arg(default_value = ".", value_hint = ValueHint::DirPath)]
+        path: PathBuf,
+
+        /// Times each measurement is repeated.
+        #[arg(short = 'n', long, default_value_t = 5)]
+        iterations: usize,
+
+        /// Generate JSON output.
+        #[arg(short, long)]
+        json: bool,
+
+        /// Fail when slower than this report, written when it does not exist.
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        baseline: Option<PathBuf>,
+
+        /// Write the baseline even if it exists.
+        #[arg(long, requires = "baseline")]
+        update_baseline: bool,
+
+        /// Percentage of slowdown from the baseline failing the command.
+        #[arg(long, default_value_t = 20.0, requires = "baseline")]
+        threshold: f64,
+    },
+
+    /// Create a starter project.
+    New {
+        /// Directory of the project, created if needed.
+        #[arg(value_hint = ValueHint::DirPath)]
+        path: PathBuf,
+
+        /// Name of the project, the directory name by default.
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Language of the sources.
+        #[arg(long, value_enum, default_value_t = Language::Cxx)]
+        language: Language,
+
+        /// Language standard, like 20 for C++20. C11 or C++17 by default.
+        #[arg(long)]
+        standard: Option<u32>,
+
+        /// Add a tests directory run with CTest.
+        #[arg(long)]
+        tests: bool,
+
+        /// Add a CMakePresets.json file.
+        #[arg(long)]
+        presets: bool,
+    },
+
+    /// Check the cmake installation and the system, and print how to fix the problems.
+    Doctor,
+
+    /// Print the LSP capabilities and the lint rules of this build.
+    Capabilities {
+        /// Generate JSON output.
+        #[arg(short, long)]
+        json: bool,
     },
 
     /// Find a CMake module by name.
@@ -1,26 +1,48 @@
-mod builtin;
+pub mod builtin;
+mod context;
+mod cpack;
+mod ctest;
+mod custom_command;
+mod debugging;
+mod export;
+mod families;
 mod findpackage;
+pub mod generator;
+mod gpu;
 mod includescanner;
 mod path_complete;
+mod platforms;
+mod source_group;
+mod toolchain;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, LazyLock};
+use std::sync::{Arc, LazyLock, PoisonError};
 
-use builtin::{BUILTIN_COMMAND, BUILTIN_MODULE, BUILTIN_VARIABLE};
+use builtin::{BUILTIN_COMMAND, BUILTIN_MODULE, BUILTIN_VARIABLE, KNOWN_COMMANDS, QT_COMMANDS};
+use context::{CompletionContext, Origin};
 use dashmap::DashMap;
 use tokio::sync::Mutex;
 use tower_lsp::lsp_types::{
-    CompletionItem, CompletionItemKind, CompletionResponse, Documentation, InsertTextFormat,
-    MessageType, Position, Uri,
+    CompletionItem, CompletionItemKind, CompletionResponse, CompletionTextEdit, Documentation,
+    InsertTextFormat, MessageType, Position, Range, TextEdit, Uri,
 };
 
 use crate::consts::TREESITTER_CMAKE_LANGUAGE;
 use crate::languageserver::{get_or_update_buffer_contents, to_use_snippet};
 use crate::scansubs::TREE_MAP;
-use crate::utils::treehelper::{PositionType, ToPoint, get_pos_type};
+use crate::utils::document::Document;
+use crate::utils::line_index::LineIndex;
+use crate::utils::parse_arguments::{
+    FUNCTION_KEYWORDS, parse_arguments_of, update_function_keywords,
+};
+use crate::utils::script_mode::{self, FileMode};
+use crate::utils::toolchain::is_toolchain_file;
+use crate::utils::treehelper::{
+    PositionType, ToPoint, command_at, get_pos_type, innermost_node, is_in_comment_or_bracket,
+};
 use crate::utils::{
-    CACHE_CMAKE_PACKAGES_WITHKEYS, LineCommentTmp, gen_module_pattern, include_is_module,
-    remove_quotation_and_replace_placeholders,
+    CACHE_CMAKE_PACKAGES_WITHKEYS, LineCommentTmp, function_doc, loops, module_path,
+    path_executables, qt, remove_quotation_and_replace_placeholders, resolve_include,
 };
 use crate::{CMakeNodeKinds, fileapi};
 
@@ -76,6 +98,8 @@ pub async fn update_cache<P: AsRef<Path>>(path: P, context: &str) -> Vec<Complet
     parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
     let thetree = parse.parse(context, None);
     let tree = thetree.unwrap();
+    // The definitions removed from the file go with the old documentation
+    function_doc::forget_file(path.as_ref());
     let Some(result_data) = getsubcomplete(
         tree.root_node(),
         &context.lines().collect(),
@@ -94,9 +118,24 @@ pub async fn update_cache<P: AsRef<Path>>(path: P, context: &str) -> Vec<Complet
     result_data
 }
 
+/// The number of functions and macros the project defines
+pub async fn defined_functions() -> usize {
+    let cache = COMPLETE_CACHE.lock().await;
+    let mut functions: Vec<&str> = cache
+        .values()
+        .flatten()
+        .filter(|item| item.kind == Some(CompletionItemKind::FUNCTION))
+        .map(|item| item.label.as_str())
+        .collect();
+    // The items of the included files are also cached with the files including them
+    functions.sort_unstable();
+    functions.dedup();
+    functions.len()
+}
+
 pub async fn get_cached_completion<P: AsRef<Path>>(
     path: P,
-    documents: &DashMap<Uri, String>,
+    documents: &DashMap<Uri, Document>,
 ) -> Vec<CompletionItem> {
     let mut path = path.as_ref().to_path_buf();
     let mut completions = Vec::new();
@@ -120,32 +159,128 @@ pub async fn get_cached_completion<P: AsRef<Path>>(
 }
 
 /// get the complete messages
+#[allow(clippy::too_many_arguments)]
 pub async fn getcomplete<P: AsRef<Path>>(
     source: &str,
+    tree: &tree_sitter::Tree,
+    lines: &LineIndex,
     location: Position,
     client: &tower_lsp::Client,
     local_path: P,
     find_cmake_in_package: bool,
-    documents: &DashMap<Uri, String>,
+    documents: &DashMap<Uri, Document>,
 ) -> Option<CompletionResponse> {
     let local_path = local_path.as_ref();
-    let mut parse = tree_sitter::Parser::new();
-    parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
-    let thetree = parse.parse(source, None);
-    let tree = thetree.unwrap();
     let mut complete: Vec<CompletionItem> = vec![];
 
     let current_point = location.to_point();
+    if is_in_comment_or_bracket(current_point, tree.root_node()) {
+        client.log_message(MessageType::INFO, "Empty").await;
+        return None;
+    }
+    if let Some(name_start) = unclosed_variable_ref(source, location) {
+        let mut variables = get_cached_completion(local_path, documents).await;
+        if let Some(mut message) = getsubcomplete(
+            tree.root_node(),
+            &source.lines().collect(),
+            local_path,
+            PositionType::VarOrFun,
+            Some(location),
+            &mut Vec::new(),
+            &mut Vec::new(),
+            true,
+            find_cmake_in_package,
+        ) {
+            variables.append(&mut message);
+        }
+        if let Some(mut cmake_cache) = fileapi::get_complete_data() {
+            variables.append(&mut cmake_cache);
+        }
+        if let Ok(messages) = &*BUILTIN_VARIABLE {
+            variables.append(&mut messages.clone());
+        }
+        variables.append(&mut families::missing_variables(&variables));
+        let replace_range = Range {
+            start: Position {
+                line: location.line,
+                character: name_start,
+            },
+            end: location,
+        };
+        if is_script(local_path, source, tree.root_node()) {
+            variables.retain(|item| !is_project_only(item));
+        }
+        let close_brace = to_use_snippet() && !is_followed_by_brace(source, location);
+        let items = variable_ref_completions(variables, replace_range, close_brace);
+        if items.is_empty() {
+            return None;
+        }
+        return Some(CompletionResponse::Array(items));
+    }
+
     let postype = get_pos_type(current_point, tree.root_node(), source);
     match postype {
         PositionType::VarOrFun
         | PositionType::TargetLink
         | PositionType::TargetInclude
         | PositionType::ArgumentOrList => {
+            let executables_on_path = path_executables().await;
+            let is_toolchain = is_toolchain_file(local_path, &source.lines().collect::<Vec<_>>());
             // Check if input looks like a path - if so, return ONLY path completions
-            let partial_info =
-                path_complete::extract_partial_path(source, location.line, location.character);
-            if path_complete::looks_like_path(&partial_info.path) {
+            let partial_info = path_complete::extract_partial_path_indexed(
+                source,
+                lines,
+                location.line,
+                location.character,
+            );
+            if !path_complete::looks_like_path(&partial_info.path)
+                && let Some(mut programs) = custom_command::command_completions(
+                    local_path,
+                    source,
+                    tree.root_node(),
+                    location,
+                    executables_on_path,
+                )
+            {
+                // The program run by `COMMAND`
+                complete.append(&mut programs);
+            } else if let Some(mut properties) =
+                ctest::property_completions(source, tree.root_node(), location)
+            {
+                // The property of `set_tests_properties(<tests> PROPERTIES |)`
+                complete.append(&mut properties);
+            } else if let Some(mut exports) =
+                export::export_set_completions(local_path, source, tree.root_node(), location)
+            {
+                // The export set after `EXPORT` in `install()` or `export()`
+                complete.append(&mut exports);
+            } else if let Some(mut generators) =
+                cpack::generator_completions(source, tree.root_node(), location)
+            {
+                // The value of `set(CPACK_GENERATOR |)`
+                complete.append(&mut generators);
+            } else if let Some(mut generators) =
+                generator::generator_completions(source, tree.root_node(), location)
+            {
+                // The value of `set(CMAKE_GENERATOR |)`
+                complete.append(&mut generators);
+            } else if let Some(mut architectures) =
+                gpu::architecture_completions(source, tree.root_node(), location)
+            {
+                // The value of `set(CMAKE_CUDA_ARCHITECTURES |)`
+                complete.append(&mut architectures);
+            } else if is_toolchain
+                && let Some(mut values) = toolchain::value_completions(
+                    local_path,
+                    source,
+                    tree.root_node(),
+                    location,
+                    executables_on_path,
+                )
+            {
+                // The value of a toolchain variable, like `set(CMAKE_SYSTEM_NAME |)`
+                complete.append(&mut values);
+            } else if path_complete::looks_like_path(&partial_info.path) {
                 let mut path_completions = path_complete::get_any_file_completions(
                     local_path,
                     &partial_info,
@@ -155,17 +290,16 @@ pub async fn getcomplete<P: AsRef<Path>>(
                 complete.append(&mut path_completions);
                 // Don't add other completions when user is typing a path
             } else {
-                // Normal completions (not a path)
-                let mut cached_completion = get_cached_completion(local_path, documents).await;
-                if !cached_completion.is_empty() {
-                    complete.append(&mut cached_completion);
-                }
+                // Normal completions (not a path), ranked for where they are asked
+                let lines: Vec<&str> = source.lines().collect();
+                let context = context::completion_context(current_point, tree.root_node(), &lines);
+                let mut project = get_cached_completion(local_path, documents).await;
                 if let Some(mut cmake_cache) = fileapi::get_complete_data() {
-                    complete.append(&mut cmake_cache);
+                    project.append(&mut cmake_cache);
                 }
                 if let Some(mut message) = getsubcomplete(
                     tree.root_node(),
-                    &source.lines().collect(),
+                    &lines,
                     Path::new(local_path),
                     postype,
                     Some(location),
@@ -174,23 +308,70 @@ pub async fn getcomplete<P: AsRef<Path>>(
                     true,
                     find_cmake_in_package,
                 ) {
-                    complete.append(&mut message);
+                    project.append(&mut message);
+                }
+                // After getsubcomplete, which records the keywords of the local functions
+                project.append(&mut parse_arguments_completions(
+                    current_point,
+                    tree.root_node(),
+                    source,
+                ));
+                project.append(&mut ctest::keyword_completions(
+                    source,
+                    tree.root_node(),
+                    location,
+                ));
+                project.append(&mut source_group::keyword_completions(
+                    source,
+                    tree.root_node(),
+                    location,
+                ));
+                complete.append(&mut context.ranked(Origin::Project, project));
+
+                match context {
+                    CompletionContext::Condition => {
+                        complete.append(
+                            &mut context.ranked(Origin::Operator, context::operator_completions()),
+                        );
+                    }
+                    CompletionContext::LinkLibraries => {
+                        let targets =
+                            context::library_completions(local_path, &lines, tree.root_node());
+                        complete.append(&mut context.ranked(Origin::Target, targets));
+                    }
+                    CompletionContext::Statement | CompletionContext::Arguments => {}
                 }
 
+                let mut builtin = vec![];
                 if let Ok(messages) = &*BUILTIN_COMMAND
                     && !matches!(postype, PositionType::ArgumentOrList)
                 {
-                    complete.append(&mut messages.clone());
+                    builtin.append(&mut messages.clone());
+                }
+                if !matches!(postype, PositionType::ArgumentOrList) {
+                    builtin.append(&mut KNOWN_COMMANDS.clone());
+                    if qt::uses_qt() {
+                        builtin.append(&mut QT_COMMANDS.clone());
+                    }
+                    builtin.append(&mut debugging::debugging_completions(source, location));
                 }
                 if let Ok(messages) = &*BUILTIN_VARIABLE {
-                    complete.append(&mut messages.clone());
+                    builtin.append(&mut messages.clone());
+                }
+                builtin.append(&mut cpack::missing_variables(&builtin));
+                builtin.append(&mut families::missing_variables(&builtin));
+                platforms::replace_variables(&mut builtin);
+                if is_toolchain {
+                    builtin.append(&mut toolchain::TOOLCHAIN_VARIABLES.clone());
                 }
+                complete.append(&mut context.ranked(Origin::Builtin, builtin));
             }
         }
         PositionType::FindPackageSpace(space) => {
             complete.append(&mut findpackage::completion_items_with_prefix(space));
         }
         PositionType::FindPackage => {
+            complete.append(&mut findpackage::project_package_items());
             complete.append(&mut findpackage::CMAKE_SOURCE.clone());
         }
         #[cfg(unix)]
@@ -199,8 +380,12 @@ pub async fn getcomplete<P: AsRef<Path>>(
         }
         PositionType::Include => {
             // Get partial path from current position
-            let partial_info =
-                path_complete::extract_partial_path(source, location.line, location.character);
+            let partial_info = path_complete::extract_partial_path_indexed(
+                source,
+                lines,
+                location.line,
+                location.character,
+            );
 
             // If input looks like a path, show ONLY path completions
             if path_complete::looks_like_path(&partial_info.path) {
@@ -230,6 +415,17 @@ pub async fn getcomplete<P: AsRef<Path>>(
                 if let Some(mut cmake_cache) = fileapi::get_complete_data() {
                     complete.append(&mut cmake_cache);
                 }
+                complete.extend(
+                    module_path::project_modules()
+                        .into_iter()
+                        .map(|(name, path)| CompletionItem {
+                            label: name,
+                            kind: Some(CompletionItemKind::MODULE),
+                            detail: Some("Module".to_string()),
+                            documentation: Some(Documentation::String(path.display().to_string())),
+                            ..Default::default()
+                        }),
+                );
                 if let Ok(messages) = &*BUILTIN_MODULE {
                     complete.append(&mut messages.clone());
                 }
@@ -237,8 +433,12 @@ pub async fn getcomplete<P: AsRef<Path>>(
         }
         PositionType::SubDir => {
             // Get partial path from current position
-            let partial_info =
-                path_complete::extract_partial_path(source, location.line, location.character);
+            let partial_info = path_complete::extract_partial_path_indexed(
+                source,
+                lines,
+                location.line,
+                location.character,
+            );
 
             // Add directory completions
             let mut path_completions = path_complete::get_subdirectory_completions(
@@ -251,8 +451,12 @@ pub async fn getcomplete<P: AsRef<Path>>(
         }
         PositionType::SourceFile => {
             // Get partial path from current position
-            let partial_info =
-                path_complete::extract_partial_path(source, location.line, location.character);
+            let partial_info = path_complete::extract_partial_path_indexed(
+                source,
+                lines,
+                location.line,
+                location.character,
+            );
 
             // If input looks like a path, show ONLY path completions
             if path_complete::looks_like_path(&partial_info.path) {
@@ -289,8 +493,12 @@ pub async fn getcomplete<P: AsRef<Path>>(
         }
         PositionType::AnyFile => {
             // Get partial path from current position
-            let partial_info =
-                path_complete::extract_partial_path(source, location.line, location.character);
+            let partial_info = path_complete::extract_partial_path_indexed(
+                source,
+                lines,
+                location.line,
+                location.character,
+            );
 
             // If input looks like a path, show ONLY path completions
             if path_complete::looks_like_path(&partial_info.path) {
@@ -327,8 +535,12 @@ pub async fn getcomplete<P: AsRef<Path>>(
         }
         PositionType::Directory => {
             // Get partial path from current position
-            let partial_info =
-                path_complete::extract_partial_path(source, location.line, location.character);
+            let partial_info = path_complete::extract_partial_path_indexed(
+                source,
+                lines,
+                location.line,
+                location.character,
+            );
 
             // If input looks like a path, show ONLY path completions
             if path_complete::looks_like_path(&partial_info.path) {
@@ -371,8 +583,12 @@ pub async fn getcomplete<P: AsRef<Path>>(
         | PositionType::FunOrMacroArgs
         | PositionType::FunOrMacroIdentifier => {
             // For unknown/error positions, check if input looks like a path
-            let partial_info =
-                path_complete::extract_partial_path(source, location.line, location.character);
+            let partial_info = path_complete::extract_partial_path_indexed(
+                source,
+                lines,
+                location.line,
+                location.character,
+            );
             if path_complete::looks_like_path(&partial_info.path) {
                 let mut path_completions = path_complete::get_any_file_completions(
                     local_path,
@@ -385,6 +601,10 @@ pub async fn getcomplete<P: AsRef<Path>>(
         }
     }
 
+    if is_script(local_path, source, tree.root_node()) {
+        complete.retain(|item| !is_project_only(item));
+    }
+
     if complete.is_empty() {
         client.log_message(MessageType::INFO, "Empty").await;
         None
@@ -393,6 +613,151 @@ pub async fn getcomplete<P: AsRef<Path>>(
     }
 }
 
+fn is_script(local_path: &Path, source: &str, root: tree_sitter::Node) -> bool {
+    let lines: Vec<&str> = source.lines().collect();
+    script_mode::detect_mode(local_path, &lines, root) == FileMode::Script
+}
+
+/// Commands refused by `cmake -P` and variables only set by `project()`
+fn is_project_only(item: &CompletionItem) -> bool {
+    match item.kind {
+        Some(CompletionItemKind::FUNCTION) => script_mode::is_project_command(&item.label),
+        Some(CompletionItemKind::VARIABLE) => script_mode::is_project_variable(&item.label),
+        _ => false,
+    }
+}
+
+/// Completions coming from `cmake_parse_arguments`: the keywords of the function
+/// being called, and the `PREFIX_*` variables inside the function being defined
+fn parse_arguments_completions(
+    location: tree_sitter::Point,
+    root: tree_sitter::Node,
+    source: &str,
+) -> Vec<CompletionItem> {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut complete = vec![];
+
+    if let Some(definition) = innermost_node(
+        location,
+        root,
+        &[CMakeNodeKinds::FUNCTION_DEF, CMakeNodeKinds::MACRO_DEF],
+    ) && let Some(parsed) = parse_arguments_of(&lines, definition)
+    {
+        complete.extend(
+            parsed
+                .variables()
+                .into_iter()
+                .map(|variable| CompletionItem {
+                    label: variable,
+                    kind: Some(CompletionItemKind::VARIABLE),
+                    detail: Some("Variable".to_string()),
+                    documentation: Some(Documentation::String(
+                        "defined by cmake_parse_arguments".to_string(),
+                    )),
+                    ..Default::default()
+                }),
+        );
+    }
+
+    // The call may not be closed yet, as its keywords are being typed
+    let Some(command) = command_at(root, location, source) else {
+        return complete;
+    };
+    let name = command.name;
+    let keywords = FUNCTION_KEYWORDS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    let Some(parsed) = keywords.get(&name) else {
+        return complete;
+    };
+    let kinds = [
+        (&parsed.options, "option"),
+        (&parsed.one_value_keywords, "one value keyword"),
+        (&parsed.multi_value_keywords, "multi value keyword"),
+    ];
+    for (keywords, kind) in kinds {
+        complete.extend(keywords.iter().map(|keyword| CompletionItem {
+            label: keyword.clone(),
+            kind: Some(CompletionItemKind::KEYWORD),
+            detail: Some(format!("{kind} of {name}")),
+            ..Default::default()
+        }));
+    }
+    complete
+}
+
+/// If the cursor is inside an unclosed `${...` reference, such as `"prefix ${CMA|"`,
+/// return the column where the variable name starts
+fn unclosed_variable_ref(source: &str, location: Position) -> Option<u32> {
+    let line = source.lines().nth(location.line as usize)?;
+    let before_cursor = line.get(..location.character as usize)?;
+    let name_start = before_cursor.rfind("${")? + 2;
+    let is_variable_char =
+        |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '/' | '+' | '-');
+    if !before_cursor[name_start..].chars().all(is_variable_char) {
+        return None;
+    }
+    Some(name_start as u32)
+}
+
+fn is_followed_by_brace(source: &str, location: Position) -> bool {
+    source
+        .lines()
+        .nth(location.line as usize)
+        .and_then(|line| line.get(location.character as usize..))
+        .is_some_and(|after_cursor| {
+            after_cursor
+                .trim_start_matches(|c: char| c.is_ascii_alphanumeric() || c == '_')
+                .starts_with('}')
+        })
+}
+
+/// Keep only the variables and make them replace the typed name,
+/// closing the reference with `}` if asked to
+fn variable_ref_completions(
+    items: Vec<CompletionItem>,
+    replace_range: Range,
+    close_brace: bool,
+) -> Vec<CompletionItem> {
+    variable_completions_closed_with(items, replace_range, close_brace.then_some("}"))
+}
+
+/// Keep only the variables and make them replace the typed name,
+/// followed by `closing` as a snippet when given
+pub(crate) fn variable_completions_closed_with(
+    items: Vec<CompletionItem>,
+    replace_range: Range,
+    closing: Option<&str>,
+) -> Vec<CompletionItem> {
+    items
+        .into_iter()
+        .filter(|item| {
+            matches!(
+                item.kind,
+                Some(CompletionItemKind::VARIABLE) | Some(CompletionItemKind::VALUE)
+            )
+        })
+        .map(|item| {
+            let (new_text, insert_text_format) = match closing {
+                Some(closing) => (
+                    format!("{}{closing}$0", item.label),
+                    Some(InsertTextFormat::SNIPPET),
+                ),
+                None => (item.label.clone(), None),
+            };
+            CompletionItem {
+                text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                    range: replace_range,
+                    new_text,
+                })),
+                insert_text: None,
+                insert_text_format,
+                ..item
+            }
+        })
+        .collect()
+}
+
 /// NOTE: postype can only be VarOrFun | TargetLink | TargetInclude | ArgumentOrList
 /// get the variable from the loop
 /// use position to make only can complete which has show before
@@ -464,6 +829,8 @@ fn getsubcomplete<P: AsRef<Path>>(
                 ));
             }
             CMakeNodeKinds::FUNCTION_DEF => {
+                update_function_keywords(source, child);
+                function_doc::update_function_doc(source, child, local_path);
                 let Some(function_whole) = child.child(0) else {
                     continue;
                 };
@@ -481,8 +848,8 @@ fn getsubcomplete<P: AsRef<Path>>(
                 };
                 let mut document_info = format!("defined function\nfrom: {}", local_path.display());
 
-                if line_comment_tmp.is_node_comment(h) {
-                    document_info = format!("{}\n\n{}", document_info, line_comment_tmp.comment());
+                if let Some(doc) = function_doc::leading_doc(source, child) {
+                    document_info = format!("{document_info}\n\n{doc}");
                 }
 
                 // Add snippet with parentheses if client supports it
@@ -506,6 +873,8 @@ fn getsubcomplete<P: AsRef<Path>>(
                 });
             }
             CMakeNodeKinds::MACRO_DEF => {
+                update_function_keywords(source, child);
+                function_doc::update_function_doc(source, child, local_path);
                 let Some(macro_whole) = child.child(0) else {
                     continue;
                 };
@@ -523,8 +892,8 @@ fn getsubcomplete<P: AsRef<Path>>(
                 };
                 let mut document_info = format!("defined macro\nfrom: {}", local_path.display());
 
-                if line_comment_tmp.is_node_comment(h) {
-                    document_info = format!("{}\n\n{}", document_info, line_comment_tmp.comment());
+                if let Some(doc) = function_doc::leading_doc(source, child) {
+                    document_info = format!("{document_info}\n\n{doc}");
                 }
 
                 // Add snippet with parentheses if client supports it
@@ -561,6 +930,27 @@ fn getsubcomplete<P: AsRef<Path>>(
                 ) {
                     complete.append(&mut message);
                 }
+                // The loop variables are only set in the body
+                if child.kind() == CMakeNodeKinds::FOREACH_LOOP
+                    && let Some(location) = location
+                    && loops::loop_body(child).is_some_and(|body| {
+                        let point = location.to_point();
+                        body.start_position() <= point && point <= body.end_position()
+                    })
+                {
+                    for variable in loops::loop_variables(child, source) {
+                        complete.push(CompletionItem {
+                            label: variable,
+                            kind: Some(CompletionItemKind::VARIABLE),
+                            detail: Some("Loop variable".to_string()),
+                            documentation: Some(Documentation::String(format!(
+                                "defined by foreach()\nfrom: {}",
+                                local_path.display()
+                            ))),
+                            ..Default::default()
+                        });
+                    }
+                }
             }
             CMakeNodeKinds::NORMAL_COMMAND => {
                 let h = child.start_position().row;
@@ -579,23 +969,10 @@ fn getsubcomplete<P: AsRef<Path>>(
                         else {
                             continue;
                         };
-                        let (is_builtin, subpath) = {
-                            if !include_is_module(&name) {
-                                (false, local_path.parent().unwrap().join(name))
-                            } else {
-                                let Some(glob_pattern) = gen_module_pattern(&name) else {
-                                    continue;
-                                };
-                                let Some(path) = glob::glob(&glob_pattern)
-                                    .into_iter()
-                                    .flatten()
-                                    .flatten()
-                                    .next()
-                                else {
-                                    continue;
-                                };
-                                (true, path)
-                            }
+                        let Some((subpath, is_builtin)) =
+                            resolve_include(&name, local_path.parent().unwrap())
+                        else {
+                            continue;
                         };
                         if include_files.contains(&subpath) {
                             continue;
@@ -869,6 +1246,136 @@ fn get_cmake_package_complete(
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_arguments_completions() {
+        let source = r#"function(my_install)
+    cmake_parse_arguments(ARG "OPTIONAL" "DESTINATION" "TARGETS" ${ARGN})
+    message(${ARG_)
+endfunction()
+my_install(TARGETS )
+"#;
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        update_function_keywords(&lines, thetree.root_node().child(0).unwrap());
+
+        let call_site = parse_arguments_completions(
+            tree_sitter::Point { row: 4, column: 19 },
+            thetree.root_node(),
+            source,
+        );
+        let labels: Vec<&str> = call_site.iter().map(|item| item.label.as_str()).collect();
+        assert_eq!(labels, vec!["OPTIONAL", "DESTINATION", "TARGETS"]);
+        assert_eq!(
+            call_site[1].detail,
+            Some("one value keyword of my_install".to_string())
+        );
+
+        let in_body = parse_arguments_completions(
+            tree_sitter::Point { row: 2, column: 16 },
+            thetree.root_node(),
+            source,
+        );
+        assert!(in_body.iter().any(|item| item.label == "ARG_DESTINATION"));
+        assert!(!in_body.iter().any(|item| item.label == "TARGETS"));
+    }
+
+    #[test]
+    fn test_is_project_only() {
+        let item = |label: &str, kind| CompletionItem {
+            label: label.to_string(),
+            kind: Some(kind),
+            ..Default::default()
+        };
+        assert!(is_project_only(&item(
+            "add_executable",
+            CompletionItemKind::FUNCTION
+        )));
+        assert!(is_project_only(&item(
+            "PROJECT_SOURCE_DIR",
+            CompletionItemKind::VARIABLE
+        )));
+        assert!(!is_project_only(&item(
+            "file",
+            CompletionItemKind::FUNCTION
+        )));
+        assert!(!is_project_only(&item(
+            "PROJECT_SOURCE_DIR",
+            CompletionItemKind::VALUE
+        )));
+    }
+
+    #[test]
+    fn test_unclosed_variable_ref() {
+        let source = "message(\"prefix ${CMA\")\nset(A ${B} ${C}/${D_";
+        let at = |line, character| unclosed_variable_ref(source, Position { line, character });
+        assert_eq!(at(0, 21), Some(18));
+        assert_eq!(at(0, 18), Some(18));
+        assert_eq!(at(0, 15), None);
+        assert_eq!(at(1, 10), None);
+        assert_eq!(at(1, 15), None);
+        assert_eq!(at(1, 20), Some(18));
+        assert_eq!(at(5, 0), None);
+    }
+
+    #[test]
+    fn test_variable_ref_completions() {
+        let items = vec![
+            CompletionItem {
+                label: "CMAKE_SOURCE_DIR".to_string(),
+                kind: Some(CompletionItemKind::VARIABLE),
+                ..Default::default()
+            },
+            CompletionItem {
+                label: "my_function".to_string(),
+                kind: Some(CompletionItemKind::FUNCTION),
+                ..Default::default()
+            },
+        ];
+        let range = Range {
+            start: Position {
+                line: 0,
+                character: 18,
+            },
+            end: Position {
+                line: 0,
+                character: 21,
+            },
+        };
+
+        let completions = variable_ref_completions(items.clone(), range, true);
+        assert_eq!(completions.len(), 1);
+        assert_eq!(
+            completions[0].text_edit,
+            Some(CompletionTextEdit::Edit(TextEdit {
+                range,
+                new_text: "CMAKE_SOURCE_DIR}$0".to_string(),
+            }))
+        );
+        assert_eq!(
+            completions[0].insert_text_format,
+            Some(InsertTextFormat::SNIPPET)
+        );
+
+        let completions = variable_ref_completions(items, range, false);
+        assert_eq!(
+            completions[0].text_edit,
+            Some(CompletionTextEdit::Edit(TextEdit {
+                range,
+                new_text: "CMAKE_SOURCE_DIR".to_string(),
+            }))
+        );
+        assert_eq!(completions[0].insert_text_format, None);
+        assert!(is_followed_by_brace(
+            "${AB}",
+            Position {
+                line: 0,
+                character: 3
+            }
+        ));
+    }
+
     #[test]
     fn rst_doc_read_test() {
         let doc = r#"
@@ -1,41 +1,57 @@
 use std::collections::HashMap;
 use std::iter::zip;
 use std::process::Command;
-use std::sync::LazyLock;
+use std::sync::{Arc, LazyLock};
 
 use anyhow::Result;
-use tower_lsp::lsp_types::{CompletionItem, CompletionItemKind, Documentation, InsertTextFormat};
+use tower_lsp::lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionItemTag, Documentation, InsertTextFormat,
+    MarkupContent, MarkupKind,
+};
 
+use crate::config::{CONFIG, KnownCommand};
 use crate::languageserver::to_use_snippet;
+use crate::utils::cmake_docs::DocKind;
+use crate::utils::command_help::{COMMAND_HELP, command_help};
+use crate::utils::deprecated::is_deprecated_command;
+use crate::utils::help_markdown::help_to_markdown;
+use crate::utils::help_rst::read_help;
+use crate::utils::qt;
 
-fn gen_builtin_commands(raw_info: &str) -> Result<Vec<CompletionItem>> {
-    let re = regex::Regex::new(r"[a-zA-z]+\n-+").unwrap();
-    let keys: Vec<_> = re
-        .find_iter(raw_info)
-        .map(|message| {
-            let temp: Vec<&str> = message.as_str().split('\n').collect();
-            temp[0]
-        })
-        .collect();
-    let contents: Vec<_> = re.split(raw_info).collect();
-    let contents = &contents[1..].to_vec();
+/// The help of cmake, shown as Markdown. The page from the `Help` sources is
+/// preferred to the output of `--help-*`.
+fn help_documentation(kind: DocKind, name: &str, help: &str) -> Documentation {
+    let value = match read_help(kind, name) {
+        Some(page) => help_to_markdown(&page),
+        None => help_to_markdown(help),
+    };
+    Documentation::MarkupContent(MarkupContent {
+        kind: MarkupKind::Markdown,
+        value,
+    })
+}
+
+/// The key of the `data` of the completion items naming the command to document
+const RESOLVED_COMMAND: &str = "command";
 
+/// The builtin commands, in lowercase and in uppercase. Their documentation is
+/// left to [`resolve_documentation`], so the items do not carry the help of
+/// every command in each response
+fn gen_builtin_commands(help: &HashMap<String, Arc<str>>) -> Result<Vec<CompletionItem>> {
     let mut completes = HashMap::new();
-    for (key, content) in keys.iter().zip(contents) {
-        let small_key = key.to_lowercase();
-        let big_key = key.to_uppercase();
-        completes.insert(small_key, content.to_string());
-        completes.insert(big_key, content.to_string());
+    for key in help.keys() {
+        completes.insert(key.to_lowercase(), None);
+        completes.insert(key.to_uppercase(), None);
     }
     #[cfg(unix)]
     {
         completes.insert(
             "pkg_check_modules".to_string(),
-            "please findpackage PkgConfig first".to_string(),
+            Some("Check for modules with pkg-config, defined by find_package(PkgConfig)"),
         );
         completes.insert(
             "PKG_CHECK_MODULES".to_string(),
-            "please findpackage PkgConfig first".to_string(),
+            Some("Check for modules with pkg-config, defined by find_package(PkgConfig)"),
         );
     }
 
@@ -68,7 +84,11 @@ fn gen_builtin_commands(raw_info: &str) -> Result<Vec<CompletionItem>> {
                 label: akey.to_string(),
                 kind: Some(CompletionItemKind::FUNCTION),
                 detail: Some("Function".to_string()),
-                documentation: Some(Documentation::String(message.trim().to_string())),
+                documentation: message.map(|message| Documentation::String(message.to_string())),
+                data: message
+                    .is_none()
+                    .then(|| serde_json::json!({ RESOLVED_COMMAND: akey.to_lowercase() })),
+                tags: is_deprecated_command(akey).then(|| vec![CompletionItemTag::DEPRECATED]),
                 insert_text,
                 insert_text_format,
                 sort_text: Some(sort_text),
@@ -95,7 +115,7 @@ fn gen_builtin_variables(raw_info: &str) -> Result<Vec<CompletionItem>> {
             label: akey.to_string(),
             kind: Some(CompletionItemKind::VARIABLE),
             detail: Some("Variable".to_string()),
-            documentation: Some(Documentation::String(message.trim().to_string())),
+            documentation: Some(help_documentation(DocKind::Variable, akey, message)),
             ..Default::default()
         })
         .collect())
@@ -117,21 +137,63 @@ fn gen_builtin_modules(raw_info: &str) -> Result<Vec<CompletionItem>> {
             label: akey.to_string(),
             kind: Some(CompletionItemKind::MODULE),
             detail: Some("Module".to_string()),
-            documentation: Some(Documentation::String(message.trim().to_string())),
+            documentation: Some(help_documentation(DocKind::Module, akey, message)),
             ..Default::default()
         })
         .collect())
 }
 
+fn gen_known_commands(commands: &[KnownCommand]) -> Vec<CompletionItem> {
+    let client_support_snippet = to_use_snippet();
+    commands
+        .iter()
+        .map(|command| {
+            let (insert_text, insert_text_format) = if client_support_snippet {
+                (
+                    Some(format!("{}($0)", command.name)),
+                    Some(InsertTextFormat::SNIPPET),
+                )
+            } else {
+                (
+                    Some(command.name.clone()),
+                    Some(InsertTextFormat::PLAIN_TEXT),
+                )
+            };
+            CompletionItem {
+                label: command.name.clone(),
+                kind: Some(CompletionItemKind::FUNCTION),
+                detail: Some("Known command".to_string()),
+                documentation: Some(Documentation::String(command.documentation())),
+                insert_text,
+                insert_text_format,
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
 /// CMake builtin commands
-pub static BUILTIN_COMMAND: LazyLock<Result<Vec<CompletionItem>>> = LazyLock::new(|| {
-    let output = Command::new("cmake")
-        .arg("--help-commands")
-        .output()?
-        .stdout;
-    let temp = String::from_utf8_lossy(&output);
-    gen_builtin_commands(&temp)
-});
+pub static BUILTIN_COMMAND: LazyLock<Result<Vec<CompletionItem>>> =
+    LazyLock::new(|| gen_builtin_commands(&COMMAND_HELP));
+
+/// Fill the documentation of a builtin command, once the client shows it
+pub fn resolve_documentation(mut item: CompletionItem) -> CompletionItem {
+    if item.documentation.is_some() {
+        return item;
+    }
+    let Some(name) = item
+        .data
+        .as_ref()
+        .and_then(|data| data.get(RESOLVED_COMMAND))
+        .and_then(|name| name.as_str())
+    else {
+        return item;
+    };
+    if let Some(help) = command_help(name) {
+        item.documentation = Some(help_documentation(DocKind::Command, name, &help));
+    }
+    item
+}
 
 /// cmake builtin vars
 pub static BUILTIN_VARIABLE: LazyLock<Result<Vec<CompletionItem>>> = LazyLock::new(|| {
@@ -143,6 +205,14 @@ pub static BUILTIN_VARIABLE: LazyLock<Result<Vec<CompletionItem>>> = LazyLock::n
     gen_builtin_variables(&temp)
 });
 
+/// Commands declared in the `known_commands` setting
+pub static KNOWN_COMMANDS: LazyLock<Vec<CompletionItem>> =
+    LazyLock::new(|| gen_known_commands(&CONFIG.known_commands));
+
+/// Commands defined by Qt, offered to the projects finding it
+pub static QT_COMMANDS: LazyLock<Vec<CompletionItem>> =
+    LazyLock::new(|| gen_known_commands(&qt::QT_COMMANDS));
+
 /// Cmake builtin modules
 pub static BUILTIN_MODULE: LazyLock<Result<Vec<CompletionItem>>> = LazyLock::new(|| {
     let output = Command::new("cmake").arg("--help-modules").output()?.stdout;
@@ -156,6 +226,7 @@ mod tests {
 
     use super::*;
     use crate::complete::builtin::{gen_builtin_modules, gen_builtin_variables};
+    use crate::utils::command_help::parse_command_help;
 
     #[test]
     fn test_regex() {
@@ -175,9 +246,13 @@ mod tests {
         // NOTE: In case the command fails, ignore test
         let output = include_str!("../../assets_for_test/cmake_help_commands.txt");
 
-        let output = gen_builtin_commands(output);
+        let output = gen_builtin_commands(&parse_command_help(output)).unwrap();
 
-        assert!(output.is_ok());
+        // Both spellings are documented by the same help, when resolved
+        let set = output.iter().find(|item| item.label == "set").unwrap();
+        let upper_set = output.iter().find(|item| item.label == "SET").unwrap();
+        assert!(set.documentation.is_none());
+        assert_eq!(set.data, upper_set.data);
     }
 
     #[test]
@@ -199,4 +274,23 @@ mod tests {
 
         assert!(output.is_ok());
     }
+
+    #[test]
+    fn test_known_commands() {
+        let commands = vec![KnownCommand {
+            name: "catch_discover_tests".to_string(),
+            signatures: vec!["catch_discover_tests(<target>)".to_string()],
+            description: None,
+        }];
+        let output = gen_known_commands(&commands);
+        assert_eq!(output.len(), 1);
+        assert_eq!(output[0].label, "catch_discover_tests");
+        assert_eq!(output[0].kind, Some(CompletionItemKind::FUNCTION));
+        assert_eq!(
+            output[0].documentation,
+            Some(Documentation::String(
+                "catch_discover_tests(<target>)".to_string()
+            ))
+        );
+    }
 }
@@ -0,0 +1,265 @@
+//! Where a completion is asked for, and which items come first there.
+//!
+//! The providers of `getcomplete` each add their items to one list, so the
+//! order is decided here once for all of them: commands at the start of a
+//! statement, variables and operators in a condition, targets and libraries in
+//! `target_link_libraries`.
+
+use std::path::Path;
+
+use tower_lsp::lsp_types::{CompletionItem, CompletionItemKind, Documentation};
+use tree_sitter::{Node, Point};
+
+use crate::CMakeNodeKinds;
+use crate::formatting::case::{BINARY_OPERATORS, UNARY_OPERATORS};
+use crate::utils::targets::{self, TargetKind};
+use crate::utils::treehelper::innermost_node;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionContext {
+    /// Where a command is written
+    Statement,
+    /// In the arguments of `if()`, `elseif()` or `while()`
+    Condition,
+    /// In the arguments of `target_link_libraries()`
+    LinkLibraries,
+    /// In the arguments of any other command
+    Arguments,
+}
+
+/// Which provider an item comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Origin {
+    /// Defined by the project, its packages or its cache
+    Project,
+    /// Documented by cmake
+    Builtin,
+    /// A target declared by the project
+    Target,
+    /// An operator of the conditions
+    Operator,
+}
+
+/// The context of `location`, from the innermost command around it
+pub fn completion_context(location: Point, root: Node, source: &[&str]) -> CompletionContext {
+    let Some(command) = innermost_node(
+        location,
+        root,
+        &[
+            CMakeNodeKinds::NORMAL_COMMAND,
+            CMakeNodeKinds::IF_COMMAND,
+            CMakeNodeKinds::ELSEIF_COMMAND,
+            CMakeNodeKinds::WHILE_COMMAND,
+        ],
+    ) else {
+        return CompletionContext::Statement;
+    };
+    // Still on the name of the command
+    if command
+        .child(1)
+        .is_none_or(|paren| location < paren.end_position())
+    {
+        return CompletionContext::Statement;
+    }
+    if command.kind() != CMakeNodeKinds::NORMAL_COMMAND {
+        return CompletionContext::Condition;
+    }
+    let Some(ids) = command.child(0) else {
+        return CompletionContext::Arguments;
+    };
+    let h = ids.start_position().row;
+    let Some(name) = source
+        .get(h)
+        .and_then(|line| line.get(ids.start_position().column..ids.end_position().column))
+    else {
+        return CompletionContext::Arguments;
+    };
+    match name.to_lowercase().as_str() {
+        "target_link_libraries" => CompletionContext::LinkLibraries,
+        _ => CompletionContext::Arguments,
+    }
+}
+
+/// The operators of `if()` and `while()`
+pub fn operator_completions() -> Vec<CompletionItem> {
+    UNARY_OPERATORS
+        .iter()
+        .chain(BINARY_OPERATORS)
+        .map(|operator| CompletionItem {
+            label: operator.to_string(),
+            kind: Some(CompletionItemKind::OPERATOR),
+            detail: Some("Operator".to_string()),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// The library targets of the project, for `target_link_libraries`
+pub fn library_completions<P: AsRef<Path>>(
+    local_path: P,
+    source: &[&str],
+    root: Node,
+) -> Vec<CompletionItem> {
+    let local_path = local_path.as_ref();
+    // The index may not know about the targets of the file being edited yet
+    let declared = targets::declared_targets(source, root)
+        .into_iter()
+        .map(|target| (local_path.to_path_buf(), target));
+    let indexed = targets::all_targets()
+        .into_iter()
+        .filter(|(path, _)| path != local_path);
+    let mut items: Vec<CompletionItem> = vec![];
+    for (path, target) in declared.chain(indexed) {
+        if !matches!(target.kind, TargetKind::Library | TargetKind::Interface)
+            || items.iter().any(|item| item.label == target.name)
+        {
+            continue;
+        }
+        let detail = match (&target.alias_of, target.kind) {
+            (Some(aliased), _) => format!("Alias of {aliased}"),
+            (None, TargetKind::Interface) => "Interface library target".to_string(),
+            (None, _) => "Library target".to_string(),
+        };
+        items.push(CompletionItem {
+            label: target.name,
+            kind: Some(CompletionItemKind::REFERENCE),
+            detail: Some(detail),
+            documentation: Some(Documentation::String(format!(
+                "declared in: {}",
+                path.display()
+            ))),
+            ..Default::default()
+        });
+    }
+    items
+}
+
+/// Whether a project item names something to link, like `Qt6::Core` or
+/// `${ZLIB_LIBRARIES}`
+fn is_library(item: &CompletionItem) -> bool {
+    item.label.contains("::")
+        || item.label.ends_with("_LIBRARIES")
+        || item.label.ends_with("_LIBRARY")
+        || item.detail.as_deref() == Some("Package")
+}
+
+impl CompletionContext {
+    /// Lower ranks are listed first
+    fn rank(self, origin: Origin, item: &CompletionItem) -> u8 {
+        let is_command = item.kind == Some(CompletionItemKind::FUNCTION);
+        match self {
+            Self::Statement => match (is_command, origin) {
+                (true, Origin::Project) => 0,
+                (true, _) => 1,
+                (false, Origin::Builtin) => 3,
+                (false, _) => 2,
+            },
+            Self::Condition => match (is_command, origin) {
+                (true, _) => 3,
+                (false, Origin::Operator | Origin::Project) => 0,
+                (false, _) => 1,
+            },
+            Self::LinkLibraries => match (is_command, origin) {
+                (true, _) => 3,
+                (false, Origin::Target) => 0,
+                (false, Origin::Project) if is_library(item) => 0,
+                (false, Origin::Builtin) => 2,
+                (false, _) => 1,
+            },
+            Self::Arguments => match (is_command, origin) {
+                (true, _) => 2,
+                (false, Origin::Builtin) => 1,
+                (false, _) => 0,
+            },
+        }
+    }
+
+    /// Rank the `items` of `origin`, keeping their own order within a rank
+    pub fn ranked(self, origin: Origin, mut items: Vec<CompletionItem>) -> Vec<CompletionItem> {
+        for item in &mut items {
+            let rank = self.rank(origin, item);
+            let sort_text = item.sort_text.as_deref().unwrap_or(&item.label);
+            item.sort_text = Some(format!("{rank}_{sort_text}"));
+        }
+        items
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    #[test]
+    fn test_completion_context() {
+        let source = r#"add_library(core core.c)
+if(WIN32 AND )
+    target_link_libraries(app PRIVATE )
+endif()
+set(A )
+"#;
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let at =
+            |row, column| completion_context(Point { row, column }, thetree.root_node(), &lines);
+        assert_eq!(at(0, 3), CompletionContext::Statement);
+        assert_eq!(at(1, 13), CompletionContext::Condition);
+        assert_eq!(at(2, 38), CompletionContext::LinkLibraries);
+        assert_eq!(at(4, 6), CompletionContext::Arguments);
+        assert_eq!(at(5, 0), CompletionContext::Statement);
+
+        let items = library_completions("/p/CMakeLists.txt", &lines, thetree.root_node());
+        assert_eq!(items[0].label, "core");
+    }
+
+    #[test]
+    fn test_ranked() {
+        let item = |label: &str, kind| CompletionItem {
+            label: label.to_string(),
+            kind: Some(kind),
+            ..Default::default()
+        };
+        let sort_texts = |context: CompletionContext| {
+            let mut items = context.ranked(
+                Origin::Builtin,
+                vec![
+                    item("CMAKE_DL_LIBS", CompletionItemKind::VARIABLE),
+                    CompletionItem {
+                        sort_text: Some("0_message".to_string()),
+                        ..item("message", CompletionItemKind::FUNCTION)
+                    },
+                ],
+            );
+            items.extend(context.ranked(
+                Origin::Project,
+                vec![
+                    item("Qt6::Core", CompletionItemKind::VARIABLE),
+                    item("MY_FLAGS", CompletionItemKind::VALUE),
+                ],
+            ));
+            if context == CompletionContext::Condition {
+                items.extend(context.ranked(Origin::Operator, operator_completions()));
+            }
+            items.sort_by(|a, b| a.sort_text.cmp(&b.sort_text));
+            items
+                .into_iter()
+                .map(|item| item.label)
+                .take(3)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            sort_texts(CompletionContext::Statement),
+            vec!["message", "MY_FLAGS", "Qt6::Core"]
+        );
+        assert_eq!(
+            sort_texts(CompletionContext::Condition),
+            vec!["AND", "COMMAND", "DEFINED"]
+        );
+        assert_eq!(
+            sort_texts(CompletionContext::LinkLibraries),
+            vec!["Qt6::Core", "MY_FLAGS", "CMAKE_DL_LIBS"]
+        );
+    }
+}
@@ -0,0 +1,90 @@
+//! Completion of the CPack variables, and of the generators in `CPACK_GENERATOR`.
+
+use std::sync::LazyLock;
+
+use tower_lsp::lsp_types::{CompletionItem, CompletionItemKind, Documentation, Position};
+use tree_sitter::{Node, Point};
+
+use crate::utils::cpack::{CPACK_GENERATORS, CPACK_VARIABLES, GENERATOR_VARIABLES};
+use crate::utils::treehelper::set_variable_at;
+
+pub static CPACK_VARIABLE_ITEMS: LazyLock<Vec<CompletionItem>> = LazyLock::new(|| {
+    CPACK_VARIABLES
+        .iter()
+        .map(|(name, doc)| CompletionItem {
+            label: name.to_string(),
+            kind: Some(CompletionItemKind::VARIABLE),
+            detail: Some("CPack variable".to_string()),
+            documentation: Some(Documentation::String(doc.to_string())),
+            ..Default::default()
+        })
+        .collect()
+});
+
+/// The CPack variables `cmake --help-variables` does not document, to add to `builtin`
+pub fn missing_variables(builtin: &[CompletionItem]) -> Vec<CompletionItem> {
+    CPACK_VARIABLE_ITEMS
+        .iter()
+        .filter(|item| builtin.iter().all(|known| known.label != item.label))
+        .cloned()
+        .collect()
+}
+
+/// Completion of the value in `set(CPACK_GENERATOR |)`
+pub fn generator_completions(
+    source: &str,
+    root: Node,
+    location: Position,
+) -> Option<Vec<CompletionItem>> {
+    let lines: Vec<&str> = source.lines().collect();
+    let point = Point {
+        row: location.line as usize,
+        column: location.character as usize,
+    };
+    let variable = set_variable_at(point, root, &lines)?;
+    if !GENERATOR_VARIABLES.contains(&variable.as_str()) {
+        return None;
+    }
+    Some(
+        CPACK_GENERATORS
+            .iter()
+            .map(|(name, doc)| CompletionItem {
+                label: name.to_string(),
+                kind: Some(CompletionItemKind::ENUM_MEMBER),
+                detail: Some("CPack generator".to_string()),
+                documentation: Some(Documentation::String(doc.to_string())),
+                ..Default::default()
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    #[test]
+    fn test_generator_completions() {
+        let source = "set(CPACK_GENERATOR )\nset(CPACK_PACKAGE_NAME )\n";
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let items =
+            generator_completions(source, thetree.root_node(), Position::new(0, 20)).unwrap();
+        assert!(items.iter().any(|item| item.label == "DEB"));
+        assert!(generator_completions(source, thetree.root_node(), Position::new(1, 23)).is_none());
+
+        let builtin = vec![CompletionItem {
+            label: "CPACK_PACKAGE_NAME".to_string(),
+            ..Default::default()
+        }];
+        let missing = missing_variables(&builtin);
+        assert!(
+            missing
+                .iter()
+                .all(|item| item.label != "CPACK_PACKAGE_NAME")
+        );
+        assert!(missing.iter().any(|item| item.label == "CPACK_GENERATOR"));
+    }
+}
@@ -0,0 +1,246 @@
+//! Completion of the keywords of `add_test()`, and of the test properties in
+//! `set_tests_properties()` and `set_property(TEST)`.
+
+use tower_lsp::lsp_types::{CompletionItem, CompletionItemKind, Documentation, Position};
+use tree_sitter::{Node, Point};
+
+use crate::utils::treehelper::command_at;
+
+/// The keywords of `add_test(NAME <name> COMMAND <command>...)`
+const ADD_TEST_KEYWORDS: &[(&str, &str)] = &[
+    ("NAME", "The name of the test"),
+    (
+        "COMMAND",
+        "The command line of the test. An executable target is replaced by its path",
+    ),
+    (
+        "CONFIGURATIONS",
+        "Only run the test for these configurations",
+    ),
+    (
+        "WORKING_DIRECTORY",
+        "The directory to run the test in, the build directory by default",
+    ),
+    (
+        "COMMAND_EXPAND_LISTS",
+        "Expand the lists in the command, the empty elements are removed",
+    ),
+];
+
+/// The keywords followed by a single value, which is not a keyword
+const ONE_VALUE_KEYWORDS: &[&str] = &["NAME", "WORKING_DIRECTORY"];
+
+/// The properties of the tests
+const TEST_PROPERTIES: &[(&str, &str)] = &[
+    (
+        "ATTACHED_FILES",
+        "Files to submit to the dashboard with the test results",
+    ),
+    (
+        "ATTACHED_FILES_ON_FAIL",
+        "Files to submit to the dashboard when the test fails",
+    ),
+    ("COST", "Run the tests with a higher cost first"),
+    ("DEPENDS", "The tests to run before this one"),
+    ("DISABLED", "Do not run the test, it is reported as not run"),
+    ("ENVIRONMENT", "The `VAR=value` environment of the test"),
+    (
+        "ENVIRONMENT_MODIFICATION",
+        "Operations on the environment of the test, like `PATH=path_list_prepend:/bin`",
+    ),
+    (
+        "FAIL_REGULAR_EXPRESSION",
+        "The test fails when its output matches one of these",
+    ),
+    ("FIXTURES_CLEANUP", "The fixtures the test cleans up"),
+    ("FIXTURES_REQUIRED", "The fixtures the test needs"),
+    ("FIXTURES_SETUP", "The fixtures the test sets up"),
+    (
+        "GENERATED_RESOURCE_SPEC_FILE",
+        "The resource specification file generated by the test",
+    ),
+    ("LABELS", "Labels to select the tests with `ctest -L`"),
+    ("MEASUREMENT", "A measurement to submit to the dashboard"),
+    (
+        "PASS_REGULAR_EXPRESSION",
+        "The test passes when its output matches one of these, whatever its exit code",
+    ),
+    (
+        "PROCESSOR_AFFINITY",
+        "Bind the processes of the test to processors",
+    ),
+    ("PROCESSORS", "The number of processors the test needs"),
+    ("REQUIRED_FILES", "The files needed to run the test"),
+    ("RESOURCE_GROUPS", "The resources the test needs"),
+    (
+        "RESOURCE_LOCK",
+        "Resources the test does not run concurrently with",
+    ),
+    ("RUN_SERIAL", "Do not run the test in parallel with others"),
+    (
+        "SKIP_REGULAR_EXPRESSION",
+        "The test is skipped when its output matches one of these",
+    ),
+    (
+        "SKIP_RETURN_CODE",
+        "The exit code marking the test as skipped",
+    ),
+    (
+        "TIMEOUT",
+        "The seconds the test may run before being killed",
+    ),
+    (
+        "TIMEOUT_AFTER_MATCH",
+        "A shorter timeout once the output matches a regular expression",
+    ),
+    (
+        "TIMEOUT_SIGNAL_GRACE_PERIOD",
+        "The seconds between the timeout signal and killing the test",
+    ),
+    (
+        "TIMEOUT_SIGNAL_NAME",
+        "The signal sent to the test on timeout",
+    ),
+    ("WILL_FAIL", "Invert the result of the test"),
+    ("WORKING_DIRECTORY", "The directory to run the test in"),
+];
+
+/// The command around `location`, and its arguments written before the one
+/// being typed
+fn arguments_before<'a>(
+    location: Point,
+    root: Node,
+    source: &'a str,
+) -> Option<(String, Vec<&'a str>)> {
+    let command = command_at(root, location, source)?;
+    let before = command
+        .arguments
+        .iter()
+        .filter(|argument| argument.end_position() < location)
+        .filter(|argument| argument.start_position().row == argument.end_position().row)
+        .map(|argument| &source[argument.byte_range()])
+        .collect();
+    Some((command.name, before))
+}
+
+fn item(label: &str, kind: CompletionItemKind, detail: &str, doc: &str) -> CompletionItem {
+    CompletionItem {
+        label: label.to_string(),
+        kind: Some(kind),
+        detail: Some(detail.to_string()),
+        documentation: Some(Documentation::String(doc.to_string())),
+        ..Default::default()
+    }
+}
+
+fn location_point(location: Position) -> Point {
+    Point {
+        row: location.line as usize,
+        column: location.character as usize,
+    }
+}
+
+/// The keywords of `add_test(NAME ...)` not written yet, offered with the
+/// other completions since the command takes any argument
+pub fn keyword_completions(source: &str, root: Node, location: Position) -> Vec<CompletionItem> {
+    let Some((name, before)) = arguments_before(location_point(location), root, source) else {
+        return vec![];
+    };
+    if name != "add_test" {
+        return vec![];
+    }
+    // The short signature, `add_test(<name> <command>...)`, has no keywords
+    if before.first().is_some_and(|first| *first != "NAME") {
+        return vec![];
+    }
+    if before
+        .last()
+        .is_some_and(|last| ONE_VALUE_KEYWORDS.contains(last))
+    {
+        return vec![];
+    }
+    ADD_TEST_KEYWORDS
+        .iter()
+        .filter(|(keyword, _)| !before.contains(keyword))
+        .map(|(keyword, doc)| {
+            item(
+                keyword,
+                CompletionItemKind::KEYWORD,
+                "add_test keyword",
+                doc,
+            )
+        })
+        .collect()
+}
+
+/// The test properties, where `set_tests_properties()` or
+/// `set_property(TEST)` expects the name of one
+pub fn property_completions(
+    source: &str,
+    root: Node,
+    location: Position,
+) -> Option<Vec<CompletionItem>> {
+    let (name, before) = arguments_before(location_point(location), root, source)?;
+    let expects_property = match name.as_str() {
+        // set_tests_properties(<tests>... PROPERTIES <prop> <value>...)
+        "set_tests_properties" => before
+            .iter()
+            .position(|argument| *argument == "PROPERTIES")
+            .is_some_and(|index| (before.len() - index - 1).is_multiple_of(2)),
+        // set_property(TEST <tests>... PROPERTY <prop> <values>...)
+        "set_property" => before.first() == Some(&"TEST") && before.last() == Some(&"PROPERTY"),
+        _ => false,
+    };
+    if !expects_property {
+        return None;
+    }
+    Some(
+        TEST_PROPERTIES
+            .iter()
+            .map(|(property, doc)| {
+                item(property, CompletionItemKind::PROPERTY, "Test property", doc)
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    #[test]
+    fn test_ctest_completions() {
+        let source = r#"add_test(NAME unit COMMAND unit_tests )
+add_test(NAME )
+add_test(legacy run )
+set_tests_properties(unit PROPERTIES TIMEOUT 10 )
+set_tests_properties(unit PROPERTIES TIMEOUT )
+set_property(TEST unit PROPERTY )
+"#;
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let root = thetree.root_node();
+
+        let keywords = keyword_completions(source, root, Position::new(0, 38));
+        let labels: Vec<&str> = keywords.iter().map(|item| item.label.as_str()).collect();
+        assert_eq!(
+            labels,
+            vec![
+                "CONFIGURATIONS",
+                "WORKING_DIRECTORY",
+                "COMMAND_EXPAND_LISTS"
+            ]
+        );
+        // The value of NAME
+        assert!(keyword_completions(source, root, Position::new(1, 14)).is_empty());
+        assert!(keyword_completions(source, root, Position::new(2, 20)).is_empty());
+
+        let properties = property_completions(source, root, Position::new(3, 48)).unwrap();
+        assert!(properties.iter().any(|item| item.label == "WILL_FAIL"));
+        // The value of TIMEOUT
+        assert!(property_completions(source, root, Position::new(4, 45)).is_none());
+        assert!(property_completions(source, root, Position::new(5, 32)).is_some());
+    }
+}
@@ -0,0 +1,160 @@
+//! Completion of the program after `COMMAND`, in the commands running one.
+
+use std::path::Path;
+
+use tower_lsp::lsp_types::{CompletionItem, CompletionItemKind, Documentation, Position};
+use tree_sitter::{Node, Point};
+
+use crate::CMakeNodeKinds;
+use crate::utils::targets::{self, TargetKind};
+use crate::utils::treehelper::get_enclosing_nodes;
+
+/// Commands where `COMMAND` is followed by a program to run
+const RUNNING_COMMANDS: &[&str] = &[
+    "add_custom_command",
+    "add_custom_target",
+    "add_test",
+    "execute_process",
+];
+
+/// Whether `location` is on the argument right after `COMMAND`
+fn is_after_command_keyword(location: Point, root: Node, source: &[&str]) -> bool {
+    let Some(command) = get_enclosing_nodes(location, root)
+        .into_iter()
+        .rev()
+        .find(|node| node.kind() == CMakeNodeKinds::NORMAL_COMMAND)
+    else {
+        return false;
+    };
+    let Some(ids) = command.child(0) else {
+        return false;
+    };
+    let h = ids.start_position().row;
+    let name = source[h][ids.start_position().column..ids.end_position().column].to_lowercase();
+    if !RUNNING_COMMANDS.contains(&name.as_str()) {
+        return false;
+    }
+    let Some(argument_list) = command
+        .child(2)
+        .filter(|arguments| arguments.kind() == CMakeNodeKinds::ARGUMENT_LIST)
+    else {
+        return false;
+    };
+    let mut walk = argument_list.walk();
+    let mut before: Vec<Node> = argument_list
+        .children(&mut walk)
+        .filter(|argument| {
+            argument.kind() == CMakeNodeKinds::ARGUMENT && argument.start_position() < location
+        })
+        .collect();
+    // The argument being typed is not the keyword
+    if before
+        .last()
+        .is_some_and(|argument| argument.end_position() >= location)
+    {
+        before.pop();
+    }
+    before.last().is_some_and(|argument| {
+        argument.start_position().row == argument.end_position().row && {
+            let h = argument.start_position().row;
+            &source[h][argument.start_position().column..argument.end_position().column]
+                == "COMMAND"
+        }
+    })
+}
+
+/// Executable targets first, then the programs found in `PATH`
+pub fn command_completions<P: AsRef<Path>>(
+    local_path: P,
+    source: &str,
+    root: Node,
+    location: Position,
+    executables_on_path: &[String],
+) -> Option<Vec<CompletionItem>> {
+    let lines: Vec<&str> = source.lines().collect();
+    let point = Point {
+        row: location.line as usize,
+        column: location.character as usize,
+    };
+    if !is_after_command_keyword(point, root, &lines) {
+        return None;
+    }
+    let local_path = local_path.as_ref();
+    // The index may not know about the targets of the file being edited yet
+    let mut executables: Vec<(String, &Path)> = Vec::new();
+    let indexed = targets::all_targets();
+    let declared = targets::declared_targets(&lines, root)
+        .into_iter()
+        .map(|target| (local_path, target));
+    let indexed = indexed
+        .iter()
+        .filter(|(path, _)| path != local_path)
+        .map(|(path, target)| (path.as_path(), target.clone()));
+    for (path, target) in declared.chain(indexed) {
+        if target.kind == TargetKind::Executable
+            && !executables.iter().any(|(name, _)| *name == target.name)
+        {
+            executables.push((target.name, path));
+        }
+    }
+    let mut items: Vec<CompletionItem> = executables
+        .into_iter()
+        .map(|(name, path)| CompletionItem {
+            sort_text: Some(format!("!0_{name}")),
+            label: name,
+            kind: Some(CompletionItemKind::REFERENCE),
+            detail: Some("Executable target".to_string()),
+            documentation: Some(Documentation::String(format!(
+                "declared in: {}",
+                path.display()
+            ))),
+            ..Default::default()
+        })
+        .collect();
+    items.extend(executables_on_path.iter().map(|name| CompletionItem {
+        label: name.clone(),
+        kind: Some(CompletionItemKind::FILE),
+        detail: Some("Executable".to_string()),
+        sort_text: Some(format!("!1_{name}")),
+        ..Default::default()
+    }));
+    Some(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    #[test]
+    fn test_after_command_keyword() {
+        let source = r#"add_executable(codegen gen.cpp)
+add_custom_command(OUTPUT out.h COMMAND )
+add_custom_target(docs COMMAND dox)
+set(COMMAND )
+execute_process(COMMAND git rev-parse HEAD)
+"#;
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let at = |line, character| {
+            command_completions(
+                "/project/CMakeLists.txt",
+                source,
+                thetree.root_node(),
+                Position { line, character },
+                &["git".to_string()],
+            )
+        };
+        let items = at(1, 40).unwrap();
+        assert_eq!(items[0].label, "codegen");
+        assert_eq!(items[0].kind, Some(CompletionItemKind::REFERENCE));
+        assert_eq!(items.last().unwrap().label, "git");
+        assert!(at(2, 34).is_some());
+        // On the keyword itself
+        assert!(at(2, 30).is_none());
+        assert!(at(3, 12).is_none());
+        // Arguments of the program
+        assert!(at(4, 30).is_none());
+    }
+}
@@ -0,0 +1,169 @@
+//! Completion of the debugging idioms: watching a variable with
+//! `variable_watch()`, printing it with `message()`, and the commands of the
+//! CMakePrintHelpers module, which add `include(CMakePrintHelpers)` when the
+//! file does not include it yet.
+
+use std::sync::LazyLock;
+
+use tower_lsp::lsp_types::{
+    CompletionItem, CompletionItemKind, Documentation, InsertTextFormat, Position, Range, TextEdit,
+};
+
+use crate::languageserver::to_use_snippet;
+
+const PRINT_HELPERS: &str = "CMakePrintHelpers";
+
+/// The commands of CMakePrintHelpers
+const PRINT_COMMANDS: &[(&str, &str)] = &[
+    (
+        "cmake_print_variables",
+        "Print the name and the value of each variable",
+    ),
+    (
+        "cmake_print_properties",
+        "Print the properties of targets, sources, directories, tests or cache entries",
+    ),
+];
+
+static INCLUDE_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(&format!(r"(?i)\binclude\s*\(\s*{PRINT_HELPERS}\b")).unwrap()
+});
+
+/// An idiom written as `snippet` when the client supports snippets, else as
+/// `plain`
+fn idiom(
+    label: &str,
+    documentation: &str,
+    snippet: &str,
+    plain: &str,
+    use_snippet: bool,
+) -> CompletionItem {
+    let (insert_text, insert_text_format) = if use_snippet {
+        (snippet, InsertTextFormat::SNIPPET)
+    } else {
+        (plain, InsertTextFormat::PLAIN_TEXT)
+    };
+    CompletionItem {
+        label: label.to_string(),
+        kind: Some(CompletionItemKind::SNIPPET),
+        detail: Some("Debugging".to_string()),
+        documentation: Some(Documentation::String(documentation.to_string())),
+        insert_text: Some(insert_text.to_string()),
+        insert_text_format: Some(insert_text_format),
+        ..Default::default()
+    }
+}
+
+/// The edit including CMakePrintHelpers before the statement at `location`,
+/// with its indentation
+fn include_edit(source: &str, location: Position) -> TextEdit {
+    let lines: Vec<&str> = source.lines().collect();
+    let row = location.line as usize;
+    let current = lines.get(row).copied().unwrap_or_default();
+    let indent = &current[..current.len() - current.trim_start().len()];
+    match row.checked_sub(1).and_then(|previous| lines.get(previous)) {
+        Some(previous) => {
+            let end = Position::new(location.line - 1, previous.encode_utf16().count() as u32);
+            TextEdit {
+                range: Range::new(end, end),
+                new_text: format!("\n{indent}include({PRINT_HELPERS})"),
+            }
+        }
+        None => TextEdit {
+            range: Range::new(Position::new(0, 0), Position::new(0, 0)),
+            new_text: format!("include({PRINT_HELPERS})\n"),
+        },
+    }
+}
+
+fn completions(source: &str, location: Position, use_snippet: bool) -> Vec<CompletionItem> {
+    let mut items = vec![
+        idiom(
+            "variable_watch(<variable>)",
+            "Log each access to the variable",
+            "variable_watch(${1:VARIABLE})",
+            "variable_watch(VARIABLE)",
+            use_snippet,
+        ),
+        idiom(
+            "message(STATUS \"<variable>=...\")",
+            "Print the name and the value of the variable",
+            "message(STATUS \"${1:VARIABLE}=\\${${1:VARIABLE}}\")",
+            "message(STATUS \"VARIABLE=${VARIABLE}\")",
+            use_snippet,
+        ),
+    ];
+    let additional_text_edits =
+        (!INCLUDE_REGEX.is_match(source)).then(|| vec![include_edit(source, location)]);
+    items.extend(PRINT_COMMANDS.iter().map(|(name, documentation)| {
+        let (insert_text, insert_text_format) = if use_snippet {
+            (format!("{name}($0)"), InsertTextFormat::SNIPPET)
+        } else {
+            (name.to_string(), InsertTextFormat::PLAIN_TEXT)
+        };
+        CompletionItem {
+            label: name.to_string(),
+            kind: Some(CompletionItemKind::FUNCTION),
+            detail: Some(PRINT_HELPERS.to_string()),
+            documentation: Some(Documentation::String(documentation.to_string())),
+            insert_text: Some(insert_text),
+            insert_text_format: Some(insert_text_format),
+            additional_text_edits: additional_text_edits.clone(),
+            ..Default::default()
+        }
+    }));
+    items
+}
+
+/// The debugging idioms, offered where a command is written
+pub fn debugging_completions(source: &str, location: Position) -> Vec<CompletionItem> {
+    completions(source, location, to_use_snippet())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debugging_completions() {
+        let source = "project(demo)\nif(A)\n  cmake_pr\nendif()\n";
+        let items = completions(source, Position::new(2, 10), true);
+        let message = items
+            .iter()
+            .find(|item| item.label.starts_with("message"))
+            .unwrap();
+        assert_eq!(
+            message.insert_text.as_deref(),
+            Some("message(STATUS \"${1:VARIABLE}=\\${${1:VARIABLE}}\")")
+        );
+        let print = items
+            .iter()
+            .find(|item| item.label == "cmake_print_variables")
+            .unwrap();
+        assert_eq!(
+            print.insert_text.as_deref(),
+            Some("cmake_print_variables($0)")
+        );
+        let edits = print.additional_text_edits.as_ref().unwrap();
+        assert_eq!(edits[0].range.start, Position::new(1, 5));
+        assert_eq!(edits[0].new_text, "\n  include(CMakePrintHelpers)");
+
+        let items = completions("cmake_pr", Position::new(0, 8), false);
+        let print = items
+            .iter()
+            .find(|item| item.label == "cmake_print_properties")
+            .unwrap();
+        assert_eq!(print.insert_text.as_deref(), Some("cmake_print_properties"));
+        assert_eq!(
+            print.additional_text_edits.as_ref().unwrap()[0].new_text,
+            "include(CMakePrintHelpers)\n"
+        );
+
+        let included = "include(CMakePrintHelpers)\ncmake_pr\n";
+        assert!(
+            completions(included, Position::new(1, 8), false)
+                .iter()
+                .all(|item| item.additional_text_edits.is_none())
+        );
+    }
+}
@@ -0,0 +1,117 @@
+//! Completion of the export sets after `EXPORT`, in
+//! `install(TARGETS ... EXPORT <set>)`, `install(EXPORT <set>)` and
+//! `export(EXPORT <set>)`.
+
+use std::path::Path;
+
+use tower_lsp::lsp_types::{CompletionItem, CompletionItemKind, Documentation, Position};
+use tree_sitter::{Node, Point};
+
+use crate::utils::targets;
+use crate::utils::treehelper::command_at;
+
+/// The export sets of the project, where the name of one is expected
+pub fn export_set_completions<P: AsRef<Path>>(
+    local_path: P,
+    source: &str,
+    root: Node,
+    location: Position,
+) -> Option<Vec<CompletionItem>> {
+    let point = Point {
+        row: location.line as usize,
+        column: location.character as usize,
+    };
+    let command = command_at(root, point, source)?;
+    if !matches!(command.name.as_str(), "install" | "export") {
+        return None;
+    }
+    let previous = command
+        .argument_index
+        .checked_sub(1)
+        .and_then(|index| command.arguments.get(index))
+        .and_then(|argument| source.get(argument.byte_range()))?;
+    if previous != "EXPORT" {
+        return None;
+    }
+
+    let local_path = local_path.as_ref();
+    let lines: Vec<&str> = source.lines().collect();
+    // The index may not know about the file being edited yet
+    let filled = targets::declared_exports(&lines, root)
+        .into_iter()
+        .map(|export| (local_path.to_path_buf(), export))
+        .chain(
+            targets::all_exports()
+                .into_iter()
+                .filter(|(path, _)| path != local_path),
+        );
+    let installed = targets::declared_installed_exports(&lines, root)
+        .into_iter()
+        .map(|export| (local_path.to_path_buf(), export))
+        .chain(
+            targets::all_installed_exports()
+                .into_iter()
+                .filter(|(path, _)| path != local_path),
+        );
+    let mut items: Vec<CompletionItem> = vec![];
+    for (path, export, how) in filled
+        .map(|(path, export)| (path, export, "Filled"))
+        .chain(installed.map(|(path, export)| (path, export, "Installed")))
+    {
+        if items.iter().any(|item| item.label == export) {
+            continue;
+        }
+        items.push(CompletionItem {
+            label: export,
+            kind: Some(CompletionItemKind::REFERENCE),
+            detail: Some("Export set".to_string()),
+            documentation: Some(Documentation::String(format!(
+                "{how} in: {}",
+                path.display()
+            ))),
+            ..Default::default()
+        });
+    }
+    Some(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    #[test]
+    fn test_export_set_completions() {
+        let source = r#"install(TARGETS core EXPORT CompletedTargets)
+install(EXPORT InstalledTargets DESTINATION lib/cmake)
+export(EXPORT )
+install(TARGETS core DESTINATION )
+"#;
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let labels = |line: u32, character: u32| -> Option<Vec<String>> {
+            export_set_completions(
+                "/tmp/export/CMakeLists.txt",
+                source,
+                thetree.root_node(),
+                Position::new(line, character),
+            )
+            .map(|items| {
+                items
+                    .into_iter()
+                    .map(|item| item.label)
+                    .filter(|label| label.ends_with("edTargets"))
+                    .collect()
+            })
+        };
+        assert_eq!(
+            labels(2, 14),
+            Some(vec![
+                "CompletedTargets".to_string(),
+                "InstalledTargets".to_string()
+            ])
+        );
+        assert_eq!(labels(3, 33), None);
+    }
+}
@@ -0,0 +1,40 @@
+//! Completion of the variables like `CMAKE_CXX_FLAGS_RELEASE`, the instances of
+//! the families documented as `CMAKE_<LANG>_FLAGS_<CONFIG>`.
+
+use std::collections::HashSet;
+
+use tower_lsp::lsp_types::{
+    CompletionItem, CompletionItemKind, Documentation, MarkupContent, MarkupKind,
+};
+
+use crate::utils::cmake_docs::{DocKind, doc_url};
+use crate::utils::variable_families::{Instance, project_instances};
+
+fn instance_item(instance: Instance) -> CompletionItem {
+    let value = format!(
+        "`{}` with {}.\n\n[Online documentation]({})",
+        instance.pattern,
+        instance.describe_bindings(),
+        doc_url(DocKind::Variable, &instance.pattern)
+    );
+    CompletionItem {
+        label: instance.name,
+        kind: Some(CompletionItemKind::VARIABLE),
+        detail: Some("Variable".to_string()),
+        documentation: Some(Documentation::MarkupContent(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value,
+        })),
+        ..Default::default()
+    }
+}
+
+/// The instances for the languages of the project, not already in `builtin`
+pub fn missing_variables(builtin: &[CompletionItem]) -> Vec<CompletionItem> {
+    let known: HashSet<&str> = builtin.iter().map(|item| item.label.as_str()).collect();
+    project_instances()
+        .into_iter()
+        .filter(|instance| !known.contains(instance.name.as_str()))
+        .map(instance_item)
+        .collect()
+}
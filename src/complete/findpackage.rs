@@ -2,7 +2,7 @@ use std::sync::LazyLock;
 
 use tower_lsp::lsp_types::{CompletionItem, CompletionItemKind, Documentation};
 
-use crate::utils::CACHE_CMAKE_PACKAGES;
+use crate::utils::{CACHE_CMAKE_PACKAGES, module_path};
 
 static FIND_PACKAGE_SPACE_KEYWORDS: LazyLock<Vec<CompletionItem>> = LazyLock::new(|| {
     vec![
@@ -74,6 +74,24 @@ pub static CMAKE_SOURCE: LazyLock<Vec<CompletionItem>> = LazyLock::new(|| {
     data
 });
 
+/// Packages found through `Find<name>.cmake` files in `CMAKE_MODULE_PATH`
+pub(super) fn project_package_items() -> Vec<CompletionItem> {
+    module_path::project_packages()
+        .into_iter()
+        .map(|(name, path)| CompletionItem {
+            label: name.clone(),
+            kind: Some(CompletionItemKind::MODULE),
+            detail: Some("Module".to_string()),
+            documentation: Some(Documentation::String(format!(
+                "name: {}\nFiletype: File\nFrom: {}\n",
+                name,
+                path.display()
+            ))),
+            ..Default::default()
+        })
+        .collect()
+}
+
 pub(super) fn completion_items_with_prefix(space: &str) -> Vec<CompletionItem> {
     let mut data: Vec<CompletionItem> = CACHE_CMAKE_PACKAGES
         .iter()
@@ -0,0 +1,147 @@
+//! Completion of the generators and platforms, in `set(CMAKE_GENERATOR)` and
+//! in the presets.
+
+use tower_lsp::lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionTextEdit, Documentation, Position, Range,
+    TextEdit,
+};
+use tree_sitter::{Node, Point};
+
+use crate::utils::generators::{
+    CMAKE_GENERATORS, GENERATOR_PLATFORMS, GENERATOR_VARIABLE, PLATFORM_VARIABLE,
+};
+use crate::utils::treehelper::set_variable_at;
+
+/// The value typed before `location`, and whether it is in quotes
+fn typed_value(line: &str, location: Position) -> (Range, bool) {
+    let before = line.get(..location.character as usize).unwrap_or(line);
+    let quoted = before.matches('"').count() % 2 == 1;
+    let start = if quoted {
+        before.rfind('"').map_or(0, |quote| quote + 1)
+    } else {
+        before
+            .rfind(|c: char| c.is_whitespace() || c == '(' || c == ':')
+            .map_or(0, |separator| separator + 1)
+    };
+    let range = Range {
+        start: Position {
+            line: location.line,
+            character: start as u32,
+        },
+        end: location,
+    };
+    (range, quoted)
+}
+
+fn value_item(value: &str, detail: &str, doc: &str, range: Range, quote: bool) -> CompletionItem {
+    let new_text = if quote {
+        format!("\"{value}\"")
+    } else {
+        value.to_string()
+    };
+    CompletionItem {
+        label: value.to_string(),
+        kind: Some(CompletionItemKind::ENUM_MEMBER),
+        detail: Some(detail.to_string()),
+        documentation: Some(Documentation::String(doc.to_string())),
+        filter_text: Some(new_text.clone()),
+        text_edit: Some(CompletionTextEdit::Edit(TextEdit { range, new_text })),
+        ..Default::default()
+    }
+}
+
+/// The generators replacing the value typed before `location` on `line`.
+/// `always_quote` is for JSON strings, cmake only needs quotes around spaces
+pub fn generator_items(line: &str, location: Position, always_quote: bool) -> Vec<CompletionItem> {
+    let (range, quoted) = typed_value(line, location);
+    CMAKE_GENERATORS
+        .iter()
+        .map(|generator| {
+            let quote = !quoted && (always_quote || generator.name.contains(' '));
+            let detail = if generator.default {
+                "Default generator"
+            } else {
+                "Generator"
+            };
+            let mut item = value_item(
+                &generator.name,
+                detail,
+                &generator.description,
+                range,
+                quote,
+            );
+            item.preselect = Some(generator.default);
+            item
+        })
+        .collect()
+}
+
+/// The platforms replacing the value typed before `location` on `line`
+pub fn platform_items(line: &str, location: Position, always_quote: bool) -> Vec<CompletionItem> {
+    let (range, quoted) = typed_value(line, location);
+    GENERATOR_PLATFORMS
+        .iter()
+        .map(|(name, doc)| {
+            value_item(
+                name,
+                "Generator platform",
+                doc,
+                range,
+                !quoted && always_quote,
+            )
+        })
+        .collect()
+}
+
+/// Completion of the value in `set(CMAKE_GENERATOR |)` and
+/// `set(CMAKE_GENERATOR_PLATFORM |)`
+pub fn generator_completions(
+    source: &str,
+    root: Node,
+    location: Position,
+) -> Option<Vec<CompletionItem>> {
+    let lines: Vec<&str> = source.lines().collect();
+    let point = Point {
+        row: location.line as usize,
+        column: location.character as usize,
+    };
+    let variable = set_variable_at(point, root, &lines)?;
+    let line = lines.get(point.row)?;
+    match variable.as_str() {
+        GENERATOR_VARIABLE => Some(generator_items(line, location, false)),
+        PLATFORM_VARIABLE => Some(platform_items(line, location, false)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    #[test]
+    fn test_generator_completions() {
+        let source = "set(CMAKE_GENERATOR_PLATFORM x)\nset(CMAKE_GENERATOR \"Unix \")\n";
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let items =
+            generator_completions(source, thetree.root_node(), Position::new(0, 30)).unwrap();
+        let x64 = items.iter().find(|item| item.label == "x64").unwrap();
+        assert_eq!(
+            x64.text_edit,
+            Some(CompletionTextEdit::Edit(TextEdit {
+                range: Range::new(Position::new(0, 29), Position::new(0, 30)),
+                new_text: "x64".to_string(),
+            }))
+        );
+
+        // Inside the quotes, the spaces need none
+        let (range, quoted) = typed_value("set(CMAKE_GENERATOR \"Unix ", Position::new(1, 26));
+        assert!(quoted);
+        assert_eq!(range.start.character, 21);
+        let (range, quoted) = typed_value("  \"generator\": ", Position::new(3, 15));
+        assert!(!quoted);
+        assert_eq!(range.start.character, 15);
+    }
+}
@@ -0,0 +1,129 @@
+//! Completion of the GPU architectures, in `CMAKE_CUDA_ARCHITECTURES` and
+//! `CMAKE_HIP_ARCHITECTURES`.
+
+use tower_lsp::lsp_types::{CompletionItem, CompletionItemKind, Documentation, Position};
+use tree_sitter::{Node, Point};
+
+use crate::utils::treehelper::set_variable_at;
+
+/// The values cmake understands for both languages
+const SPECIAL_ARCHITECTURES: &[(&str, &str)] = &[
+    (
+        "native",
+        "The architectures of the GPUs of the building machine",
+    ),
+    ("all", "Every architecture the compiler supports"),
+    (
+        "all-major",
+        "Every major architecture the compiler supports, like 70 and 80 but not 75",
+    ),
+    (
+        "OFF",
+        "Pass no architecture flag, for a toolchain choosing it",
+    ),
+];
+
+/// The compute capabilities of the NVIDIA GPUs. A `-real` suffix only
+/// builds the machine code, a `-virtual` one only the PTX
+const CUDA_ARCHITECTURES: &[(&str, &str)] = &[
+    ("50", "Maxwell: GeForce GTX 750"),
+    ("52", "Maxwell: GeForce GTX 900, Titan X"),
+    ("53", "Maxwell: Jetson TX1, Jetson Nano"),
+    ("60", "Pascal: Tesla P100"),
+    ("61", "Pascal: GeForce GTX 10, Titan Xp, Tesla P40"),
+    ("62", "Pascal: Jetson TX2"),
+    ("70", "Volta: Tesla V100, Titan V"),
+    ("72", "Volta: Jetson AGX Xavier"),
+    ("75", "Turing: GeForce RTX 20, GTX 16, Tesla T4"),
+    ("80", "Ampere: A100"),
+    ("86", "Ampere: GeForce RTX 30, A40, A10"),
+    ("87", "Ampere: Jetson AGX Orin"),
+    ("89", "Ada Lovelace: GeForce RTX 40, L4, L40"),
+    ("90", "Hopper: H100, H200"),
+    ("90a", "Hopper, with the architecture specific features"),
+    ("100", "Blackwell: B200"),
+    ("120", "Blackwell: GeForce RTX 50"),
+];
+
+/// The targets of the AMD GPUs
+const HIP_ARCHITECTURES: &[(&str, &str)] = &[
+    ("gfx900", "GCN 5: Radeon Vega 56 and 64, Instinct MI25"),
+    ("gfx906", "GCN 5: Radeon VII, Instinct MI50 and MI60"),
+    ("gfx908", "CDNA: Instinct MI100"),
+    ("gfx90a", "CDNA 2: Instinct MI210 and MI250"),
+    ("gfx940", "CDNA 3: Instinct MI300 engineering samples"),
+    ("gfx942", "CDNA 3: Instinct MI300A and MI300X"),
+    (
+        "gfx1030",
+        "RDNA 2: Radeon RX 6800 and 6900, Radeon PRO W6800",
+    ),
+    ("gfx1100", "RDNA 3: Radeon RX 7900, Radeon PRO W7900"),
+    ("gfx1101", "RDNA 3: Radeon RX 7800 and 7700"),
+    ("gfx1102", "RDNA 3: Radeon RX 7600"),
+    ("gfx1200", "RDNA 4: Radeon RX 9060"),
+    ("gfx1201", "RDNA 4: Radeon RX 9070"),
+];
+
+fn architecture_item(name: &str, detail: &str, doc: &str) -> CompletionItem {
+    CompletionItem {
+        label: name.to_string(),
+        kind: Some(CompletionItemKind::ENUM_MEMBER),
+        detail: Some(detail.to_string()),
+        documentation: Some(Documentation::String(doc.to_string())),
+        ..Default::default()
+    }
+}
+
+/// Completion of the values in `set(CMAKE_CUDA_ARCHITECTURES |)` and
+/// `set(CMAKE_HIP_ARCHITECTURES |)`
+pub fn architecture_completions(
+    source: &str,
+    root: Node,
+    location: Position,
+) -> Option<Vec<CompletionItem>> {
+    let lines: Vec<&str> = source.lines().collect();
+    let point = Point {
+        row: location.line as usize,
+        column: location.character as usize,
+    };
+    let (detail, architectures) = match set_variable_at(point, root, &lines)?.as_str() {
+        "CMAKE_CUDA_ARCHITECTURES" => ("CUDA architecture", CUDA_ARCHITECTURES),
+        "CMAKE_HIP_ARCHITECTURES" => ("HIP architecture", HIP_ARCHITECTURES),
+        _ => return None,
+    };
+    let mut items: Vec<CompletionItem> = SPECIAL_ARCHITECTURES
+        .iter()
+        .map(|(name, doc)| architecture_item(name, detail, doc))
+        .collect();
+    items.extend(
+        architectures
+            .iter()
+            .map(|(name, doc)| architecture_item(name, detail, doc)),
+    );
+    Some(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    #[test]
+    fn test_architecture_completions() {
+        let source = "set(CMAKE_CUDA_ARCHITECTURES 75 )\nset(CMAKE_HIP_ARCHITECTURES )\n";
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let items =
+            architecture_completions(source, thetree.root_node(), Position::new(0, 32)).unwrap();
+        assert!(items.iter().any(|item| item.label == "86"));
+        assert!(items.iter().any(|item| item.label == "native"));
+        assert!(!items.iter().any(|item| item.label == "gfx90a"));
+        let items =
+            architecture_completions(source, thetree.root_node(), Position::new(1, 28)).unwrap();
+        assert!(items.iter().any(|item| item.label == "gfx90a"));
+        assert!(
+            architecture_completions(source, thetree.root_node(), Position::new(0, 10)).is_none()
+        );
+    }
+}
@@ -3,19 +3,24 @@
 //! This module provides path completions for various CMake commands,
 //! using the scanner module for cached directory scanning.
 
+use std::collections::HashSet;
 use std::path::Path;
 
 use tower_lsp::lsp_types::{
     CompletionItem, CompletionItemKind, CompletionTextEdit, Position, Range, TextEdit,
 };
 
-use crate::scanner::{CachedEntry, ScanOptions, scan_directory};
+use crate::scanner::{CachedEntry, FileSystem, RealFileSystem, ScanOptions};
+use crate::utils::input::{self, InputError};
+use crate::utils::line_index::LineIndex;
 
 /// Result of extracting partial path - includes the path and its start position
 #[derive(Debug, Clone)]
 pub struct PartialPathInfo {
     pub path: String,
     pub start_character: u32,
+    /// The path is in a quoted argument
+    pub quoted: bool,
 }
 
 /// Check if the partial input looks like a file path.
@@ -50,21 +55,30 @@ pub fn looks_like_path(partial: &str) -> bool {
 /// Extract the partial path input from the current line at the given position.
 /// Returns the partial path and the character position where it starts.
 pub fn extract_partial_path(source: &str, line: u32, character: u32) -> PartialPathInfo {
-    let lines: Vec<&str> = source.lines().collect();
-    if (line as usize) >= lines.len() {
+    extract_partial_path_indexed(source, &LineIndex::new(source), line, character)
+}
+
+/// [`extract_partial_path`] with the line index kept with the document
+pub fn extract_partial_path_indexed(
+    source: &str,
+    lines: &LineIndex,
+    line: u32,
+    character: u32,
+) -> PartialPathInfo {
+    let Some(current_line) = lines.line(source, line as usize) else {
         return PartialPathInfo {
             path: String::new(),
             start_character: character,
+            quoted: false,
         };
-    }
-
-    let current_line = lines[line as usize];
+    };
     let char_pos = character as usize;
 
-    if char_pos > current_line.len() {
+    if char_pos > current_line.len() || !current_line.is_char_boundary(char_pos) {
         return PartialPathInfo {
             path: String::new(),
             start_character: character,
+            quoted: false,
         };
     }
 
@@ -86,9 +100,24 @@ pub fn extract_partial_path(source: &str, line: u32, character: u32) -> PartialP
     PartialPathInfo {
         path,
         start_character: start_pos as u32,
+        quoted: before_cursor.matches('"').count() % 2 == 1,
     }
 }
 
+/// Checked variant of [`extract_partial_path`] for arbitrary bytes
+#[allow(dead_code)]
+pub fn try_extract_partial_path(
+    source: &[u8],
+    line: u32,
+    character: u32,
+) -> Result<PartialPathInfo, InputError> {
+    Ok(extract_partial_path(
+        input::decode(source)?,
+        line,
+        character,
+    ))
+}
+
 /// Determine search directory and prefix from partial input
 fn resolve_search_path<P: AsRef<Path>>(
     base_dir: P,
@@ -181,9 +210,38 @@ struct CompletionOptions {
     show_cmake_marker: bool,
 }
 
-/// Get path completions for add_subdirectory command.
-/// Returns directories that contain CMakeLists.txt relative to the current file.
-pub fn get_subdirectory_completions<P: AsRef<Path>>(
+/// The kind of path an argument expects, which decides what gets scanned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathKind {
+    Subdirectory,
+    Include,
+    SourceFile,
+    AnyFile,
+    Directory,
+}
+
+impl PathKind {
+    fn scan_options(self) -> ScanOptions {
+        match self {
+            PathKind::Subdirectory => ScanOptions::for_subdirectory(),
+            PathKind::Include => ScanOptions::for_include(),
+            PathKind::SourceFile => ScanOptions::for_source_files(),
+            PathKind::AnyFile => ScanOptions::for_any_file(),
+            PathKind::Directory => ScanOptions::for_directory(),
+        }
+    }
+
+    fn completion_options(self) -> CompletionOptions {
+        CompletionOptions {
+            show_cmake_marker: self == PathKind::Subdirectory,
+        }
+    }
+}
+
+/// Get path completions relative to the current file, resolving paths through `fs`.
+pub fn get_path_completions_with_fs<F: FileSystem + ?Sized, P: AsRef<Path>>(
+    fs: &F,
+    kind: PathKind,
     current_file: P,
     partial_info: &PartialPathInfo,
     line: u32,
@@ -193,6 +251,9 @@ pub fn get_subdirectory_completions<P: AsRef<Path>>(
     let base_dir = current_file.parent().unwrap_or(Path::new("."));
 
     let (search_dir, prefix) = resolve_search_path(base_dir, &partial_info.path);
+    if !fs.is_dir(&search_dir) {
+        return Vec::new();
+    }
 
     let replace_range = Range {
         start: Position {
@@ -202,15 +263,101 @@ pub fn get_subdirectory_completions<P: AsRef<Path>>(
         end: Position { line, character },
     };
 
-    let entries = scan_directory(&search_dir, &ScanOptions::for_subdirectory());
+    let entries = fs.scan(&search_dir, &kind.scan_options());
+    // Two paths in one quoted argument would make a single one
+    let pairs = if kind == PathKind::SourceFile && !partial_info.quoted {
+        paired_completions(fs, base_dir, &search_dir, &prefix, &entries, replace_range)
+    } else {
+        Vec::new()
+    };
 
-    entries_to_completions(
-        entries,
-        &prefix,
-        replace_range,
-        &CompletionOptions {
-            show_cmake_marker: true,
-        },
+    let mut completions =
+        entries_to_completions(entries, &prefix, replace_range, &kind.completion_options());
+    completions.extend(pairs);
+    completions
+}
+
+/// The extensions of the implementation files, which may have a header
+const IMPLEMENTATION_EXTENSIONS: &[&str] = &["c", "cc", "cpp", "cxx", "c++", "m", "mm", "cu"];
+
+const HEADER_EXTENSIONS: &[&str] = &["h", "hpp", "hh", "hxx", "h++", "cuh"];
+
+/// An implementation file together with its header, found next to it or in
+/// the `include` directory, so both are added at once
+fn paired_completions<F: FileSystem + ?Sized>(
+    fs: &F,
+    base_dir: &Path,
+    search_dir: &Path,
+    prefix: &str,
+    entries: &[CachedEntry],
+    replace_range: Range,
+) -> Vec<CompletionItem> {
+    // One listing of each directory, not a lookup of each header of each file
+    let header_dirs: Vec<(HashSet<String>, String)> = [
+        (search_dir.to_path_buf(), prefix.to_string()),
+        (base_dir.join("include"), "include/".to_string()),
+    ]
+    .into_iter()
+    .map(|(dir, header_prefix)| {
+        let files = fs
+            .scan(&dir, &ScanOptions::for_any_file())
+            .into_iter()
+            .filter(|entry| !entry.is_dir)
+            .map(|entry| entry.name)
+            .collect();
+        (files, header_prefix)
+    })
+    .collect();
+    entries
+        .iter()
+        .filter(|entry| !entry.is_dir)
+        .filter_map(|entry| {
+            let (stem, extension) = entry.name.rsplit_once('.')?;
+            if !IMPLEMENTATION_EXTENSIONS.contains(&extension) {
+                return None;
+            }
+            let (header, header_prefix) =
+                header_dirs.iter().find_map(|(files, header_prefix)| {
+                    HEADER_EXTENSIONS
+                        .iter()
+                        .map(|header_extension| format!("{stem}.{header_extension}"))
+                        .find(|header| files.contains(header))
+                        .map(|header| (header, header_prefix))
+                })?;
+            let new_text = format!("{prefix}{} {header_prefix}{header}", entry.name);
+            Some(CompletionItem {
+                label: format!("{} + {header}", entry.name),
+                kind: Some(CompletionItemKind::FILE),
+                detail: Some(format!("With {header_prefix}{header}")),
+                documentation: None,
+                // Right after the implementation file alone
+                sort_text: Some(format!("!1_{}~", entry.name)),
+                filter_text: Some(format!("{prefix}{}", entry.name)),
+                text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                    range: replace_range,
+                    new_text,
+                })),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Get path completions for add_subdirectory command.
+/// Returns directories that contain CMakeLists.txt relative to the current file.
+pub fn get_subdirectory_completions<P: AsRef<Path>>(
+    current_file: P,
+    partial_info: &PartialPathInfo,
+    line: u32,
+    character: u32,
+) -> Vec<CompletionItem> {
+    get_path_completions_with_fs(
+        &RealFileSystem,
+        PathKind::Subdirectory,
+        current_file,
+        partial_info,
+        line,
+        character,
     )
 }
 
@@ -222,26 +369,13 @@ pub fn get_include_path_completions<P: AsRef<Path>>(
     line: u32,
     character: u32,
 ) -> Vec<CompletionItem> {
-    let current_file = current_file.as_ref();
-    let base_dir = current_file.parent().unwrap_or(Path::new("."));
-
-    let (search_dir, prefix) = resolve_search_path(base_dir, &partial_info.path);
-
-    let replace_range = Range {
-        start: Position {
-            line,
-            character: partial_info.start_character,
-        },
-        end: Position { line, character },
-    };
-
-    let entries = scan_directory(&search_dir, &ScanOptions::for_include());
-
-    entries_to_completions(
-        entries,
-        &prefix,
-        replace_range,
-        &CompletionOptions::default(),
+    get_path_completions_with_fs(
+        &RealFileSystem,
+        PathKind::Include,
+        current_file,
+        partial_info,
+        line,
+        character,
     )
 }
 
@@ -253,26 +387,13 @@ pub fn get_source_file_completions<P: AsRef<Path>>(
     line: u32,
     character: u32,
 ) -> Vec<CompletionItem> {
-    let current_file = current_file.as_ref();
-    let base_dir = current_file.parent().unwrap_or(Path::new("."));
-
-    let (search_dir, prefix) = resolve_search_path(base_dir, &partial_info.path);
-
-    let replace_range = Range {
-        start: Position {
-            line,
-            character: partial_info.start_character,
-        },
-        end: Position { line, character },
-    };
-
-    let entries = scan_directory(&search_dir, &ScanOptions::for_source_files());
-
-    entries_to_completions(
-        entries,
-        &prefix,
-        replace_range,
-        &CompletionOptions::default(),
+    get_path_completions_with_fs(
+        &RealFileSystem,
+        PathKind::SourceFile,
+        current_file,
+        partial_info,
+        line,
+        character,
     )
 }
 
@@ -284,26 +405,13 @@ pub fn get_any_file_completions<P: AsRef<Path>>(
     line: u32,
     character: u32,
 ) -> Vec<CompletionItem> {
-    let current_file = current_file.as_ref();
-    let base_dir = current_file.parent().unwrap_or(Path::new("."));
-
-    let (search_dir, prefix) = resolve_search_path(base_dir, &partial_info.path);
-
-    let replace_range = Range {
-        start: Position {
-            line,
-            character: partial_info.start_character,
-        },
-        end: Position { line, character },
-    };
-
-    let entries = scan_directory(&search_dir, &ScanOptions::for_any_file());
-
-    entries_to_completions(
-        entries,
-        &prefix,
-        replace_range,
-        &CompletionOptions::default(),
+    get_path_completions_with_fs(
+        &RealFileSystem,
+        PathKind::AnyFile,
+        current_file,
+        partial_info,
+        line,
+        character,
     )
 }
 
@@ -315,26 +423,13 @@ pub fn get_directory_completions<P: AsRef<Path>>(
     line: u32,
     character: u32,
 ) -> Vec<CompletionItem> {
-    let current_file = current_file.as_ref();
-    let base_dir = current_file.parent().unwrap_or(Path::new("."));
-
-    let (search_dir, prefix) = resolve_search_path(base_dir, &partial_info.path);
-
-    let replace_range = Range {
-        start: Position {
-            line,
-            character: partial_info.start_character,
-        },
-        end: Position { line, character },
-    };
-
-    let entries = scan_directory(&search_dir, &ScanOptions::for_directory());
-
-    entries_to_completions(
-        entries,
-        &prefix,
-        replace_range,
-        &CompletionOptions::default(),
+    get_path_completions_with_fs(
+        &RealFileSystem,
+        PathKind::Directory,
+        current_file,
+        partial_info,
+        line,
+        character,
     )
 }
 
@@ -399,6 +494,7 @@ include("cmake/mo")
         let partial_info = PartialPathInfo {
             path: String::new(),
             start_character: 17, // after "add_subdirectory("
+            quoted: false,
         };
         let completions = get_subdirectory_completions(&cmake_file, &partial_info, 0, 17);
         assert!(!completions.is_empty());
@@ -431,6 +527,7 @@ include("cmake/mo")
         let partial_info = PartialPathInfo {
             path: "src/".to_string(),
             start_character: 16,
+            quoted: false,
         };
         let completions = get_source_file_completions(&cmake_file, &partial_info, 0, 20);
 
@@ -441,6 +538,56 @@ include("cmake/mo")
         assert!(!completions.iter().any(|c| c.label == "readme.txt"));
     }
 
+    #[test]
+    fn test_source_file_pairs() {
+        let dir = tempdir().unwrap();
+        let cmake_file = dir.path().join("CMakeLists.txt");
+        File::create(&cmake_file).unwrap();
+        let src_dir = dir.path().join("src");
+        let include_dir = dir.path().join("include");
+        fs::create_dir(&src_dir).unwrap();
+        fs::create_dir(&include_dir).unwrap();
+        File::create(src_dir.join("parser.cpp")).unwrap();
+        File::create(src_dir.join("parser.hpp")).unwrap();
+        File::create(src_dir.join("lexer.cpp")).unwrap();
+        File::create(include_dir.join("lexer.h")).unwrap();
+        File::create(src_dir.join("main.cpp")).unwrap();
+
+        let partial_info = PartialPathInfo {
+            path: "src/".to_string(),
+            start_character: 16,
+            quoted: false,
+        };
+        let completions = get_source_file_completions(&cmake_file, &partial_info, 0, 20);
+        let pair = |label: &str| {
+            completions
+                .iter()
+                .find(|c| c.label == label)
+                .and_then(|c| c.text_edit.clone())
+        };
+        let Some(CompletionTextEdit::Edit(edit)) = pair("parser.cpp + parser.hpp") else {
+            panic!("parser.cpp is not paired");
+        };
+        assert_eq!(edit.new_text, "src/parser.cpp src/parser.hpp");
+        let Some(CompletionTextEdit::Edit(edit)) = pair("lexer.cpp + lexer.h") else {
+            panic!("lexer.cpp is not paired");
+        };
+        assert_eq!(edit.new_text, "src/lexer.cpp include/lexer.h");
+        assert!(
+            !completions
+                .iter()
+                .any(|c| c.label.starts_with("main.cpp +"))
+        );
+
+        let source = "target_sources(app PRIVATE \"src/";
+        let partial_info = extract_partial_path(source, 0, source.len() as u32);
+        assert!(partial_info.quoted);
+        let completions =
+            get_source_file_completions(&cmake_file, &partial_info, 0, source.len() as u32);
+        assert!(completions.iter().any(|c| c.label == "parser.cpp"));
+        assert!(!completions.iter().any(|c| c.label.contains(" + ")));
+    }
+
     #[test]
     fn test_any_file_completions() {
         let dir = tempdir().unwrap();
@@ -455,6 +602,7 @@ include("cmake/mo")
         let partial_info = PartialPathInfo {
             path: String::new(),
             start_character: 10,
+            quoted: false,
         };
         let completions = get_any_file_completions(&cmake_file, &partial_info, 0, 10);
 
@@ -481,6 +629,7 @@ include("cmake/mo")
         let partial_info = PartialPathInfo {
             path: "cmake/".to_string(),
             start_character: 9, // after "include(\""
+            quoted: false,
         };
         let completions = get_include_path_completions(&cmake_file, &partial_info, 0, 15);
         assert!(!completions.is_empty());
@@ -524,4 +673,86 @@ include("cmake/mo")
         assert_eq!(info.path, ".");
         assert!(looks_like_path(&info.path));
     }
+
+    #[test]
+    fn test_completions_with_memory_fs() {
+        use crate::scanner::MemoryFileSystem;
+
+        // Files that only exist in the editor so far
+        let fs = MemoryFileSystem::new();
+        fs.add_file("/project/CMakeLists.txt");
+        fs.add_file("/project/lib/CMakeLists.txt");
+        fs.add_file("/project/lib/lib.cpp");
+        fs.add_dir("/project/docs");
+
+        let partial_info = PartialPathInfo {
+            path: String::new(),
+            start_character: 17,
+            quoted: false,
+        };
+        let completions = get_path_completions_with_fs(
+            &fs,
+            PathKind::Subdirectory,
+            "/project/CMakeLists.txt",
+            &partial_info,
+            0,
+            17,
+        );
+        assert_eq!(completions.len(), 2);
+        let lib_item = completions.iter().find(|c| c.label == "lib/").unwrap();
+        assert_eq!(lib_item.detail, Some("✓".to_string()));
+
+        let partial_info = PartialPathInfo {
+            path: "lib/".to_string(),
+            start_character: 15,
+            quoted: false,
+        };
+        let completions = get_path_completions_with_fs(
+            &fs,
+            PathKind::SourceFile,
+            "/project/CMakeLists.txt",
+            &partial_info,
+            0,
+            19,
+        );
+        assert!(completions.iter().any(|c| c.label == "lib.cpp"));
+        assert!(!completions.iter().any(|c| c.label == "CMakeLists.txt"));
+
+        let partial_info = PartialPathInfo {
+            path: "missing/".to_string(),
+            start_character: 15,
+            quoted: false,
+        };
+        assert!(
+            get_path_completions_with_fs(
+                &fs,
+                PathKind::AnyFile,
+                "/project/CMakeLists.txt",
+                &partial_info,
+                0,
+                23,
+            )
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_try_extract_partial_path() {
+        // Cursor in the middle of a multi-byte character
+        let info = try_extract_partial_path("include(\"é/a".as_bytes(), 0, 10).unwrap();
+        assert_eq!(info.path, "");
+        let info = try_extract_partial_path("include(\"é/a".as_bytes(), 0, 12).unwrap();
+        assert_eq!(info.path, "é/");
+
+        assert!(try_extract_partial_path(b"include(\xc3", 0, 9).is_err());
+        assert!(try_extract_partial_path(b"", u32::MAX, u32::MAX).is_ok());
+
+        let alphabet: Vec<u8> = (0..=u8::MAX).collect();
+        for bytes in input::arbitrary_inputs(0x9e37_79b9, &alphabet, 200, 64) {
+            let input = String::from_utf8_lossy(&bytes);
+            for character in 0..200 {
+                assert!(try_extract_partial_path(input.as_bytes(), 0, character).is_ok());
+            }
+        }
+    }
 }
@@ -0,0 +1,66 @@
+//! Completion of the platform variables, like `WIN32` and `APPLE`, documented
+//! with when each is true rather than with their whole help page.
+
+use std::sync::LazyLock;
+
+use tower_lsp::lsp_types::{
+    CompletionItem, CompletionItemKind, Documentation, MarkupContent, MarkupKind,
+};
+
+use crate::utils::cmake_docs::{DocKind, doc_url};
+use crate::utils::platforms::{PLATFORM_VARIABLES, platform_doc};
+
+static PLATFORM_VARIABLE_ITEMS: LazyLock<Vec<CompletionItem>> = LazyLock::new(|| {
+    PLATFORM_VARIABLES
+        .iter()
+        .map(|(name, doc)| CompletionItem {
+            label: name.to_string(),
+            kind: Some(CompletionItemKind::VARIABLE),
+            detail: Some("Platform variable".to_string()),
+            documentation: Some(Documentation::MarkupContent(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: format!(
+                    "{doc}.\n\n[Online documentation]({})",
+                    doc_url(DocKind::Variable, name)
+                ),
+            })),
+            ..Default::default()
+        })
+        .collect()
+});
+
+/// Replace the platform variables of `builtin` with their documented items,
+/// adding those `cmake --help-variables` does not list
+pub fn replace_variables(builtin: &mut Vec<CompletionItem>) {
+    builtin.retain(|item| platform_doc(&item.label).is_none());
+    builtin.extend(PLATFORM_VARIABLE_ITEMS.iter().cloned());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replace_variables() {
+        let mut builtin = vec![
+            CompletionItem {
+                label: "WIN32".to_string(),
+                detail: Some("Variable".to_string()),
+                ..Default::default()
+            },
+            CompletionItem {
+                label: "CMAKE_SYSTEM_NAME".to_string(),
+                ..Default::default()
+            },
+        ];
+        replace_variables(&mut builtin);
+        let win32: Vec<&CompletionItem> = builtin
+            .iter()
+            .filter(|item| item.label == "WIN32")
+            .collect();
+        assert_eq!(win32.len(), 1);
+        assert_eq!(win32[0].detail.as_deref(), Some("Platform variable"));
+        assert!(builtin.iter().any(|item| item.label == "CMAKE_SYSTEM_NAME"));
+        assert!(builtin.iter().any(|item| item.label == "EMSCRIPTEN"));
+    }
+}
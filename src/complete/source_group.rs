@@ -0,0 +1,111 @@
+//! Completion of the keywords of `source_group()`, for its two signatures:
+//! `source_group(<name> [FILES <src>...] [REGULAR_EXPRESSION <regex>])` and
+//! `source_group(TREE <root> [PREFIX <prefix>] [FILES <src>...])`.
+
+use tower_lsp::lsp_types::{CompletionItem, CompletionItemKind, Documentation, Position};
+use tree_sitter::{Node, Point};
+
+use crate::utils::treehelper::command_at;
+
+const TREE_KEYWORD: (&str, &str) = (
+    "TREE",
+    "Group the files as they are laid out under the root directory",
+);
+
+/// The keywords of `source_group(TREE <root> ...)`
+const TREE_KEYWORDS: &[(&str, &str)] = &[
+    ("PREFIX", "The group the directories of the tree are put in"),
+    ("FILES", "The files to group, all under the root directory"),
+];
+
+/// The keywords of `source_group(<name> ...)`
+const NAMED_KEYWORDS: &[(&str, &str)] = &[
+    ("FILES", "The files put in the group"),
+    (
+        "REGULAR_EXPRESSION",
+        "The files matching it are put in the group, unless listed in another one",
+    ),
+];
+
+/// The keywords followed by a single value, which is not a keyword
+const ONE_VALUE_KEYWORDS: &[&str] = &["TREE", "PREFIX", "REGULAR_EXPRESSION"];
+
+fn item(keyword: &str, doc: &str) -> CompletionItem {
+    CompletionItem {
+        label: keyword.to_string(),
+        kind: Some(CompletionItemKind::KEYWORD),
+        detail: Some("source_group keyword".to_string()),
+        documentation: Some(Documentation::String(doc.to_string())),
+        ..Default::default()
+    }
+}
+
+/// The keywords of `source_group()` not written yet, offered with the other
+/// completions since the files can be any argument
+pub fn keyword_completions(source: &str, root: Node, location: Position) -> Vec<CompletionItem> {
+    let point = Point {
+        row: location.line as usize,
+        column: location.character as usize,
+    };
+    let Some(command) = command_at(root, point, source) else {
+        return vec![];
+    };
+    if command.name != "source_group" {
+        return vec![];
+    }
+    let before: Vec<&str> = command
+        .arguments
+        .iter()
+        .filter(|argument| argument.end_position() < point)
+        .filter_map(|argument| source.get(argument.byte_range()))
+        .collect();
+    let Some(first) = before.first() else {
+        return vec![item(TREE_KEYWORD.0, TREE_KEYWORD.1)];
+    };
+    if before
+        .last()
+        .is_some_and(|last| ONE_VALUE_KEYWORDS.contains(last))
+    {
+        return vec![];
+    }
+    let keywords = if *first == "TREE" {
+        TREE_KEYWORDS
+    } else {
+        NAMED_KEYWORDS
+    };
+    keywords
+        .iter()
+        .filter(|(keyword, _)| !before.contains(keyword))
+        .map(|(keyword, doc)| item(keyword, doc))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    #[test]
+    fn test_source_group_keywords() {
+        let source = r#"source_group()
+source_group(TREE ${CMAKE_CURRENT_SOURCE_DIR} )
+source_group(TREE src PREFIX )
+source_group(TREE src PREFIX Sources )
+source_group(Headers )
+"#;
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let labels = |line: u32, character: u32| -> Vec<String> {
+            keyword_completions(source, thetree.root_node(), Position::new(line, character))
+                .into_iter()
+                .map(|item| item.label)
+                .collect()
+        };
+        assert_eq!(labels(0, 13), vec!["TREE"]);
+        assert_eq!(labels(1, 46), vec!["PREFIX", "FILES"]);
+        assert!(labels(2, 29).is_empty());
+        assert_eq!(labels(3, 37), vec!["FILES"]);
+        assert_eq!(labels(4, 21), vec!["FILES", "REGULAR_EXPRESSION"]);
+    }
+}
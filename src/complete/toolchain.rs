@@ -0,0 +1,323 @@
+//! Completion for toolchain files: the cross compiling variables and their values.
+
+use std::path::Path;
+use std::sync::LazyLock;
+
+use tower_lsp::lsp_types::{CompletionItem, CompletionItemKind, Documentation, Position};
+
+use super::path_complete;
+use crate::utils::treehelper::set_variable_at;
+
+const LANGUAGES: &[&str] = &[
+    "C", "CXX", "ASM", "Fortran", "CUDA", "HIP", "OBJC", "OBJCXX",
+];
+
+const VARIABLES: &[(&str, &str)] = &[
+    (
+        "CMAKE_SYSTEM_NAME",
+        "Name of the target system, setting it enables cross compiling",
+    ),
+    ("CMAKE_SYSTEM_VERSION", "Version of the target system"),
+    ("CMAKE_SYSTEM_PROCESSOR", "Processor of the target system"),
+    (
+        "CMAKE_SYSROOT",
+        "Path passed to the compiler with --sysroot",
+    ),
+    ("CMAKE_SYSROOT_COMPILE", "Sysroot used only when compiling"),
+    ("CMAKE_SYSROOT_LINK", "Sysroot used only when linking"),
+    (
+        "CMAKE_STAGING_PREFIX",
+        "Install prefix on the host when cross compiling",
+    ),
+    (
+        "CMAKE_FIND_ROOT_PATH",
+        "Root directories prepended to every find_* search",
+    ),
+    (
+        "CMAKE_FIND_ROOT_PATH_MODE_PROGRAM",
+        "Whether find_program() uses CMAKE_FIND_ROOT_PATH",
+    ),
+    (
+        "CMAKE_FIND_ROOT_PATH_MODE_LIBRARY",
+        "Whether find_library() uses CMAKE_FIND_ROOT_PATH",
+    ),
+    (
+        "CMAKE_FIND_ROOT_PATH_MODE_INCLUDE",
+        "Whether find_file() and find_path() use CMAKE_FIND_ROOT_PATH",
+    ),
+    (
+        "CMAKE_FIND_ROOT_PATH_MODE_PACKAGE",
+        "Whether find_package() uses CMAKE_FIND_ROOT_PATH",
+    ),
+    (
+        "CMAKE_CROSSCOMPILING_EMULATOR",
+        "Emulator used to run target executables on the host",
+    ),
+    (
+        "CMAKE_TRY_COMPILE_TARGET_TYPE",
+        "Kind of target built by try_compile()",
+    ),
+    ("CMAKE_LINKER", "Linker to use"),
+    ("CMAKE_AR", "Archiver to use for static libraries"),
+    (
+        "CMAKE_RANLIB",
+        "Archive indexer to use for static libraries",
+    ),
+    (
+        "CMAKE_OSX_SYSROOT",
+        "SDK to build against on Apple platforms",
+    ),
+    (
+        "CMAKE_OSX_ARCHITECTURES",
+        "Architectures to build for on Apple platforms",
+    ),
+    ("CMAKE_ANDROID_NDK", "Path of the Android NDK"),
+    ("CMAKE_ANDROID_ARCH_ABI", "Android ABI to build for"),
+    ("CMAKE_ANDROID_API", "Android API level to build for"),
+];
+
+const SYSTEM_NAMES: &[&str] = &[
+    "Linux",
+    "Windows",
+    "Darwin",
+    "Android",
+    "iOS",
+    "tvOS",
+    "watchOS",
+    "visionOS",
+    "FreeBSD",
+    "NetBSD",
+    "OpenBSD",
+    "QNX",
+    "Emscripten",
+    "WindowsStore",
+    "WindowsPhone",
+    "Generic",
+];
+
+const PROCESSORS: &[&str] = &[
+    "x86_64", "i686", "aarch64", "arm", "armv7", "riscv64", "riscv32", "ppc64le", "s390x", "mips",
+    "AMD64", "ARM64",
+];
+
+const FIND_ROOT_PATH_MODES: &[&str] = &["NEVER", "ONLY", "BOTH"];
+
+const TRY_COMPILE_TARGET_TYPES: &[&str] = &["EXECUTABLE", "STATIC_LIBRARY"];
+
+const ANDROID_ABIS: &[&str] = &["arm64-v8a", "armeabi-v7a", "x86", "x86_64"];
+
+/// Variables configuring the toolchain, including the per language ones
+pub static TOOLCHAIN_VARIABLES: LazyLock<Vec<CompletionItem>> = LazyLock::new(|| {
+    let per_language = LANGUAGES.iter().flat_map(|lang| {
+        [
+            (format!("CMAKE_{lang}_COMPILER"), format!("{lang} compiler")),
+            (
+                format!("CMAKE_{lang}_COMPILER_TARGET"),
+                format!("Target triple passed to the {lang} compiler"),
+            ),
+            (
+                format!("CMAKE_{lang}_FLAGS_INIT"),
+                format!("Initial {lang} flags"),
+            ),
+        ]
+    });
+    VARIABLES
+        .iter()
+        .map(|(name, doc)| (name.to_string(), doc.to_string()))
+        .chain(per_language)
+        .map(|(name, doc)| CompletionItem {
+            label: name,
+            kind: Some(CompletionItemKind::VARIABLE),
+            detail: Some("Toolchain variable".to_string()),
+            documentation: Some(Documentation::String(doc)),
+            ..Default::default()
+        })
+        .collect()
+});
+
+fn value_items(values: &[&str], kind: CompletionItemKind) -> Vec<CompletionItem> {
+    values
+        .iter()
+        .map(|value| CompletionItem {
+            label: value.to_string(),
+            kind: Some(kind),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Names of the compilers for `lang`, as found in executable names
+fn compiler_names(lang: &str) -> &'static [&'static str] {
+    match lang {
+        "CXX" | "OBJCXX" | "HIP" => &["g++", "c++", "clang++", "clang-cl", "cl"],
+        "Fortran" => &["gfortran", "flang", "ifort", "ifx"],
+        "CUDA" => &["nvcc", "clang++"],
+        _ => &["gcc", "cc", "clang", "clang-cl", "cl"],
+    }
+}
+
+/// `arm-none-eabi-gcc`, `gcc-13` or `clang++.exe` are all compilers named gcc or clang++
+fn is_compiler(file_name: &str, names: &[&str]) -> bool {
+    let file_name = file_name.strip_suffix(".exe").unwrap_or(file_name);
+    let without_version = file_name
+        .trim_end_matches(|c: char| c.is_ascii_digit() || c == '.')
+        .trim_end_matches('-');
+    names
+        .iter()
+        .any(|name| without_version == *name || without_version.ends_with(&format!("-{name}")))
+}
+
+fn compilers_on_path(lang: &str, executables_on_path: &[String]) -> Vec<String> {
+    let names = compiler_names(lang);
+    executables_on_path
+        .iter()
+        .filter(|name| is_compiler(name, names))
+        .cloned()
+        .collect()
+}
+
+/// Directories which are commonly sysroots, like `/usr/aarch64-linux-gnu`
+fn sysroot_candidates() -> Vec<String> {
+    [
+        "/usr/*-linux-gnu*",
+        "/usr/*-linux-musl*",
+        "/opt/*/sysroot",
+        "/opt/*/*/sysroot",
+    ]
+    .iter()
+    .filter_map(|pattern| glob::glob(pattern).ok())
+    .flat_map(|paths| paths.flatten())
+    .filter(|path| path.is_dir())
+    .filter_map(|path| path.to_str().map(str::to_string))
+    .collect()
+}
+
+/// Completion of the value in `set(<toolchain variable> |)`
+pub fn value_completions<P: AsRef<Path>>(
+    local_path: P,
+    source: &str,
+    root: tree_sitter::Node,
+    location: Position,
+    executables_on_path: &[String],
+) -> Option<Vec<CompletionItem>> {
+    let lines: Vec<&str> = source.lines().collect();
+    let point = tree_sitter::Point {
+        row: location.line as usize,
+        column: location.character as usize,
+    };
+    let variable = set_variable_at(point, root, &lines)?;
+    let items = match variable.as_str() {
+        "CMAKE_SYSTEM_NAME" => value_items(SYSTEM_NAMES, CompletionItemKind::ENUM_MEMBER),
+        "CMAKE_SYSTEM_PROCESSOR" => value_items(PROCESSORS, CompletionItemKind::ENUM_MEMBER),
+        "CMAKE_TRY_COMPILE_TARGET_TYPE" => {
+            value_items(TRY_COMPILE_TARGET_TYPES, CompletionItemKind::ENUM_MEMBER)
+        }
+        "CMAKE_ANDROID_ARCH_ABI" => value_items(ANDROID_ABIS, CompletionItemKind::ENUM_MEMBER),
+        name if name.starts_with("CMAKE_FIND_ROOT_PATH_MODE_") => {
+            value_items(FIND_ROOT_PATH_MODES, CompletionItemKind::ENUM_MEMBER)
+        }
+        "CMAKE_SYSROOT"
+        | "CMAKE_SYSROOT_COMPILE"
+        | "CMAKE_SYSROOT_LINK"
+        | "CMAKE_FIND_ROOT_PATH"
+        | "CMAKE_STAGING_PREFIX"
+        | "CMAKE_ANDROID_NDK" => {
+            let partial_info =
+                path_complete::extract_partial_path(source, location.line, location.character);
+            if path_complete::looks_like_path(&partial_info.path) {
+                path_complete::get_directory_completions(
+                    local_path,
+                    &partial_info,
+                    location.line,
+                    location.character,
+                )
+            } else {
+                sysroot_candidates()
+                    .into_iter()
+                    .map(|path| CompletionItem {
+                        label: path,
+                        kind: Some(CompletionItemKind::FOLDER),
+                        detail: Some("Sysroot".to_string()),
+                        ..Default::default()
+                    })
+                    .collect()
+            }
+        }
+        name => {
+            let lang = name.strip_prefix("CMAKE_")?.strip_suffix("_COMPILER")?;
+            if !LANGUAGES.contains(&lang) {
+                return None;
+            }
+            compilers_on_path(lang, executables_on_path)
+                .into_iter()
+                .map(|compiler| CompletionItem {
+                    label: compiler,
+                    kind: Some(CompletionItemKind::FILE),
+                    detail: Some(format!("{lang} compiler")),
+                    ..Default::default()
+                })
+                .collect()
+        }
+    };
+    Some(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    #[test]
+    fn test_is_compiler() {
+        let c = compiler_names("C");
+        assert!(is_compiler("gcc", c));
+        assert!(is_compiler("gcc-13", c));
+        assert!(is_compiler("arm-none-eabi-gcc", c));
+        assert!(is_compiler("clang.exe", c));
+        assert!(!is_compiler("gcc-ar", c));
+        assert!(!is_compiler("g++", c));
+        assert!(is_compiler(
+            "aarch64-linux-gnu-g++-12",
+            compiler_names("CXX")
+        ));
+    }
+
+    #[test]
+    fn test_value_completions() {
+        let source =
+            "set(CMAKE_SYSTEM_NAME )\nset(CMAKE_FIND_ROOT_PATH_MODE_PROGRAM N)\nset(OTHER )\n";
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let at = |line, character| {
+            value_completions(
+                "/project/toolchain.cmake",
+                source,
+                thetree.root_node(),
+                Position { line, character },
+                &[],
+            )
+        };
+        let items = at(0, 22).unwrap();
+        assert!(items.iter().any(|item| item.label == "Linux"));
+        let items = at(1, 39).unwrap();
+        assert_eq!(items.len(), 3);
+        assert!(at(2, 10).is_none());
+        // On the variable name itself
+        assert!(at(0, 8).is_none());
+    }
+
+    #[test]
+    fn test_toolchain_variables() {
+        assert!(
+            TOOLCHAIN_VARIABLES
+                .iter()
+                .any(|item| item.label == "CMAKE_CXX_COMPILER")
+        );
+        assert!(
+            TOOLCHAIN_VARIABLES
+                .iter()
+                .any(|item| item.label == "CMAKE_SYSROOT")
+        );
+    }
+}
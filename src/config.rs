@@ -1,8 +1,13 @@
-use std::path::PathBuf;
-use std::sync::LazyLock;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, PoisonError, RwLock};
 
 use etcetera::{BaseStrategy, choose_base_strategy};
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
+use tower_lsp::lsp_types::DiagnosticSeverity;
+
+use crate::formatting::compat::{Case, FOREIGN_STYLE};
+use crate::formatting::layout::Layout;
 
 #[derive(Deserialize, PartialEq, Eq, Debug)]
 pub struct Config {
@@ -13,24 +18,128 @@ pub struct Config {
     #[serde(default)]
     pub enable_external_cmake_lint: bool,
     /// Max line length.
-    #[serde(default = "default_max_words")]
-    pub line_max_words: usize,
+    #[serde(default)]
+    pub line_max_words: Option<usize>,
     #[serde(default)]
     pub format: FormatConfig,
+    /// Commands defined outside the sources the server can see, such as
+    /// `catch_discover_tests` or macros from a prebuilt SDK.
+    #[serde(default)]
+    pub known_commands: Vec<KnownCommand>,
+    /// Glob patterns of the toolchain files, for the ones not found by name.
+    #[serde(default)]
+    pub toolchain_files: Vec<String>,
+    /// Extensions offered with the source files, such as `metal` or `ui`.
+    #[serde(default)]
+    pub source_extensions: Vec<String>,
+    /// Severity of the lint rules, by id or by name, such as `N401 = "off"`.
+    #[serde(default, deserialize_with = "deserialize_rules")]
+    pub rules: HashMap<String, RuleLevel>,
+    /// Baseline of the diagnostics not to report, written by `lint --baseline`,
+    /// relative to the config file, or to the project for a user config.
+    #[serde(default)]
+    pub baseline: Option<PathBuf>,
+    /// Glob patterns of the directories of vendored code, relative to the
+    /// project, in addition to `third_party/`, `extern/`, `_deps/`...
+    #[serde(default)]
+    pub external_dirs: Vec<String>,
+    /// Severity the diagnostics of vendored code are lowered to, or `off`.
+    #[serde(default = "default_external_diagnostics")]
+    pub external_diagnostics: RuleLevel,
+}
+
+/// The `[rules]` table, keyed in upper case so `n401` is `N401`
+fn deserialize_rules<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<String, RuleLevel>, D::Error> {
+    let rules = HashMap::<String, RuleLevel>::deserialize(deserializer)?;
+    Ok(rules
+        .into_iter()
+        .map(|(key, level)| (key.to_uppercase(), level))
+        .collect())
 }
 
 const fn default_max_words() -> usize {
     80
 }
 
+const fn default_external_diagnostics() -> RuleLevel {
+    RuleLevel::Off
+}
+
+impl Config {
+    /// Make the relative paths of the config file in `dir` relative to `dir`
+    fn relative_to(&mut self, dir: &Path) {
+        if let Some(baseline) = self.baseline.as_mut() {
+            *baseline = dir.join(&*baseline);
+        }
+    }
+
+    /// The extra source extensions, with or without their dot
+    pub fn source_extensions(&self) -> impl Iterator<Item = &str> {
+        self.source_extensions
+            .iter()
+            .map(|extension| extension.trim_start_matches('.'))
+            .filter(|extension| !extension.is_empty())
+    }
+
+    /// Max line length, from the cmake-format or gersemi config when not set
+    pub fn line_max_words(&self) -> usize {
+        self.line_max_words
+            .or(FOREIGN_STYLE.line_width)
+            .unwrap_or_else(default_max_words)
+    }
+
+    /// Letter case of commands, from the cmake-format or gersemi config when not set
+    pub fn command_case(&self) -> Option<CommandCase> {
+        self.command_case
+            .or_else(|| match FOREIGN_STYLE.command_case? {
+                Case::Lower => Some(CommandCase::Lower),
+                Case::Upper => Some(CommandCase::Upper),
+                Case::Preserve => None,
+            })
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
             command_case: None,
             enable_external_cmake_lint: false,
-            line_max_words: default_max_words(),
+            line_max_words: None,
             format: FormatConfig::default(),
+            known_commands: Vec::new(),
+            toolchain_files: Vec::new(),
+            source_extensions: Vec::new(),
+            rules: HashMap::new(),
+            baseline: None,
+            external_dirs: Vec::new(),
+            external_diagnostics: default_external_diagnostics(),
+        }
+    }
+}
+
+#[derive(Deserialize, PartialEq, Eq, Debug, Clone)]
+pub struct KnownCommand {
+    pub name: String,
+    /// Signatures in the form `name(<arg> [OPTION] ...)`.
+    #[serde(default)]
+    pub signatures: Vec<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+impl KnownCommand {
+    /// Documentation shown on hover and in completion.
+    pub fn documentation(&self) -> String {
+        let mut documentation = self.signatures.join("\n");
+        if let Some(description) = &self.description {
+            if !documentation.is_empty() {
+                documentation.push_str("\n\n");
+            }
+            documentation.push_str(description);
         }
+        documentation
     }
 }
 
@@ -60,13 +169,276 @@ impl CommandCase {
     }
 }
 
-#[derive(Default, Deserialize, PartialEq, Eq, Debug)]
+#[derive(Debug, Clone, Copy, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleLevel {
+    Off,
+    Error,
+    #[serde(alias = "warn")]
+    Warning,
+    #[serde(alias = "info")]
+    Information,
+    Hint,
+}
+
+impl RuleLevel {
+    /// The severity of the diagnostics, `None` when they are turned off
+    pub fn severity(self) -> Option<DiagnosticSeverity> {
+        match self {
+            RuleLevel::Off => None,
+            RuleLevel::Error => Some(DiagnosticSeverity::ERROR),
+            RuleLevel::Warning => Some(DiagnosticSeverity::WARNING),
+            RuleLevel::Information => Some(DiagnosticSeverity::INFORMATION),
+            RuleLevel::Hint => Some(DiagnosticSeverity::HINT),
+        }
+    }
+}
+
+#[derive(Deserialize, PartialEq, Eq, Debug, Clone)]
 pub struct FormatConfig {
     pub program: Option<String>,
     pub args: Option<Vec<String>>,
+    /// Letter case of the command names: `lower`, `upper` or `preserve`.
+    #[serde(default)]
+    pub command_case: Option<Case>,
+    /// Letter case of the keywords: `upper` or `preserve`.
+    #[serde(default)]
+    pub keyword_case: Option<Case>,
+    /// Layout of the arguments: `preserve`, `one_per_line`, `bin_pack` or `aligned`.
+    #[serde(default)]
+    pub layout: Layout,
+    /// Layout of the arguments of some commands, by lower case name.
+    #[serde(default)]
+    pub command_layout: HashMap<String, Layout>,
+    #[serde(default = "default_true")]
+    pub trim_trailing_whitespace: bool,
+    /// End the files with exactly one newline.
+    #[serde(default)]
+    pub final_newline: bool,
+    /// Collapse longer runs of blank lines to this many.
+    #[serde(default)]
+    pub max_blank_lines: Option<usize>,
+}
+
+const fn default_true() -> bool {
+    true
+}
+
+impl Default for FormatConfig {
+    fn default() -> Self {
+        Self {
+            program: None,
+            args: None,
+            command_case: None,
+            keyword_case: None,
+            layout: Layout::default(),
+            command_layout: HashMap::new(),
+            trim_trailing_whitespace: default_true(),
+            final_newline: false,
+            max_blank_lines: None,
+        }
+    }
+}
+
+impl FormatConfig {
+    /// The layout of `command`, from `command_layout` or else `layout`
+    pub fn layout_of(&self, command: &str) -> Layout {
+        self.command_layout
+            .get(command)
+            .copied()
+            .unwrap_or(self.layout)
+    }
+
+    fn apply(&mut self, overrides: &FormatOverrides) {
+        self.command_case = overrides.command_case.or(self.command_case);
+        self.keyword_case = overrides.keyword_case.or(self.keyword_case);
+        self.layout = overrides.layout.unwrap_or(self.layout);
+        self.command_layout.extend(overrides.command_layout.clone());
+        self.trim_trailing_whitespace = overrides
+            .trim_trailing_whitespace
+            .unwrap_or(self.trim_trailing_whitespace);
+        self.final_newline = overrides.final_newline.unwrap_or(self.final_newline);
+        self.max_blank_lines = overrides.max_blank_lines.or(self.max_blank_lines);
+    }
+}
+
+/// The files of the settings of a subdirectory
+pub(crate) const DIRECTORY_CONFIG_FILES: &[&str] = &["neocmake.toml", ".neocmake.toml"];
+
+/// The settings of a `neocmake.toml` in a subdirectory of the project, for the
+/// files under it, such as relaxed rules under `third_party/`.
+#[derive(Deserialize, PartialEq, Eq, Debug, Default)]
+pub struct DirectoryConfig {
+    /// Severity of the lint rules, by id or by name.
+    #[serde(default, deserialize_with = "deserialize_rules")]
+    pub rules: HashMap<String, RuleLevel>,
+    #[serde(default)]
+    pub format: FormatOverrides,
+    /// Glob patterns of the files the scanner skips, relative to the directory.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+/// The `[format]` options a subdirectory sets, the others are inherited.
+#[derive(Deserialize, PartialEq, Eq, Debug, Default)]
+pub struct FormatOverrides {
+    pub command_case: Option<Case>,
+    pub keyword_case: Option<Case>,
+    pub layout: Option<Layout>,
+    #[serde(default)]
+    pub command_layout: HashMap<String, Layout>,
+    pub trim_trailing_whitespace: Option<bool>,
+    pub final_newline: Option<bool>,
+    pub max_blank_lines: Option<usize>,
+}
+
+/// Key is the directory, value is its settings, if it has any
+static DIRECTORY_CONFIGS: LazyLock<RwLock<HashMap<PathBuf, Option<Arc<DirectoryConfig>>>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+fn read_directory_config(dir: &Path) -> Option<Arc<DirectoryConfig>> {
+    if let Some(config) = DIRECTORY_CONFIGS
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(dir)
+    {
+        return config.clone();
+    }
+    let config = DIRECTORY_CONFIG_FILES.iter().find_map(|file| {
+        let buf = std::fs::read_to_string(dir.join(file)).ok()?;
+        match toml::from_str::<DirectoryConfig>(&buf) {
+            Ok(config) => Some(Arc::new(config)),
+            Err(err) => {
+                tracing::warn!("Invalid config file {:?}: {err}", dir.join(file));
+                None
+            }
+        }
+    });
+    DIRECTORY_CONFIGS
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(dir.to_path_buf(), config.clone());
+    config
 }
 
-fn find_config_file() -> Option<PathBuf> {
+/// Read the settings of the directory of `path` again, after a change
+pub fn forget_directory_config(path: &Path) {
+    if let Some(dir) = path.parent() {
+        DIRECTORY_CONFIGS
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(dir);
+        // The subdirectories inherit them
+        DIRECTORY_CHAINS
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+}
+
+/// Whether `name` is the file of the settings of a subdirectory
+pub fn is_directory_config(name: &str) -> bool {
+    DIRECTORY_CONFIG_FILES.contains(&name)
+}
+
+/// The directory the overrides start from: the project, or the working directory
+pub(crate) fn project_root() -> Option<PathBuf> {
+    crate::utils::module_path::source_dir().or_else(|| std::env::current_dir().ok())
+}
+
+type DirectoryChain = Arc<[(PathBuf, Arc<DirectoryConfig>)]>;
+
+/// Key is the project and a directory, value is the settings applying to it
+static DIRECTORY_CHAINS: LazyLock<RwLock<HashMap<(PathBuf, PathBuf), DirectoryChain>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// The settings of the directories from `root` down to the one of `path`, the
+/// nearest last, with their directory
+fn directory_configs_in(path: &Path, root: &Path) -> DirectoryChain {
+    let dir = if path.is_dir() {
+        path
+    } else {
+        path.parent().unwrap_or(path)
+    };
+    let key = (root.to_path_buf(), dir.to_path_buf());
+    if let Some(chain) = DIRECTORY_CHAINS
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(&key)
+    {
+        return chain.clone();
+    }
+    let mut configs: Vec<(PathBuf, Arc<DirectoryConfig>)> = dir
+        .ancestors()
+        .take_while(|ancestor| ancestor.starts_with(root))
+        .filter_map(|ancestor| Some((ancestor.to_path_buf(), read_directory_config(ancestor)?)))
+        .collect();
+    configs.reverse();
+    let chain: DirectoryChain = configs.into();
+    DIRECTORY_CHAINS
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(key, chain.clone());
+    chain
+}
+
+fn directory_configs(path: &Path) -> DirectoryChain {
+    let (Some(root), Ok(path)) = (project_root(), std::path::absolute(path)) else {
+        return Arc::new([]);
+    };
+    directory_configs_in(&path, &root)
+}
+
+/// The level of `rule`, by id or by name, set for the files of `path`, the
+/// nearest directory first, then the config
+pub fn rule_level(path: Option<&Path>, id: &str, name: &str) -> Option<RuleLevel> {
+    let configs = path.map(directory_configs).unwrap_or_default();
+    configs
+        .iter()
+        .rev()
+        .map(|(_, config)| &config.rules)
+        .chain([&CONFIG.rules])
+        .find_map(|rules| rules.get(id).or_else(|| rules.get(&name.to_uppercase())))
+        .copied()
+}
+
+/// The `[format]` options for the file at `path`, with the overrides of its
+/// directories
+pub fn format_config(path: Option<&Path>) -> FormatConfig {
+    let mut format = CONFIG.format.clone();
+    for (_, config) in path.map(directory_configs).unwrap_or_default().iter() {
+        format.apply(&config.format);
+    }
+    format
+}
+
+fn excluded_by(path: &Path, configs: &[(PathBuf, Arc<DirectoryConfig>)]) -> bool {
+    configs.iter().any(|(dir, config)| {
+        let Ok(relative) = path.strip_prefix(dir) else {
+            return false;
+        };
+        config.exclude.iter().any(|pattern| {
+            let Ok(pattern) = glob::Pattern::new(pattern.trim_end_matches('/')) else {
+                return false;
+            };
+            // The pattern of a directory excludes its content too
+            relative
+                .ancestors()
+                .filter(|part| !part.as_os_str().is_empty())
+                .any(|part| pattern.matches_path(part))
+        })
+    })
+}
+
+/// Whether a `neocmake.toml` of a parent directory excludes `path` from the scans
+pub fn is_excluded(path: &Path) -> bool {
+    let Ok(path) = std::path::absolute(path) else {
+        return false;
+    };
+    excluded_by(&path, &directory_configs(&path))
+}
+
+fn project_config_file() -> Option<PathBuf> {
     let current_dir = std::env::current_dir().ok()?;
 
     for file in [".neocmake.toml", ".neocmakelint.toml"] {
@@ -77,6 +449,10 @@ fn find_config_file() -> Option<PathBuf> {
         }
     }
 
+    None
+}
+
+fn user_config_file() -> Option<PathBuf> {
     let strategy = choose_base_strategy().ok()?;
     let config_dir = strategy.config_dir();
 
@@ -91,11 +467,23 @@ fn find_config_file() -> Option<PathBuf> {
     None
 }
 
+/// The directory of the files the server keeps between runs
+pub fn cache_dir() -> Option<PathBuf> {
+    Some(choose_base_strategy().ok()?.cache_dir().join("neocmakelsp"))
+}
+
 pub static CONFIG: LazyLock<Config> = LazyLock::new(|| {
-    if let Some(path) = find_config_file()
-        && let Ok(buf) = std::fs::read_to_string(path)
-        && let Ok(config) = toml::from_str::<Config>(&buf)
+    let project_config = project_config_file();
+    if let Some(path) = project_config.clone().or_else(user_config_file)
+        && let Ok(buf) = std::fs::read_to_string(&path)
+        && let Ok(mut config) = toml::from_str::<Config>(&buf)
     {
+        // The paths of a user config are relative to the project it is used for
+        if project_config.is_some()
+            && let Some(dir) = path.parent()
+        {
+            config.relative_to(dir);
+        }
         return config;
     }
 
@@ -173,6 +561,144 @@ mod tests {
         assert_eq!(args, Some(vec!["--hello".to_owned()]));
     }
 
+    #[test]
+    fn known_commands() {
+        let config_file = indoc::indoc! {r#"
+            [[known_commands]]
+            name = "catch_discover_tests"
+            signatures = ["catch_discover_tests(<target> [TEST_SPEC <spec>])"]
+            description = "Discover Catch2 tests"
+
+            [[known_commands]]
+            name = "sdk_add_module"
+        "#};
+        let config: Config = toml::from_str(config_file).unwrap();
+        assert_eq!(config.known_commands.len(), 2);
+        assert_eq!(
+            config.known_commands[0].documentation(),
+            "catch_discover_tests(<target> [TEST_SPEC <spec>])\n\nDiscover Catch2 tests"
+        );
+        assert!(config.known_commands[1].signatures.is_empty());
+        assert_eq!(config.known_commands[1].documentation(), "");
+    }
+
+    #[test]
+    fn source_extensions() {
+        let config_file = indoc::indoc! {r#"
+            source_extensions = [".metal", "proto", "."]
+        "#};
+        let config: Config = toml::from_str(config_file).unwrap();
+        assert_eq!(
+            config.source_extensions().collect::<Vec<_>>(),
+            vec!["metal", "proto"]
+        );
+    }
+
+    #[test]
+    fn rule_levels() {
+        let config_file = indoc::indoc! {r#"
+            [rules]
+            n401 = "off"
+            missing-path = "warn"
+        "#};
+        let config: Config = toml::from_str(config_file).unwrap();
+        assert_eq!(config.rules.get("N401"), Some(&RuleLevel::Off));
+        assert_eq!(config.rules.get("MISSING-PATH"), Some(&RuleLevel::Warning));
+    }
+
+    #[test]
+    fn baseline_relative_to_config() {
+        let mut config: Config = toml::from_str(r#"baseline = "lint/baseline.json""#).unwrap();
+        config.relative_to(Path::new("/project"));
+        assert_eq!(
+            config.baseline,
+            Some(PathBuf::from("/project/lint/baseline.json"))
+        );
+        let mut config: Config = toml::from_str(r#"baseline = "/tmp/baseline.json""#).unwrap();
+        config.relative_to(Path::new("/project"));
+        assert_eq!(config.baseline, Some(PathBuf::from("/tmp/baseline.json")));
+    }
+
+    #[test]
+    fn directory_overrides() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        assert!(directory_configs_in(&root.join("CMakeLists.txt"), root).is_empty());
+        std::fs::write(
+            root.join("neocmake.toml"),
+            indoc::indoc! {r#"
+                [rules]
+                N402 = "off"
+            "#},
+        )
+        .unwrap();
+        // The settings are cached until the watcher reports a change
+        forget_directory_config(&root.join("neocmake.toml"));
+        let vendored = root.join("third_party").join("zlib");
+        std::fs::create_dir_all(&vendored).unwrap();
+        std::fs::write(
+            root.join("third_party").join("neocmake.toml"),
+            indoc::indoc! {r#"
+                exclude = ["*/tests/", "*.in"]
+
+                [rules]
+                N401 = "off"
+
+                [format]
+                layout = "preserve"
+            "#},
+        )
+        .unwrap();
+        std::fs::write(
+            vendored.join(".neocmake.toml"),
+            indoc::indoc! {r#"
+                [rules]
+                command-case = "warn"
+
+                [format]
+                max_blank_lines = 1
+            "#},
+        )
+        .unwrap();
+
+        let file = vendored.join("CMakeLists.txt");
+        let configs = directory_configs_in(&file, root);
+        assert_eq!(configs.len(), 3);
+        assert_eq!(configs[0].0, root);
+        assert_eq!(configs[1].0, root.join("third_party"));
+        let levels: Vec<Option<&RuleLevel>> = configs
+            .iter()
+            .map(|(_, config)| {
+                config
+                    .rules
+                    .get("N401")
+                    .or(config.rules.get("COMMAND-CASE"))
+            })
+            .collect();
+        assert_eq!(
+            levels,
+            vec![None, Some(&RuleLevel::Off), Some(&RuleLevel::Warning)]
+        );
+
+        let mut format = FormatConfig::default();
+        for (_, config) in configs.iter() {
+            format.apply(&config.format);
+        }
+        assert_eq!(format.layout, Layout::Preserve);
+        assert_eq!(format.max_blank_lines, Some(1));
+        assert!(format.trim_trailing_whitespace);
+
+        assert!(excluded_by(
+            &vendored.join("tests").join("CMakeLists.txt"),
+            &configs
+        ));
+        assert!(excluded_by(&vendored.join("config.h.in"), &configs));
+        assert!(!excluded_by(&file, &configs));
+        let configs = directory_configs_in(&root.join("CMakeLists.txt"), root);
+        assert_eq!(configs.len(), 1);
+        assert_eq!(configs[0].1.rules.get("N402"), Some(&RuleLevel::Off));
+    }
+
     #[test]
     fn check_lower_case_word() {
         assert_eq!(CommandCase::Lower.check("add_executable"), None);
@@ -0,0 +1,332 @@
+//! Support for `configure_file()` templates such as `config.h.in`.
+//!
+//! Templates are not CMake code, so the usual handlers do not apply to them.
+//! Instead `@VAR@`, `${VAR}` and `#cmakedefine VAR` placeholders are resolved
+//! against the variables of the CMakeLists.txt that configures the template.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex, PoisonError};
+
+use dashmap::DashMap;
+use tower_lsp::lsp_types::{
+    CompletionItem, CompletionResponse, GotoDefinitionResponse, Hover, HoverContents, Location,
+    MarkedString, Position, Range, Uri,
+};
+use tree_sitter::Node;
+
+use crate::complete::builtin::BUILTIN_VARIABLE;
+use crate::complete::{self, variable_completions_closed_with};
+use crate::languageserver::{get_or_update_buffer_contents, to_use_snippet};
+use crate::utils::document::Document;
+use crate::utils::module_path;
+use crate::{CMakeNodeKinds, hover, jump};
+
+/// Key is the template, value is the CMakeLists.txt configuring it
+static TEMPLATES: LazyLock<Mutex<HashMap<PathBuf, PathBuf>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+const NAME_PATTERN: &str = r"[A-Za-z0-9_./+-]+";
+
+static PLACEHOLDER_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(&format!(
+        r"\$\{{({NAME_PATTERN})\}}|@({NAME_PATTERN})@|^\s*#\s*cmakedefine(?:01)?\s+({NAME_PATTERN})"
+    ))
+    .unwrap()
+});
+
+static UNCLOSED_PLACEHOLDER_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(r"(?:\$\{|@|^\s*#\s*cmakedefine(?:01)?\s+)([A-Za-z0-9_./+-]*)$").unwrap()
+});
+
+fn update_templates_inner(
+    source: &[&str],
+    node: Node,
+    current_dir: &Path,
+    output: &mut Vec<PathBuf>,
+) {
+    let mut course = node.walk();
+    for child in node.children(&mut course) {
+        match child.kind() {
+            CMakeNodeKinds::NORMAL_COMMAND => {
+                let Some(ids) = child.child(0) else {
+                    continue;
+                };
+                let h = ids.start_position().row;
+                let x = ids.start_position().column;
+                let y = ids.end_position().column;
+                if source[h][x..y].to_lowercase() != "configure_file" {
+                    continue;
+                }
+                let Some(first_arg) = child.child(2).and_then(|arguments| arguments.child(0))
+                else {
+                    continue;
+                };
+                if first_arg.start_position().row != first_arg.end_position().row {
+                    continue;
+                }
+                let h = first_arg.start_position().row;
+                let x = first_arg.start_position().column;
+                let y = first_arg.end_position().column;
+                if let Some(template) = module_path::resolve_path(&source[h][x..y], current_dir) {
+                    output.push(template);
+                }
+            }
+            CMakeNodeKinds::IF_CONDITION
+            | CMakeNodeKinds::FOREACH_LOOP
+            | CMakeNodeKinds::WHILE_LOOP
+            | CMakeNodeKinds::BLOCK_DEF
+            | CMakeNodeKinds::BODY => {
+                update_templates_inner(source, child, current_dir, output);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The templates configured by `local_path`
+pub fn configured_templates<P: AsRef<Path>>(
+    local_path: P,
+    source: &[&str],
+    root: Node,
+) -> Vec<PathBuf> {
+    let Some(current_dir) = local_path.as_ref().parent() else {
+        return Vec::new();
+    };
+    let mut templates = Vec::new();
+    update_templates_inner(source, root, current_dir, &mut templates);
+    templates
+}
+
+/// Record the `configure_file()` templates of `local_path`
+pub fn update_templates<P: AsRef<Path>>(local_path: P, source: &[&str], root: Node) {
+    let local_path = local_path.as_ref();
+    let templates = configured_templates(local_path, source, root);
+    let mut map = TEMPLATES.lock().unwrap_or_else(PoisonError::into_inner);
+    map.retain(|_, owner| owner != local_path);
+    for template in templates {
+        map.insert(template, local_path.to_path_buf());
+    }
+}
+
+/// Whether `path` is a template, like `config.h.in` or `FooConfig.cmake.in`
+pub fn is_template<P: AsRef<Path>>(path: P) -> bool {
+    let path = path.as_ref();
+    path.extension().is_some_and(|extension| extension == "in")
+        || TEMPLATES
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .contains_key(path)
+}
+
+/// The CMakeLists.txt configuring `template`, or the closest one above it
+fn owner_of(template: &Path) -> Option<PathBuf> {
+    if let Some(owner) = TEMPLATES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(template)
+    {
+        return Some(owner.clone());
+    }
+    template
+        .ancestors()
+        .skip(1)
+        .map(|dir| dir.join("CMakeLists.txt"))
+        .find(|path| path.is_file())
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Placeholder<'a> {
+    start: usize,
+    end: usize,
+    name: &'a str,
+}
+
+fn placeholder_at(line: &str, column: usize) -> Option<Placeholder<'_>> {
+    PLACEHOLDER_REGEX.captures_iter(line).find_map(|caps| {
+        // `#cmakedefine NAME` only covers the name, `@NAME@` and `${NAME}` all of it
+        let (range, name) = match caps.get(3) {
+            Some(name) => (name.range(), name),
+            None => (caps.get(0)?.range(), caps.get(1).or(caps.get(2))?),
+        };
+        if !range.contains(&column) {
+            return None;
+        }
+        Some(Placeholder {
+            start: range.start,
+            end: range.end,
+            name: name.as_str(),
+        })
+    })
+}
+
+/// If the cursor is after an unclosed `@`, `${` or `#cmakedefine `,
+/// return the column where the name starts and the text closing it
+fn unclosed_placeholder(line: &str, column: usize) -> Option<(usize, Option<&'static str>)> {
+    let before_cursor = line.get(..column)?;
+    let caps = UNCLOSED_PLACEHOLDER_REGEX.captures(before_cursor)?;
+    let whole = caps.get(0)?.as_str();
+    let closing = if whole.starts_with('@') {
+        Some("@")
+    } else if whole.starts_with("${") {
+        Some("}")
+    } else {
+        None
+    };
+    Some((caps.get(1)?.start(), closing))
+}
+
+/// Parse the owner so its variables are cached, and return them
+async fn owner_variables(owner: &Path, documents: &DashMap<Uri, Document>) -> Vec<CompletionItem> {
+    let Ok(context) = get_or_update_buffer_contents(owner, documents).await else {
+        return Vec::new();
+    };
+    jump::update_cache(owner, &context).await;
+    let mut items = complete::update_cache(owner, &context).await;
+    items.append(&mut complete::get_cached_completion(owner, documents).await);
+    items
+}
+
+pub async fn complete<P: AsRef<Path>>(
+    template: P,
+    source: &str,
+    location: Position,
+    documents: &DashMap<Uri, Document>,
+) -> Option<CompletionResponse> {
+    let line = source.lines().nth(location.line as usize).unwrap_or("");
+    let (name_start, closing) = unclosed_placeholder(line, location.character as usize)?;
+    let mut items = match owner_of(template.as_ref()) {
+        Some(owner) => owner_variables(&owner, documents).await,
+        None => Vec::new(),
+    };
+    if let Ok(messages) = &*BUILTIN_VARIABLE {
+        items.append(&mut messages.clone());
+    }
+    let after_cursor = line.get(location.character as usize..).unwrap_or("");
+    let closing = closing.filter(|closing| to_use_snippet() && !after_cursor.starts_with(closing));
+    let replace_range = Range {
+        start: Position {
+            line: location.line,
+            character: name_start as u32,
+        },
+        end: location,
+    };
+    let items = variable_completions_closed_with(items, replace_range, closing);
+    if items.is_empty() {
+        return None;
+    }
+    Some(CompletionResponse::Array(items))
+}
+
+pub async fn hover<P: AsRef<Path>>(
+    template: P,
+    source: &str,
+    location: Position,
+    documents: &DashMap<Uri, Document>,
+) -> Option<Hover> {
+    let line = source.lines().nth(location.line as usize)?;
+    let placeholder = placeholder_at(line, location.character as usize)?;
+    if let Some(owner) = owner_of(template.as_ref()) {
+        owner_variables(&owner, documents).await;
+    }
+    let doc = hover::get_variable_doc(placeholder.name).await?;
+    Some(Hover {
+        contents: HoverContents::Scalar(MarkedString::String(doc)),
+        range: Some(Range {
+            start: Position {
+                line: location.line,
+                character: placeholder.start as u32,
+            },
+            end: Position {
+                line: location.line,
+                character: placeholder.end as u32,
+            },
+        }),
+    })
+}
+
+pub async fn goto_definition<P: AsRef<Path>>(
+    template: P,
+    source: &str,
+    location: Position,
+    documents: &DashMap<Uri, Document>,
+) -> Option<GotoDefinitionResponse> {
+    let line = source.lines().nth(location.line as usize)?;
+    let placeholder = placeholder_at(line, location.character as usize)?;
+    let owner = owner_of(template.as_ref())?;
+    let definition: Location = jump::find_cached_def(&owner, placeholder.name, documents).await?;
+    Some(GotoDefinitionResponse::Scalar(definition))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    #[test]
+    fn test_placeholder_at() {
+        let line = "#define VERSION \"@PROJECT_VERSION@\" ${OTHER}";
+        assert_eq!(
+            placeholder_at(line, 20),
+            Some(Placeholder {
+                start: 17,
+                end: 34,
+                name: "PROJECT_VERSION"
+            })
+        );
+        assert_eq!(placeholder_at(line, 40).map(|p| p.name), Some("OTHER"));
+        assert_eq!(placeholder_at(line, 3), None);
+        assert_eq!(
+            placeholder_at("#cmakedefine01 HAVE_UNISTD_H", 20).map(|p| p.name),
+            Some("HAVE_UNISTD_H")
+        );
+        assert_eq!(placeholder_at("user@example.com", 5), None);
+    }
+
+    #[test]
+    fn test_unclosed_placeholder() {
+        assert_eq!(
+            unclosed_placeholder("#define A @PROJ", 15),
+            Some((11, Some("@")))
+        );
+        assert_eq!(
+            unclosed_placeholder("#define A ${PROJ", 16),
+            Some((12, Some("}")))
+        );
+        assert_eq!(
+            unclosed_placeholder("#cmakedefine HAVE", 17),
+            Some((13, None))
+        );
+        assert_eq!(unclosed_placeholder("#define A @PROJ@ ", 17), None);
+        assert_eq!(unclosed_placeholder("#define A", 9), None);
+    }
+
+    #[test]
+    fn test_configured_templates() {
+        let source = r#"configure_file(config.h.in "${CMAKE_CURRENT_BINARY_DIR}/config.h")
+if(UNIX)
+    configure_file("${CMAKE_CURRENT_SOURCE_DIR}/cmake/FooConfig.cmake.in" FooConfig.cmake @ONLY)
+endif()
+"#;
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let templates =
+            configured_templates("/project/CMakeLists.txt", &lines, thetree.root_node());
+        assert_eq!(
+            templates,
+            vec![
+                PathBuf::from("/project/config.h.in"),
+                PathBuf::from("/project/cmake/FooConfig.cmake.in"),
+            ]
+        );
+
+        update_templates("/project/CMakeLists.txt", &lines, thetree.root_node());
+        assert!(is_template("/project/cmake/FooConfig.cmake.in"));
+        assert_eq!(
+            owner_of(Path::new("/project/config.h.in")),
+            Some(PathBuf::from("/project/CMakeLists.txt"))
+        );
+    }
+}
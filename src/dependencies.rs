@@ -0,0 +1,297 @@
+//! The external dependencies of the project, for the `neocmake/dependencies`
+//! request: the packages of `find_package()`, the modules of
+//! `pkg_check_modules()` and `pkg_search_module()`, and the sources downloaded by
+//! `FetchContent_Declare()` and `ExternalProject_Add()`.
+//!
+//! The versions, URLs and hashes are the text written in the calls, variables
+//! are not expanded.
+
+use std::path::Path;
+use std::sync::LazyLock;
+
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::{Location, Range, Uri};
+use tree_sitter::Node;
+
+use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+use crate::utils::treehelper::ToPosition;
+use crate::{CMakeNodeKinds, lint};
+
+/// The custom request listing the dependencies of the project
+pub const DEPENDENCIES_METHOD: &str = "neocmake/dependencies";
+
+const PKG_CONFIG_COMMANDS: &[&str] = &["pkg_check_modules", "pkg_search_module"];
+
+const DOWNLOAD_COMMANDS: &[&str] = &["fetchcontent_declare", "externalproject_add"];
+
+/// The options of the pkg-config commands, before the modules
+const PKG_CONFIG_OPTIONS: &[&str] = &[
+    "REQUIRED",
+    "QUIET",
+    "NO_CMAKE_PATH",
+    "NO_CMAKE_ENVIRONMENT_PATH",
+    "IMPORTED_TARGET",
+    "GLOBAL",
+];
+
+/// The options giving the revision of a download
+const REVISION_OPTIONS: &[&str] = &["GIT_TAG", "SVN_REVISION", "HG_TAG"];
+
+/// The options giving the location of a download
+const SOURCE_OPTIONS: &[&str] = &["GIT_REPOSITORY", "URL", "SVN_REPOSITORY", "HG_REPOSITORY"];
+
+/// The options giving the expected hash of a `URL` download
+const HASH_OPTIONS: &[&str] = &["URL_HASH", "URL_MD5"];
+
+/// An option of the commands, ending the values of the previous one
+static OPTION_REGEX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"^[A-Z][A-Z0-9_]*$").unwrap());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DependencyKind {
+    FindPackage,
+    PkgConfig,
+    FetchContent,
+    ExternalProject,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Dependency {
+    pub kind: DependencyKind,
+    pub name: String,
+    /// The version required, or the revision downloaded, like `3.2...<4`,
+    /// `>=2.10` or `v1.14.0`
+    pub version: Option<String>,
+    /// The URL or repository downloaded from
+    pub source: Option<String>,
+    /// The expected hash of a `URL` download, like `SHA256=...`
+    pub hash: Option<String>,
+    /// The name of the dependency in the call
+    pub location: Location,
+}
+
+struct Argument<'a> {
+    node: Node<'a>,
+    text: &'a str,
+}
+
+fn arguments<'a>(command: Node<'a>, source: &'a str) -> Vec<Argument<'a>> {
+    let Some(argument_list) = command
+        .child(2)
+        .filter(|node| node.kind() == CMakeNodeKinds::ARGUMENT_LIST)
+    else {
+        return vec![];
+    };
+    let mut walk = argument_list.walk();
+    argument_list
+        .children(&mut walk)
+        .filter(|node| node.kind() == CMakeNodeKinds::ARGUMENT)
+        .map(|node| Argument {
+            node,
+            text: source[node.byte_range()].trim_matches('"'),
+        })
+        .collect()
+}
+
+/// The first value given after one of `options`
+fn value<'a>(arguments: &[Argument<'a>], options: &[&str]) -> Option<&'a str> {
+    let index = arguments
+        .iter()
+        .position(|argument| options.contains(&argument.text))?;
+    arguments
+        .get(index + 1)
+        .filter(|argument| !OPTION_REGEX.is_match(argument.text))
+        .map(|argument| argument.text)
+}
+
+/// A pkg-config module with its version, like `glib-2.0>=2.10`
+fn split_module(module: &str) -> (&str, Option<&str>) {
+    match module.find(['<', '>', '=']) {
+        Some(index) => (&module[..index], Some(&module[index..])),
+        None => (module, None),
+    }
+}
+
+fn dependency_at(
+    uri: &Uri,
+    kind: DependencyKind,
+    name: &Argument,
+    name_text: &str,
+    version: Option<&str>,
+) -> Dependency {
+    Dependency {
+        kind,
+        name: name_text.to_string(),
+        version: version.map(str::to_string),
+        source: None,
+        hash: None,
+        location: Location {
+            uri: uri.clone(),
+            range: Range::new(
+                name.node.start_position().to_position(),
+                name.node.end_position().to_position(),
+            ),
+        },
+    }
+}
+
+fn command_dependencies(command: Node, source: &str, uri: &Uri, output: &mut Vec<Dependency>) {
+    let Some(name) = command.child(0) else {
+        return;
+    };
+    let name = source[name.byte_range()].to_lowercase();
+    let arguments = arguments(command, source);
+    let Some(first) = arguments.first() else {
+        return;
+    };
+    if name == "find_package" {
+        let version = arguments
+            .get(1)
+            .map(|argument| argument.text)
+            .filter(|text| text.starts_with(|c: char| c.is_ascii_digit()));
+        output.push(dependency_at(
+            uri,
+            DependencyKind::FindPackage,
+            first,
+            first.text,
+            version,
+        ));
+    } else if PKG_CONFIG_COMMANDS.contains(&name.as_str()) {
+        for module in arguments
+            .iter()
+            .skip(1)
+            .filter(|argument| !PKG_CONFIG_OPTIONS.contains(&argument.text))
+        {
+            let (module_name, version) = split_module(module.text);
+            output.push(dependency_at(
+                uri,
+                DependencyKind::PkgConfig,
+                module,
+                module_name,
+                version,
+            ));
+        }
+    } else if DOWNLOAD_COMMANDS.contains(&name.as_str()) {
+        let kind = if name == "fetchcontent_declare" {
+            DependencyKind::FetchContent
+        } else {
+            DependencyKind::ExternalProject
+        };
+        let mut dependency = dependency_at(
+            uri,
+            kind,
+            first,
+            first.text,
+            value(&arguments, REVISION_OPTIONS),
+        );
+        dependency.source = value(&arguments, SOURCE_OPTIONS).map(str::to_string);
+        dependency.hash = value(&arguments, HASH_OPTIONS).map(str::to_string);
+        output.push(dependency);
+    }
+}
+
+fn dependencies_inner(node: Node, source: &str, uri: &Uri, output: &mut Vec<Dependency>) {
+    let mut course = node.walk();
+    for child in node.children(&mut course) {
+        if child.kind() == CMakeNodeKinds::NORMAL_COMMAND {
+            command_dependencies(child, source, uri, output);
+        } else {
+            dependencies_inner(child, source, uri, output);
+        }
+    }
+}
+
+/// The dependencies of the file at `uri`, in the order they are written
+pub fn file_dependencies(uri: &Uri, source: &str) -> Vec<Dependency> {
+    let mut parse = tree_sitter::Parser::new();
+    parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+    let Some(tree) = parse.parse(source, None) else {
+        return vec![];
+    };
+    let mut output = vec![];
+    dependencies_inner(tree.root_node(), source, uri, &mut output);
+    output
+}
+
+/// The dependencies of the CMake files under `root`
+pub fn dependencies(root: &Path) -> Vec<Dependency> {
+    lint::collect_files(&[root.to_path_buf()])
+        .into_iter()
+        .filter_map(|file| {
+            let source = std::fs::read_to_string(&file).ok()?;
+            let uri = Uri::from_file_path(&file).ok()?;
+            Some(file_dependencies(&uri, &source))
+        })
+        .flatten()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::Position;
+
+    use super::*;
+
+    #[test]
+    fn test_dependencies() {
+        let source = r#"find_package(fmt 10.2 CONFIG REQUIRED)
+find_package(Threads)
+pkg_check_modules(DEPS REQUIRED IMPORTED_TARGET glib-2.0>=2.10 "libpng")
+if(WITH_TESTS)
+  FetchContent_Declare(
+    googletest
+    GIT_REPOSITORY https://github.com/google/googletest.git
+    GIT_TAG v1.14.0
+  )
+endif()
+ExternalProject_Add(zlib
+  URL https://zlib.net/zlib-1.3.1.tar.gz
+  URL_HASH SHA256=9a93b2b7dfdac77ceba5a558a580e74667dd6fede4585b91eefb60f03b72df23
+)
+"#;
+        let uri = Uri::from_file_path("/tmp/CMakeLists.txt").unwrap();
+        let dependencies = file_dependencies(&uri, source);
+        let summary: Vec<(DependencyKind, &str, Option<&str>, Option<&str>)> = dependencies
+            .iter()
+            .map(|dependency| {
+                (
+                    dependency.kind,
+                    dependency.name.as_str(),
+                    dependency.version.as_deref(),
+                    dependency.source.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (DependencyKind::FindPackage, "fmt", Some("10.2"), None),
+                (DependencyKind::FindPackage, "Threads", None, None),
+                (DependencyKind::PkgConfig, "glib-2.0", Some(">=2.10"), None),
+                (DependencyKind::PkgConfig, "libpng", None, None),
+                (
+                    DependencyKind::FetchContent,
+                    "googletest",
+                    Some("v1.14.0"),
+                    Some("https://github.com/google/googletest.git")
+                ),
+                (
+                    DependencyKind::ExternalProject,
+                    "zlib",
+                    None,
+                    Some("https://zlib.net/zlib-1.3.1.tar.gz")
+                ),
+            ]
+        );
+        assert_eq!(
+            dependencies[5].hash.as_deref(),
+            Some("SHA256=9a93b2b7dfdac77ceba5a558a580e74667dd6fede4585b91eefb60f03b72df23")
+        );
+        assert_eq!(
+            dependencies[4].location.range,
+            Range::new(Position::new(5, 4), Position::new(5, 14))
+        );
+    }
+}
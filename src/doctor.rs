@@ -0,0 +1,268 @@
+//! The `doctor` subcommand: checks what the server needs from the machine, and
+//! says how to fix what is missing.
+
+use std::fmt;
+use std::path::Path;
+use std::process::Command;
+
+use crate::config::cache_dir;
+use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+/// The oldest cmake whose help pages the completion and the hover are read from
+const MINIMUM_CMAKE_VERSION: (u32, u32) = (3, 10);
+
+/// Below this, watching a few workspaces exhausts the inotify watches
+const MINIMUM_INOTIFY_WATCHES: u64 = 8192;
+
+const INOTIFY_WATCHES: &str = "/proc/sys/fs/inotify/max_user_watches";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Ok => "ok",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        })
+    }
+}
+
+/// The result of a check, with how to fix it when it is not ok
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub detail: String,
+    pub remedy: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, detail: String) -> Self {
+        Self {
+            name,
+            status: Status::Ok,
+            detail,
+            remedy: None,
+        }
+    }
+
+    fn problem(name: &'static str, status: Status, detail: String, remedy: String) -> Self {
+        Self {
+            name,
+            status,
+            detail,
+            remedy: Some(remedy),
+        }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}: {}", self.status, self.name, self.detail)?;
+        if let Some(remedy) = &self.remedy {
+            write!(f, "\n    -> {remedy}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The check of the output of `cmake --version`, none when it could not run
+fn check_cmake_output(output: Option<&str>) -> Check {
+    const NAME: &str = "cmake";
+    let Some(output) = output else {
+        return Check::problem(
+            NAME,
+            Status::Error,
+            "no cmake found in PATH".to_string(),
+            "install cmake and add the directory of the `cmake` binary to PATH, without it the \
+             builtin commands, variables and modules are neither completed nor documented"
+                .to_string(),
+        );
+    };
+    let first_line = output.lines().next().unwrap_or_default().trim();
+    let version = first_line
+        .strip_prefix("cmake version ")
+        .and_then(|version| {
+            let mut parts = version.split(['.', '-']);
+            let major: u32 = parts.next()?.parse().ok()?;
+            let minor: u32 = parts.next()?.parse().ok()?;
+            Some((major, minor))
+        });
+    match version {
+        Some(version) if version >= MINIMUM_CMAKE_VERSION => {
+            Check::ok(NAME, first_line.to_string())
+        }
+        Some(_) => Check::problem(
+            NAME,
+            Status::Warning,
+            first_line.to_string(),
+            format!(
+                "cmake {}.{} or newer is needed for complete help pages, upgrade cmake",
+                MINIMUM_CMAKE_VERSION.0, MINIMUM_CMAKE_VERSION.1
+            ),
+        ),
+        None => Check::problem(
+            NAME,
+            Status::Error,
+            format!("unexpected output of `cmake --version`: {first_line:?}"),
+            "check that the `cmake` in PATH is cmake and runs, with `cmake --version`".to_string(),
+        ),
+    }
+}
+
+fn check_cmake() -> Check {
+    let output = Command::new("cmake")
+        .arg("--version")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).into_owned());
+    check_cmake_output(output.as_deref())
+}
+
+fn check_grammar() -> Check {
+    const NAME: &str = "tree-sitter grammar";
+    let mut parser = tree_sitter::Parser::new();
+    if let Err(error) = parser.set_language(&TREESITTER_CMAKE_LANGUAGE) {
+        return Check::problem(
+            NAME,
+            Status::Error,
+            error.to_string(),
+            "the grammar does not match the tree-sitter library, rebuild neocmakelsp".to_string(),
+        );
+    }
+    match parser.parse("project(demo)\nset(A ${B})\n", None) {
+        Some(tree) if !tree.root_node().has_error() => {
+            Check::ok(NAME, "the CMake grammar parses".to_string())
+        }
+        _ => Check::problem(
+            NAME,
+            Status::Error,
+            "the CMake grammar fails to parse a valid file".to_string(),
+            "rebuild neocmakelsp, or report the issue with its version".to_string(),
+        ),
+    }
+}
+
+/// The check of the content of `max_user_watches`, none when it is not readable
+fn check_inotify_limit(content: Option<&str>) -> Check {
+    const NAME: &str = "inotify watches";
+    let Some(limit) = content.and_then(|content| content.trim().parse::<u64>().ok()) else {
+        return Check::problem(
+            NAME,
+            Status::Warning,
+            format!("cannot read {INOTIFY_WATCHES}"),
+            "the new and removed files may be missed by the completion of paths".to_string(),
+        );
+    };
+    if limit < MINIMUM_INOTIFY_WATCHES {
+        return Check::problem(
+            NAME,
+            Status::Warning,
+            format!("the limit is {limit}"),
+            "raise it with `sudo sysctl fs.inotify.max_user_watches=524288`, and add \
+             `fs.inotify.max_user_watches=524288` to a file of /etc/sysctl.d to keep it"
+                .to_string(),
+        );
+    }
+    Check::ok(NAME, format!("the limit is {limit}"))
+}
+
+/// The check that files can be written in `dir`, created if needed
+fn check_cache_dir_in(dir: Option<&Path>) -> Check {
+    const NAME: &str = "cache directory";
+    let Some(dir) = dir else {
+        return Check::problem(
+            NAME,
+            Status::Warning,
+            "no home directory".to_string(),
+            "set HOME, or XDG_CACHE_HOME, for the user running the server".to_string(),
+        );
+    };
+    let writable = std::fs::create_dir_all(dir).and_then(|_| tempfile::tempfile_in(dir));
+    match writable {
+        Ok(_) => Check::ok(NAME, format!("{} is writable", dir.display())),
+        Err(error) => Check::problem(
+            NAME,
+            Status::Error,
+            format!("cannot write in {}: {error}", dir.display()),
+            format!(
+                "give the user write access to {}, or point XDG_CACHE_HOME to a writable directory",
+                dir.display()
+            ),
+        ),
+    }
+}
+
+/// Every check, in the order they are printed
+pub fn checks() -> Vec<Check> {
+    let mut checks = vec![check_cmake(), check_grammar()];
+    if cfg!(target_os = "linux") {
+        let content = std::fs::read_to_string(INOTIFY_WATCHES).ok();
+        checks.push(check_inotify_limit(content.as_deref()));
+    }
+    checks.push(check_cache_dir_in(cache_dir().as_deref()));
+    checks
+}
+
+/// Print the checks, returns whether one of them failed
+pub fn doctor() -> bool {
+    let checks = checks();
+    for check in &checks {
+        println!("{check}");
+    }
+    checks.iter().any(|check| check.status == Status::Error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_cmake() {
+        let check = check_cmake_output(Some("cmake version 3.28.3\n\nCMake suite maintained"));
+        assert_eq!(check.status, Status::Ok);
+        assert_eq!(check.detail, "cmake version 3.28.3");
+        assert_eq!(
+            check_cmake_output(Some("cmake version 2.8.12\n")).status,
+            Status::Warning
+        );
+        assert_eq!(check_cmake_output(Some("bash: oops")).status, Status::Error);
+        let missing = check_cmake_output(None);
+        assert_eq!(missing.status, Status::Error);
+        assert!(missing.remedy.unwrap().contains("PATH"));
+    }
+
+    #[test]
+    fn test_check_inotify_limit() {
+        assert_eq!(check_inotify_limit(Some("524288\n")).status, Status::Ok);
+        let low = check_inotify_limit(Some("1024\n"));
+        assert_eq!(low.status, Status::Warning);
+        assert!(low.remedy.unwrap().contains("sysctl"));
+        assert_eq!(check_inotify_limit(None).status, Status::Warning);
+    }
+
+    #[test]
+    fn test_check_cache_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("neocmakelsp");
+        assert_eq!(check_cache_dir_in(Some(&cache)).status, Status::Ok);
+        assert!(cache.is_dir());
+        let file = dir.path().join("file");
+        std::fs::write(&file, "").unwrap();
+        let check = check_cache_dir_in(Some(&file.join("neocmakelsp")));
+        assert_eq!(check.status, Status::Error);
+        assert!(check.remedy.is_some());
+    }
+
+    #[test]
+    fn test_check_grammar() {
+        assert_eq!(check_grammar().status, Status::Ok);
+    }
+}
@@ -1,11 +1,9 @@
-use std::path::{Path, PathBuf};
+use std::path::Path;
 
 use tower_lsp::lsp_types::{DocumentLink, Position, Range};
 
 use crate::consts::TREESITTER_CMAKE_LANGUAGE;
-use crate::utils::{
-    gen_module_pattern, include_is_module, remove_quotation_and_replace_placeholders,
-};
+use crate::utils::{remove_quotation_and_replace_placeholders, resolve_include};
 use crate::{CMakeNodeKinds, Uri};
 
 const LINK_NODE_KIND: &[&str] = &["include", "add_subdirectory"];
@@ -122,8 +120,7 @@ pub fn document_link_search_inner<P: AsRef<Path>>(
                         false,
                     )
                 } else {
-                    let Some((cmake_path, builtin)) =
-                        convert_include_cmake(&filename, current_parent)
+                    let Some((cmake_path, builtin)) = resolve_include(&filename, current_parent)
                     else {
                         continue;
                     };
@@ -159,21 +156,6 @@ pub fn document_link_search_inner<P: AsRef<Path>>(
     }
 }
 
-fn convert_include_cmake<P: AsRef<Path>>(name: &str, current_parent: P) -> Option<(PathBuf, bool)> {
-    if !include_is_module(name) {
-        return Some((current_parent.as_ref().join(name), false));
-    }
-    let global_pattern = gen_module_pattern(name)?;
-    Some((
-        glob::glob(&global_pattern)
-            .into_iter()
-            .flatten()
-            .flatten()
-            .next()?,
-        true,
-    ))
-}
-
 // FIXME: unit test failed on windows
 // thread 'document_link::test_document_link_search' panicked at src\document_link.rs:156:67:
 // called `Result::unwrap()` on an `Err` value: Error("invalid escape", line: 16, column: 27)
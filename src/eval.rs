@@ -0,0 +1,695 @@
+//! Static evaluation of a command, for the `neocmake/evalCommand` request.
+//!
+//! The `set()`, `list(APPEND)`, `option()` and `project()` calls written before
+//! the command are replayed in order, and the variables still unknown are looked
+//! up in the CMake cache. The branches of an `if()` are replayed each on their
+//! own, the variables they set differently depend on its conditions, unless the
+//! command is in one of them. The result is what the command most likely
+//! receives, not what cmake would compute.
+//!
+//! The same order tells the lints which variables are read before the file
+//! sets them.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::LazyLock;
+
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::{Position, Range};
+use tree_sitter::Node;
+
+use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+use crate::utils::module_path;
+use crate::utils::treehelper::{ToPoint, ToPosition, get_enclosing_nodes};
+use crate::{CMakeNodeKinds, fileapi};
+
+/// The custom request evaluating the command at a position
+pub const EVAL_COMMAND_METHOD: &str = "neocmake/evalCommand";
+
+/// A `${NAME}` without another reference inside
+static VARIABLE_REF_REGEX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"\$\{([^${}]*)\}").unwrap());
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EvaluatedCommand {
+    /// The range of the command in the document
+    pub range: Range,
+    /// The invocation with its variables expanded, like `target_sources(app PRIVATE a.c b.c)`
+    pub text: String,
+    /// The arguments the command receives, once the lists are split
+    pub arguments: Vec<String>,
+    /// The variables which could not be resolved, left as `${NAME}`
+    pub unresolved: Vec<String>,
+    /// The variables set differently by the branches of an `if()`, left as `${NAME}`
+    pub conditional: Vec<ConditionalVariable>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConditionalVariable {
+    pub name: String,
+    /// The conditions of the `if()` and `elseif()` its value depends on, like `WIN32`
+    pub conditions: Vec<String>,
+}
+
+type Variables = HashMap<String, Vec<String>>;
+
+/// The variables once the commands before the evaluated one are replayed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct State {
+    variables: Variables,
+    /// The variables set differently by the branches of an `if()`, or only by
+    /// some of them, with the conditions choosing the branch
+    conditional: HashMap<String, Vec<String>>,
+}
+
+/// The references an expansion leaves as `${NAME}`
+#[derive(Debug, Default)]
+struct Left {
+    unresolved: Vec<String>,
+    conditional: Vec<String>,
+}
+
+fn push_new(list: &mut Vec<String>, item: &str) {
+    if !list.iter().any(|known| known == item) {
+        list.push(item.to_string());
+    }
+}
+
+pub fn node_text<'a>(node: Node, source: &'a str) -> &'a str {
+    &source[node.byte_range()]
+}
+
+/// Expand the references of `text`, innermost first, keeping the unknown ones
+/// and the ones depending on a branch. A variable whose value refers to other
+/// variables is expanded once, so `set(X ${X} a)` cannot expand forever.
+fn expand(text: &str, state: &State, left: &mut Left) -> String {
+    let cache = fileapi::get_entries_data();
+    let mut text = text.to_string();
+    let mut expanding: HashSet<String> = HashSet::new();
+    loop {
+        let expanded = VARIABLE_REF_REGEX.replace_all(&text, |caps: &regex::Captures| {
+            let name = &caps[1];
+            if expanding.contains(name) {
+                return caps[0].to_string();
+            }
+            let value = if let Some(value) = state.variables.get(name) {
+                value.join(";")
+            } else if state.conditional.contains_key(name) {
+                push_new(&mut left.conditional, name);
+                return caps[0].to_string();
+            } else if let Some(value) = cache.as_ref().and_then(|cache| cache.get(name)) {
+                value.clone()
+            } else {
+                push_new(&mut left.unresolved, name);
+                return caps[0].to_string();
+            };
+            if VARIABLE_REF_REGEX.is_match(&value) {
+                expanding.insert(name.to_string());
+            }
+            value
+        });
+        if expanded == text {
+            return text;
+        }
+        text = expanded.into_owned();
+    }
+}
+
+/// An argument once expanded, with whether it was quoted
+struct Argument {
+    value: String,
+    quoted: bool,
+}
+
+/// The arguments of `command`, an unquoted list giving one argument per element
+fn arguments(command: Node, source: &str, state: &State, left: &mut Left) -> Vec<Argument> {
+    let mut cursor = command.walk();
+    let Some(argument_list) = command
+        .children(&mut cursor)
+        .find(|node| node.kind() == CMakeNodeKinds::ARGUMENT_LIST)
+    else {
+        return vec![];
+    };
+    let mut walk = argument_list.walk();
+    let mut output = vec![];
+    for argument in argument_list.children(&mut walk) {
+        if argument.kind() != CMakeNodeKinds::ARGUMENT {
+            continue;
+        }
+        let Some(inner) = argument.child(0) else {
+            continue;
+        };
+        match inner.kind() {
+            CMakeNodeKinds::BRACKET_ARGUMENT => output.push(Argument {
+                value: node_text(inner, source).to_string(),
+                quoted: false,
+            }),
+            CMakeNodeKinds::QUOTED_ARGUMENT => {
+                let text = node_text(inner, source);
+                let text = text.strip_prefix('"').unwrap_or(text);
+                let text = text.strip_suffix('"').unwrap_or(text);
+                output.push(Argument {
+                    value: expand(text, state, left),
+                    quoted: true,
+                });
+            }
+            _ => {
+                let value = expand(node_text(inner, source), state, left);
+                output.extend(value.split(';').filter(|element| !element.is_empty()).map(
+                    |element| Argument {
+                        value: element.to_string(),
+                        quoted: false,
+                    },
+                ));
+            }
+        }
+    }
+    output
+}
+
+fn command_name(command: Node, source: &str) -> Option<String> {
+    Some(node_text(command.child(0)?, source).to_lowercase())
+}
+
+fn collect_ordered<'a>(node: Node<'a>, output: &mut Vec<Node<'a>>) {
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        match child.kind() {
+            CMakeNodeKinds::NORMAL_COMMAND
+            | CMakeNodeKinds::IF_COMMAND
+            | CMakeNodeKinds::ELSEIF_COMMAND
+            | CMakeNodeKinds::WHILE_COMMAND
+            | CMakeNodeKinds::FOREACH_COMMAND => output.push(child),
+            // Each call of a function or a macro has its own variables
+            CMakeNodeKinds::FUNCTION_DEF | CMakeNodeKinds::MACRO_DEF => {}
+            _ => collect_ordered(child, output),
+        }
+    }
+}
+
+/// The commands of `node` in the order they are written, which is the order
+/// cmake runs them in, leaving out the bodies of the functions and macros
+pub fn ordered_commands(node: Node) -> Vec<Node> {
+    let mut output = vec![];
+    collect_ordered(node, &mut output);
+    output
+}
+
+fn replay_command(command: Node, source: &str, state: &mut State) {
+    let Some(name) = command_name(command, source) else {
+        return;
+    };
+    if !matches!(
+        name.as_str(),
+        "set" | "unset" | "list" | "option" | "project"
+    ) {
+        return;
+    }
+    let mut left = Left::default();
+    let values: Vec<String> = arguments(command, source, state, &mut left)
+        .into_iter()
+        .map(|argument| argument.value)
+        .collect();
+    let variable = match (name.as_str(), values.as_slice()) {
+        ("project", _) => "PROJECT_NAME",
+        ("list", [_, list, ..]) => list.as_str(),
+        (_, [variable, ..]) => variable.as_str(),
+        _ => return,
+    };
+    // Appending to a value depending on a branch still depends on it
+    let mut conditions = state.conditional.remove(variable).unwrap_or_default();
+    if !matches!(name.as_str(), "list" | "option") {
+        conditions.clear();
+    }
+    for reference in &left.conditional {
+        for condition in state.conditional.get(reference).into_iter().flatten() {
+            push_new(&mut conditions, condition);
+        }
+    }
+    if conditions.is_empty() {
+        apply(&name, &values, &mut state.variables);
+    } else {
+        state.variables.remove(variable);
+        state.conditional.insert(variable.to_string(), conditions);
+    }
+}
+
+/// The condition of an `if()` or `elseif()`
+fn condition(command: Node, source: &str) -> String {
+    let mut cursor = command.walk();
+    command
+        .children(&mut cursor)
+        .find(|node| node.kind() == CMakeNodeKinds::ARGUMENT_LIST)
+        .map(|arguments| node_text(arguments, source).to_string())
+        .unwrap_or_default()
+}
+
+/// Replay the branches of `node`, an `if()` block. When `end` is in a branch,
+/// this branch is the one taken, otherwise the variables the branches set
+/// differently depend on the conditions.
+fn replay_branches(node: Node, source: &str, end: usize, state: &mut State) {
+    let mut conditions = vec![];
+    let mut outcomes: Vec<State> = vec![];
+    let mut has_else = false;
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        match child.kind() {
+            CMakeNodeKinds::IF_COMMAND | CMakeNodeKinds::ELSEIF_COMMAND => {
+                conditions.push(condition(child, source));
+                outcomes.push(state.clone());
+            }
+            CMakeNodeKinds::ELSE_COMMAND => {
+                has_else = true;
+                outcomes.push(state.clone());
+            }
+            CMakeNodeKinds::BODY if child.byte_range().contains(&end) => {
+                replay(child, source, end, state);
+                return;
+            }
+            CMakeNodeKinds::BODY => {
+                if let Some(outcome) = outcomes.last_mut() {
+                    replay(child, source, end, outcome);
+                }
+            }
+            _ => {}
+        }
+        // The command is a condition of the block, the branches before it are not taken
+        if child.byte_range().contains(&end) {
+            return;
+        }
+    }
+    if !has_else {
+        outcomes.push(state.clone());
+    }
+    merge(state, outcomes, &conditions);
+}
+
+/// The variables after the branches of an `if()` giving `outcomes`
+fn merge(state: &mut State, outcomes: Vec<State>, conditions: &[String]) {
+    let mut names: Vec<&String> = outcomes
+        .iter()
+        .flat_map(|outcome| outcome.variables.keys().chain(outcome.conditional.keys()))
+        .collect();
+    names.sort();
+    names.dedup();
+    let mut merged = State::default();
+    for name in names {
+        let values: Vec<Option<&Vec<String>>> = outcomes
+            .iter()
+            .map(|outcome| outcome.variables.get(name))
+            .collect();
+        let branch_conditions: Vec<Option<&Vec<String>>> = outcomes
+            .iter()
+            .map(|outcome| outcome.conditional.get(name))
+            .collect();
+        // The branches left it as it was, it does not depend on their conditions
+        if values.windows(2).all(|pair| pair[0] == pair[1])
+            && branch_conditions.windows(2).all(|pair| pair[0] == pair[1])
+        {
+            match (values.first(), branch_conditions.first()) {
+                (_, Some(Some(depends))) => {
+                    merged.conditional.insert(name.clone(), (*depends).clone());
+                }
+                (Some(Some(value)), _) => {
+                    merged.variables.insert(name.clone(), (*value).clone());
+                }
+                _ => {}
+            }
+            continue;
+        }
+        let inner = branch_conditions.into_iter().flatten().flatten();
+        let mut depends = conditions.to_vec();
+        for condition in inner {
+            push_new(&mut depends, condition);
+        }
+        merged.conditional.insert(name.clone(), depends);
+    }
+    *state = merged;
+}
+
+/// Replay the calls setting variables in `node`, until `end`
+fn replay(node: Node, source: &str, end: usize, state: &mut State) {
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if child.start_byte() >= end {
+            return;
+        }
+        match child.kind() {
+            CMakeNodeKinds::NORMAL_COMMAND => replay_command(child, source, state),
+            CMakeNodeKinds::IF_CONDITION => replay_branches(child, source, end, state),
+            // Each call of a function or a macro has its own variables
+            CMakeNodeKinds::FUNCTION_DEF | CMakeNodeKinds::MACRO_DEF => {}
+            _ => replay(child, source, end, state),
+        }
+    }
+}
+
+/// A `${NAME}` read before the file sets `NAME`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EarlyReference<'a> {
+    /// The name in the reference
+    pub variable: Node<'a>,
+    pub name: &'a str,
+    /// The `set()` coming after the reference
+    pub set: Node<'a>,
+}
+
+/// The names of the references of `node`, leaving out the computed ones
+pub fn references<'a>(node: Node<'a>, output: &mut Vec<Node<'a>>) {
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if child.kind() == CMakeNodeKinds::NORMAL_VAR {
+            let mut walk = child.walk();
+            output.extend(
+                child
+                    .children(&mut walk)
+                    .filter(|node| node.kind() == CMakeNodeKinds::VARIABLE)
+                    .filter(|node| node.named_child_count() == 0),
+            );
+        } else {
+            references(child, output);
+        }
+    }
+}
+
+/// The unquoted arguments of `command` without references, which may name the
+/// variables it sets or checks
+pub fn plain_arguments<'a>(command: Node, source: &'a str) -> Vec<&'a str> {
+    let mut cursor = command.walk();
+    let Some(argument_list) = command
+        .children(&mut cursor)
+        .find(|node| node.kind() == CMakeNodeKinds::ARGUMENT_LIST)
+    else {
+        return vec![];
+    };
+    let mut walk = argument_list.walk();
+    argument_list
+        .children(&mut walk)
+        .filter_map(|argument| argument.child(0))
+        .filter(|inner| inner.kind() == CMakeNodeKinds::UNQUOTED_ARGUMENT)
+        .map(|inner| node_text(inner, source))
+        .filter(|text| !text.contains("${"))
+        .collect()
+}
+
+/// The normal variable `command` sets, if it is a `set()`
+fn set_variable<'a>(command: Node, source: &'a str) -> Option<&'a str> {
+    if command_name(command, source).as_deref() != Some("set") {
+        return None;
+    }
+    let arguments = plain_arguments(command, source);
+    if arguments
+        .iter()
+        .any(|argument| matches!(*argument, "CACHE" | "PARENT_SCOPE"))
+    {
+        return None;
+    }
+    arguments.first().copied()
+}
+
+fn ancestors(node: Node) -> Vec<Node> {
+    let mut output = vec![];
+    let mut parent = node.parent();
+    while let Some(node) = parent {
+        output.push(node);
+        parent = node.parent();
+    }
+    output
+}
+
+/// Whether the reference in `command` may read the variable of `set`: the
+/// `set()` is not hidden in a `block()`, is not in another branch of an `if()`,
+/// and does not run before the reference on the next turn of a loop around both
+fn set_later(command: Node, set: Node) -> bool {
+    let contains = |node: &Node, other: Node| node.byte_range().contains(&other.start_byte());
+    let hidden = ancestors(set)
+        .iter()
+        .find(|node| node.kind() == CMakeNodeKinds::BLOCK_DEF)
+        .is_some_and(|block| !contains(block, command));
+    let other_branch = ancestors(command).iter().any(|node| {
+        node.kind() == CMakeNodeKinds::BODY
+            && !contains(node, set)
+            && node.parent().is_some_and(|block| {
+                block.kind() == CMakeNodeKinds::IF_CONDITION && contains(&block, set)
+            })
+    });
+    let looped = ancestors(command).iter().any(|node| {
+        matches!(
+            node.kind(),
+            CMakeNodeKinds::FOREACH_LOOP | CMakeNodeKinds::WHILE_LOOP
+        ) && contains(node, set)
+    });
+    !hidden && !other_branch && !looped
+}
+
+/// The references read before the first command naming their variable, when
+/// a `set()` of it comes later in the same scope. The variables `inherited`
+/// from the scope of the caller may be set before the file runs.
+pub fn references_before_set<'a>(
+    source: &'a str,
+    root: Node<'a>,
+    inherited: impl Fn(&str) -> bool,
+) -> Vec<EarlyReference<'a>> {
+    let commands = ordered_commands(root);
+    let cache = fileapi::get_entries_data();
+    // The `set()` calls of each variable, in order
+    let mut sets: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (index, command) in commands.iter().enumerate() {
+        if let Some(name) = set_variable(*command, source) {
+            sets.entry(name).or_default().push(index);
+        }
+    }
+    let mut named: HashSet<&str> = HashSet::new();
+    let mut output = vec![];
+    for (index, command) in commands.iter().enumerate() {
+        let plain = plain_arguments(*command, source);
+        let mut variables = vec![];
+        references(*command, &mut variables);
+        for variable in variables {
+            let name = node_text(variable, source);
+            let Some(later) = sets.get(name) else {
+                continue;
+            };
+            if named.contains(name)
+                || plain.contains(&name)
+                || cache.as_ref().is_some_and(|cache| cache.contains_key(name))
+                || inherited(name)
+            {
+                continue;
+            }
+            let Some(set) = later
+                .iter()
+                .filter(|set| **set > index)
+                .map(|set| commands[*set])
+                .find(|set| set_later(*command, *set))
+            else {
+                continue;
+            };
+            output.push(EarlyReference {
+                variable,
+                name,
+                set,
+            });
+        }
+        named.extend(plain);
+    }
+    output
+}
+
+fn apply(name: &str, values: &[String], variables: &mut Variables) {
+    let Some((variable, rest)) = values.split_first() else {
+        return;
+    };
+    match name {
+        "set" => {
+            let value: Vec<String> = rest
+                .iter()
+                .take_while(|value| !matches!(value.as_str(), "CACHE" | "PARENT_SCOPE"))
+                .cloned()
+                .collect();
+            if value.is_empty() {
+                variables.remove(variable);
+            } else {
+                variables.insert(variable.clone(), value);
+            }
+        }
+        "unset" => {
+            variables.remove(variable);
+        }
+        "list" => {
+            let [list, items @ ..] = rest else {
+                return;
+            };
+            let current = variables.entry(list.clone()).or_default();
+            match variable.as_str() {
+                "APPEND" => current.extend(items.iter().cloned()),
+                "PREPEND" => {
+                    current.splice(0..0, items.iter().cloned());
+                }
+                _ => {}
+            }
+        }
+        "option" => {
+            let value = rest.get(1).cloned().unwrap_or_else(|| "OFF".to_string());
+            variables.entry(variable.clone()).or_insert(vec![value]);
+        }
+        "project" => {
+            for key in ["PROJECT_NAME", "CMAKE_PROJECT_NAME"] {
+                variables.insert(key.to_string(), vec![variable.clone()]);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The variables cmake sets for every file
+fn file_variables(local_path: &Path) -> State {
+    let mut variables = Variables::new();
+    let mut insert = |name: &str, path: &Path| {
+        variables.insert(name.to_string(), vec![path.display().to_string()]);
+    };
+    insert("CMAKE_CURRENT_LIST_FILE", local_path);
+    if let Some(dir) = local_path.parent() {
+        insert("CMAKE_CURRENT_SOURCE_DIR", dir);
+        insert("CMAKE_CURRENT_LIST_DIR", dir);
+    }
+    if let Some(dir) = module_path::source_dir() {
+        insert("CMAKE_SOURCE_DIR", &dir);
+        insert("PROJECT_SOURCE_DIR", &dir);
+    }
+    State {
+        variables,
+        ..Default::default()
+    }
+}
+
+fn quote(argument: &Argument) -> String {
+    if argument.quoted {
+        format!("\"{}\"", argument.value)
+    } else {
+        argument.value.clone()
+    }
+}
+
+/// Evaluate the command at `location`
+pub fn eval_command(
+    local_path: &Path,
+    source: &str,
+    location: Position,
+) -> Option<EvaluatedCommand> {
+    let mut parse = tree_sitter::Parser::new();
+    parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+    let tree = parse.parse(source, None)?;
+    let root = tree.root_node();
+    let command = get_enclosing_nodes(location.to_point(), root)
+        .into_iter()
+        .rev()
+        .find(|node| {
+            matches!(
+                node.kind(),
+                CMakeNodeKinds::NORMAL_COMMAND
+                    | CMakeNodeKinds::IF_COMMAND
+                    | CMakeNodeKinds::ELSEIF_COMMAND
+                    | CMakeNodeKinds::WHILE_COMMAND
+                    | CMakeNodeKinds::FOREACH_COMMAND
+            )
+        })?;
+    let name = node_text(command.child(0)?, source);
+
+    let mut state = file_variables(local_path);
+    replay(root, source, command.start_byte(), &mut state);
+    let mut left = Left::default();
+    let arguments = arguments(command, source, &state, &mut left);
+    let text = format!(
+        "{name}({})",
+        arguments.iter().map(quote).collect::<Vec<_>>().join(" ")
+    );
+    Some(EvaluatedCommand {
+        range: Range {
+            start: command.start_position().to_position(),
+            end: command.end_position().to_position(),
+        },
+        text,
+        arguments: arguments
+            .into_iter()
+            .map(|argument| argument.value)
+            .collect(),
+        unresolved: left.unresolved,
+        conditional: left
+            .conditional
+            .into_iter()
+            .map(|name| ConditionalVariable {
+                conditions: state.conditional.remove(&name).unwrap_or_default(),
+                name,
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eval_command() {
+        let source = r#"project(demo)
+set(SRCS main.c util.c)
+if(WIN32)
+    list(APPEND SRCS win.c)
+    message(STATUS ${SRCS})
+endif()
+if(UNIX)
+    set(OUTPUT out)
+else()
+    set(OUTPUT out)
+endif()
+set(LIB ${PROJECT_NAME}_core)
+function(helper)
+    set(SRCS other.c)
+endfunction()
+target_sources(${LIB} PRIVATE ${SRCS} "${CMAKE_CURRENT_SOURCE_DIR}/gen.c" ${OUTPUT} ${MISSING})
+"#;
+        let eval = |line| {
+            eval_command(
+                Path::new("/project/CMakeLists.txt"),
+                source,
+                Position { line, character: 8 },
+            )
+            .unwrap()
+        };
+        let evaluated = eval(15);
+        assert_eq!(
+            evaluated.text,
+            "target_sources(demo_core PRIVATE ${SRCS} \"/project/gen.c\" out ${MISSING})"
+        );
+        assert_eq!(evaluated.arguments.len(), 6);
+        assert_eq!(evaluated.unresolved, vec!["MISSING".to_string()]);
+        assert_eq!(
+            evaluated.conditional,
+            vec![ConditionalVariable {
+                name: "SRCS".to_string(),
+                conditions: vec!["WIN32".to_string()],
+            }]
+        );
+        assert_eq!(evaluated.range.start, Position::new(15, 0));
+
+        // In the branch, its value is known
+        let evaluated = eval(4);
+        assert_eq!(evaluated.text, "message(STATUS main.c util.c win.c)");
+        assert!(evaluated.conditional.is_empty());
+    }
+
+    #[test]
+    fn test_eval_self_reference() {
+        let source = "set(X ${X} a)\nset(X ${X} b)\nmessage(${X})\n";
+        let evaluated = eval_command(
+            Path::new("/project/CMakeLists.txt"),
+            source,
+            Position::new(2, 8),
+        )
+        .unwrap();
+        assert_eq!(evaluated.text, "message(${X} a b)");
+    }
+}
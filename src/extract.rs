@@ -0,0 +1,1198 @@
+//! Refactorings moving commands around: "Extract to function" moves the
+//! selected commands to a new function defined above and calls it instead,
+//! "Extract to file" moves them to a new `.cmake` file and includes it
+//! instead, and "Inline" replaces an `include()` with the included commands.
+//! A target can also move to the CMakeLists.txt of a subdirectory, with the
+//! commands setting it up, and its sources grouped in the folders of the IDEs
+//! with a `source_group(TREE)` call.
+//!
+//! A function sees the variables of its caller, but the ones it sets are lost
+//! when it returns. The variables read before being set become parameters, to
+//! show what the function depends on, and the ones set and used after the
+//! selection are given back with `PARENT_SCOPE`.
+
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use tower_lsp::lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, CreateFile, DeleteFile,
+    DocumentChangeOperation, DocumentChanges, OneOf, OptionalVersionedTextDocumentIdentifier,
+    Position, Range, ResourceOp, TextDocumentEdit, TextEdit, Uri, WorkspaceEdit,
+};
+use tree_sitter::{Node, Point};
+
+use crate::CMakeNodeKinds;
+use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+use crate::utils::treehelper::get_enclosing_nodes;
+use crate::utils::{include_is_module, resolve_include};
+
+const FUNCTION_NAME: &str = "extracted_function";
+
+const FILE_NAME: &str = "extracted";
+
+/// The paths of `include()` relative to the directory of the including file
+const LIST_DIR_PREFIXES: &[&str] = &["${CMAKE_CURRENT_LIST_DIR}/", "${CMAKE_CURRENT_SOURCE_DIR}/"];
+
+/// The indentation of the body when the file has no indented line
+const DEFAULT_INDENT: &str = "  ";
+
+/// `return()` leaves the included file, but would leave its includer
+static RETURN_REGEX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"(?im)^\s*return\s*\(").unwrap());
+
+static VARIABLE_REF_REGEX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"\$\{([A-Za-z0-9_]+)\}").unwrap());
+
+/// The commands setting a variable, with the index of its argument, and
+/// whether they read it first
+const SETTERS: &[(&str, usize, bool)] = &[
+    ("set", 0, false),
+    ("unset", 0, false),
+    ("option", 0, false),
+    ("foreach", 0, false),
+    ("list", 1, true),
+    ("math", 1, false),
+    ("string", 1, true),
+];
+
+/// The statements which can be extracted, a definition cannot
+const STATEMENTS: &[&str] = &[
+    CMakeNodeKinds::NORMAL_COMMAND,
+    CMakeNodeKinds::IF_CONDITION,
+    CMakeNodeKinds::FOREACH_LOOP,
+    CMakeNodeKinds::WHILE_LOOP,
+    CMakeNodeKinds::BLOCK_DEF,
+];
+
+const DEFINITIONS: &[&str] = &[CMakeNodeKinds::FUNCTION_DEF, CMakeNodeKinds::MACRO_DEF];
+
+const COMMENTS: &[&str] = &[
+    CMakeNodeKinds::LINE_COMMENT,
+    CMakeNodeKinds::BRACKET_COMMENT,
+];
+
+/// The variables set by cmake, or by the caller of a function
+fn is_builtin(name: &str) -> bool {
+    name.starts_with("CMAKE_")
+        || name.starts_with("PROJECT_")
+        || name.starts_with("ARGV")
+        || matches!(name, "ARGN" | "ARGC")
+}
+
+fn node_text<'a>(node: Node, source: &'a str) -> &'a str {
+    &source[node.byte_range()]
+}
+
+/// The commands of `node` in the order they are written, with the ones
+/// opening the blocks
+fn commands<'a>(node: Node<'a>, output: &mut Vec<Node<'a>>) {
+    if matches!(
+        node.kind(),
+        CMakeNodeKinds::NORMAL_COMMAND
+            | CMakeNodeKinds::IF_COMMAND
+            | CMakeNodeKinds::ELSEIF_COMMAND
+            | CMakeNodeKinds::WHILE_COMMAND
+            | CMakeNodeKinds::FOREACH_COMMAND
+    ) {
+        output.push(node);
+        return;
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        commands(child, output);
+    }
+}
+
+fn command_name(command: Node, source: &str) -> Option<String> {
+    command
+        .child(0)
+        .map(|ids| node_text(ids, source).to_lowercase())
+}
+
+fn argument_nodes(command: Node) -> Vec<Node> {
+    let mut cursor = command.walk();
+    let Some(argument_list) = command
+        .children(&mut cursor)
+        .find(|node| node.kind() == CMakeNodeKinds::ARGUMENT_LIST)
+    else {
+        return vec![];
+    };
+    let mut walk = argument_list.walk();
+    argument_list
+        .children(&mut walk)
+        .filter(|node| node.kind() == CMakeNodeKinds::ARGUMENT)
+        .collect()
+}
+
+fn arguments<'a>(command: Node, source: &'a str) -> Vec<&'a str> {
+    argument_nodes(command)
+        .into_iter()
+        .map(|node| node_text(node, source).trim_matches('"'))
+        .collect()
+}
+
+/// The variables read before being set, and the variables set, by `statements`
+fn variables(statements: &[Node], source: &str) -> (Vec<String>, Vec<String>) {
+    let mut calls = vec![];
+    for statement in statements {
+        commands(*statement, &mut calls);
+    }
+    let mut read: Vec<String> = vec![];
+    let mut set: Vec<String> = vec![];
+    let mut read_first = |name: &str, set: &[String]| {
+        if !is_builtin(name)
+            && !set.iter().any(|known| known == name)
+            && !read.iter().any(|known| known == name)
+        {
+            read.push(name.to_string());
+        }
+    };
+    for call in calls {
+        for caps in VARIABLE_REF_REGEX.captures_iter(node_text(call, source)) {
+            read_first(&caps[1], &set);
+        }
+        let Some(name) = command_name(call, source) else {
+            continue;
+        };
+        let Some(&(_, index, reads)) = SETTERS.iter().find(|(setter, ..)| *setter == name) else {
+            continue;
+        };
+        let Some(variable) = arguments(call, source)
+            .get(index)
+            .map(|name| name.to_string())
+        else {
+            continue;
+        };
+        if variable.contains("${") || is_builtin(&variable) {
+            continue;
+        }
+        if reads {
+            read_first(&variable, &set);
+        }
+        if !set.contains(&variable) {
+            set.push(variable);
+        }
+    }
+    (read, set)
+}
+
+/// The statements of `container` on the rows `start..=end`, `None` when the
+/// selection cuts one of them. The definitions are kept for a file only, a
+/// function cannot hold them
+fn selected_statements<'a>(
+    container: Node<'a>,
+    start: usize,
+    end: usize,
+    definitions: bool,
+) -> Option<Vec<Node<'a>>> {
+    let mut cursor = container.walk();
+    let mut selected = vec![];
+    for child in container.children(&mut cursor) {
+        let (first, last) = (child.start_position().row, child.end_position().row);
+        if last < start || first > end {
+            continue;
+        }
+        let inside = first >= start && last <= end;
+        if STATEMENTS.contains(&child.kind())
+            || (definitions && DEFINITIONS.contains(&child.kind()))
+        {
+            if !inside {
+                return None;
+            }
+            selected.push(child);
+        } else if !inside || !COMMENTS.contains(&child.kind()) {
+            return None;
+        }
+    }
+    if selected.is_empty() {
+        return None;
+    }
+    Some(selected)
+}
+
+/// A name no function of the file has
+fn function_name(source: &str) -> String {
+    let lowercase = source.to_lowercase();
+    let mut name = FUNCTION_NAME.to_string();
+    let mut index = 1;
+    while lowercase.contains(&format!("({name}")) || lowercase.contains(&format!("{name}(")) {
+        index += 1;
+        name = format!("{FUNCTION_NAME}_{index}");
+    }
+    name
+}
+
+fn leading_whitespace(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+/// The statements a selection covers
+struct Selection<'a> {
+    statements: Vec<Node<'a>>,
+    first_row: usize,
+    last_row: usize,
+    /// The first row of the top level statement the selection is in
+    insert_row: usize,
+}
+
+/// The whole statements on the rows of `range`, all in the same body
+fn select<'a>(
+    root: Node<'a>,
+    lines: &[&str],
+    range: Range,
+    definitions: bool,
+) -> Option<Selection<'a>> {
+    if range.start == range.end {
+        return None;
+    }
+    let start_row = range.start.line as usize;
+    // A selection of whole lines ends at the start of the next one
+    let end_row = if range.end.character == 0 && range.end.line > range.start.line {
+        range.end.line as usize - 1
+    } else {
+        range.end.line as usize
+    };
+    if end_row >= lines.len() {
+        return None;
+    }
+    // From the first word of the line, inside the statement starting there
+    let start_point = Point {
+        row: start_row,
+        column: leading_whitespace(lines[start_row]).len(),
+    };
+    let enclosing = get_enclosing_nodes(start_point, root);
+    let container = enclosing
+        .iter()
+        .rev()
+        .find(|node| node.kind() == CMakeNodeKinds::BODY && node.end_position().row >= end_row)
+        .copied()
+        .unwrap_or(root);
+    let statements = selected_statements(container, start_row, end_row, definitions)?;
+    if statements.iter().any(|node| node.has_error()) {
+        return None;
+    }
+    let first_row = statements[0].start_position().row;
+    let last_row = statements[statements.len() - 1].end_position().row;
+    let insert_row = enclosing
+        .first()
+        .map_or(first_row, |node| node.start_position().row.min(first_row));
+    Some(Selection {
+        statements,
+        first_row,
+        last_row,
+        insert_row,
+    })
+}
+
+/// Whether nothing else shares the lines of `node`
+fn alone_on_lines(node: Node, lines: &[&str]) -> bool {
+    let start = node.start_position();
+    let end = node.end_position();
+    lines[start.row][..start.column].trim().is_empty()
+        && lines[end.row][end.column..].trim().is_empty()
+}
+
+/// The range of the rows `first..=last`, without the last newline
+fn rows_range(lines: &[&str], first: usize, last: usize) -> Range {
+    Range {
+        start: Position {
+            line: first as u32,
+            character: 0,
+        },
+        end: Position {
+            line: last as u32,
+            character: lines[last].len() as u32,
+        },
+    }
+}
+
+/// The edit extracting the commands on the rows of `range` to a function
+pub fn extract_function_edit(source: &str, range: Range) -> Option<TextEdit> {
+    let mut parse = tree_sitter::Parser::new();
+    parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+    let tree = parse.parse(source, None)?;
+    let lines: Vec<&str> = source.lines().collect();
+    let Selection {
+        statements,
+        first_row,
+        last_row,
+        insert_row,
+    } = select(tree.root_node(), &lines, range, false)?;
+
+    let (parameters, set) = variables(&statements, source);
+    let after: String = lines[last_row + 1..].join("\n");
+    let outputs: Vec<&String> = set
+        .iter()
+        .filter(|name| {
+            regex::Regex::new(&format!(r"\b{}\b", regex::escape(name)))
+                .is_ok_and(|word| word.is_match(&after))
+        })
+        .collect();
+
+    let unit = lines
+        .iter()
+        .map(|line| leading_whitespace(line))
+        .find(|indent| !indent.is_empty())
+        .unwrap_or(DEFAULT_INDENT);
+    let base = leading_whitespace(lines[first_row]);
+    let name = function_name(source);
+
+    let mut new_text = format!("function({name}");
+    for parameter in &parameters {
+        new_text.push(' ');
+        new_text.push_str(parameter);
+    }
+    new_text.push_str(")\n");
+    for line in &lines[first_row..=last_row] {
+        let line = line.strip_prefix(base).unwrap_or(line);
+        if line.is_empty() {
+            new_text.push('\n');
+        } else {
+            new_text.push_str(&format!("{unit}{line}\n"));
+        }
+    }
+    for output in outputs {
+        new_text.push_str(&format!(
+            "{unit}set({output} \"${{{output}}}\" PARENT_SCOPE)\n"
+        ));
+    }
+    new_text.push_str("endfunction()\n\n");
+    for line in &lines[insert_row..first_row] {
+        new_text.push_str(line);
+        new_text.push('\n');
+    }
+    let arguments: Vec<String> = parameters
+        .iter()
+        .map(|parameter| format!("\"${{{parameter}}}\""))
+        .collect();
+    new_text.push_str(&format!("{base}{name}({})", arguments.join(" ")));
+
+    Some(TextEdit {
+        range: rows_range(&lines, insert_row, last_row),
+        new_text,
+    })
+}
+
+fn refactor_action(
+    title: String,
+    kind: CodeActionKind,
+    document_changes: DocumentChanges,
+) -> CodeActionOrCommand {
+    CodeActionOrCommand::CodeAction(CodeAction {
+        title,
+        kind: Some(kind),
+        diagnostics: None,
+        edit: Some(WorkspaceEdit {
+            changes: None,
+            change_annotations: None,
+            document_changes: Some(document_changes),
+        }),
+        command: None,
+        is_preferred: None,
+        disabled: None,
+        data: None,
+    })
+}
+
+fn document_edit(uri: Uri, edits: Vec<TextEdit>) -> TextDocumentEdit {
+    TextDocumentEdit {
+        text_document: OptionalVersionedTextDocumentIdentifier { uri, version: None },
+        edits: edits.into_iter().map(OneOf::Left).collect(),
+    }
+}
+
+/// The code action extracting the selection to a function
+pub fn extract_function_action(
+    source: &str,
+    range: Range,
+    uri: Uri,
+) -> Option<CodeActionOrCommand> {
+    let edit = extract_function_edit(source, range)?;
+    Some(refactor_action(
+        "Extract to function".to_string(),
+        CodeActionKind::REFACTOR_EXTRACT,
+        DocumentChanges::Edits(vec![document_edit(uri, vec![edit])]),
+    ))
+}
+
+/// A name no file of `dir` has
+fn file_name(dir: &Path) -> String {
+    let mut name = format!("{FILE_NAME}.cmake");
+    let mut index = 1;
+    while dir.join(&name).exists() {
+        index += 1;
+        name = format!("{FILE_NAME}_{index}.cmake");
+    }
+    name
+}
+
+/// The statements on the rows of `range` moved to a new file of `dir`: its
+/// name, its content and the edit including it instead
+pub fn extract_file_edit(
+    source: &str,
+    range: Range,
+    dir: &Path,
+) -> Option<(String, String, TextEdit)> {
+    let mut parse = tree_sitter::Parser::new();
+    parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+    let tree = parse.parse(source, None)?;
+    let lines: Vec<&str> = source.lines().collect();
+    let Selection {
+        first_row,
+        last_row,
+        ..
+    } = select(tree.root_node(), &lines, range, true)?;
+
+    let base = leading_whitespace(lines[first_row]);
+    let mut content = String::new();
+    for line in &lines[first_row..=last_row] {
+        content.push_str(line.strip_prefix(base).unwrap_or(line));
+        content.push('\n');
+    }
+    let name = file_name(dir);
+    let edit = TextEdit {
+        range: rows_range(&lines, first_row, last_row),
+        new_text: format!("{base}include(${{CMAKE_CURRENT_LIST_DIR}}/{name})"),
+    };
+    Some((name, content, edit))
+}
+
+/// The code action moving the selection to a new file next to the document
+pub fn extract_file_action(source: &str, range: Range, uri: Uri) -> Option<CodeActionOrCommand> {
+    let path = uri.to_file_path().ok()?;
+    let dir = path.parent()?;
+    let (name, content, edit) = extract_file_edit(source, range, dir)?;
+    let new_uri = Uri::from_file_path(dir.join(&name)).ok()?;
+    Some(refactor_action(
+        format!("Extract to {name}"),
+        CodeActionKind::REFACTOR_EXTRACT,
+        DocumentChanges::Operations(vec![
+            DocumentChangeOperation::Op(ResourceOp::Create(CreateFile {
+                uri: new_uri.clone(),
+                options: None,
+                annotation_id: None,
+            })),
+            DocumentChangeOperation::Edit(document_edit(
+                new_uri,
+                vec![TextEdit {
+                    range: Range::default(),
+                    new_text: content,
+                }],
+            )),
+            DocumentChangeOperation::Edit(document_edit(uri, vec![edit])),
+        ]),
+    ))
+}
+
+/// The `include()` on `line` replaced with the content of the included file,
+/// and the path of that file
+pub fn inline_include_edit(
+    source: &str,
+    local_path: &Path,
+    line: usize,
+) -> Option<(PathBuf, TextEdit)> {
+    let mut parse = tree_sitter::Parser::new();
+    parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+    let tree = parse.parse(source, None)?;
+    let lines: Vec<&str> = source.lines().collect();
+    let mut calls = vec![];
+    commands(tree.root_node(), &mut calls);
+    let command = calls.into_iter().find(|call| {
+        call.start_position().row == line
+            && call.kind() == CMakeNodeKinds::NORMAL_COMMAND
+            && command_name(*call, source).is_some_and(|name| name == "include")
+    })?;
+    let arguments = arguments(command, source);
+    let [argument] = arguments[..] else {
+        return None;
+    };
+    let name = LIST_DIR_PREFIXES
+        .iter()
+        .find_map(|prefix| argument.strip_prefix(prefix))
+        .unwrap_or(argument);
+    if name.contains("${") || include_is_module(name) {
+        return None;
+    }
+    let dir = local_path.parent()?;
+    let (path, _) = resolve_include(name, dir)?;
+    let content = std::fs::read_to_string(&path).ok()?;
+    // The meaning of these changes once the content moves
+    if RETURN_REGEX.is_match(&content)
+        || content.contains("CMAKE_CURRENT_LIST_FILE")
+        || content.contains("CMAKE_CURRENT_LIST_LINE")
+        || (path.parent() != Some(dir) && content.contains("CMAKE_CURRENT_LIST_DIR"))
+    {
+        return None;
+    }
+    if !alone_on_lines(command, &lines) {
+        return None;
+    }
+    let start = command.start_position();
+    let end = command.end_position();
+    let indent = leading_whitespace(lines[start.row]);
+    let new_text: Vec<String> = content
+        .trim_end()
+        .lines()
+        .map(|line| {
+            if line.is_empty() {
+                String::new()
+            } else {
+                format!("{indent}{line}")
+            }
+        })
+        .collect();
+    Some((
+        path,
+        TextEdit {
+            range: rows_range(&lines, start.row, end.row),
+            new_text: new_text.join("\n"),
+        },
+    ))
+}
+
+/// The code actions inlining the file included on `line`, and deleting it
+pub fn inline_include_actions(source: &str, line: u32, uri: Uri) -> Vec<CodeActionOrCommand> {
+    let Ok(local_path) = uri.to_file_path() else {
+        return vec![];
+    };
+    let Some((path, edit)) = inline_include_edit(source, &local_path, line as usize) else {
+        return vec![];
+    };
+    let Ok(included) = Uri::from_file_path(&path) else {
+        return vec![];
+    };
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    vec![
+        refactor_action(
+            format!("Inline {name}"),
+            CodeActionKind::REFACTOR_INLINE,
+            DocumentChanges::Edits(vec![document_edit(uri.clone(), vec![edit.clone()])]),
+        ),
+        refactor_action(
+            format!("Inline {name} and delete it"),
+            CodeActionKind::REFACTOR_INLINE,
+            DocumentChanges::Operations(vec![
+                DocumentChangeOperation::Edit(document_edit(uri, vec![edit])),
+                DocumentChangeOperation::Op(ResourceOp::Delete(DeleteFile {
+                    uri: included,
+                    options: None,
+                })),
+            ]),
+        ),
+    ]
+}
+
+/// The keywords of `add_executable()` and `add_library()` before the sources
+const TARGET_KEYWORDS: &[&str] = &[
+    "STATIC",
+    "SHARED",
+    "MODULE",
+    "OBJECT",
+    "INTERFACE",
+    "WIN32",
+    "MACOSX_BUNDLE",
+    "EXCLUDE_FROM_ALL",
+];
+
+/// A target moved to the CMakeLists.txt of a subdirectory
+pub struct MoveTarget {
+    /// The CMakeLists.txt of the subdirectory
+    pub lists: PathBuf,
+    /// Whether `lists` has to be created
+    pub create: bool,
+    /// Where the commands are added to `lists`
+    pub position: Position,
+    pub new_text: String,
+    /// The edits of the current file
+    pub edits: Vec<TextEdit>,
+}
+
+/// The top level `add_executable()` or `add_library()` on `line`, with the
+/// name of its target. Imported and alias targets belong to their directory
+fn target_declaration<'a>(root: Node<'a>, source: &str, line: usize) -> Option<(Node<'a>, String)> {
+    let mut cursor = root.walk();
+    let declaration = root.children(&mut cursor).find(|node| {
+        node.kind() == CMakeNodeKinds::NORMAL_COMMAND
+            && node.start_position().row == line
+            && command_name(*node, source)
+                .is_some_and(|name| name == "add_executable" || name == "add_library")
+    })?;
+    let arguments = arguments(declaration, source);
+    let name = arguments.first()?;
+    if name.contains("${")
+        || arguments
+            .iter()
+            .any(|arg| matches!(*arg, "IMPORTED" | "ALIAS"))
+    {
+        return None;
+    }
+    Some((declaration, name.to_string()))
+}
+
+/// The top level `target_*()` and `set_target_properties()` calls of `target` only
+fn target_commands<'a>(root: Node<'a>, source: &str, target: &str) -> Vec<Node<'a>> {
+    let mut cursor = root.walk();
+    root.children(&mut cursor)
+        .filter(|node| node.kind() == CMakeNodeKinds::NORMAL_COMMAND)
+        .filter(|node| {
+            let Some(name) = command_name(*node, source) else {
+                return false;
+            };
+            let arguments = arguments(*node, source);
+            if arguments.first() != Some(&target) {
+                return false;
+            }
+            name.starts_with("target_")
+                || (name == "set_target_properties" && arguments.get(1) == Some(&"PROPERTIES"))
+        })
+        .collect()
+}
+
+/// The directories the target may move to: the one holding all its sources,
+/// and the ones already added
+fn move_candidates(root: Node, source: &str, declaration: Node, dir: &Path) -> Vec<String> {
+    let mut candidates = vec![];
+    let sources: Vec<&str> = arguments(declaration, source)
+        .into_iter()
+        .skip(1)
+        .filter(|arg| !TARGET_KEYWORDS.contains(arg))
+        .collect();
+    let first_dirs: Vec<Option<&str>> = sources
+        .iter()
+        .map(|source| {
+            source
+                .split_once('/')
+                .map(|(first, _)| first)
+                .filter(|first| !first.is_empty() && !first.contains("${") && *first != "..")
+        })
+        .collect();
+    if let Some(Some(first)) = first_dirs.first()
+        && first_dirs.iter().all(|other| other == &Some(*first))
+    {
+        candidates.push(first.to_string());
+    }
+    let mut cursor = root.walk();
+    for node in root.children(&mut cursor) {
+        if node.kind() == CMakeNodeKinds::NORMAL_COMMAND
+            && command_name(node, source).is_some_and(|name| name == "add_subdirectory")
+            && let Some(sub) = arguments(node, source).first()
+            && !sub.contains("${")
+            && !candidates.iter().any(|known| known == sub)
+        {
+            candidates.push(sub.to_string());
+        }
+    }
+    candidates.retain(|sub| dir.join(sub).is_dir());
+    candidates
+}
+
+/// `path`, relative to the current directory, seen from the subdirectory `sub`
+fn path_from(sub: &str, path: &str) -> String {
+    if let Some(rest) = path.strip_prefix(&format!("{sub}/")) {
+        return rest.to_string();
+    }
+    let depth = Path::new(sub).components().count();
+    format!("{}{path}", "../".repeat(depth))
+}
+
+/// The commands moved with a target whose arguments after the first may be
+/// paths: its sources, include and link directories and headers. The link
+/// items and the definitions are names, even when a directory has the same.
+const PATH_COMMANDS: &[&str] = &[
+    "add_executable",
+    "add_library",
+    "add_subdirectory",
+    "target_sources",
+    "target_include_directories",
+    "target_link_directories",
+    "target_precompile_headers",
+];
+
+/// The text of `command` with its relative paths seen from `sub`
+fn moved_text(command: Node, source: &str, dir: &Path, sub: &str) -> String {
+    let start = command.start_byte();
+    let mut text = node_text(command, source).to_string();
+    let arguments = argument_nodes(command);
+    let takes_paths = command_name(command, source)
+        .is_some_and(|name| PATH_COMMANDS.contains(&name.as_str()))
+        && !arguments
+            .iter()
+            .any(|argument| matches!(node_text(*argument, source), "ALIAS" | "IMPORTED"));
+    if !takes_paths {
+        return text;
+    }
+    for argument in arguments.into_iter().skip(1).rev() {
+        let raw = node_text(argument, source);
+        let value = raw.trim_matches('"');
+        let (prefix, path) = LIST_DIR_PREFIXES
+            .iter()
+            .find_map(|prefix| value.strip_prefix(prefix).map(|path| (*prefix, path)))
+            .unwrap_or(("", value));
+        if path.is_empty()
+            || path.contains("${")
+            || Path::new(path).is_absolute()
+            || !dir.join(path).exists()
+        {
+            continue;
+        }
+        let quote = if raw.starts_with('"') { "\"" } else { "" };
+        let range = argument.start_byte() - start..argument.end_byte() - start;
+        text.replace_range(
+            range,
+            &format!("{quote}{prefix}{}{quote}", path_from(sub, path)),
+        );
+    }
+    text
+}
+
+/// The target declared on `line` moved to `sub/CMakeLists.txt` with its
+/// `target_*()` calls, and replaced with `add_subdirectory(sub)`
+pub fn move_target_edits(
+    source: &str,
+    local_path: &Path,
+    line: usize,
+    sub: &str,
+) -> Option<MoveTarget> {
+    let mut parse = tree_sitter::Parser::new();
+    parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+    let tree = parse.parse(source, None)?;
+    let root = tree.root_node();
+    let lines: Vec<&str> = source.lines().collect();
+    let dir = local_path.parent()?;
+    let (declaration, target) = target_declaration(root, source, line)?;
+    let mut moved = vec![declaration];
+    moved.extend(target_commands(root, source, &target));
+    if moved
+        .iter()
+        .any(|node| node.has_error() || !alone_on_lines(*node, &lines))
+    {
+        return None;
+    }
+    moved.sort_by_key(|node| node.start_byte());
+
+    let mut cursor = root.walk();
+    let added = root.children(&mut cursor).any(|node| {
+        command_name(node, source).is_some_and(|name| name == "add_subdirectory")
+            && arguments(node, source).first() == Some(&sub)
+    });
+    let mut edits = vec![];
+    for node in &moved {
+        let first = node.start_position().row;
+        let last = node.end_position().row;
+        if *node == declaration && !added {
+            edits.push(TextEdit {
+                range: rows_range(&lines, first, last),
+                new_text: format!("add_subdirectory({sub})"),
+            });
+        } else if last + 1 < lines.len() {
+            edits.push(TextEdit {
+                range: Range {
+                    start: Position::new(first as u32, 0),
+                    end: Position::new(last as u32 + 1, 0),
+                },
+                new_text: String::new(),
+            });
+        } else {
+            edits.push(TextEdit {
+                range: rows_range(&lines, first, last),
+                new_text: String::new(),
+            });
+        }
+    }
+    let moved: Vec<String> = moved
+        .iter()
+        .map(|node| moved_text(*node, source, dir, sub))
+        .collect();
+
+    let lists = dir.join(sub).join("CMakeLists.txt");
+    let existing = std::fs::read_to_string(&lists).ok();
+    let content = existing.as_deref().unwrap_or_default();
+    let separator = if content.is_empty() {
+        ""
+    } else if content.ends_with('\n') {
+        "\n"
+    } else {
+        "\n\n"
+    };
+    let position = if content.is_empty() || content.ends_with('\n') {
+        Position::new(content.lines().count() as u32, 0)
+    } else {
+        let last = content.lines().last().unwrap_or_default();
+        Position::new(content.lines().count() as u32 - 1, last.len() as u32)
+    };
+    Some(MoveTarget {
+        lists,
+        create: existing.is_none(),
+        position,
+        new_text: format!("{separator}{}\n", moved.join("\n")),
+        edits,
+    })
+}
+
+/// The code actions moving the target declared on `line` to a subdirectory,
+/// one for each directory it may go to
+pub fn move_target_actions(source: &str, line: u32, uri: Uri) -> Vec<CodeActionOrCommand> {
+    let Ok(local_path) = uri.to_file_path() else {
+        return vec![];
+    };
+    let Some(dir) = local_path.parent() else {
+        return vec![];
+    };
+    let mut parse = tree_sitter::Parser::new();
+    parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+    let Some(tree) = parse.parse(source, None) else {
+        return vec![];
+    };
+    let root = tree.root_node();
+    let Some((declaration, target)) = target_declaration(root, source, line as usize) else {
+        return vec![];
+    };
+    let mut actions = vec![];
+    for sub in move_candidates(root, source, declaration, dir) {
+        let Some(moved) = move_target_edits(source, &local_path, line as usize, &sub) else {
+            continue;
+        };
+        let Ok(lists) = Uri::from_file_path(&moved.lists) else {
+            continue;
+        };
+        let mut operations = vec![];
+        if moved.create {
+            operations.push(DocumentChangeOperation::Op(ResourceOp::Create(
+                CreateFile {
+                    uri: lists.clone(),
+                    options: None,
+                    annotation_id: None,
+                },
+            )));
+        }
+        operations.push(DocumentChangeOperation::Edit(document_edit(
+            lists,
+            vec![TextEdit {
+                range: Range::new(moved.position, moved.position),
+                new_text: moved.new_text,
+            }],
+        )));
+        operations.push(DocumentChangeOperation::Edit(document_edit(
+            uri.clone(),
+            moved.edits,
+        )));
+        actions.push(refactor_action(
+            format!("Move target {target} to {sub}/CMakeLists.txt"),
+            CodeActionKind::REFACTOR_EXTRACT,
+            DocumentChanges::Operations(operations),
+        ));
+    }
+    actions
+}
+
+/// The keywords of `target_sources()` around the sources
+const SCOPE_KEYWORDS: &[&str] = &["PRIVATE", "PUBLIC", "INTERFACE", "FILES"];
+
+/// The keywords of a file set followed by a value which is not a source
+const FILE_SET_KEYWORDS: &[&str] = &["FILE_SET", "TYPE"];
+
+/// The sources of a `target_sources()` call, leaving out the names, the types
+/// and the base directories of its file sets
+fn target_sources<'a>(arguments: &[&'a str]) -> Vec<&'a str> {
+    let mut sources = vec![];
+    let mut skip_next = false;
+    let mut base_dirs = false;
+    for argument in arguments {
+        if skip_next {
+            skip_next = false;
+        } else if FILE_SET_KEYWORDS.contains(argument) {
+            skip_next = true;
+        } else if *argument == "BASE_DIRS" {
+            base_dirs = true;
+        } else if SCOPE_KEYWORDS.contains(argument) {
+            base_dirs = false;
+        } else if !base_dirs {
+            sources.push(*argument);
+        }
+    }
+    sources
+}
+
+/// `path` relative to the current directory, unless it is computed or outside
+/// of it
+fn relative_source(path: &str) -> Option<&str> {
+    let path = LIST_DIR_PREFIXES
+        .iter()
+        .find_map(|prefix| path.strip_prefix(prefix))
+        .unwrap_or(path);
+    if path.is_empty()
+        || path.contains("${")
+        || path.contains("$<")
+        || Path::new(path).is_absolute()
+        || path.split('/').any(|part| part == "..")
+    {
+        return None;
+    }
+    Some(path)
+}
+
+/// The `source_group(TREE)` call grouping the sources of the target declared
+/// on `line` and of its `target_sources()` calls as they are laid out on disk,
+/// added after the last of them
+pub fn source_group_edit(source: &str, line: usize) -> Option<(String, TextEdit)> {
+    let mut parse = tree_sitter::Parser::new();
+    parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+    let tree = parse.parse(source, None)?;
+    let root = tree.root_node();
+    let (declaration, target) = target_declaration(root, source, line)?;
+
+    let mut files: Vec<&str> = arguments(declaration, source)
+        .into_iter()
+        .skip(1)
+        .filter(|arg| !TARGET_KEYWORDS.contains(arg))
+        .collect();
+    let mut last = declaration;
+    for command in target_commands(root, source, &target) {
+        if command_name(command, source).is_some_and(|name| name == "target_sources") {
+            files.extend(target_sources(&arguments(command, source)[1..]));
+            if command.end_byte() > last.end_byte() {
+                last = command;
+            }
+        }
+    }
+    let mut grouped: Vec<&str> = vec![];
+    for file in files.into_iter().filter_map(relative_source) {
+        if !grouped.contains(&file) {
+            grouped.push(file);
+        }
+    }
+    if grouped.is_empty() {
+        return None;
+    }
+
+    let lines: Vec<&str> = source.lines().collect();
+    let unit = lines
+        .iter()
+        .map(|line| leading_whitespace(line))
+        .find(|indent| !indent.is_empty())
+        .unwrap_or(DEFAULT_INDENT);
+    let mut call = "source_group(TREE ${CMAKE_CURRENT_SOURCE_DIR} FILES\n".to_string();
+    for file in grouped {
+        call.push_str(&format!("{unit}{file}\n"));
+    }
+    call.push(')');
+
+    let row = last.end_position().row;
+    let edit = if row + 1 < lines.len() || source.ends_with('\n') {
+        let start = Position::new(row as u32 + 1, 0);
+        TextEdit {
+            range: Range::new(start, start),
+            new_text: format!("{call}\n"),
+        }
+    } else {
+        let end = Position::new(row as u32, lines[row].len() as u32);
+        TextEdit {
+            range: Range::new(end, end),
+            new_text: format!("\n{call}"),
+        }
+    };
+    Some((target, edit))
+}
+
+/// The code action grouping the sources of the target declared on `line` in
+/// the folders of the IDEs
+pub fn source_group_action(source: &str, line: u32, uri: Uri) -> Option<CodeActionOrCommand> {
+    let (target, edit) = source_group_edit(source, line as usize)?;
+    Some(refactor_action(
+        format!("Group the sources of {target} with source_group()"),
+        CodeActionKind::REFACTOR,
+        DocumentChanges::Edits(vec![document_edit(uri, vec![edit])]),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extract(source: &str, start: u32, end: u32) -> Option<String> {
+        let edit = extract_function_edit(
+            source,
+            Range {
+                start: Position::new(start, 0),
+                end: Position::new(end + 1, 0),
+            },
+        )?;
+        let lines: Vec<&str> = source.lines().collect();
+        let start = edit.range.start.line as usize;
+        let end = edit.range.end.line as usize;
+        let mut output: Vec<String> = lines[..start].iter().map(|line| line.to_string()).collect();
+        output.push(edit.new_text);
+        output.extend(lines[end + 1..].iter().map(|line| line.to_string()));
+        Some(output.join("\n"))
+    }
+
+    #[test]
+    fn test_extract_function() {
+        let source = r#"set(SRCS main.c)
+if(WIN32)
+    list(APPEND SRCS win.c)
+endif()
+add_executable(app ${SRCS})
+message(STATUS "${NAME} in ${CMAKE_SOURCE_DIR}")
+set(DONE 1)
+message(${DONE})"#;
+        assert_eq!(
+            extract(source, 1, 6).unwrap(),
+            r#"set(SRCS main.c)
+function(extracted_function SRCS NAME)
+    if(WIN32)
+        list(APPEND SRCS win.c)
+    endif()
+    add_executable(app ${SRCS})
+    message(STATUS "${NAME} in ${CMAKE_SOURCE_DIR}")
+    set(DONE 1)
+    set(DONE "${DONE}" PARENT_SCOPE)
+endfunction()
+
+extracted_function("${SRCS}" "${NAME}")
+message(${DONE})"#
+        );
+        // Inside a block, the definition goes above it
+        assert_eq!(
+            extract(source, 2, 2).unwrap(),
+            r#"set(SRCS main.c)
+function(extracted_function SRCS)
+    list(APPEND SRCS win.c)
+    set(SRCS "${SRCS}" PARENT_SCOPE)
+endfunction()
+
+if(WIN32)
+    extracted_function("${SRCS}")
+endif()
+add_executable(app ${SRCS})
+message(STATUS "${NAME} in ${CMAKE_SOURCE_DIR}")
+set(DONE 1)
+message(${DONE})"#
+        );
+        // Half of a block cannot be extracted
+        assert!(extract(source, 1, 2).is_none());
+    }
+
+    #[test]
+    fn test_extract_file_and_inline() {
+        let dir = tempfile::tempdir().unwrap();
+        let local_path = dir.path().join("CMakeLists.txt");
+        let source = r#"project(demo)
+function(helper)
+  message(STATUS "helper")
+endfunction()
+if(WIN32)
+  add_definitions(-DWIN)
+  helper()
+endif()
+"#;
+        let range = Range {
+            start: Position::new(1, 0),
+            end: Position::new(4, 0),
+        };
+        let (name, content, edit) = extract_file_edit(source, range, dir.path()).unwrap();
+        assert_eq!(name, "extracted.cmake");
+        assert_eq!(
+            content,
+            "function(helper)\n  message(STATUS \"helper\")\nendfunction()\n"
+        );
+        assert_eq!(
+            edit.range,
+            rows_range(&source.lines().collect::<Vec<_>>(), 1, 3)
+        );
+        assert_eq!(
+            edit.new_text,
+            "include(${CMAKE_CURRENT_LIST_DIR}/extracted.cmake)"
+        );
+
+        std::fs::write(
+            dir.path().join(&name),
+            "add_definitions(-DWIN)\n\nhelper()\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("returns.cmake"), "return()\n").unwrap();
+        let source = r#"if(WIN32)
+  include(${CMAKE_CURRENT_LIST_DIR}/extracted.cmake)
+  include(returns.cmake)
+endif()
+include(CTest)
+"#;
+        let (path, edit) = inline_include_edit(source, &local_path, 1).unwrap();
+        assert_eq!(path, dir.path().join("extracted.cmake"));
+        assert_eq!(edit.new_text, "  add_definitions(-DWIN)\n\n  helper()");
+        // The commands would leave the includer, and modules are not inlined
+        assert!(inline_include_edit(source, &local_path, 2).is_none());
+        assert!(inline_include_edit(source, &local_path, 4).is_none());
+    }
+
+    #[test]
+    fn test_move_target() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::create_dir_all(dir.path().join("include")).unwrap();
+        // A link item named like a directory is left as it is
+        std::fs::create_dir_all(dir.path().join("fmt")).unwrap();
+        std::fs::write(dir.path().join("src/main.cpp"), "").unwrap();
+        std::fs::write(dir.path().join("src/util.cpp"), "").unwrap();
+        let local_path = dir.path().join("CMakeLists.txt");
+        let source = r#"project(demo)
+add_executable(app src/main.cpp "src/util.cpp")
+target_include_directories(app PRIVATE include)
+target_link_libraries(app PRIVATE fmt)
+target_link_libraries(other PRIVATE app)
+"#;
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let tree = parse.parse(source, None).unwrap();
+        let (declaration, target) = target_declaration(tree.root_node(), source, 1).unwrap();
+        assert_eq!(target, "app");
+        assert_eq!(
+            move_candidates(tree.root_node(), source, declaration, dir.path()),
+            vec!["src"]
+        );
+
+        let moved = move_target_edits(source, &local_path, 1, "src").unwrap();
+        assert!(moved.create);
+        assert_eq!(moved.lists, dir.path().join("src/CMakeLists.txt"));
+        assert_eq!(
+            moved.new_text,
+            r#"add_executable(app main.cpp "util.cpp")
+target_include_directories(app PRIVATE ../include)
+target_link_libraries(app PRIVATE fmt)
+"#
+        );
+        assert_eq!(moved.edits.len(), 3);
+        assert_eq!(moved.edits[0].new_text, "add_subdirectory(src)");
+        assert_eq!(
+            moved.edits[2].range,
+            Range::new(Position::new(3, 0), Position::new(4, 0))
+        );
+        // Only a declaration can be moved
+        assert!(move_target_edits(source, &local_path, 2, "src").is_none());
+    }
+
+    #[test]
+    fn test_source_group() {
+        let source = r#"add_library(core STATIC src/core.cpp ${GENERATED} src/core.cpp)
+target_link_libraries(core PRIVATE fmt)
+target_sources(core
+  PRIVATE ${CMAKE_CURRENT_SOURCE_DIR}/src/detail.cpp ../shared/log.cpp
+  PUBLIC FILE_SET HEADERS BASE_DIRS include FILES include/core.h
+)
+add_executable(tool $<TARGET_OBJECTS:core>)
+"#;
+        let (target, edit) = source_group_edit(source, 0).unwrap();
+        assert_eq!(target, "core");
+        assert_eq!(edit.range.start, Position::new(6, 0));
+        assert_eq!(
+            edit.new_text,
+            "source_group(TREE ${CMAKE_CURRENT_SOURCE_DIR} FILES\n  src/core.cpp\n  src/detail.cpp\n  include/core.h\n)\n"
+        );
+        // No source of its own directory to group
+        assert!(source_group_edit(source, 6).is_none());
+        assert!(source_group_edit(source, 1).is_none());
+    }
+}
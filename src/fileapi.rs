@@ -24,6 +24,11 @@ pub fn get_cache_data() -> Option<Cache> {
     let data = CACHE_DATA.lock().ok()?;
     data.clone()
 }
+/// Run `f` on the cache without cloning it
+pub fn with_cache_data<T>(f: impl FnOnce(&Cache) -> T) -> Option<T> {
+    let data = CACHE_DATA.lock().ok()?;
+    data.as_ref().map(f)
+}
 pub fn set_cache_data(cache: Cache) -> Option<Cache> {
     let mut data = CACHE_DATA.lock().ok()?;
     let old_data = data.take();
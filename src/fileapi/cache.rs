@@ -34,6 +34,14 @@ pub struct CacheEntry {
 }
 
 impl CacheEntry {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
     fn gen_completion(&self) -> CompletionItem {
         CompletionItem {
             label: self.name.clone(),
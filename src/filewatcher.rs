@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::{LazyLock, Mutex};
 
 // match like ss_DIR:PATH=ss_DIR-NOTFOUND
@@ -23,6 +23,24 @@ pub fn refresh_error_packages<P: AsRef<Path>>(cmake_cache: P) -> Option<Vec<Stri
     Some(toswap_packages)
 }
 
+/// The source directory of the project configured in `cmake_cache`, or the
+/// closest directory above it with a CMakeLists.txt when the cache is gone
+pub fn cache_project<P: AsRef<Path>>(cmake_cache: P) -> Option<PathBuf> {
+    let cmake_cache = cmake_cache.as_ref();
+    if let Ok(context) = std::fs::read_to_string(cmake_cache)
+        && let Some(home) = context
+            .lines()
+            .find_map(|line| line.strip_prefix("CMAKE_HOME_DIRECTORY:INTERNAL="))
+    {
+        return Some(PathBuf::from(home));
+    }
+    cmake_cache
+        .ancestors()
+        .skip(1)
+        .find(|dir| dir.join("CMakeLists.txt").is_file())
+        .map(Path::to_path_buf)
+}
+
 pub fn clear_error_packages() -> Option<Vec<String>> {
     let mut packages = ERROR_PACKAGES.lock().ok()?;
     let mut old_packages = vec![];
@@ -71,4 +89,22 @@ ss_DIR:PATH=ss_DIR-NOTFOUND
         let error_packages_after = get_error_packages();
         assert!(error_packages_after.is_empty());
     }
+
+    #[test]
+    fn test_cache_project() {
+        use tempfile::tempdir;
+        let dir = tempdir().unwrap();
+        let build = dir.path().join("build");
+        std::fs::create_dir(&build).unwrap();
+        std::fs::write(dir.path().join("CMakeLists.txt"), "project(demo)\n").unwrap();
+        let cache = build.join("CMakeCache.txt");
+        // Removed, the project is found above it
+        assert_eq!(cache_project(&cache).as_deref(), Some(dir.path()));
+        std::fs::write(
+            &cache,
+            "CMAKE_HOME_DIRECTORY:INTERNAL=/src/demo\nCMAKE_BUILD_TYPE:STRING=Debug\n",
+        )
+        .unwrap();
+        assert_eq!(cache_project(&cache), Some(PathBuf::from("/src/demo")));
+    }
 }
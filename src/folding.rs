@@ -0,0 +1,210 @@
+//! Folding ranges: the blocks of the syntax, the long comments and calls, and
+//! the regions marked by comments.
+//!
+//! A region runs from `# region <title>` to `# endregion`, and a section from a
+//! `#--` banner to the line before the next banner.
+
+use std::sync::LazyLock;
+
+use tower_lsp::lsp_types::{FoldingRange, FoldingRangeKind};
+use tree_sitter::Node;
+
+use crate::CMakeNodeKinds;
+use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+static REGION_REGEX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"(?i)^#\s*(?<end>end)?region\b\s*(?<title>.*)$").unwrap());
+
+/// The blocks folded up to the line of their terminator, which stays visible
+const BLOCKS: &[&str] = &[
+    CMakeNodeKinds::IF_CONDITION,
+    CMakeNodeKinds::FUNCTION_DEF,
+    CMakeNodeKinds::MACRO_DEF,
+    CMakeNodeKinds::FOREACH_LOOP,
+    CMakeNodeKinds::WHILE_LOOP,
+    CMakeNodeKinds::BLOCK_DEF,
+];
+
+fn range(start_line: usize, end_line: usize, kind: Option<FoldingRangeKind>) -> FoldingRange {
+    FoldingRange {
+        start_line: start_line as u32,
+        start_character: None,
+        end_line: end_line as u32,
+        end_character: None,
+        kind,
+        collapsed_text: None,
+    }
+}
+
+/// The syntactic ranges, and the line comments as (row, text)
+fn syntax_ranges<'a>(
+    node: Node,
+    source: &[&'a str],
+    ranges: &mut Vec<FoldingRange>,
+    comments: &mut Vec<(usize, &'a str)>,
+) {
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        let start = child.start_position().row;
+        let end = child.end_position().row;
+        match child.kind() {
+            CMakeNodeKinds::LINE_COMMENT => {
+                let line = source[start];
+                comments.push((start, line[child.start_position().column..].trim_end()));
+                continue;
+            }
+            CMakeNodeKinds::BRACKET_COMMENT if end > start => {
+                ranges.push(range(start, end, Some(FoldingRangeKind::Comment)));
+                continue;
+            }
+            kind if BLOCKS.contains(&kind) && end > start + 1 => {
+                ranges.push(range(start, end - 1, None));
+            }
+            CMakeNodeKinds::NORMAL_COMMAND if end > start => {
+                ranges.push(range(start, end, None));
+            }
+            _ => {}
+        }
+        syntax_ranges(child, source, ranges, comments);
+    }
+}
+
+/// Runs of at least two line comments, each alone on its line
+fn comment_blocks(comments: &[(usize, &str)], source: &[&str], ranges: &mut Vec<FoldingRange>) {
+    let alone = |row: usize| source[row].trim_start().starts_with('#');
+    let mut index = 0;
+    while index < comments.len() {
+        let first = comments[index].0;
+        let mut last = first;
+        while index + 1 < comments.len()
+            && comments[index + 1].0 == last + 1
+            && alone(comments[index + 1].0)
+        {
+            index += 1;
+            last = comments[index].0;
+        }
+        if last > first && alone(first) {
+            ranges.push(range(first, last, Some(FoldingRangeKind::Comment)));
+        }
+        index += 1;
+    }
+}
+
+fn region_ranges(comments: &[(usize, &str)], ranges: &mut Vec<FoldingRange>) {
+    let mut open: Vec<(usize, String)> = vec![];
+    for (row, text) in comments {
+        let Some(caps) = REGION_REGEX.captures(text) else {
+            continue;
+        };
+        if caps.name("end").is_none() {
+            open.push((*row, caps["title"].trim().to_string()));
+            continue;
+        }
+        if let Some((start, title)) = open.pop() {
+            let mut region = range(start, *row, Some(FoldingRangeKind::Region));
+            if !title.is_empty() {
+                region.collapsed_text = Some(title);
+            }
+            ranges.push(region);
+        }
+    }
+}
+
+fn is_banner(text: &str) -> bool {
+    text.starts_with("#--")
+}
+
+/// From each banner to the line before the next one, the banner being the
+/// `#--` lines with the comment lines between them
+fn section_ranges(comments: &[(usize, &str)], source: &[&str], ranges: &mut Vec<FoldingRange>) {
+    let mut headers: Vec<usize> = vec![];
+    let mut last_banner: Option<usize> = None;
+    for (row, text) in comments {
+        if !is_banner(text) || !source[*row].starts_with('#') {
+            continue;
+        }
+        // The closing line of a banner, only comments since the opening one
+        let closes = last_banner.is_some_and(|last| {
+            headers.last() == Some(&last)
+                && (last + 1..*row).all(|between| source[between].trim_start().starts_with('#'))
+        });
+        if !closes {
+            headers.push(*row);
+        }
+        last_banner = Some(*row);
+    }
+    for (index, start) in headers.iter().enumerate() {
+        let mut end = headers
+            .get(index + 1)
+            .map_or(source.len(), |next| *next)
+            .saturating_sub(1);
+        while end > *start && source[end].trim().is_empty() {
+            end -= 1;
+        }
+        if end > *start {
+            ranges.push(range(*start, end, Some(FoldingRangeKind::Region)));
+        }
+    }
+}
+
+pub fn folding_ranges(source: &str) -> Option<Vec<FoldingRange>> {
+    let mut parse = tree_sitter::Parser::new();
+    parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+    let tree = parse.parse(source, None)?;
+    let lines: Vec<&str> = source.lines().collect();
+    let mut ranges = vec![];
+    let mut comments = vec![];
+    syntax_ranges(tree.root_node(), &lines, &mut ranges, &mut comments);
+    comment_blocks(&comments, &lines, &mut ranges);
+    region_ranges(&comments, &mut ranges);
+    section_ranges(&comments, &lines, &mut ranges);
+    if ranges.is_empty() {
+        return None;
+    }
+    ranges.sort_by_key(|range| (range.start_line, std::cmp::Reverse(range.end_line)));
+    Some(ranges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_folding_ranges() {
+        let source = r#"#----------
+# Options
+#----------
+option(A "a" ON)
+
+# region Sources
+if(A)
+  set(SRC
+    a.cpp
+    b.cpp)
+endif()
+# endregion
+
+#----------
+# Install
+#----------
+install(TARGETS app)
+"#;
+        let ranges: Vec<(u32, u32, Option<FoldingRangeKind>)> = folding_ranges(source)
+            .unwrap()
+            .into_iter()
+            .map(|range| (range.start_line, range.end_line, range.kind))
+            .collect();
+        assert_eq!(
+            ranges,
+            vec![
+                (0, 11, Some(FoldingRangeKind::Region)),
+                (0, 2, Some(FoldingRangeKind::Comment)),
+                (5, 11, Some(FoldingRangeKind::Region)),
+                (6, 9, None),
+                (7, 9, None),
+                (13, 16, Some(FoldingRangeKind::Region)),
+                (13, 15, Some(FoldingRangeKind::Comment)),
+            ]
+        );
+    }
+}
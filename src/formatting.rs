@@ -1,19 +1,32 @@
-use std::fs::File;
-use std::io::Write;
+use std::collections::HashSet;
+use std::io::{Read, Write};
 use std::path::Path;
 use std::process::Stdio;
 
 use anyhow::{Context, Result};
-use lsp_types::{MessageType, Position, TextEdit};
+use lsp_types::{MessageType, TextEdit};
 use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
 use tower_lsp::lsp_types;
 
 use crate::CMakeNodeKinds;
-use crate::config::CONFIG;
+use crate::config::{self, CONFIG};
 use crate::consts::TREESITTER_CMAKE_LANGUAGE;
 use crate::utils::treehelper::contain_comment;
 
+pub mod case;
+pub mod compat;
+mod diff;
+pub mod layout;
+pub mod whitespace;
+pub mod workspace;
+
+use self::case::{CaseStyle, normalize_case};
+use self::compat::{FOREIGN_STYLE, Indent};
+use self::diff::diff_edits;
+use self::layout::apply_layout;
+use self::whitespace::{WhitespaceStyle, normalize_whitespace, protected_rows};
+
 const CLOSURE: &[&str] = &[
     CMakeNodeKinds::FUNCTION_DEF,
     CMakeNodeKinds::MACRO_DEF,
@@ -21,22 +34,54 @@ const CLOSURE: &[&str] = &[
     CMakeNodeKinds::FOREACH_LOOP,
 ];
 
-// TODO: Maybe make this async and run formatting in parallel.
+/// Indentation of the cmake-format or gersemi config, as the size and whether spaces are used
+pub(crate) fn foreign_indent() -> Option<(u32, bool)> {
+    FOREIGN_STYLE.indent.map(|indent| match indent {
+        Indent::Spaces(size) => (size, true),
+        Indent::Tabs => (1, false),
+    })
+}
+
+/// Print the formatted content of `path`
 pub(crate) fn format_file(
     path: &Path,
-    inplace: bool,
     use_space: bool,
     indent_size: u32,
     insert_final_newline: bool,
 ) -> Result<()> {
     let content = std::fs::read_to_string(path)?;
-    let formatted_content = get_format_cli(&content, indent_size, use_space, insert_final_newline)?;
-    if inplace {
-        let mut file = File::open(path)?;
-        file.write_all(formatted_content.as_bytes())?;
-    } else {
-        println!("{formatted_content}");
-    }
+    let formatted_content = get_format_cli(
+        Some(path),
+        &content,
+        indent_size,
+        use_space,
+        insert_final_newline,
+    )?;
+    println!("{formatted_content}");
+    Ok(())
+}
+
+/// Format the content of stdin to stdout, for the editors and the git filters
+/// piping a buffer. `path` is where the content comes from, for its config files.
+pub(crate) fn format_stdin(
+    path: Option<&Path>,
+    use_space: bool,
+    indent_size: u32,
+    insert_final_newline: bool,
+) -> Result<()> {
+    let mut content = String::new();
+    std::io::stdin().read_to_string(&mut content)?;
+    let path = path.map(std::path::absolute).transpose()?;
+    let formatted_content = get_format_cli(
+        path.as_deref(),
+        &content,
+        indent_size,
+        use_space,
+        insert_final_newline,
+    )?;
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(formatted_content.as_bytes())?;
+    stdout.flush()?;
     Ok(())
 }
 
@@ -119,15 +164,17 @@ fn get_space(spacelen: u32, use_space: bool) -> String {
 
 pub async fn getformat(
     root_path: Option<&Path>,
+    local_path: Option<&Path>,
     source: &str,
     client: &tower_lsp::Client,
     spacelen: u32,
     use_space: bool,
     insert_final_newline: bool,
 ) -> Option<Vec<TextEdit>> {
-    if let Some(program) = &CONFIG.format.program {
+    let format = config::format_config(local_path);
+    if let Some(program) = &format.program {
         let mut cmd = Command::new(program);
-        if let Some(ref args) = CONFIG.format.args {
+        if let Some(ref args) = format.args {
             cmd.args(args);
         }
         if let Some(root_path) = root_path {
@@ -208,26 +255,11 @@ pub async fn getformat(
             Ok(new_source) => new_source,
         };
 
-        let lines = std::cmp::max(
-            source.chars().filter(|c| *c == '\n').count(),
-            new_source.chars().filter(|c| *c == '\n').count(),
-        );
-
-        return Some(vec![TextEdit {
-            range: lsp_types::Range {
-                start: Position {
-                    line: 0,
-                    character: 0,
-                },
-                end: Position {
-                    line: (lines + 1) as u32,
-                    character: 0,
-                },
-            },
-            new_text: new_source,
-        }]);
+        return Some(diff_edits(source, &new_source));
     }
 
+    // The project config wins over the options of the editor
+    let (spacelen, use_space) = foreign_indent().unwrap_or((spacelen, use_space));
     let mut parse = tree_sitter::Parser::new();
     parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
     let tree = parse.parse(source, None).unwrap();
@@ -238,6 +270,8 @@ pub async fn getformat(
             .await;
         return None;
     }
+    let original = source;
+    let source = &normalize_case(source, tree.root_node(), &CaseStyle::configured(&format));
     let (mut new_text, endline) = format_content(
         tree.root_node(),
         &source.lines().collect(),
@@ -250,27 +284,21 @@ pub async fn getformat(
     for _ in endline..source.lines().count() {
         new_text.push('\n');
     }
+    let mut new_text = apply_layout(
+        &new_text,
+        &get_space(spacelen, use_space),
+        CONFIG.line_max_words(),
+        |command| format.layout_of(command),
+    );
 
     if insert_final_newline && new_text.chars().last().is_some_and(|c| c != '\n') {
         new_text.push('\n');
     }
-
-    let len_count = new_text.lines().count();
-    let len_origin = source.lines().count();
-    let len = std::cmp::max(len_count, len_origin);
-    Some(vec![TextEdit {
-        range: lsp_types::Range {
-            start: Position {
-                line: 0,
-                character: 0,
-            },
-            end: Position {
-                line: len as u32,
-                character: 0,
-            },
-        },
-        new_text,
-    }])
+    let new_text = normalize_whitespace(
+        &new_text,
+        &WhitespaceStyle::configured(&format, insert_final_newline),
+    );
+    Some(diff_edits(original, &new_text))
 }
 
 fn format_content(
@@ -355,14 +383,33 @@ fn format_content(
         endline = end_position.row;
         lastendline = end_position.row;
 
+        // The whitespace inside a multi-line string or bracket argument is
+        // part of its value
+        let mut protected = HashSet::new();
+        protected_rows(child, &mut protected);
         for (index, currentline) in newsource
             .iter()
             .take(end_row + 1)
             .skip(start_row)
             .enumerate()
         {
-            let currentline = pre_format(currentline, start_row + index, child, input);
-            let currentline = currentline.trim_end();
+            let row = start_row + index;
+            let ends_inside = protected.contains(&row);
+            if index != 0 && protected.contains(&(row - 1)) {
+                new_text.push_str(if ends_inside {
+                    currentline
+                } else {
+                    currentline.trim_end()
+                });
+                new_text.push('\n');
+                continue;
+            }
+            let currentline = pre_format(currentline, row, child, input);
+            let currentline = if ends_inside {
+                currentline.as_str()
+            } else {
+                currentline.trim_end()
+            };
             let trimapter = currentline.trim_start();
             let spacesize = currentline.len() - trimapter.len();
             let mut newline = if index != 0 {
@@ -375,16 +422,16 @@ fn format_content(
                 firstline
             };
 
-            let startsource = currentline
-                .trim_start()
-                .trim_end()
-                .split(' ')
-                .collect::<Vec<&str>>();
+            let startsource = trimapter.split(' ').collect::<Vec<&str>>();
             for unit in startsource {
                 newline.push_str(unit);
                 newline.push(' ');
             }
-            let newline = newline.trim_end();
+            let newline = if ends_inside {
+                &newline[..newline.len() - 1]
+            } else {
+                newline.trim_end()
+            };
             new_text.push_str(newline);
             new_text.push('\n');
         }
@@ -396,6 +443,7 @@ fn format_content(
 
 // Only source from cli need do normalize first
 pub fn get_format_cli(
+    local_path: Option<&Path>,
     source: &str,
     indent_size: u32,
     use_space: bool,
@@ -406,6 +454,8 @@ pub fn get_format_cli(
     let tree = parser
         .parse(source, None)
         .context("Failed to parse CMake file into tree")?;
+    let format = config::format_config(local_path);
+    let source = &normalize_case(source, tree.root_node(), &CaseStyle::configured(&format));
     let (mut new_text, endline) = format_content(
         tree.root_node(),
         &source.lines().collect(),
@@ -418,10 +468,20 @@ pub fn get_format_cli(
     for _ in endline..source.lines().count() {
         new_text.push('\n');
     }
+    let mut new_text = apply_layout(
+        &new_text,
+        &get_space(indent_size, use_space),
+        CONFIG.line_max_words(),
+        |command| format.layout_of(command),
+    );
 
     if insert_final_newline && new_text.chars().last().is_some_and(|c| c != '\n') {
         new_text.push('\n');
     }
+    let new_text = normalize_whitespace(
+        &new_text,
+        &WhitespaceStyle::configured(&format, insert_final_newline),
+    );
     Ok(new_text)
 }
 
@@ -433,8 +493,8 @@ mod tests {
     fn test_format_function() {
         let source = include_str!("../assets_for_test/function/formatbefore.cmake");
         let sourceafter = include_str!("../assets_for_test/function/formatafter.cmake");
-        let formatestr = get_format_cli(source, 1, false, false).unwrap();
-        let formatestr_with_lastline = get_format_cli(source, 1, false, true).unwrap();
+        let formatestr = get_format_cli(None, source, 1, false, false).unwrap();
+        let formatestr_with_lastline = get_format_cli(None, source, 1, false, true).unwrap();
         assert_eq!(formatestr.as_str(), sourceafter);
         assert_eq!(formatestr_with_lastline.as_str(), sourceafter);
     }
@@ -443,8 +503,8 @@ mod tests {
     fn test_format_base() {
         let source = include_str!("../assets_for_test/base/formatbefore.cmake");
         let sourceafter = include_str!("../assets_for_test/base/formatafter.cmake");
-        let formatestr = get_format_cli(source, 1, false, false).unwrap();
-        let formatestr_with_lastline = get_format_cli(source, 1, false, true).unwrap();
+        let formatestr = get_format_cli(None, source, 1, false, false).unwrap();
+        let formatestr_with_lastline = get_format_cli(None, source, 1, false, true).unwrap();
         assert_eq!(formatestr.as_str(), sourceafter);
         assert_eq!(formatestr_with_lastline.as_str(), sourceafter);
     }
@@ -453,9 +513,17 @@ mod tests {
     fn test_format_lastline() {
         let source = include_str!("../assets_for_test/lastline/before.cmake");
         let sourceafter = include_str!("../assets_for_test/lastline/after.cmake");
-        let formatestr = get_format_cli(source, 4, true, false).unwrap();
-        let formatestr_with_lastline = get_format_cli(source, 4, true, true).unwrap();
+        let formatestr = get_format_cli(None, source, 4, true, false).unwrap();
+        let formatestr_with_lastline = get_format_cli(None, source, 4, true, true).unwrap();
         assert_eq!(formatestr.as_str(), sourceafter);
         assert_eq!(formatestr_with_lastline.as_str(), sourceafter);
     }
+
+    #[test]
+    fn test_format_multiline_values() {
+        let source = "function(demo)\nmessage([=[\n  keep   these  \n    spaces\n]=] \"and\n  this \")\nendfunction()\n";
+        let sourceafter = "function(demo)\n\tmessage([=[\n  keep   these  \n    spaces\n]=] \"and\n  this \")\nendfunction()\n";
+        let formatestr = get_format_cli(None, source, 1, false, false).unwrap();
+        assert_eq!(formatestr.as_str(), sourceafter);
+    }
 }
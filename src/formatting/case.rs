@@ -0,0 +1,393 @@
+//! Letter case of the command names and keywords written by the formatter.
+//!
+//! Only the identifiers of commands and the unquoted arguments in a keyword
+//! position are rewritten: strings, comments and variable references are other
+//! nodes of the tree, so they are never touched. The rewrite only changes the
+//! case of ASCII letters, which keeps every byte offset of the tree valid.
+
+use tree_sitter::Node;
+
+use super::compat::{Case, FOREIGN_STYLE};
+use crate::CMakeNodeKinds;
+use crate::config::FormatConfig;
+
+/// The nodes which are the name of a flow control command
+const FLOW_COMMANDS: &[&str] = &[
+    "if",
+    "elseif",
+    "else",
+    "endif",
+    "foreach",
+    "endforeach",
+    "while",
+    "endwhile",
+    "function",
+    "endfunction",
+    "macro",
+    "endmacro",
+    "block",
+    "endblock",
+];
+
+/// The operators of `if()` and `while()` taking the argument after them
+pub const UNARY_OPERATORS: &[&str] = &[
+    "NOT",
+    "DEFINED",
+    "EXISTS",
+    "COMMAND",
+    "POLICY",
+    "TARGET",
+    "TEST",
+    "IS_DIRECTORY",
+    "IS_SYMLINK",
+    "IS_ABSOLUTE",
+    "IS_READABLE",
+    "IS_WRITABLE",
+    "IS_EXECUTABLE",
+];
+
+/// The operators of `if()` and `while()` between two arguments
+pub const BINARY_OPERATORS: &[&str] = &[
+    "AND",
+    "OR",
+    "EQUAL",
+    "LESS",
+    "LESS_EQUAL",
+    "GREATER",
+    "GREATER_EQUAL",
+    "STREQUAL",
+    "STRLESS",
+    "STRLESS_EQUAL",
+    "STRGREATER",
+    "STRGREATER_EQUAL",
+    "VERSION_EQUAL",
+    "VERSION_LESS",
+    "VERSION_LESS_EQUAL",
+    "VERSION_GREATER",
+    "VERSION_GREATER_EQUAL",
+    "PATH_EQUAL",
+    "MATCHES",
+    "IN_LIST",
+    "IS_NEWER_THAN",
+];
+
+const SCOPE_KEYWORDS: &[&str] = &[
+    "PUBLIC",
+    "PRIVATE",
+    "INTERFACE",
+    "BEFORE",
+    "AFTER",
+    "SYSTEM",
+];
+
+/// The keywords of the commands, and whether the first argument is a name
+/// which is never a keyword, such as the target of `add_library`
+const COMMAND_KEYWORDS: &[(&str, bool, &[&str])] = &[
+    ("cmake_minimum_required", false, &["VERSION", "FATAL_ERROR"]),
+    (
+        "project",
+        true,
+        &["VERSION", "DESCRIPTION", "HOMEPAGE_URL", "LANGUAGES"],
+    ),
+    (
+        "find_package",
+        true,
+        &[
+            "EXACT",
+            "QUIET",
+            "MODULE",
+            "CONFIG",
+            "NO_MODULE",
+            "REQUIRED",
+            "COMPONENTS",
+            "OPTIONAL_COMPONENTS",
+            "GLOBAL",
+            "NAMES",
+            "HINTS",
+            "PATHS",
+            "PATH_SUFFIXES",
+            "NO_DEFAULT_PATH",
+        ],
+    ),
+    (
+        "add_library",
+        true,
+        &[
+            "STATIC",
+            "SHARED",
+            "MODULE",
+            "OBJECT",
+            "INTERFACE",
+            "IMPORTED",
+            "ALIAS",
+            "GLOBAL",
+            "EXCLUDE_FROM_ALL",
+        ],
+    ),
+    (
+        "add_executable",
+        true,
+        &[
+            "WIN32",
+            "MACOSX_BUNDLE",
+            "IMPORTED",
+            "ALIAS",
+            "GLOBAL",
+            "EXCLUDE_FROM_ALL",
+        ],
+    ),
+    ("target_link_libraries", true, SCOPE_KEYWORDS),
+    ("target_include_directories", true, SCOPE_KEYWORDS),
+    ("target_compile_definitions", true, SCOPE_KEYWORDS),
+    ("target_compile_options", true, SCOPE_KEYWORDS),
+    ("target_compile_features", true, SCOPE_KEYWORDS),
+    ("target_link_options", true, SCOPE_KEYWORDS),
+    ("target_sources", true, SCOPE_KEYWORDS),
+    ("target_precompile_headers", true, SCOPE_KEYWORDS),
+    ("set_target_properties", false, &["PROPERTIES"]),
+    (
+        "foreach",
+        true,
+        &["IN", "ITEMS", "LISTS", "RANGE", "ZIP_LISTS"],
+    ),
+    (
+        "install",
+        false,
+        &[
+            "TARGETS",
+            "FILES",
+            "PROGRAMS",
+            "DIRECTORY",
+            "EXPORT",
+            "DESTINATION",
+            "RUNTIME",
+            "LIBRARY",
+            "ARCHIVE",
+            "COMPONENT",
+            "OPTIONAL",
+            "NAMESPACE",
+            "INCLUDES",
+        ],
+    ),
+    (
+        "add_custom_command",
+        false,
+        &[
+            "OUTPUT",
+            "TARGET",
+            "PRE_BUILD",
+            "PRE_LINK",
+            "POST_BUILD",
+            "COMMAND",
+            "DEPENDS",
+            "BYPRODUCTS",
+            "WORKING_DIRECTORY",
+            "COMMENT",
+            "VERBATIM",
+            "USES_TERMINAL",
+        ],
+    ),
+    (
+        "add_custom_target",
+        true,
+        &[
+            "ALL",
+            "COMMAND",
+            "DEPENDS",
+            "BYPRODUCTS",
+            "WORKING_DIRECTORY",
+            "COMMENT",
+            "VERBATIM",
+            "USES_TERMINAL",
+        ],
+    ),
+];
+
+/// The letter cases the formatter writes
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CaseStyle {
+    pub command_case: Option<Case>,
+    pub keyword_case: Option<Case>,
+}
+
+impl CaseStyle {
+    /// From the `[format]` options, else from the cmake-format or gersemi config
+    pub fn configured(format: &FormatConfig) -> Self {
+        Self {
+            command_case: format.command_case.or(FOREIGN_STYLE.command_case),
+            keyword_case: format.keyword_case.or(FOREIGN_STYLE.keyword_case),
+        }
+    }
+
+    fn is_preserve(&self) -> bool {
+        !matches!(self.command_case, Some(Case::Lower | Case::Upper))
+            && self.keyword_case != Some(Case::Upper)
+    }
+}
+
+fn set_case(output: &mut String, node: Node, case: Case) {
+    let range = node.byte_range();
+    let text = match case {
+        Case::Lower => output[range.clone()].to_ascii_lowercase(),
+        Case::Upper => output[range.clone()].to_ascii_uppercase(),
+        Case::Preserve => return,
+    };
+    output.replace_range(range, &text);
+}
+
+/// The plain words of the argument list: the unquoted arguments without
+/// variable references or escapes, and `None` for the other arguments
+fn words<'t, 's>(argument_list: Node<'t>, source: &'s str) -> Vec<(Node<'t>, Option<&'s str>)> {
+    let mut walk = argument_list.walk();
+    argument_list
+        .children(&mut walk)
+        .filter(|node| node.kind() == CMakeNodeKinds::ARGUMENT)
+        .map(|node| {
+            let word = node
+                .child(0)
+                .filter(|child| {
+                    child.kind() == CMakeNodeKinds::UNQUOTED_ARGUMENT && child.child_count() == 0
+                })
+                .map(|child| &source[child.byte_range()]);
+            (node, word)
+        })
+        .collect()
+}
+
+/// The arguments of a command which are keywords
+fn keywords<'a>(command: &str, argument_list: Node<'a>, source: &str) -> Vec<Node<'a>> {
+    let words = words(argument_list, source);
+    let mut output = vec![];
+    if matches!(command, "if" | "elseif" | "while") {
+        for (index, (node, word)) in words.iter().enumerate() {
+            let Some(word) = word.map(str::to_ascii_uppercase) else {
+                continue;
+            };
+            let unary = UNARY_OPERATORS.contains(&word.as_str()) && index + 1 < words.len();
+            let binary =
+                BINARY_OPERATORS.contains(&word.as_str()) && index > 0 && index + 1 < words.len();
+            if unary || binary {
+                output.push(*node);
+            }
+        }
+        return output;
+    }
+    let Some((_, first_is_name, known)) = COMMAND_KEYWORDS
+        .iter()
+        .find(|(name, _, _)| *name == command)
+    else {
+        return output;
+    };
+    let skip = usize::from(*first_is_name);
+    for (node, word) in words.iter().skip(skip) {
+        if let Some(word) = word
+            && known.contains(&word.to_ascii_uppercase().as_str())
+        {
+            output.push(*node);
+        }
+    }
+    output
+}
+
+fn normalize_inner(output: &mut String, node: Node, style: &CaseStyle) {
+    let mut cursor = node.walk();
+    let children: Vec<Node> = node.children(&mut cursor).collect();
+    let command = children.iter().find_map(|child| {
+        if child.kind() == CMakeNodeKinds::IDENTIFIER {
+            Some(output[child.byte_range()].to_ascii_lowercase())
+        } else if FLOW_COMMANDS.contains(&child.kind()) {
+            Some(child.kind().to_string())
+        } else {
+            None
+        }
+    });
+    for child in &children {
+        let is_name =
+            child.kind() == CMakeNodeKinds::IDENTIFIER || FLOW_COMMANDS.contains(&child.kind());
+        if is_name {
+            if let Some(case) = style.command_case {
+                set_case(output, *child, case);
+            }
+            continue;
+        }
+        if child.kind() == CMakeNodeKinds::ARGUMENT_LIST {
+            if style.keyword_case == Some(Case::Upper)
+                && let Some(command) = &command
+            {
+                let source = output.clone();
+                for keyword in keywords(command, *child, &source) {
+                    set_case(output, keyword, Case::Upper);
+                }
+            }
+            continue;
+        }
+        normalize_inner(output, *child, style);
+    }
+}
+
+/// Rewrite the command names and keywords of `source`, parsed as `root`.
+/// Keywords are only written in upper case, as CMake only knows them so.
+pub fn normalize_case(source: &str, root: Node, style: &CaseStyle) -> String {
+    let mut output = source.to_string();
+    if !style.is_preserve() {
+        normalize_inner(&mut output, root, style);
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    fn normalize(source: &str, style: CaseStyle) -> String {
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let tree = parse.parse(source, None).unwrap();
+        normalize_case(source, tree.root_node(), &style)
+    }
+
+    #[test]
+    fn test_normalize_case() {
+        let source = r#"IF(not DEFINED Foo_DIR)
+  Find_Package(Foo Config required) # Find_Package(Foo)
+  Message(STATUS "If(x) public")
+EndIf()
+Target_Link_Libraries(public public ${private} "private")
+"#;
+        let style = CaseStyle {
+            command_case: Some(Case::Lower),
+            keyword_case: Some(Case::Upper),
+        };
+        assert_eq!(
+            normalize(source, style),
+            r#"if(NOT DEFINED Foo_DIR)
+  find_package(Foo CONFIG REQUIRED) # Find_Package(Foo)
+  message(STATUS "If(x) public")
+endif()
+target_link_libraries(public PUBLIC ${private} "private")
+"#
+        );
+        let style = CaseStyle {
+            command_case: Some(Case::Upper),
+            keyword_case: Some(Case::Preserve),
+        };
+        assert_eq!(
+            normalize("if(not x)\nendif()\n", style),
+            "IF(not x)\nENDIF()\n"
+        );
+        let style = CaseStyle {
+            command_case: Some(Case::Preserve),
+            keyword_case: Some(Case::Upper),
+        };
+        assert_eq!(
+            normalize("While(a less b)\nendwhile()\n", style),
+            "While(a LESS b)\nendwhile()\n"
+        );
+        let style = CaseStyle {
+            command_case: Some(Case::Preserve),
+            keyword_case: None,
+        };
+        assert_eq!(normalize(source, style), source);
+    }
+}
@@ -0,0 +1,238 @@
+//! Settings read from the config files of cmake-format and gersemi.
+//!
+//! Teams moving from one of these tools keep their settings: the options with a
+//! native equivalent are taken from `.cmake-format.yaml` (or `.yml`, `.json`) and
+//! `.gersemirc`, unless the neocmakelsp config sets them itself.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indent {
+    Spaces(u32),
+    Tabs,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Case {
+    Lower,
+    Upper,
+    Preserve,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ForeignStyle {
+    pub line_width: Option<usize>,
+    pub indent: Option<Indent>,
+    pub command_case: Option<Case>,
+    pub keyword_case: Option<Case>,
+}
+
+const CMAKE_FORMAT_FILES: &[&str] = &[
+    ".cmake-format.yaml",
+    ".cmake-format.yml",
+    ".cmake-format.json",
+    "cmake-format.yaml",
+    "cmake-format.json",
+];
+
+const GERSEMI_FILES: &[&str] = &[".gersemirc"];
+
+/// The `key: value` pairs of a YAML file, nested keys joined with a dot.
+/// Only the flat mappings these config files are made of are understood.
+fn parse_simple_yaml(content: &str) -> HashMap<String, String> {
+    let mut output = HashMap::new();
+    // Indentation and name of the mappings the current line is nested in
+    let mut parents: Vec<(usize, String)> = vec![];
+    for line in content.lines() {
+        let line = match line.find(" #") {
+            Some(index) => &line[..index],
+            None if line.trim_start().starts_with('#') => "",
+            None => line,
+        };
+        if line.trim().is_empty() || line.trim() == "---" {
+            continue;
+        }
+        let indent = line.len() - line.trim_start().len();
+        let Some((key, value)) = line.trim().split_once(':') else {
+            continue;
+        };
+        while parents.last().is_some_and(|(level, _)| *level >= indent) {
+            parents.pop();
+        }
+        let key = key.trim().trim_matches(['"', '\'']);
+        let value = value.trim().trim_matches(['"', '\'']);
+        if value.is_empty() {
+            parents.push((indent, key.to_string()));
+            continue;
+        }
+        let mut full_key: Vec<&str> = parents.iter().map(|(_, name)| name.as_str()).collect();
+        full_key.push(key);
+        output.insert(full_key.join("."), value.to_string());
+    }
+    output
+}
+
+/// The values of a JSON file, nested keys joined with a dot
+fn flatten_json(value: &serde_json::Value, prefix: &str, output: &mut HashMap<String, String>) {
+    let serde_json::Value::Object(map) = value else {
+        return;
+    };
+    for (key, value) in map {
+        let full_key = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{prefix}.{key}")
+        };
+        match value {
+            serde_json::Value::Object(_) => flatten_json(value, &full_key, output),
+            serde_json::Value::String(text) => {
+                output.insert(full_key, text.clone());
+            }
+            other => {
+                output.insert(full_key, other.to_string());
+            }
+        }
+    }
+}
+
+fn parse_case(value: &str) -> Option<Case> {
+    match value {
+        "lower" | "canonical" => Some(Case::Lower),
+        "upper" => Some(Case::Upper),
+        "unchanged" | "preserve" => Some(Case::Preserve),
+        _ => None,
+    }
+}
+
+/// cmake-format reads its options at the top level or in the `format` section
+fn cmake_format_style(values: &HashMap<String, String>) -> ForeignStyle {
+    let get = |key: &str| {
+        values
+            .get(&format!("format.{key}"))
+            .or_else(|| values.get(key))
+            .map(String::as_str)
+    };
+    let indent = if get("use_tabchars") == Some("true") {
+        Some(Indent::Tabs)
+    } else {
+        get("tab_size")
+            .and_then(|size| size.parse().ok())
+            .map(Indent::Spaces)
+    };
+    ForeignStyle {
+        line_width: get("line_width").and_then(|width| width.parse().ok()),
+        indent,
+        command_case: get("command_case").and_then(parse_case),
+        keyword_case: get("keyword_case").and_then(parse_case),
+    }
+}
+
+/// gersemi has no case options, command names are always lower case
+fn gersemi_style(values: &HashMap<String, String>) -> ForeignStyle {
+    let indent = values
+        .get("indent")
+        .and_then(|indent| match indent.as_str() {
+            "tabs" => Some(Indent::Tabs),
+            size => size.parse().ok().map(Indent::Spaces),
+        });
+    ForeignStyle {
+        line_width: values
+            .get("line_length")
+            .and_then(|width| width.parse().ok()),
+        indent,
+        command_case: Some(Case::Lower),
+        keyword_case: None,
+    }
+}
+
+/// The style of the first cmake-format or gersemi config file found in `dir`
+pub fn find_foreign_style(dir: &Path) -> Option<ForeignStyle> {
+    for file in CMAKE_FORMAT_FILES {
+        let path = dir.join(file);
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        let values = if file.ends_with(".json") {
+            let mut values = HashMap::new();
+            flatten_json(&serde_json::from_str(&content).ok()?, "", &mut values);
+            values
+        } else {
+            parse_simple_yaml(&content)
+        };
+        tracing::info!("Using the cmake-format config file: {:?}", path);
+        return Some(cmake_format_style(&values));
+    }
+    for file in GERSEMI_FILES {
+        let path = dir.join(file);
+        if let Ok(content) = std::fs::read_to_string(&path) {
+            tracing::info!("Using the gersemi config file: {:?}", path);
+            return Some(gersemi_style(&parse_simple_yaml(&content)));
+        }
+    }
+    None
+}
+
+/// Found in the directory the server runs in, like the neocmakelsp config
+pub static FOREIGN_STYLE: LazyLock<ForeignStyle> = LazyLock::new(|| {
+    std::env::current_dir()
+        .ok()
+        .and_then(|dir| find_foreign_style(&dir))
+        .unwrap_or_default()
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cmake_format_yaml() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(".cmake-format.yaml"),
+            "# generated\nformat:\n  line_width: 100 # wide\n  tab_size: 4\n  command_case: 'canonical'\n  keyword_case: upper\nmarkup:\n  enable_markup: false\n",
+        )
+        .unwrap();
+        assert_eq!(
+            find_foreign_style(dir.path()),
+            Some(ForeignStyle {
+                line_width: Some(100),
+                indent: Some(Indent::Spaces(4)),
+                command_case: Some(Case::Lower),
+                keyword_case: Some(Case::Upper),
+            })
+        );
+    }
+
+    #[test]
+    fn test_cmake_format_json() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(".cmake-format.json"),
+            r#"{"format": {"use_tabchars": true, "command_case": "unchanged"}}"#,
+        )
+        .unwrap();
+        let style = find_foreign_style(dir.path()).unwrap();
+        assert_eq!(style.indent, Some(Indent::Tabs));
+        assert_eq!(style.command_case, Some(Case::Preserve));
+        assert_eq!(style.line_width, None);
+    }
+
+    #[test]
+    fn test_gersemirc() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(".gersemirc"),
+            "line_length: 120\nindent: 2\ndefinitions: [cmake]\n",
+        )
+        .unwrap();
+        let style = find_foreign_style(dir.path()).unwrap();
+        assert_eq!(style.line_width, Some(120));
+        assert_eq!(style.indent, Some(Indent::Spaces(2)));
+        assert_eq!(style.command_case, Some(Case::Lower));
+    }
+}
@@ -0,0 +1,150 @@
+//! The formatting as a list of edits of the lines which changed.
+//!
+//! Replacing the whole document moves the cursor and resets the folds of most
+//! clients, and undoes as one block. The lines are matched with a longest
+//! common subsequence, after the common head and tail are left out.
+
+use tower_lsp::lsp_types::{Position, Range, TextEdit};
+
+/// Above this many cells the middle of the files is replaced at once
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// The lines of `text`, with their line break
+fn lines_of(text: &str) -> Vec<&str> {
+    text.split_inclusive('\n').collect()
+}
+
+/// Whether each line of `old` and `new` is kept, from their longest common subsequence
+fn common_lines(old: &[&str], new: &[&str]) -> (Vec<bool>, Vec<bool>) {
+    let width = new.len() + 1;
+    // lengths[i * width + j]: longest common subsequence of old[i..] and new[j..]
+    let mut lengths = vec![0u32; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i * width + j] = if old[i] == new[j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+    let mut old_kept = vec![false; old.len()];
+    let mut new_kept = vec![false; new.len()];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            old_kept[i] = true;
+            new_kept[j] = true;
+            i += 1;
+            j += 1;
+        } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    (old_kept, new_kept)
+}
+
+/// Where line `row` of `old` starts, the end of the text past its last line
+fn line_start(old: &[&str], row: usize) -> Position {
+    match old.last() {
+        Some(last) if row == old.len() && !last.ends_with('\n') => Position {
+            line: (row - 1) as u32,
+            character: last.len() as u32,
+        },
+        _ => Position {
+            line: row as u32,
+            character: 0,
+        },
+    }
+}
+
+/// The edits turning `old` into `new`, one for each group of changed lines
+pub fn diff_edits(old: &str, new: &str) -> Vec<TextEdit> {
+    let old_lines = lines_of(old);
+    let new_lines = lines_of(new);
+    let head = old_lines
+        .iter()
+        .zip(&new_lines)
+        .take_while(|(old, new)| old == new)
+        .count();
+    let tail = old_lines[head..]
+        .iter()
+        .rev()
+        .zip(new_lines[head..].iter().rev())
+        .take_while(|(old, new)| old == new)
+        .count();
+    let old_middle = &old_lines[head..old_lines.len() - tail];
+    let new_middle = &new_lines[head..new_lines.len() - tail];
+    if old_middle.is_empty() && new_middle.is_empty() {
+        return vec![];
+    }
+
+    let (old_kept, new_kept) = if (old_middle.len() + 1) * (new_middle.len() + 1) > MAX_DIFF_CELLS {
+        (vec![false; old_middle.len()], vec![false; new_middle.len()])
+    } else {
+        common_lines(old_middle, new_middle)
+    };
+
+    let mut edits = vec![];
+    let (mut i, mut j) = (0, 0);
+    loop {
+        while i < old_middle.len() && j < new_middle.len() && old_kept[i] && new_kept[j] {
+            i += 1;
+            j += 1;
+        }
+        if i == old_middle.len() && j == new_middle.len() {
+            break;
+        }
+        let (start_i, start_j) = (i, j);
+        while i < old_middle.len() && !old_kept[i] {
+            i += 1;
+        }
+        while j < new_middle.len() && !new_kept[j] {
+            j += 1;
+        }
+        edits.push(TextEdit {
+            range: Range {
+                start: line_start(&old_lines, head + start_i),
+                end: line_start(&old_lines, head + i),
+            },
+            new_text: new_middle[start_j..j].concat(),
+        });
+    }
+    edits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Apply `edits` from the last one, as a client would
+    fn apply(old: &str, edits: &[TextEdit]) -> String {
+        let line_starts: Vec<usize> = std::iter::once(0)
+            .chain(old.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+        let offset =
+            |position: Position| line_starts[position.line as usize] + position.character as usize;
+        let mut output = old.to_string();
+        for edit in edits.iter().rev() {
+            output.replace_range(
+                offset(edit.range.start)..offset(edit.range.end),
+                &edit.new_text,
+            );
+        }
+        output
+    }
+
+    #[test]
+    fn test_diff_edits() {
+        let old = "project(a)\nif(A)\nset(B c)\n  set(D e)\nendif()\n\n\nmessage(x)";
+        let new = "project(a)\nif(A)\n  set(B c)\n  set(D e)\nendif()\n\nmessage(x)\n";
+        let edits = diff_edits(old, new);
+        assert_eq!(edits.len(), 2);
+        assert_eq!(edits[0].range.start.line, 2);
+        assert_eq!(edits[0].new_text, "  set(B c)\n");
+        assert_eq!(apply(old, &edits), new);
+        assert!(diff_edits(new, new).is_empty());
+    }
+}
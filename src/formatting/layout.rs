@@ -0,0 +1,233 @@
+//! Layout of the arguments of the commands written by the formatter.
+//!
+//! Runs on the formatted text, so the indentation of each command is already
+//! the final one. A call which fits on its line is written on one line whatever
+//! the layout, and an argument list with comments or multi-line arguments is
+//! left as it is, as moving them could change what the code means.
+
+use serde::Deserialize;
+use tree_sitter::Node;
+
+use crate::CMakeNodeKinds;
+use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+use crate::gammar::Fix;
+use crate::gammar::fix::apply_fixes;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Layout {
+    /// Keep the lines of the source
+    #[default]
+    Preserve,
+    /// Every argument on its own line, indented once, and the `)` on its own line
+    OnePerLine,
+    /// As many arguments per line as the line width allows, indented once
+    BinPack,
+    /// As many arguments per line as the line width allows, aligned after the `(`
+    Aligned,
+}
+
+/// Number of characters of `text`, as seen in an editor
+fn width(text: &str) -> usize {
+    text.chars().count()
+}
+
+/// Pack `arguments` in lines of at most `max_len` characters, starting after
+/// `first_prefix` and continuing after `prefix`
+fn pack(arguments: &[&str], first_prefix: &str, prefix: &str, max_len: usize) -> String {
+    let mut output = String::new();
+    let mut column = width(first_prefix);
+    for (index, argument) in arguments.iter().enumerate() {
+        if index > 0 {
+            // One more character for the space, or the `)` after the last one
+            if column + 1 + width(argument) + 1 > max_len {
+                output.push('\n');
+                output.push_str(prefix);
+                column = width(prefix);
+            } else {
+                output.push(' ');
+                column += 1;
+            }
+        }
+        output.push_str(argument);
+        column += width(argument);
+    }
+    output
+}
+
+/// The text between the parentheses of `command` in the given layout
+fn layout_fix(
+    command: Node,
+    source: &[&str],
+    layout: Layout,
+    unit: &str,
+    max_len: usize,
+) -> Option<Fix> {
+    let open = command.child(1).filter(|node| node.kind() == "(")?;
+    let close = command
+        .child(command.child_count() as u32 - 1)
+        .filter(|node| node.kind() == ")")?;
+    let argument_list = command
+        .child(2)
+        .filter(|node| node.kind() == CMakeNodeKinds::ARGUMENT_LIST)?;
+
+    let mut walk = argument_list.walk();
+    let mut arguments = vec![];
+    for argument in argument_list.children(&mut walk) {
+        let row = argument.start_position().row;
+        if argument.kind() != CMakeNodeKinds::ARGUMENT || row != argument.end_position().row {
+            return None;
+        }
+        arguments
+            .push(&source[row][argument.start_position().column..argument.end_position().column]);
+    }
+
+    let line = source[open.start_position().row];
+    let indent = &line[..line.len() - line.trim_start().len()];
+    let before_open = &line[..open.end_position().column];
+    let after_close = source
+        .get(close.end_position().row)
+        .map_or("", |line| &line[close.end_position().column..]);
+    let one_line = arguments.join(" ");
+    if width(before_open) + width(&one_line) + 1 + width(after_close.trim_end()) <= max_len {
+        return Some(Fix {
+            title: "layout".to_string(),
+            start: open.end_position(),
+            end: close.start_position(),
+            new_text: one_line,
+            safe: true,
+        });
+    }
+
+    let new_text = match layout {
+        Layout::Preserve => return None,
+        Layout::OnePerLine => {
+            let mut new_text = String::new();
+            for argument in &arguments {
+                new_text.push('\n');
+                new_text.push_str(indent);
+                new_text.push_str(unit);
+                new_text.push_str(argument);
+            }
+            new_text.push('\n');
+            new_text.push_str(indent);
+            new_text
+        }
+        Layout::BinPack => pack(&arguments, before_open, &format!("{indent}{unit}"), max_len),
+        Layout::Aligned => {
+            // Tabs stay tabs, so the alignment holds whatever their width
+            let prefix: String = before_open
+                .chars()
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            pack(&arguments, before_open, &prefix, max_len)
+        }
+    };
+    Some(Fix {
+        title: "layout".to_string(),
+        start: open.end_position(),
+        end: close.start_position(),
+        new_text,
+        safe: true,
+    })
+}
+
+fn collect_fixes(
+    node: Node,
+    source: &[&str],
+    unit: &str,
+    max_len: usize,
+    layout_of: &impl Fn(&str) -> Layout,
+    output: &mut Vec<Fix>,
+) {
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if child.kind() != CMakeNodeKinds::NORMAL_COMMAND {
+            collect_fixes(child, source, unit, max_len, layout_of, output);
+            continue;
+        }
+        let Some(identifier) = child.child(0) else {
+            continue;
+        };
+        let row = identifier.start_position().row;
+        let name = source[row]
+            [identifier.start_position().column..identifier.end_position().column]
+            .to_lowercase();
+        let layout = layout_of(&name);
+        if layout != Layout::Preserve
+            && let Some(fix) = layout_fix(child, source, layout, unit, max_len)
+        {
+            output.push(fix);
+        }
+    }
+}
+
+/// Lay out the arguments of the commands of the formatted `source`, `unit`
+/// being one level of indentation
+pub fn apply_layout(
+    source: &str,
+    unit: &str,
+    max_len: usize,
+    layout_of: impl Fn(&str) -> Layout,
+) -> String {
+    let mut parse = tree_sitter::Parser::new();
+    parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+    let Some(tree) = parse.parse(source, None) else {
+        return source.to_string();
+    };
+    if tree.root_node().has_error() {
+        return source.to_string();
+    }
+    let lines: Vec<&str> = source.lines().collect();
+    let mut fixes = vec![];
+    collect_fixes(
+        tree.root_node(),
+        &lines,
+        unit,
+        max_len,
+        &layout_of,
+        &mut fixes,
+    );
+    apply_fixes(source, &fixes).0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "if(A)\n  target_link_libraries(app PUBLIC fmt::fmt spdlog::spdlog\n    PRIVATE warnings)\n  set(X a b)\nendif()\n";
+
+    fn layout(layout: Layout) -> String {
+        apply_layout(SOURCE, "  ", 40, |name| {
+            if name == "target_link_libraries" {
+                layout
+            } else {
+                Layout::Preserve
+            }
+        })
+    }
+
+    #[test]
+    fn test_layouts() {
+        assert_eq!(layout(Layout::Preserve), SOURCE);
+        assert_eq!(
+            layout(Layout::OnePerLine),
+            "if(A)\n  target_link_libraries(\n    app\n    PUBLIC\n    fmt::fmt\n    spdlog::spdlog\n    PRIVATE\n    warnings\n  )\n  set(X a b)\nendif()\n"
+        );
+        assert_eq!(
+            layout(Layout::BinPack),
+            "if(A)\n  target_link_libraries(app PUBLIC\n    fmt::fmt spdlog::spdlog PRIVATE\n    warnings)\n  set(X a b)\nendif()\n"
+        );
+        assert_eq!(
+            layout(Layout::Aligned),
+            "if(A)\n  target_link_libraries(app PUBLIC\n                        fmt::fmt\n                        spdlog::spdlog\n                        PRIVATE\n                        warnings)\n  set(X a b)\nendif()\n"
+        );
+        // Fits on its line
+        assert_eq!(
+            apply_layout("target_link_libraries(app\n  fmt)\n", "  ", 40, |_| {
+                Layout::OnePerLine
+            }),
+            "target_link_libraries(app fmt)\n"
+        );
+    }
+}
@@ -0,0 +1,158 @@
+//! Trailing whitespace, final newline and runs of blank lines.
+//!
+//! Each problem is fixed by its own small edit, so the code action only touches
+//! the lines which change. Lines ending inside a multi-line string or bracket
+//! argument are left alone, as their whitespace is part of the value.
+
+use std::collections::HashSet;
+
+use tree_sitter::{Node, Point};
+
+use crate::CMakeNodeKinds;
+use crate::config::FormatConfig;
+use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+use crate::gammar::Fix;
+use crate::gammar::fix::apply_fixes;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WhitespaceStyle {
+    pub trim_trailing_whitespace: bool,
+    /// End the file with exactly one newline
+    pub final_newline: bool,
+    /// Collapse longer runs of blank lines to this many
+    pub max_blank_lines: Option<usize>,
+}
+
+impl WhitespaceStyle {
+    /// From the `[format]` options, a final newline also being asked for by the editor
+    pub fn configured(format: &FormatConfig, insert_final_newline: bool) -> Self {
+        Self {
+            trim_trailing_whitespace: format.trim_trailing_whitespace,
+            final_newline: insert_final_newline || format.final_newline,
+            max_blank_lines: format.max_blank_lines,
+        }
+    }
+}
+
+const MULTILINE_VALUES: &[&str] = &[
+    CMakeNodeKinds::QUOTED_ARGUMENT,
+    CMakeNodeKinds::BRACKET_ARGUMENT,
+    CMakeNodeKinds::BRACKET_COMMENT,
+];
+
+/// The rows whose end is inside a multi-line value
+pub(super) fn protected_rows(node: Node, output: &mut HashSet<usize>) {
+    if MULTILINE_VALUES.contains(&node.kind()) {
+        output.extend(node.start_position().row..node.end_position().row);
+        return;
+    }
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if child.end_position().row > child.start_position().row {
+            protected_rows(child, output);
+        }
+    }
+}
+
+fn point(row: usize, column: usize) -> Point {
+    Point { row, column }
+}
+
+/// The edits normalizing the whitespace of `source`, parsed as `root`.
+/// They never overlap, so they can be sent together.
+pub fn whitespace_fixes(source: &str, root: Node, style: &WhitespaceStyle) -> Vec<Fix> {
+    let mut protected = HashSet::new();
+    protected_rows(root, &mut protected);
+    let lines: Vec<&str> = source.lines().collect();
+    let mut fixes = vec![];
+
+    // The end of the file is rewritten at once, from the end of the last line with code
+    let content_end = source.trim_end().len();
+    let mut last_row = lines.len();
+    if style.final_newline && content_end > 0 {
+        last_row = source[..content_end].matches('\n').count();
+        let line_start = source[..content_end]
+            .rfind('\n')
+            .map_or(0, |index| index + 1);
+        if &source[content_end..] != "\n" {
+            fixes.push(Fix {
+                title: "Insert a single final newline".to_string(),
+                start: point(last_row, content_end - line_start),
+                end: point(lines.len(), 0),
+                new_text: "\n".to_string(),
+                safe: true,
+            });
+        }
+    }
+
+    let mut blank_run = 0;
+    for (row, line) in lines.iter().enumerate().take(last_row) {
+        let content = line.trim_end_matches('\r');
+        let trimmed = content.trim_end_matches([' ', '\t']);
+        let is_protected = protected.contains(&row);
+        if trimmed.is_empty() && !is_protected {
+            blank_run += 1;
+            if style.max_blank_lines.is_some_and(|max| blank_run > max) {
+                // Delete the whole line, with its own whitespace
+                fixes.push(Fix {
+                    title: "Remove a blank line".to_string(),
+                    start: point(row, 0),
+                    end: point(row + 1, 0),
+                    new_text: String::new(),
+                    safe: true,
+                });
+                continue;
+            }
+        } else {
+            blank_run = 0;
+        }
+        if style.trim_trailing_whitespace && !is_protected && trimmed.len() < content.len() {
+            fixes.push(Fix {
+                title: "Remove trailing whitespace".to_string(),
+                start: point(row, trimmed.len()),
+                end: point(row, content.len()),
+                new_text: String::new(),
+                safe: true,
+            });
+        }
+    }
+    fixes.sort_by_key(|fix| (fix.start, fix.end));
+    fixes
+}
+
+/// Normalize the whitespace of the formatted `source`
+pub fn normalize_whitespace(source: &str, style: &WhitespaceStyle) -> String {
+    let mut parse = tree_sitter::Parser::new();
+    parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+    let Some(tree) = parse.parse(source, None) else {
+        return source.to_string();
+    };
+    apply_fixes(source, &whitespace_fixes(source, tree.root_node(), style)).0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_whitespace() {
+        let style = WhitespaceStyle {
+            trim_trailing_whitespace: true,
+            final_newline: true,
+            max_blank_lines: Some(1),
+        };
+        let source = "set(A b)  \n\n\n\t\nmessage(\"keep  \nthis  \")\t\nset(C d)  \n\n";
+        assert_eq!(
+            normalize_whitespace(source, &style),
+            "set(A b)\n\nmessage(\"keep  \nthis  \")\nset(C d)\n"
+        );
+        assert_eq!(normalize_whitespace("set(A b)", &style), "set(A b)\n");
+
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let tree = parse.parse(source, None).unwrap();
+        let fixes = whitespace_fixes(source, tree.root_node(), &style);
+        // The two extra blank lines, two trailing spaces and the end of the file
+        assert_eq!(fixes.len(), 5);
+    }
+}
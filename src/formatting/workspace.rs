@@ -0,0 +1,245 @@
+//! Formatting every CMake file of the workspace at once, for the migrations to
+//! a new style: the `neocmake/formatWorkspace` request returns the edits of
+//! each file for the editor to apply, and `format -i` on a directory writes
+//! them.
+//!
+//! The files are formatted side by side on the rayon pool. A file which cannot
+//! be read, parsed or formatted is reported with its error, the others are
+//! formatted anyway.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::{Context, Result, bail};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::{
+    DocumentChanges, FormattingOptions, OneOf, OptionalVersionedTextDocumentIdentifier,
+    TextDocumentEdit, Uri, WorkspaceEdit,
+};
+
+use super::diff::diff_edits;
+use super::get_format_cli;
+use crate::config;
+use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+/// The custom request formatting every CMake file of the workspace
+pub const FORMAT_WORKSPACE_METHOD: &str = "neocmake/formatWorkspace";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FormatWorkspaceParams {
+    pub options: FormattingOptions,
+}
+
+/// A file which was not formatted
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FormatError {
+    pub uri: Uri,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FormatWorkspaceResult {
+    /// The edits of the files whose formatting changed
+    pub edit: WorkspaceEdit,
+    /// The number of files formatted, changed or not
+    pub formatted: usize,
+    pub errors: Vec<FormatError>,
+}
+
+/// Format `source` with the program of the `[format]` table
+fn format_external(program: &str, args: &[String], source: &str) -> Result<String> {
+    let mut process = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run {program}"))?;
+    process
+        .stdin
+        .take()
+        .context("No stdin for the external formatter")?
+        .write_all(source.as_bytes())?;
+    let output = process.wait_with_output()?;
+    if !output.status.success() {
+        bail!(
+            "{program} exited with error code {}",
+            output.status.code().unwrap_or(-1)
+        );
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// `source`, the content of `path`, formatted as its config says
+pub fn format_source(
+    path: &Path,
+    source: &str,
+    indent_size: u32,
+    use_space: bool,
+    insert_final_newline: bool,
+) -> Result<String> {
+    let format = config::format_config(Some(path));
+    if let Some(program) = &format.program {
+        return format_external(program, format.args.as_deref().unwrap_or_default(), source);
+    }
+    let mut parser = tree_sitter::Parser::new();
+    parser.set_language(&TREESITTER_CMAKE_LANGUAGE)?;
+    let tree = parser
+        .parse(source, None)
+        .context("Failed to parse CMake file into tree")?;
+    // The formatting of a broken file may lose its content
+    if tree.root_node().has_error() {
+        bail!("Syntax error, not formatted");
+    }
+    get_format_cli(
+        Some(path),
+        source,
+        indent_size,
+        use_space,
+        insert_final_newline,
+    )
+}
+
+/// Format `files` in parallel, the content of the ones in `sources` rather
+/// than the one on disk. The results are in the order of `files`, with the
+/// content before and after the formatting. Once `cancelled` is set, the
+/// files left are skipped
+pub fn format_files(
+    files: &[PathBuf],
+    sources: &HashMap<PathBuf, String>,
+    indent_size: u32,
+    use_space: bool,
+    insert_final_newline: bool,
+    cancelled: &AtomicBool,
+) -> Vec<(PathBuf, Result<(String, String)>)> {
+    files
+        .par_iter()
+        .filter(|_| !cancelled.load(Ordering::Relaxed))
+        .map(|path| {
+            let result = match sources.get(path) {
+                Some(source) => Ok(source.clone()),
+                None => std::fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read {}", path.display())),
+            }
+            .and_then(|source| {
+                let formatted =
+                    format_source(path, &source, indent_size, use_space, insert_final_newline)?;
+                Ok((source, formatted))
+            });
+            (path.clone(), result)
+        })
+        .collect()
+}
+
+/// The edits formatting `files`, with the files which could not be formatted
+pub fn format_workspace(
+    files: &[PathBuf],
+    sources: &HashMap<PathBuf, String>,
+    options: &FormattingOptions,
+    cancelled: &AtomicBool,
+) -> FormatWorkspaceResult {
+    let indent_size = if options.insert_spaces {
+        options.tab_size
+    } else {
+        1
+    };
+    // The project config wins over the options of the editor
+    let (indent_size, use_space) =
+        super::foreign_indent().unwrap_or((indent_size, options.insert_spaces));
+    let insert_final_newline = options.insert_final_newline.unwrap_or(false);
+
+    let mut result = FormatWorkspaceResult::default();
+    let mut edits = vec![];
+    for (path, formatted) in format_files(
+        files,
+        sources,
+        indent_size,
+        use_space,
+        insert_final_newline,
+        cancelled,
+    ) {
+        let Ok(uri) = Uri::from_file_path(&path) else {
+            continue;
+        };
+        match formatted {
+            Ok((source, formatted)) => {
+                result.formatted += 1;
+                if source != formatted {
+                    edits.push(TextDocumentEdit {
+                        text_document: OptionalVersionedTextDocumentIdentifier {
+                            uri,
+                            version: None,
+                        },
+                        edits: diff_edits(&source, &formatted)
+                            .into_iter()
+                            .map(OneOf::Left)
+                            .collect(),
+                    });
+                }
+            }
+            Err(err) => result.errors.push(FormatError {
+                uri,
+                message: format!("{err:#}"),
+            }),
+        }
+    }
+    result.edit.document_changes = Some(DocumentChanges::Edits(edits));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_workspace() {
+        let dir = tempfile::tempdir().unwrap();
+        let formatted = dir.path().join("CMakeLists.txt");
+        let messy = dir.path().join("messy.cmake");
+        let broken = dir.path().join("broken.cmake");
+        let open = dir.path().join("open.cmake");
+        std::fs::write(&formatted, "project(demo)\n").unwrap();
+        std::fs::write(&messy, "if(A)\nmessage(STATUS a)\nendif()\n").unwrap();
+        std::fs::write(&broken, "if(A\n").unwrap();
+        std::fs::write(&open, "project(demo)\n").unwrap();
+        // The content in the editor, not saved yet
+        let sources = HashMap::from([(open.clone(), "if(B)\nset(C 1)\nendif()\n".to_string())]);
+        let files = vec![formatted, messy.clone(), broken.clone(), open.clone()];
+        let options = FormattingOptions {
+            tab_size: 2,
+            insert_spaces: true,
+            insert_final_newline: Some(true),
+            ..Default::default()
+        };
+
+        let result = format_workspace(&files, &sources, &options, &AtomicBool::new(false));
+        assert_eq!(result.formatted, 3);
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].uri, Uri::from_file_path(&broken).unwrap());
+        assert_eq!(result.errors[0].message, "Syntax error, not formatted");
+        let Some(DocumentChanges::Edits(edits)) = result.edit.document_changes else {
+            panic!("expected text edits");
+        };
+        let changed: Vec<Uri> = edits
+            .iter()
+            .map(|edit| edit.text_document.uri.clone())
+            .collect();
+        assert_eq!(
+            changed,
+            vec![
+                Uri::from_file_path(&messy).unwrap(),
+                Uri::from_file_path(&open).unwrap()
+            ]
+        );
+        let OneOf::Left(edit) = &edits[1].edits[0] else {
+            panic!("expected a text edit");
+        };
+        assert_eq!(edit.new_text, "  set(C 1)\n");
+    }
+}
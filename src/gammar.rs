@@ -7,15 +7,58 @@ use tower_lsp::lsp_types::DiagnosticSeverity;
 use tree_sitter::Point;
 
 use crate::CMakeNodeKinds;
+use crate::baseline;
 use crate::config::{self, CONFIG};
 use crate::consts::TREESITTER_CMAKE_LANGUAGE;
-use crate::utils::{include_is_module, remove_quotation_and_replace_placeholders};
+use crate::rules::{self, Rule};
+use crate::scansubs;
+use crate::utils::cmake_docs::CMAKE_VERSION;
+use crate::utils::input::{self, InputError};
+use crate::utils::script_mode::{self, FileMode};
+use crate::utils::{
+    deprecated, external, include_is_module, module_path,
+    remove_quotation_and_replace_placeholders, targets, toolchain,
+};
+
+pub mod balance;
+mod calls;
+mod checks;
+mod cpack;
+mod download;
+mod duplicates;
+mod expressions;
+pub mod fix;
+mod flow;
+mod gpu;
+mod install;
+pub mod line_length;
+mod ordering;
+mod packages;
+mod pkg_config;
+mod platforms;
+mod qt;
+mod references;
+mod source_group;
+mod spelling;
+pub mod unquoted;
+mod vcpkg;
+
+pub use checks::{CheckTiming, slowest_checks};
+pub use fix::Fix;
+
+const GRAMMAR_ERROR: &str = "Grammar error";
+
+/// The version required by the preamble fix when cmake is not installed
+const FALLBACK_MINIMUM_VERSION: (u32, u32) = (3, 20);
 
 const INCLUDE_CHECK_KEYWORDS: &[&str; 2] = &["include", "add_subdirectory"];
 
 pub(crate) struct LintConfigInfo {
     pub use_lint: bool,
     pub use_extra_cmake_lint: bool,
+    /// The file is outside of the workspace, the checks reading the index of
+    /// the project are skipped
+    pub detached: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -24,6 +67,52 @@ pub struct ErrorInformation {
     pub end_point: tree_sitter::Point,
     pub message: String,
     pub severity: Option<DiagnosticSeverity>,
+    /// `None` for the output of cmake-lint
+    pub rule: Option<&'static Rule>,
+}
+
+/// A diagnostic which comes with the edit fixing it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixableIssue {
+    pub start_point: Point,
+    pub end_point: Point,
+    pub message: String,
+    pub rule: &'static Rule,
+    pub fix: Fix,
+}
+
+impl From<FixableIssue> for ErrorInformation {
+    fn from(issue: FixableIssue) -> Self {
+        Self {
+            start_point: issue.start_point,
+            end_point: issue.end_point,
+            message: issue.message,
+            severity: Some(issue.rule.severity),
+            rule: Some(issue.rule),
+        }
+    }
+}
+
+/// All the diagnostics of `source`, the content of `local_path`, which can be
+/// fixed, for the quick fixes
+pub fn fixable_issues(local_path: Option<&Path>, source: &str) -> Vec<FixableIssue> {
+    let mut parse = tree_sitter::Parser::new();
+    parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+    let Some(thetree) = parse.parse(source, None) else {
+        return Vec::new();
+    };
+    let mut issues = vec![];
+    if thetree.root_node().has_error() {
+        issues.append(&mut balance::find_issues(source));
+    }
+    let lines: Vec<&str> = source.lines().collect();
+    issues.append(&mut unquoted::find_issues(&lines, thetree.root_node()));
+    issues.append(&mut spelling::find_issues(&lines, thetree.root_node()));
+    issues.append(&mut duplicates::find_issues(&lines, thetree.root_node()));
+    if !local_path.is_some_and(pkg_config::found_by_parent) {
+        issues.append(&mut pkg_config::find_issues(&lines, thetree.root_node()));
+    }
+    issues
 }
 
 /// checkerror the gammer error
@@ -46,9 +135,16 @@ pub fn checkerror<P: AsRef<Path>>(
     LintConfigInfo {
         use_lint,
         use_extra_cmake_lint,
+        detached,
     }: LintConfigInfo,
 ) -> Option<ErrorInfo> {
-    let newsource = source.lines().collect();
+    // Vendored code is indexed, but its problems are not for the user to fix
+    let external_level = external::is_external(local_path.as_ref())
+        .then_some(CONFIG.external_diagnostics.severity());
+    if external_level == Some(None) {
+        return None;
+    }
+    let newsource: Vec<&str> = source.lines().collect();
     let cmake_lint_info = if use_lint {
         run_cmake_lint(local_path, use_extra_cmake_lint, &newsource)
     } else {
@@ -58,14 +154,93 @@ pub fn checkerror<P: AsRef<Path>>(
     parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
     let thetree = parse.parse(source, None)?;
     let mut result = checkerror_inner(local_path, &newsource, thetree.root_node(), use_lint);
+    if thetree.root_node().has_error() {
+        let issues = balance::find_issues(source);
+        // The precise diagnostics replace the errors running to the end of the file
+        if !issues.is_empty() {
+            let error_info = result.get_or_insert(ErrorInfo { inner: vec![] });
+            error_info
+                .inner
+                .retain(|error| error.message != GRAMMAR_ERROR);
+            error_info
+                .inner
+                .extend(issues.into_iter().map(ErrorInformation::from));
+        }
+    }
+    if use_lint {
+        let mut lint_info = checks::run_checks(&checks::Context {
+            local_path: local_path.as_ref(),
+            source,
+            lines: &newsource,
+            root: thetree.root_node(),
+            detached,
+        });
+        if !lint_info.is_empty() {
+            result
+                .get_or_insert(ErrorInfo { inner: vec![] })
+                .inner
+                .append(&mut lint_info);
+        }
+    }
     if let Some(v) = cmake_lint_info {
         let error_info = result.get_or_insert(ErrorInfo { inner: vec![] });
         for item in v.inner {
             error_info.inner.push(item);
         }
     }
+    if let Some(error_info) = result.as_mut() {
+        apply_rule_levels(local_path.as_ref(), &mut error_info.inner);
+        if let Some(Some(level)) = external_level {
+            lower_severities(&mut error_info.inner, level);
+        }
+        baseline::apply_baseline(local_path.as_ref(), &newsource, &mut error_info.inner);
+    }
+
+    result.filter(|error_info| !error_info.is_empty())
+}
+
+/// Drop the diagnostics of the rules turned off, and apply the severities
+/// configured for `local_path`
+fn apply_rule_levels(local_path: &Path, errors: &mut Vec<ErrorInformation>) {
+    errors.retain_mut(|error| {
+        let Some(rule) = error.rule else {
+            return true;
+        };
+        match rule.configured_severity(Some(local_path)) {
+            Some(severity) => {
+                error.severity = Some(severity);
+                true
+            }
+            None => false,
+        }
+    });
+}
+
+fn severity_rank(severity: Option<DiagnosticSeverity>) -> u8 {
+    match severity {
+        Some(DiagnosticSeverity::HINT) => 0,
+        Some(DiagnosticSeverity::INFORMATION) => 1,
+        Some(DiagnosticSeverity::WARNING) => 2,
+        _ => 3,
+    }
+}
+
+/// Lower the diagnostics more severe than `level`
+fn lower_severities(errors: &mut [ErrorInformation], level: DiagnosticSeverity) {
+    for error in errors {
+        if severity_rank(error.severity) > severity_rank(Some(level)) {
+            error.severity = Some(level);
+        }
+    }
+}
 
-    result
+/// Checked variant of [`checkerror`] for arbitrary bytes
+pub(crate) fn try_checkerror<P: AsRef<Path>>(
+    local_path: &P,
+    source: &[u8],
+    lint_info: LintConfigInfo,
+) -> Result<Option<ErrorInfo>, InputError> {
+    Ok(checkerror(local_path, input::decode(source)?, lint_info))
 }
 
 const RE_MATCH_LINT_RESULT: &str =
@@ -83,7 +258,7 @@ fn run_cmake_lint<P: AsRef<Path>>(
         return run_extra_lint(path);
     }
     let mut info = vec![];
-    let max_len = CONFIG.line_max_words;
+    let max_len = CONFIG.line_max_words();
     for (index, line) in contexts.iter().enumerate() {
         let len = line.len();
         if len > max_len {
@@ -101,6 +276,7 @@ fn run_cmake_lint<P: AsRef<Path>>(
                 end_point,
                 message,
                 severity: Some(DiagnosticSeverity::WARNING),
+                rule: Some(&rules::LINE_TOO_LONG),
             });
         }
     }
@@ -129,10 +305,13 @@ fn run_extra_lint<P: AsRef<Path>>(path: P) -> Option<ErrorInfo> {
                 "W" => DiagnosticSeverity::WARNING,
                 _ => DiagnosticSeverity::INFORMATION,
             };
-            let row = m.name("line").unwrap().as_str().parse().unwrap_or(1) - 1;
+            let row = m
+                .name("line")
+                .and_then(|line| line.as_str().parse::<usize>().ok())
+                .map_or(0, |line| line.saturating_sub(1));
             let column = m
                 .name("column")
-                .map(|m| m.as_str().parse().unwrap())
+                .and_then(|column| column.as_str().parse().ok())
                 .unwrap_or(0);
             let message = m.name("message").unwrap().as_str().to_owned();
 
@@ -143,6 +322,7 @@ fn run_extra_lint<P: AsRef<Path>>(path: P) -> Option<ErrorInfo> {
                 end_point,
                 message,
                 severity: Some(severity),
+                rule: None,
             });
         }
     }
@@ -154,6 +334,17 @@ fn run_extra_lint<P: AsRef<Path>>(path: P) -> Option<ErrorInfo> {
     }
 }
 
+/// The text of `node`, if it is on a single line of `source`
+fn line_text<'a>(source: &[&'a str], node: tree_sitter::Node) -> Option<&'a str> {
+    let row = node.start_position().row;
+    if row != node.end_position().row {
+        return None;
+    }
+    source
+        .get(row)?
+        .get(node.start_position().column..node.end_position().column)
+}
+
 fn checkerror_inner<P: AsRef<Path>>(
     local_path: P,
     newsource: &Vec<&str>,
@@ -165,8 +356,9 @@ fn checkerror_inner<P: AsRef<Path>>(
             inner: vec![ErrorInformation {
                 start_point: input.start_position(),
                 end_point: input.end_position(),
-                message: "Grammar error".to_string(),
+                message: GRAMMAR_ERROR.to_string(),
                 severity: None,
+                rule: Some(&rules::SYNTAX_ERROR),
             }],
         });
     }
@@ -182,15 +374,15 @@ fn checkerror_inner<P: AsRef<Path>>(
             continue;
         }
 
-        let h = node.start_position().row;
-        let ids = node.child(0).unwrap();
-        //let ids = ids.child(2).unwrap();
-        let x = ids.start_position().column;
-        let y = ids.end_position().column;
-        let name = &newsource[h][x..y];
+        let Some(ids) = node.child(0) else {
+            continue;
+        };
+        let Some(name) = line_text(newsource, ids) else {
+            continue;
+        };
         if use_lint
             && let Some(hint) = config::CONFIG
-                .command_case
+                .command_case()
                 .and_then(|lint| lint.check(name))
         {
             output.push(ErrorInformation {
@@ -198,6 +390,21 @@ fn checkerror_inner<P: AsRef<Path>>(
                 end_point: ids.end_position(),
                 message: hint.to_owned(),
                 severity: Some(DiagnosticSeverity::HINT),
+                rule: Some(&rules::COMMAND_CASE),
+            });
+        }
+        if use_lint && let Some(command) = deprecated::find_deprecated_command(name) {
+            let rule = if command.removed_in.is_some() {
+                &rules::REMOVED_COMMAND
+            } else {
+                &rules::DEPRECATED_COMMAND
+            };
+            output.push(ErrorInformation {
+                start_point: ids.start_position(),
+                end_point: ids.end_position(),
+                message: command.note(),
+                severity: Some(rule.severity),
+                rule: Some(rule),
             });
         }
         let lowercase_name = name.to_lowercase();
@@ -211,21 +418,17 @@ fn checkerror_inner<P: AsRef<Path>>(
             };
             let mut walk = arguments.walk();
             for child in arguments.children(&mut walk) {
-                let h = child.start_position().row;
-                let h2 = child.end_position().row;
                 // TODO: now make sure package in the same level
-                if h != h2 {
+                let Some(name) = line_text(newsource, child) else {
                     continue;
-                }
-                let x = child.start_position().column;
-                let y = child.end_position().column;
-                let name = &newsource[h][x..y];
+                };
                 if errorpackages.contains(&name.to_string()) {
                     output.push(ErrorInformation {
                         start_point: child.start_position(),
                         end_point: child.end_position(),
                         message: "Cannot find such package".to_string(),
                         severity: Some(DiagnosticSeverity::ERROR),
+                        rule: Some(&rules::PACKAGE_NOT_FOUND),
                     });
                 }
             }
@@ -245,10 +448,10 @@ fn checkerror_inner<P: AsRef<Path>>(
             if ids.start_position().row != ids.end_position().row {
                 continue;
             }
-            let h = ids.start_position().row;
-            let x = first_arg_node.start_position().column;
-            let y = first_arg_node.end_position().column;
-            let first_arg = newsource[h][x..y].trim();
+            let Some(first_arg) = line_text(newsource, first_arg_node) else {
+                continue;
+            };
+            let first_arg = first_arg.trim();
             let Some(first_arg) = remove_quotation_and_replace_placeholders(first_arg) else {
                 continue;
             };
@@ -259,10 +462,23 @@ fn checkerror_inner<P: AsRef<Path>>(
                     end_point: first_arg_node.end_position(),
                     message: "Argument is empty".to_string(),
                     severity: Some(DiagnosticSeverity::ERROR),
+                    rule: Some(&rules::EMPTY_ARGUMENT),
                 });
                 continue;
             }
             if !is_sub_directory && include_is_module(&first_arg) {
+                // Modules of the project itself are checked like included files
+                if let Some(module_path) = module_path::find_project_module(&first_arg)
+                    && scanner_include_error(module_path)
+                {
+                    output.push(ErrorInformation {
+                        start_point: first_arg_node.start_position(),
+                        end_point: first_arg_node.end_position(),
+                        message: "Error in include file".to_string(),
+                        severity: Some(DiagnosticSeverity::ERROR),
+                        rule: Some(&rules::INCLUDE_ERROR),
+                    });
+                }
                 continue;
             }
             let sub_path = Path::new(&first_arg);
@@ -280,6 +496,7 @@ fn checkerror_inner<P: AsRef<Path>>(
                                 end_point: first_arg_node.end_position(),
                                 message: "Error in include file".to_string(),
                                 severity: Some(DiagnosticSeverity::ERROR),
+                                rule: Some(&rules::INCLUDE_ERROR),
                             });
                         }
                     } else {
@@ -289,11 +506,9 @@ fn checkerror_inner<P: AsRef<Path>>(
                         output.push(ErrorInformation {
                             start_point: first_arg_node.start_position(),
                             end_point: first_arg_node.end_position(),
-                            message: format!(
-                                "\"{}\" is a directory",
-                                include_path.to_str().unwrap()
-                            ),
+                            message: format!("\"{}\" is a directory", include_path.display()),
                             severity: Some(DiagnosticSeverity::ERROR),
+                            rule: Some(&rules::INCLUDE_DIRECTORY),
                         });
                     }
                 }
@@ -301,12 +516,12 @@ fn checkerror_inner<P: AsRef<Path>>(
                     let message = if is_sub_directory {
                         format!(
                             "Directory \"{}\" does not exist or is inaccessible",
-                            include_path.to_str().unwrap()
+                            include_path.display()
                         )
                     } else {
                         format!(
                             "File \"{}\" does not exist or is inaccessible",
-                            include_path.to_str().unwrap()
+                            include_path.display()
                         )
                     };
                     output.push(ErrorInformation {
@@ -314,6 +529,7 @@ fn checkerror_inner<P: AsRef<Path>>(
                         end_point: first_arg_node.end_position(),
                         message,
                         severity: Some(DiagnosticSeverity::WARNING),
+                        rule: Some(&rules::MISSING_PATH),
                     });
                 }
             }
@@ -326,6 +542,208 @@ fn checkerror_inner<P: AsRef<Path>>(
     }
 }
 
+/// The commands called outside of any block, with their lowercase name
+fn top_level_commands<'a>(
+    source: &[&str],
+    root: tree_sitter::Node<'a>,
+) -> Vec<(String, tree_sitter::Node<'a>)> {
+    let mut course = root.walk();
+    root.children(&mut course)
+        .filter(|node| node.kind() == CMakeNodeKinds::NORMAL_COMMAND)
+        .filter_map(|node| Some((line_text(source, node.child(0)?)?.to_lowercase(), node)))
+        .collect()
+}
+
+/// Whether `name` is called anywhere under `node`, in a block or not
+fn calls_command(source: &[&str], node: tree_sitter::Node, name: &str) -> bool {
+    if node.kind() == CMakeNodeKinds::NORMAL_COMMAND {
+        return node
+            .child(0)
+            .and_then(|id| line_text(source, id))
+            .is_some_and(|id| id.eq_ignore_ascii_case(name));
+    }
+    let mut course = node.walk();
+    node.children(&mut course)
+        .any(|child| calls_command(source, child, name))
+}
+
+/// The name of the project in `dir`: its name with the characters cmake would
+/// not take unquoted replaced, or `project`
+fn project_name(dir: Option<&Path>) -> String {
+    let name: String = dir
+        .and_then(|dir| dir.file_name())
+        .and_then(|name| name.to_str())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '+') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if name.chars().any(|c| c.is_ascii_alphanumeric()) {
+        name
+    } else {
+        "project".to_string()
+    }
+}
+
+/// Add the `cmake_minimum_required()` and `project()` calls missing from the top
+/// level CMakeLists.txt, requiring the installed version of cmake and naming the
+/// project after its directory. A call made in a block counts as there.
+pub fn preamble_fix<P: AsRef<Path>>(local_path: P, source: &str) -> Option<Fix> {
+    let mut parse = tree_sitter::Parser::new();
+    parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+    let tree = parse.parse(source, None)?;
+    let lines: Vec<&str> = source.lines().collect();
+    let top_commands = top_level_commands(&lines, tree.root_node());
+    let minimum_required = top_commands
+        .iter()
+        .find(|(name, _)| name == "cmake_minimum_required")
+        .map(|(_, node)| *node);
+    let has_project = calls_command(&lines, tree.root_node(), "project");
+    if minimum_required.is_none()
+        && calls_command(&lines, tree.root_node(), "cmake_minimum_required")
+    {
+        return None;
+    }
+
+    let (major, minor) = CMAKE_VERSION.unwrap_or(FALLBACK_MINIMUM_VERSION);
+    let name = project_name(local_path.as_ref().parent());
+    let project = format!("project({name} LANGUAGES CXX)");
+    match (minimum_required, has_project) {
+        (Some(_), true) => None,
+        // After `cmake_minimum_required()`, which sets the policies `project()` follows
+        (Some(node), false) => Some(Fix {
+            title: "Add project()".to_string(),
+            start: node.end_position(),
+            end: node.end_position(),
+            new_text: format!("\n{project}"),
+            safe: false,
+        }),
+        (None, has_project) => {
+            let mut new_text = format!("cmake_minimum_required(VERSION {major}.{minor})\n");
+            if !has_project {
+                new_text.push_str(&project);
+                new_text.push('\n');
+            }
+            if !source.trim().is_empty() {
+                new_text.push('\n');
+            }
+            let title = if has_project {
+                "Add cmake_minimum_required()"
+            } else {
+                "Add cmake_minimum_required() and project()"
+            };
+            Some(Fix {
+                title: title.to_string(),
+                start: Point { row: 0, column: 0 },
+                end: Point { row: 0, column: 0 },
+                new_text,
+                safe: false,
+            })
+        }
+    }
+}
+
+/// Script files cannot use project commands.
+/// Toolchain files are read by `project()` and must not define the project.
+fn check_file_mode<P: AsRef<Path>>(
+    local_path: P,
+    source: &[&str],
+    root: tree_sitter::Node,
+) -> Vec<ErrorInformation> {
+    let local_path = local_path.as_ref();
+    let mut output = vec![];
+    if toolchain::is_toolchain_file(local_path, source) {
+        for (name, ids) in script_mode::commands(source, root) {
+            if toolchain::is_forbidden_command(&name) {
+                output.push(ErrorInformation {
+                    start_point: ids.start_position(),
+                    end_point: ids.end_position(),
+                    message: format!("{name}() should not be called from a toolchain file"),
+                    severity: Some(DiagnosticSeverity::WARNING),
+                    rule: Some(&rules::TOOLCHAIN_COMMAND),
+                });
+            }
+        }
+        return output;
+    }
+    if script_mode::detect_mode(local_path, source, root) == FileMode::Script {
+        for (name, ids) in script_mode::commands(source, root) {
+            if script_mode::is_project_command(&name) {
+                output.push(ErrorInformation {
+                    start_point: ids.start_position(),
+                    end_point: ids.end_position(),
+                    message: format!(
+                        "{name} is not scriptable, this file looks like a cmake -P script"
+                    ),
+                    severity: Some(DiagnosticSeverity::WARNING),
+                    rule: Some(&rules::NOT_SCRIPTABLE),
+                });
+            }
+        }
+    }
+    output
+}
+
+/// Targets declared twice, in this file or in another file of the same project.
+/// The `IMPORTED` targets which are not `GLOBAL` only live in their directory,
+/// so they may be declared again elsewhere.
+fn check_duplicate_targets<P: AsRef<Path>>(
+    local_path: P,
+    source: &[&str],
+    root: tree_sitter::Node,
+) -> Vec<ErrorInformation> {
+    let local_path = local_path.as_ref();
+    let project_root = scansubs::project_root(local_path);
+    let declared = targets::declared_targets(source, root);
+    let others = targets::all_targets();
+    let compared = |target: &targets::Target| !target.conditional && !target.local_import;
+    let mut output = vec![];
+    for (index, target) in declared.iter().enumerate() {
+        if !compared(target) {
+            continue;
+        }
+        let earlier = declared[..index]
+            .iter()
+            .find(|other| compared(other) && other.name == target.name)
+            .map(|other| (local_path, other.position));
+        let elsewhere = || {
+            others
+                .iter()
+                .find(|(path, other)| {
+                    path != local_path
+                        && compared(other)
+                        && other.name == target.name
+                        && scansubs::project_root(path) == project_root
+                })
+                .map(|(path, other)| (path.as_path(), other.position))
+        };
+        let Some((path, position)) = earlier.or_else(elsewhere) else {
+            continue;
+        };
+        output.push(ErrorInformation {
+            start_point: target.position,
+            end_point: Point {
+                row: target.position.row,
+                column: target.position.column + target.name.len(),
+            },
+            message: format!(
+                "Target \"{}\" is already declared at {}:{}",
+                target.name,
+                path.display(),
+                position.row + 1
+            ),
+            severity: Some(DiagnosticSeverity::WARNING),
+            rule: Some(&rules::DUPLICATE_TARGET),
+        });
+    }
+    output
+}
+
 // Used to check if root_node has error
 fn scanner_include_error<P: AsRef<Path>>(path: P) -> bool {
     let Ok(content) = std::fs::read_to_string(path) else {
@@ -417,7 +835,8 @@ add_subdirectory("unexist_subdir")
                         "File \"{}\" does not exist or is inaccessible",
                         hello_cmake_error.display()
                     ),
-                    severity: Some(DiagnosticSeverity::WARNING)
+                    severity: Some(DiagnosticSeverity::WARNING),
+                    rule: Some(&rules::MISSING_PATH),
                 },
                 ErrorInformation {
                     start_point: Point { row: 4, column: 17 },
@@ -426,7 +845,8 @@ add_subdirectory("unexist_subdir")
                         "Directory \"{}\" does not exist or is inaccessible",
                         unexist_subdir.display()
                     ),
-                    severity: Some(DiagnosticSeverity::WARNING)
+                    severity: Some(DiagnosticSeverity::WARNING),
+                    rule: Some(&rules::MISSING_PATH),
                 },
             ]
         );
@@ -480,6 +900,7 @@ include(abcd.text)
                     end_point: input.end_position(),
                     message: "Grammar error".to_string(),
                     severity: None,
+                    rule: Some(&rules::SYNTAX_ERROR),
                 }]
             })
         );
@@ -525,4 +946,206 @@ aa.cmake:57: [C0301] Line too long (145/80)"#;
             println!("{row}:{column} -- {message}");
         }
     }
+
+    #[test]
+    fn test_script_mode_check() {
+        let source = "#!/usr/bin/env -S cmake -P\nfile(WRITE out.txt hello)\nadd_executable(demo main.cpp)\n";
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let errors = check_file_mode("/tmp/generate.cmake", &lines, thetree.root_node());
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].start_point, Point { row: 2, column: 0 });
+        assert_eq!(errors[0].severity, Some(DiagnosticSeverity::WARNING));
+    }
+
+    #[test]
+    fn test_balance_replaces_grammar_error() {
+        let lint_info = LintConfigInfo {
+            use_lint: false,
+            use_extra_cmake_lint: false,
+            detached: false,
+        };
+        let errors = checkerror(
+            &"/tmp/CMakeLists.txt",
+            "message(\"hello)\nset(A b)\n",
+            lint_info,
+        )
+        .unwrap();
+        assert_eq!(
+            errors
+                .iter()
+                .map(|error| error.message.as_str())
+                .collect::<Vec<_>>(),
+            vec![balance::UNTERMINATED_QUOTE]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_targets() {
+        let source = "add_library(dup_core core.cpp)\nif(WIN32)\n  add_executable(dup_tool win.cpp)\nelse()\n  add_executable(dup_tool unix.cpp)\nendif()\nadd_library(dup_core other.cpp)\n";
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let errors = check_duplicate_targets(
+            "/tmp/duplicates/CMakeLists.txt",
+            &lines,
+            thetree.root_node(),
+        );
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].start_point, Point { row: 6, column: 12 });
+        assert_eq!(
+            errors[0].message,
+            "Target \"dup_core\" is already declared at /tmp/duplicates/CMakeLists.txt:1"
+        );
+        assert_eq!(errors[0].severity, Some(DiagnosticSeverity::WARNING));
+
+        // Imported targets live in their directory, unless they are global
+        let source = "add_library(dup_zlib UNKNOWN IMPORTED)\nadd_library(dup_zlib UNKNOWN IMPORTED)\nadd_library(dup_ssl UNKNOWN IMPORTED GLOBAL)\nadd_library(dup_ssl UNKNOWN IMPORTED GLOBAL)\n";
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let errors = check_duplicate_targets(
+            "/tmp/duplicates/imported/CMakeLists.txt",
+            &lines,
+            thetree.root_node(),
+        );
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].start_point, Point { row: 3, column: 12 });
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_targets_across_projects() {
+        let dir = tempdir().unwrap();
+        let top = dir.path().join("CMakeLists.txt");
+        std::fs::create_dir_all(dir.path().join("lib")).unwrap();
+        std::fs::create_dir_all(dir.path().join("example")).unwrap();
+        std::fs::write(&top, "project(demo)\nadd_subdirectory(lib)\n").unwrap();
+        std::fs::write(
+            dir.path().join("lib/CMakeLists.txt"),
+            "add_library(dup_across lib.cpp)\n",
+        )
+        .unwrap();
+        // A project of its own, which the top level one does not add
+        let example = dir.path().join("example/CMakeLists.txt");
+        std::fs::write(
+            &example,
+            "project(example)\nadd_library(dup_across main.cpp)\n",
+        )
+        .unwrap();
+        scansubs::scan_all(dir.path(), false).await;
+        scansubs::scan_dir(&example, false).await;
+
+        let source = "add_library(dup_across other.cpp)\n";
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let errors = check_duplicate_targets(&top, &lines, thetree.root_node());
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].message.ends_with("lib/CMakeLists.txt:1"));
+
+        let errors = check_duplicate_targets(&example, &lines, thetree.root_node());
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn test_toolchain_check() {
+        let source = "set(CMAKE_SYSTEM_NAME Linux)\nset(CMAKE_C_COMPILER aarch64-linux-gnu-gcc)\nproject(demo)\n";
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let errors = check_file_mode("/tmp/aarch64.cmake", &lines, thetree.root_node());
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].start_point, Point { row: 2, column: 0 });
+        assert_eq!(
+            errors[0].message,
+            "project() should not be called from a toolchain file"
+        );
+    }
+
+    #[test]
+    fn test_preamble_fix() {
+        let (major, minor) = CMAKE_VERSION.unwrap_or(FALLBACK_MINIMUM_VERSION);
+        let fix = preamble_fix(
+            "/tmp/my demo/CMakeLists.txt",
+            "add_executable(app main.cpp)\n",
+        )
+        .unwrap();
+        let (fixed, _) = fix::apply_fixes("add_executable(app main.cpp)\n", &[fix]);
+        assert_eq!(
+            fixed,
+            format!(
+                "cmake_minimum_required(VERSION {major}.{minor})\nproject(my_demo LANGUAGES CXX)\n\nadd_executable(app main.cpp)\n"
+            )
+        );
+
+        let source = "cmake_minimum_required(VERSION 3.20)\nadd_executable(app main.cpp)\n";
+        let fix = preamble_fix("/tmp/demo/CMakeLists.txt", source).unwrap();
+        assert_eq!(fix.title, "Add project()");
+        let (fixed, _) = fix::apply_fixes(source, &[fix]);
+        assert_eq!(
+            fixed,
+            "cmake_minimum_required(VERSION 3.20)\nproject(demo LANGUAGES CXX)\nadd_executable(app main.cpp)\n"
+        );
+
+        let source = "cmake_minimum_required(VERSION 3.20)\nproject(demo)\n";
+        assert!(preamble_fix("/tmp/demo/CMakeLists.txt", source).is_none());
+        let source = "if(NOT DEFINED NAME)\n    cmake_minimum_required(VERSION 3.20)\n    Project(demo)\nendif()\n";
+        assert!(preamble_fix("/tmp/demo/CMakeLists.txt", source).is_none());
+        let source =
+            "cmake_minimum_required(VERSION 3.20)\nif(WIN32)\n    project(demo)\nendif()\n";
+        assert!(preamble_fix("/tmp/demo/CMakeLists.txt", source).is_none());
+    }
+
+    #[test]
+    fn test_project_name() {
+        assert_eq!(project_name(Some(Path::new("/tmp/my demo"))), "my_demo");
+        assert_eq!(
+            project_name(Some(Path::new("/tmp/lib(v2);#$x\"y"))),
+            "lib_v2____x_y"
+        );
+        assert_eq!(project_name(Some(Path::new("/tmp/fmt-9.1"))), "fmt-9.1");
+        assert_eq!(project_name(Some(Path::new("/tmp/(())"))), "project");
+        assert_eq!(project_name(None), "project");
+    }
+
+    #[test]
+    fn test_deprecated_commands() {
+        let source = "exec_program(ls)\nbuild_name(NAME)\nexecute_process(COMMAND ls)\n";
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let errors =
+            checkerror_inner("/tmp/deprecated.cmake", &lines, thetree.root_node(), true).unwrap();
+        let rules: Vec<&str> = errors
+            .iter()
+            .filter_map(|error| error.rule)
+            .map(|rule| rule.id)
+            .collect();
+        assert_eq!(rules, ["N501", "N107"]);
+        assert_eq!(
+            errors[1].message,
+            "build_name() was removed in CMake 4.0, use the CMAKE_SYSTEM and CMAKE_<LANG>_COMPILER variables instead"
+        );
+    }
+
+    #[test]
+    fn test_try_checkerror_arbitrary_input() {
+        const ALPHABET: &[u8] = b"set(${}) \"\\\n#[]=ab\r\xc3\xa9";
+        let lint_info = || LintConfigInfo {
+            use_lint: true,
+            use_extra_cmake_lint: false,
+            detached: false,
+        };
+        assert!(try_checkerror(&"CMakeLists.txt", b"set(\xff)", lint_info()).is_err());
+
+        for bytes in input::arbitrary_inputs(0x1234_5678, ALPHABET, 200, 128) {
+            let input = String::from_utf8_lossy(&bytes);
+            assert!(try_checkerror(&"CMakeLists.txt", input.as_bytes(), lint_info()).is_ok());
+        }
+    }
 }
@@ -0,0 +1,320 @@
+//! Lexer level checks of quotes and parentheses.
+//!
+//! tree-sitter reports an unterminated `"` or a missing `)` as an ERROR covering
+//! everything up to the end of the file. Scanning the tokens tells where the quote
+//! or the parenthesis was opened, and where the missing one most likely goes.
+
+use tree_sitter::Point;
+
+use super::{Fix, FixableIssue};
+use crate::rules;
+
+pub const UNTERMINATED_QUOTE: &str = "Unterminated quoted argument";
+pub const MISSING_PAREN: &str = "Missing closing parenthesis";
+pub const UNMATCHED_PAREN: &str = "Unmatched closing parenthesis";
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Normal,
+    Quoted { start: Point },
+    Bracket { equals: usize, comment: bool },
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// Whether the line starts with `identifier(`, the way commands do
+fn starts_command(line: &str) -> bool {
+    let line = line.trim_start();
+    let name_len = line
+        .bytes()
+        .take_while(|byte| byte.is_ascii_alphanumeric() || *byte == b'_')
+        .count();
+    name_len > 0
+        && !line.as_bytes()[0].is_ascii_digit()
+        && line[name_len..].trim_start().starts_with('(')
+}
+
+/// `[[`, `[=[`, ... at `at`, returning the count of `=`
+fn bracket_open(line: &[u8], at: usize) -> Option<usize> {
+    if line.get(at) != Some(&b'[') {
+        return None;
+    }
+    let equals = line[at + 1..]
+        .iter()
+        .take_while(|byte| **byte == b'=')
+        .count();
+    (line.get(at + 1 + equals) == Some(&b'[')).then_some(equals)
+}
+
+fn bracket_closes(line: &[u8], at: usize, equals: usize) -> bool {
+    line.get(at) == Some(&b']')
+        && line[at + 1..]
+            .iter()
+            .take_while(|byte| **byte == b'=')
+            .count()
+            == equals
+        && line.get(at + 1 + equals) == Some(&b']')
+}
+
+/// The `)` a quote left open on its line most likely belongs after it
+fn quote_issue(lines: &[&str], start: Point) -> (FixableIssue, bool) {
+    let line = lines[start.row];
+    let content = line.trim_end();
+    let closes_command = content.ends_with(')') && content.len() - 1 > start.column;
+    let insert_at = Point {
+        row: start.row,
+        column: if closes_command {
+            content.len() - 1
+        } else {
+            content.len()
+        },
+    };
+    let issue = FixableIssue {
+        start_point: start,
+        end_point: Point {
+            row: start.row,
+            column: line.len(),
+        },
+        message: UNTERMINATED_QUOTE.to_string(),
+        rule: &rules::UNTERMINATED_QUOTE,
+        fix: Fix {
+            title: "Insert missing `\"`".to_string(),
+            start: insert_at,
+            end: insert_at,
+            new_text: "\"".to_string(),
+            safe: false,
+        },
+    };
+    (issue, closes_command)
+}
+
+fn paren_issue(lines: &[&str], open: Point, last_content: Point) -> FixableIssue {
+    let line = lines[open.row].as_bytes();
+    let mut start = open.column;
+    while start > 0 && line[start - 1].is_ascii_whitespace() {
+        start -= 1;
+    }
+    while start > 0 && (line[start - 1].is_ascii_alphanumeric() || line[start - 1] == b'_') {
+        start -= 1;
+    }
+    let after_open = Point {
+        row: open.row,
+        column: open.column + 1,
+    };
+    let insert_at = last_content.max(after_open);
+    FixableIssue {
+        start_point: Point {
+            row: open.row,
+            column: start,
+        },
+        end_point: after_open,
+        message: MISSING_PAREN.to_string(),
+        rule: &rules::MISSING_PAREN,
+        fix: Fix {
+            title: "Insert missing `)`".to_string(),
+            start: insert_at,
+            end: insert_at,
+            new_text: ")".to_string(),
+            safe: false,
+        },
+    }
+}
+
+/// Find the unterminated quotes and the unbalanced parentheses of `source`
+pub fn find_issues(source: &str) -> Vec<FixableIssue> {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut issues = vec![];
+    let mut opened: Vec<Point> = vec![];
+    let mut state = State::Normal;
+    // End of the last code seen, where a missing `)` goes
+    let mut last_content = Point { row: 0, column: 0 };
+    for (row, text) in lines.iter().enumerate() {
+        // A new command at the indentation of an unclosed one means it was never closed
+        if let State::Quoted { start } = state
+            && row > start.row
+            && starts_command(text)
+            && indentation(text) <= indentation(lines[start.row])
+        {
+            let (issue, closes_command) = quote_issue(&lines, start);
+            issues.push(issue);
+            if closes_command {
+                opened.pop();
+            }
+            state = State::Normal;
+        }
+        if matches!(state, State::Normal)
+            && let Some(&outer) = opened.first()
+            && row > outer.row
+            && starts_command(text)
+            && indentation(text) <= indentation(lines[outer.row])
+        {
+            issues.push(paren_issue(&lines, outer, last_content));
+            opened.clear();
+        }
+        let line = text.as_bytes();
+        let mut column = 0;
+        while column < line.len() {
+            let byte = line[column];
+            let mut step = 1;
+            match state {
+                State::Normal => match byte {
+                    b'\\' => step = 2,
+                    b'#' => match bracket_open(line, column + 1) {
+                        Some(equals) => {
+                            state = State::Bracket {
+                                equals,
+                                comment: true,
+                            };
+                            step = equals + 3;
+                        }
+                        // Line comment
+                        None => break,
+                    },
+                    b'[' => {
+                        if let Some(equals) = bracket_open(line, column) {
+                            state = State::Bracket {
+                                equals,
+                                comment: false,
+                            };
+                            step = equals + 2;
+                        }
+                    }
+                    b'"' => {
+                        state = State::Quoted {
+                            start: Point { row, column },
+                        };
+                    }
+                    b'(' => opened.push(Point { row, column }),
+                    b')' if opened.pop().is_none() => {
+                        let end = Point {
+                            row,
+                            column: column + 1,
+                        };
+                        issues.push(FixableIssue {
+                            start_point: Point { row, column },
+                            end_point: end,
+                            message: UNMATCHED_PAREN.to_string(),
+                            rule: &rules::UNMATCHED_PAREN,
+                            fix: Fix {
+                                title: "Remove unmatched `)`".to_string(),
+                                start: Point { row, column },
+                                end,
+                                new_text: String::new(),
+                                safe: false,
+                            },
+                        });
+                    }
+                    _ => {}
+                },
+                State::Quoted { .. } => match byte {
+                    b'\\' => step = 2,
+                    b'"' => state = State::Normal,
+                    _ => {}
+                },
+                State::Bracket { equals, comment } => {
+                    if bracket_closes(line, column, equals) {
+                        state = State::Normal;
+                        step = equals + 2;
+                        if comment {
+                            column += step;
+                            continue;
+                        }
+                    } else if comment {
+                        column += 1;
+                        continue;
+                    }
+                }
+            }
+            if !byte.is_ascii_whitespace() && !matches!(state, State::Bracket { comment: true, .. })
+            {
+                last_content = Point {
+                    row,
+                    column: (column + step).min(line.len()),
+                };
+            }
+            column += step;
+        }
+    }
+    if let State::Quoted { start } = state {
+        let (issue, closes_command) = quote_issue(&lines, start);
+        issues.push(issue);
+        if closes_command {
+            opened.pop();
+        }
+    }
+    if let Some(&outer) = opened.first() {
+        issues.push(paren_issue(&lines, outer, last_content));
+    }
+    issues.sort_by_key(|issue| issue.start_point);
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unterminated_quote() {
+        let issues = find_issues("message(\"hello)\nset(A b)\n");
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].message, UNTERMINATED_QUOTE);
+        assert_eq!(issues[0].start_point, Point { row: 0, column: 8 });
+        let insert_at = Point { row: 0, column: 14 };
+        assert_eq!(
+            (issues[0].fix.start, issues[0].fix.end),
+            (insert_at, insert_at)
+        );
+        assert_eq!(issues[0].fix.new_text, "\"");
+
+        let issues = find_issues("message(STATUS \"hello");
+        assert_eq!(
+            issues
+                .iter()
+                .map(|issue| issue.message.as_str())
+                .collect::<Vec<_>>(),
+            vec![MISSING_PAREN, UNTERMINATED_QUOTE]
+        );
+    }
+
+    #[test]
+    fn test_missing_paren() {
+        let issues = find_issues("set(A b # comment\nmessage(hi)\n");
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].message, MISSING_PAREN);
+        assert_eq!(issues[0].start_point, Point { row: 0, column: 0 });
+        assert_eq!(issues[0].end_point, Point { row: 0, column: 4 });
+        let insert_at = Point { row: 0, column: 7 };
+        assert_eq!(
+            (issues[0].fix.start, issues[0].fix.end),
+            (insert_at, insert_at)
+        );
+        assert_eq!(issues[0].fix.new_text, ")");
+
+        // Nested commands are not a new statement
+        let issues = find_issues("if(A\n  message(x)\n");
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].fix.start, Point { row: 1, column: 12 });
+    }
+
+    #[test]
+    fn test_unmatched_paren() {
+        let issues = find_issues("set(A b))\n");
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].message, UNMATCHED_PAREN);
+        assert_eq!(issues[0].start_point, Point { row: 0, column: 8 });
+    }
+
+    #[test]
+    fn test_balanced() {
+        let source = r#"set(A "multi
+line \" ( ")
+#[[ ( " ]]
+message([=[ ) " ]=] \) \")
+if((A AND B) OR C) # )
+endif()
+"#;
+        assert_eq!(find_issues(source), vec![]);
+    }
+}
@@ -0,0 +1,162 @@
+//! The calls of a file and their arguments, shared by the checks.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex, PoisonError};
+use std::time::SystemTime;
+
+use tree_sitter::Node;
+
+use super::ErrorInformation;
+use crate::CMakeNodeKinds;
+use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+use crate::rules::Rule;
+use crate::utils::module_path;
+
+pub(super) struct Argument<'a> {
+    pub(super) node: Node<'a>,
+    pub(super) text: &'a str,
+}
+
+pub(super) fn arguments<'a>(command: Node<'a>, source: &[&'a str]) -> Vec<Argument<'a>> {
+    let Some(argument_list) = command
+        .child(2)
+        .filter(|node| node.kind() == CMakeNodeKinds::ARGUMENT_LIST)
+    else {
+        return vec![];
+    };
+    let mut walk = argument_list.walk();
+    argument_list
+        .children(&mut walk)
+        .filter(|node| {
+            node.kind() == CMakeNodeKinds::ARGUMENT
+                && node.start_position().row == node.end_position().row
+        })
+        .filter_map(|node| {
+            let h = node.start_position().row;
+            Some(Argument {
+                node,
+                text: source
+                    .get(h)?
+                    .get(node.start_position().column..node.end_position().column)?,
+            })
+        })
+        .collect()
+}
+
+pub(super) fn command_name(command: Node, source: &[&str]) -> Option<String> {
+    let ids = command.child(0)?;
+    let h = ids.start_position().row;
+    Some(
+        source
+            .get(h)?
+            .get(ids.start_position().column..ids.end_position().column)?
+            .to_lowercase(),
+    )
+}
+
+/// The calls of `name` in `node`, leaving out the bodies of functions and macros
+pub(super) fn commands<'a>(
+    node: Node<'a>,
+    source: &[&str],
+    name: &str,
+    output: &mut Vec<Node<'a>>,
+) {
+    let mut course = node.walk();
+    for child in node.children(&mut course) {
+        match child.kind() {
+            CMakeNodeKinds::NORMAL_COMMAND
+                if command_name(child, source).as_deref() == Some(name) =>
+            {
+                output.push(child);
+            }
+            CMakeNodeKinds::IF_CONDITION
+            | CMakeNodeKinds::FOREACH_LOOP
+            | CMakeNodeKinds::WHILE_LOOP
+            | CMakeNodeKinds::BLOCK_DEF
+            | CMakeNodeKinds::BODY => commands(child, source, name, output),
+            _ => {}
+        }
+    }
+}
+
+type Files = Mutex<HashMap<PathBuf, (SystemTime, Arc<str>)>>;
+
+/// The content of each project file read by the checks, with its modified time
+static FILES: LazyLock<Files> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// The content of the file at `path`, read again only once it has changed
+pub(super) fn read_cached(path: &Path) -> Option<Arc<str>> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    if let Some((cached, content)) = FILES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(path)
+        && *cached == modified
+    {
+        return Some(content.clone());
+    }
+    let content: Arc<str> = std::fs::read_to_string(path).ok()?.into();
+    FILES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(path.to_path_buf(), (modified, content.clone()));
+    Some(content)
+}
+
+/// The minimum version of `cmake_minimum_required(VERSION <min>[...<max>])`
+pub(super) fn required_version(source: &[&str], root: Node) -> Option<(u32, u32)> {
+    let mut calls = vec![];
+    commands(root, source, "cmake_minimum_required", &mut calls);
+    let call = calls.first()?;
+    let arguments = arguments(*call, source);
+    let index = arguments
+        .iter()
+        .position(|argument| argument.text == "VERSION")?;
+    let version = arguments.get(index + 1)?.text.split("...").next()?;
+    let mut parts = version.split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next().map_or(Some(0), |minor| minor.parse().ok())?;
+    Some((major, minor))
+}
+
+/// The version required by `source`, or else by the top level CMakeLists.txt
+pub(super) fn project_version(source: &[&str], root: Node) -> Option<(u32, u32)> {
+    if let Some(version) = required_version(source, root) {
+        return Some(version);
+    }
+    let top_level = read_cached(&module_path::source_dir()?.join("CMakeLists.txt"))?;
+    let mut parse = tree_sitter::Parser::new();
+    parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+    let tree = parse.parse(top_level.as_bytes(), None)?;
+    let lines: Vec<&str> = top_level.lines().collect();
+    required_version(&lines, tree.root_node())
+}
+
+/// Whether the CMakeLists.txt of a parent directory in the project matches
+/// `regex`, its settings apply to the subdirectories
+pub(super) fn matched_by_parent(local_path: &Path, regex: &regex::Regex) -> bool {
+    let (Some(source_dir), Some(dir)) = (module_path::source_dir(), local_path.parent()) else {
+        return false;
+    };
+    dir.ancestors()
+        .skip(1)
+        .take_while(|parent| parent.starts_with(&source_dir))
+        .filter_map(|parent| read_cached(&parent.join("CMakeLists.txt")))
+        .any(|lists| regex.is_match(&lists))
+}
+
+/// Names computed when configuring, which cannot be checked
+pub(super) fn is_computed(text: &str) -> bool {
+    text.contains("${") || text.contains("$<")
+}
+
+pub(super) fn error(node: Node, message: String, rule: &'static Rule) -> ErrorInformation {
+    ErrorInformation {
+        start_point: node.start_position(),
+        end_point: node.end_position(),
+        message,
+        severity: Some(rule.severity),
+        rule: Some(rule),
+    }
+}
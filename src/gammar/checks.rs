@@ -0,0 +1,318 @@
+//! The checks of the lint, run concurrently.
+//!
+//! The checks of a file only read its syntax tree, so they run side by side on
+//! the rayon pool. Each run is timed, and `neocmake/status` reports the checks
+//! which took the most time with the rules they report, to turn off in the
+//! `[rules]` table. A check whose rules are all turned off is not run.
+
+use std::cmp::Reverse;
+use std::path::Path;
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use tree_sitter::Node;
+
+use super::{
+    ErrorInformation, FixableIssue, check_duplicate_targets, check_file_mode, cpack, download,
+    duplicates, expressions, flow, gpu, install, ordering, packages, pkg_config, platforms, qt,
+    references, source_group, spelling, unquoted, vcpkg,
+};
+use crate::rules::{self, Rule};
+
+/// What the checks read
+pub(super) struct Context<'a> {
+    pub local_path: &'a Path,
+    pub source: &'a str,
+    pub lines: &'a [&'a str],
+    pub root: Node<'a>,
+    /// The file is outside of the workspace, see [`PROJECT_CHECKS`]
+    pub detached: bool,
+}
+
+struct Check {
+    name: &'static str,
+    /// The rules of the diagnostics of the check
+    rules: &'static [&'static Rule],
+    run: fn(&Context) -> Vec<ErrorInformation>,
+}
+
+fn fixable(issues: Vec<FixableIssue>) -> Vec<ErrorInformation> {
+    issues.into_iter().map(ErrorInformation::from).collect()
+}
+
+/// The checks reading the index, the variables or the presets of the project,
+/// which would report the file of a dependency or a module of cmake against a
+/// project it is not part of
+const PROJECT_CHECKS: &[&str] = &[
+    "spelling",
+    "duplicate-targets",
+    "install",
+    "gpu",
+    "vcpkg",
+    "unused-packages",
+];
+
+/// Every check, in the order of their diagnostics
+static CHECKS: &[Check] = &[
+    Check {
+        name: "unquoted",
+        rules: &[&rules::UNQUOTED_COMPARISON, &rules::UNQUOTED_SPACES],
+        run: |cx| fixable(unquoted::find_issues(cx.lines, cx.root)),
+    },
+    Check {
+        name: "references",
+        rules: &[&rules::UNTERMINATED_REFERENCE, &rules::STRAY_DOLLAR],
+        run: |cx| fixable(references::find_issues(cx.source)),
+    },
+    Check {
+        name: "spelling",
+        rules: &[&rules::UNKNOWN_VARIABLE],
+        run: |cx| fixable(spelling::find_issues(cx.lines, cx.root)),
+    },
+    Check {
+        name: "platforms",
+        rules: &[&rules::UNKNOWN_PLATFORM],
+        run: |cx| fixable(platforms::find_issues(cx.lines, cx.root)),
+    },
+    Check {
+        name: "duplicate-arguments",
+        rules: &[&rules::DUPLICATE_ARGUMENT],
+        run: |cx| fixable(duplicates::find_issues(cx.lines, cx.root)),
+    },
+    Check {
+        name: "pkg-config",
+        rules: &[&rules::MISSING_PKG_CONFIG],
+        run: |cx| {
+            if pkg_config::found_by_parent(cx.local_path) {
+                return vec![];
+            }
+            fixable(pkg_config::find_issues(cx.lines, cx.root))
+        },
+    },
+    Check {
+        name: "file-mode",
+        rules: &[&rules::NOT_SCRIPTABLE, &rules::TOOLCHAIN_COMMAND],
+        run: |cx| check_file_mode(cx.local_path, cx.lines, cx.root),
+    },
+    Check {
+        name: "duplicate-targets",
+        rules: &[&rules::DUPLICATE_TARGET],
+        run: |cx| check_duplicate_targets(cx.local_path, cx.lines, cx.root),
+    },
+    Check {
+        name: "install",
+        rules: &[
+            &rules::INSTALL_UNKNOWN_TARGET,
+            &rules::INSTALL_MISSING_FILE,
+            &rules::INSTALL_DESTINATION,
+            &rules::INSTALL_UNKNOWN_EXPORT,
+            &rules::EXPORT_NOT_INSTALLED,
+        ],
+        run: |cx| install::check_install(cx.local_path, cx.lines, cx.root),
+    },
+    Check {
+        name: "source-groups",
+        rules: &[&rules::SOURCE_GROUP],
+        run: |cx| source_group::check_source_groups(cx.local_path, cx.lines, cx.root),
+    },
+    Check {
+        name: "downloads",
+        rules: &[
+            &rules::DOWNLOAD_MISSING_HASH,
+            &rules::MALFORMED_GIT_URL,
+            &rules::UNPINNED_GIT_TAG,
+        ],
+        run: |cx| download::check_downloads(cx.lines, cx.root),
+    },
+    Check {
+        name: "cpack",
+        rules: &[&rules::CPACK_AFTER_INCLUDE],
+        run: |cx| cpack::check_cpack_order(cx.lines, cx.root),
+    },
+    Check {
+        name: "gpu",
+        rules: &[
+            &rules::LANGUAGE_NOT_ENABLED,
+            &rules::GPU_ARCHITECTURES_UNSET,
+        ],
+        run: |cx| gpu::check_gpu_languages(cx.local_path, cx.lines, cx.root),
+    },
+    Check {
+        name: "qt",
+        rules: &[&rules::QT_AUTOMOC_OFF],
+        run: |cx| qt::check_automoc(cx.local_path, cx.lines, cx.root),
+    },
+    Check {
+        name: "vcpkg",
+        rules: &[&rules::VCPKG_UNDECLARED_PORT],
+        run: |cx| vcpkg::check_vcpkg_dependencies(cx.lines, cx.root),
+    },
+    Check {
+        name: "unused-packages",
+        rules: &[&rules::UNUSED_PACKAGE],
+        run: |cx| packages::check_unused_packages(cx.local_path, cx.lines, cx.root),
+    },
+    Check {
+        name: "ordering",
+        rules: &[
+            &rules::TARGET_BEFORE_DECLARATION,
+            &rules::INSTALL_DIRS_BEFORE_INCLUDE,
+            &rules::VARIABLE_BEFORE_SET,
+            &rules::LOOP_VARIABLE_AFTER_LOOP,
+        ],
+        run: |cx| ordering::check_ordering(cx.local_path, cx.source, cx.lines, cx.root),
+    },
+    Check {
+        name: "flow",
+        rules: &[&rules::BREAK_OUTSIDE_LOOP, &rules::RETURN_ARGUMENTS],
+        run: |cx| flow::check_flow(cx.lines, cx.root),
+    },
+    Check {
+        name: "expressions",
+        rules: &[&rules::MATH_EXPRESSION, &rules::INVALID_REGEX],
+        run: |cx| expressions::check_expressions(cx.lines, cx.root),
+    },
+];
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Timing {
+    runs: u64,
+    total: Duration,
+    max: Duration,
+}
+
+/// The time taken by the checks since the start
+static TIMINGS: LazyLock<DashMap<&'static str, Timing>> = LazyLock::new(DashMap::new);
+
+impl Check {
+    /// Whether one of the rules of the check is on for the files of `path`
+    fn enabled(&self, path: &Path) -> bool {
+        self.rules
+            .iter()
+            .any(|rule| rule.configured_severity(Some(path)).is_some())
+    }
+
+    fn run_timed(&self, cx: &Context) -> Vec<ErrorInformation> {
+        let start = Instant::now();
+        let output = (self.run)(cx);
+        let elapsed = start.elapsed();
+        let mut timing = TIMINGS.entry(self.name).or_default();
+        timing.runs += 1;
+        timing.total += elapsed;
+        timing.max = timing.max.max(elapsed);
+        output
+    }
+}
+
+/// Run the checks of the rules turned on, the diagnostics in the order of
+/// [`CHECKS`]
+pub(super) fn run_checks(cx: &Context) -> Vec<ErrorInformation> {
+    CHECKS
+        .par_iter()
+        .filter(|check| check.enabled(cx.local_path))
+        .filter(|check| !(cx.detached && PROJECT_CHECKS.contains(&check.name)))
+        .flat_map_iter(|check| check.run_timed(cx))
+        .collect()
+}
+
+/// The time taken by a check, for `neocmake/status`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckTiming {
+    pub name: String,
+    /// The ids of the rules to turn off to skip the check
+    pub rules: Vec<String>,
+    pub runs: u64,
+    pub total_micros: u64,
+    pub average_micros: u64,
+    pub max_micros: u64,
+}
+
+/// The `count` checks which took the most time overall
+pub fn slowest_checks(count: usize) -> Vec<CheckTiming> {
+    let mut timings: Vec<CheckTiming> = CHECKS
+        .iter()
+        .filter_map(|check| {
+            let timing = *TIMINGS.get(&check.name)?;
+            let total_micros = timing.total.as_micros() as u64;
+            Some(CheckTiming {
+                name: check.name.to_string(),
+                rules: check.rules.iter().map(|rule| rule.id.to_string()).collect(),
+                runs: timing.runs,
+                total_micros,
+                average_micros: total_micros / timing.runs.max(1),
+                max_micros: timing.max.as_micros() as u64,
+            })
+        })
+        .collect();
+    timings.sort_by_key(|timing| Reverse(timing.total_micros));
+    timings.truncate(count);
+    timings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    #[test]
+    fn test_checks_cover_their_rules() {
+        for (index, check) in CHECKS.iter().enumerate() {
+            assert!(!check.rules.is_empty(), "{} reports no rule", check.name);
+            for rule in check.rules {
+                assert!(
+                    CHECKS[index + 1..]
+                        .iter()
+                        .all(|other| !other.rules.contains(rule)),
+                    "{} is reported by two checks",
+                    rule.id
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_project_checks_exist() {
+        for name in PROJECT_CHECKS {
+            assert!(CHECKS.iter().any(|check| check.name == *name), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_run_checks() {
+        let source = "if(${A} STREQUAL ON)\nendif()\nbreak()\nset(B $ {C})\n";
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let tree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let cx = Context {
+            local_path: Path::new("/tmp/checks/CMakeLists.txt"),
+            source,
+            lines: &lines,
+            root: tree.root_node(),
+            detached: false,
+        };
+
+        let sequential: Vec<ErrorInformation> = CHECKS
+            .iter()
+            .filter(|check| check.enabled(cx.local_path))
+            .flat_map(|check| (check.run)(&cx))
+            .collect();
+        assert!(!sequential.is_empty());
+        assert_eq!(run_checks(&cx), sequential);
+
+        let slowest = slowest_checks(CHECKS.len());
+        let flow = slowest.iter().find(|timing| timing.name == "flow").unwrap();
+        assert!(flow.runs >= 1);
+        assert_eq!(flow.rules, vec!["N127", "N128"]);
+        assert!(
+            slowest
+                .windows(2)
+                .all(|pair| pair[0].total_micros >= pair[1].total_micros)
+        );
+        assert_eq!(slowest_checks(2).len(), 2);
+    }
+}
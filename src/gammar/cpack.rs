@@ -0,0 +1,133 @@
+//! `include(CPack)` writes the configuration of cpack from the `CPACK_*`
+//! variables set at that time, so setting them afterwards has no effect.
+
+use tree_sitter::Node;
+
+use super::ErrorInformation;
+use super::calls::{arguments, command_name, error};
+use crate::CMakeNodeKinds;
+use crate::rules;
+
+/// The commands changing a variable, with the index of its argument
+const SETTERS: &[(&str, usize)] = &[("set", 0), ("unset", 0), ("list", 1)];
+
+/// The subcommands of `list()` changing the list, the others only read it
+const LIST_SETTERS: &[&str] = &[
+    "APPEND",
+    "PREPEND",
+    "INSERT",
+    "REMOVE_ITEM",
+    "REMOVE_AT",
+    "REMOVE_DUPLICATES",
+    "POP_BACK",
+    "POP_FRONT",
+    "FILTER",
+    "TRANSFORM",
+    "REVERSE",
+    "SORT",
+];
+
+/// The calls of `node` in the order they run, leaving out the bodies of
+/// functions and macros
+fn calls<'a>(node: Node<'a>, output: &mut Vec<Node<'a>>) {
+    let mut course = node.walk();
+    for child in node.children(&mut course) {
+        match child.kind() {
+            CMakeNodeKinds::NORMAL_COMMAND => output.push(child),
+            CMakeNodeKinds::IF_CONDITION
+            | CMakeNodeKinds::FOREACH_LOOP
+            | CMakeNodeKinds::WHILE_LOOP
+            | CMakeNodeKinds::BLOCK_DEF
+            | CMakeNodeKinds::BODY => calls(child, output),
+            _ => {}
+        }
+    }
+}
+
+/// Find the CPack variables set after `include(CPack)`
+pub fn check_cpack_order(source: &[&str], root: Node) -> Vec<ErrorInformation> {
+    let mut commands = vec![];
+    calls(root, &mut commands);
+    let mut included_at = None;
+    let mut output = vec![];
+    for command in commands {
+        let Some(name) = command_name(command, source) else {
+            continue;
+        };
+        let h = command.start_position().row;
+        let arguments = arguments(command, source);
+        if name == "include" {
+            if included_at.is_none()
+                && arguments
+                    .first()
+                    .is_some_and(|module| module.text.trim_matches('"') == "CPack")
+            {
+                included_at = Some(h);
+            }
+            continue;
+        }
+        let Some(line) = included_at else {
+            continue;
+        };
+        let Some((_, index)) = SETTERS.iter().find(|(setter, _)| *setter == name) else {
+            continue;
+        };
+        if name == "list"
+            && !arguments
+                .first()
+                .is_some_and(|subcommand| LIST_SETTERS.contains(&subcommand.text))
+        {
+            continue;
+        }
+        let Some(variable) = arguments.get(*index) else {
+            continue;
+        };
+        let variable_name = variable.text.trim_matches('"');
+        if variable_name.starts_with("CPACK_") {
+            output.push(error(
+                variable.node,
+                format!(
+                    "{variable_name} is set after include(CPack) at line {}, which already wrote the cpack configuration",
+                    line + 1
+                ),
+                &rules::CPACK_AFTER_INCLUDE,
+            ));
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::Point;
+
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    #[test]
+    fn test_cpack_order() {
+        let source = r#"set(CPACK_PACKAGE_NAME demo)
+include(CPack)
+set(CPACK_GENERATOR "TGZ;DEB")
+list(APPEND CPACK_SOURCE_IGNORE_FILES build)
+list(LENGTH CPACK_COMPONENTS_ALL count)
+list(FIND CPACK_GENERATOR DEB index)
+set(OTHER 1)
+cpack_add_component(runtime)
+"#;
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let errors = check_cpack_order(&lines, thetree.root_node());
+        let found: Vec<Point> = errors.iter().map(|error| error.start_point).collect();
+        assert_eq!(
+            found,
+            vec![Point { row: 2, column: 4 }, Point { row: 3, column: 12 }]
+        );
+        assert_eq!(
+            errors[0].message,
+            "CPACK_GENERATOR is set after include(CPack) at line 2, which already wrote the cpack configuration"
+        );
+    }
+}
@@ -0,0 +1,221 @@
+//! Checks of the sources downloaded by `FetchContent_Declare()` and
+//! `ExternalProject_Add()`: archives without a hash, git URLs which cannot be
+//! cloned, and git references following a branch.
+
+use std::sync::LazyLock;
+
+use tree_sitter::Node;
+
+use super::ErrorInformation;
+use super::calls::{Argument, arguments, commands, error, is_computed};
+use crate::rules;
+
+const DOWNLOAD_COMMANDS: &[&str] = &["fetchcontent_declare", "externalproject_add"];
+
+/// The options giving the expected hash of a `URL` download
+const HASH_OPTIONS: &[&str] = &["URL_HASH", "URL_MD5"];
+
+/// Branch names, which give another commit once they move
+const BRANCHES: &[&str] = &["master", "main", "develop", "dev", "trunk", "HEAD"];
+
+/// The schemes git can clone from
+const GIT_SCHEMES: &[&str] = &["http", "https", "ssh", "git", "file", "git+ssh", "ssh+git"];
+
+/// An option of the commands, ending the values of the previous one
+static OPTION_REGEX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"^[A-Z][A-Z0-9_]*$").unwrap());
+
+/// `user@host:path`, the scp-like syntax of git
+static SCP_REGEX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"^[\w.-]+@[\w.-]+:").unwrap());
+
+/// `C:/path` or `C:\path`, a local path on Windows
+static DRIVE_REGEX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"^[A-Za-z]:[/\\]").unwrap());
+
+/// The values given after `option`
+fn values<'a, 'b>(arguments: &'b [Argument<'a>], option: &str) -> Option<&'b [Argument<'a>]> {
+    let index = arguments
+        .iter()
+        .position(|argument| argument.text == option)?;
+    let rest = &arguments[index + 1..];
+    let end = rest
+        .iter()
+        .position(|argument| OPTION_REGEX.is_match(argument.text))
+        .unwrap_or(rest.len());
+    Some(&rest[..end])
+}
+
+/// Why `url` cannot be cloned by git, if it looks wrong
+fn git_url_problem(url: &str) -> Option<&'static str> {
+    if url.chars().any(char::is_whitespace) {
+        return Some("it contains spaces");
+    }
+    if let Some((scheme, rest)) = url.split_once("://") {
+        if !GIT_SCHEMES.contains(&scheme.to_lowercase().as_str()) {
+            return Some("git does not know this scheme");
+        }
+        if rest.is_empty() || (rest.starts_with('/') && scheme != "file") {
+            return Some("the host is missing");
+        }
+        return None;
+    }
+    if SCP_REGEX.is_match(url) || DRIVE_REGEX.is_match(url) || url.starts_with(['/', '.', '~']) {
+        return None;
+    }
+    if url.contains(":/") {
+        return Some("the scheme needs `://`");
+    }
+    // `github.com/user/repo` is taken as a local path
+    if url
+        .split('/')
+        .next()
+        .is_some_and(|host| host.contains('.') && url.contains('/'))
+    {
+        return Some("the scheme is missing");
+    }
+    None
+}
+
+fn check_call(command: Node, source: &[&str], output: &mut Vec<ErrorInformation>) {
+    let arguments = arguments(command, source);
+    if arguments.is_empty() {
+        return;
+    }
+    let has = |option: &str| arguments.iter().any(|argument| argument.text == option);
+
+    if let Some(urls) = values(&arguments, "URL")
+        && !HASH_OPTIONS.iter().any(|option| has(option))
+        && let Some(url) = urls.first()
+    {
+        let text = url.text.trim_matches('"');
+        // A computed URL may well be a local archive
+        let remote = !is_computed(text)
+            && text
+                .split_once("://")
+                .is_some_and(|(scheme, _)| !scheme.eq_ignore_ascii_case("file"));
+        if remote {
+            output.push(error(
+                url.node,
+                format!("The download of \"{text}\" is not checked, URL_HASH is missing"),
+                &rules::DOWNLOAD_MISSING_HASH,
+            ));
+        }
+    }
+
+    let Some(repository) = values(&arguments, "GIT_REPOSITORY").and_then(|urls| urls.first())
+    else {
+        return;
+    };
+    let url = repository.text.trim_matches('"');
+    if !is_computed(url)
+        && let Some(problem) = git_url_problem(url)
+    {
+        output.push(error(
+            repository.node,
+            format!("\"{url}\" is not a git URL, {problem}"),
+            &rules::MALFORMED_GIT_URL,
+        ));
+    }
+    let tag = arguments
+        .iter()
+        .position(|argument| argument.text == "GIT_TAG")
+        .and_then(|index| arguments.get(index + 1));
+    match tag {
+        None => output.push(error(
+            arguments[0].node,
+            "Without GIT_TAG the default branch is checked out, which changes over time"
+                .to_string(),
+            &rules::UNPINNED_GIT_TAG,
+        )),
+        Some(tag) => {
+            let name = tag.text.trim_matches('"');
+            if BRANCHES.contains(&name) || name.starts_with("origin/") {
+                output.push(error(
+                    tag.node,
+                    format!("GIT_TAG {name} follows a branch, pin a tag or a commit instead"),
+                    &rules::UNPINNED_GIT_TAG,
+                ));
+            }
+        }
+    }
+}
+
+fn check_calls(source: &[&str], calls: &[Node]) -> Vec<ErrorInformation> {
+    let mut output = vec![];
+    for call in calls {
+        check_call(*call, source, &mut output);
+    }
+    output
+}
+
+/// Check the downloads declared in `source`
+pub fn check_downloads(source: &[&str], root: Node) -> Vec<ErrorInformation> {
+    let mut calls = vec![];
+    for name in DOWNLOAD_COMMANDS {
+        commands(root, source, name, &mut calls);
+    }
+    calls.sort_by_key(|call| call.start_byte());
+    check_calls(source, &calls)
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::Point;
+
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    #[test]
+    fn test_git_url_problem() {
+        assert_eq!(git_url_problem("https://github.com/fmtlib/fmt.git"), None);
+        assert_eq!(git_url_problem("git@github.com:fmtlib/fmt.git"), None);
+        assert_eq!(git_url_problem("../fmt"), None);
+        assert_eq!(git_url_problem("file:///srv/git/fmt"), None);
+        assert_eq!(git_url_problem("C:/repos/fmt"), None);
+        assert_eq!(git_url_problem("D:\\repos\\fmt"), None);
+        assert_eq!(
+            git_url_problem("github.com/fmtlib/fmt"),
+            Some("the scheme is missing")
+        );
+        assert_eq!(
+            git_url_problem("https:/github.com/fmtlib/fmt"),
+            Some("the scheme needs `://`")
+        );
+        assert_eq!(
+            git_url_problem("htps://github.com/fmtlib/fmt"),
+            Some("git does not know this scheme")
+        );
+        assert_eq!(git_url_problem("https:///fmt"), Some("the host is missing"));
+    }
+
+    #[test]
+    fn test_download_checks() {
+        let source = r#"include(FetchContent)
+FetchContent_Declare(json URL https://example.com/json.tar.xz)
+FetchContent_Declare(zlib URL https://example.com/zlib.tar.gz URL_HASH SHA256=abc)
+FetchContent_Declare(png URL ${PNG_ARCHIVE} C:/archives/png.tar.gz)
+FetchContent_Declare(fmt GIT_REPOSITORY github.com/fmtlib/fmt GIT_TAG master)
+ExternalProject_Add(spdlog GIT_REPOSITORY https://github.com/gabime/spdlog.git)
+FetchContent_Declare(catch GIT_REPOSITORY https://github.com/catchorg/Catch2.git GIT_TAG v3.5.0)
+"#;
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let errors = check_downloads(&lines, thetree.root_node());
+        let found: Vec<(Point, &str)> = errors
+            .iter()
+            .map(|error| (error.start_point, error.rule.unwrap().name))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (Point { row: 1, column: 30 }, "download-missing-hash"),
+                (Point { row: 4, column: 40 }, "malformed-git-url"),
+                (Point { row: 4, column: 70 }, "unpinned-git-tag"),
+                (Point { row: 5, column: 20 }, "unpinned-git-tag"),
+            ]
+        );
+    }
+}
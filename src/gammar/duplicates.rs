@@ -0,0 +1,311 @@
+//! Source files, link libraries and include directories given twice, in one
+//! call or in two calls adding them to the same target.
+//!
+//! The calls in the branches of `if()` and in loops are compared with the ones
+//! before them but not with each other, as only one branch may run.
+
+use std::collections::HashMap;
+
+use tree_sitter::{Node, Point};
+
+use super::{Fix, FixableIssue};
+use crate::CMakeNodeKinds;
+use crate::rules;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum ListKind {
+    Sources,
+    Libraries,
+    IncludeDirectories,
+}
+
+impl ListKind {
+    fn description(self) -> &'static str {
+        match self {
+            Self::Sources => "source file",
+            Self::Libraries => "link library",
+            Self::IncludeDirectories => "include directory",
+        }
+    }
+}
+
+/// The commands listing items, whether their first argument is the target,
+/// and the keywords among the items
+const LIST_COMMANDS: &[(&str, ListKind, bool, &[&str])] = &[
+    (
+        "add_executable",
+        ListKind::Sources,
+        true,
+        &["WIN32", "MACOSX_BUNDLE", "EXCLUDE_FROM_ALL"],
+    ),
+    (
+        "add_library",
+        ListKind::Sources,
+        true,
+        &[
+            "STATIC",
+            "SHARED",
+            "MODULE",
+            "OBJECT",
+            "INTERFACE",
+            "UNKNOWN",
+            "EXCLUDE_FROM_ALL",
+        ],
+    ),
+    (
+        "target_sources",
+        ListKind::Sources,
+        true,
+        &["INTERFACE", "PUBLIC", "PRIVATE"],
+    ),
+    (
+        "target_link_libraries",
+        ListKind::Libraries,
+        true,
+        &[
+            "INTERFACE",
+            "PUBLIC",
+            "PRIVATE",
+            "LINK_PUBLIC",
+            "LINK_PRIVATE",
+            "LINK_INTERFACE_LIBRARIES",
+        ],
+    ),
+    ("link_libraries", ListKind::Libraries, false, &[]),
+    (
+        "target_include_directories",
+        ListKind::IncludeDirectories,
+        true,
+        &[
+            "SYSTEM",
+            "AFTER",
+            "BEFORE",
+            "INTERFACE",
+            "PUBLIC",
+            "PRIVATE",
+        ],
+    ),
+    (
+        "include_directories",
+        ListKind::IncludeDirectories,
+        false,
+        &["SYSTEM", "AFTER", "BEFORE"],
+    ),
+];
+
+/// Keywords after which the arguments are not items anymore
+const END_KEYWORDS: &[&str] = &["FILE_SET"];
+
+/// Keywords of `target_link_libraries` for the library after them only
+const CONFIGURATION_KEYWORDS: &[&str] = &["debug", "optimized", "general"];
+
+/// Arguments of `add_library` and `add_executable` for targets without sources
+const NO_SOURCE_KEYWORDS: &[&str] = &["ALIAS", "IMPORTED"];
+
+/// Keywords setting the scope of the items after them, an item given in two
+/// scopes is not a duplicate
+const SCOPE_KEYWORDS: &[&str] = &[
+    "INTERFACE",
+    "PUBLIC",
+    "PRIVATE",
+    "LINK_PUBLIC",
+    "LINK_PRIVATE",
+    "LINK_INTERFACE_LIBRARIES",
+];
+
+/// The target, or `None` for the directory, the kind of list, the scope and
+/// the item
+type ItemKey = (Option<String>, ListKind, Option<String>, String);
+
+fn text<'a>(node: Node, source: &[&'a str]) -> Option<&'a str> {
+    let row = node.start_position().row;
+    if row != node.end_position().row {
+        return None;
+    }
+    source[row].get(node.start_position().column..node.end_position().column)
+}
+
+fn check_command<'a>(
+    command: Node,
+    source: &[&'a str],
+    seen: &mut HashMap<ItemKey, (Point, &'a str)>,
+    output: &mut Vec<FixableIssue>,
+) {
+    let Some(name) = command.child(0).and_then(|ids| text(ids, source)) else {
+        return;
+    };
+    let name = name.to_lowercase();
+    let Some(&(_, kind, has_target, keywords)) =
+        LIST_COMMANDS.iter().find(|(command, ..)| *command == name)
+    else {
+        return;
+    };
+    let Some(argument_list) = command
+        .child(2)
+        .filter(|node| node.kind() == CMakeNodeKinds::ARGUMENT_LIST)
+    else {
+        return;
+    };
+    let mut walk = argument_list.walk();
+    let mut arguments = argument_list
+        .children(&mut walk)
+        .filter(|node| node.kind() == CMakeNodeKinds::ARGUMENT)
+        .filter_map(|node| Some((node, text(node, source)?)));
+    let target = if has_target {
+        let Some((_, target)) = arguments.next() else {
+            return;
+        };
+        Some(target.trim_matches('"').to_string())
+    } else {
+        None
+    };
+    let arguments: Vec<(Node, &str)> = arguments.collect();
+    if arguments
+        .iter()
+        .any(|(_, argument)| NO_SOURCE_KEYWORDS.contains(argument))
+    {
+        return;
+    }
+
+    // The sources given when adding the target are private ones
+    let adding = name.starts_with("add_");
+    let mut scope = adding.then(|| "PRIVATE".to_string());
+    let mut skip_next = false;
+    for (node, argument) in arguments {
+        if END_KEYWORDS.contains(&argument) {
+            break;
+        }
+        if !adding && keywords.contains(&argument) && SCOPE_KEYWORDS.contains(&argument) {
+            scope = Some(argument.to_string());
+        }
+        if std::mem::take(&mut skip_next) || keywords.contains(&argument) {
+            continue;
+        }
+        if kind == ListKind::Libraries && CONFIGURATION_KEYWORDS.contains(&argument) {
+            skip_next = true;
+            continue;
+        }
+        let item = argument.trim_matches('"');
+        // The value of a variable is not known, it may well be empty
+        if item.is_empty() || item.contains("${") {
+            continue;
+        }
+        let key = (target.clone(), kind, scope.clone(), item.to_string());
+        let Some((first, first_text)) = seen.get(&key) else {
+            seen.insert(key, (node.start_position(), argument));
+            continue;
+        };
+        // Removing the whitespace before it too, which the previous argument or
+        // the target name always has
+        let start = node
+            .prev_sibling()
+            .filter(|previous| previous.kind() == CMakeNodeKinds::ARGUMENT)
+            .map_or(node.start_position(), |previous| previous.end_position());
+        output.push(FixableIssue {
+            start_point: node.start_position(),
+            end_point: node.end_position(),
+            message: format!(
+                "Duplicate {} {item}, already given at line {}",
+                kind.description(),
+                first.row + 1
+            ),
+            rule: &rules::DUPLICATE_ARGUMENT,
+            fix: Fix {
+                title: format!("Remove the duplicate {item}"),
+                start,
+                end: node.end_position(),
+                new_text: String::new(),
+                // The order of the libraries matters to the linker, and a
+                // quoted item may be meant to differ from an unquoted one
+                safe: kind != ListKind::Libraries && *first_text == argument,
+            },
+        });
+    }
+}
+
+fn find_issues_inner<'a>(
+    node: Node,
+    source: &[&'a str],
+    seen: &mut HashMap<ItemKey, (Point, &'a str)>,
+    output: &mut Vec<FixableIssue>,
+) {
+    let mut course = node.walk();
+    for child in node.children(&mut course) {
+        match child.kind() {
+            CMakeNodeKinds::NORMAL_COMMAND => check_command(child, source, seen, output),
+            // Each call of a function or a macro adds to its own targets
+            CMakeNodeKinds::FUNCTION_DEF | CMakeNodeKinds::MACRO_DEF => {
+                find_issues_inner(child, source, &mut HashMap::new(), output);
+            }
+            CMakeNodeKinds::IF_CONDITION
+            | CMakeNodeKinds::FOREACH_LOOP
+            | CMakeNodeKinds::WHILE_LOOP
+            | CMakeNodeKinds::BLOCK_DEF => find_issues_inner(child, source, seen, output),
+            CMakeNodeKinds::BODY => {
+                find_issues_inner(child, source, &mut seen.clone(), output);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Find the items given twice to the same target
+pub fn find_issues(source: &[&str], root: Node) -> Vec<FixableIssue> {
+    let mut output = vec![];
+    find_issues_inner(root, source, &mut HashMap::new(), &mut output);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+    use crate::gammar::fix::apply_fixes;
+
+    #[test]
+    fn test_duplicate_arguments() {
+        let source = r#"add_executable(app main.c util.c main.c)
+target_link_libraries(app PRIVATE m debug foo optimized foo)
+target_link_libraries(app PUBLIC m)
+target_link_libraries(app PRIVATE m ${LIBS} ${LIBS})
+if(WIN32)
+    target_sources(app PRIVATE win.c)
+else()
+    target_sources(app PRIVATE win.c "util.c")
+endif()
+add_library(lib ALIAS app)
+include_directories(include include)
+target_include_directories(app PUBLIC include PRIVATE include)
+"#;
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let issues = find_issues(&lines, thetree.root_node());
+        let messages: Vec<&str> = issues.iter().map(|issue| issue.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "Duplicate source file main.c, already given at line 1",
+                "Duplicate link library m, already given at line 2",
+                "Duplicate source file util.c, already given at line 1",
+                "Duplicate include directory include, already given at line 11",
+            ]
+        );
+        assert!(!issues[1].fix.safe);
+        assert!(!issues[2].fix.safe);
+        assert!(issues[3].fix.safe);
+
+        let fixes: Vec<Fix> = issues.into_iter().map(|issue| issue.fix).collect();
+        let (fixed, _) = apply_fixes(source, &fixes);
+        let fixed: Vec<&str> = fixed.lines().collect();
+        assert_eq!(fixed[0], "add_executable(app main.c util.c)");
+        assert_eq!(fixed[2], "target_link_libraries(app PUBLIC m)");
+        assert_eq!(
+            fixed[3],
+            "target_link_libraries(app PRIVATE ${LIBS} ${LIBS})"
+        );
+        assert_eq!(fixed[7], "    target_sources(app PRIVATE win.c)");
+        assert_eq!(fixed[10], "include_directories(include)");
+    }
+}
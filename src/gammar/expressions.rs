@@ -0,0 +1,435 @@
+//! The expressions cmake parses when configuring: the `math(EXPR)` arithmetic,
+//! and the patterns of `string(REGEX)`, for its own regex engine which lacks
+//! most of the PCRE syntax.
+
+use std::iter::Peekable;
+use std::str::Chars;
+use std::sync::LazyLock;
+
+use tree_sitter::Node;
+
+use super::ErrorInformation;
+use super::calls::{arguments, error};
+use crate::CMakeNodeKinds;
+use crate::rules;
+use crate::utils::bracket_content;
+
+/// The `string(REGEX)` modes, followed by the pattern
+const REGEX_MODES: &[&str] = &["MATCH", "MATCHALL", "REPLACE"];
+
+/// The capture groups of the regex engine, after the whole match
+const MAX_GROUPS: usize = 9;
+
+/// A variable reference with no other inside
+static REFERENCE_REGEX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"\$(ENV)?\{[^${}]*\}").unwrap());
+
+/// A `{n}` or `{n,m}` repetition of PCRE
+static REPETITION_REGEX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"^\{\d+(,\d*)?\}").unwrap());
+
+/// The text given to the command, with the brackets, the quotes and the
+/// escapes of cmake removed
+fn unescape(text: &str) -> String {
+    if let Some(content) = bracket_content(text) {
+        return content.to_string();
+    }
+    let text = text
+        .strip_prefix('"')
+        .and_then(|text| text.strip_suffix('"'))
+        .unwrap_or(text);
+    let mut output = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            output.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => output.push('\n'),
+            Some('t') => output.push('\t'),
+            Some('r') => output.push('\r'),
+            Some(c) => output.push(c),
+            None => output.push('\\'),
+        }
+    }
+    output
+}
+
+struct MathParser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl MathParser<'_> {
+    fn peek(&mut self) -> Option<char> {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+        self.chars.peek().copied()
+    }
+
+    fn binary_operator(&mut self) -> bool {
+        match self.peek() {
+            Some('+' | '-' | '*' | '/' | '%' | '|' | '&' | '^') => {
+                self.chars.next();
+                true
+            }
+            Some(c @ ('<' | '>')) => {
+                let mut ahead = self.chars.clone();
+                ahead.next();
+                if ahead.next() != Some(c) {
+                    return false;
+                }
+                self.chars = ahead;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The literal after the unary operators of the operand, `negative` when
+    /// the last one is a minus, which lets -9223372036854775808 fit
+    fn number(&mut self, negative: bool) -> Result<(), String> {
+        let mut digits = String::new();
+        while let Some(c) = self.chars.next_if(|c| c.is_ascii_alphanumeric()) {
+            digits.push(c);
+        }
+        let valid = match digits.strip_prefix("0x").or(digits.strip_prefix("0X")) {
+            Some(hex) => !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit()),
+            None => digits.chars().all(|c| c.is_ascii_digit()),
+        };
+        if !valid {
+            return Err(format!("{digits} is not a number"));
+        }
+        let value = if negative {
+            format!("-{digits}")
+        } else {
+            digits.clone()
+        };
+        if !digits.starts_with("0x") && !digits.starts_with("0X") && value.parse::<i64>().is_err() {
+            return Err(format!("{digits} does not fit in 64 bits"));
+        }
+        Ok(())
+    }
+
+    fn operand(&mut self) -> Result<(), String> {
+        let mut negative = false;
+        while let Some(c @ ('+' | '-' | '~')) = self.peek() {
+            negative = c == '-';
+            self.chars.next();
+        }
+        match self.peek() {
+            Some('(') => {
+                self.chars.next();
+                self.expression()?;
+                if self.peek() != Some(')') {
+                    return Err("Missing )".to_string());
+                }
+                self.chars.next();
+                Ok(())
+            }
+            Some(c) if c.is_ascii_alphanumeric() => self.number(negative),
+            Some(c) => Err(format!("Unexpected {c}")),
+            None => Err("Missing an operand at the end".to_string()),
+        }
+    }
+
+    fn expression(&mut self) -> Result<(), String> {
+        self.operand()?;
+        while self.binary_operator() {
+            self.operand()?;
+        }
+        match self.peek() {
+            None | Some(')') => Ok(()),
+            Some(c) => Err(format!("Unexpected {c}, expected an operator")),
+        }
+    }
+}
+
+/// What is wrong with the `math(EXPR)` expression, the variables standing for
+/// numbers
+pub(super) fn math_error(expression: &str) -> Option<String> {
+    let mut expression = expression.to_string();
+    while REFERENCE_REGEX.is_match(&expression) {
+        expression = REFERENCE_REGEX.replace_all(&expression, "1").into_owned();
+    }
+    if expression.contains('$') {
+        return None;
+    }
+    let mut parser = MathParser {
+        chars: expression.chars().peekable(),
+    };
+    if let Err(message) = parser.expression() {
+        return Some(message);
+    }
+    (parser.peek() == Some(')')).then(|| "Unmatched )".to_string())
+}
+
+struct RegexParser<'a> {
+    chars: Peekable<Chars<'a>>,
+    groups: usize,
+}
+
+impl RegexParser<'_> {
+    /// Whether the alternatives may match nothing
+    fn alternatives(&mut self, nested: bool) -> Result<bool, String> {
+        let mut empty = self.branch()?;
+        while self.chars.next_if_eq(&'|').is_some() {
+            empty |= self.branch()?;
+        }
+        match self.chars.next() {
+            Some(')') if nested => Ok(empty),
+            Some(')') => Err("Unmatched ), escape it as \\\\)".to_string()),
+            _ if nested => Err("Unmatched (".to_string()),
+            _ => Ok(empty),
+        }
+    }
+
+    fn branch(&mut self) -> Result<bool, String> {
+        let mut empty = true;
+        while self.chars.peek().is_some_and(|c| *c != '|' && *c != ')') {
+            empty &= self.piece()?;
+        }
+        Ok(empty)
+    }
+
+    fn piece(&mut self) -> Result<bool, String> {
+        let empty = self.atom()?;
+        let Some(quantifier) = self.chars.next_if(|c| matches!(c, '*' | '+' | '?')) else {
+            let rest: String = self.chars.clone().collect();
+            if let Some(repetition) = REPETITION_REGEX.find(&rest) {
+                return Err(format!(
+                    "Repetitions like {} are not supported, the braces match themselves",
+                    repetition.as_str()
+                ));
+            }
+            return Ok(empty);
+        };
+        if empty && quantifier != '?' {
+            return Err(format!("The operand of {quantifier} could match nothing"));
+        }
+        if let Some(next) = self.chars.next_if(|c| matches!(c, '*' | '+' | '?')) {
+            return Err(format!(
+                "Lazy and possessive quantifiers like {quantifier}{next} are not supported"
+            ));
+        }
+        Ok(quantifier != '+' || empty)
+    }
+
+    fn atom(&mut self) -> Result<bool, String> {
+        match self.chars.next() {
+            Some('^' | '$') => Ok(true),
+            Some('[') => self.class().map(|_| false),
+            Some('(') => {
+                if self.chars.peek() == Some(&'?') {
+                    return Err("Groups like (?:...) and lookarounds are not supported".to_string());
+                }
+                self.groups += 1;
+                if self.groups > MAX_GROUPS {
+                    return Err(format!("More than {MAX_GROUPS} groups"));
+                }
+                self.alternatives(true)
+            }
+            Some(c @ ('*' | '+' | '?')) => Err(format!("{c} follows nothing")),
+            Some('\\') => match self.chars.next() {
+                None => Err("Trailing \\".to_string()),
+                Some(c @ ('d' | 'w' | 's' | 'b' | 'D' | 'W' | 'S' | 'B')) => {
+                    Err(format!("\\{c} is not supported, it matches the letter {c}"))
+                }
+                Some(_) => Ok(false),
+            },
+            _ => Ok(false),
+        }
+    }
+
+    fn class(&mut self) -> Result<(), String> {
+        self.chars.next_if_eq(&'^');
+        // A leading ] or - is one of the characters
+        let mut previous = self.chars.next_if(|c| matches!(c, ']' | '-'));
+        loop {
+            match self.chars.next() {
+                None => return Err("Unmatched [".to_string()),
+                Some(']') => return Ok(()),
+                Some('-') if self.chars.peek().is_some_and(|c| *c != ']') => {
+                    let end = self.chars.next().unwrap_or_default();
+                    if previous.is_some_and(|start| start > end) {
+                        return Err("Invalid range in []".to_string());
+                    }
+                    previous = None;
+                }
+                Some(c) => previous = Some(c),
+            }
+        }
+    }
+}
+
+/// What cmake rejects, or reads differently, in the `pattern` of `string(REGEX)`
+pub(super) fn regex_error(pattern: &str) -> Option<String> {
+    if pattern.contains('$') && REFERENCE_REGEX.is_match(pattern) {
+        return None;
+    }
+    let mut parser = RegexParser {
+        chars: pattern.chars().peekable(),
+        groups: 0,
+    };
+    parser.alternatives(false).err()
+}
+
+fn check_command(command: Node, source: &[&str], output: &mut Vec<ErrorInformation>) {
+    let Some(ids) = command.child(0) else {
+        return;
+    };
+    let h = ids.start_position().row;
+    let name = source[h][ids.start_position().column..ids.end_position().column].to_lowercase();
+    let arguments = arguments(command, source);
+    let (argument, message) = match (name.as_str(), arguments.as_slice()) {
+        ("math", [mode, _, expression, ..]) if mode.text == "EXPR" => {
+            let Some(message) = math_error(&unescape(expression.text)) else {
+                return;
+            };
+            (
+                expression,
+                format!("Invalid math(EXPR) expression: {message}"),
+            )
+        }
+        ("string", [regex, mode, pattern, ..])
+            if regex.text == "REGEX" && REGEX_MODES.contains(&mode.text) =>
+        {
+            let Some(message) = regex_error(&unescape(pattern.text)) else {
+                return;
+            };
+            (pattern, format!("Invalid regular expression: {message}"))
+        }
+        _ => return,
+    };
+    let rule = if name == "math" {
+        &rules::MATH_EXPRESSION
+    } else {
+        &rules::INVALID_REGEX
+    };
+    output.push(error(argument.node, message, rule));
+}
+
+fn check_expressions_inner(node: Node, source: &[&str], output: &mut Vec<ErrorInformation>) {
+    let mut course = node.walk();
+    for child in node.children(&mut course) {
+        if child.kind() == CMakeNodeKinds::NORMAL_COMMAND {
+            check_command(child, source, output);
+        } else {
+            check_expressions_inner(child, source, output);
+        }
+    }
+}
+
+pub fn check_expressions(source: &[&str], root: Node) -> Vec<ErrorInformation> {
+    let mut output = vec![];
+    check_expressions_inner(root, source, &mut output);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    #[test]
+    fn test_math_error() {
+        assert_eq!(math_error("(1 + 0x1F) * -${COUNT} << 2"), None);
+        assert_eq!(math_error("~${A}%3 ^ ${B_${C}}"), None);
+        assert_eq!(
+            math_error("1 +"),
+            Some("Missing an operand at the end".to_string())
+        );
+        assert_eq!(math_error("(1 + 2"), Some("Missing )".to_string()));
+        assert_eq!(math_error("1 + 2)"), Some("Unmatched )".to_string()));
+        assert_eq!(math_error("2 ** 3"), Some("Unexpected *".to_string()));
+        assert_eq!(
+            math_error("1 2"),
+            Some("Unexpected 2, expected an operator".to_string())
+        );
+        assert_eq!(math_error("0x"), Some("0x is not a number".to_string()));
+        assert_eq!(
+            math_error("99999999999999999999"),
+            Some("99999999999999999999 does not fit in 64 bits".to_string())
+        );
+        assert_eq!(math_error("-9223372036854775808"), None);
+        assert_eq!(math_error("1 - -9223372036854775808"), None);
+        assert_eq!(
+            math_error("9223372036854775808"),
+            Some("9223372036854775808 does not fit in 64 bits".to_string())
+        );
+    }
+
+    #[test]
+    fn test_regex_error() {
+        assert_eq!(regex_error("^([a-z_]+)-v?([0-9]+(\\.[0-9]+)*)$"), None);
+        assert_eq!(regex_error("[]a-]|x+|(a|)?"), None);
+        assert_eq!(regex_error("${PREFIX}(\\d+)"), None);
+        assert_eq!(regex_error(&unescape("[[^v([0-9]+)]]")), None);
+        assert_eq!(regex_error(&unescape("[=[a\\.b]]=]")), None);
+        assert_eq!(
+            regex_error("(\\d+)"),
+            Some("\\d is not supported, it matches the letter d".to_string())
+        );
+        assert_eq!(
+            regex_error("(?:ab)+"),
+            Some("Groups like (?:...) and lookarounds are not supported".to_string())
+        );
+        assert_eq!(
+            regex_error("a.*?b"),
+            Some("Lazy and possessive quantifiers like *? are not supported".to_string())
+        );
+        assert_eq!(
+            regex_error("[0-9]{3}"),
+            Some("Repetitions like {3} are not supported, the braces match themselves".to_string())
+        );
+        assert_eq!(regex_error("*a"), Some("* follows nothing".to_string()));
+        assert_eq!(regex_error("(a"), Some("Unmatched (".to_string()));
+        assert_eq!(
+            regex_error("[z-a]"),
+            Some("Invalid range in []".to_string())
+        );
+        assert_eq!(
+            regex_error("(a*)*"),
+            Some("The operand of * could match nothing".to_string())
+        );
+    }
+
+    #[test]
+    fn test_check_expressions() {
+        let source = r#"math(EXPR total "${A} + * 2")
+function(version out)
+  string(REGEX MATCH "v(\\d+)" match "${ARGN}")
+  string(REGEX REPLACE "^v([0-9]+)$" "\\1" ${out} "${match}")
+endfunction()
+"#;
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let tree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let errors: Vec<(usize, usize, String)> = check_expressions(&lines, tree.root_node())
+            .into_iter()
+            .map(|error| {
+                (
+                    error.start_point.row,
+                    error.start_point.column,
+                    error.message,
+                )
+            })
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (
+                    0,
+                    16,
+                    "Invalid math(EXPR) expression: Unexpected *".to_string()
+                ),
+                (
+                    2,
+                    21,
+                    "Invalid regular expression: \\d is not supported, it matches the letter d"
+                        .to_string()
+                ),
+            ]
+        );
+    }
+}
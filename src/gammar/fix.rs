@@ -0,0 +1,98 @@
+//! The edits fixing the diagnostics, shared by the quick fixes and `lint --fix`.
+
+use tree_sitter::Point;
+
+/// Replace the text between `start` and `end` with `new_text`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fix {
+    pub title: String,
+    pub start: Point,
+    pub end: Point,
+    pub new_text: String,
+    /// Whether the fix keeps what the file does, so it can be applied without a review
+    pub safe: bool,
+}
+
+/// Byte offset of `point` in `source`
+fn offset_of(source: &str, line_starts: &[usize], point: Point) -> usize {
+    line_starts.get(point.row).map_or(source.len(), |start| {
+        (start + point.column).min(source.len())
+    })
+}
+
+/// Apply `fixes` to `source`. A fix overlapping one already applied is left out,
+/// as its range no longer means anything, and so is one changing nothing.
+/// Returns the new text and the number of fixes applied.
+pub fn apply_fixes(source: &str, fixes: &[Fix]) -> (String, usize) {
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(source.match_indices('\n').map(|(index, _)| index + 1))
+        .collect();
+    let mut ranges: Vec<(usize, usize, &str)> = fixes
+        .iter()
+        .map(|fix| {
+            (
+                offset_of(source, &line_starts, fix.start),
+                offset_of(source, &line_starts, fix.end),
+                fix.new_text.as_str(),
+            )
+        })
+        .collect();
+    ranges.sort_by_key(|(start, end, _)| (*start, *end));
+    let mut output = String::with_capacity(source.len());
+    let mut last_end = 0;
+    let mut applied = 0;
+    for (start, end, new_text) in ranges {
+        if start < last_end
+            || end < start
+            || !source.is_char_boundary(start)
+            || !source.is_char_boundary(end)
+            || source[start..end] == *new_text
+        {
+            continue;
+        }
+        output.push_str(&source[last_end..start]);
+        output.push_str(new_text);
+        last_end = end;
+        applied += 1;
+    }
+    output.push_str(&source[last_end..]);
+    (output, applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fix(start: (usize, usize), end: (usize, usize), new_text: &str) -> Fix {
+        Fix {
+            title: String::new(),
+            start: Point {
+                row: start.0,
+                column: start.1,
+            },
+            end: Point {
+                row: end.0,
+                column: end.1,
+            },
+            new_text: new_text.to_string(),
+            safe: true,
+        }
+    }
+
+    #[test]
+    fn test_apply_fixes() {
+        let source = "if(${A} STREQUAL b)\r\nendif()\nset(X /opt/my\\ sdk)\n";
+        let fixes = [
+            fix((2, 6), (2, 18), "\"/opt/my sdk\""),
+            fix((0, 3), (0, 7), "\"${A}\""),
+            // Overlaps the first one
+            fix((0, 5), (0, 6), "B"),
+        ];
+        let (output, applied) = apply_fixes(source, &fixes);
+        assert_eq!(applied, 2);
+        assert_eq!(
+            output,
+            "if(\"${A}\" STREQUAL b)\r\nendif()\nset(X \"/opt/my sdk\")\n"
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! The commands changing the flow out of place: `break()` and `continue()`
+//! outside of a loop, and `return()` given values outside of a function.
+
+use tree_sitter::Node;
+
+use super::ErrorInformation;
+use super::calls::{arguments, error, project_version};
+use crate::CMakeNodeKinds;
+use crate::rules;
+
+/// The version from which `return()` takes `PROPAGATE` and fails on other
+/// arguments, with the policy CMP0140
+const PROPAGATE_VERSION: (u32, u32) = (3, 25);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Scope {
+    Directory,
+    Function,
+    /// Running in the scope of the caller, which may be a loop
+    Macro,
+}
+
+fn command_name(command: Node, source: &[&str]) -> Option<String> {
+    let ids = command.child(0)?;
+    let h = ids.start_position().row;
+    Some(source[h][ids.start_position().column..ids.end_position().column].to_lowercase())
+}
+
+fn check_return(
+    command: Node,
+    source: &[&str],
+    version: Option<(u32, u32)>,
+    output: &mut Vec<ErrorInformation>,
+) {
+    let arguments = arguments(command, source);
+    let Some(first) = arguments.first() else {
+        return;
+    };
+    if first.text != "PROPAGATE" {
+        let message = if version.is_some_and(|version| version >= PROPAGATE_VERSION) {
+            "return() only takes PROPAGATE <variables>, cmake fails on other arguments"
+        } else {
+            "The arguments of return() are ignored, it returns no value"
+        };
+        output.push(error(
+            first.node,
+            message.to_string(),
+            &rules::RETURN_ARGUMENTS,
+        ));
+    } else if let Some((major, minor)) = version
+        && (major, minor) < PROPAGATE_VERSION
+    {
+        output.push(error(
+            first.node,
+            format!("return(PROPAGATE) needs CMake 3.25, the project requires {major}.{minor}"),
+            &rules::RETURN_ARGUMENTS,
+        ));
+    }
+}
+
+fn check_flow_inner(
+    node: Node,
+    source: &[&str],
+    version: Option<(u32, u32)>,
+    scope: Scope,
+    in_loop: bool,
+    output: &mut Vec<ErrorInformation>,
+) {
+    let mut course = node.walk();
+    for child in node.children(&mut course) {
+        match child.kind() {
+            CMakeNodeKinds::NORMAL_COMMAND => {
+                let Some(name) = command_name(child, source) else {
+                    continue;
+                };
+                match name.as_str() {
+                    "break" | "continue" if !in_loop && scope != Scope::Macro => {
+                        output.push(error(
+                            child,
+                            format!("{name}() is not in a foreach() or while() loop"),
+                            &rules::BREAK_OUTSIDE_LOOP,
+                        ));
+                    }
+                    "return" if scope == Scope::Directory => {
+                        check_return(child, source, version, output);
+                    }
+                    _ => {}
+                }
+            }
+            CMakeNodeKinds::FUNCTION_DEF => {
+                check_flow_inner(child, source, version, Scope::Function, false, output);
+            }
+            CMakeNodeKinds::MACRO_DEF => {
+                check_flow_inner(child, source, version, Scope::Macro, false, output);
+            }
+            CMakeNodeKinds::FOREACH_LOOP | CMakeNodeKinds::WHILE_LOOP => {
+                check_flow_inner(child, source, version, scope, true, output);
+            }
+            CMakeNodeKinds::IF_CONDITION | CMakeNodeKinds::BLOCK_DEF | CMakeNodeKinds::BODY => {
+                check_flow_inner(child, source, version, scope, in_loop, output);
+            }
+            _ => {}
+        }
+    }
+}
+
+pub fn check_flow(source: &[&str], root: Node) -> Vec<ErrorInformation> {
+    let mut output = vec![];
+    check_flow_inner(
+        root,
+        source,
+        project_version(source, root),
+        Scope::Directory,
+        false,
+        &mut output,
+    );
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    fn check(source: &str) -> Vec<(usize, String)> {
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let tree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        check_flow(&lines, tree.root_node())
+            .into_iter()
+            .map(|error| (error.start_point.row, error.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_flow() {
+        let source = r#"cmake_minimum_required(VERSION 3.20)
+foreach(item a b)
+  if(item STREQUAL a)
+    continue()
+  endif()
+  block()
+    break()
+  endblock()
+endforeach()
+break()
+function(first list)
+  foreach(item IN LISTS list)
+    return(${item})
+  endforeach()
+  continue()
+endfunction()
+macro(skip)
+  continue()
+endmacro()
+return(${RESULT})
+return(PROPAGATE RESULT)
+"#;
+        assert_eq!(
+            check(source),
+            vec![
+                (
+                    9,
+                    "break() is not in a foreach() or while() loop".to_string()
+                ),
+                (
+                    14,
+                    "continue() is not in a foreach() or while() loop".to_string()
+                ),
+                (
+                    19,
+                    "The arguments of return() are ignored, it returns no value".to_string()
+                ),
+                (
+                    20,
+                    "return(PROPAGATE) needs CMake 3.25, the project requires 3.20".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            check("cmake_minimum_required(VERSION 3.25)\nreturn(1)\nreturn(PROPAGATE A)\n"),
+            vec![(
+                1,
+                "return() only takes PROPAGATE <variables>, cmake fails on other arguments"
+                    .to_string()
+            )]
+        );
+    }
+}
@@ -0,0 +1,228 @@
+//! cmake only compiles the CUDA and HIP sources when the language is enabled,
+//! and builds them for the architectures of `CMAKE_<LANG>_ARCHITECTURES`.
+
+use std::path::Path;
+use std::sync::LazyLock;
+
+use tree_sitter::Node;
+
+use super::ErrorInformation;
+use super::calls::{arguments, commands, error, matched_by_parent};
+use crate::rules;
+use crate::utils::{languages, module_path};
+
+/// The extensions of the sources compiled by each GPU language
+const GPU_SOURCES: &[(&str, &str)] = &[("CUDA", "cu"), ("HIP", "hip")];
+
+/// The environment variables giving the default of `CMAKE_<LANG>_ARCHITECTURES`
+const ARCHITECTURES_ENVIRONMENT: &[(&str, &str)] = &[("CUDA", "CUDAARCHS"), ("HIP", "HIPARCHS")];
+
+/// The settings of the architectures of each GPU language
+static ARCHITECTURES_REGEXES: LazyLock<Vec<(&str, regex::Regex)>> = LazyLock::new(|| {
+    GPU_SOURCES
+        .iter()
+        .map(|(language, _)| {
+            let regex = regex::Regex::new(&format!(
+                r"(?i)\bset\s*\(\s*CMAKE_{language}_ARCHITECTURES\b|\b{language}_ARCHITECTURES\b"
+            ))
+            .unwrap();
+            (*language, regex)
+        })
+        .collect()
+});
+
+const SOURCE_COMMANDS: &[&str] = &["add_executable", "add_library", "target_sources"];
+
+/// The presets may set the architectures as cache variables
+const PRESETS_FILES: &[&str] = &["CMakePresets.json", "CMakeUserPresets.json"];
+
+/// The languages enabled by the file and by the indexed project, `None` when
+/// neither is known
+fn enabled_languages(source: &[&str], root: Node) -> Option<Vec<String>> {
+    let mut enabled = languages::languages_of(source, root);
+    enabled.extend(languages::enabled_languages().unwrap_or_default());
+    (!enabled.is_empty()).then_some(enabled)
+}
+
+/// The CUDA and HIP sources of the targets when the project does not enable
+/// their language
+fn check_sources(source: &[&str], root: Node) -> Vec<ErrorInformation> {
+    let Some(enabled) = enabled_languages(source, root) else {
+        return vec![];
+    };
+    let mut calls = vec![];
+    for name in SOURCE_COMMANDS {
+        commands(root, source, name, &mut calls);
+    }
+    let mut output = vec![];
+    for call in calls {
+        for argument in arguments(call, source).iter().skip(1) {
+            let extension = Path::new(argument.text.trim_matches('"'))
+                .extension()
+                .and_then(|extension| extension.to_str());
+            let Some((language, _)) = GPU_SOURCES
+                .iter()
+                .find(|(_, gpu_extension)| Some(*gpu_extension) == extension)
+            else {
+                continue;
+            };
+            if enabled.iter().any(|enabled| enabled == language) {
+                continue;
+            }
+            output.push(error(
+                argument.node,
+                format!(
+                    "{} is a {language} source, but the project does not enable {language}, so it is not compiled. Add it to the LANGUAGES of project() or call enable_language({language})",
+                    argument.text
+                ),
+                &rules::LANGUAGE_NOT_ENABLED,
+            ));
+        }
+    }
+    output
+}
+
+/// Whether the architectures of `language` are set by `source`, a parent
+/// directory, the presets or the environment
+fn architectures_set(
+    local_path: &Path,
+    source: &str,
+    language: &str,
+    environment: &impl Fn(&str) -> bool,
+) -> bool {
+    if ARCHITECTURES_ENVIRONMENT
+        .iter()
+        .any(|(gpu_language, variable)| *gpu_language == language && environment(variable))
+    {
+        return true;
+    }
+    let Some((_, regex)) = ARCHITECTURES_REGEXES
+        .iter()
+        .find(|(gpu_language, _)| *gpu_language == language)
+    else {
+        return false;
+    };
+    if regex.is_match(source) || matched_by_parent(local_path, regex) {
+        return true;
+    }
+    let variable = format!("CMAKE_{language}_ARCHITECTURES");
+    module_path::source_dir().is_some_and(|source_dir| {
+        PRESETS_FILES.iter().any(|presets| {
+            std::fs::read_to_string(source_dir.join(presets))
+                .is_ok_and(|content| content.contains(&variable))
+        })
+    })
+}
+
+/// The CUDA and HIP languages enabled without choosing the architectures,
+/// which builds them only for the default one of the compiler
+fn check_architectures(
+    local_path: &Path,
+    source: &[&str],
+    root: Node,
+    environment: impl Fn(&str) -> bool,
+) -> Vec<ErrorInformation> {
+    let mut calls = vec![];
+    commands(root, source, "project", &mut calls);
+    commands(root, source, "enable_language", &mut calls);
+    if calls.is_empty() {
+        return vec![];
+    }
+    let text = source.join("\n");
+    let mut output = vec![];
+    for call in calls {
+        for argument in arguments(call, source) {
+            let Some((language, _)) = GPU_SOURCES
+                .iter()
+                .find(|(language, _)| *language == argument.text)
+            else {
+                continue;
+            };
+            if architectures_set(local_path, &text, language, &environment) {
+                continue;
+            }
+            output.push(error(
+                argument.node,
+                format!(
+                    "CMAKE_{language}_ARCHITECTURES is not set, so the targets are only built for the default architecture of the compiler"
+                ),
+                &rules::GPU_ARCHITECTURES_UNSET,
+            ));
+        }
+    }
+    output
+}
+
+pub fn check_gpu_languages(
+    local_path: &Path,
+    source: &[&str],
+    root: Node,
+) -> Vec<ErrorInformation> {
+    let mut output = check_sources(source, root);
+    output.append(&mut check_architectures(
+        local_path,
+        source,
+        root,
+        |variable| std::env::var_os(variable).is_some_and(|value| !value.is_empty()),
+    ));
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    fn check(source: &str) -> Vec<ErrorInformation> {
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        check_gpu_languages(
+            Path::new("/tmp/gpu/CMakeLists.txt"),
+            &lines,
+            thetree.root_node(),
+        )
+    }
+
+    #[test]
+    fn test_gpu_languages() {
+        let errors =
+            check("project(demo LANGUAGES CXX)\nadd_executable(demo main.cpp kernel.cu)\n");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].rule, Some(&rules::LANGUAGE_NOT_ENABLED));
+        assert_eq!(errors[0].start_point.column, 29);
+
+        let errors = check("project(demo LANGUAGES CXX CUDA)\nadd_executable(demo kernel.cu)\n");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].rule, Some(&rules::GPU_ARCHITECTURES_UNSET));
+        assert_eq!(errors[0].start_point.row, 0);
+
+        assert!(
+            check("set(CMAKE_CUDA_ARCHITECTURES native)\nproject(demo LANGUAGES CXX CUDA)\nadd_executable(demo kernel.cu)\n")
+                .is_empty()
+        );
+        assert!(
+            check("project(demo LANGUAGES CXX)\nenable_language(HIP)\nadd_executable(demo kernel.hip)\nset_target_properties(demo PROPERTIES HIP_ARCHITECTURES gfx90a)\n")
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_architectures_from_environment() {
+        let source = "project(demo LANGUAGES CXX CUDA)\n";
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let local_path = Path::new("/tmp/gpu/CMakeLists.txt");
+        let check = |set: &'static str| {
+            check_architectures(local_path, &lines, thetree.root_node(), |variable| {
+                variable == set
+            })
+            .len()
+        };
+        assert_eq!(check("CUDAARCHS"), 0);
+        assert_eq!(check("HIPARCHS"), 1);
+    }
+}
@@ -0,0 +1,334 @@
+//! Checks of the `install()` calls: the targets, files and export sets they
+//! name, and their destination. The export sets of `export(EXPORT)` are
+//! checked alike.
+
+use std::path::Path;
+
+use tree_sitter::Node;
+
+use super::ErrorInformation;
+use super::calls::{
+    Argument, arguments, command_name, commands, error, is_computed, project_version,
+};
+use crate::rules;
+use crate::scansubs;
+use crate::utils::targets;
+
+/// The version from which `install()` has default destinations, and `TYPE`
+const DEFAULT_DESTINATION_VERSION: (u32, u32) = (3, 14);
+
+/// The keywords ending the list of targets of `install(TARGETS)`
+const TARGETS_KEYWORDS: &[&str] = &[
+    "EXPORT",
+    "RUNTIME_DEPENDENCIES",
+    "RUNTIME_DEPENDENCY_SET",
+    "ARCHIVE",
+    "LIBRARY",
+    "RUNTIME",
+    "OBJECTS",
+    "FRAMEWORK",
+    "BUNDLE",
+    "PRIVATE_HEADER",
+    "PUBLIC_HEADER",
+    "RESOURCE",
+    "FILE_SET",
+    "CXX_MODULES_BMI",
+    "DESTINATION",
+    "PERMISSIONS",
+    "CONFIGURATIONS",
+    "COMPONENT",
+    "NAMELINK_COMPONENT",
+    "OPTIONAL",
+    "EXCLUDE_FROM_ALL",
+    "NAMELINK_ONLY",
+    "NAMELINK_SKIP",
+    "INCLUDES",
+];
+
+/// The keywords ending the list of files of `install(FILES)` and `install(PROGRAMS)`
+const FILES_KEYWORDS: &[&str] = &[
+    "TYPE",
+    "DESTINATION",
+    "PERMISSIONS",
+    "CONFIGURATIONS",
+    "COMPONENT",
+    "RENAME",
+    "OPTIONAL",
+    "EXCLUDE_FROM_ALL",
+];
+
+struct Project {
+    /// The targets, unknown while the project is being indexed
+    targets: Option<Vec<String>>,
+    /// The export sets filled by `install(TARGETS ... EXPORT)`
+    exports: Vec<String>,
+    /// The export sets of `install(EXPORT)` and `export(EXPORT)`
+    installed: Vec<String>,
+    version: Option<(u32, u32)>,
+}
+
+/// The export set of `install(EXPORT <set>)` or `export(EXPORT <set>)`, which
+/// is empty when no target is installed in it
+fn check_filled(arguments: &[Argument], project: &Project, output: &mut Vec<ErrorInformation>) {
+    let Some(export) = arguments.get(1) else {
+        return;
+    };
+    let name = export.text.trim_matches('"');
+    if !is_computed(name) && !project.exports.iter().any(|known| known == name) {
+        output.push(error(
+            export.node,
+            format!("No install(TARGETS ... EXPORT {name}) fills this export set"),
+            &rules::INSTALL_UNKNOWN_EXPORT,
+        ));
+    }
+}
+
+fn check_call(
+    local_path: &Path,
+    command: Node,
+    source: &[&str],
+    project: &Project,
+    output: &mut Vec<ErrorInformation>,
+) {
+    let arguments = arguments(command, source);
+    let Some(mode) = arguments.first() else {
+        return;
+    };
+    let has = |keyword: &str| arguments.iter().any(|argument| argument.text == keyword);
+    let items = |keywords: &[&str]| -> Vec<&Argument> {
+        arguments[1..]
+            .iter()
+            .take_while(|argument| !keywords.contains(&argument.text))
+            .collect()
+    };
+    let Some(ids) = command.child(0) else {
+        return;
+    };
+    if command_name(command, source).as_deref() == Some("export") {
+        if mode.text == "EXPORT" {
+            check_filled(&arguments, project, output);
+        }
+        return;
+    }
+    match mode.text {
+        "TARGETS" => {
+            let targets = project.targets.as_deref().unwrap_or_default();
+            for target in items(TARGETS_KEYWORDS) {
+                let name = target.text.trim_matches('"');
+                if project.targets.is_some()
+                    && !is_computed(name)
+                    && !targets.iter().any(|known| known == name)
+                {
+                    output.push(error(
+                        target.node,
+                        format!("Target \"{name}\" is not declared in the project"),
+                        &rules::INSTALL_UNKNOWN_TARGET,
+                    ));
+                }
+            }
+            if let Some(index) = arguments
+                .iter()
+                .position(|argument| argument.text == "EXPORT")
+                && let Some(export) = arguments.get(index + 1)
+            {
+                let name = export.text.trim_matches('"');
+                if !is_computed(name) && !project.installed.iter().any(|known| known == name) {
+                    output.push(error(
+                        export.node,
+                        format!(
+                            "Export set {name} is never installed by install(EXPORT) nor exported by export(EXPORT), its targets cannot be found by find_package()"
+                        ),
+                        &rules::EXPORT_NOT_INSTALLED,
+                    ));
+                }
+            }
+            if !has("DESTINATION")
+                && project
+                    .version
+                    .is_some_and(|version| version < DEFAULT_DESTINATION_VERSION)
+            {
+                output.push(error(
+                    ids,
+                    "install(TARGETS) needs a DESTINATION before CMake 3.14".to_string(),
+                    &rules::INSTALL_DESTINATION,
+                ));
+            }
+        }
+        "FILES" | "PROGRAMS" => {
+            if !has("OPTIONAL")
+                && let Some(dir) = local_path.parent()
+            {
+                for file in items(FILES_KEYWORDS) {
+                    let name = file
+                        .text
+                        .trim_matches('"')
+                        .replace("${CMAKE_CURRENT_SOURCE_DIR}", &dir.to_string_lossy())
+                        .replace("${CMAKE_CURRENT_LIST_DIR}", &dir.to_string_lossy());
+                    if name.is_empty() || is_computed(&name) || dir.join(&name).exists() {
+                        continue;
+                    }
+                    output.push(error(
+                        file.node,
+                        format!("File \"{name}\" does not exist"),
+                        &rules::INSTALL_MISSING_FILE,
+                    ));
+                }
+            }
+            if !has("DESTINATION") && !has("TYPE") {
+                output.push(error(
+                    ids,
+                    format!("install({}) needs a DESTINATION or a TYPE", mode.text),
+                    &rules::INSTALL_DESTINATION,
+                ));
+            }
+        }
+        "DIRECTORY" if !has("DESTINATION") && !has("TYPE") => {
+            output.push(error(
+                ids,
+                "install(DIRECTORY) needs a DESTINATION or a TYPE".to_string(),
+                &rules::INSTALL_DESTINATION,
+            ));
+        }
+        "EXPORT" => {
+            check_filled(&arguments, project, output);
+            if !has("DESTINATION") {
+                output.push(error(
+                    ids,
+                    "install(EXPORT) needs a DESTINATION".to_string(),
+                    &rules::INSTALL_DESTINATION,
+                ));
+            }
+        }
+        _ => {}
+    }
+}
+
+fn check_calls(
+    local_path: &Path,
+    source: &[&str],
+    calls: &[Node],
+    project: &Project,
+) -> Vec<ErrorInformation> {
+    let mut output = vec![];
+    for call in calls {
+        check_call(local_path, *call, source, project, &mut output);
+    }
+    output
+}
+
+/// Check the `install()` and `export()` calls of `source` against the targets
+/// and export sets of the project
+pub fn check_install<P: AsRef<Path>>(
+    local_path: P,
+    source: &[&str],
+    root: Node,
+) -> Vec<ErrorInformation> {
+    let local_path = local_path.as_ref();
+    let mut calls = vec![];
+    commands(root, source, "install", &mut calls);
+    commands(root, source, "export", &mut calls);
+    if calls.is_empty() {
+        return vec![];
+    }
+    // The index may not know about the file being edited yet
+    let targets = (!scansubs::is_indexing()).then(|| {
+        targets::declared_targets(source, root)
+            .into_iter()
+            .map(|target| target.name)
+            .chain(
+                targets::all_targets()
+                    .into_iter()
+                    .map(|(_, target)| target.name),
+            )
+            .collect()
+    });
+    let exports = targets::declared_exports(source, root)
+        .into_iter()
+        .chain(targets::all_exports().into_iter().map(|(_, export)| export))
+        .collect();
+    let installed = targets::declared_installed_exports(source, root)
+        .into_iter()
+        .chain(
+            targets::all_installed_exports()
+                .into_iter()
+                .map(|(_, export)| export),
+        )
+        .collect();
+    let project = Project {
+        targets,
+        exports,
+        installed,
+        version: project_version(source, root),
+    };
+    check_calls(local_path, source, &calls, &project)
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::Point;
+
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+    use crate::gammar::calls::required_version;
+
+    #[test]
+    fn test_install_checks() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("core.h"), "").unwrap();
+        let source = r#"cmake_minimum_required(VERSION 3.10...3.28)
+add_library(core core.c)
+install(TARGETS core tool EXPORT CoreTargets)
+install(FILES core.h missing.h ${GENERATED} DESTINATION include)
+install(DIRECTORY docs/)
+install(EXPORT CoreTargets DESTINATION lib/cmake/core)
+install(EXPORT OtherTargets DESTINATION lib/cmake/core)
+install(TARGETS core EXPORT LostTargets DESTINATION lib)
+export(EXPORT MissingTargets FILE MissingTargets.cmake)
+"#;
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let root = thetree.root_node();
+        assert_eq!(required_version(&lines, root), Some((3, 10)));
+
+        let project = Project {
+            targets: Some(vec!["core".to_string()]),
+            exports: targets::declared_exports(&lines, root),
+            installed: targets::declared_installed_exports(&lines, root),
+            version: required_version(&lines, root),
+        };
+        let mut calls = vec![];
+        commands(root, &lines, "install", &mut calls);
+        commands(root, &lines, "export", &mut calls);
+        let errors = check_calls(&dir.path().join("CMakeLists.txt"), &lines, &calls, &project);
+        let found: Vec<(Point, &str)> = errors
+            .iter()
+            .map(|error| (error.start_point, error.rule.unwrap().name))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (Point { row: 2, column: 21 }, "install-unknown-target"),
+                (Point { row: 2, column: 0 }, "install-destination"),
+                (Point { row: 3, column: 21 }, "install-missing-file"),
+                (Point { row: 4, column: 0 }, "install-destination"),
+                (Point { row: 6, column: 15 }, "install-unknown-export"),
+                (Point { row: 7, column: 28 }, "export-not-installed"),
+                (Point { row: 8, column: 14 }, "install-unknown-export"),
+            ]
+        );
+
+        // While indexing, the targets of the other files are not known yet
+        let project = Project {
+            targets: None,
+            ..project
+        };
+        let errors = check_calls(&dir.path().join("CMakeLists.txt"), &lines, &calls, &project);
+        assert!(
+            errors
+                .iter()
+                .all(|error| error.rule != Some(&rules::INSTALL_UNKNOWN_TARGET))
+        );
+    }
+}
@@ -0,0 +1,119 @@
+//! Lines longer than `line_max_words`, fixed by wrapping the arguments.
+
+use tree_sitter::{Node, Point};
+
+use super::{Fix, FixableIssue};
+use crate::CMakeNodeKinds;
+use crate::rules;
+
+/// Wrap the arguments of the command on `line` so no line is longer than `longest`
+pub fn wrap_fix(input: Node, source: &[&str], line: usize, longest: usize) -> Option<Fix> {
+    let mut cursor = input.walk();
+    for child in input.children(&mut cursor) {
+        if child.end_position().row < line {
+            continue;
+        }
+        if child.start_position().row > line {
+            break;
+        }
+        match child.kind() {
+            CMakeNodeKinds::ARGUMENT_LIST => {
+                let start = child.start_position();
+                let mut arg_cursor = child.walk();
+                let mut start_row = start.column;
+                let start_space_len = start.column;
+                let start_space: String = vec![' '; start_space_len].iter().collect();
+                let mut new_text = "".to_string();
+                for arg in child.children(&mut arg_cursor) {
+                    let current_row = arg.start_position().row;
+                    // I mean I cannot fix this problem
+                    if current_row != arg.end_position().row
+                        || arg.kind() != CMakeNodeKinds::ARGUMENT
+                    {
+                        return None;
+                    }
+                    let start_col = arg.start_position().column;
+                    let end_col = arg.end_position().column;
+                    let len = end_col - start_col;
+                    let arg = &source[current_row][start_col..end_col];
+                    if start_row + len + 1 > longest {
+                        start_row = start_space_len + len + 1;
+                        new_text.push('\n');
+                        new_text.push_str(&start_space);
+                    } else {
+                        start_row += len + 1;
+                        if !new_text.is_empty() {
+                            new_text.push(' ');
+                        }
+                    }
+                    new_text.push_str(arg);
+                }
+                // Only the spaces between the arguments change
+                return Some(Fix {
+                    title: "too long lint fix".to_string(),
+                    start,
+                    end: child.end_position(),
+                    new_text,
+                    safe: true,
+                });
+            }
+            _ => {
+                let try_data = wrap_fix(child, source, line, longest);
+                if try_data.is_some() {
+                    return try_data;
+                }
+            }
+        }
+    }
+    None
+}
+
+/// The lines longer than `max_len` which can be wrapped
+pub fn find_issues(source: &[&str], root: Node, max_len: usize) -> Vec<FixableIssue> {
+    let mut issues = vec![];
+    for (index, line) in source.iter().enumerate() {
+        let len = line.len();
+        if len <= max_len {
+            continue;
+        }
+        let Some(fix) = wrap_fix(root, source, index, max_len) else {
+            continue;
+        };
+        let point = Point {
+            row: index,
+            column: 0,
+        };
+        issues.push(FixableIssue {
+            start_point: point,
+            end_point: point,
+            message: format!("[C0301] Line too long ({len}/{max_len})"),
+            rule: &rules::LINE_TOO_LONG,
+            fix,
+        });
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    #[test]
+    fn test_wrap_fix() {
+        let source =
+            "add_executable(app main.cpp window.cpp dialog.cpp)\nset(A # comment\n  b c d e f g)\n";
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let issues = find_issues(&lines, thetree.root_node(), 30);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(
+            issues[0].fix.new_text,
+            "app main.cpp\n               window.cpp\n               dialog.cpp"
+        );
+        // Moving arguments after a comment would comment them out
+        assert!(wrap_fix(thetree.root_node(), &lines, 1, 10).is_none());
+    }
+}
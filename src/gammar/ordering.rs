@@ -0,0 +1,341 @@
+//! Commands written in an order cmake cannot run them in: a target used
+//! before it is declared, the directories of `GNUInstallDirs` used before it
+//! is included, variables read before the file sets them, and the variables of
+//! a loop read after it.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::LazyLock;
+
+use tree_sitter::Node;
+
+use super::ErrorInformation;
+use super::calls::{arguments, command_name, error, matched_by_parent};
+use crate::eval::{self, node_text};
+use crate::rules;
+use crate::utils::{loops, module_path};
+use crate::{CMakeNodeKinds, fileapi};
+
+/// The commands of a target, which has to be declared first
+const TARGET_COMMANDS: &[&str] = &[
+    "target_link_libraries",
+    "target_include_directories",
+    "target_compile_definitions",
+    "target_compile_options",
+    "target_compile_features",
+    "target_sources",
+    "target_link_options",
+    "target_link_directories",
+    "target_precompile_headers",
+];
+
+const DECLARING_COMMANDS: &[&str] = &["add_executable", "add_library", "add_custom_target"];
+
+/// The directories defined by `include(GNUInstallDirs)`
+static INSTALL_DIR_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(
+        r"\$\{(CMAKE_INSTALL_(?:FULL_)?(?:BINDIR|SBINDIR|LIBEXECDIR|SYSCONFDIR|SHAREDSTATEDIR|LOCALSTATEDIR|RUNSTATEDIR|LIBDIR|INCLUDEDIR|OLDINCLUDEDIR|DATAROOTDIR|DATADIR|INFODIR|LOCALEDIR|MANDIR|DOCDIR))\}",
+    )
+    .unwrap()
+});
+
+static INCLUDE_REGEX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"(?i)\binclude\s*\(\s*GNUInstallDirs\b").unwrap());
+
+fn line(node: Node) -> usize {
+    node.start_position().row + 1
+}
+
+/// The `target_*()` calls before the target is declared in the file
+fn check_targets(lines: &[&str], commands: &[Node]) -> Vec<ErrorInformation> {
+    let mut declared: HashMap<&str, usize> = HashMap::new();
+    for (index, command) in commands.iter().enumerate().rev() {
+        if command_name(*command, lines)
+            .is_some_and(|name| DECLARING_COMMANDS.contains(&name.as_str()))
+            && let Some(target) = arguments(*command, lines).first()
+        {
+            declared.insert(target.text.trim_matches('"'), index);
+        }
+    }
+    let mut output = vec![];
+    for (index, command) in commands.iter().enumerate() {
+        let Some(name) = command_name(*command, lines) else {
+            continue;
+        };
+        if !TARGET_COMMANDS.contains(&name.as_str()) {
+            continue;
+        }
+        let Some(target) = arguments(*command, lines).into_iter().next() else {
+            continue;
+        };
+        let text = target.text.trim_matches('"');
+        let Some(declaration) = declared
+            .get(text)
+            .filter(|declaration| **declaration > index)
+        else {
+            continue;
+        };
+        output.push(error(
+            target.node,
+            format!(
+                "Target \"{text}\" is declared later, at line {}. {name}() has to come after its declaration",
+                line(commands[*declaration])
+            ),
+            &rules::TARGET_BEFORE_DECLARATION,
+        ));
+    }
+    output
+}
+
+/// The `install()` calls using the directories of `GNUInstallDirs` before it
+/// is included
+fn check_install_dirs(
+    local_path: &Path,
+    lines: &[&str],
+    commands: &[Node],
+) -> Vec<ErrorInformation> {
+    if matched_by_parent(local_path, &INCLUDE_REGEX) {
+        return vec![];
+    }
+    let include = commands.iter().position(|command| {
+        command_name(*command, lines).as_deref() == Some("include")
+            && arguments(*command, lines)
+                .first()
+                .is_some_and(|module| module.text.trim_matches('"') == "GNUInstallDirs")
+    });
+    let cache = fileapi::get_entries_data();
+    let mut output = vec![];
+    for (index, command) in commands.iter().enumerate() {
+        if include.is_some_and(|include| include < index)
+            || command_name(*command, lines).as_deref() != Some("install")
+        {
+            continue;
+        }
+        for argument in arguments(*command, lines) {
+            let Some(variable) = INSTALL_DIR_REGEX
+                .captures(argument.text)
+                .map(|captures| captures[1].to_string())
+            else {
+                continue;
+            };
+            let message = match include {
+                Some(include) => format!(
+                    "{variable} is defined by include(GNUInstallDirs), which comes later, at line {}",
+                    line(commands[include])
+                ),
+                // Another file of the project may include it
+                None if cache
+                    .as_ref()
+                    .is_some_and(|cache| cache.contains_key(&variable)) =>
+                {
+                    continue;
+                }
+                None => format!(
+                    "{variable} is defined by include(GNUInstallDirs), which the file does not include"
+                ),
+            };
+            output.push(error(
+                argument.node,
+                message,
+                &rules::INSTALL_DIRS_BEFORE_INCLUDE,
+            ));
+        }
+    }
+    output
+}
+
+static SET_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(r"(?i)\b(?:set|option)\s*\(\s*([A-Za-z_][A-Za-z0-9_]*)").unwrap()
+});
+
+/// The variables set by the CMakeLists.txt of the parent directories in the
+/// project, which a subdirectory inherits. A module is included from the
+/// CMakeLists.txt of its own directory or of a parent.
+fn inherited_variables(local_path: &Path) -> HashSet<String> {
+    let (Some(source_dir), Some(dir)) = (module_path::source_dir(), local_path.parent()) else {
+        return HashSet::new();
+    };
+    let skip = usize::from(
+        local_path
+            .file_name()
+            .is_some_and(|name| name == "CMakeLists.txt"),
+    );
+    dir.ancestors()
+        .skip(skip)
+        .take_while(|parent| parent.starts_with(&source_dir))
+        .filter_map(|parent| std::fs::read_to_string(parent.join("CMakeLists.txt")).ok())
+        .flat_map(|lists| {
+            SET_REGEX
+                .captures_iter(&lists)
+                .map(|captures| captures[1].to_string())
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+fn check_variables(source: &str, root: Node, inherited: &HashSet<String>) -> Vec<ErrorInformation> {
+    eval::references_before_set(source, root, |name| inherited.contains(name))
+        .into_iter()
+        .map(|reference| {
+            error(
+                reference.variable,
+                format!(
+                    "{} is read before it is set, at line {}",
+                    reference.name,
+                    line(reference.set)
+                ),
+                &rules::VARIABLE_BEFORE_SET,
+            )
+        })
+        .collect()
+}
+
+/// The references to the variables of a `foreach()` after the loop, where
+/// they are no longer set
+fn check_loop_variables(source: &str, commands: &[Node]) -> Vec<ErrorInformation> {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut output = vec![];
+    for (index, command) in commands.iter().enumerate() {
+        if command.kind() != CMakeNodeKinds::FOREACH_COMMAND {
+            continue;
+        }
+        let Some(foreach_loop) = command.parent() else {
+            continue;
+        };
+        for variable in loops::loop_variables(foreach_loop, &lines) {
+            // The value it had before the loop is restored
+            if commands[..index]
+                .iter()
+                .any(|earlier| eval::plain_arguments(*earlier, source).contains(&variable.as_str()))
+            {
+                continue;
+            }
+            for later in commands[index + 1..]
+                .iter()
+                .filter(|later| later.start_byte() >= foreach_loop.end_byte())
+            {
+                let mut references = vec![];
+                eval::references(*later, &mut references);
+                for reference in references
+                    .into_iter()
+                    .filter(|reference| node_text(*reference, source) == variable)
+                {
+                    output.push(error(
+                        reference,
+                        format!(
+                            "{variable} is the variable of the foreach() loop ending at line {}, it is no longer set after the loop",
+                            foreach_loop.end_position().row + 1
+                        ),
+                        &rules::LOOP_VARIABLE_AFTER_LOOP,
+                    ));
+                }
+                if eval::plain_arguments(*later, source).contains(&variable.as_str()) {
+                    break;
+                }
+            }
+        }
+    }
+    output
+}
+
+pub fn check_ordering(
+    local_path: &Path,
+    source: &str,
+    lines: &[&str],
+    root: Node,
+) -> Vec<ErrorInformation> {
+    let commands = eval::ordered_commands(root);
+    let mut output = check_targets(lines, &commands);
+    output.append(&mut check_install_dirs(local_path, lines, &commands));
+    output.append(&mut check_variables(
+        source,
+        root,
+        &inherited_variables(local_path),
+    ));
+    output.append(&mut check_loop_variables(source, &commands));
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    fn check(source: &str) -> Vec<(usize, String)> {
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        check_ordering(
+            Path::new("/tmp/ordering/CMakeLists.txt"),
+            source,
+            &source.lines().collect::<Vec<_>>(),
+            thetree.root_node(),
+        )
+        .into_iter()
+        .map(|error| (error.start_point.row, error.rule.unwrap().name.to_string()))
+        .collect()
+    }
+
+    #[test]
+    fn test_ordering() {
+        let source = r#"target_link_libraries(app PRIVATE core)
+install(TARGETS app DESTINATION ${CMAKE_INSTALL_BINDIR})
+message(STATUS "${FLAGS}")
+add_executable(app main.c)
+include(GNUInstallDirs)
+set(FLAGS -Wall)
+install(FILES a.h DESTINATION ${CMAKE_INSTALL_INCLUDEDIR})
+target_compile_options(app PRIVATE ${FLAGS})
+foreach(source IN LISTS SOURCES)
+  message(${source})
+endforeach()
+message(${source})
+"#;
+        assert_eq!(
+            check(source),
+            vec![
+                (0, "target-before-declaration".to_string()),
+                (1, "install-dirs-before-include".to_string()),
+                (2, "variable-before-set".to_string()),
+                (11, "loop-variable-after-loop".to_string()),
+            ]
+        );
+
+        let source = r#"set(LIST "${LIST};a")
+foreach(item a b)
+  message(${previous})
+  set(previous ${item})
+endforeach()
+set(item last)
+message(${item})
+block()
+  set(LOCAL 1)
+endblock()
+message(${LOCAL})
+if(NOT DEFINED OUT)
+  message(${OUT})
+endif()
+set(OUT 1)
+if(WIN32)
+  message(${PLATFORM})
+else()
+  set(PLATFORM unix)
+endif()
+"#;
+        assert!(check(source).is_empty());
+    }
+
+    #[test]
+    fn test_inherited_variables() {
+        let source = "message(${FLAGS} ${MODE})\nset(FLAGS -Wall)\nset(MODE fast)\n";
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let inherited = HashSet::from(["FLAGS".to_string()]);
+        let names: Vec<String> = check_variables(source, thetree.root_node(), &inherited)
+            .into_iter()
+            .map(|error| error.message)
+            .collect();
+        assert_eq!(names, vec!["MODE is read before it is set, at line 3"]);
+    }
+}
@@ -0,0 +1,116 @@
+//! Packages found by `find_package()` which nothing in the project uses. Each
+//! lookup slows down the configure step, and fails it when required.
+
+use std::path::Path;
+
+use tree_sitter::Node;
+
+use super::ErrorInformation;
+use super::calls::{arguments, commands, error, is_computed};
+use crate::rules;
+use crate::utils::packages;
+
+/// The config files of a package find its dependencies for the projects
+/// using it
+fn is_package_config(local_path: &Path) -> bool {
+    local_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| {
+            let name = name.trim_end_matches(".in");
+            name.ends_with("Config.cmake") || name.ends_with("-config.cmake")
+        })
+}
+
+/// The `find_package()` calls of packages nothing uses, `used_elsewhere` telling
+/// whether another file uses a package, `None` when it is not known yet
+fn find_unused_packages(
+    local_path: &Path,
+    source: &[&str],
+    root: Node,
+    used_elsewhere: impl Fn(&str) -> Option<bool>,
+) -> Vec<ErrorInformation> {
+    if is_package_config(local_path) {
+        return vec![];
+    }
+    let mut calls = vec![];
+    commands(root, source, "find_package", &mut calls);
+    if calls.is_empty() {
+        return vec![];
+    }
+    let words = packages::words_of(&source.join("\n"));
+    let mut output = vec![];
+    for call in calls {
+        let Some(package) = arguments(call, source).into_iter().next() else {
+            continue;
+        };
+        let name = package.text.trim_matches('"');
+        if is_computed(name) || packages::uses_package(&words, name) {
+            continue;
+        }
+        // Unknown until the project is indexed
+        if used_elsewhere(name) != Some(false) {
+            continue;
+        }
+        output.push(error(
+            package.node,
+            format!(
+                "Nothing in the project uses {name}: none of its targets, variables or commands is referenced"
+            ),
+            &rules::UNUSED_PACKAGE,
+        ));
+    }
+    output
+}
+
+pub fn check_unused_packages(
+    local_path: &Path,
+    source: &[&str],
+    root: Node,
+) -> Vec<ErrorInformation> {
+    find_unused_packages(local_path, source, root, |name| {
+        packages::used_elsewhere(local_path, name)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    #[test]
+    fn test_unused_packages() {
+        assert!(is_package_config(Path::new(
+            "/tmp/cmake/DemoConfig.cmake.in"
+        )));
+        assert!(is_package_config(Path::new("/tmp/cmake/demo-config.cmake")));
+        assert!(!is_package_config(Path::new("/tmp/CMakeLists.txt")));
+
+        let source =
+            "find_package(ZLIB)\nfind_package(fmt)\ntarget_link_libraries(demo fmt::fmt)\n";
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let local_path = Path::new("/tmp/packages/CMakeLists.txt");
+        // Nothing is reported before the project is indexed
+        assert!(find_unused_packages(local_path, &lines, thetree.root_node(), |_| None).is_empty());
+
+        let source = "find_package(ZLIB)\nfind_package(glfw3 REQUIRED)\nfind_package(SFML 2.6 COMPONENTS graphics)\nfind_package(fmt)\ntarget_link_libraries(demo fmt::fmt glfw sfml-graphics)\n";
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let errors = find_unused_packages(local_path, &lines, thetree.root_node(), |_| Some(false));
+        let messages: Vec<&str> = errors.iter().map(|error| error.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "Nothing in the project uses ZLIB: none of its targets, variables or commands is referenced"
+            ]
+        );
+        // Another file links it
+        let errors = find_unused_packages(local_path, &lines, thetree.root_node(), |name| {
+            Some(name == "ZLIB")
+        });
+        assert!(errors.is_empty());
+    }
+}
@@ -0,0 +1,176 @@
+//! `pkg_check_modules()` and `pkg_search_module()` are defined by
+//! `find_package(PkgConfig)`, which has to be called first.
+
+use std::path::Path;
+use std::sync::LazyLock;
+
+use tree_sitter::{Node, Point};
+
+use super::{Fix, FixableIssue};
+use crate::CMakeNodeKinds;
+use crate::rules;
+use crate::utils::module_path;
+
+const PKG_CONFIG_COMMANDS: &[&str] = &["pkg_check_modules", "pkg_search_module"];
+
+/// `find_package(PkgConfig)` or `include(FindPkgConfig)`
+static FIND_PKG_CONFIG_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(r"(?i)\b(find_package\s*\(\s*PkgConfig\b|include\s*\(\s*FindPkgConfig\b)")
+        .unwrap()
+});
+
+/// The calls of `node` in the order they are written
+fn calls<'a>(node: Node<'a>, output: &mut Vec<Node<'a>>) {
+    let mut course = node.walk();
+    for child in node.children(&mut course) {
+        if child.kind() == CMakeNodeKinds::NORMAL_COMMAND {
+            output.push(child);
+        } else {
+            calls(child, output);
+        }
+    }
+}
+
+fn text<'a>(node: Node, source: &[&'a str]) -> Option<&'a str> {
+    let row = node.start_position().row;
+    if row != node.end_position().row {
+        return None;
+    }
+    source[row].get(node.start_position().column..node.end_position().column)
+}
+
+fn first_argument<'a>(command: Node, source: &[&'a str]) -> Option<&'a str> {
+    let argument_list = command
+        .child(2)
+        .filter(|node| node.kind() == CMakeNodeKinds::ARGUMENT_LIST)?;
+    let mut walk = argument_list.walk();
+    let argument = argument_list
+        .children(&mut walk)
+        .find(|node| node.kind() == CMakeNodeKinds::ARGUMENT)?;
+    text(argument, source).map(|text| text.trim_matches('"'))
+}
+
+/// Whether the CMakeLists.txt of a parent directory finds PkgConfig, which
+/// then defines its commands for the subdirectories too
+pub fn found_by_parent(local_path: &Path) -> bool {
+    module_path::source_dir().is_some_and(|source_dir| found_by_parent_in(local_path, &source_dir))
+}
+
+/// [`found_by_parent`] in the project at `source_dir`
+fn found_by_parent_in(local_path: &Path, source_dir: &Path) -> bool {
+    let Some(dir) = local_path.parent() else {
+        return false;
+    };
+    dir.ancestors()
+        .skip(1)
+        .take_while(|parent| parent.starts_with(source_dir))
+        .filter_map(|parent| std::fs::read_to_string(parent.join("CMakeLists.txt")).ok())
+        .any(|lists| FIND_PKG_CONFIG_REGEX.is_match(&lists))
+}
+
+/// The first pkg-config command called before PkgConfig is found in `source`,
+/// the only one to fix
+pub fn find_issues(source: &[&str], root: Node) -> Vec<FixableIssue> {
+    let mut commands = vec![];
+    calls(root, &mut commands);
+    let mut output = vec![];
+    for command in commands {
+        let Some(ids) = command.child(0) else {
+            continue;
+        };
+        let Some(name) = text(ids, source) else {
+            continue;
+        };
+        let lowercase = name.to_lowercase();
+        let module = match lowercase.as_str() {
+            "find_package" => Some("PkgConfig"),
+            "include" => Some("FindPkgConfig"),
+            _ => None,
+        };
+        if let Some(module) = module {
+            if first_argument(command, source) == Some(module) {
+                break;
+            }
+            continue;
+        }
+        if !PKG_CONFIG_COMMANDS.contains(&lowercase.as_str()) {
+            continue;
+        }
+        let start = command.start_position();
+        let indent: String = source[start.row]
+            .chars()
+            .take_while(|c| c.is_whitespace())
+            .collect();
+        output.push(FixableIssue {
+            start_point: ids.start_position(),
+            end_point: ids.end_position(),
+            message: format!("{name}() is defined by find_package(PkgConfig), which is not called"),
+            rule: &rules::MISSING_PKG_CONFIG,
+            fix: Fix {
+                title: "Add find_package(PkgConfig REQUIRED)".to_string(),
+                start: Point {
+                    row: start.row,
+                    column: 0,
+                },
+                end: Point {
+                    row: start.row,
+                    column: 0,
+                },
+                new_text: format!("{indent}find_package(PkgConfig REQUIRED)\n"),
+                safe: true,
+            },
+        });
+        break;
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+    use crate::gammar::fix::apply_fixes;
+
+    #[test]
+    fn test_missing_pkg_config() {
+        let source = r#"if(UNIX)
+    pkg_check_modules(GTK REQUIRED gtk+-3.0)
+endif()
+find_package(PkgConfig)
+pkg_check_modules(GLIB glib-2.0)
+find_package(PkgConfig)
+pkg_search_module(ZLIB zlib)
+"#;
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let issues = find_issues(&lines, thetree.root_node());
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].start_point, Point { row: 1, column: 4 });
+
+        let fixes: Vec<Fix> = issues.into_iter().map(|issue| issue.fix).collect();
+        let (fixed, _) = apply_fixes(source, &fixes);
+        assert!(fixed.starts_with(
+            "if(UNIX)\n    find_package(PkgConfig REQUIRED)\n    pkg_check_modules(GTK"
+        ));
+    }
+
+    #[test]
+    fn test_found_by_parent() {
+        let dir = tempfile::tempdir().unwrap();
+        let sub = dir.path().join("sub");
+        std::fs::create_dir(&sub).unwrap();
+        std::fs::write(
+            dir.path().join("CMakeLists.txt"),
+            "find_package(PkgConfig REQUIRED)\nadd_subdirectory(sub)\n",
+        )
+        .unwrap();
+        assert!(found_by_parent_in(&sub.join("CMakeLists.txt"), dir.path()));
+        assert!(!found_by_parent_in(
+            &dir.path().join("CMakeLists.txt"),
+            dir.path()
+        ));
+        assert!(!found_by_parent_in(&sub.join("CMakeLists.txt"), &sub));
+    }
+}
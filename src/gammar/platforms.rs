@@ -0,0 +1,175 @@
+//! Conditions on a platform variable cmake never sets, like `if(WINDOWS)` or
+//! `if(MACOS)`, which are false wherever the project is built.
+//!
+//! A name set by the file itself, by another file of the workspace like a
+//! toolchain file, or in the CMake cache by `-D` is a variable of the project
+//! and is not reported, nor is one compared to a value, like in
+//! `if(CMAKE_SYSTEM_NAME STREQUAL WINDOWS)`, or tested by `if(DEFINED WINDOWS)`.
+
+use std::collections::HashSet;
+use std::sync::PoisonError;
+
+use tree_sitter::Node;
+
+use super::unquoted::{Argument, COMPARISON_OPERATORS, arguments};
+use super::{Fix, FixableIssue};
+use crate::jump::WORKSPACE_VARIABLES;
+use crate::utils::platforms::mistaken_platform;
+use crate::{CMakeNodeKinds, fileapi, rules};
+
+/// The commands whose first argument is the variable they set
+const SETTING_COMMANDS: &[&str] = &["set", "option", "cmake_dependent_option"];
+
+fn argument_list(command: Node) -> Option<Node> {
+    let mut walk = command.walk();
+    command
+        .children(&mut walk)
+        .find(|node| node.kind() == CMakeNodeKinds::ARGUMENT_LIST)
+}
+
+/// Whether the argument at `index` is compared to another one
+fn compared(arguments: &[Argument], index: usize) -> bool {
+    let operator = |i: usize| {
+        arguments
+            .get(i)
+            .is_some_and(|argument| COMPARISON_OPERATORS.contains(&argument.text))
+    };
+    index.checked_sub(1).is_some_and(operator) || operator(index + 1)
+}
+
+fn find_issues_inner<'a>(
+    source: &[&'a str],
+    node: Node<'a>,
+    set: &mut HashSet<&'a str>,
+    found: &mut Vec<(Node<'a>, &'a str, &'static str)>,
+) {
+    let mut course = node.walk();
+    for child in node.children(&mut course) {
+        match child.kind() {
+            CMakeNodeKinds::IF_COMMAND
+            | CMakeNodeKinds::ELSEIF_COMMAND
+            | CMakeNodeKinds::WHILE_COMMAND => {
+                let Some(list) = argument_list(child) else {
+                    continue;
+                };
+                let arguments = arguments(list, source);
+                for (index, argument) in arguments.iter().enumerate() {
+                    if !argument.unquoted
+                        || compared(&arguments, index)
+                        || index
+                            .checked_sub(1)
+                            .is_some_and(|previous| arguments[previous].text == "DEFINED")
+                    {
+                        continue;
+                    }
+                    if let Some(meant) = mistaken_platform(argument.text) {
+                        found.push((argument.node, argument.text, meant));
+                    }
+                }
+            }
+            CMakeNodeKinds::NORMAL_COMMAND => {
+                let Some(ids) = child.child(0) else {
+                    continue;
+                };
+                let Some(name) = source.get(ids.start_position().row).and_then(|line| {
+                    line.get(ids.start_position().column..ids.end_position().column)
+                }) else {
+                    continue;
+                };
+                if !SETTING_COMMANDS.contains(&name.to_lowercase().as_str()) {
+                    continue;
+                }
+                if let Some(first) =
+                    argument_list(child).and_then(|list| arguments(list, source).into_iter().next())
+                {
+                    set.insert(first.text);
+                }
+            }
+            _ => find_issues_inner(source, child, set, found),
+        }
+    }
+}
+
+/// Find the conditions on a platform variable which does not exist, the
+/// variables of `known` being set elsewhere
+fn find_issues_with(source: &[&str], root: Node, known: &HashSet<String>) -> Vec<FixableIssue> {
+    let mut set = HashSet::new();
+    let mut found = vec![];
+    find_issues_inner(source, root, &mut set, &mut found);
+    found
+        .into_iter()
+        .filter(|(_, name, _)| !set.contains(name) && !known.contains(*name))
+        .map(|(node, name, meant)| FixableIssue {
+            start_point: node.start_position(),
+            end_point: node.end_position(),
+            message: format!("{name} is not a platform variable cmake sets, did you mean {meant}?"),
+            rule: &rules::UNKNOWN_PLATFORM,
+            // `APPLE` is not only macOS, the replacement may be broader
+            fix: Fix {
+                title: format!("Replace with {meant}"),
+                start: node.start_position(),
+                end: node.end_position(),
+                new_text: meant.to_string(),
+                safe: false,
+            },
+        })
+        .collect()
+}
+
+/// Find the conditions on a platform variable which does not exist, in the
+/// file, the workspace or the CMake cache
+pub fn find_issues(source: &[&str], root: Node) -> Vec<FixableIssue> {
+    let mut known = WORKSPACE_VARIABLES
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+    known.extend(fileapi::get_entries_data().unwrap_or_default().into_keys());
+    find_issues_with(source, root, &known)
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::Point;
+
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    #[test]
+    fn test_unknown_platforms() {
+        let source = r#"if(WINDOWS)
+elseif(NOT MACOS AND UNIX)
+endif()
+if(CMAKE_SYSTEM_NAME STREQUAL DARWIN OR "OSX")
+endif()
+option(WASM "Build for the web" OFF)
+while(WASM)
+endwhile()
+if(DEFINED WINDOWS OR NOT DEFINED MACOS)
+endif()
+if(MACOSX)
+endif()
+"#;
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        // MACOSX is set by the toolchain file, or by -DMACOSX=ON
+        let known = HashSet::from(["MACOSX".to_string()]);
+        let issues = find_issues_with(&lines, thetree.root_node(), &known);
+        let found: Vec<(Point, &str)> = issues
+            .iter()
+            .map(|issue| (issue.start_point, issue.fix.new_text.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (Point { row: 0, column: 3 }, "WIN32"),
+                (Point { row: 1, column: 11 }, "APPLE"),
+            ]
+        );
+        assert_eq!(
+            issues[0].message,
+            "WINDOWS is not a platform variable cmake sets, did you mean WIN32?"
+        );
+    }
+}
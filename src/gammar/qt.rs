@@ -0,0 +1,199 @@
+//! moc has to run on the classes declaring `Q_OBJECT`, which `AUTOMOC` does
+//! for the targets it is enabled on. Without it they fail to link.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex, PoisonError};
+use std::time::SystemTime;
+
+use tree_sitter::Node;
+
+use super::ErrorInformation;
+use super::calls::{arguments, commands, error, matched_by_parent};
+use crate::rules;
+use crate::utils::qt::FIND_QT_REGEX;
+
+/// `set(CMAKE_AUTOMOC ON)`, or `qt_standard_project_setup()` which does it
+static AUTOMOC_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(
+        r"(?i)\bset\s*\(\s*CMAKE_AUTOMOC\s+(ON|TRUE|YES|1)\b|\bqt[56]?_standard_project_setup\s*\(",
+    )
+    .unwrap()
+});
+
+const HEADER_EXTENSIONS: &[&str] = &["h", "hh", "hpp", "hxx"];
+
+const IMPLEMENTATION_EXTENSIONS: &[&str] = &["c", "cc", "cpp", "cxx"];
+
+/// The keywords of `add_executable()` and `add_library()` before the sources
+const TARGET_KEYWORDS: &[&str] = &[
+    "STATIC",
+    "SHARED",
+    "MODULE",
+    "OBJECT",
+    "INTERFACE",
+    "WIN32",
+    "MACOSX_BUNDLE",
+    "EXCLUDE_FROM_ALL",
+];
+
+/// Whether `AUTOMOC` is turned on for `target` in `source`
+fn automoc_on_target(source: &str, target: &str) -> bool {
+    let target = regex::escape(target);
+    regex::Regex::new(&format!(
+        r"(?is)\b(set_target_properties\s*\([^)]*\b{target}\b[^)]*\bPROPERTIES\b[^)]*\bAUTOMOC\s+(ON|TRUE|YES|1)\b|set_property\s*\(\s*TARGET\b[^)]*\b{target}\b[^)]*\bPROPERTY\s+AUTOMOC\s+(ON|TRUE|YES|1)\b)"
+    ))
+    .is_ok_and(|regex| regex.is_match(source))
+}
+
+/// Whether each source read declares a `Q_OBJECT` class, with its modified time
+static QOBJECT_FILES: LazyLock<Mutex<HashMap<PathBuf, (SystemTime, bool)>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn declares_qobject(path: &Path) -> bool {
+    let Ok(modified) = std::fs::metadata(path).and_then(|metadata| metadata.modified()) else {
+        return false;
+    };
+    if let Some((cached, declares)) = QOBJECT_FILES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(path)
+        && *cached == modified
+    {
+        return *declares;
+    }
+    let declares = std::fs::read_to_string(path).is_ok_and(|content| content.contains("Q_OBJECT"));
+    QOBJECT_FILES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(path.to_path_buf(), (modified, declares));
+    declares
+}
+
+/// The first source of the target, or header next to one of them, declaring
+/// a `Q_OBJECT` class
+fn qobject_file(dir: &Path, sources: &[&str]) -> Option<String> {
+    for source in sources {
+        if source.contains("${") {
+            continue;
+        }
+        let path = dir.join(source);
+        let Some(extension) = path.extension().and_then(|extension| extension.to_str()) else {
+            continue;
+        };
+        if HEADER_EXTENSIONS.contains(&extension) {
+            if declares_qobject(&path) {
+                return Some(source.to_string());
+            }
+        } else if IMPLEMENTATION_EXTENSIONS.contains(&extension) {
+            if declares_qobject(&path) {
+                return Some(source.to_string());
+            }
+            for header_extension in HEADER_EXTENSIONS {
+                let header = path.with_extension(header_extension);
+                if declares_qobject(&header) {
+                    return Some(
+                        Path::new(source)
+                            .with_extension(header_extension)
+                            .display()
+                            .to_string(),
+                    );
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Find the targets of a Qt project with `Q_OBJECT` classes, but without
+/// `AUTOMOC`. The targets of `qt_add_executable()` and `qt_add_library()`
+/// have it
+pub fn check_automoc(local_path: &Path, source: &[&str], root: Node) -> Vec<ErrorInformation> {
+    let text = source.join("\n");
+    if !FIND_QT_REGEX.is_match(&text) && !matched_by_parent(local_path, &FIND_QT_REGEX) {
+        return vec![];
+    }
+    if AUTOMOC_REGEX.is_match(&text) || matched_by_parent(local_path, &AUTOMOC_REGEX) {
+        return vec![];
+    }
+    let Some(dir) = local_path.parent() else {
+        return vec![];
+    };
+    let mut targets = vec![];
+    commands(root, source, "add_executable", &mut targets);
+    commands(root, source, "add_library", &mut targets);
+    let mut output = vec![];
+    for command in targets {
+        let arguments = arguments(command, source);
+        let Some(target) = arguments.first() else {
+            continue;
+        };
+        let sources: Vec<&str> = arguments[1..]
+            .iter()
+            .map(|argument| argument.text.trim_matches('"'))
+            .filter(|argument| !TARGET_KEYWORDS.contains(argument))
+            .collect();
+        if sources
+            .iter()
+            .any(|argument| matches!(*argument, "IMPORTED" | "ALIAS"))
+        {
+            continue;
+        }
+        if automoc_on_target(&text, target.text) {
+            continue;
+        }
+        let Some(file) = qobject_file(dir, &sources) else {
+            continue;
+        };
+        output.push(error(
+            target.node,
+            format!(
+                "{file} declares a Q_OBJECT class, but AUTOMOC is off for {}, so moc does not run on it",
+                target.text
+            ),
+            &rules::QT_AUTOMOC_OFF,
+        ));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    fn check(dir: &Path, source: &str) -> Vec<ErrorInformation> {
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        check_automoc(&dir.join("CMakeLists.txt"), &lines, thetree.root_node())
+    }
+
+    #[test]
+    fn test_automoc_off() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("window.cpp"), "#include \"window.h\"\n").unwrap();
+        std::fs::write(
+            dir.path().join("window.h"),
+            "class Window : public QWidget {\n    Q_OBJECT\n};\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("main.cpp"), "int main() {}\n").unwrap();
+
+        let source = "find_package(Qt6 REQUIRED COMPONENTS Widgets)\nadd_executable(app main.cpp window.cpp)\nadd_executable(tool main.cpp)\n";
+        let errors = check(dir.path(), source);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].start_point.row, 1);
+        assert!(errors[0].message.starts_with("window.h declares"));
+
+        let source = "find_package(Qt6 REQUIRED COMPONENTS Widgets)\nadd_executable(app main.cpp window.cpp)\nset_target_properties(app PROPERTIES AUTOMOC ON)\n";
+        assert!(check(dir.path(), source).is_empty());
+        let source = "find_package(Qt6 REQUIRED COMPONENTS Widgets)\nset(CMAKE_AUTOMOC ON)\nadd_executable(app main.cpp window.cpp)\n";
+        assert!(check(dir.path(), source).is_empty());
+        let source = "find_package(Qt6 REQUIRED COMPONENTS Widgets)\nqt_add_executable(app main.cpp window.cpp)\n";
+        assert!(check(dir.path(), source).is_empty());
+        // Not a Qt project
+        assert!(check(dir.path(), "add_executable(app window.cpp)\n").is_empty());
+    }
+}
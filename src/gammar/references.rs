@@ -0,0 +1,272 @@
+//! Lexer level checks of the variable references.
+//!
+//! A `${` missing its `}`, like `${A_${B}`, and a `$NAME` written the way shells
+//! do are not references, the arguments keep them as text and nothing tells where
+//! the value went.
+
+use std::sync::LazyLock;
+
+use tree_sitter::Point;
+
+use super::{Fix, FixableIssue};
+use crate::rules;
+
+/// The `$` words of the dynamic linker, used in the `RPATH` of targets
+const LINKER_TOKENS: &[&str] = &["ORIGIN", "LIB", "PLATFORM"];
+
+/// `sh -c`, `bash -c`..., the script after it reads the variables of the shell
+static SHELL_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(r"(?:^|[\s(/])(?:sh|bash|zsh|dash|ksh)(?:\.exe)?\s+(?:-\w+\s+)*-c\b").unwrap()
+});
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Normal,
+    Quoted,
+    Bracket { equals: usize },
+}
+
+/// The characters of a variable name, the others end an unterminated reference
+fn is_name_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || matches!(byte, b'_' | b'/' | b'.' | b'+' | b'-')
+}
+
+/// `[[`, `[=[`, ... at `at`, returning the count of `=`
+fn bracket_open(line: &[u8], at: usize) -> Option<usize> {
+    if line.get(at) != Some(&b'[') {
+        return None;
+    }
+    let equals = line[at + 1..]
+        .iter()
+        .take_while(|byte| **byte == b'=')
+        .count();
+    (line.get(at + 1 + equals) == Some(&b'[')).then_some(equals)
+}
+
+fn bracket_closes(line: &[u8], at: usize, equals: usize) -> bool {
+    line.get(at) == Some(&b']')
+        && line[at + 1..]
+            .iter()
+            .take_while(|byte| **byte == b'=')
+            .count()
+            == equals
+        && line.get(at + 1 + equals) == Some(&b']')
+}
+
+/// The length of the `${`, `$ENV{` or `$CACHE{` opening a reference at `at`
+fn reference_open(line: &[u8], at: usize) -> Option<usize> {
+    ["${", "$ENV{", "$CACHE{"]
+        .into_iter()
+        .find(|open| line[at..].starts_with(open.as_bytes()))
+        .map(str::len)
+}
+
+/// Close the references opened at `opened`, still open at `column`
+fn unterminated_issue(
+    line: &str,
+    row: usize,
+    opened: &mut Vec<usize>,
+    column: usize,
+) -> FixableIssue {
+    let start = opened[0];
+    let closing = "}".repeat(opened.len());
+    opened.clear();
+    let end = Point { row, column };
+    FixableIssue {
+        start_point: Point { row, column: start },
+        end_point: end,
+        message: format!(
+            "Unterminated variable reference `{}`, missing `{closing}`",
+            &line[start..column]
+        ),
+        rule: &rules::UNTERMINATED_REFERENCE,
+        fix: Fix {
+            title: format!("Insert missing `{closing}`"),
+            start: end,
+            end,
+            new_text: closing,
+            safe: false,
+        },
+    }
+}
+
+/// `$NAME` at `at`, for the shells, and not `$$NAME`
+fn stray_issue(line: &str, row: usize, at: usize) -> Option<FixableIssue> {
+    let bytes = line.as_bytes();
+    if at > 0 && bytes[at - 1] == b'$' {
+        return None;
+    }
+    let length = bytes[at + 1..]
+        .iter()
+        .take_while(|byte| byte.is_ascii_alphanumeric() || **byte == b'_')
+        .count();
+    let name = &line[at + 1..at + 1 + length];
+    if length == 0 || bytes[at + 1].is_ascii_digit() || LINKER_TOKENS.contains(&name) {
+        return None;
+    }
+    let start = Point { row, column: at };
+    let end = Point {
+        row,
+        column: at + 1 + length,
+    };
+    Some(FixableIssue {
+        start_point: start,
+        end_point: end,
+        message: format!("${name} is not a variable reference, did you mean ${{{name}}}?"),
+        rule: &rules::STRAY_DOLLAR,
+        fix: Fix {
+            title: format!("Replace with ${{{name}}}"),
+            start,
+            end,
+            new_text: format!("${{{name}}}"),
+            safe: false,
+        },
+    })
+}
+
+/// Find the unterminated variable references and the stray `$` of `source`
+pub fn find_issues(source: &str) -> Vec<FixableIssue> {
+    let mut issues = vec![];
+    let mut state = State::Normal;
+    // The parentheses open, and whether the command runs a shell script
+    let mut depth = 0usize;
+    let mut shell = false;
+    for (row, text) in source.lines().enumerate() {
+        let line = text.as_bytes();
+        // The columns of the `$` of the references not closed yet
+        let mut opened: Vec<usize> = vec![];
+        let mut column = 0;
+        while column < line.len() {
+            let byte = line[column];
+            let mut step = 1;
+            // Brackets keep their content as it is
+            if let State::Bracket { equals } = state {
+                if bracket_closes(line, column, equals) {
+                    state = State::Normal;
+                    step = equals + 2;
+                }
+                column += step;
+                continue;
+            }
+            if !opened.is_empty() && !is_name_byte(byte) && byte != b'$' && byte != b'}' {
+                issues.push(unterminated_issue(text, row, &mut opened, column));
+            }
+            match (state, byte) {
+                (_, b'\\') => step = 2,
+                (_, b'$') => match reference_open(line, column) {
+                    Some(length) => {
+                        opened.push(column);
+                        step = length;
+                    }
+                    None => {
+                        if opened.is_empty()
+                            && !shell
+                            && !SHELL_REGEX.is_match(&text[..column])
+                            && let Some(issue) = stray_issue(text, row, column)
+                        {
+                            issues.push(issue);
+                        }
+                    }
+                },
+                (_, b'}') => {
+                    opened.pop();
+                }
+                (State::Normal, b'#') => match bracket_open(line, column + 1) {
+                    Some(equals) => {
+                        state = State::Bracket { equals };
+                        step = equals + 3;
+                    }
+                    // Line comment
+                    None => break,
+                },
+                (State::Normal, b'[') => {
+                    if let Some(equals) = bracket_open(line, column) {
+                        state = State::Bracket { equals };
+                        step = equals + 2;
+                    }
+                }
+                (State::Normal, b'(') => depth += 1,
+                (State::Normal, b')') => {
+                    depth = depth.saturating_sub(1);
+                    if depth == 0 {
+                        shell = false;
+                    }
+                }
+                (State::Normal, b'"') => state = State::Quoted,
+                (State::Quoted, b'"') => state = State::Normal,
+                _ => {}
+            }
+            column += step;
+        }
+        if !opened.is_empty() {
+            let end = text.trim_end().len().max(opened[opened.len() - 1]);
+            issues.push(unterminated_issue(text, row, &mut opened, end));
+        }
+        // The script may go on over the next lines of the command
+        shell = depth > 0 && (shell || SHELL_REGEX.is_match(text));
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_references() {
+        let source = r#"set(NAME "${PROJECT_NAME")
+message(STATUS "${PREFIX_${SUFFIX}" ${A})
+add_library(lib $ENV{ROOT/src/a.c)
+set(CMAKE_INSTALL_RPATH "$ORIGIN/../lib")
+message("$HOME \${A} $$B $<CONFIG> ${A}}")
+# ${COMMENT
+message([[ ${BRACKET ]])
+add_custom_command(OUTPUT a COMMAND sh -c "echo $HOME > a")
+execute_process(COMMAND /bin/bash -e -c
+  "cd $TMPDIR && ls")
+message("$USER")
+"#;
+        let issues = find_issues(source);
+        let found: Vec<(Point, &str, &str)> = issues
+            .iter()
+            .map(|issue| {
+                (
+                    issue.start_point,
+                    issue.message.as_str(),
+                    issue.fix.new_text.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    Point { row: 0, column: 10 },
+                    "Unterminated variable reference `${PROJECT_NAME`, missing `}`",
+                    "}"
+                ),
+                (
+                    Point { row: 1, column: 16 },
+                    "Unterminated variable reference `${PREFIX_${SUFFIX}`, missing `}`",
+                    "}"
+                ),
+                (
+                    Point { row: 2, column: 16 },
+                    "Unterminated variable reference `$ENV{ROOT/src/a.c`, missing `}`",
+                    "}"
+                ),
+                (
+                    Point { row: 4, column: 9 },
+                    "$HOME is not a variable reference, did you mean ${HOME}?",
+                    "${HOME}"
+                ),
+                (
+                    Point { row: 10, column: 9 },
+                    "$USER is not a variable reference, did you mean ${USER}?",
+                    "${USER}"
+                ),
+            ]
+        );
+        assert_eq!(issues[0].fix.start, Point { row: 0, column: 24 });
+    }
+}
@@ -0,0 +1,239 @@
+//! The `source_group()` calls cmake rejects: a file of
+//! `source_group(TREE <root> FILES ...)` outside of the root, a keyword missing
+//! its value, and a file of `source_group(<name> ...)` before `FILES`.
+
+use std::path::{Component, Path, PathBuf};
+
+use tree_sitter::Node;
+
+use super::ErrorInformation;
+use super::calls::{Argument, arguments, commands, error};
+use crate::rules;
+
+/// The keywords followed by a single value
+const ONE_VALUE_KEYWORDS: &[&str] = &["TREE", "PREFIX", "REGULAR_EXPRESSION"];
+
+/// The directories the paths of a file are relative to
+struct Dirs<'a> {
+    /// `CMAKE_CURRENT_LIST_DIR`, the directory of the file
+    list_dir: &'a Path,
+    /// `CMAKE_CURRENT_SOURCE_DIR`, which the relative paths start from. For a
+    /// file included by a `CMakeLists.txt`, it is the one of the including
+    /// file, unknown here.
+    source_dir: Option<&'a Path>,
+}
+
+/// `path` with its `.` and `..` resolved, unless it is computed or relative to
+/// an unknown directory
+fn resolve(dirs: &Dirs, path: &str) -> Option<PathBuf> {
+    let mut path = path.trim_matches('"').replace(
+        "${CMAKE_CURRENT_LIST_DIR}",
+        &dirs.list_dir.to_string_lossy(),
+    );
+    if path.contains("${CMAKE_CURRENT_SOURCE_DIR}") {
+        path = path.replace(
+            "${CMAKE_CURRENT_SOURCE_DIR}",
+            &dirs.source_dir?.to_string_lossy(),
+        );
+    }
+    if path.is_empty() || path.contains("${") || path.contains("$<") {
+        return None;
+    }
+    let path = Path::new(&path);
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        dirs.source_dir?.join(path)
+    };
+    let mut resolved = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            component => resolved.push(component),
+        }
+    }
+    Some(resolved)
+}
+
+fn check_tree(dirs: &Dirs, arguments: &[Argument], output: &mut Vec<ErrorInformation>) {
+    let Some(root) = arguments
+        .get(1)
+        .filter(|root| !ONE_VALUE_KEYWORDS.contains(&root.text) && root.text != "FILES")
+        .and_then(|root| resolve(dirs, root.text))
+    else {
+        return;
+    };
+    let Some(files) = arguments
+        .iter()
+        .position(|argument| argument.text == "FILES")
+    else {
+        return;
+    };
+    for file in &arguments[files + 1..] {
+        if ONE_VALUE_KEYWORDS.contains(&file.text) {
+            break;
+        }
+        if let Some(path) = resolve(dirs, file.text)
+            && !path.starts_with(&root)
+        {
+            output.push(error(
+                file.node,
+                format!(
+                    "{} is not under the TREE root {}, cmake fails",
+                    file.text, arguments[1].text
+                ),
+                &rules::SOURCE_GROUP,
+            ));
+        }
+    }
+}
+
+fn check_named(arguments: &[Argument], output: &mut Vec<ErrorInformation>) {
+    // The legacy signature, `source_group(<name> <regex>)`
+    if arguments.len() == 2 {
+        return;
+    }
+    for (index, argument) in arguments.iter().enumerate().skip(1) {
+        if argument.text == "FILES" {
+            break;
+        }
+        if ONE_VALUE_KEYWORDS.contains(&argument.text)
+            || arguments[index - 1].text == "REGULAR_EXPRESSION"
+        {
+            continue;
+        }
+        output.push(error(
+            argument.node,
+            format!(
+                "Unknown argument {}, the FILES keyword may be missing",
+                argument.text
+            ),
+            &rules::SOURCE_GROUP,
+        ));
+        break;
+    }
+}
+
+fn check_call(dirs: &Dirs, command: Node, source: &[&str], output: &mut Vec<ErrorInformation>) {
+    let arguments = arguments(command, source);
+    let Some(first) = arguments.first() else {
+        return;
+    };
+    for (index, argument) in arguments.iter().enumerate() {
+        if !ONE_VALUE_KEYWORDS.contains(&argument.text) {
+            continue;
+        }
+        // `source_group(TREE ...)` names no group called TREE
+        if index == 0 && argument.text != "TREE" {
+            continue;
+        }
+        let value = arguments.get(index + 1);
+        if value
+            .is_none_or(|value| ONE_VALUE_KEYWORDS.contains(&value.text) || value.text == "FILES")
+        {
+            output.push(error(
+                argument.node,
+                format!("{} needs a value", argument.text),
+                &rules::SOURCE_GROUP,
+            ));
+        }
+    }
+    if first.text == "TREE" {
+        check_tree(dirs, &arguments, output);
+    } else {
+        check_named(&arguments, output);
+    }
+}
+
+/// Check the `source_group()` calls of `source`
+pub fn check_source_groups<P: AsRef<Path>>(
+    local_path: P,
+    source: &[&str],
+    root: Node,
+) -> Vec<ErrorInformation> {
+    let local_path = local_path.as_ref();
+    let Some(list_dir) = local_path.parent() else {
+        return vec![];
+    };
+    let dirs = Dirs {
+        list_dir,
+        source_dir: local_path
+            .file_name()
+            .is_some_and(|name| name == "CMakeLists.txt")
+            .then_some(list_dir),
+    };
+    let mut calls = vec![];
+    commands(root, source, "source_group", &mut calls);
+    let mut output = vec![];
+    for call in calls {
+        check_call(&dirs, call, source, &mut output);
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::Point;
+
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    #[test]
+    fn test_source_groups() {
+        let source = r#"source_group(TREE ${CMAKE_CURRENT_SOURCE_DIR}/src PREFIX Sources FILES
+  src/main.cpp
+  ${CMAKE_CURRENT_SOURCE_DIR}/src/../include/api.h
+  ${GENERATED}
+)
+source_group(TREE src/.. FILES include/api.h)
+source_group(Headers include/api.h FILES other.h)
+source_group(Sources "\\.cpp$")
+source_group(Generated REGULAR_EXPRESSION ".*\\.in" FILES config.h)
+source_group(TREE src PREFIX)
+"#;
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let errors =
+            check_source_groups("/tmp/project/CMakeLists.txt", &lines, thetree.root_node());
+        let found: Vec<(Point, &str)> = errors
+            .iter()
+            .map(|error| (error.start_point, error.message.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    Point { row: 2, column: 2 },
+                    "${CMAKE_CURRENT_SOURCE_DIR}/src/../include/api.h is not under the TREE root ${CMAKE_CURRENT_SOURCE_DIR}/src, cmake fails"
+                ),
+                (
+                    Point { row: 6, column: 21 },
+                    "Unknown argument include/api.h, the FILES keyword may be missing"
+                ),
+                (Point { row: 9, column: 22 }, "PREFIX needs a value"),
+            ]
+        );
+
+        // An included file knows its own directory only
+        let source = r#"source_group(TREE ${CMAKE_CURRENT_LIST_DIR}/src FILES
+  ${CMAKE_CURRENT_LIST_DIR}/main.cpp
+  ${CMAKE_CURRENT_SOURCE_DIR}/main.cpp
+  main.cpp
+)
+"#;
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let errors = check_source_groups(
+            "/tmp/project/cmake/sources.cmake",
+            &lines,
+            thetree.root_node(),
+        );
+        let found: Vec<Point> = errors.iter().map(|error| error.start_point).collect();
+        assert_eq!(found, vec![Point { row: 1, column: 2 }]);
+    }
+}
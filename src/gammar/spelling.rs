@@ -0,0 +1,383 @@
+//! `${VAR}` references to variables which are never set, when a known name is
+//! a typo away.
+//!
+//! A variable can come from a cache entry, the command line or a package, so an
+//! unknown name alone means nothing. Only the references close to a builtin
+//! variable or one set in the workspace are reported.
+
+use std::collections::HashSet;
+use std::ops::Range;
+use std::sync::{LazyLock, PoisonError};
+
+use tree_sitter::Node;
+
+use super::{Fix, FixableIssue};
+use crate::CMakeNodeKinds;
+use crate::complete::builtin::BUILTIN_VARIABLE;
+use crate::jump::WORKSPACE_VARIABLES;
+use crate::rules;
+use crate::utils::loops;
+use crate::utils::parse_arguments::parse_arguments_of;
+
+static WORD_REGEX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"[A-Za-z_][A-Za-z0-9_]*").unwrap());
+
+static PLACEHOLDER_REGEX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"<[^>]*>").unwrap());
+
+/// Set by cmake in functions and macros, and not listed by `--help-variables`
+const IMPLICIT_VARIABLES: &[&str] = &["ARGC", "ARGV", "ARGN"];
+
+/// The commands whose first argument is the variable they set
+const SETTING_COMMANDS: &[&str] = &["set", "option"];
+
+struct BuiltinVariables {
+    names: Vec<String>,
+    /// Matches the variables like `CMAKE_<LANG>_FLAGS`
+    pattern: Option<regex::Regex>,
+}
+
+/// The variables of `--help-variables`
+static BUILTIN_VARIABLES: LazyLock<BuiltinVariables> = LazyLock::new(|| {
+    let labels: Vec<&str> = BUILTIN_VARIABLE
+        .iter()
+        .flatten()
+        .map(|item| item.label.as_str())
+        .collect();
+    builtin_variables(&labels)
+});
+
+fn builtin_variables(labels: &[&str]) -> BuiltinVariables {
+    let patterns: Vec<String> = labels
+        .iter()
+        .filter(|label| label.contains('<'))
+        .map(|label| {
+            PLACEHOLDER_REGEX
+                .split(label)
+                .map(regex::escape)
+                .collect::<Vec<_>>()
+                .join("[A-Za-z0-9_.+-]+")
+        })
+        .collect();
+    BuiltinVariables {
+        names: labels
+            .iter()
+            .filter(|label| !label.contains('<'))
+            .map(|label| label.to_string())
+            .collect(),
+        pattern: if patterns.is_empty() {
+            None
+        } else {
+            regex::Regex::new(&format!("^(?:{})$", patterns.join("|"))).ok()
+        },
+    }
+}
+
+/// Edits between `a` and `b`, or `None` when more than `max`
+fn edit_distance(a: &str, b: &str, max: usize) -> Option<usize> {
+    let a = a.as_bytes();
+    let b = b.as_bytes();
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(!ca.eq_ignore_ascii_case(cb));
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    let distance = previous[b.len()];
+    (distance <= max).then_some(distance)
+}
+
+/// How many edits a name of `len` characters can be away from a suggestion
+fn max_distance(len: usize) -> usize {
+    match len {
+        0..4 => 0,
+        4..8 => 1,
+        _ => 2,
+    }
+}
+
+/// The closest of `candidates` to the unknown `name`
+fn suggestion<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let max = max_distance(name.len());
+    candidates
+        .filter(|candidate| *candidate != name)
+        .filter_map(|candidate| Some((edit_distance(name, candidate, max)?, candidate)))
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// The names written in the file other than in a `${}` reference
+fn written_names<'a>(source: &[&'a str]) -> HashSet<&'a str> {
+    let mut names = HashSet::new();
+    for line in source {
+        for word in WORD_REGEX.find_iter(line) {
+            if !line[..word.start()].ends_with("${") {
+                names.insert(word.as_str());
+            }
+        }
+    }
+    names
+}
+
+/// The variables of the `foreach()` loops of the file
+fn collect_loop_variables(node: Node, source: &[&str], output: &mut Vec<String>) {
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if child.kind() == CMakeNodeKinds::FOREACH_LOOP {
+            output.extend(loops::loop_variables(child, source));
+        }
+        collect_loop_variables(child, source, output);
+    }
+}
+
+/// The variables `cmake_parse_arguments()` defines in the functions and macros
+/// of the file, with the bytes of the definition they are known in
+fn collect_parsed_arguments(
+    node: Node,
+    source: &[&str],
+    output: &mut Vec<(Range<usize>, Vec<String>)>,
+) {
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if matches!(
+            child.kind(),
+            CMakeNodeKinds::FUNCTION_DEF | CMakeNodeKinds::MACRO_DEF
+        ) && let Some(parsed) = parse_arguments_of(source, child)
+        {
+            output.push((child.byte_range(), parsed.variables()));
+        }
+        collect_parsed_arguments(child, source, output);
+    }
+}
+
+/// The variables set by the commands of the file, and the `${}` references
+fn collect<'a>(
+    node: Node<'a>,
+    source: &[&'a str],
+    set: &mut Vec<&'a str>,
+    references: &mut Vec<(Node<'a>, &'a str)>,
+) {
+    let text = |node: Node| -> Option<&'a str> {
+        let row = node.start_position().row;
+        (row == node.end_position().row)
+            .then(|| source[row].get(node.start_position().column..node.end_position().column))
+            .flatten()
+    };
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        match child.kind() {
+            CMakeNodeKinds::NORMAL_COMMAND => {
+                if let Some(name) = child.child(0).and_then(text)
+                    && SETTING_COMMANDS.contains(&name.to_lowercase().as_str())
+                    && let Some(variable) = child
+                        .child(2)
+                        .and_then(|arguments| arguments.named_child(0))
+                        .and_then(text)
+                {
+                    set.push(variable);
+                }
+            }
+            CMakeNodeKinds::NORMAL_VAR => {
+                let mut walk = child.walk();
+                if let Some(variable) = child
+                    .children(&mut walk)
+                    .find(|node| node.kind() == CMakeNodeKinds::VARIABLE)
+                    && variable.named_child_count() == 0
+                    && let Some(name) = text(variable)
+                {
+                    references.push((variable, name));
+                }
+                continue;
+            }
+            _ => {}
+        }
+        collect(child, source, set, references);
+    }
+}
+
+fn find_issues_with(
+    source: &[&str],
+    root: Node,
+    builtin: &BuiltinVariables,
+    workspace: &[String],
+) -> Vec<FixableIssue> {
+    let mut set = vec![];
+    let mut references = vec![];
+    collect(root, source, &mut set, &mut references);
+    if references.is_empty() {
+        return vec![];
+    }
+    let written = written_names(source);
+    let mut loop_variables = vec![];
+    collect_loop_variables(root, source, &mut loop_variables);
+    let mut parsed_arguments = vec![];
+    collect_parsed_arguments(root, source, &mut parsed_arguments);
+    // The variables of `cmake_parse_arguments()` in the definition around `node`
+    let parsed_at = |node: Node| -> Vec<&String> {
+        parsed_arguments
+            .iter()
+            .filter(|(range, _)| range.contains(&node.start_byte()))
+            .flat_map(|(_, variables)| variables)
+            .collect()
+    };
+    let is_known = |node: Node, name: &str| {
+        written.contains(name)
+            || parsed_at(node).into_iter().any(|variable| variable == name)
+            || loop_variables.iter().any(|variable| variable == name)
+            || IMPLICIT_VARIABLES.contains(&name)
+            || name
+                .strip_prefix("ARGV")
+                .is_some_and(|index| index.chars().all(|c| c.is_ascii_digit()))
+            || builtin.names.iter().any(|builtin| builtin == name)
+            || builtin
+                .pattern
+                .as_ref()
+                .is_some_and(|pattern| pattern.is_match(name))
+            || workspace.iter().any(|variable| variable == name)
+    };
+
+    let mut output = vec![];
+    for (node, name) in references {
+        if is_known(node, name) {
+            continue;
+        }
+        let candidates = builtin
+            .names
+            .iter()
+            .chain(workspace)
+            .chain(&loop_variables)
+            .chain(parsed_at(node))
+            .map(String::as_str)
+            .chain(set.iter().copied());
+        let Some(replacement) = suggestion(name, candidates) else {
+            continue;
+        };
+        output.push(FixableIssue {
+            start_point: node.start_position(),
+            end_point: node.end_position(),
+            message: format!("Unknown variable {name}, did you mean {replacement}?"),
+            rule: &rules::UNKNOWN_VARIABLE,
+            fix: Fix {
+                title: format!("Replace with {replacement}"),
+                start: node.start_position(),
+                end: node.end_position(),
+                new_text: replacement.to_string(),
+                safe: false,
+            },
+        });
+    }
+    output
+}
+
+/// Find the references to unknown variables with a close known name
+pub fn find_issues(source: &[&str], root: Node) -> Vec<FixableIssue> {
+    let workspace: Vec<String> = WORKSPACE_VARIABLES
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .cloned()
+        .collect();
+    find_issues_with(source, root, &BUILTIN_VARIABLES, &workspace)
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::Point;
+
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    #[test]
+    fn test_misspelled_variables() {
+        let source = r#"set(MY_SOURCES a.c)
+add_library(x ${MY_SOURCE} ${UNRELATED})
+set_target_properties(x PROPERTIES CXX_STANDARD ${CMAKE_CXX_STANDRD})
+message("${CMAKE_CXX_FLAGS} ${ARGN} ${ARGV2} ${CMAKE_C_FLAG}")
+"#;
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let builtin = builtin_variables(&["CMAKE_CXX_STANDARD", "CMAKE_<LANG>_FLAGS"]);
+        let issues = find_issues_with(&lines, thetree.root_node(), &builtin, &[]);
+        let found: Vec<(Point, &str)> = issues
+            .iter()
+            .map(|issue| (issue.start_point, issue.fix.new_text.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (Point { row: 1, column: 16 }, "MY_SOURCES"),
+                (Point { row: 2, column: 50 }, "CMAKE_CXX_STANDARD"),
+            ]
+        );
+        assert_eq!(
+            issues[0].message,
+            "Unknown variable MY_SOURCE, did you mean MY_SOURCES?"
+        );
+    }
+
+    #[test]
+    fn test_parsed_arguments() {
+        let source = r#"set(ARG_SOURCE main.c)
+function(my_add NAME)
+  cmake_parse_arguments(ARG "STATIC" "DESTINATION" "SOURCES" ${ARGN})
+  add_library(${NAME} ${ARG_SOURCES} ${ARG_UNPARSED_ARGUMENTS})
+  message(${ARG_KEYWORDS_MISSING_VALUES} ${ARG_DESTINATON})
+endfunction()
+message(${ARG_SOURCES})
+"#;
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let builtin = builtin_variables(&[]);
+        let issues = find_issues_with(&lines, thetree.root_node(), &builtin, &[]);
+        let found: Vec<(usize, &str)> = issues
+            .iter()
+            .map(|issue| (issue.start_point.row, issue.fix.new_text.as_str()))
+            .collect();
+        // Only known in the function calling cmake_parse_arguments()
+        assert_eq!(found, vec![(4, "ARG_DESTINATION"), (6, "ARG_SOURCE")]);
+    }
+
+    #[tokio::test]
+    async fn test_workspace_variables_while_indexing() {
+        crate::jump::update_cache(
+            "/spelling/CMakeLists.txt",
+            "set(SPELLING_TEST_SOURCES a.c)\n",
+        )
+        .await;
+        // The same diagnostics while another file is being indexed
+        let _indexing = crate::jump::JUMP_CACHE.lock().await;
+        let source = "message(${SPELLING_TEST_SOURCE})\n";
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let issues = find_issues(&lines, thetree.root_node());
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].fix.new_text, "SPELLING_TEST_SOURCES");
+    }
+
+    #[test]
+    fn test_loop_variables() {
+        let source = "foreach(pair IN ZIP_LISTS NAMES PATHS)\n  message(${pair_0} ${pair_O})\nendforeach()\n";
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let builtin = builtin_variables(&[]);
+        let issues = find_issues_with(&lines, thetree.root_node(), &builtin, &[]);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].start_point, Point { row: 1, column: 22 });
+        assert_eq!(issues[0].fix.new_text, "pair_0");
+    }
+}
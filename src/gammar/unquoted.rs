@@ -0,0 +1,276 @@
+//! Unquoted arguments which do not do what they look like.
+//!
+//! `if(${VAR} STREQUAL value)` expands `VAR` before the comparison, so an empty
+//! or list value breaks it, and `C:/Program Files/foo` is two arguments.
+
+use tree_sitter::Node;
+
+use super::{Fix, FixableIssue};
+use crate::CMakeNodeKinds;
+use crate::rules;
+
+/// The binary operators of `if()` and `while()`
+pub(super) const COMPARISON_OPERATORS: &[&str] = &[
+    "EQUAL",
+    "LESS",
+    "LESS_EQUAL",
+    "GREATER",
+    "GREATER_EQUAL",
+    "STREQUAL",
+    "STRLESS",
+    "STRLESS_EQUAL",
+    "STRGREATER",
+    "STRGREATER_EQUAL",
+    "VERSION_EQUAL",
+    "VERSION_LESS",
+    "VERSION_LESS_EQUAL",
+    "VERSION_GREATER",
+    "VERSION_GREATER_EQUAL",
+    "PATH_EQUAL",
+    "MATCHES",
+    "IN_LIST",
+];
+
+pub(super) struct Argument<'a> {
+    pub node: Node<'a>,
+    pub text: &'a str,
+    pub unquoted: bool,
+}
+
+pub(super) fn arguments<'a>(argument_list: Node<'a>, source: &[&'a str]) -> Vec<Argument<'a>> {
+    let mut walk = argument_list.walk();
+    argument_list
+        .children(&mut walk)
+        .filter(|node| {
+            node.kind() == CMakeNodeKinds::ARGUMENT
+                && node.start_position().row == node.end_position().row
+        })
+        .map(|node| {
+            let h = node.start_position().row;
+            Argument {
+                node,
+                text: &source[h][node.start_position().column..node.end_position().column],
+                unquoted: node
+                    .child(0)
+                    .is_some_and(|child| child.kind() == CMakeNodeKinds::UNQUOTED_ARGUMENT),
+            }
+        })
+        .collect()
+}
+
+/// `${NAME}` and nothing else
+fn single_variable_ref(text: &str) -> Option<&str> {
+    let name = text.strip_prefix("${")?.strip_suffix('}')?;
+    (!name.is_empty() && !name.contains(['$', '{', '}'])).then_some(name)
+}
+
+/// Quoting an argument does not change it, unless it is split or expanded to a list
+fn quote_fix(title: String, node: Node, new_text: String) -> Fix {
+    Fix {
+        title,
+        start: node.start_position(),
+        end: node.end_position(),
+        new_text,
+        safe: true,
+    }
+}
+
+fn check_condition(arguments: &[Argument], output: &mut Vec<FixableIssue>) {
+    for (index, operator) in arguments.iter().enumerate() {
+        if !operator.unquoted || !COMPARISON_OPERATORS.contains(&operator.text) {
+            continue;
+        }
+        let operands = [index.checked_sub(1), Some(index + 1)];
+        for operand in operands
+            .into_iter()
+            .flatten()
+            .filter_map(|i| arguments.get(i))
+        {
+            if !operand.unquoted {
+                continue;
+            }
+            let Some(name) = single_variable_ref(operand.text) else {
+                continue;
+            };
+            output.push(FixableIssue {
+                start_point: operand.node.start_position(),
+                end_point: operand.node.end_position(),
+                message: format!(
+                    "Unquoted {} in a comparison breaks when {name} is empty or a list, quote it",
+                    operand.text
+                ),
+                rule: &rules::UNQUOTED_COMPARISON,
+                fix: quote_fix(
+                    format!("Quote {}", operand.text),
+                    operand.node,
+                    format!("\"{}\"", operand.text),
+                ),
+            });
+        }
+    }
+}
+
+/// The start of a path, like `/opt`, `C:/` or `${CMAKE_SOURCE_DIR}/`
+fn looks_absolute(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    text.starts_with('/')
+        || text.starts_with("${")
+        || (bytes.len() >= 3
+            && bytes[0].is_ascii_alphabetic()
+            && bytes[1] == b':'
+            && (bytes[2] == b'/' || bytes[2] == b'\\'))
+}
+
+/// `Files/foo` after `C:/Program`: a capitalized word, not a KEYWORD
+fn continues_path(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars.next().is_some_and(|c| c.is_ascii_uppercase())
+        && chars.next().is_some_and(|c| c.is_ascii_lowercase())
+}
+
+fn check_spaces(arguments: &[Argument], source: &[&str], output: &mut Vec<FixableIssue>) {
+    for argument in arguments.iter().filter(|argument| argument.unquoted) {
+        if argument.text.contains("\\ ") {
+            output.push(FixableIssue {
+                start_point: argument.node.start_position(),
+                end_point: argument.node.end_position(),
+                message: "Path with escaped spaces, quote it instead".to_string(),
+                rule: &rules::UNQUOTED_SPACES,
+                fix: quote_fix(
+                    "Quote the path".to_string(),
+                    argument.node,
+                    format!("\"{}\"", argument.text.replace("\\ ", " ")),
+                ),
+            });
+        }
+    }
+    for pair in arguments.windows(2) {
+        let [first, second] = pair else {
+            continue;
+        };
+        let (start, end) = (first.node.end_position(), second.node.start_position());
+        // Separated by a single space on the same line
+        if !first.unquoted
+            || !second.unquoted
+            || start.row != end.row
+            || end.column != start.column + 1
+            || &source[start.row][start.column..end.column] != " "
+        {
+            continue;
+        }
+        let last_segment = first.text.rsplit('/').next().unwrap_or_default();
+        if !first.text.contains('/')
+            || !looks_absolute(first.text)
+            || last_segment.is_empty()
+            || last_segment.contains('.')
+            || !continues_path(second.text)
+        {
+            continue;
+        }
+        let joined = format!("{} {}", first.text, second.text);
+        output.push(FixableIssue {
+            start_point: first.node.start_position(),
+            end_point: second.node.end_position(),
+            message: format!("`{joined}` is split into two arguments, quote it"),
+            rule: &rules::UNQUOTED_SPACES,
+            // Joining two arguments is what was meant, but it changes the call
+            fix: Fix {
+                title: "Quote the path".to_string(),
+                start: first.node.start_position(),
+                end: second.node.end_position(),
+                new_text: format!("\"{joined}\""),
+                safe: false,
+            },
+        });
+    }
+}
+
+fn find_issues_inner(source: &[&str], node: Node, output: &mut Vec<FixableIssue>) {
+    let mut course = node.walk();
+    for child in node.children(&mut course) {
+        match child.kind() {
+            CMakeNodeKinds::IF_COMMAND
+            | CMakeNodeKinds::ELSEIF_COMMAND
+            | CMakeNodeKinds::WHILE_COMMAND => {
+                let mut walk = child.walk();
+                if let Some(argument_list) = child
+                    .children(&mut walk)
+                    .find(|node| node.kind() == CMakeNodeKinds::ARGUMENT_LIST)
+                {
+                    check_condition(&arguments(argument_list, source), output);
+                }
+            }
+            CMakeNodeKinds::NORMAL_COMMAND => {
+                if let Some(argument_list) = child
+                    .child(2)
+                    .filter(|node| node.kind() == CMakeNodeKinds::ARGUMENT_LIST)
+                {
+                    check_spaces(&arguments(argument_list, source), source, output);
+                }
+            }
+            _ => find_issues_inner(source, child, output),
+        }
+    }
+}
+
+/// Find the suspicious unquoted arguments of the file
+pub fn find_issues(source: &[&str], root: Node) -> Vec<FixableIssue> {
+    let mut output = vec![];
+    find_issues_inner(source, root, &mut output);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::Point;
+
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    fn issues_of(source: &str) -> Vec<FixableIssue> {
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        find_issues(&lines, thetree.root_node())
+    }
+
+    #[test]
+    fn test_unquoted_comparison() {
+        let source = r#"if(${CMAKE_BUILD_TYPE} STREQUAL "Debug")
+elseif("${MODE}" STREQUAL Release OR ${A} MATCHES ${B})
+endif()
+if(${ENABLED})
+endif()
+"#;
+        let issues = issues_of(source);
+        assert_eq!(issues.len(), 3);
+        assert_eq!(issues[0].start_point, Point { row: 0, column: 3 });
+        assert_eq!(
+            (issues[0].fix.start, issues[0].fix.end),
+            (Point { row: 0, column: 3 }, Point { row: 0, column: 22 })
+        );
+        assert_eq!(issues[0].fix.new_text, "\"${CMAKE_BUILD_TYPE}\"");
+        assert!(issues[0].fix.safe);
+        assert_eq!(issues[1].fix.new_text, "\"${A}\"");
+        assert_eq!(issues[2].fix.new_text, "\"${B}\"");
+    }
+
+    #[test]
+    fn test_unquoted_spaces() {
+        let source = r#"include_directories(C:/Program Files/Sdk/include)
+set(SDK_DIR /opt/my\ sdk)
+add_executable(app ${SRC_DIR}/main.cpp Helper.cpp)
+install(DIRECTORY ${DIR}/include DESTINATION include)
+"#;
+        let issues = issues_of(source);
+        assert_eq!(issues.len(), 2);
+        assert_eq!(
+            issues[0].fix.new_text,
+            "\"C:/Program Files/Sdk/include\"".to_string()
+        );
+        assert!(!issues[0].fix.safe);
+        assert_eq!(issues[1].fix.new_text, "\"/opt/my sdk\"".to_string());
+        assert!(issues[1].fix.safe);
+    }
+}
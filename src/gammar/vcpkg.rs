@@ -0,0 +1,51 @@
+//! In manifest mode, vcpkg only installs the ports declared by `vcpkg.json`. A
+//! package found from another port is there by chance, as a dependency of a
+//! declared one.
+
+use tree_sitter::Node;
+
+use super::ErrorInformation;
+use super::calls::{arguments, commands, error};
+use crate::rules;
+use crate::utils::module_path;
+use crate::vcpkg::{self, PackageUse};
+
+/// The `find_package()` calls of packages installed from a port the manifest
+/// of the project does not declare
+pub fn check_vcpkg_dependencies(source: &[&str], root: Node) -> Vec<ErrorInformation> {
+    let Some(declared) = module_path::source_dir().and_then(|dir| vcpkg::declared_ports(&dir))
+    else {
+        return vec![];
+    };
+    let mut calls = vec![];
+    commands(root, source, "find_package", &mut calls);
+    let mut output = vec![];
+    for call in calls {
+        let arguments = arguments(call, source);
+        let Some((package, rest)) = arguments.split_first() else {
+            continue;
+        };
+        let rest: Vec<&str> = rest.iter().map(|argument| argument.text).collect();
+        let package_use = PackageUse {
+            name: package.text.trim_matches('"').to_string(),
+            components: vcpkg::components(&rest)
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            start: package.node.start_position(),
+            end: package.node.end_position(),
+        };
+        let Some(port) = vcpkg::undeclared_port(&package_use, &declared) else {
+            continue;
+        };
+        output.push(error(
+            package.node,
+            format!(
+                "{} is installed by the port {port}, which vcpkg.json does not declare. Add {port} to its dependencies",
+                package.text
+            ),
+            &rules::VCPKG_UNDECLARED_PORT,
+        ));
+    }
+    output
+}
@@ -1,17 +1,29 @@
-use lsp_types::Position;
+mod file_preview;
+mod target_summary;
+
+pub use file_preview::get_file_preview;
+use lsp_types::{Position, Range};
+pub use target_summary::get_target_summary;
 use tower_lsp::lsp_types;
 use tree_sitter::Node;
 
-use crate::fileapi;
 use crate::jump::JUMP_CACHE;
+use crate::utils::cmake_docs::{DocKind, builtin_doc_url, builtin_page, doc_url};
+use crate::utils::deprecated::find_deprecated_command;
+use crate::utils::help_markdown::help_to_markdown;
+use crate::utils::help_rst::read_help;
 #[cfg(unix)]
 use crate::utils::packagepkgconfig::PKG_CONFIG_PACKAGES_WITHKEY;
 #[cfg(unix)]
 use crate::utils::packagepkgconfig::PkgConfig;
 use crate::utils::treehelper::{
-    MESSAGE_STORAGE, PositionType, ToPoint, get_point_string, get_pos_type,
+    MESSAGE_STORAGE, PositionType, ToPoint, get_point_string, get_pos_type, innermost_node,
+    is_in_comment_or_bracket, set_variable_at,
 };
+use crate::utils::variable_families::{VARIABLE_PATTERNS, family_of};
 use crate::utils::{CACHE_CMAKE_PACKAGES_WITHKEYS, CMakePackage, PackageType, get_the_packagename};
+use crate::utils::{cpack, platforms};
+use crate::{CMakeNodeKinds, fileapi};
 
 #[inline]
 #[cfg(unix)]
@@ -45,10 +57,87 @@ PackageVersion: {}
     )
 }
 
+/// A `${NAME}` reference found under the cursor, in byte columns of its line
+#[derive(Debug, PartialEq, Eq)]
+struct VariableRef<'a> {
+    start: usize,
+    end: usize,
+    name: &'a str,
+}
+
+/// Find the innermost `${...}` under `column`, so `"prefix ${FOO} suffix"`
+/// resolves just `FOO` and `${A_${B}}` resolves `B` when hovering it
+fn variable_ref_at(line: &str, column: usize) -> Option<VariableRef<'_>> {
+    let bytes = line.as_bytes();
+    let mut opened = vec![];
+    let mut found: Option<VariableRef> = None;
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'$' && bytes.get(index + 1) == Some(&b'{') {
+            opened.push(index);
+            index += 2;
+            continue;
+        }
+        if bytes[index] == b'}'
+            && let Some(start) = opened.pop()
+        {
+            let end = index + 1;
+            let name = &line[start + 2..index];
+            if start <= column
+                && column < end
+                && !name.is_empty()
+                && !name.contains("${")
+                && found
+                    .as_ref()
+                    .is_none_or(|old| end - start < old.end - old.start)
+            {
+                found = Some(VariableRef { start, end, name });
+            }
+        }
+        index += 1;
+    }
+    found
+}
+
+fn hovered_variable_ref<'a>(
+    location: Position,
+    root: Node,
+    source: &'a str,
+) -> Option<VariableRef<'a>> {
+    if is_in_comment_or_bracket(location.to_point(), root) {
+        return None;
+    }
+    let line = source.lines().nth(location.line as usize)?;
+    variable_ref_at(line, location.character as usize)
+}
+
+/// The range of the variable reference under the cursor, if there is one
+pub fn get_hovered_range(location: Position, root: Node, source: &str) -> Option<Range> {
+    let variable = hovered_variable_ref(location, root, source)?;
+    Some(Range {
+        start: Position {
+            line: location.line,
+            character: variable.start as u32,
+        },
+        end: Position {
+            line: location.line,
+            character: variable.end as u32,
+        },
+    })
+}
+
 /// get the doc for on hover
 pub async fn get_hovered_doc(location: Position, root: Node<'_>, source: &str) -> Option<String> {
+    if let Some(variable) = hovered_variable_ref(location, root, source) {
+        return get_variable_doc(variable.name).await;
+    }
     let current_point = location.to_point();
-    let message = get_point_string(current_point, root, &source.lines().collect())?;
+    // A bracket argument is a literal, its words are not commands nor variables
+    if innermost_node(current_point, root, &[CMakeNodeKinds::BRACKET_ARGUMENT]).is_some() {
+        return None;
+    }
+    let lines: Vec<&str> = source.lines().collect();
+    let message = get_point_string(current_point, root, &lines)?;
     let inner_result = match get_pos_type(current_point, root, source) {
         #[cfg(unix)]
         PositionType::FindPkgConfig => {
@@ -72,18 +161,95 @@ pub async fn get_hovered_doc(location: Position, root: Node<'_>, source: &str) -
             }
             value.map(cmakepackage_document_fmt)
         }
-        _ => {
+        _ => get_platform_doc(message)
+            .or_else(|| get_builtin_doc(message))
+            .or_else(|| get_cpack_doc(message))
+            .or_else(|| get_family_doc(message)),
+    };
+    // A generator in `set(CPACK_GENERATOR ...)`
+    let generator = message.trim_matches('"');
+    if inner_result.is_none()
+        && let Some(doc) = cpack::generator_doc(generator)
+        && set_variable_at(current_point, root, &lines)
+            .is_some_and(|variable| cpack::GENERATOR_VARIABLES.contains(&variable.as_str()))
+    {
+        return Some(format!("CPack generator `{generator}`: {doc}"));
+    }
+    if inner_result.is_some() {
+        return inner_result;
+    }
+    get_cached_doc(message).await
+}
+
+/// The doc of a variable, builtin ones first
+pub async fn get_variable_doc(name: &str) -> Option<String> {
+    match get_platform_doc(name)
+        .or_else(|| get_builtin_doc(name))
+        .or_else(|| get_cpack_doc(name))
+        .or_else(|| get_family_doc(name))
+    {
+        Some(doc) => Some(doc),
+        None => get_cached_doc(name).await,
+    }
+}
+
+/// When a platform variable like `WIN32` is true, then its help page
+fn get_platform_doc(name: &str) -> Option<String> {
+    let doc = platforms::platform_doc(name)?;
+    let help = get_builtin_doc(name).unwrap_or_else(|| {
+        format!(
+            "[Online documentation]({})",
+            doc_url(DocKind::Variable, name)
+        )
+    });
+    Some(format!("Platform variable `{name}`: {doc}.\n\n{help}"))
+}
+
+/// The doc of a CPack variable which cmake does not document as a variable
+fn get_cpack_doc(name: &str) -> Option<String> {
+    let doc = cpack::variable_doc(name)?;
+    Some(format!(
+        "CPack variable `{name}`: {doc}.\n\n[Online documentation]({})",
+        doc_url(DocKind::Module, "CPack")
+    ))
+}
+
+/// The doc of a variable like `CMAKE_CXX_FLAGS_RELEASE`, documented by its family
+fn get_family_doc(name: &str) -> Option<String> {
+    let instance = family_of(&VARIABLE_PATTERNS, name)?;
+    let doc = get_builtin_doc(&instance.pattern)?;
+    Some(format!(
+        "`{name}` is `{}` with {}.
+
+{doc}",
+        instance.pattern,
+        instance.describe_bindings()
+    ))
+}
+
+fn get_builtin_doc(message: &str) -> Option<String> {
+    let page = builtin_page(message);
+    let doc = match page.and_then(|(kind, name)| read_help(kind, name)) {
+        Some(help) => help_to_markdown(&help),
+        None => {
             let mut value = MESSAGE_STORAGE.get(message);
             if value.is_none() {
                 value = MESSAGE_STORAGE.get(&message.to_lowercase());
             }
-            value.map(|context| context.to_string())
+            help_to_markdown(value?)
         }
     };
-    if inner_result.is_some() {
-        return inner_result;
+    let doc = match find_deprecated_command(message) {
+        Some(command) => format!("**Deprecated:** {}.\n\n{doc}", command.note()),
+        None => doc,
+    };
+    match builtin_doc_url(message) {
+        Some(url) => Some(format!("{doc}\n\n[Online documentation]({url})")),
+        None => Some(doc),
     }
+}
 
+async fn get_cached_doc(message: &str) -> Option<String> {
     let jump_cache = JUMP_CACHE.lock().await;
     let cached_info = jump_cache.get(message)?.document_info.clone();
     // use cache_data to show info first
@@ -124,4 +290,99 @@ find_package(bash-completion-fake)
         .unwrap();
         assert_eq!(document, cmakepackage_document_fmt(fake_package));
     }
+
+    #[tokio::test]
+    async fn test_hover_bracket_argument() {
+        let content = "message([[set ${CMAKE_CXX_FLAGS}]])\n";
+        let mut parse = tree_sitter::Parser::new();
+        parse
+            .set_language(&tree_sitter_cmake::LANGUAGE.into())
+            .unwrap();
+        let thetree = parse.parse(content, None).unwrap();
+        for character in [11, 20] {
+            let position = Position { line: 0, character };
+            assert_eq!(
+                get_hovered_doc(position, thetree.root_node(), content).await,
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn test_variable_ref_at() {
+        let line = r#"message("prefix ${FOO} and ${A_${B}} suffix")"#;
+        assert_eq!(
+            variable_ref_at(line, 19),
+            Some(VariableRef {
+                start: 16,
+                end: 22,
+                name: "FOO"
+            })
+        );
+        assert_eq!(variable_ref_at(line, 16).unwrap().name, "FOO");
+        assert_eq!(variable_ref_at(line, 22), None);
+        assert_eq!(variable_ref_at(line, 12), None);
+        assert_eq!(variable_ref_at(line, 33).unwrap().name, "B");
+        // the outer reference cannot be resolved as a single variable
+        assert_eq!(variable_ref_at(line, 29), None);
+        assert_eq!(variable_ref_at("set(A ${B", 8), None);
+    }
+
+    #[tokio::test]
+    async fn test_hover_inside_string() {
+        let content = "message(STATUS \"dir: ${CMAKE_SOURCE_DIR}/x\") # ${CMAKE_SOURCE_DIR}\n";
+        let mut parse = tree_sitter::Parser::new();
+        parse
+            .set_language(&tree_sitter_cmake::LANGUAGE.into())
+            .unwrap();
+        let thetree = parse.parse(content, None).unwrap();
+        let position = Position {
+            line: 0,
+            character: 27,
+        };
+        assert_eq!(
+            get_hovered_range(position, thetree.root_node(), content),
+            Some(Range {
+                start: Position {
+                    line: 0,
+                    character: 21
+                },
+                end: Position {
+                    line: 0,
+                    character: 40
+                },
+            })
+        );
+        let in_comment = Position {
+            line: 0,
+            character: 50,
+        };
+        assert_eq!(
+            get_hovered_range(in_comment, thetree.root_node(), content),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_hover_platform_variable() {
+        let content = "if(WIN32 OR ${APPLE})\nendif()\n";
+        let mut parse = tree_sitter::Parser::new();
+        parse
+            .set_language(&tree_sitter_cmake::LANGUAGE.into())
+            .unwrap();
+        let thetree = parse.parse(content, None).unwrap();
+        for (character, name) in [(5, "WIN32"), (16, "APPLE")] {
+            let document = get_hovered_doc(
+                Position { line: 0, character },
+                thetree.root_node(),
+                content,
+            )
+            .await
+            .unwrap();
+            assert!(
+                document.starts_with(&format!("Platform variable `{name}`: True when")),
+                "{document}"
+            );
+        }
+    }
 }
@@ -0,0 +1,195 @@
+//! Hover on file arguments: where the file is, and what it starts with.
+
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::time::SystemTime;
+
+use tower_lsp::lsp_types::{Position, Range};
+use tree_sitter::Node;
+
+use crate::CMakeNodeKinds;
+use crate::utils::module_path;
+use crate::utils::treehelper::{PositionType, ToPoint, get_enclosing_nodes, get_pos_type};
+
+const PREVIEW_LINES: usize = 20;
+
+fn human_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if size < 1024 {
+        return format!("{size} B");
+    }
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+fn human_age(modified: SystemTime) -> String {
+    let Ok(elapsed) = SystemTime::now().duration_since(modified) else {
+        return "just now".to_string();
+    };
+    let seconds = elapsed.as_secs();
+    let (count, unit) = match seconds {
+        0..60 => return "just now".to_string(),
+        60..3600 => (seconds / 60, "minute"),
+        3600..86400 => (seconds / 3600, "hour"),
+        86400..2592000 => (seconds / 86400, "day"),
+        2592000..31536000 => (seconds / 2592000, "month"),
+        _ => (seconds / 31536000, "year"),
+    };
+    let plural = if count == 1 { "" } else { "s" };
+    format!("{count} {unit}{plural} ago")
+}
+
+/// Language of the fenced block, from the file name
+fn fence_language(path: &Path) -> &'static str {
+    if path
+        .file_name()
+        .is_some_and(|name| name == "CMakeLists.txt")
+    {
+        return "cmake";
+    }
+    match path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+    {
+        "c" | "h" => "c",
+        "cc" | "cpp" | "cxx" | "c++" | "hh" | "hpp" | "hxx" | "h++" | "ipp" | "inl" => "cpp",
+        "cu" | "cuh" => "cuda",
+        "m" | "mm" => "objc",
+        "cmake" => "cmake",
+        "py" => "python",
+        "sh" => "sh",
+        "json" => "json",
+        "rs" => "rust",
+        "f" | "f90" | "f95" => "fortran",
+        "s" | "asm" => "asm",
+        _ => "",
+    }
+}
+
+fn preview_lines(path: &Path) -> Option<Vec<String>> {
+    let file = std::fs::File::open(path).ok()?;
+    BufReader::new(file)
+        .lines()
+        .take(PREVIEW_LINES)
+        .collect::<std::io::Result<Vec<String>>>()
+        .ok()
+}
+
+fn describe(path: &Path) -> String {
+    let mut document = format!("`{}`\n\n", path.display());
+    let Ok(metadata) = path.metadata() else {
+        document.push_str("does not exist");
+        return document;
+    };
+    if metadata.is_dir() {
+        document.push_str("directory");
+        return document;
+    }
+    document.push_str(&human_size(metadata.len()));
+    if let Ok(modified) = metadata.modified() {
+        document.push_str(&format!(", modified {}", human_age(modified)));
+    }
+    let Some(lines) = preview_lines(path) else {
+        document.push_str(", binary file");
+        return document;
+    };
+    let body = lines.join("\n");
+    // A longer fence keeps markdown files from closing the block
+    let fence = if body.contains("```") { "````" } else { "```" };
+    document.push_str(&format!(
+        "\n\n{fence}{}\n{body}\n{fence}",
+        fence_language(path)
+    ));
+    document
+}
+
+/// Describe the file named by the argument under the cursor, with its range
+pub fn get_file_preview(
+    local_path: &Path,
+    location: Position,
+    root: Node,
+    source: &str,
+) -> Option<(String, Range)> {
+    let current_dir = local_path.parent()?;
+    let point = location.to_point();
+    let argument = get_enclosing_nodes(point, root)
+        .into_iter()
+        .find(|node| node.kind() == CMakeNodeKinds::ARGUMENT)?;
+    if argument.start_position().row != argument.end_position().row {
+        return None;
+    }
+    let line = source.lines().nth(argument.start_position().row)?;
+    let text = line.get(argument.start_position().column..argument.end_position().column)?;
+    let path = module_path::resolve_path(text, current_dir)?;
+    // Only the commands taking files tell us a missing one is a mistake
+    let expects_file = matches!(
+        get_pos_type(point, root, source),
+        PositionType::SourceFile | PositionType::AnyFile
+    );
+    let looks_like_file = text.contains('/') || Path::new(text).extension().is_some();
+    if !(path.is_file() || (expects_file && looks_like_file)) {
+        return None;
+    }
+    let range = Range {
+        start: Position {
+            line: location.line,
+            character: argument.start_position().column as u32,
+        },
+        end: Position {
+            line: location.line,
+            character: argument.end_position().column as u32,
+        },
+    };
+    Some((describe(&path), range))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    #[test]
+    fn test_human_size() {
+        assert_eq!(human_size(12), "12 B");
+        assert_eq!(human_size(1536), "1.5 KiB");
+        assert_eq!(human_size(3 * 1024 * 1024), "3.0 MiB");
+    }
+
+    #[test]
+    fn test_file_preview() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("main.cpp");
+        std::fs::write(&main, "int main() {\n    return 0;\n}\n").unwrap();
+        let local_path = dir.path().join("CMakeLists.txt");
+        let source = "add_executable(app main.cpp \"${CMAKE_CURRENT_SOURCE_DIR}/missing.cpp\")\n";
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let at = |character| {
+            get_file_preview(
+                &local_path,
+                Position { line: 0, character },
+                thetree.root_node(),
+                source,
+            )
+        };
+
+        let (document, range) = at(22).unwrap();
+        assert!(document.starts_with(&format!("`{}`", main.display())));
+        assert!(document.contains("```cpp\nint main() {\n    return 0;\n}\n```"));
+        assert_eq!(range.start.character, 19);
+        assert_eq!(range.end.character, 27);
+
+        let (document, _) = at(60).unwrap();
+        assert!(document.ends_with("does not exist"));
+
+        // The target name is not a file
+        assert!(at(16).is_none());
+    }
+}
@@ -0,0 +1,186 @@
+//! Hover on a target name: where it is declared, the target it is an alias
+//! of, and what the calls of the whole project add to it.
+
+use std::path::{Path, PathBuf};
+
+use tower_lsp::lsp_types::{Position, Range};
+use tree_sitter::Node;
+
+use crate::CMakeNodeKinds;
+use crate::utils::module_path;
+use crate::utils::targets::{self, Target, TargetKind, TargetProperty, TargetUsage};
+use crate::utils::treehelper::{ToPoint, get_enclosing_nodes};
+
+const PROPERTIES: &[(TargetProperty, &str)] = &[
+    (TargetProperty::Sources, "Sources"),
+    (TargetProperty::LinkLibraries, "Link libraries"),
+    (TargetProperty::IncludeDirectories, "Include directories"),
+    (TargetProperty::CompileDefinitions, "Compile definitions"),
+];
+
+fn kind_name(kind: TargetKind) -> &'static str {
+    match kind {
+        TargetKind::Executable => "Executable",
+        TargetKind::Library => "Library",
+        TargetKind::Interface => "Interface library",
+        TargetKind::Custom => "Custom target",
+    }
+}
+
+/// The path relative to the top level source directory, when it is inside
+fn display_path(path: &Path) -> String {
+    module_path::source_dir()
+        .and_then(|dir| path.strip_prefix(dir).ok().map(Path::to_path_buf))
+        .unwrap_or_else(|| path.to_path_buf())
+        .display()
+        .to_string()
+}
+
+fn summary(name: &str, project: &[(PathBuf, Target)], usages: &[(PathBuf, TargetUsage)]) -> String {
+    let mut document = format!("### Target `{name}`");
+    for (path, target) in project.iter().filter(|(_, target)| target.name == name) {
+        document.push_str(&format!(
+            "\n\n{} declared at `{}:{}`{}",
+            kind_name(target.kind),
+            display_path(path),
+            target.position.row + 1,
+            if target.conditional {
+                ", in a branch of if()"
+            } else {
+                ""
+            }
+        ));
+        if target.alias_of.is_none() {
+            continue;
+        }
+        let real = targets::resolve_alias(name, project);
+        document.push_str(&format!("\n\nAlias of `{real}`"));
+        if let Some((path, target)) = project
+            .iter()
+            .find(|(_, target)| target.name == real && target.alias_of.is_none())
+        {
+            document.push_str(&format!(
+                " (defined at `{}:{}`)",
+                display_path(path),
+                target.position.row + 1
+            ));
+        }
+    }
+    // What is added to the real target is added to its aliases
+    let family = targets::alias_family(name, project);
+    for (property, title) in PROPERTIES {
+        let mut items: Vec<&str> = vec![];
+        for (_, usage) in usages {
+            if !family.contains(&usage.target.as_str()) || usage.property != *property {
+                continue;
+            }
+            for item in &usage.items {
+                if !items.contains(&item.as_str()) {
+                    items.push(item);
+                }
+            }
+        }
+        if items.is_empty() {
+            continue;
+        }
+        document.push_str(&format!("\n\n**{title}**\n"));
+        for item in items {
+            document.push_str(&format!("\n- `{item}`"));
+        }
+    }
+    document
+}
+
+/// Summarize the target named by the argument under the cursor, with its range
+pub fn get_target_summary(
+    local_path: &Path,
+    location: Position,
+    root: Node,
+    source: &str,
+) -> Option<(String, Range)> {
+    let argument = get_enclosing_nodes(location.to_point(), root)
+        .into_iter()
+        .find(|node| node.kind() == CMakeNodeKinds::ARGUMENT)?;
+    if argument.start_position().row != argument.end_position().row {
+        return None;
+    }
+    let line = source.lines().nth(argument.start_position().row)?;
+    let name =
+        line[argument.start_position().column..argument.end_position().column].trim_matches('"');
+
+    // The index may not know about the last changes of the file being edited
+    let lines: Vec<&str> = source.lines().collect();
+    let project = targets::project_targets(local_path, &lines, root);
+    if !project.iter().any(|(_, target)| target.name == name) {
+        return None;
+    }
+    let usages: Vec<(PathBuf, TargetUsage)> = targets::declared_usages(&lines, root)
+        .into_iter()
+        .map(|usage| (local_path.to_path_buf(), usage))
+        .chain(
+            targets::all_usages()
+                .into_iter()
+                .filter(|(path, _)| path != local_path),
+        )
+        .collect();
+    let range = Range {
+        start: Position {
+            line: location.line,
+            character: argument.start_position().column as u32,
+        },
+        end: Position {
+            line: location.line,
+            character: argument.end_position().column as u32,
+        },
+    };
+    Some((summary(name, &project, &usages), range))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    #[test]
+    fn test_target_summary() {
+        let source = r#"add_library(summary_core STATIC core.cpp)
+target_include_directories(summary_core PUBLIC include)
+target_link_libraries(summary_core PRIVATE m)
+add_executable(summary_app main.cpp)
+target_link_libraries(summary_app PRIVATE summary_core)
+add_library(summary::core ALIAS summary_core)
+"#;
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let (document, range) = get_target_summary(
+            Path::new("/tmp/summary/CMakeLists.txt"),
+            Position {
+                line: 4,
+                character: 45,
+            },
+            thetree.root_node(),
+            source,
+        )
+        .unwrap();
+        assert_eq!(range.start.character, 42);
+        assert_eq!(
+            document,
+            "### Target `summary_core`\n\nLibrary declared at `/tmp/summary/CMakeLists.txt:1`\n\n**Sources**\n\n- `core.cpp`\n\n**Link libraries**\n\n- `m`\n\n**Include directories**\n\n- `include`"
+        );
+
+        let (document, _) = get_target_summary(
+            Path::new("/tmp/summary/CMakeLists.txt"),
+            Position {
+                line: 5,
+                character: 16,
+            },
+            thetree.root_node(),
+            source,
+        )
+        .unwrap();
+        assert!(document.starts_with(
+            "### Target `summary::core`\n\nLibrary declared at `/tmp/summary/CMakeLists.txt:6`\n\nAlias of `summary_core` (defined at `/tmp/summary/CMakeLists.txt:1`)\n\n**Sources**\n\n- `core.cpp`"
+        ));
+    }
+}
@@ -0,0 +1,55 @@
+//! The `neocmake/indexed` notification, sent once the workspace is indexed.
+//!
+//! Editor extensions can show it in a status bar item, so the users know when
+//! the completion, the lints and the navigation cover the whole project.
+
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::notification::Notification;
+
+pub enum Indexed {}
+
+impl Notification for Indexed {
+    type Params = IndexStatistics;
+    const METHOD: &'static str = "neocmake/indexed";
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexStatistics {
+    /// The CMake files scanned, from the top level CMakeLists.txt
+    pub files: usize,
+    /// The targets declared by the project
+    pub targets: usize,
+    /// The functions and macros defined by the project
+    pub functions: usize,
+    /// The diagnostics published for the open and the checked files
+    pub diagnostics: usize,
+    /// The time taken by the indexing, in milliseconds
+    pub elapsed_ms: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_statistics_json() {
+        let statistics = IndexStatistics {
+            files: 12,
+            targets: 4,
+            functions: 3,
+            diagnostics: 1,
+            elapsed_ms: 250,
+        };
+        assert_eq!(
+            serde_json::to_value(statistics).unwrap(),
+            serde_json::json!({
+                "files": 12,
+                "targets": 4,
+                "functions": 3,
+                "diagnostics": 1,
+                "elapsedMs": 250,
+            })
+        );
+    }
+}
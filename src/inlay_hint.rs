@@ -0,0 +1,196 @@
+//! Inlay hints after `option()` and `set(... CACHE ...)` with the value the
+//! build directory is configured with, read from the CMake cache.
+//!
+//! The default written in the call is shown next to the value when they differ,
+//! so an option changed with `-D` or `ccmake` stands out.
+
+use std::collections::HashMap;
+
+use tower_lsp::lsp_types::{InlayHint, InlayHintLabel, InlayHintTooltip, Range};
+use tree_sitter::Node;
+
+use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+use crate::utils::treehelper::{ToPosition, command_arguments};
+use crate::{CMakeNodeKinds, fileapi};
+
+/// The longest value shown in a hint, the full value is in the tooltip
+const MAX_VALUE_LENGTH: usize = 40;
+
+/// The arguments of `command` on one line, without their quotes
+fn arguments<'a>(command: Node, source: &'a str) -> Vec<&'a str> {
+    command_arguments(command)
+        .into_iter()
+        .map(|node| source[node.byte_range()].trim_matches('"'))
+        .collect()
+}
+
+/// The variable of a cache entry declared by `command`, with its default
+fn cache_declaration(command: Node, source: &str) -> Option<(String, String)> {
+    let name = source[command.child(0)?.byte_range()].to_lowercase();
+    let arguments = arguments(command, source);
+    let (variable, rest) = arguments.split_first()?;
+    match name.as_str() {
+        "option" => Some((
+            variable.to_string(),
+            rest.get(1).copied().unwrap_or("OFF").to_string(),
+        )),
+        "set" => {
+            let cache = rest.iter().position(|argument| *argument == "CACHE")?;
+            Some((variable.to_string(), rest[..cache].join(";")))
+        }
+        _ => None,
+    }
+}
+
+/// Whether the configured value is the default, the booleans of cmake in any case
+fn is_default(value: &str, default: &str) -> bool {
+    // A default computed when configuring cannot be compared
+    value == default || default.contains("${") || value.eq_ignore_ascii_case(default)
+}
+
+fn shorten(value: &str) -> String {
+    if value.chars().count() <= MAX_VALUE_LENGTH {
+        return value.to_string();
+    }
+    let short: String = value.chars().take(MAX_VALUE_LENGTH).collect();
+    format!("{short}…")
+}
+
+fn cache_hints_inner(
+    node: Node,
+    source: &str,
+    cache: &HashMap<String, String>,
+    hints: &mut Vec<InlayHint>,
+) {
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if child.kind() != CMakeNodeKinds::NORMAL_COMMAND {
+            cache_hints_inner(child, source, cache, hints);
+            continue;
+        }
+        let Some((variable, default)) = cache_declaration(child, source) else {
+            continue;
+        };
+        let Some(value) = cache.get(&variable) else {
+            continue;
+        };
+        let label = if is_default(value, &default) {
+            format!("= {}", shorten(value))
+        } else {
+            format!("= {} (default {})", shorten(value), shorten(&default))
+        };
+        hints.push(InlayHint {
+            position: child.end_position().to_position(),
+            label: InlayHintLabel::String(label),
+            kind: None,
+            text_edits: None,
+            tooltip: Some(InlayHintTooltip::String(format!(
+                "{variable} is {value} in CMakeCache.txt"
+            ))),
+            padding_left: Some(true),
+            padding_right: None,
+            data: None,
+        });
+    }
+}
+
+/// The hints of `source` with the values of `cache`
+fn cache_hints(source: &str, cache: &HashMap<String, String>) -> Vec<InlayHint> {
+    let mut parse = tree_sitter::Parser::new();
+    parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+    let Some(tree) = parse.parse(source, None) else {
+        return vec![];
+    };
+    let mut hints = vec![];
+    cache_hints_inner(tree.root_node(), source, cache, &mut hints);
+    hints
+}
+
+fn cache_declarations_inner(node: Node, source: &str, declarations: &mut Vec<(String, String)>) {
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if child.kind() != CMakeNodeKinds::NORMAL_COMMAND {
+            cache_declarations_inner(child, source, declarations);
+        } else if let Some(declaration) = cache_declaration(child, source) {
+            declarations.push(declaration);
+        }
+    }
+}
+
+/// The cache variables declared by `source`, with their default
+pub fn cache_declarations(source: &str) -> Vec<(String, String)> {
+    let mut parse = tree_sitter::Parser::new();
+    parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+    let Some(tree) = parse.parse(source, None) else {
+        return vec![];
+    };
+    let mut declarations = vec![];
+    cache_declarations_inner(tree.root_node(), source, &mut declarations);
+    declarations
+}
+
+/// The configured values of the cache variables declared in `range`, when a
+/// build directory has been found
+pub fn inlay_hints(source: &str, range: Range) -> Option<Vec<InlayHint>> {
+    let cache = fileapi::get_entries_data()?;
+    Some(
+        cache_hints(source, &cache)
+            .into_iter()
+            .filter(|hint| range.start <= hint.position && hint.position <= range.end)
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_hints() {
+        let source = r#"option(BUILD_TESTS "Build the tests" ON)
+option(WITH_DOCS "Build the docs")
+if(UNIX)
+    set(INSTALL_PREFIX "/usr" CACHE PATH "Where to install")
+endif()
+set(LOCAL_ONLY 1)
+set(OUTPUT_DIR ${CMAKE_BINARY_DIR}/out CACHE PATH "")
+"#;
+        let cache: HashMap<String, String> = [
+            ("BUILD_TESTS", "ON"),
+            ("WITH_DOCS", "ON"),
+            ("INSTALL_PREFIX", "/opt"),
+            ("LOCAL_ONLY", "2"),
+            ("OUTPUT_DIR", "/build/out"),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+        let declarations = cache_declarations(source);
+        let declarations: Vec<&str> = declarations
+            .iter()
+            .map(|(variable, _)| variable.as_str())
+            .collect();
+        assert_eq!(
+            declarations,
+            vec!["BUILD_TESTS", "WITH_DOCS", "INSTALL_PREFIX", "OUTPUT_DIR"]
+        );
+        let hints: Vec<(u32, String)> = cache_hints(source, &cache)
+            .into_iter()
+            .map(|hint| {
+                let InlayHintLabel::String(label) = hint.label else {
+                    unreachable!()
+                };
+                (hint.position.line, label)
+            })
+            .collect();
+        assert_eq!(
+            hints,
+            vec![
+                (0, "= ON".to_string()),
+                (1, "= ON (default OFF)".to_string()),
+                (3, "= /opt (default /usr)".to_string()),
+                (6, "= /build/out".to_string()),
+            ]
+        );
+    }
+}
@@ -1,6 +1,6 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, LazyLock};
+use std::sync::{Arc, LazyLock, PoisonError, RwLock};
 
 use dashmap::DashMap;
 use tokio::sync::Mutex;
@@ -8,6 +8,7 @@ use tower_lsp::lsp_types::{Location, MessageType, Position, Range, Uri};
 
 use crate::languageserver::get_or_update_buffer_contents;
 use crate::scansubs::TREE_CMAKE_MAP;
+use crate::utils::document::Document;
 use crate::utils::remove_quotation_and_replace_placeholders;
 /// provide go to definition
 use crate::{
@@ -15,14 +16,16 @@ use crate::{
     consts::TREESITTER_CMAKE_LANGUAGE,
     scansubs::TREE_MAP,
     utils::{
-        CACHE_CMAKE_PACKAGES_WITHKEYS, LineCommentTmp, gen_module_pattern, get_the_packagename,
-        include_is_module, replace_placeholders,
+        CACHE_CMAKE_PACKAGES_WITHKEYS, LineCommentTmp, function_doc, get_the_packagename,
+        replace_placeholders, resolve_include,
         treehelper::{ToPoint, ToPosition, get_point_string},
     },
 };
 mod findpackage;
 mod include;
+mod sourcefile;
 mod subdirectory;
+pub mod target;
 use tree_sitter::Node;
 
 use crate::utils::treehelper::{PositionType, get_pos_type};
@@ -40,6 +43,11 @@ pub type JumpKV = HashMap<String, JumpCacheUnit>;
 pub static JUMP_CACHE: LazyLock<Arc<Mutex<JumpKV>>> =
     LazyLock::new(|| Arc::new(Mutex::new(HashMap::new())));
 
+/// The names of the variables in [`JUMP_CACHE`], copied for the lints, which
+/// run outside of the runtime and cannot wait for its lock
+pub static WORKSPACE_VARIABLES: LazyLock<RwLock<HashSet<String>>> =
+    LazyLock::new(|| RwLock::new(HashSet::new()));
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct CacheDataUnit {
     key: String,
@@ -63,6 +71,9 @@ pub async fn update_cache<P: AsRef<Path>>(path: P, context: &str) -> Option<()>
         true,
     )?;
     let mut cache = JUMP_CACHE.lock().await;
+    let mut variables = WORKSPACE_VARIABLES
+        .write()
+        .unwrap_or_else(PoisonError::into_inner);
     for CacheDataUnit {
         key,
         location,
@@ -70,6 +81,11 @@ pub async fn update_cache<P: AsRef<Path>>(path: P, context: &str) -> Option<()>
         is_function,
     } in result_data
     {
+        if is_function {
+            variables.remove(&key);
+        } else {
+            variables.insert(key.clone());
+        }
         cache.insert(
             key,
             JumpCacheUnit {
@@ -91,7 +107,7 @@ pub struct ReferenceInfo {
 pub async fn get_cached_def<P: AsRef<Path>>(
     path: P,
     key: &str,
-    documents: &DashMap<Uri, String>,
+    documents: &DashMap<Uri, Document>,
 ) -> Option<ReferenceInfo> {
     let mut path = path.as_ref().to_path_buf();
 
@@ -161,6 +177,17 @@ pub async fn get_cached_def<P: AsRef<Path>>(
     None
 }
 
+/// Find where `name` is defined, looking at `path` and the files including it
+pub async fn find_cached_def<P: AsRef<Path>>(
+    path: P,
+    name: &str,
+    documents: &DashMap<Uri, Document>,
+) -> Option<Location> {
+    get_cached_def(path, name, documents)
+        .await
+        .map(|info| info.loc)
+}
+
 /// find the definition
 pub async fn godef<P: AsRef<Path>>(
     location: Position,
@@ -169,7 +196,7 @@ pub async fn godef<P: AsRef<Path>>(
     client: &tower_lsp::Client,
     is_jump: bool,
     just_var_or_fun: bool,
-    documents: &DashMap<Uri, String>,
+    documents: &DashMap<Uri, Document>,
 ) -> Option<Vec<Location>> {
     let current_point = location.to_point();
     let locations = godef_inner(
@@ -195,7 +222,7 @@ async fn godef_inner<P: AsRef<Path>>(
     originuri: P,
     is_jump: bool,
     just_var_or_fun: bool,
-    documents: &DashMap<Uri, String>,
+    documents: &DashMap<Uri, Document>,
 ) -> Option<Vec<Location>> {
     let mut parse = tree_sitter::Parser::new();
     parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
@@ -216,6 +243,21 @@ async fn godef_inner<P: AsRef<Path>>(
         return None;
     }
 
+    // A target is looked for before a variable or a package of the same name
+    if !just_var_or_fun
+        && let Some(locations) = target::cmptarget(
+            originuri.as_ref(),
+            location.to_position(),
+            source,
+            &tree,
+            is_jump,
+            documents,
+        )
+        .await
+    {
+        return Some(locations);
+    }
+
     match jumptype {
         PositionType::VarOrFun
         | PositionType::ArgumentOrList
@@ -257,8 +299,13 @@ async fn godef_inner<P: AsRef<Path>>(
             let fixed_url = replace_placeholders(tofind)?;
             subdirectory::cmpsubdirectory(originuri, &fixed_url)
         }
-        // Path completion types - no jump behavior
-        PositionType::SourceFile | PositionType::AnyFile | PositionType::Directory => None,
+        PositionType::SourceFile | PositionType::AnyFile => sourcefile::cmpsourcefile(
+            originuri,
+            location,
+            tree.root_node(),
+            &source.lines().collect::<Vec<_>>(),
+        ),
+        PositionType::Directory => None,
     }
 }
 
@@ -422,8 +469,8 @@ fn getsubdef<P: AsRef<Path>>(
                 };
                 let mut document_info = format!("defined function\nfrom: {}", local_path.display());
 
-                if line_comment_tmp.is_node_comment(h) {
-                    document_info = format!("{}\n\n{}", document_info, line_comment_tmp.comment());
+                if let Some(doc) = function_doc::leading_doc(source, child) {
+                    document_info = format!("{document_info}\n\n{doc}");
                 }
                 defs.push(CacheDataUnit {
                     key: name.to_string(),
@@ -455,8 +502,8 @@ fn getsubdef<P: AsRef<Path>>(
                 };
                 let mut document_info = format!("defined macro\nfrom: {}", local_path.display());
 
-                if line_comment_tmp.is_node_comment(h) {
-                    document_info = format!("{}\n\n{}", document_info, line_comment_tmp.comment());
+                if let Some(doc) = function_doc::leading_doc(source, child) {
+                    document_info = format!("{document_info}\n\n{doc}");
                 }
                 defs.push(CacheDataUnit {
                     key: name.to_string(),
@@ -498,25 +545,12 @@ fn getsubdef<P: AsRef<Path>>(
                         let Some(name) = remove_quotation_and_replace_placeholders(name) else {
                             continue;
                         };
-                        let (is_builtin, subpath) = {
-                            if !include_is_module(&name) {
-                                (false, local_path.parent().unwrap().join(name))
-                            } else {
-                                // NOTE: Module file now is not works on windows
-                                // Maybe also not works on android, please make pr for me
-                                let Some(glob_pattern) = gen_module_pattern(&name) else {
-                                    continue;
-                                };
-                                let Some(path) = glob::glob(&glob_pattern)
-                                    .into_iter()
-                                    .flatten()
-                                    .flatten()
-                                    .next()
-                                else {
-                                    continue;
-                                };
-                                (true, path)
-                            }
+                        // NOTE: Module file now is not works on windows
+                        // Maybe also not works on android, please make pr for me
+                        let Some((subpath, is_builtin)) =
+                            resolve_include(&name, local_path.parent().unwrap())
+                        else {
+                            continue;
                         };
                         if include_files.contains(&subpath) {
                             continue;
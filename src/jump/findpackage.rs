@@ -2,9 +2,28 @@ use lsp_types::Uri;
 use tower_lsp::lsp_types;
 
 use super::Location;
-use crate::utils::CACHE_CMAKE_PACKAGES_WITHKEYS;
+use crate::utils::{CACHE_CMAKE_PACKAGES_WITHKEYS, cmake_modules, module_path};
 
 pub(super) fn cmpfindpackage(input: &str) -> Option<Vec<Location>> {
+    // Find modules in CMAKE_MODULE_PATH are searched first, then the ones of
+    // cmake, like cmake does
+    if let Some(find_module) = module_path::find_project_package(input)
+        .or_else(|| cmake_modules::builtin_find_module(input))
+    {
+        return Some(vec![Location {
+            range: lsp_types::Range {
+                start: lsp_types::Position {
+                    line: 0,
+                    character: 0,
+                },
+                end: lsp_types::Position {
+                    line: 0,
+                    character: 0,
+                },
+            },
+            uri: Uri::from_file_path(find_module).unwrap(),
+        }]);
+    }
     CACHE_CMAKE_PACKAGES_WITHKEYS.get(input).map(|context| {
         context
             .tojump
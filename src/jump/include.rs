@@ -6,23 +6,13 @@ use std::sync::{Arc, LazyLock, Mutex};
 use lsp_types::Uri;
 use tower_lsp::lsp_types;
 
-use super::{CacheDataUnit, Location, gen_module_pattern, getsubdef};
+use super::{CacheDataUnit, Location, getsubdef};
 use crate::consts::TREESITTER_CMAKE_LANGUAGE;
-use crate::utils::include_is_module;
+use crate::utils::resolve_include;
 use crate::utils::treehelper::PositionType;
 
 pub(super) fn cmpinclude<P: AsRef<Path>>(localpath: P, subpath: &str) -> Option<Vec<Location>> {
-    let target = if !include_is_module(subpath) {
-        let root_dir = localpath.as_ref().parent()?;
-        root_dir.join(subpath)
-    } else {
-        let glob_pattern = gen_module_pattern(subpath)?;
-        glob::glob(glob_pattern.as_str())
-            .into_iter()
-            .flatten()
-            .flatten()
-            .next()?
-    };
+    let (target, _) = resolve_include(subpath, localpath.as_ref().parent()?)?;
 
     if target.exists() {
         Some(vec![Location {
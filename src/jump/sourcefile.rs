@@ -0,0 +1,85 @@
+use std::path::Path;
+
+use lsp_types::Uri;
+use tower_lsp::lsp_types;
+use tree_sitter::{Node, Point};
+
+use super::Location;
+use crate::CMakeNodeKinds;
+use crate::utils::module_path;
+use crate::utils::treehelper::get_enclosing_nodes;
+
+/// Jump from a file argument, like `src/main.cpp` in `add_executable`, to the file.
+/// The whole argument is resolved, so `${CMAKE_CURRENT_SOURCE_DIR}/main.cpp` works too.
+pub(super) fn cmpsourcefile<P: AsRef<Path>>(
+    localpath: P,
+    location: Point,
+    root: Node,
+    source: &[&str],
+) -> Option<Vec<Location>> {
+    let argument = get_enclosing_nodes(location, root)
+        .into_iter()
+        .find(|node| node.kind() == CMakeNodeKinds::ARGUMENT)?;
+    if argument.start_position().row != argument.end_position().row {
+        return None;
+    }
+    let h = argument.start_position().row;
+    let text = &source[h][argument.start_position().column..argument.end_position().column];
+    let target = module_path::resolve_path(text, localpath.as_ref().parent()?)?;
+    if !target.is_file() {
+        return None;
+    }
+    Some(vec![Location {
+        range: lsp_types::Range {
+            start: lsp_types::Position {
+                line: 0,
+                character: 0,
+            },
+            end: lsp_types::Position {
+                line: 0,
+                character: 0,
+            },
+        },
+        uri: Uri::from_file_path(target).unwrap(),
+    }])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    #[test]
+    fn test_cmp_sourcefile() {
+        let dir = tempdir().unwrap();
+        let top_cmake = dir.path().join("CMakeLists.txt");
+        let src = dir.path().join("src");
+        fs::create_dir_all(&src).unwrap();
+        let main = src.join("main.cpp");
+        fs::write(&main, "int main() {}\n").unwrap();
+
+        let source = "add_executable(app src/main.cpp \"${CMAKE_CURRENT_SOURCE_DIR}/src/main.cpp\" missing.cpp)";
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let at = |column| {
+            cmpsourcefile(
+                &top_cmake,
+                Point { row: 0, column },
+                thetree.root_node(),
+                &lines,
+            )
+        };
+
+        let expected = Uri::from_file_path(&main).unwrap();
+        assert_eq!(at(22).unwrap()[0].uri, expected);
+        assert_eq!(at(70).unwrap()[0].uri, expected);
+        assert_eq!(at(80), None);
+        assert_eq!(at(16), None);
+    }
+}
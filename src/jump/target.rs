@@ -0,0 +1,491 @@
+//! Go to the declaration of a target, and find its references, following
+//! its aliases: the arguments of the commands taking targets, quoted or not,
+//! and the generator expressions reading one, like `$<TARGET_FILE:app>`.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use dashmap::DashMap;
+use tower_lsp::lsp_types::{Position, Range, Uri};
+use tree_sitter::{Node, Tree};
+
+use super::Location;
+use crate::CMakeNodeKinds;
+use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+use crate::lint;
+use crate::utils::document::Document;
+use crate::utils::module_path;
+use crate::utils::targets::{self, Target};
+use crate::utils::treehelper::{ToPoint, ToPosition, get_enclosing_nodes};
+
+/// The keywords of `install(TARGETS ...)` and `export(TARGETS ...)` ending the
+/// list of targets
+const TARGETS_END_KEYWORDS: &[&str] = &[
+    "EXPORT",
+    "ARCHIVE",
+    "LIBRARY",
+    "RUNTIME",
+    "OBJECTS",
+    "FRAMEWORK",
+    "BUNDLE",
+    "PRIVATE_HEADER",
+    "PUBLIC_HEADER",
+    "RESOURCE",
+    "FILE_SET",
+    "DESTINATION",
+    "INCLUDES",
+    "RUNTIME_DEPENDENCIES",
+    "RUNTIME_DEPENDENCY_SET",
+    "NAMESPACE",
+    "FILE",
+    "APPEND",
+];
+
+/// The keywords of `add_custom_command()` and `add_custom_target()` ending the
+/// list of dependencies
+const DEPENDS_END_KEYWORDS: &[&str] = &[
+    "COMMAND",
+    "OUTPUT",
+    "BYPRODUCTS",
+    "WORKING_DIRECTORY",
+    "COMMENT",
+    "SOURCES",
+    "VERBATIM",
+    "USES_TERMINAL",
+    "COMMAND_EXPAND_LISTS",
+    "JOB_POOL",
+    "JOB_SERVER_AWARE",
+    "ALL",
+    "MAIN_DEPENDENCY",
+    "IMPLICIT_DEPENDS",
+    "DEPFILE",
+    "APPEND",
+];
+
+/// The keywords of `target_link_libraries()`, the other arguments are items
+/// which may be targets
+const LINK_KEYWORDS: &[&str] = &[
+    "PUBLIC",
+    "PRIVATE",
+    "INTERFACE",
+    "LINK_PUBLIC",
+    "LINK_PRIVATE",
+    "LINK_INTERFACE_LIBRARIES",
+    "debug",
+    "optimized",
+    "general",
+];
+
+struct Argument<'a> {
+    node: Node<'a>,
+    /// The text of the argument, with its quotes
+    text: &'a str,
+}
+
+fn call_arguments<'a>(command: Node<'a>, source: &'a str) -> Vec<Argument<'a>> {
+    let mut walk = command.walk();
+    let Some(list) = command
+        .children(&mut walk)
+        .find(|node| node.kind() == CMakeNodeKinds::ARGUMENT_LIST)
+    else {
+        return vec![];
+    };
+    let mut walk = list.walk();
+    list.children(&mut walk)
+        .filter(|node| node.kind() == CMakeNodeKinds::ARGUMENT)
+        .filter_map(|node| {
+            Some(Argument {
+                node,
+                text: source.get(node.byte_range())?,
+            })
+        })
+        .collect()
+}
+
+/// The indexes of the arguments of the call `command` which are a whole target
+/// name
+fn target_arguments(command: &str, arguments: &[Argument]) -> Vec<usize> {
+    let texts: Vec<&str> = arguments
+        .iter()
+        .map(|argument| argument.text.trim_matches('"'))
+        .collect();
+    let after = |keyword: &str| -> Vec<usize> {
+        texts
+            .iter()
+            .enumerate()
+            .filter(|(index, text)| **text == keyword && index + 1 < texts.len())
+            .map(|(index, _)| index + 1)
+            .collect()
+    };
+    let until = |start: usize, ends: &[&str]| -> Vec<usize> {
+        (start..texts.len())
+            .take_while(|index| !ends.contains(&texts[*index]))
+            .collect()
+    };
+    match command {
+        "add_executable" | "add_library" => [vec![0], after("ALIAS")].concat(),
+        "add_dependencies" => (0..texts.len()).collect(),
+        "add_custom_target" | "add_custom_command" => {
+            let mut indexes = [vec![0], after("TARGET"), after("COMMAND")].concat();
+            if let Some(depends) = texts.iter().position(|text| *text == "DEPENDS") {
+                indexes.extend(until(depends + 1, DEPENDS_END_KEYWORDS));
+            }
+            indexes
+        }
+        "add_test" => after("COMMAND"),
+        "set_target_properties" => until(0, &["PROPERTIES"]),
+        "get_target_property" => vec![1],
+        "set_property" if texts.first() == Some(&"TARGET") => {
+            until(1, &["APPEND", "APPEND_STRING", "PROPERTY"])
+        }
+        "install" | "export" if texts.first() == Some(&"TARGETS") => until(1, TARGETS_END_KEYWORDS),
+        "get_property" | "if" => after("TARGET"),
+        "target_link_libraries" => (0..texts.len())
+            .filter(|index| *index == 0 || !LINK_KEYWORDS.contains(&texts[*index]))
+            .collect(),
+        command if command.starts_with("target_") => vec![0],
+        _ => vec![],
+    }
+}
+
+/// The offsets of `name` in `text` as the whole target of a generator
+/// expression reading one, like `$<TARGET_FILE:name>`,
+/// `$<TARGET_PROPERTY:name,prop>` or `$<LINK_ONLY:name>`
+fn generator_expression_offsets(text: &str, name: &str) -> Vec<usize> {
+    text.match_indices(name)
+        .map(|(offset, _)| offset)
+        .filter(|offset| {
+            let (before, after) = (&text[..*offset], &text[offset + name.len()..]);
+            let Some(expression) = before.rfind("$<").map(|start| &before[start + 2..]) else {
+                return false;
+            };
+            let Some(operator) = expression.strip_suffix(':') else {
+                return false;
+            };
+            (operator.starts_with("TARGET_") || operator.starts_with("LINK_"))
+                && operator
+                    .bytes()
+                    .all(|c| c.is_ascii_uppercase() || c == b'_')
+                && after.starts_with(['>', ','])
+        })
+        .collect()
+}
+
+/// The range of `name` at `offset` in the text of `argument`, which may span
+/// lines when quoted
+fn name_range(argument: &Argument, offset: usize, name: &str) -> Range {
+    let start = argument.node.start_position();
+    let position = |offset: usize| {
+        let before = &argument.text[..offset];
+        match before.rfind('\n') {
+            Some(newline) => Position::new(
+                (start.row + before.matches('\n').count()) as u32,
+                (offset - newline - 1) as u32,
+            ),
+            None => Position::new(start.row as u32, (start.column + offset) as u32),
+        }
+    };
+    Range::new(position(offset), position(offset + name.len()))
+}
+
+fn target_references_inner(source: &str, node: Node, name: &str, output: &mut Vec<Range>) {
+    let mut course = node.walk();
+    for child in node.children(&mut course) {
+        let command = match child.kind() {
+            CMakeNodeKinds::IF_COMMAND
+            | CMakeNodeKinds::ELSEIF_COMMAND
+            | CMakeNodeKinds::WHILE_COMMAND => "if".to_string(),
+            CMakeNodeKinds::NORMAL_COMMAND => {
+                let Some(identifier) = child
+                    .child(0)
+                    .and_then(|identifier| source.get(identifier.byte_range()))
+                else {
+                    continue;
+                };
+                identifier.to_lowercase()
+            }
+            _ => {
+                target_references_inner(source, child, name, output);
+                continue;
+            }
+        };
+        let arguments = call_arguments(child, source);
+        let whole = target_arguments(&command, &arguments);
+        for (index, argument) in arguments.iter().enumerate() {
+            if whole.contains(&index) && argument.text.trim_matches('"') == name {
+                let offset = usize::from(argument.text.starts_with('"'));
+                output.push(name_range(argument, offset, name));
+                continue;
+            }
+            for offset in generator_expression_offsets(argument.text, name) {
+                output.push(name_range(argument, offset, name));
+            }
+        }
+    }
+}
+
+/// The ranges naming the target `name` in `source`: the arguments of the
+/// commands taking targets, quoted or not, and the generator expressions
+/// reading a target, wherever they are
+pub fn target_references(source: &str, root: Node, name: &str) -> Vec<Range> {
+    let mut output = vec![];
+    target_references_inner(source, root, name, &mut output);
+    output
+}
+
+/// The target named by a reference under `location`, one of `project`
+pub fn target_at(
+    location: Position,
+    source: &str,
+    root: Node,
+    project: &[(PathBuf, Target)],
+) -> Option<String> {
+    let argument = get_enclosing_nodes(location.to_point(), root)
+        .into_iter()
+        .find(|node| node.kind() == CMakeNodeKinds::ARGUMENT)?;
+    let text = source.get(argument.byte_range())?;
+    let mut names: Vec<String> = project
+        .iter()
+        .map(|(_, target)| target.name.clone())
+        .filter(|name| text.contains(name.as_str()))
+        .collect();
+    names.sort();
+    names.dedup();
+    names.into_iter().find(|name| {
+        target_references(source, root, name)
+            .iter()
+            .any(|range| range.start <= location && location <= range.end)
+    })
+}
+
+/// The CMake files under `root_path` and `local_path` with their content,
+/// `source` for `local_path` and `open` for the files open in the editor
+fn project_sources(
+    local_path: &Path,
+    source: &str,
+    root_path: Option<&Path>,
+    open: &HashMap<PathBuf, String>,
+) -> Vec<(PathBuf, String)> {
+    let mut files = root_path
+        .map(|root| lint::collect_files(&[root.to_path_buf()]))
+        .unwrap_or_default();
+    if !files.iter().any(|file| file == local_path) {
+        files.push(local_path.to_path_buf());
+    }
+    files
+        .into_iter()
+        .filter_map(|path| {
+            let content = if path == local_path {
+                source.to_string()
+            } else if let Some(text) = open.get(&path) {
+                text.clone()
+            } else {
+                std::fs::read_to_string(&path).ok()?
+            };
+            Some((path, content))
+        })
+        .collect()
+}
+
+/// The ranges naming the targets `names` in each CMake file of the project,
+/// `source` being the content of `local_path` in the editor. The files are
+/// read and parsed on a blocking thread
+pub async fn project_references(
+    local_path: &Path,
+    source: &str,
+    root_path: Option<&Path>,
+    documents: &DashMap<Uri, Document>,
+    names: Vec<String>,
+) -> Vec<(Uri, Vec<Range>)> {
+    let open: HashMap<PathBuf, String> = documents
+        .iter()
+        .filter_map(|document| {
+            let path = document.key().to_file_path().ok()?.to_path_buf();
+            Some((path, document.text().to_string()))
+        })
+        .collect();
+    let local_path = local_path.to_path_buf();
+    let source = source.to_string();
+    let root_path = root_path.map(Path::to_path_buf);
+    tokio::task::spawn_blocking(move || {
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).ok()?;
+        let mut references = vec![];
+        for (path, content) in project_sources(&local_path, &source, root_path.as_deref(), &open) {
+            let Ok(uri) = Uri::from_file_path(&path) else {
+                continue;
+            };
+            let Some(tree) = parse.parse(&content, None) else {
+                continue;
+            };
+            let ranges: Vec<Range> = names
+                .iter()
+                .flat_map(|name| target_references(&content, tree.root_node(), name))
+                .collect();
+            if !ranges.is_empty() {
+                references.push((uri, ranges));
+            }
+        }
+        Some(references)
+    })
+    .await
+    .ok()
+    .flatten()
+    .unwrap_or_default()
+}
+
+fn declaration(path: &Path, target: &Target) -> Option<Location> {
+    let start = target.position.to_position();
+    Some(Location {
+        uri: Uri::from_file_path(path).ok()?,
+        range: Range::new(
+            start,
+            Position::new(start.line, start.character + target.name.len() as u32),
+        ),
+    })
+}
+
+/// The declarations of the real target named under `location` when
+/// `is_jump`, else the references to it and to its aliases in the project
+pub(super) async fn cmptarget(
+    local_path: &Path,
+    location: Position,
+    source: &str,
+    tree: &Tree,
+    is_jump: bool,
+    documents: &DashMap<Uri, Document>,
+) -> Option<Vec<Location>> {
+    let lines: Vec<&str> = source.lines().collect();
+    let project = targets::project_targets(local_path, &lines, tree.root_node());
+    let name = target_at(location, source, tree.root_node(), &project)?;
+    if is_jump {
+        let real = targets::resolve_alias(&name, &project);
+        // The alias itself when the target it stands for is not in the project
+        let declarations: Vec<Location> = [real, name.as_str()]
+            .into_iter()
+            .map(|name| {
+                project
+                    .iter()
+                    .filter(|(_, target)| target.name == name)
+                    .filter_map(|(path, target)| declaration(path, target))
+                    .collect::<Vec<Location>>()
+            })
+            .find(|declarations| !declarations.is_empty())?;
+        return Some(declarations);
+    }
+
+    let family = targets::alias_family(&name, &project);
+    let source_dir = module_path::source_dir();
+    let references = project_references(
+        local_path,
+        source,
+        source_dir.as_deref(),
+        documents,
+        family.into_iter().map(str::to_string).collect(),
+    )
+    .await;
+    Some(
+        references
+            .into_iter()
+            .flat_map(|(uri, ranges)| {
+                ranges.into_iter().map(move |range| Location {
+                    uri: uri.clone(),
+                    range,
+                })
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_references() {
+        let source = r#"project(core)
+add_library(core core.cpp)
+add_library(demo::core ALIAS core)
+add_executable(app main.cpp)
+target_link_libraries(app PRIVATE core $<LINK_ONLY:core_extra>)
+set_target_properties("core" PROPERTIES OUTPUT_NAME core)
+add_custom_command(TARGET app POST_BUILD
+  COMMAND ${CMAKE_COMMAND} -E copy "$<TARGET_FILE:core>" $<TARGET_PROPERTY:core,BINARY_DIR>
+)
+install(TARGETS app core DESTINATION core)
+if(TARGET core AND core)
+  message(STATUS "${core}")
+endif()
+target_compile_definitions(core PRIVATE core $<$<CONFIG:core>:core>)
+target_link_libraries(app PRIVATE $<TARGET_FILE:demo::core> $<TARGET_FILE:core::core>)
+"#;
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let found: Vec<(u32, u32)> = target_references(source, thetree.root_node(), "core")
+            .into_iter()
+            .map(|range| {
+                assert_eq!(range.end.character - range.start.character, 4);
+                (range.start.line, range.start.character)
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (1, 12),
+                (2, 29),
+                (4, 34),
+                (5, 23),
+                (7, 50),
+                (7, 75),
+                (9, 20),
+                (10, 10),
+                (13, 27),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_alias_declaration() {
+        let source = r#"add_library(jump_core INTERFACE)
+add_library(jump::core ALIAS jump_core)
+target_link_libraries(app PRIVATE jump::core)
+"#;
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let path = Path::new("/tmp/jump/CMakeLists.txt");
+        let declarations = cmptarget(
+            path,
+            Position::new(2, 40),
+            source,
+            &thetree,
+            true,
+            &DashMap::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            declarations,
+            vec![Location {
+                uri: Uri::from_file_path(path).unwrap(),
+                range: Range::new(Position::new(0, 12), Position::new(0, 21)),
+            }]
+        );
+        let references = cmptarget(
+            path,
+            Position::new(0, 14),
+            source,
+            &thetree,
+            false,
+            &DashMap::new(),
+        )
+        .await
+        .unwrap();
+        let found: Vec<(u32, u32)> = references
+            .iter()
+            .map(|location| (location.range.start.line, location.range.start.character))
+            .collect();
+        assert_eq!(found, vec![(0, 12), (1, 29), (1, 12), (2, 34)]);
+    }
+}
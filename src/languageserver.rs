@@ -1,56 +1,86 @@
-mod config;
+pub(crate) mod config;
+mod exit;
+mod guard;
+#[cfg(test)]
+pub(crate) mod harness;
+mod memory;
+mod session;
 #[cfg(test)]
 mod test;
 
+use std::collections::HashMap;
+use std::future::Future;
 use std::path::{Path, PathBuf};
-use std::process::exit;
-use std::sync::RwLock;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::{Duration, Instant};
 
 use dashmap::DashMap;
 use tower_lsp::jsonrpc::{Error as LspError, Result};
 use tower_lsp::lsp_types::*;
-use tower_lsp::{LanguageServer, lsp_types};
-use tree_sitter::Parser;
+use tower_lsp::{ClientSocket, LanguageServer, LspService, lsp_types};
+use tree_sitter::{Parser, Tree};
 
-use self::config::Config;
+use self::config::{Config, LintRun};
+pub(crate) use self::exit::serve;
+use self::guard::Site;
 use super::Backend;
 use crate::config::CONFIG;
 use crate::consts::TREESITTER_CMAKE_LANGUAGE;
 use crate::fileapi::DEFAULT_QUERY;
 use crate::formatting::getformat;
-use crate::gammar::{ErrorInformation, LintConfigInfo, checkerror};
-use crate::semantic_token::LEGEND_TYPE;
+use crate::formatting::workspace::{self, FormatWorkspaceParams, FormatWorkspaceResult};
+use crate::gammar::{ErrorInformation, LintConfigInfo, try_checkerror};
+use crate::indexed::{IndexStatistics, Indexed};
+use crate::rules::Rule;
+use crate::semantic_token::{LEGEND_MODIFIER, LEGEND_TYPE};
+use crate::utils::cmake_docs::builtin_doc_url;
+use crate::utils::document::{self, Document};
+use crate::utils::line_index::LineIndex;
 use crate::utils::treehelper::ToPosition;
-use crate::utils::{VCPKG_LIBS, VCPKG_PREFIX, did_vcpkg_project, treehelper};
+use crate::utils::{
+    VCPKG_LIBS, VCPKG_PREFIX, did_vcpkg_project, function_doc, targets, treehelper,
+};
 use crate::{
-    BackendInitInfo, ast, complete, document_link, fileapi, filewatcher, hover, jump, quick_fix,
-    rename, scanner, scansubs, semantic_token, signature_help, utils,
+    BackendInitInfo, ast, auto_close, complete, configure_file, dependencies, document_link, eval,
+    extract, fileapi, filewatcher, folding, hover, inlay_hint, jump, lint, presets, quick_fix,
+    rename, scanner, scansubs, semantic_token, signature_help, utils, vcpkg,
 };
 
+/// Check the documents given as arguments, or all the open ones
+pub(crate) const LINT_COMMAND: &str = "neocmake.lint";
+
 static CLIENT_CAPABILITIES: RwLock<Option<TextDocumentClientCapabilities>> = RwLock::new(None);
 static ENABLE_SNIPPET: AtomicBool = AtomicBool::new(false);
 
+/// CMake writes its cache several times while configuring, the files are
+/// checked again once it settles
+const CACHE_DEBOUNCE: Duration = Duration::from_millis(500);
+
 pub(crate) async fn get_or_update_buffer_contents<P: AsRef<Path>>(
     path: P,
-    documents: &DashMap<Uri, String>,
+    documents: &DashMap<Uri, Document>,
 ) -> std::io::Result<String> {
     let uri = Uri::from_file_path(&path).unwrap();
     if let Some(text) = documents.get(&uri) {
         return Ok(text.to_string());
     }
     let text = tokio::fs::read_to_string(&path).await?;
-    documents.insert(uri, text.clone());
+    documents.insert(uri, Document::new(text.clone()));
     Ok(text)
 }
 
 fn set_client_text_document(text_document: Option<TextDocumentClientCapabilities>) {
-    let mut data = CLIENT_CAPABILITIES.write().unwrap();
+    let mut data = CLIENT_CAPABILITIES
+        .write()
+        .unwrap_or_else(PoisonError::into_inner);
     *data = text_document;
 }
 
 pub fn get_client_capabilities() -> Option<TextDocumentClientCapabilities> {
-    let data = CLIENT_CAPABILITIES.read().unwrap();
+    let data = CLIENT_CAPABILITIES
+        .read()
+        .unwrap_or_else(PoisonError::into_inner);
     data.clone()
 }
 
@@ -72,17 +102,283 @@ pub fn to_use_snippet() -> bool {
     }
 }
 
+/// The cmake documentation of the builtin a diagnostic is on, or else the page of its rule
+fn diagnostic_doc_url(
+    context: &str,
+    start: tree_sitter::Point,
+    end: tree_sitter::Point,
+    rule: Option<&Rule>,
+) -> Option<String> {
+    if start.row == end.row
+        && let Some(name) = context
+            .lines()
+            .nth(start.row)
+            .and_then(|line| line.get(start.column..end.column))
+        && !name.is_empty()
+        && let Some(url) = builtin_doc_url(name)
+    {
+        return Some(url);
+    }
+    rule.map(Rule::url)
+}
+
+/// The methods of the custom requests, each registered by [`service`]
+pub(crate) const CUSTOM_REQUESTS: &[&str] = &[
+    eval::EVAL_COMMAND_METHOD,
+    vcpkg::VCPKG_REPORT_METHOD,
+    dependencies::DEPENDENCIES_METHOD,
+    memory::STATUS_METHOD,
+    workspace::FORMAT_WORKSPACE_METHOD,
+];
+
+/// The language server, with the custom requests of neocmakelsp
+pub(crate) fn service() -> (LspService<Backend>, ClientSocket) {
+    LspService::build(Backend::new)
+        .custom_method(eval::EVAL_COMMAND_METHOD, Backend::eval_command)
+        .custom_method(vcpkg::VCPKG_REPORT_METHOD, Backend::vcpkg_report)
+        .custom_method(dependencies::DEPENDENCIES_METHOD, Backend::dependencies)
+        .custom_method(memory::STATUS_METHOD, Backend::status)
+        .custom_method(
+            workspace::FORMAT_WORKSPACE_METHOD,
+            Backend::format_workspace,
+        )
+        .finish()
+}
+
+/// The capabilities of the server for the initialization options `config`
+pub(crate) fn server_capabilities(config: &Config) -> ServerCapabilities {
+    ServerCapabilities {
+        rename_provider: Some(OneOf::Left(true)),
+        code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+        text_document_sync: Some(TextDocumentSyncCapability::Options(
+            TextDocumentSyncOptions {
+                open_close: Some(true),
+                change: Some(TextDocumentSyncKind::INCREMENTAL),
+                will_save: Some(false),
+                will_save_wait_until: Some(false),
+                save: Some(TextDocumentSyncSaveOptions::Supported(true)),
+            },
+        )),
+        completion_provider: Some(CompletionOptions {
+            resolve_provider: Some(true),
+            trigger_characters: Some(vec!["/".to_string(), ".".to_string()]),
+            work_done_progress_options: Default::default(),
+            all_commit_characters: None,
+            completion_item: None,
+        }),
+        signature_help_provider: Some(SignatureHelpOptions {
+            trigger_characters: Some(vec!["(".to_string(), ",".to_string(), " ".to_string()]),
+            retrigger_characters: Some(vec![",".to_string(), " ".to_string(), ")".to_string()]),
+            work_done_progress_options: Default::default(),
+        }),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        document_formatting_provider: if config.is_format_enabled() {
+            Some(OneOf::Left(true))
+        } else {
+            None
+        },
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        workspace: Some(WorkspaceServerCapabilities {
+            workspace_folders: Some(WorkspaceFoldersServerCapabilities {
+                supported: Some(true),
+                change_notifications: Some(OneOf::Left(true)),
+            }),
+            file_operations: None,
+        }),
+        semantic_tokens_provider: if config.enable_semantic_token() {
+            Some(
+                SemanticTokensServerCapabilities::SemanticTokensRegistrationOptions(
+                    SemanticTokensRegistrationOptions {
+                        text_document_registration_options: {
+                            TextDocumentRegistrationOptions {
+                                document_selector: Some(vec![DocumentFilter {
+                                    language: Some("cmake".to_string()),
+                                    scheme: Some("file".to_string()),
+                                    pattern: None,
+                                }]),
+                            }
+                        },
+                        semantic_tokens_options: SemanticTokensOptions {
+                            work_done_progress_options: WorkDoneProgressOptions::default(),
+                            legend: SemanticTokensLegend {
+                                token_types: LEGEND_TYPE.into(),
+                                token_modifiers: LEGEND_MODIFIER.into(),
+                            },
+                            range: None,
+                            full: Some(SemanticTokensFullOptions::Bool(true)),
+                        },
+                        static_registration_options: StaticRegistrationOptions::default(),
+                    },
+                ),
+            )
+        } else {
+            None
+        },
+        references_provider: Some(OneOf::Left(true)),
+        execute_command_provider: Some(ExecuteCommandOptions {
+            commands: vec![LINT_COMMAND.to_string()],
+            work_done_progress_options: WorkDoneProgressOptions::default(),
+        }),
+
+        folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+        inlay_hint_provider: Some(OneOf::Left(true)),
+        document_on_type_formatting_provider: if config.auto_close_blocks() {
+            Some(DocumentOnTypeFormattingOptions {
+                first_trigger_character: "\n".to_string(),
+                more_trigger_character: None,
+            })
+        } else {
+            None
+        },
+        document_link_provider: Some(DocumentLinkOptions {
+            resolve_provider: Some(true),
+            work_done_progress_options: WorkDoneProgressOptions {
+                work_done_progress: Some(false),
+            },
+        }),
+        ..ServerCapabilities::default()
+    }
+}
+
+/// The syntax tree of `text`, the parser having neither timeout nor cancellation
+fn parse_tree(text: &str) -> Tree {
+    let mut parse = Parser::new();
+    parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+    parse.parse(text, None).unwrap()
+}
+
 impl Backend {
     fn root_path(&self) -> Option<&PathBuf> {
         self.root_path.get_or_init(|| None).as_ref()
     }
 
+    /// The line index of the document, computed when it was not opened in
+    /// the editor
+    fn line_index(&self, uri: &Uri, text: &str) -> Arc<LineIndex> {
+        self.touch(uri);
+        self.line_indexes
+            .entry(uri.clone())
+            .or_insert_with(|| Arc::new(LineIndex::new(text)))
+            .clone()
+    }
+
+    /// The syntax tree of the document, parsed when it was not opened in the
+    /// editor
+    fn tree(&self, uri: &Uri, text: &str) -> Tree {
+        self.touch(uri);
+        self.trees
+            .entry(uri.clone())
+            .or_insert_with(|| parse_tree(text))
+            .clone()
+    }
+
+    /// Whether the server got the `shutdown` request, the pending background
+    /// work is then dropped
+    fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::Relaxed)
+    }
+
+    /// Run `task` in the background, aborted by the `shutdown` request
+    fn spawn_background<F>(&self, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let mut background = self
+            .background
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        while background.try_join_next().is_some() {}
+        background.spawn(task);
+    }
+
+    /// Pass the changes of the editor to the index, and check the document
+    async fn check_change(&self, uri: Uri) {
+        // Not held across the check, the next change writes the document
+        let Some((text, line_count)) = self
+            .documents
+            .get(&uri)
+            .map(|document| (document.to_string(), document.line_count()))
+        else {
+            return;
+        };
+        // The scans of the project read the content not saved yet
+        if let Some(path) = self.document_path(&uri) {
+            scansubs::set_overlay(&path, &text);
+            // Never saved, the index only follows it from the editor
+            if document::is_untitled(&uri) {
+                scansubs::scan_dir(&path, false).await;
+            }
+        }
+        if self.init_info().lint_run != LintRun::OnType {
+            return;
+        }
+        if line_count < 500 {
+            self.publish_diagnostics(
+                uri.clone(),
+                &text,
+                LintConfigInfo {
+                    use_lint: self.init_info().enable_lint,
+                    use_extra_cmake_lint: false,
+                    detached: false,
+                },
+            )
+            .await;
+        }
+        self.client
+            .log_message(MessageType::INFO, &format!("update file: {}", uri.as_str()))
+            .await;
+    }
+
+    /// Read the help of cmake in the background, so the `cmake --help-*` calls do
+    /// not delay the first completion or signature help
+    fn precompute_builtins(&self) {
+        let mut background = self
+            .background
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        while background.try_join_next().is_some() {}
+        background.spawn_blocking(|| {
+            complete::init_builtin_command();
+            signature_help::init_signatures();
+            complete::init_builtin_variable();
+            complete::init_builtin_module();
+        });
+    }
+
     fn init_info(&self) -> &BackendInitInfo {
         self.init_info
             .get()
             .expect("Should have been inited before")
     }
 
+    /// The path of the document, see [`document::document_path`]
+    fn document_path(&self, uri: &Uri) -> Option<PathBuf> {
+        document::document_path(uri, self.root_path().map(PathBuf::as_path))
+    }
+
+    /// The locations found from an untitled document, pointing to it rather
+    /// than to the file its path stands for
+    fn untitled_locations(&self, uri: &Uri, mut locations: Vec<Location>) -> Vec<Location> {
+        if let Some(file_uri) = self.untitled_file_uri(uri) {
+            for location in &mut locations {
+                if location.uri == file_uri {
+                    location.uri = uri.clone();
+                }
+            }
+        }
+        locations
+    }
+
+    /// The file URI standing for an untitled document
+    fn untitled_file_uri(&self, uri: &Uri) -> Option<Uri> {
+        if !document::is_untitled(uri) {
+            return None;
+        }
+        self.document_path(uri)
+            .and_then(|path| Uri::from_file_path(path).ok())
+    }
+
     fn path_in_project<P: AsRef<Path>>(&self, path: P) -> bool {
         let Some(root_path) = self.root_path() else {
             return true;
@@ -96,8 +392,78 @@ impl Backend {
             .all(|component| component != Component::ParentDir)
     }
 
+    /// The command at the position, with its variables expanded
+    async fn eval_command(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<eval::EvaluatedCommand>> {
+        let site = Site::new(eval::EVAL_COMMAND_METHOD).at(&params);
+        self.guard(site, async move {
+            let uri = params.text_document.uri;
+            let Some(text) = self.documents.get(&uri) else {
+                return Ok(None);
+            };
+            let Some(file_path) = self.document_path(&uri) else {
+                return Ok(None);
+            };
+            Ok(eval::eval_command(&file_path, &text, params.position))
+        })
+        .await?
+    }
+
+    async fn vcpkg_report(&self) -> Result<Option<vcpkg::VcpkgReport>> {
+        let site = Site::new(vcpkg::VCPKG_REPORT_METHOD);
+        self.guard(site, async move {
+            Ok(self.root_path().and_then(|root| vcpkg::report(root)))
+        })
+        .await?
+    }
+
+    async fn dependencies(&self) -> Result<Option<Vec<dependencies::Dependency>>> {
+        let site = Site::new(dependencies::DEPENDENCIES_METHOD);
+        self.guard(site, async move {
+            Ok(self
+                .root_path()
+                .map(|root| dependencies::dependencies(root)))
+        })
+        .await?
+    }
+
+    async fn format_workspace(
+        &self,
+        params: FormatWorkspaceParams,
+    ) -> Result<Option<FormatWorkspaceResult>> {
+        let site = Site::new(workspace::FORMAT_WORKSPACE_METHOD);
+        self.guard(site, async move {
+            let Some(root) = self.root_path() else {
+                return Ok(None);
+            };
+            let files = lint::collect_files(std::slice::from_ref(root));
+            // The open documents are formatted as they are in the editor. The map
+            // also keeps closed files, maybe with changes discarded by the user, so
+            // these are read from the disk
+            let sources: HashMap<PathBuf, String> = self
+                .documents
+                .iter()
+                .filter(|entry| self.open_documents.contains(entry.key()))
+                .filter_map(|entry| {
+                    let path = entry.key().to_file_path().ok()?.to_path_buf();
+                    Some((path, entry.value().text().to_string()))
+                })
+                .collect();
+            let cancelled = self.shutting_down.clone();
+            tokio::task::spawn_blocking(move || {
+                workspace::format_workspace(&files, &sources, &params.options, &cancelled)
+            })
+            .await
+            .map(Some)
+            .map_err(|_| LspError::internal_error())
+        })
+        .await?
+    }
+
     async fn publish_diagnostics(&self, uri: Uri, context: &str, lint_info: LintConfigInfo) {
-        let Ok(file_path) = uri.to_file_path() else {
+        let Some(file_path) = self.document_path(&uri) else {
             tracing::error!("Cannot transport {uri:?} to file_path");
             self.client
                 .log_message(
@@ -108,11 +474,36 @@ impl Backend {
             return;
         };
 
-        if !self.path_in_project(&file_path) {
+        // A module of cmake or a dependency opened from outside of the workspace
+        // is checked on its own
+        let lint_info = LintConfigInfo {
+            detached: !self.path_in_project(&file_path),
+            ..lint_info
+        };
+        if presets::is_presets(&file_path) || vcpkg::is_manifest(&file_path) {
+            let diagnostics = if vcpkg::is_manifest(&file_path) {
+                vcpkg::check(&file_path, context)
+            } else {
+                presets::check(&file_path, context)
+            };
+            self.record_diagnostics(&uri, &diagnostics);
+            self.client
+                .publish_diagnostics(uri, diagnostics, None)
+                .await;
+            return;
+        }
+        // Templates are not CMake code
+        if configure_file::is_template(&file_path) {
             return;
         }
 
-        let gammererror = checkerror(&file_path, context, lint_info);
+        let gammererror = match try_checkerror(&file_path, context.as_bytes(), lint_info) {
+            Ok(result) => result,
+            Err(err) => {
+                tracing::error!("Cannot check {}: {err}", file_path.display());
+                None
+            }
+        };
         if let Some(diagnoses) = gammererror {
             let mut pusheddiagnoses = vec![];
             for ErrorInformation {
@@ -120,6 +511,7 @@ impl Backend {
                 end_point,
                 message,
                 severity,
+                rule,
             } in diagnoses.inner
             {
                 let pointx = start_point.to_position();
@@ -131,9 +523,11 @@ impl Backend {
                 let diagnose = Diagnostic {
                     range,
                     severity,
-                    code: None,
-                    code_description: None,
-                    source: None,
+                    code: rule.map(|rule| NumberOrString::String(rule.id.to_string())),
+                    code_description: diagnostic_doc_url(context, start_point, end_point, rule)
+                        .and_then(|url| url.parse().ok())
+                        .map(|href| CodeDescription { href }),
+                    source: Some("neocmakelsp".to_string()),
                     message,
                     related_information: None,
                     tags: None,
@@ -141,17 +535,39 @@ impl Backend {
                 };
                 pusheddiagnoses.push(diagnose);
             }
+            self.record_diagnostics(&uri, &pusheddiagnoses);
             self.client
                 .publish_diagnostics(uri, pusheddiagnoses, Some(1))
                 .await;
         } else {
+            self.record_diagnostics(&uri, &[]);
             self.client.publish_diagnostics(uri, vec![], None).await;
         }
     }
 
     async fn update_diagnostics(&self) {
+        self.update_diagnostics_under(None).await;
+    }
+
+    /// Check again the documents under `dirs`, or all of them
+    async fn update_diagnostics_under(&self, dirs: Option<&[PathBuf]>) {
+        if self.init_info().lint_run == LintRun::Manual {
+            return;
+        }
+        let is_under = |uri: &Uri| {
+            dirs.is_none_or(|dirs| {
+                uri.to_file_path()
+                    .is_ok_and(|path| dirs.iter().any(|dir| path.starts_with(dir)))
+            })
+        };
         for item in &self.documents {
+            if self.is_shutting_down() {
+                return;
+            }
             let uri = item.key();
+            if !is_under(uri) {
+                continue;
+            }
             let text = item.value();
             self.publish_diagnostics(
                 uri.clone(),
@@ -159,698 +575,1121 @@ impl Backend {
                 LintConfigInfo {
                     use_lint: self.init_info().enable_lint,
                     use_extra_cmake_lint: true,
+                    detached: false,
+                },
+            )
+            .await;
+        }
+        self.publish_closed_diagnostics(is_under).await;
+    }
+
+    /// Check the files of the project which are not open, so the problems of the
+    /// whole tree are listed, not only the ones of the open files
+    async fn publish_closed_diagnostics(&self, is_under: impl Fn(&Uri) -> bool) {
+        let init_info = self.init_info();
+        if !init_info.lint_closed_files || init_info.lint_run == LintRun::Manual {
+            return;
+        }
+        let Some(root_path) = self.root_path() else {
+            return;
+        };
+        for path in lint::collect_files(std::slice::from_ref(root_path)) {
+            let uri = Uri::from_file_path(&path).unwrap();
+            if self.is_shutting_down() {
+                return;
+            }
+            if !is_under(&uri) {
+                continue;
+            }
+            // Unchanged since the last session, which checked it
+            if self.documents.contains_key(&uri) || self.restored.remove(&uri).is_some() {
+                continue;
+            }
+            let Ok(text) = tokio::fs::read_to_string(&path).await else {
+                continue;
+            };
+            self.publish_diagnostics(
+                uri,
+                &text,
+                LintConfigInfo {
+                    use_lint: init_info.enable_lint,
+                    use_extra_cmake_lint: false,
+                    detached: false,
                 },
             )
             .await;
         }
     }
+
+    /// Check the projects whose cache changed again, once no change came for
+    /// [`CACHE_DEBOUNCE`]
+    fn schedule_project_check(&self, project: PathBuf) {
+        self.changed_projects.insert(project);
+        let change = self.cache_changes.fetch_add(1, Ordering::AcqRel) + 1;
+        let backend = self.clone();
+        self.spawn_background(async move {
+            tokio::time::sleep(CACHE_DEBOUNCE.max(backend.init_info().lint_debounce)).await;
+            // Another change came meanwhile, its own check is waiting
+            if backend.is_shutting_down() || backend.cache_changes.load(Ordering::Acquire) != change
+            {
+                return;
+            }
+            let projects: Vec<PathBuf> = backend
+                .changed_projects
+                .iter()
+                .map(|project| project.clone())
+                .collect();
+            for project in &projects {
+                backend.changed_projects.remove(project);
+            }
+            backend.update_diagnostics_under(Some(&projects)).await;
+        });
+    }
 }
 
 impl LanguageServer for Backend {
     async fn initialize(&self, initial: InitializeParams) -> Result<InitializeResult> {
-        let initial_config: Config = initial
-            .initialization_options
-            .and_then(|value| serde_json::from_value(value).unwrap_or(None))
-            .unwrap_or_default();
-
-        init_snippet_setting(initial_config.use_snippets());
-
-        let do_format = initial_config.is_format_enabled();
-
-        let scan_cmake_in_package = initial_config.is_scan_cmake_in_package();
-
-        let enable_lint = initial_config.is_lint_enabled();
-
-        self.init_info
-            .set(BackendInitInfo {
-                scan_cmake_in_package,
-                enable_lint,
-            })
-            .expect("here should be the first place to init the init_info");
+        let site = Site::new("initialize");
+        self.guard(site, async move {
+            let initial_config: Config = initial
+                .initialization_options
+                .and_then(|value| serde_json::from_value(value).unwrap_or(None))
+                .unwrap_or_default();
+
+            init_snippet_setting(initial_config.use_snippets());
+
+            let scan_cmake_in_package = initial_config.is_scan_cmake_in_package();
+
+            let enable_lint = initial_config.is_lint_enabled();
+
+            self.init_info
+                .set(BackendInitInfo {
+                    scan_cmake_in_package,
+                    enable_lint,
+                    lint_run: initial_config.lint_run(),
+                    lint_debounce: initial_config.lint_debounce(),
+                    lint_closed_files: initial_config.lint_closed_files(),
+                    persist_session: initial_config.persist_session(),
+                    inlay_hint_refresh: initial
+                        .capabilities
+                        .workspace
+                        .as_ref()
+                        .and_then(|workspace| workspace.inlay_hint.as_ref())
+                        .and_then(|inlay_hint| inlay_hint.refresh_support)
+                        .unwrap_or(false),
+                    memory_budget: initial_config.memory_budget(),
+                })
+                .expect("here should be the first place to init the init_info");
+
+            if let Some(workspace) = initial.capabilities.workspace
+                && let Some(watch_file) = workspace.did_change_watched_files
+                && let (Some(true), Some(true)) = (
+                    watch_file.dynamic_registration,
+                    watch_file.relative_pattern_support,
+                )
+                && let Some(ref top_path) = initial
+                    .workspace_folders
+                    .as_ref()
+                    .and_then(|folders| folders.first())
+                    .and_then(|folder| folder.uri.to_file_path().ok())
+            {
+                let path = top_path.join("build").join("CMakeCache.txt");
+                if path.exists() {
+                    filewatcher::refresh_error_packages(path);
+                }
 
-        if let Some(workspace) = initial.capabilities.workspace
-            && let Some(watch_file) = workspace.did_change_watched_files
-            && let (Some(true), Some(true)) = (
-                watch_file.dynamic_registration,
-                watch_file.relative_pattern_support,
-            )
-            && let Some(ref top_path) = initial
-                .workspace_folders
-                .as_ref()
-                .and_then(|folders| folders.first())
-                .and_then(|folder| folder.uri.to_file_path().ok())
-        {
-            let path = top_path.join("build").join("CMakeCache.txt");
-            if path.exists() {
-                filewatcher::refresh_error_packages(path);
-            }
-
-            tracing::info!("find cache-v2 json, start reading the data");
-            let cache_path = top_path
-                .join("build")
-                .join(".cmake")
-                .join("api")
-                .join("v1")
-                .join("reply");
-            if cache_path.is_dir() {
-                use std::fs;
-                if let Ok(entries) = fs::read_dir(cache_path) {
-                    for entry in entries.flatten() {
-                        let file_path = entry.path();
-                        if file_path.is_file() {
-                            let Some(file_name) = file_path.file_name() else {
-                                continue;
-                            };
-                            let file_name = file_name.to_string_lossy().to_string();
-                            if file_name.starts_with("cache-v2") && file_name.ends_with(".json") {
-                                fileapi::update_cache_data(file_path);
-                                break;
+                tracing::info!("find cache-v2 json, start reading the data");
+                let cache_path = top_path
+                    .join("build")
+                    .join(".cmake")
+                    .join("api")
+                    .join("v1")
+                    .join("reply");
+                if cache_path.is_dir() {
+                    use std::fs;
+                    if let Ok(entries) = fs::read_dir(cache_path) {
+                        for entry in entries.flatten() {
+                            let file_path = entry.path();
+                            if file_path.is_file() {
+                                let Some(file_name) = file_path.file_name() else {
+                                    continue;
+                                };
+                                let file_name = file_name.to_string_lossy().to_string();
+                                if file_name.starts_with("cache-v2") && file_name.ends_with(".json")
+                                {
+                                    fileapi::update_cache_data(file_path);
+                                    break;
+                                }
                             }
                         }
                     }
                 }
+                tracing::info!("Finish getting the data in cache-v2 json");
             }
-            tracing::info!("Finish getting the data in cache-v2 json");
-        }
-
-        if let Some(ref project_root) = initial
-            .workspace_folders
-            .as_ref()
-            .and_then(|folders| folders.first())
-            .and_then(|folder| folder.uri.to_file_path().ok())
-        {
-            self.root_path
-                .set(Some(project_root.to_path_buf()))
-                .expect("here should be the only place to set the root_path");
-        }
 
-        set_client_text_document(initial.capabilities.text_document);
+            if let Some(ref project_root) = initial
+                .workspace_folders
+                .as_ref()
+                .and_then(|folders| folders.first())
+                .and_then(|folder| folder.uri.to_file_path().ok())
+            {
+                self.root_path
+                    .set(Some(project_root.to_path_buf()))
+                    .expect("here should be the only place to set the root_path");
+            }
 
-        let version: String = env!("CARGO_PKG_VERSION").to_string();
-        Ok(InitializeResult {
-            server_info: Some(ServerInfo {
-                name: "neocmakelsp-fast".to_string(),
-                version: Some(version),
-            }),
-            capabilities: ServerCapabilities {
-                rename_provider: Some(OneOf::Left(true)),
-                code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
-                text_document_sync: Some(TextDocumentSyncCapability::Options(
-                    TextDocumentSyncOptions {
-                        open_close: Some(true),
-                        change: Some(TextDocumentSyncKind::FULL),
-                        will_save: Some(false),
-                        will_save_wait_until: Some(false),
-                        save: Some(TextDocumentSyncSaveOptions::Supported(true)),
-                    },
-                )),
-                completion_provider: Some(CompletionOptions {
-                    resolve_provider: Some(false),
-                    trigger_characters: Some(vec!["/".to_string(), ".".to_string()]),
-                    work_done_progress_options: Default::default(),
-                    all_commit_characters: None,
-                    completion_item: None,
-                }),
-                signature_help_provider: Some(SignatureHelpOptions {
-                    trigger_characters: Some(vec!["(".to_string(), ",".to_string()]),
-                    retrigger_characters: Some(vec![",".to_string(), " ".to_string()]),
-                    work_done_progress_options: Default::default(),
-                }),
-                document_symbol_provider: Some(OneOf::Left(true)),
-                definition_provider: Some(OneOf::Left(true)),
-                document_formatting_provider: if do_format {
-                    Some(OneOf::Left(true))
-                } else {
-                    None
-                },
-                hover_provider: Some(HoverProviderCapability::Simple(true)),
-                workspace: Some(WorkspaceServerCapabilities {
-                    workspace_folders: Some(WorkspaceFoldersServerCapabilities {
-                        supported: Some(true),
-                        change_notifications: Some(OneOf::Left(true)),
-                    }),
-                    file_operations: None,
-                }),
-                semantic_tokens_provider: if initial_config.enable_semantic_token() {
-                    Some(
-                        SemanticTokensServerCapabilities::SemanticTokensRegistrationOptions(
-                            SemanticTokensRegistrationOptions {
-                                text_document_registration_options: {
-                                    TextDocumentRegistrationOptions {
-                                        document_selector: Some(vec![DocumentFilter {
-                                            language: Some("cmake".to_string()),
-                                            scheme: Some("file".to_string()),
-                                            pattern: None,
-                                        }]),
-                                    }
-                                },
-                                semantic_tokens_options: SemanticTokensOptions {
-                                    work_done_progress_options: WorkDoneProgressOptions::default(),
-                                    legend: SemanticTokensLegend {
-                                        token_types: LEGEND_TYPE.into(),
-                                        token_modifiers: vec![],
-                                    },
-                                    range: None,
-                                    full: Some(SemanticTokensFullOptions::Bool(true)),
-                                },
-                                static_registration_options: StaticRegistrationOptions::default(),
-                            },
-                        ),
-                    )
-                } else {
-                    None
-                },
-                references_provider: Some(OneOf::Left(true)),
+            set_client_text_document(initial.capabilities.text_document);
+            // After the capabilities, which decide the snippets of the commands
+            self.precompute_builtins();
 
-                document_link_provider: Some(DocumentLinkOptions {
-                    resolve_provider: Some(true),
-                    work_done_progress_options: WorkDoneProgressOptions {
-                        work_done_progress: Some(false),
-                    },
+            let version: String = env!("CARGO_PKG_VERSION").to_string();
+            Ok(InitializeResult {
+                server_info: Some(ServerInfo {
+                    name: "neocmakelsp-fast".to_string(),
+                    version: Some(version),
                 }),
-                ..ServerCapabilities::default()
-            },
+                capabilities: server_capabilities(&initial_config),
+            })
         })
+        .await?
     }
 
     async fn initialized(&self, _: InitializedParams) {
-        let cachefilechangeparms = DidChangeWatchedFilesRegistrationOptions {
-            watchers: vec![
-                FileSystemWatcher {
-                    glob_pattern: GlobPattern::String("**/CMakeCache.txt".to_string()),
-                    kind: Some(lsp_types::WatchKind::all()),
-                },
-                FileSystemWatcher {
-                    glob_pattern: GlobPattern::String("**/.cmake/api/v1/reply/*.json".to_string()),
-                    kind: Some(lsp_types::WatchKind::all()),
-                },
-                FileSystemWatcher {
-                    glob_pattern: GlobPattern::String("**/CMakeLists.txt".to_string()),
-                    kind: Some(lsp_types::WatchKind::Create | lsp_types::WatchKind::Delete),
-                },
-            ],
-        };
-
-        let cmakecache_watcher = Registration {
-            id: "CMakeCacheWatcher".to_string(),
-            method: "workspace/didChangeWatchedFiles".to_string(),
-            register_options: Some(serde_json::to_value(cachefilechangeparms).unwrap()),
-        };
-
-        self.client
-            .register_capability(vec![cmakecache_watcher])
-            .await
-            .unwrap();
+        let site = Site::new("initialized");
+        let _ = self
+            .guard(site, async move {
+                let cachefilechangeparms = DidChangeWatchedFilesRegistrationOptions {
+                    watchers: vec![
+                        FileSystemWatcher {
+                            glob_pattern: GlobPattern::String("**/CMakeCache.txt".to_string()),
+                            kind: Some(lsp_types::WatchKind::all()),
+                        },
+                        FileSystemWatcher {
+                            glob_pattern: GlobPattern::String(
+                                "**/.cmake/api/v1/reply/*.json".to_string(),
+                            ),
+                            kind: Some(lsp_types::WatchKind::all()),
+                        },
+                        FileSystemWatcher {
+                            glob_pattern: GlobPattern::String("**/CMakeLists.txt".to_string()),
+                            kind: Some(lsp_types::WatchKind::Create | lsp_types::WatchKind::Delete),
+                        },
+                        FileSystemWatcher {
+                            glob_pattern: GlobPattern::String(
+                                "**/{neocmake,.neocmake}.toml".to_string(),
+                            ),
+                            kind: Some(lsp_types::WatchKind::all()),
+                        },
+                    ],
+                };
 
-        self.client
-            .log_message(MessageType::INFO, "initialized!")
-            .await;
+                let cmakecache_watcher = Registration {
+                    id: "CMakeCacheWatcher".to_string(),
+                    method: "workspace/didChangeWatchedFiles".to_string(),
+                    register_options: Some(serde_json::to_value(cachefilechangeparms).unwrap()),
+                };
 
-        let work_done_token = ProgressToken::Number(1);
-        let progress = self
-            .client
-            .progress(work_done_token, "start initing the workspace")
-            .with_message("initial start")
-            .with_percentage(0)
-            .begin()
-            .await;
+                self.client
+                    .register_capability(vec![cmakecache_watcher])
+                    .await
+                    .unwrap();
 
-        if let Some(ref project_root) = self.root_path() {
-            progress
-                .report_with_message(&format!("start scanning {}", project_root.display()), 10)
-                .await;
-            scansubs::scan_all(&project_root, true).await;
+                self.client
+                    .log_message(MessageType::INFO, "initialized!")
+                    .await;
 
-            progress
-                .report_with_message("Initializing file watcher", 15)
-                .await;
-            if scanner::init_file_watcher().is_some() {
-                scanner::watch_workspace(project_root);
-                tracing::info!("File watcher initialized for workspace");
-            }
-            let build_dir = project_root.join("build");
-            if build_dir.is_dir()
-                && let Some(query) = &*DEFAULT_QUERY
-            {
-                query.write_to_build_dir(build_dir.as_path()).ok();
-            }
-            if did_vcpkg_project(project_root) {
-                progress
-                    .report_with_message("find vcpkg dir, start scanning", 20)
+                let started = Instant::now();
+                let mut statistics = IndexStatistics::default();
+                let work_done_token = ProgressToken::Number(1);
+                let progress = self
+                    .client
+                    .progress(work_done_token, "start initing the workspace")
+                    .with_message("initial start")
+                    .with_percentage(0)
+                    .begin()
                     .await;
-                tracing::info!("This project is vcpkg project, start init vcpkg data");
-                let vcpkg_installed_path = project_root.join("vcpkg_installed");
 
-                #[cfg(unix)]
-                {
-                    use crate::utils::packagepkgconfig::QUERYSRULES;
-                    QUERYSRULES.lock().unwrap().insert(
-                        0,
-                        Box::leak(
-                            format!("{}/*.pc", vcpkg_installed_path.to_str().unwrap())
-                                .into_boxed_str(),
-                        ),
-                    );
-                }
+                if let Some(ref project_root) = self.root_path() {
+                    progress
+                        .report_with_message(
+                            &format!("start scanning {}", project_root.display()),
+                            10,
+                        )
+                        .await;
+                    statistics.files = scansubs::scan_all(&project_root, true).await;
+
+                    progress
+                        .report_with_message("Initializing file watcher", 15)
+                        .await;
+                    if scanner::init_file_watcher().is_some() {
+                        scanner::watch_workspace(project_root);
+                        tracing::info!("File watcher initialized for workspace");
+                    }
+                    let build_dir = project_root.join("build");
+                    if build_dir.is_dir()
+                        && let Some(query) = &*DEFAULT_QUERY
+                    {
+                        query.write_to_build_dir(build_dir.as_path()).ok();
+                    }
+                    if did_vcpkg_project(project_root) {
+                        progress
+                            .report_with_message("find vcpkg dir, start scanning", 20)
+                            .await;
+                        tracing::info!("This project is vcpkg project, start init vcpkg data");
+                        let vcpkg_installed_path = project_root.join("vcpkg_installed");
+                        vcpkg::update_installed_packages(&vcpkg_installed_path);
+
+                        #[cfg(unix)]
+                        {
+                            use crate::utils::packagepkgconfig::QUERYSRULES;
+                            QUERYSRULES
+                                .lock()
+                                .unwrap_or_else(PoisonError::into_inner)
+                                .insert(
+                                    0,
+                                    Box::leak(
+                                        format!("{}/*.pc", vcpkg_installed_path.to_str().unwrap())
+                                            .into_boxed_str(),
+                                    ),
+                                );
+                        }
 
-                VCPKG_PREFIX.lock().unwrap().push(Box::leak(
-                    vcpkg_installed_path
-                        .to_str()
-                        .unwrap()
-                        .to_string()
-                        .into_boxed_str(),
-                ));
-
-                if let Ok(paths) = utils::make_vcpkg_package_search_path(&vcpkg_installed_path) {
-                    let mut vcpkg_libs = VCPKG_LIBS.lock().unwrap();
-                    for t in paths {
-                        vcpkg_libs.push(Box::leak(t.into_boxed_str()));
+                        VCPKG_PREFIX
+                            .lock()
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .push(Box::leak(
+                                vcpkg_installed_path
+                                    .to_str()
+                                    .unwrap()
+                                    .to_string()
+                                    .into_boxed_str(),
+                            ));
+
+                        if let Ok(paths) =
+                            utils::make_vcpkg_package_search_path(&vcpkg_installed_path)
+                        {
+                            let mut vcpkg_libs =
+                                VCPKG_LIBS.lock().unwrap_or_else(PoisonError::into_inner);
+                            for t in paths {
+                                vcpkg_libs.push(Box::leak(t.into_boxed_str()));
+                            }
+                        }
                     }
                 }
-            }
-        }
-        progress
-            .report_with_message("Start generating builtin commands", 50)
-            .await;
-        complete::init_builtin_command();
-        progress
-            .report_with_message("Start generating builtin module", 55)
-            .await;
-        complete::init_builtin_module();
-        progress
-            .report_with_message("Start generating builtin variable", 60)
-            .await;
-        complete::init_builtin_variable();
-        progress
-            .report_with_message("Start init system modules", 70)
-            .await;
-        complete::init_system_modules();
-        progress
-            .report_with_message("Start init signature help", 80)
+                progress
+                    .report_with_message("Start init system modules", 70)
+                    .await;
+                complete::init_system_modules();
+                progress
+                    .report_with_message("Restoring the last session", 80)
+                    .await;
+                self.restore_session().await;
+                progress.report_with_message("Checking the files", 90).await;
+                // The open files were checked before the targets of the project were known
+                self.update_diagnostics().await;
+                self.save_session();
+                self.trim_memory().await;
+                progress.report_with_message("Scan finished", 100).await;
+                progress.finish().await;
+
+                statistics.targets = targets::all_targets().len();
+                statistics.functions = complete::defined_functions().await;
+                statistics.diagnostics = self.diagnostics.iter().map(|count| *count.value()).sum();
+                statistics.elapsed_ms = started.elapsed().as_millis() as u64;
+                self.client.send_notification::<Indexed>(statistics).await;
+            })
             .await;
-        signature_help::init_signatures();
-        progress.report_with_message("Scan finished", 100).await;
-        progress.finish().await;
     }
 
     async fn shutdown(&self) -> Result<()> {
-        if let Some(watcher) = scanner::get_file_watcher() {
-            watcher.shutdown();
-        }
-        exit(0)
+        let site = Site::new("shutdown");
+        self.guard(site, async move {
+            self.shutting_down.store(true, Ordering::Relaxed);
+            // The blocking work already running ends with the process
+            self.background
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .abort_all();
+            self.flush_session().await;
+            if let Some(watcher) = scanner::get_file_watcher() {
+                watcher.shutdown().await;
+            }
+            Ok(())
+        })
+        .await?
     }
 
     async fn did_change_workspace_folders(&self, _: DidChangeWorkspaceFoldersParams) {
-        self.client
-            .log_message(MessageType::INFO, "workspace folders changed!")
+        let site = Site::new("workspace/didChangeWorkspaceFolders");
+        let _ = self
+            .guard(site, async move {
+                self.client
+                    .log_message(MessageType::INFO, "workspace folders changed!")
+                    .await;
+            })
             .await;
     }
 
     async fn did_change_configuration(&self, _: DidChangeConfigurationParams) {
-        self.client
-            .log_message(MessageType::INFO, "configuration changed!")
+        let site = Site::new("workspace/didChangeConfiguration");
+        let _ = self
+            .guard(site, async move {
+                self.client
+                    .log_message(MessageType::INFO, "configuration changed!")
+                    .await;
+            })
             .await;
     }
 
     async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
-        let mut has_cached_changed = false;
-        for change in params.changes {
-            let Ok(file_path) = change.uri.to_file_path() else {
-                continue;
-            };
-            let Some(file_name) = file_path
-                .file_name()
-                .and_then(|name| name.to_str())
-                .map(|name| name.to_string())
-            else {
-                continue;
-            };
-
-            if file_name.ends_with("json") && file_name.starts_with("cache-v2") {
-                fileapi::update_cache_data(&file_path);
-            }
-            if file_name.ends_with("txt") {
-                has_cached_changed = true;
-                if file_name == "CMakeLists.txt" {
-                    let Some(path) = self.root_path() else {
+        let site = Site::new("workspace/didChangeWatchedFiles");
+        let _ = self
+            .guard(site, async move {
+                let mut has_cached_changed = false;
+                for change in params.changes {
+                    let Ok(file_path) = change.uri.to_file_path() else {
                         continue;
                     };
-                    scansubs::scan_all(path, false).await;
-                    continue;
+                    let Some(file_name) = file_path
+                        .file_name()
+                        .and_then(|name| name.to_str())
+                        .map(|name| name.to_string())
+                    else {
+                        continue;
+                    };
+
+                    // The settings of a subdirectory change the diagnostics of its files
+                    if crate::config::is_directory_config(&file_name) {
+                        crate::config::forget_directory_config(&file_path);
+                        has_cached_changed = true;
+                        continue;
+                    }
+                    if file_name.ends_with("json") && file_name.starts_with("cache-v2") {
+                        fileapi::update_cache_data(&file_path);
+                        // The hints show the values of the cache
+                        if self.init_info().inlay_hint_refresh {
+                            let _ = self.client.inlay_hint_refresh().await;
+                        }
+                    }
+                    if file_name.ends_with("txt") {
+                        if file_name == "CMakeLists.txt" {
+                            has_cached_changed = true;
+                            let Some(path) = self.root_path() else {
+                                continue;
+                            };
+                            scansubs::scan_all(path, false).await;
+                            continue;
+                        }
+                        self.client
+                            .log_message(MessageType::INFO, "CMakeCache changed")
+                            .await;
+                        if let FileChangeType::DELETED = change.typ {
+                            filewatcher::clear_error_packages();
+                        } else {
+                            filewatcher::refresh_error_packages(&file_path);
+                        }
+                        if let Some(project) = filewatcher::cache_project(&file_path)
+                            .or_else(|| self.root_path().cloned())
+                        {
+                            self.schedule_project_check(project);
+                        }
+                    }
+                }
+                if has_cached_changed {
+                    self.update_diagnostics().await;
                 }
                 self.client
-                    .log_message(MessageType::INFO, "CMakeCache changed")
+                    .log_message(MessageType::INFO, "watched files have changed!")
                     .await;
-                if let FileChangeType::DELETED = change.typ {
-                    filewatcher::clear_error_packages();
-                } else {
-                    filewatcher::refresh_error_packages(file_path);
-                }
-            }
-        }
-        if has_cached_changed {
-            self.update_diagnostics().await;
-        }
-        self.client
-            .log_message(MessageType::INFO, "watched files have changed!")
+            })
             .await;
     }
 
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
-        let TextDocumentItem { uri, text, .. } = params.text_document;
-        self.documents.insert(uri.clone(), text.clone());
-
-        let path = match uri.to_file_path() {
-            Ok(path) => path,
-            Err(_) => {
-                tracing::error!("Can't create path from {}", uri.as_str());
-                return;
-            }
-        };
+        let site = Site::new("textDocument/didOpen").document(&params.text_document.uri);
+        let _ = self
+            .guard(site, async move {
+                let TextDocumentItem { uri, text, .. } = params.text_document;
+                if uri.to_file_path().is_ok_and(presets::is_ignored_json) {
+                    return;
+                }
+                {
+                    let mut document = self.documents.entry(uri.clone()).or_default();
+                    *document = Document::new(text.clone());
+                    self.trees.insert(uri.clone(), parse_tree(&text));
+                    self.line_indexes
+                        .insert(uri.clone(), Arc::new(LineIndex::new(&text)));
+                }
+                self.open_documents.insert(uri.clone());
+                self.touch(&uri);
+                self.schedule_trim();
+
+                let path = match self.document_path(&uri) {
+                    Some(path) => path,
+                    None => {
+                        tracing::error!("Can't create path from {}", uri.as_str());
+                        return;
+                    }
+                };
 
-        complete::update_cache(&path, &text).await;
-        jump::update_cache(&path, &text).await;
-        self.publish_diagnostics(
-            uri,
-            &text,
-            LintConfigInfo {
-                use_lint: self.init_info().enable_lint,
-                use_extra_cmake_lint: true,
-            },
-        )
-        .await;
+                complete::update_cache(&path, &text).await;
+                jump::update_cache(&path, &text).await;
+                if document::is_untitled(&uri) {
+                    scansubs::set_overlay(&path, &text);
+                    scansubs::scan_dir(&path, false).await;
+                }
+                if self.init_info().lint_run != LintRun::Manual {
+                    self.publish_diagnostics(
+                        uri,
+                        &text,
+                        LintConfigInfo {
+                            use_lint: self.init_info().enable_lint,
+                            use_extra_cmake_lint: true,
+                            detached: false,
+                        },
+                    )
+                    .await;
+                }
+                self.save_session();
 
-        self.client
-            .log_message(MessageType::INFO, format!("Opened file {}", path.display()))
+                self.client
+                    .log_message(MessageType::INFO, format!("Opened file {}", path.display()))
+                    .await;
+            })
             .await;
     }
 
     async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
-        let Some(toolong) = params
-            .context
-            .diagnostics
-            .iter()
-            .find(|dia| dia.message.starts_with("[C0301]"))
-        else {
-            return Ok(None);
-        };
-
-        let uri = params.text_document.uri;
-        let Some(text) = self.documents.get(&uri) else {
-            return Ok(None);
-        };
-        let line = params.range.start.line;
-        Ok(quick_fix::lint_fix_action(&text, line, toolong, uri))
+        let site = Site::new("textDocument/codeAction").document(&params.text_document.uri);
+        self.guard(site, async move {
+            let uri = params.text_document.uri;
+            let Some(text) = self.documents.get(&uri) else {
+                return Ok(None);
+            };
+            let mut actions = vec![];
+            if let Some(toolong) = params
+                .context
+                .diagnostics
+                .iter()
+                .find(|dia| dia.message.starts_with("[C0301]"))
+            {
+                let line = params.range.start.line;
+                if let Some(mut fix) = quick_fix::lint_fix_action(&text, line, toolong, uri.clone())
+                {
+                    actions.append(&mut fix);
+                }
+            }
+            if let Some(action) = quick_fix::preamble_action(&text, uri.clone()) {
+                actions.push(action);
+            }
+            for diagnose in &params.context.diagnostics {
+                if let Some(mut fix) = quick_fix::issue_fix_action(&text, diagnose, uri.clone()) {
+                    actions.append(&mut fix);
+                }
+            }
+            if let Some(action) = quick_fix::whitespace_action(&text, uri.clone()) {
+                actions.push(action);
+            }
+            if let Some(action) = quick_fix::print_variable_action(&text, params.range, uri.clone())
+            {
+                actions.push(action);
+            }
+            if let Some(action) = extract::extract_function_action(&text, params.range, uri.clone())
+            {
+                actions.push(action);
+            }
+            if let Some(action) = extract::extract_file_action(&text, params.range, uri.clone()) {
+                actions.push(action);
+            }
+            actions.append(&mut extract::inline_include_actions(
+                &text,
+                params.range.start.line,
+                uri.clone(),
+            ));
+            actions.append(&mut extract::move_target_actions(
+                &text,
+                params.range.start.line,
+                uri.clone(),
+            ));
+            if let Some(action) =
+                extract::source_group_action(&text, params.range.start.line, uri.clone())
+            {
+                actions.push(action);
+            }
+            if actions.is_empty() {
+                return Ok(None);
+            }
+            Ok(Some(actions))
+        })
+        .await?
     }
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
-        let uri = params.text_document.uri;
-        let text = params.content_changes.into_iter().next().unwrap().text;
-        self.documents.insert(uri.clone(), text);
-        let text = self.documents.get(&uri).unwrap();
-        if text.lines().count() < 500 {
-            self.publish_diagnostics(
-                uri.clone(),
-                &text,
-                LintConfigInfo {
-                    use_lint: self.init_info().enable_lint,
-                    use_extra_cmake_lint: false,
-                },
-            )
-            .await;
-        }
-        self.client
-            .log_message(MessageType::INFO, &format!("update file: {}", uri.as_str()))
+        let site = Site::new("textDocument/didChange").document(&params.text_document.uri);
+        let _ = self
+            .guard(site, async move {
+                let uri = params.text_document.uri;
+                if uri.to_file_path().is_ok_and(presets::is_ignored_json) {
+                    return;
+                }
+                {
+                    // The tree is replaced before the document is released, so
+                    // no request sees the new text with the old tree
+                    let mut document = self.documents.entry(uri.clone()).or_default();
+                    let mut tree = self.trees.get(&uri).map(|tree| tree.clone());
+                    for change in params.content_changes {
+                        match change.range {
+                            Some(range) => {
+                                let edit = document.edit(range, &change.text);
+                                if let Some(tree) = tree.as_mut() {
+                                    tree.edit(&edit);
+                                }
+                            }
+                            None => {
+                                *document = Document::new(change.text);
+                                tree = None;
+                            }
+                        }
+                    }
+                    self.trees
+                        .insert(uri.clone(), document.parse(tree.as_ref()));
+                    self.line_indexes.remove(&uri);
+                }
+                let change = {
+                    let mut changes = self.changes.entry(uri.clone()).or_default();
+                    *changes += 1;
+                    *changes
+                };
+                let debounce = self.init_info().lint_debounce;
+                if debounce.is_zero() {
+                    self.check_change(uri).await;
+                    return;
+                }
+                // The handler returns at once, a sleeping one would hold a slot
+                // the requests like completion wait for
+                let backend = self.clone();
+                self.spawn_background(async move {
+                    tokio::time::sleep(debounce).await;
+                    // Another change came meanwhile, its own check is waiting
+                    if backend.is_shutting_down()
+                        || backend
+                            .changes
+                            .get(&uri)
+                            .is_none_or(|changes| *changes != change)
+                    {
+                        return;
+                    }
+                    backend.check_change(uri).await;
+                });
+            })
             .await;
     }
 
     async fn did_save(&self, params: DidSaveTextDocumentParams) {
-        let uri = params.text_document.uri;
-
-        let has_root = self.root_path().is_some();
-        let Some(text) = self.documents.get(&uri) else {
-            self.client
-                .log_message(MessageType::INFO, "file saved!")
-                .await;
-            return;
-        };
-        let file_path = match uri.to_file_path() {
-            Ok(file_path) => file_path,
-            Err(_) => {
-                tracing::error!("Cannot get file_path from {}", uri.as_str());
-                return;
-            }
-        };
-        if has_root {
-            scansubs::scan_dir(&file_path, false).await;
-            complete::update_cache(&file_path, &text).await;
-            jump::update_cache(&file_path, &text).await;
-        }
-        self.publish_diagnostics(
-            uri,
-            &text,
-            LintConfigInfo {
-                use_lint: self.init_info().enable_lint,
-                use_extra_cmake_lint: CONFIG.enable_external_cmake_lint,
-            },
-        )
-        .await;
+        let site = Site::new("textDocument/didSave").document(&params.text_document.uri);
+        let _ = self
+            .guard(site, async move {
+                let uri = params.text_document.uri;
+
+                let has_root = self.root_path().is_some();
+                let Some(text) = self.documents.get(&uri) else {
+                    self.client
+                        .log_message(MessageType::INFO, "file saved!")
+                        .await;
+                    return;
+                };
+                let file_path = match self.document_path(&uri) {
+                    Some(file_path) => file_path,
+                    None => {
+                        tracing::error!("Cannot get file_path from {}", uri.as_str());
+                        return;
+                    }
+                };
+                scansubs::remove_overlay(&file_path);
+                if has_root {
+                    // A file outside of the workspace is not part of the project
+                    if self.path_in_project(&file_path) {
+                        scansubs::scan_dir(&file_path, false).await;
+                    }
+                    complete::update_cache(&file_path, &text).await;
+                    jump::update_cache(&file_path, &text).await;
+                }
+                if self.init_info().lint_run != LintRun::Manual {
+                    self.publish_diagnostics(
+                        uri,
+                        &text,
+                        LintConfigInfo {
+                            use_lint: self.init_info().enable_lint,
+                            use_extra_cmake_lint: CONFIG.enable_external_cmake_lint,
+                            detached: false,
+                        },
+                    )
+                    .await;
+                }
+                self.save_session();
 
-        self.client
-            .log_message(MessageType::INFO, "file saved!")
+                self.client
+                    .log_message(MessageType::INFO, "file saved!")
+                    .await;
+            })
             .await;
     }
 
     async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
-        let position = params.text_document_position_params.position;
-        let uri = params.text_document_position_params.text_document.uri;
-        let Some(text) = self.documents.get(&uri) else {
-            return Ok(None);
-        };
-        let mut parse = Parser::new();
-        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
-        let tree = parse.parse(text.value(), None).unwrap();
-        let output = hover::get_hovered_doc(position, tree.root_node(), &text).await;
-        match output {
-            Some(context) => Ok(Some(Hover {
-                contents: HoverContents::Scalar(MarkedString::String(context)),
-                range: Some(Range {
-                    start: position,
-                    end: position,
-                }),
-            })),
-            None => Ok(None),
-        }
+        let site = Site::new("textDocument/hover").at(&params.text_document_position_params);
+        self.guard(site, async move {
+            let position = params.text_document_position_params.position;
+            let uri = params.text_document_position_params.text_document.uri;
+            let Some(text) = self.documents.get(&uri) else {
+                return Ok(None);
+            };
+            if let Some(file_path) = self.document_path(&uri)
+                && configure_file::is_template(&file_path)
+            {
+                return Ok(
+                    configure_file::hover(&file_path, &text, position, &self.documents).await,
+                );
+            }
+            if let Some(file_path) = self.document_path(&uri)
+                && presets::is_presets(&file_path)
+            {
+                return Ok(presets::hover(&file_path, &text, position));
+            }
+            let tree = self.tree(&uri, &text);
+            let variable_range = hover::get_hovered_range(position, tree.root_node(), &text);
+            if variable_range.is_none()
+                && let Some(file_path) = self.document_path(&uri)
+                && let Some((preview, range)) =
+                    hover::get_file_preview(&file_path, position, tree.root_node(), &text)
+            {
+                return Ok(Some(Hover {
+                    contents: HoverContents::Scalar(MarkedString::String(preview)),
+                    range: Some(range),
+                }));
+            }
+            if variable_range.is_none()
+                && let Some(file_path) = self.document_path(&uri)
+                && let Some((summary, range)) =
+                    hover::get_target_summary(&file_path, position, tree.root_node(), &text)
+            {
+                return Ok(Some(Hover {
+                    contents: HoverContents::Scalar(MarkedString::String(summary)),
+                    range: Some(range),
+                }));
+            }
+            let output = hover::get_hovered_doc(position, tree.root_node(), &text).await;
+            let range = variable_range.unwrap_or(Range {
+                start: position,
+                end: position,
+            });
+            match output {
+                Some(context) => Ok(Some(Hover {
+                    contents: HoverContents::Scalar(MarkedString::String(context)),
+                    range: Some(range),
+                })),
+                None => Ok(None),
+            }
+        })
+        .await?
     }
 
     async fn signature_help(&self, params: SignatureHelpParams) -> Result<Option<SignatureHelp>> {
-        let position = params.text_document_position_params.position;
-        let uri = params.text_document_position_params.text_document.uri;
-        let Some(text) = self.documents.get(&uri) else {
-            return Ok(None);
-        };
-        Ok(signature_help::get_signature_help(&text, position))
+        let site =
+            Site::new("textDocument/signatureHelp").at(&params.text_document_position_params);
+        self.guard(site, async move {
+            let position = params.text_document_position_params.position;
+            let uri = params.text_document_position_params.text_document.uri;
+            let Some(text) = self.documents.get(&uri) else {
+                return Ok(None);
+            };
+            Ok(signature_help::get_signature_help(
+                &text,
+                &self.tree(&uri, &text),
+                position,
+                params.context.as_ref(),
+            ))
+        })
+        .await?
     }
 
     async fn formatting(&self, input: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
-        self.client
-            .log_message(
-                MessageType::INFO,
-                format!("formatting, space is {}", input.options.insert_spaces),
-            )
-            .await;
-        let uri = input.text_document.uri;
-        let space_line = if input.options.insert_spaces {
-            input.options.tab_size
-        } else {
-            1
-        };
-        let insert_final_newline = input.options.insert_final_newline.unwrap_or(false);
-        match self.documents.get(&uri) {
-            Some(text) => Ok(getformat(
-                self.root_path().map(|p| p.as_path()),
-                &text,
-                &self.client,
-                space_line,
-                input.options.insert_spaces,
-                insert_final_newline,
-            )
-            .await),
-            None => Ok(None),
-        }
+        let site = Site::new("textDocument/formatting").document(&input.text_document.uri);
+        self.guard(site, async move {
+            self.client
+                .log_message(
+                    MessageType::INFO,
+                    format!("formatting, space is {}", input.options.insert_spaces),
+                )
+                .await;
+            let uri = input.text_document.uri;
+            let space_line = if input.options.insert_spaces {
+                input.options.tab_size
+            } else {
+                1
+            };
+            let insert_final_newline = input.options.insert_final_newline.unwrap_or(false);
+            match self.documents.get(&uri) {
+                Some(text) => Ok(getformat(
+                    self.root_path().map(|p| p.as_path()),
+                    self.document_path(&uri).as_deref(),
+                    &text,
+                    &self.client,
+                    space_line,
+                    input.options.insert_spaces,
+                    insert_final_newline,
+                )
+                .await),
+                None => Ok(None),
+            }
+        })
+        .await?
+    }
+
+    async fn execute_command(&self, params: ExecuteCommandParams) -> Result<Option<LSPAny>> {
+        let site = Site::new("workspace/executeCommand");
+        self.guard(site, async move {
+            if params.command != LINT_COMMAND {
+                return Err(LspError::invalid_params(format!(
+                    "Unknown command {}",
+                    params.command
+                )));
+            }
+            // Without arguments, every open document is checked
+            let mut uris: Vec<Uri> = params
+                .arguments
+                .into_iter()
+                .filter_map(|argument| serde_json::from_value(argument).ok())
+                .collect();
+            if uris.is_empty() {
+                uris = self
+                    .documents
+                    .iter()
+                    .map(|item| item.key().clone())
+                    .collect();
+            }
+            for uri in uris {
+                let Some(text) = self.documents.get(&uri).map(|text| text.clone()) else {
+                    continue;
+                };
+                self.publish_diagnostics(
+                    uri,
+                    &text,
+                    LintConfigInfo {
+                        use_lint: self.init_info().enable_lint,
+                        use_extra_cmake_lint: CONFIG.enable_external_cmake_lint,
+                        detached: false,
+                    },
+                )
+                .await;
+            }
+            Ok(None)
+        })
+        .await?
     }
 
     async fn did_close(&self, params: DidCloseTextDocumentParams) {
-        self.client
-            .log_message(
-                MessageType::INFO,
-                format!("file {:?} closed!", params.text_document.uri),
-            )
+        let site = Site::new("textDocument/didClose").document(&params.text_document.uri);
+        let _ = self
+            .guard(site, async move {
+                let uri = &params.text_document.uri;
+                self.open_documents.remove(uri);
+                // A debounced check still waiting is dropped with it
+                self.changes.remove(uri);
+                // The changes not saved are dropped, and an untitled document is gone
+                if let Some(path) = self.document_path(uri) {
+                    if document::is_untitled(uri) {
+                        scansubs::set_overlay(&path, "");
+                    } else {
+                        scansubs::remove_overlay(&path);
+                    }
+                    if document::is_untitled(uri) || self.path_in_project(&path) {
+                        scansubs::scan_dir(&path, false).await;
+                    } else {
+                        function_doc::forget_file(&path);
+                    }
+                    scansubs::remove_overlay(&path);
+                }
+                self.save_session();
+                self.schedule_trim();
+                self.client
+                    .log_message(
+                        MessageType::INFO,
+                        format!("file {:?} closed!", params.text_document.uri),
+                    )
+                    .await;
+            })
             .await;
     }
 
     async fn completion(&self, input: CompletionParams) -> Result<Option<CompletionResponse>> {
-        self.client.log_message(MessageType::INFO, "Complete").await;
-        let location = input.text_document_position.position;
-        let uri = input.text_document_position.text_document.uri;
-        let file_path = match uri.to_file_path() {
-            Ok(file_path) => file_path,
-            Err(_) => {
-                tracing::error!("Cannot get file_path from {}", uri.as_str());
-                return Err(LspError::internal_error());
+        let site = Site::new("textDocument/completion").at(&input.text_document_position);
+        self.guard(site, async move {
+            self.client.log_message(MessageType::INFO, "Complete").await;
+            let location = input.text_document_position.position;
+            let uri = input.text_document_position.text_document.uri;
+            let file_path = match self.document_path(&uri) {
+                Some(file_path) => file_path,
+                None => {
+                    tracing::error!("Cannot get file_path from {}", uri.as_str());
+                    return Err(LspError::internal_error());
+                }
+            };
+            let Some(text) = self.documents.get(&uri) else {
+                return Ok(None);
+            };
+            if configure_file::is_template(&file_path) {
+                return Ok(
+                    configure_file::complete(&file_path, &text, location, &self.documents).await,
+                );
             }
-        };
-        let Some(text) = self.documents.get(&uri) else {
-            return Ok(None);
-        };
-        Ok(complete::getcomplete(
-            &text,
-            location,
-            &self.client,
-            &file_path,
-            self.init_info().scan_cmake_in_package,
-            &self.documents,
-        )
-        .await)
+            if presets::is_presets(&file_path) {
+                return Ok(presets::complete(&file_path, &text, location));
+            }
+            if vcpkg::is_manifest(&file_path) {
+                return Ok(vcpkg::complete(&file_path, &text, location));
+            }
+            let lines = self.line_index(&uri, &text);
+            Ok(complete::getcomplete(
+                &text,
+                &self.tree(&uri, &text),
+                &lines,
+                location,
+                &self.client,
+                &file_path,
+                self.init_info().scan_cmake_in_package,
+                &self.documents,
+            )
+            .await)
+        })
+        .await?
+    }
+
+    async fn completion_resolve(&self, item: CompletionItem) -> Result<CompletionItem> {
+        let site = Site::new("completionItem/resolve");
+        self.guard(site, async move {
+            let item = self.resolve_stripped(item).await;
+            Ok(complete::builtin::resolve_documentation(item))
+        })
+        .await?
     }
 
     async fn references(&self, input: ReferenceParams) -> Result<Option<Vec<Location>>> {
-        let uri = input.text_document_position.text_document.uri;
-        let location = input.text_document_position.position;
-        let Some(text) = self.documents.get(&uri) else {
-            return Ok(None);
-        };
-        let file_path = match uri.to_file_path() {
-            Ok(file_path) => file_path,
-            Err(_) => {
-                tracing::error!("Cannot get file_path from {uri:?}");
-                return Err(LspError::internal_error());
-            }
-        };
-        Ok(jump::godef(
-            location,
-            &text,
-            &file_path,
-            &self.client,
-            false,
-            false,
-            &self.documents,
-        )
-        .await)
+        let site = Site::new("textDocument/references").at(&input.text_document_position);
+        self.guard(site, async move {
+            let uri = input.text_document_position.text_document.uri;
+            let location = input.text_document_position.position;
+            let Some(text) = self.documents.get(&uri) else {
+                return Ok(None);
+            };
+            let file_path = match self.document_path(&uri) {
+                Some(file_path) => file_path,
+                None => {
+                    tracing::error!("Cannot get file_path from {uri:?}");
+                    return Err(LspError::internal_error());
+                }
+            };
+            Ok(jump::godef(
+                location,
+                &text,
+                &file_path,
+                &self.client,
+                false,
+                false,
+                &self.documents,
+            )
+            .await
+            .map(|locations| self.untitled_locations(&uri, locations)))
+        })
+        .await?
     }
 
     async fn rename(&self, input: RenameParams) -> Result<Option<WorkspaceEdit>> {
-        let edited = input.new_name;
-        let uri = input.text_document_position.text_document.uri;
-        let location = input.text_document_position.position;
-        let Some(text) = self.documents.get(&uri) else {
-            return Ok(None);
-        };
-        let file_path = match uri.to_file_path() {
-            Ok(file_path) => file_path,
-            Err(_) => {
-                tracing::error!("Cannot get file_path from {uri:?}");
-                return Err(LspError::internal_error());
+        let site = Site::new("textDocument/rename").at(&input.text_document_position);
+        self.guard(site, async move {
+            let edited = input.new_name;
+            let uri = input.text_document_position.text_document.uri;
+            let location = input.text_document_position.position;
+            let Some(text) = self.documents.get(&uri) else {
+                return Ok(None);
+            };
+            let file_path = match self.document_path(&uri) {
+                Some(file_path) => file_path,
+                None => {
+                    tracing::error!("Cannot get file_path from {uri:?}");
+                    return Err(LspError::internal_error());
+                }
+            };
+            let mut edit = rename::rename(
+                &edited,
+                location,
+                file_path,
+                &self.client,
+                &text,
+                self.root_path().map(PathBuf::as_path),
+                &self.documents,
+            )
+            .await;
+            // The edits of an untitled document are applied to its buffer
+            if let Some(file_uri) = self.untitled_file_uri(&uri)
+                && let Some(changes) = edit.as_mut().and_then(|edit| edit.changes.as_mut())
+                && let Some(edits) = changes.remove(&file_uri)
+            {
+                changes.insert(uri.clone(), edits);
             }
-        };
-        Ok(rename::rename(
-            &edited,
-            location,
-            file_path,
-            &self.client,
-            &text,
-            &self.documents,
-        )
-        .await)
+            Ok(edit)
+        })
+        .await?
     }
 
     async fn goto_definition(
         &self,
         input: GotoDefinitionParams,
     ) -> Result<Option<GotoDefinitionResponse>> {
-        let uri = input.text_document_position_params.text_document.uri;
-        let location = input.text_document_position_params.position;
-        let Some(text) = self.documents.get(&uri) else {
-            return Ok(None);
-        };
+        let site = Site::new("textDocument/definition").at(&input.text_document_position_params);
+        self.guard(site, async move {
+            let uri = input.text_document_position_params.text_document.uri;
+            let location = input.text_document_position_params.position;
+            let Some(text) = self.documents.get(&uri) else {
+                return Ok(None);
+            };
+            let file_path = match self.document_path(&uri) {
+                Some(file_path) => file_path,
+                None => {
+                    tracing::error!("Cannot get file_path from {uri:?}");
+                    return Err(LspError::internal_error());
+                }
+            };
+            if configure_file::is_template(&file_path) {
+                return Ok(configure_file::goto_definition(
+                    &file_path,
+                    &text,
+                    location,
+                    &self.documents,
+                )
+                .await);
+            }
 
-        let mut parse = Parser::new();
-        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
-        let tree = parse.parse(text.value(), None).unwrap();
-        let origin_selection_range = treehelper::get_position_range(location, tree.root_node());
+            let tree = self.tree(&uri, &text);
+            let origin_selection_range = treehelper::get_position_range(location, tree.root_node());
 
-        let file_path = match uri.to_file_path() {
-            Ok(file_path) => file_path,
-            Err(_) => {
-                tracing::error!("Cannot get file_path from {uri:?}");
-                return Err(LspError::internal_error());
+            match jump::godef(
+                location,
+                &text,
+                &file_path,
+                &self.client,
+                true,
+                false,
+                &self.documents,
+            )
+            .await
+            .map(|locations| self.untitled_locations(&uri, locations))
+            {
+                Some(range) => Ok(Some(GotoDefinitionResponse::Link({
+                    range
+                        .iter()
+                        .filter(|input| match origin_selection_range {
+                            Some(origin) => origin != input.range,
+                            None => true,
+                        })
+                        .map(|range| LocationLink {
+                            origin_selection_range,
+                            target_uri: range.uri.clone(),
+                            target_range: range.range,
+                            target_selection_range: range.range,
+                        })
+                        .collect()
+                }))),
+                None => Ok(None),
             }
-        };
-        match jump::godef(
-            location,
-            &text,
-            &file_path,
-            &self.client,
-            true,
-            false,
-            &self.documents,
-        )
-        .await
-        {
-            Some(range) => Ok(Some(GotoDefinitionResponse::Link({
-                range
-                    .iter()
-                    .filter(|input| match origin_selection_range {
-                        Some(origin) => origin != input.range,
-                        None => true,
-                    })
-                    .map(|range| LocationLink {
-                        origin_selection_range,
-                        target_uri: range.uri.clone(),
-                        target_range: range.range,
-                        target_selection_range: range.range,
-                    })
-                    .collect()
-            }))),
-            None => Ok(None),
-        }
+        })
+        .await?
     }
 
     async fn document_symbol(
         &self,
         input: DocumentSymbolParams,
     ) -> Result<Option<DocumentSymbolResponse>> {
-        let uri = input.text_document.uri;
-        match self.documents.get(&uri) {
-            Some(text) => Ok(ast::getast(&self.client, &text).await),
-            None => Ok(None),
-        }
+        let site = Site::new("textDocument/documentSymbol").document(&input.text_document.uri);
+        self.guard(site, async move {
+            let uri = input.text_document.uri;
+            match self.documents.get(&uri) {
+                Some(text) => Ok(ast::getast(&self.client, &text).await),
+                None => Ok(None),
+            }
+        })
+        .await?
     }
 
     async fn semantic_tokens_full(
         &self,
         params: SemanticTokensParams,
     ) -> Result<Option<SemanticTokensResult>> {
-        let uri = params.text_document.uri.clone();
-
-        match self.documents.get(&uri) {
-            Some(text) => Ok(semantic_token::semantic_token(&self.client, &text).await),
-            None => Ok(None),
-        }
+        let site =
+            Site::new("textDocument/semanticTokens/full").document(&params.text_document.uri);
+        self.guard(site, async move {
+            let uri = params.text_document.uri.clone();
+
+            match self.documents.get(&uri) {
+                Some(text) => Ok(semantic_token::semantic_token(&self.client, &text).await),
+                None => Ok(None),
+            }
+        })
+        .await?
     }
 
     async fn document_link(&self, input: DocumentLinkParams) -> Result<Option<Vec<DocumentLink>>> {
-        let uri = input.text_document.uri;
-        let file_path = match uri.to_file_path() {
-            Ok(file_path) => file_path,
-            Err(_) => {
-                tracing::error!("Cannot get file_path from {uri:?}");
-                return Err(LspError::internal_error());
-            }
-        };
-        let Some(text) = self.documents.get(&uri) else {
-            return Ok(None);
-        };
-        Ok(document_link::document_link_search(&text, file_path))
+        let site = Site::new("textDocument/documentLink").document(&input.text_document.uri);
+        self.guard(site, async move {
+            let uri = input.text_document.uri;
+            let file_path = match self.document_path(&uri) {
+                Some(file_path) => file_path,
+                None => {
+                    tracing::error!("Cannot get file_path from {uri:?}");
+                    return Err(LspError::internal_error());
+                }
+            };
+            let Some(text) = self.documents.get(&uri) else {
+                return Ok(None);
+            };
+            Ok(document_link::document_link_search(&text, file_path))
+        })
+        .await?
+    }
+
+    async fn on_type_formatting(
+        &self,
+        input: DocumentOnTypeFormattingParams,
+    ) -> Result<Option<Vec<TextEdit>>> {
+        let site = Site::new("textDocument/onTypeFormatting").at(&input.text_document_position);
+        self.guard(site, async move {
+            let position = input.text_document_position;
+            let Some(text) = self.documents.get(&position.text_document.uri) else {
+                return Ok(None);
+            };
+            Ok(auto_close::close_block(&text, position.position))
+        })
+        .await?
+    }
+
+    async fn folding_range(&self, input: FoldingRangeParams) -> Result<Option<Vec<FoldingRange>>> {
+        let site = Site::new("textDocument/foldingRange").document(&input.text_document.uri);
+        self.guard(site, async move {
+            let Some(text) = self.documents.get(&input.text_document.uri) else {
+                return Ok(None);
+            };
+            Ok(folding::folding_ranges(&text))
+        })
+        .await?
+    }
+
+    async fn inlay_hint(&self, input: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
+        let site = Site::new("textDocument/inlayHint").document(&input.text_document.uri);
+        self.guard(site, async move {
+            let Some(text) = self.documents.get(&input.text_document.uri) else {
+                return Ok(None);
+            };
+            Ok(inlay_hint::inlay_hints(&text, input.range))
+        })
+        .await?
     }
 }
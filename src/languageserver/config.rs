@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize, Serialize, PartialEq, Eq, Debug)]
@@ -12,6 +14,15 @@ pub struct Config {
     pub lint: LintConfig,
     #[serde(default = "default_enable")]
     pub use_snippets: bool,
+    /// Insert `endif()` and the other terminators after a line opening a block
+    #[serde(default)]
+    pub auto_close_blocks: bool,
+    /// Save the open documents and their diagnostics, to resume with them after a restart
+    #[serde(default)]
+    pub session: bool,
+    /// Trim the caches past this many megabytes
+    #[serde(default)]
+    pub memory_budget_mb: Option<u64>,
 }
 
 const fn scan_cmake_in_package_default() -> bool {
@@ -34,9 +45,35 @@ impl Config {
         self.lint.enable
     }
 
+    pub fn lint_run(&self) -> LintRun {
+        self.lint.run
+    }
+
+    pub fn lint_debounce(&self) -> Duration {
+        Duration::from_millis(self.lint.debounce_ms)
+    }
+
+    pub fn lint_closed_files(&self) -> bool {
+        self.lint.closed_files
+    }
+
     pub fn use_snippets(&self) -> bool {
         self.use_snippets
     }
+
+    pub fn auto_close_blocks(&self) -> bool {
+        self.auto_close_blocks
+    }
+
+    pub fn persist_session(&self) -> bool {
+        self.session
+    }
+
+    /// The memory budget in bytes
+    pub fn memory_budget(&self) -> Option<usize> {
+        self.memory_budget_mb
+            .map(|megabytes| (megabytes as usize).saturating_mul(1024 * 1024))
+    }
 }
 
 impl Default for Config {
@@ -47,6 +84,9 @@ impl Default for Config {
             semantic_token: false,
             lint: LintConfig::default(),
             use_snippets: true,
+            auto_close_blocks: false,
+            session: false,
+            memory_budget_mb: None,
         }
     }
 }
@@ -67,21 +107,51 @@ impl Default for FormatConfig {
     }
 }
 
+/// When the diagnostics of a document are computed
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LintRun {
+    /// On change, once no change came for `debounce_ms`, and on save
+    #[default]
+    #[serde(alias = "onType")]
+    OnType,
+    #[serde(alias = "onSave")]
+    OnSave,
+    /// Only through the `neocmake.lint` command
+    Manual,
+}
+
+const fn default_debounce_ms() -> u64 {
+    300
+}
+
 #[derive(Deserialize, Serialize, PartialEq, Eq, Debug)]
 pub struct LintConfig {
     #[serde(default = "default_enable")]
     pub enable: bool,
+    #[serde(default)]
+    pub run: LintRun,
+    #[serde(default = "default_debounce_ms")]
+    pub debounce_ms: u64,
+    /// Check the files of the project not open in the editor too, once it is indexed
+    #[serde(default)]
+    pub closed_files: bool,
 }
 
 impl Default for LintConfig {
     fn default() -> Self {
-        LintConfig { enable: true }
+        LintConfig {
+            enable: true,
+            run: LintRun::default(),
+            debounce_ms: default_debounce_ms(),
+            closed_files: false,
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::Config;
+    use super::{Config, LintRun};
     #[test]
     fn config_test() {
         let data = r#"{}"#;
@@ -90,5 +160,15 @@ mod test {
         assert!(config.use_snippets);
         assert!(config.is_lint_enabled());
         assert!(config.is_format_enabled());
+        assert_eq!(config.lint_run(), LintRun::OnType);
+    }
+
+    #[test]
+    fn lint_run_test() {
+        let data = r#"{"lint": {"run": "onSave", "debounce_ms": 1000}}"#;
+        let config: Config = serde_json::from_str(data).unwrap();
+        assert!(config.is_lint_enabled());
+        assert_eq!(config.lint_run(), LintRun::OnSave);
+        assert_eq!(config.lint_debounce().as_millis(), 1000);
     }
 }
@@ -0,0 +1,67 @@
+//! Stopping on the `exit` notification.
+//!
+//! After `exit`, the server of tower-lsp only stops on the next message or when
+//! the input is closed, which the editors waiting for the process to end never
+//! do. [`serve`] stops as soon as `exit` comes instead.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::Notify;
+use tower_lsp::Server;
+use tower_lsp::jsonrpc::Request;
+use tower_service::Service;
+
+/// The service of the server, telling when `exit` comes and whether `shutdown` came before
+struct ExitSignal<S> {
+    inner: S,
+    exited: Arc<Notify>,
+    shut_down: Arc<AtomicBool>,
+}
+
+impl<S: Service<Request>> Service<Request> for ExitSignal<S> {
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let is_exit = request.method() == "exit";
+        if request.method() == "shutdown" {
+            self.shut_down.store(true, Ordering::Release);
+        }
+        let response = self.inner.call(request);
+        if is_exit {
+            self.exited.notify_one();
+        }
+        response
+    }
+}
+
+/// Serve the language server on `input` and `output` until the `exit` notification.
+/// Returns the exit code of the process: 1 when `exit` came without a `shutdown` first.
+pub(crate) async fn serve<I, O>(input: I, output: O) -> i32
+where
+    I: AsyncRead + Unpin,
+    O: AsyncWrite,
+{
+    let (service, socket) = super::service();
+    let exited = Arc::new(Notify::new());
+    let shut_down = Arc::new(AtomicBool::new(false));
+    let service = ExitSignal {
+        inner: service,
+        exited: exited.clone(),
+        shut_down: shut_down.clone(),
+    };
+    tokio::select! {
+        () = Server::new(input, output, socket).serve(service) => 0,
+        () = exited.notified() => {
+            if shut_down.load(Ordering::Acquire) { 0 } else { 1 }
+        }
+    }
+}
@@ -0,0 +1,188 @@
+//! Crash resilience of the handlers.
+//!
+//! A panic in one feature, like a regex edge case while reading a help page,
+//! would take the whole server down with every other feature. Each handler
+//! runs in [`Backend::guard`] instead, so the panic only fails its request, and
+//! a report with the document around the position goes to the log.
+
+use std::any::Any;
+use std::future::{Future, poll_fn};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::pin::pin;
+use std::task::Poll;
+
+use tower_lsp::jsonrpc::{Error as LspError, ErrorCode, Result};
+use tower_lsp::lsp_types::{MessageType, Position, TextDocumentPositionParams, Uri};
+
+use crate::Backend;
+
+/// The lines shown before and after the position in a report
+const EXCERPT_CONTEXT: usize = 3;
+
+/// The request being handled, for the report of its crash
+#[derive(Debug)]
+pub(super) struct Site {
+    method: &'static str,
+    uri: Option<Uri>,
+    position: Option<Position>,
+}
+
+impl Site {
+    pub fn new(method: &'static str) -> Self {
+        Self {
+            method,
+            uri: None,
+            position: None,
+        }
+    }
+
+    /// A request about the document `uri`
+    pub fn document(mut self, uri: &Uri) -> Self {
+        self.uri = Some(uri.clone());
+        self
+    }
+
+    /// A request about a position of a document
+    pub fn at(mut self, params: &TextDocumentPositionParams) -> Self {
+        self.position = Some(params.position);
+        self.document(&params.text_document.uri)
+    }
+}
+
+/// Run `future`, catching a panic while it is polled
+async fn catch_panic<F: Future>(future: F) -> std::result::Result<F::Output, Box<dyn Any + Send>> {
+    let mut future = pin!(future);
+    poll_fn(
+        |cx| match catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(cx))) {
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(payload) => Poll::Ready(Err(payload)),
+        },
+    )
+    .await
+}
+
+/// The message given to `panic!`
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+/// The numbered lines of `text` around `row`, the line of `row` marked with `>`
+fn excerpt(text: &str, row: usize) -> String {
+    text.lines()
+        .enumerate()
+        .skip(row.saturating_sub(EXCERPT_CONTEXT))
+        .take_while(|(index, _)| *index <= row + EXCERPT_CONTEXT)
+        .map(|(index, line)| {
+            let marker = if index == row { '>' } else { ' ' };
+            format!("{marker}{:>5} | {line}", index + 1)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+impl Backend {
+    /// Run the handler `future` of a request, a panic in it becomes an internal
+    /// error for this request and a report in the log
+    pub(super) async fn guard<F: Future>(&self, site: Site, future: F) -> Result<F::Output> {
+        let message = match catch_panic(future).await {
+            Ok(output) => return Ok(output),
+            Err(payload) => panic_message(&*payload),
+        };
+        let report = self.crash_report(&site, &message);
+        tracing::error!("{report}");
+        self.client.log_message(MessageType::ERROR, report).await;
+        Err(LspError {
+            code: ErrorCode::InternalError,
+            message: format!("{} failed: {message}", site.method).into(),
+            data: None,
+        })
+    }
+
+    fn crash_report(&self, site: &Site, message: &str) -> String {
+        let mut report = format!("Panic while handling {}: {message}", site.method);
+        let Some(uri) = &site.uri else {
+            return report;
+        };
+        report.push_str(&format!("\nDocument: {}", uri.as_str()));
+        let row = match site.position {
+            Some(position) => {
+                report.push_str(&format!(
+                    "\nPosition: {}:{}",
+                    position.line + 1,
+                    position.character + 1
+                ));
+                position.line as usize
+            }
+            None => 0,
+        };
+        if let Some(text) = self.documents.get(uri) {
+            report.push('\n');
+            report.push_str(&excerpt(&text, row));
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn feature(input: &str) -> usize {
+        tokio::task::yield_now().await;
+        if input.is_empty() {
+            panic!("empty input");
+        }
+        input.parse().unwrap_or_else(|_| panic!("bad {input}"))
+    }
+
+    #[tokio::test]
+    async fn test_catch_panic() {
+        assert_eq!(catch_panic(feature("1")).await.ok(), Some(1));
+        let payload = catch_panic(feature("regex")).await.unwrap_err();
+        assert_eq!(panic_message(&*payload), "bad regex");
+        let payload = catch_panic(feature("")).await.unwrap_err();
+        assert_eq!(panic_message(&*payload), "empty input");
+    }
+
+    #[tokio::test]
+    async fn test_guarded_handler() {
+        use futures::StreamExt;
+        use tower_lsp::LanguageServer;
+
+        let (service, socket) = crate::languageserver::service();
+        // The crash report is sent to the client
+        tokio::spawn(socket.for_each(|_| async {}));
+        let backend = service.inner();
+        backend.initialize(Default::default()).await.unwrap();
+        let error = backend
+            .guard(Site::new("textDocument/hover"), async {
+                let _background = backend.background.lock().unwrap();
+                panic!("broken handler");
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(error.code, ErrorCode::InternalError);
+        assert_eq!(error.message, "textDocument/hover failed: broken handler");
+        // The lock left poisoned by the panic does not fail the next requests
+        assert!(backend.background.is_poisoned());
+        assert!(backend.shutdown().await.is_ok());
+    }
+
+    #[test]
+    fn test_excerpt() {
+        let text = "a\nb\nc\nd\ne\nf\ng\nh\ni\n";
+        assert_eq!(
+            excerpt(text, 4),
+            "     2 | b\n     3 | c\n     4 | d\n>    5 | e\n     6 | f\n     7 | g\n     8 | h"
+        );
+        assert_eq!(
+            excerpt(text, 0),
+            ">    1 | a\n     2 | b\n     3 | c\n     4 | d"
+        );
+    }
+}
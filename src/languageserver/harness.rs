@@ -0,0 +1,313 @@
+//! In-process test harness.
+//!
+//! Spins up the real server over an in-memory duplex stream and talks JSON-RPC
+//! to it like an editor would, so features can be covered end to end:
+//!
+//! ```ignore
+//! let mut server = TestServer::start(dir.path()).await;
+//! let uri = server.open(dir.path().join("CMakeLists.txt"), "set(A 1)\n").await;
+//! let diagnostics = server.expect_diagnostics(&uri).await;
+//! let items = server.complete_at(&uri, 0, 4).await;
+//! ```
+
+use std::collections::VecDeque;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
+use tokio::io::{
+    AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, DuplexStream, ReadHalf, WriteHalf,
+};
+use tokio::task::JoinHandle;
+use tower_lsp::lsp_types::notification::Notification;
+use tower_lsp::lsp_types::{
+    ClientCapabilities, CompletionItem, CompletionParams, CompletionResponse, Diagnostic,
+    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+    InitializeParams, InitializeResult, InitializedParams, PartialResultParams, Position,
+    PublishDiagnosticsParams, Range, TextDocumentContentChangeEvent, TextDocumentIdentifier,
+    TextDocumentItem, TextDocumentPositionParams, Uri, VersionedTextDocumentIdentifier,
+    WorkDoneProgressParams, WorkspaceFolder,
+};
+
+use super::Config;
+use crate::indexed::Indexed;
+
+const BUFFER_SIZE: usize = 1 << 16;
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct TestServer {
+    reader: BufReader<ReadHalf<DuplexStream>>,
+    writer: WriteHalf<DuplexStream>,
+    next_id: i64,
+    diagnostics: VecDeque<PublishDiagnosticsParams>,
+    server: JoinHandle<i32>,
+    pub init_result: InitializeResult,
+}
+
+impl TestServer {
+    /// Start a server rooted at `root` with the default config and client capabilities.
+    pub async fn start<P: AsRef<Path>>(root: P) -> Self {
+        Self::start_with(root, Config::default(), ClientCapabilities::default()).await
+    }
+
+    /// Start a server and run the `initialize`/`initialized` handshake.
+    pub async fn start_with<P: AsRef<Path>>(
+        root: P,
+        config: Config,
+        capabilities: ClientCapabilities,
+    ) -> Self {
+        let (client_stream, server_stream) = tokio::io::duplex(BUFFER_SIZE);
+        let (server_read, server_write) = tokio::io::split(server_stream);
+        let (client_read, client_write) = tokio::io::split(client_stream);
+
+        let server = tokio::spawn(super::serve(server_read, server_write));
+
+        let mut test_server = Self {
+            reader: BufReader::new(client_read),
+            writer: client_write,
+            next_id: 0,
+            diagnostics: VecDeque::new(),
+            server,
+            init_result: InitializeResult::default(),
+        };
+
+        let init_param = InitializeParams {
+            workspace_folders: Some(vec![WorkspaceFolder {
+                name: "main".to_string(),
+                uri: Uri::from_file_path(root.as_ref()).unwrap(),
+            }]),
+            initialization_options: Some(serde_json::to_value(config).unwrap()),
+            capabilities,
+            ..Default::default()
+        };
+        test_server.init_result = test_server.request("initialize", init_param).await;
+        test_server
+            .notify("initialized", InitializedParams {})
+            .await;
+        // The checks of the files opened before the end of the indexing run again
+        while test_server
+            .receive()
+            .await
+            .get("method")
+            .and_then(Value::as_str)
+            != Some(Indexed::METHOD)
+        {}
+        test_server
+    }
+
+    /// Send a request and wait for its response, serving anything the server sends meanwhile.
+    pub async fn request<P: Serialize, R: DeserializeOwned>(
+        &mut self,
+        method: &'static str,
+        params: P,
+    ) -> R {
+        match self.try_request(method, params).await {
+            Ok(result) => serde_json::from_value(result).unwrap(),
+            Err(error) => panic!("{method} failed: {error}"),
+        }
+    }
+
+    /// Send a request and wait for its result, or the error of the server.
+    pub async fn try_request<P: Serialize>(
+        &mut self,
+        method: &'static str,
+        params: P,
+    ) -> Result<Value, Value> {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        }))
+        .await;
+
+        loop {
+            let message = self.receive().await;
+            if message.get("method").is_some() || message["id"] != json!(id) {
+                continue;
+            }
+            if let Some(error) = message.get("error") {
+                return Err(error.clone());
+            }
+            return Ok(message["result"].clone());
+        }
+    }
+
+    pub async fn notify<P: Serialize>(&mut self, method: &'static str, params: P) {
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params,
+        }))
+        .await;
+    }
+
+    /// Open `path` with `text` as its in-editor content.
+    pub async fn open<P: AsRef<Path>>(&mut self, path: P, text: &str) -> Uri {
+        let uri = Uri::from_file_path(path.as_ref()).unwrap();
+        self.open_uri(&uri, text).await;
+        uri
+    }
+
+    /// Open the document at `uri`, which may not be a file, like an untitled one.
+    pub async fn open_uri(&mut self, uri: &Uri, text: &str) {
+        self.notify(
+            "textDocument/didOpen",
+            DidOpenTextDocumentParams {
+                text_document: TextDocumentItem {
+                    uri: uri.clone(),
+                    language_id: "cmake".to_string(),
+                    version: 0,
+                    text: text.to_string(),
+                },
+            },
+        )
+        .await;
+    }
+
+    /// Replace `range` of the document with `text`, like an editor sending the
+    /// edits of the user.
+    pub async fn change(&mut self, uri: &Uri, range: Range, text: &str) {
+        self.notify(
+            "textDocument/didChange",
+            DidChangeTextDocumentParams {
+                text_document: VersionedTextDocumentIdentifier {
+                    uri: uri.clone(),
+                    version: 1,
+                },
+                content_changes: vec![TextDocumentContentChangeEvent {
+                    range: Some(range),
+                    range_length: None,
+                    text: text.to_string(),
+                }],
+            },
+        )
+        .await;
+    }
+
+    /// Close the document, dropping its changes which were not saved.
+    pub async fn close(&mut self, uri: &Uri) {
+        self.notify(
+            "textDocument/didClose",
+            DidCloseTextDocumentParams {
+                text_document: TextDocumentIdentifier { uri: uri.clone() },
+            },
+        )
+        .await;
+    }
+
+    pub async fn complete_at(
+        &mut self,
+        uri: &Uri,
+        line: u32,
+        character: u32,
+    ) -> Vec<CompletionItem> {
+        let response: Option<CompletionResponse> = self
+            .request(
+                "textDocument/completion",
+                CompletionParams {
+                    text_document_position: TextDocumentPositionParams {
+                        text_document: TextDocumentIdentifier { uri: uri.clone() },
+                        position: Position { line, character },
+                    },
+                    work_done_progress_params: WorkDoneProgressParams::default(),
+                    partial_result_params: PartialResultParams::default(),
+                    context: None,
+                },
+            )
+            .await;
+        match response {
+            Some(CompletionResponse::Array(items)) => items,
+            Some(CompletionResponse::List(list)) => list.items,
+            None => vec![],
+        }
+    }
+
+    /// Wait for the next diagnostics published for `uri`.
+    pub async fn expect_diagnostics(&mut self, uri: &Uri) -> Vec<Diagnostic> {
+        loop {
+            if let Some(index) = self.diagnostics.iter().position(|item| &item.uri == uri) {
+                return self.diagnostics.remove(index).unwrap().diagnostics;
+            }
+            self.receive().await;
+        }
+    }
+
+    /// Shut the server down like an editor does, and wait for it to stop.
+    pub async fn shutdown(&mut self) {
+        let () = self.request("shutdown", ()).await;
+        assert_eq!(self.exit().await, 0);
+    }
+
+    /// Send `exit`, and wait for the server to stop with its exit code.
+    pub async fn exit(&mut self) -> i32 {
+        self.notify("exit", ()).await;
+        tokio::time::timeout(TIMEOUT, &mut self.server)
+            .await
+            .expect("timed out waiting for the server to stop")
+            .unwrap()
+    }
+
+    async fn send(&mut self, mut message: Value) {
+        // Requests without parameters, like `shutdown`, must not carry a `null` one
+        if message["params"].is_null()
+            && let Some(message) = message.as_object_mut()
+        {
+            message.remove("params");
+        }
+        let body = message.to_string();
+        let frame = format!("Content-Length: {}\r\n\r\n{body}", body.len());
+        self.writer.write_all(frame.as_bytes()).await.unwrap();
+        self.writer.flush().await.unwrap();
+    }
+
+    /// Read one message. Server requests are answered and diagnostics stored
+    /// before the message is handed back.
+    async fn receive(&mut self) -> Value {
+        let message = tokio::time::timeout(TIMEOUT, self.read_message())
+            .await
+            .expect("timed out waiting for the server");
+
+        if let Some(method) = message.get("method").and_then(Value::as_str) {
+            if let Some(id) = message.get("id") {
+                // registerCapability, workDoneProgress/create, ...
+                let reply = json!({ "jsonrpc": "2.0", "id": id, "result": null });
+                self.send(reply).await;
+            } else if method == "textDocument/publishDiagnostics" {
+                let params: PublishDiagnosticsParams =
+                    serde_json::from_value(message["params"].clone()).unwrap();
+                self.diagnostics.push_back(params);
+            }
+        }
+        message
+    }
+
+    async fn read_message(&mut self) -> Value {
+        let mut content_length = 0;
+        loop {
+            let mut header = String::new();
+            let read = self.reader.read_line(&mut header).await.unwrap();
+            assert!(read > 0, "server closed the stream");
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some(length) = header.strip_prefix("Content-Length:") {
+                content_length = length.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0; content_length];
+        self.reader.read_exact(&mut body).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
@@ -0,0 +1,351 @@
+//! The memory budget, with the `memory_budget_mb` init option.
+//!
+//! In a monorepo of thousands of files, the caches grow with every file the
+//! editor visits. Past the budget, the caches filled again on demand are
+//! trimmed: the directory listings, then the parse trees of the closed
+//! documents, then the documentation of the completion items of the closed
+//! files, the least recently used first. The documentation of an item is read
+//! again from its file when the client resolves it. The trimming runs a while
+//! after the documents are opened and closed, not on each of them.
+//! `neocmake/status` reports the usage, and the lint checks which took the
+//! most time.
+
+use std::path::{Path, PathBuf};
+use std::sync::PoisonError;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tower_lsp::jsonrpc::Result;
+use tower_lsp::lsp_types::{CompletionItem, Documentation, Uri};
+
+use super::guard::Site;
+use crate::Backend;
+use crate::complete::{self, COMPLETE_CACHE};
+use crate::gammar::{self, CheckTiming};
+use crate::scanner::DIRECTORY_CACHE;
+use crate::utils::function_doc::FUNCTION_DOCS;
+use crate::utils::line_index::LineIndex;
+
+pub const STATUS_METHOD: &str = "neocmake/status";
+
+/// The checks reported by `neocmake/status`
+const SLOWEST_CHECKS: usize = 5;
+
+/// A rough size of the syntax tree of a byte of source
+const TREE_BYTES_PER_BYTE: usize = 8;
+
+/// How long the documents opened and closed settle before the caches are trimmed
+const TRIM_DELAY: Duration = Duration::from_secs(5);
+
+/// The key of the data of a completion item whose documentation was dropped,
+/// with the file to read it from again
+const STRIPPED_FROM: &str = "documentationOf";
+
+/// The estimated bytes taken by the caches
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryUsage {
+    /// The text of the documents
+    pub documents: usize,
+    /// The syntax trees and the line indexes of the documents
+    pub parse_trees: usize,
+    pub directory_cache: usize,
+    /// The documentation of the completion items and of the functions
+    pub documentation: usize,
+}
+
+impl MemoryUsage {
+    pub fn total(&self) -> usize {
+        self.documents + self.parse_trees + self.directory_cache + self.documentation
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Status {
+    /// The budget in bytes, none without a budget
+    pub budget: Option<usize>,
+    pub used: usize,
+    pub usage: MemoryUsage,
+    pub open_documents: usize,
+    pub parse_trees: usize,
+    /// The lint checks which took the most time, the slowest first
+    pub slowest_checks: Vec<CheckTiming>,
+}
+
+fn tree_size(len: usize) -> usize {
+    len * TREE_BYTES_PER_BYTE
+}
+
+fn line_index_size(index: &LineIndex) -> usize {
+    index.line_count() * size_of::<usize>()
+}
+
+fn documentation_size(item: &CompletionItem) -> usize {
+    match &item.documentation {
+        Some(Documentation::String(text)) => text.len(),
+        Some(Documentation::MarkupContent(content)) => content.value.len(),
+        None => 0,
+    }
+}
+
+/// Drop the documentation of `items`, read from `path`, returns the bytes freed
+fn strip_documentation(path: &Path, items: &mut [CompletionItem]) -> usize {
+    items
+        .iter_mut()
+        .filter(|item| item.documentation.is_some() && item.data.is_none())
+        .map(|item| {
+            let size = documentation_size(item);
+            item.documentation = None;
+            item.data = Some(serde_json::json!({ STRIPPED_FROM: path }));
+            size
+        })
+        .sum()
+}
+
+/// The file the documentation of `item` was dropped from
+fn stripped_from(item: &CompletionItem) -> Option<PathBuf> {
+    let path = item.data.as_ref()?.get(STRIPPED_FROM)?.as_str()?;
+    Some(PathBuf::from(path))
+}
+
+/// `keys` ordered from the least recently used, the ones never used first
+fn by_last_access<K>(mut keys: Vec<(K, Option<Instant>)>) -> Vec<K> {
+    keys.sort_by_key(|(_, accessed)| *accessed);
+    keys.into_iter().map(|(key, _)| key).collect()
+}
+
+impl Backend {
+    /// Record that the document is used now, for the trimming
+    pub(super) fn touch(&self, uri: &Uri) {
+        if self
+            .init_info
+            .get()
+            .is_some_and(|info| info.memory_budget.is_some())
+        {
+            self.accessed.insert(uri.clone(), Instant::now());
+        }
+    }
+
+    fn last_access(&self, uri: &Uri) -> Option<Instant> {
+        self.accessed.get(uri).map(|accessed| *accessed)
+    }
+
+    async fn memory_usage(&self) -> MemoryUsage {
+        let documents = self.documents.iter().map(|text| text.len()).sum();
+        let trees: usize = self
+            .trees
+            .iter()
+            .filter_map(|tree| {
+                self.documents
+                    .get(tree.key())
+                    .map(|text| tree_size(text.len()))
+            })
+            .sum();
+        let line_indexes: usize = self
+            .line_indexes
+            .iter()
+            .map(|index| line_index_size(index.value()))
+            .sum();
+        let completion_docs: usize = COMPLETE_CACHE
+            .lock()
+            .await
+            .values()
+            .flatten()
+            .map(documentation_size)
+            .sum();
+        let function_docs: usize = FUNCTION_DOCS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .values()
+            .map(|doc| doc.label.len() + doc.documentation.len())
+            .sum();
+        MemoryUsage {
+            documents,
+            parse_trees: trees + line_indexes,
+            directory_cache: DIRECTORY_CACHE.memory_usage(),
+            documentation: completion_docs + function_docs,
+        }
+    }
+
+    /// Trim the caches once the documents opened and closed settle
+    pub(super) fn schedule_trim(&self) {
+        if self.init_info().memory_budget.is_none()
+            || self.trim_pending.swap(true, Ordering::AcqRel)
+        {
+            return;
+        }
+        let backend = self.clone();
+        self.spawn_background(async move {
+            tokio::time::sleep(TRIM_DELAY).await;
+            backend.trim_pending.store(false, Ordering::Release);
+            backend.trim_memory().await;
+        });
+    }
+
+    /// Trim the caches until they fit in the budget
+    pub(super) async fn trim_memory(&self) {
+        let Some(budget) = self.init_info().memory_budget else {
+            return;
+        };
+        // The documents forgotten since need no access time
+        self.accessed.retain(|uri, _| {
+            self.documents.contains_key(uri)
+                || self.trees.contains_key(uri)
+                || self.line_indexes.contains_key(uri)
+        });
+        let mut usage = self.memory_usage().await;
+        if usage.total() <= budget {
+            return;
+        }
+        let before = usage.total();
+        DIRECTORY_CACHE.clear();
+        usage.directory_cache = 0;
+
+        let closed_trees = by_last_access(
+            self.trees
+                .iter()
+                .map(|tree| tree.key().clone())
+                .filter(|uri| !self.open_documents.contains(uri))
+                .map(|uri| {
+                    let accessed = self.last_access(&uri);
+                    (uri, accessed)
+                })
+                .collect(),
+        );
+        for uri in closed_trees {
+            if usage.total() <= budget {
+                break;
+            }
+            let mut freed = 0;
+            if self.trees.remove(&uri).is_some()
+                && let Some(text) = self.documents.get(&uri)
+            {
+                freed += tree_size(text.len());
+            }
+            if let Some((_, index)) = self.line_indexes.remove(&uri) {
+                freed += line_index_size(&index);
+            }
+            usage.parse_trees = usage.parse_trees.saturating_sub(freed);
+        }
+
+        if usage.total() > budget {
+            let mut cache = COMPLETE_CACHE.lock().await;
+            let closed_files = by_last_access(
+                cache
+                    .keys()
+                    .filter_map(|path| {
+                        let uri = Uri::from_file_path(path).ok()?;
+                        (!self.open_documents.contains(&uri))
+                            .then(|| (path.clone(), self.last_access(&uri)))
+                    })
+                    .collect(),
+            );
+            for path in closed_files {
+                if usage.total() <= budget {
+                    break;
+                }
+                if let Some(items) = cache.get_mut(&path) {
+                    let freed = strip_documentation(&path, items);
+                    usage.documentation = usage.documentation.saturating_sub(freed);
+                }
+            }
+        }
+        tracing::info!(
+            "Trimmed the caches from {before} to {} bytes, for a budget of {budget}",
+            usage.total()
+        );
+    }
+
+    /// Read again the documentation of a completion item dropped by the
+    /// trimming, from its file
+    pub(super) async fn resolve_stripped(&self, mut item: CompletionItem) -> CompletionItem {
+        if item.documentation.is_some() {
+            return item;
+        }
+        let Some(path) = stripped_from(&item) else {
+            return item;
+        };
+        let text = match Uri::from_file_path(&path)
+            .ok()
+            .and_then(|uri| self.documents.get(&uri).map(|text| text.to_string()))
+        {
+            Some(text) => text,
+            None => match tokio::fs::read_to_string(&path).await {
+                Ok(text) => text,
+                Err(_) => return item,
+            },
+        };
+        // Fills the cache of the file again, with all its documentation
+        let items = complete::update_cache(&path, &text).await;
+        if let Some(found) = items
+            .into_iter()
+            .find(|other| other.label == item.label && other.kind == item.kind)
+        {
+            item.documentation = found.documentation;
+            item.data = None;
+        }
+        item
+    }
+
+    /// The `neocmake/status` request
+    pub(super) async fn status(&self) -> Result<Status> {
+        let site = Site::new(STATUS_METHOD);
+        self.guard(site, async move {
+            let usage = self.memory_usage().await;
+            Ok(Status {
+                budget: self.init_info().memory_budget,
+                used: usage.total(),
+                usage,
+                open_documents: self.open_documents.len(),
+                parse_trees: self.trees.len(),
+                slowest_checks: gammar::slowest_checks(SLOWEST_CHECKS),
+            })
+        })
+        .await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_strip_documentation() {
+        let mut items = vec![
+            CompletionItem {
+                label: "add_plugin".to_string(),
+                documentation: Some(Documentation::String("Add a plugin".to_string())),
+                ..Default::default()
+            },
+            CompletionItem {
+                label: "PLUGIN_DIR".to_string(),
+                ..Default::default()
+            },
+        ];
+        let path = Path::new("/project/plugins.cmake");
+        assert_eq!(strip_documentation(path, &mut items), "Add a plugin".len());
+        assert!(items.iter().all(|item| item.documentation.is_none()));
+        assert_eq!(items[0].label, "add_plugin");
+        // Only the items which had documentation are read again
+        assert_eq!(stripped_from(&items[0]), Some(path.to_path_buf()));
+        assert_eq!(stripped_from(&items[1]), None);
+    }
+
+    #[test]
+    fn test_by_last_access() {
+        let now = Instant::now();
+        let later = now + Duration::from_secs(1);
+        assert_eq!(
+            by_last_access(vec![
+                ("recent", Some(later)),
+                ("old", Some(now)),
+                ("never", None)
+            ]),
+            vec!["never", "old", "recent"]
+        );
+    }
+}
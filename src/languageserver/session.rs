@@ -0,0 +1,293 @@
+//! Snapshot of the session, with the `session` init option.
+//!
+//! Editors updating themselves restart the server often, and a crash does too.
+//! The open documents and the last diagnostics are saved in the cache
+//! directory with a fingerprint of the project, so the next server of the same
+//! project publishes the diagnostics right away and does not check the closed
+//! files again when nothing changed, and warms the caches of the documents the
+//! editor reopens.
+//!
+//! The diagnostics of a file depend on the other files of the project, like a
+//! target declared twice, so they are only trusted when no file of the project
+//! changed: the fingerprint hashes the content of all of them with the config.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::{Diagnostic, Uri};
+
+use crate::config::{self, CONFIG};
+use crate::{Backend, complete, jump, lint};
+
+/// How long the changes settle before the session is saved
+const SAVE_DELAY: Duration = Duration::from_secs(2);
+
+/// The diagnostics of a file when the session was saved
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileState {
+    pub uri: Uri,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    /// See [`fingerprint`], the diagnostics are outdated once it changes
+    pub fingerprint: u64,
+    pub open_documents: Vec<Uri>,
+    pub files: Vec<FileState>,
+}
+
+/// A hash of what the diagnostics are computed from: the version of the server,
+/// its `settings`, and the content of the files of the project at `root`, of
+/// their `neocmake.toml` and of the `files` outside of it
+fn fingerprint(root: &Path, files: &[FileState], settings: &str) -> u64 {
+    let mut paths = lint::collect_files(&[root.to_path_buf()]);
+    let directories: Vec<PathBuf> = paths
+        .iter()
+        .filter_map(|path| path.parent())
+        .map(Path::to_path_buf)
+        .collect();
+    for directory in directories {
+        paths.extend(
+            config::DIRECTORY_CONFIG_FILES
+                .iter()
+                .map(|name| directory.join(name))
+                .filter(|path| path.is_file()),
+        );
+    }
+    paths.extend(files.iter().filter_map(|file| file.uri.to_file_path().ok()));
+    paths.sort();
+    paths.dedup();
+
+    let mut hasher = DefaultHasher::new();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    settings.hash(&mut hasher);
+    for path in paths {
+        path.hash(&mut hasher);
+        std::fs::read(&path).ok().hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// The file of the session of the project at `root`
+fn session_path(root: &Path) -> Option<PathBuf> {
+    let mut hasher = DefaultHasher::new();
+    root.hash(&mut hasher);
+    Some(
+        config::cache_dir()?
+            .join("sessions")
+            .join(format!("{:016x}.json", hasher.finish())),
+    )
+}
+
+fn load_from(path: &Path) -> Option<Session> {
+    let content = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Write the session at `path`, replacing the file at once so a crash while
+/// writing leaves the last one
+fn save_to(path: &Path, session: &Session) -> anyhow::Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    std::fs::create_dir_all(dir)?;
+    let mut file = tempfile::NamedTempFile::new_in(dir)?;
+    file.write_all(serde_json::to_string(session)?.as_bytes())?;
+    file.persist(path)?;
+    Ok(())
+}
+
+impl Backend {
+    /// The config and the init options, which the diagnostics depend on
+    fn settings(&self) -> String {
+        format!("{:?} {:?}", *CONFIG, self.init_info())
+    }
+
+    fn session_path(&self) -> Option<PathBuf> {
+        if !self.init_info().persist_session {
+            return None;
+        }
+        session_path(self.root_path()?)
+    }
+
+    /// Count the diagnostics published for `uri`, and keep them for the session
+    /// when it is enabled
+    pub(super) fn record_diagnostics(&self, uri: &Uri, diagnostics: &[Diagnostic]) {
+        self.diagnostics.insert(uri.clone(), diagnostics.len());
+        if self.init_info().persist_session {
+            self.published.insert(uri.clone(), diagnostics.to_vec());
+        }
+    }
+
+    /// The session as it is now, reading the disk
+    fn snapshot(&self, root: &Path) -> Session {
+        let mut open_documents: Vec<Uri> = self
+            .open_documents
+            .iter()
+            .map(|uri| uri.key().clone())
+            .collect();
+        open_documents.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        let mut files: Vec<FileState> = self
+            .published
+            .iter()
+            .filter(|item| {
+                // Checked from unsaved changes, which are not on the disk
+                self.documents.get(item.key()).is_none_or(|document| {
+                    item.key()
+                        .to_file_path()
+                        .ok()
+                        .and_then(|path| std::fs::read_to_string(path).ok())
+                        .is_some_and(|text| text == document.to_string())
+                })
+            })
+            .map(|item| FileState {
+                uri: item.key().clone(),
+                diagnostics: item.value().clone(),
+            })
+            .collect();
+        files.sort_by(|a, b| a.uri.as_str().cmp(b.uri.as_str()));
+        Session {
+            fingerprint: fingerprint(root, &files, &self.settings()),
+            open_documents,
+            files,
+        }
+    }
+
+    fn write_session(&self) {
+        let (Some(path), Some(root)) = (self.session_path(), self.root_path()) else {
+            return;
+        };
+        if let Err(err) = save_to(&path, &self.snapshot(root)) {
+            tracing::warn!("Cannot save the session to {}: {err}", path.display());
+        }
+    }
+
+    /// Save the session once the changes settle, when it is enabled
+    pub(super) fn save_session(&self) {
+        if self.session_path().is_none() || self.session_pending.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let backend = self.clone();
+        self.spawn_background(async move {
+            tokio::time::sleep(SAVE_DELAY).await;
+            // The changes from now on are saved by the next one
+            backend.session_pending.store(false, Ordering::Release);
+            let _ = tokio::task::spawn_blocking(move || backend.write_session()).await;
+        });
+    }
+
+    /// Save the session now, when it is enabled
+    pub(super) async fn flush_session(&self) {
+        if self.session_path().is_none() {
+            return;
+        }
+        let backend = self.clone();
+        let _ = tokio::task::spawn_blocking(move || backend.write_session()).await;
+    }
+
+    /// Publish the diagnostics of the last session when the project did not
+    /// change since, and fill the caches of the documents which were open
+    pub(super) async fn restore_session(&self) {
+        let Some(session) = self.session_path().and_then(|path| load_from(&path)) else {
+            return;
+        };
+        for uri in &session.open_documents {
+            let Ok(path) = uri.to_file_path() else {
+                continue;
+            };
+            let Ok(text) = tokio::fs::read_to_string(&path).await else {
+                continue;
+            };
+            complete::update_cache(&path, &text).await;
+            jump::update_cache(&path, &text).await;
+        }
+        let Some(root) = self.root_path().cloned() else {
+            return;
+        };
+        let files = session.files.clone();
+        let settings = self.settings();
+        let unchanged = tokio::task::spawn_blocking(move || fingerprint(&root, &files, &settings))
+            .await
+            .is_ok_and(|fingerprint| fingerprint == session.fingerprint);
+        if !unchanged {
+            tracing::info!("The project changed since the last session, checking it again");
+            return;
+        }
+        for file in &session.files {
+            // Opened meanwhile, its diagnostics are the ones of the buffer
+            if self.diagnostics.contains_key(&file.uri) {
+                continue;
+            }
+            self.record_diagnostics(&file.uri, &file.diagnostics);
+            self.restored.insert(file.uri.clone());
+            self.client
+                .publish_diagnostics(file.uri.clone(), file.diagnostics.clone(), None)
+                .await;
+        }
+        tracing::info!(
+            "Restored the session, {} open documents and {} files",
+            session.open_documents.len(),
+            self.restored.len()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::{Position, Range};
+
+    use super::*;
+
+    #[test]
+    fn test_session() {
+        let dir = tempfile::tempdir().unwrap();
+        let module = dir.path().join("module.cmake");
+        let top = dir.path().join("CMakeLists.txt");
+        std::fs::write(&module, "set(A 1)\n").unwrap();
+        std::fs::write(&top, "project(demo)\n").unwrap();
+        let diagnostic = Diagnostic {
+            range: Range::new(Position::new(0, 0), Position::new(0, 7)),
+            message: "problem".to_string(),
+            ..Default::default()
+        };
+        let top = Uri::from_file_path(&top).unwrap();
+        let files = vec![FileState {
+            uri: top.clone(),
+            diagnostics: vec![diagnostic],
+        }];
+        let session = Session {
+            fingerprint: fingerprint(dir.path(), &files, "settings"),
+            open_documents: vec![top],
+            files,
+        };
+
+        let path = dir.path().join("sessions").join("project.json");
+        save_to(&path, &session).unwrap();
+        let loaded = load_from(&path).unwrap();
+        assert_eq!(loaded, session);
+        assert_eq!(
+            fingerprint(dir.path(), &loaded.files, "settings"),
+            loaded.fingerprint
+        );
+        assert_ne!(
+            fingerprint(dir.path(), &loaded.files, "other settings"),
+            loaded.fingerprint
+        );
+
+        // Another file of the project changed, the diagnostics of all may be outdated
+        std::fs::write(&module, "set(A 2)\n").unwrap();
+        assert_ne!(
+            fingerprint(dir.path(), &loaded.files, "settings"),
+            loaded.fingerprint
+        );
+
+        std::fs::write(&path, "{ broken").unwrap();
+        assert_eq!(load_from(&path), None);
+    }
+}
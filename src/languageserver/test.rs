@@ -15,7 +15,7 @@ use tower_lsp::{LanguageServer, LspService};
 
 use super::Backend;
 use crate::languageserver::Config;
-use crate::semantic_token::LEGEND_TYPE;
+use crate::semantic_token::{LEGEND_MODIFIER, LEGEND_TYPE};
 
 fn create_request<T>(id: i64, init_param: T, method: &'static str) -> Request
 where
@@ -89,7 +89,7 @@ async fn test_init() {
                 },
                 legend: SemanticTokensLegend {
                     token_types: LEGEND_TYPE.into(),
-                    token_modifiers: [].to_vec()
+                    token_modifiers: LEGEND_MODIFIER.into()
                 },
                 range: None,
                 full: Some(SemanticTokensFullOptions::Bool(true)),
@@ -138,3 +138,373 @@ async fn test_init() {
         serde_json::from_value(response.unwrap().result().unwrap().clone()).unwrap();
     println!("{:?}", _complete_result);
 }
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_harness_diagnostics_and_completion() {
+    use tempfile::tempdir;
+
+    use super::harness::TestServer;
+
+    let dir = tempdir().unwrap();
+    let mut server = TestServer::start(dir.path()).await;
+    assert!(
+        server
+            .init_result
+            .capabilities
+            .completion_provider
+            .is_some()
+    );
+
+    let broken = server
+        .open(dir.path().join("broken.cmake"), "set(FOO 1))\n")
+        .await;
+    assert!(!server.expect_diagnostics(&broken).await.is_empty());
+
+    let source =
+        "cmake_minimum_required(VERSION 3.20)\nproject(demo)\nset(MY_VARIABLE 1)\nmessage(MY_)\n";
+    let uri = server.open(dir.path().join("CMakeLists.txt"), source).await;
+    assert!(server.expect_diagnostics(&uri).await.is_empty());
+
+    let items = server.complete_at(&uri, 3, 11).await;
+    assert!(items.iter().any(|item| item.label == "MY_VARIABLE"));
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_harness_manual_lint() {
+    use tempfile::tempdir;
+    use tower_lsp::lsp_types::{ClientCapabilities, ExecuteCommandParams};
+
+    use super::harness::TestServer;
+    use crate::languageserver::config::{LintConfig, LintRun};
+
+    let dir = tempdir().unwrap();
+    let config = Config {
+        lint: LintConfig {
+            run: LintRun::Manual,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut server =
+        TestServer::start_with(dir.path(), config, ClientCapabilities::default()).await;
+    let uri = server
+        .open(dir.path().join("broken.cmake"), "set(FOO 1\n")
+        .await;
+    let _: Option<serde_json::Value> = server
+        .request(
+            "workspace/executeCommand",
+            ExecuteCommandParams {
+                command: "neocmake.lint".to_string(),
+                arguments: vec![serde_json::to_value(&uri).unwrap()],
+                work_done_progress_params: WorkDoneProgressParams::default(),
+            },
+        )
+        .await;
+    assert!(!server.expect_diagnostics(&uri).await.is_empty());
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_harness_shutdown() {
+    use tempfile::tempdir;
+
+    use super::harness::TestServer;
+
+    let dir = tempdir().unwrap();
+    let mut server = TestServer::start(dir.path()).await;
+    server
+        .open(dir.path().join("CMakeLists.txt"), "project(demo)\n")
+        .await;
+    // The server answers, then stops on `exit` instead of killing the process
+    server.shutdown().await;
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_harness_exit_without_shutdown() {
+    use tempfile::tempdir;
+
+    use super::harness::TestServer;
+
+    let dir = tempdir().unwrap();
+    let mut server = TestServer::start(dir.path()).await;
+    assert_eq!(server.exit().await, 1);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_harness_status() {
+    use tempfile::tempdir;
+    use tower_lsp::lsp_types::ClientCapabilities;
+
+    use super::harness::TestServer;
+    use super::memory::Status;
+
+    let dir = tempdir().unwrap();
+    let config = Config {
+        memory_budget_mb: Some(64),
+        ..Default::default()
+    };
+    let mut server =
+        TestServer::start_with(dir.path(), config, ClientCapabilities::default()).await;
+    let uri = server
+        .open(dir.path().join("CMakeLists.txt"), "project(demo)\n")
+        .await;
+    server.expect_diagnostics(&uri).await;
+
+    let status: Status = server.request("neocmake/status", ()).await;
+    assert_eq!(status.budget, Some(64 * 1024 * 1024));
+    assert_eq!(status.open_documents, 1);
+    assert_eq!(status.usage.documents, "project(demo)\n".len());
+    assert!(status.used >= status.usage.documents);
+    assert!(!status.slowest_checks.is_empty());
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_harness_resolve_stripped_documentation() {
+    use tempfile::tempdir;
+    use tower_lsp::lsp_types::{CompletionItem, CompletionItemKind};
+
+    use super::harness::TestServer;
+
+    let dir = tempdir().unwrap();
+    let module = dir.path().join("plugins.cmake");
+    std::fs::write(
+        &module,
+        "# Add a plugin\nfunction(add_plugin name)\nendfunction()\n",
+    )
+    .unwrap();
+    let mut server = TestServer::start(dir.path()).await;
+
+    let cached = crate::complete::update_cache(&module, &std::fs::read_to_string(&module).unwrap())
+        .await
+        .into_iter()
+        .find(|item| item.label == "add_plugin")
+        .unwrap();
+    assert!(cached.documentation.is_some());
+    // As left by the trimming, which dropped the documentation
+    let stripped = CompletionItem {
+        label: "add_plugin".to_string(),
+        kind: cached.kind,
+        data: Some(serde_json::json!({ "documentationOf": module })),
+        ..Default::default()
+    };
+    assert_eq!(stripped.kind, Some(CompletionItemKind::FUNCTION));
+    let resolved: CompletionItem = server.request("completionItem/resolve", stripped).await;
+    assert_eq!(resolved.documentation, cached.documentation);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_harness_incremental_change() {
+    use tempfile::tempdir;
+    use tower_lsp::lsp_types::Range;
+
+    use super::harness::TestServer;
+
+    let dir = tempdir().unwrap();
+    let mut server = TestServer::start(dir.path()).await;
+    let uri = server
+        .open(dir.path().join("broken.cmake"), "set(FOO 1\n")
+        .await;
+    assert!(!server.expect_diagnostics(&uri).await.is_empty());
+
+    let end = Position::new(0, 9);
+    server.change(&uri, Range::new(end, end), ")").await;
+    assert!(server.expect_diagnostics(&uri).await.is_empty());
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_harness_debounced_change() {
+    use std::time::Duration;
+
+    use tempfile::tempdir;
+    use tower_lsp::lsp_types::{ClientCapabilities, Range};
+
+    use super::config::LintConfig;
+    use super::harness::TestServer;
+
+    let dir = tempdir().unwrap();
+    let config = Config {
+        lint: LintConfig {
+            debounce_ms: 2000,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut server =
+        TestServer::start_with(dir.path(), config, ClientCapabilities::default()).await;
+    let uri = server
+        .open(dir.path().join("module.cmake"), "set(FOO 1\n")
+        .await;
+    assert!(!server.expect_diagnostics(&uri).await.is_empty());
+
+    // The checks waiting for the typing to stop do not hold the requests back
+    for column in 9..15 {
+        let end = Position::new(0, column);
+        server.change(&uri, Range::new(end, end), " ").await;
+    }
+    tokio::time::timeout(Duration::from_millis(1500), server.complete_at(&uri, 1, 0))
+        .await
+        .expect("the completion waited for the debounced checks");
+
+    let end = Position::new(0, 15);
+    server.change(&uri, Range::new(end, end), ")").await;
+    assert!(server.expect_diagnostics(&uri).await.is_empty());
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_harness_format_workspace() {
+    use tempfile::tempdir;
+    use tower_lsp::lsp_types::{DocumentChanges, FormattingOptions};
+
+    use super::harness::TestServer;
+    use crate::formatting::workspace::{FormatWorkspaceParams, FormatWorkspaceResult};
+
+    let dir = tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("CMakeLists.txt"),
+        "project(demo)\nif(A)\nadd_subdirectory(src)\nendif()\n",
+    )
+    .unwrap();
+    std::fs::write(dir.path().join("broken.cmake"), "if(A\n").unwrap();
+    let mut server = TestServer::start(dir.path()).await;
+
+    let params = FormatWorkspaceParams {
+        options: FormattingOptions {
+            tab_size: 2,
+            insert_spaces: true,
+            ..Default::default()
+        },
+    };
+    let result: FormatWorkspaceResult = server.request("neocmake/formatWorkspace", params).await;
+    assert_eq!(result.formatted, 1);
+    assert_eq!(result.errors.len(), 1);
+    let Some(DocumentChanges::Edits(edits)) = result.edit.document_changes else {
+        panic!("expected text edits");
+    };
+    assert_eq!(edits.len(), 1);
+    assert!(
+        edits[0]
+            .text_document
+            .uri
+            .as_str()
+            .ends_with("/CMakeLists.txt")
+    );
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_harness_format_workspace_after_close() {
+    use tempfile::tempdir;
+    use tower_lsp::lsp_types::{DocumentChanges, FormattingOptions, Position, Range};
+
+    use super::harness::TestServer;
+    use crate::formatting::workspace::{FormatWorkspaceParams, FormatWorkspaceResult};
+
+    let dir = tempdir().unwrap();
+    let lists = dir.path().join("CMakeLists.txt");
+    std::fs::write(&lists, "project(demo)\n").unwrap();
+    let mut server = TestServer::start(dir.path()).await;
+
+    // Changed in the editor, then closed without saving
+    let uri = server.open(&lists, "project(demo)\n").await;
+    server.expect_diagnostics(&uri).await;
+    server
+        .change(
+            &uri,
+            Range::new(Position::new(1, 0), Position::new(1, 0)),
+            "if(A)\nset(B 1)\nendif()\n",
+        )
+        .await;
+    server.close(&uri).await;
+
+    let params = FormatWorkspaceParams {
+        options: FormattingOptions {
+            tab_size: 2,
+            insert_spaces: true,
+            ..Default::default()
+        },
+    };
+    let result: FormatWorkspaceResult = server.request("neocmake/formatWorkspace", params).await;
+    // The file on disk is formatted already, the discarded text is not used
+    assert_eq!(result.formatted, 1);
+    let Some(DocumentChanges::Edits(edits)) = result.edit.document_changes else {
+        panic!("expected text edits");
+    };
+    assert!(edits.is_empty());
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_harness_file_outside_of_workspace() {
+    use tempfile::tempdir;
+    use tower_lsp::lsp_types::NumberOrString;
+
+    use super::harness::TestServer;
+
+    let dir = tempdir().unwrap();
+    let outside = tempdir().unwrap();
+    let mut server = TestServer::start(dir.path()).await;
+
+    let broken = server
+        .open(outside.path().join("broken.cmake"), "set(FOO 1\n")
+        .await;
+    assert!(!server.expect_diagnostics(&broken).await.is_empty());
+
+    // The targets of the project are not the ones of a dependency
+    let module = server
+        .open(
+            outside.path().join("CMakeLists.txt"),
+            "install(TARGETS elsewhere DESTINATION lib)\n",
+        )
+        .await;
+    let diagnostics = server.expect_diagnostics(&module).await;
+    assert!(
+        diagnostics
+            .iter()
+            .all(|diagnostic| diagnostic.code != Some(NumberOrString::String("N110".to_string())))
+    );
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_harness_untitled_document() {
+    use tempfile::tempdir;
+    use tower_lsp::lsp_types::Uri;
+
+    use super::harness::TestServer;
+
+    let dir = tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("src")).unwrap();
+    std::fs::write(dir.path().join("src").join("CMakeLists.txt"), "").unwrap();
+    let mut server = TestServer::start(dir.path()).await;
+
+    let broken: Uri = "untitled:Untitled-1".parse().unwrap();
+    server.open_uri(&broken, "set(FOO 1\n").await;
+    assert!(!server.expect_diagnostics(&broken).await.is_empty());
+
+    // The paths are completed from the workspace root
+    let untitled: Uri = "untitled:Untitled-2".parse().unwrap();
+    server.open_uri(&untitled, "add_subdirectory(s)\n").await;
+    let items = server.complete_at(&untitled, 0, 18).await;
+    assert!(items.iter().any(|item| item.label == "src/"));
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_harness_custom_requests() {
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    use super::CUSTOM_REQUESTS;
+    use super::harness::TestServer;
+
+    const METHOD_NOT_FOUND: i64 = -32601;
+
+    let dir = tempdir().unwrap();
+    let mut server = TestServer::start(dir.path()).await;
+    // Sent without their parameters, some are refused, but none is unknown
+    for method in CUSTOM_REQUESTS {
+        if let Err(error) = server.try_request(method, ()).await {
+            assert_ne!(error["code"], json!(METHOD_NOT_FOUND), "{method}");
+        }
+    }
+    let error = server
+        .try_request("neocmake/unknown", ())
+        .await
+        .unwrap_err();
+    assert_eq!(error["code"], json!(METHOD_NOT_FOUND));
+}
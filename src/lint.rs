@@ -0,0 +1,323 @@
+//! The `lint` subcommand: the diagnostics of the language server, on the command line.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use ignore::Walk;
+use tower_lsp::lsp_types::DiagnosticSeverity;
+
+use crate::baseline::{self, Baseline};
+use crate::config::{self, CONFIG, Config};
+use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+use crate::gammar::{
+    self, ErrorInformation, Fix, FixableIssue, LintConfigInfo, checkerror, line_length,
+};
+use crate::utils::external;
+use crate::{rules, scansubs};
+
+fn is_cmake_file(path: &Path) -> bool {
+    path.is_file()
+        && (path
+            .file_name()
+            .is_some_and(|name| name == "CMakeLists.txt")
+            || path.extension().is_some_and(|ext| ext == "cmake"))
+}
+
+/// The CMake files under `paths`
+pub fn collect_files(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut files = vec![];
+    for path in paths {
+        if path.is_dir() {
+            files.extend(
+                Walk::new(path)
+                    .flatten()
+                    .map(|entry| entry.into_path())
+                    .filter(|path| is_cmake_file(path) && !config::is_excluded(path)),
+            );
+        } else if path.exists() {
+            files.push(path.clone());
+        } else {
+            tracing::warn!("Failed to lint '{}': path doesn't exist", path.display());
+        }
+    }
+    files
+}
+
+pub(crate) fn severity_name(severity: Option<DiagnosticSeverity>) -> &'static str {
+    match severity {
+        Some(DiagnosticSeverity::WARNING) => "warning",
+        Some(DiagnosticSeverity::INFORMATION) => "info",
+        Some(DiagnosticSeverity::HINT) => "hint",
+        _ => "error",
+    }
+}
+
+/// `path:line:column: severity: message [id name, category]`, with one based lines and columns
+pub fn format_error(path: &Path, error: &ErrorInformation) -> String {
+    let mut line = format!(
+        "{}:{}:{}: {}: {}",
+        path.display(),
+        error.start_point.row + 1,
+        error.start_point.column + 1,
+        severity_name(error.severity),
+        error.message
+    );
+    if let Some(rule) = error.rule {
+        line.push_str(&format!(" [{} {}, {}]", rule.id, rule.name, rule.category));
+    }
+    line
+}
+
+/// A fix may open the way for another one, like wrapping a line once its arguments are quoted
+const MAX_FIX_PASSES: usize = 10;
+
+/// The issues whose diagnostic `filter` keeps
+fn kept_issues(
+    issues: Vec<FixableIssue>,
+    filter: impl FnOnce(&mut Vec<ErrorInformation>),
+) -> Vec<FixableIssue> {
+    let mut errors: Vec<ErrorInformation> =
+        issues.iter().cloned().map(ErrorInformation::from).collect();
+    filter(&mut errors);
+    issues
+        .into_iter()
+        .filter(|issue| {
+            let Some(index) = errors.iter().position(|error| {
+                error.start_point == issue.start_point && error.message == issue.message
+            }) else {
+                return false;
+            };
+            errors.swap_remove(index);
+            true
+        })
+        .collect()
+}
+
+/// The safe fixes of `source`, for the diagnostics reported for `path`: the
+/// rules turned off and the diagnostics of the baseline are left as they are
+fn safe_fixes(path: Option<&Path>, source: &str) -> Vec<Fix> {
+    let mut issues = gammar::fixable_issues(path, source);
+    let lines: Vec<&str> = source.lines().collect();
+    let mut parse = tree_sitter::Parser::new();
+    parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+    // cmake-lint reports the long lines itself, with its own limit
+    if !CONFIG.enable_external_cmake_lint
+        && let Some(thetree) = parse.parse(source, None)
+    {
+        issues.append(&mut line_length::find_issues(
+            &lines,
+            thetree.root_node(),
+            CONFIG.line_max_words(),
+        ));
+    }
+    issues.retain(|issue| issue.fix.safe && issue.rule.configured_severity(path).is_some());
+    if let Some(path) = path {
+        issues = kept_issues(issues, |errors| {
+            baseline::apply_baseline(path, &lines, errors);
+        });
+    }
+    issues.into_iter().map(|issue| issue.fix).collect()
+}
+
+/// Apply the safe fixes to `source`, the file at `path`, until none is left.
+/// Returns the new text and the number of fixes applied.
+pub fn fix_source(path: Option<&Path>, source: &str) -> (String, usize) {
+    let mut source = source.to_string();
+    let mut total = 0;
+    for _ in 0..MAX_FIX_PASSES {
+        let (fixed, applied) = gammar::fix::apply_fixes(&source, &safe_fixes(path, &source));
+        if applied == 0 {
+            break;
+        }
+        source = fixed;
+        total += applied;
+    }
+    (source, total)
+}
+
+#[derive(Debug, Default)]
+pub struct LintOptions {
+    /// Apply the safe fixes first
+    pub fix: bool,
+    /// Report only the diagnostics missing from this baseline, written when it does not exist
+    pub baseline: Option<PathBuf>,
+    /// Write the baseline even if it exists
+    pub update_baseline: bool,
+}
+
+/// The keys of the `[rules]` of `config` which are neither an id nor a name
+fn unknown_rules(config: &Config) -> Vec<&str> {
+    config
+        .rules
+        .keys()
+        .map(String::as_str)
+        .filter(|key| rules::find_rule(key).is_none())
+        .collect()
+}
+
+/// Lint the CMake files under `paths`, and print the diagnostics.
+/// Returns whether an error was found.
+pub async fn lint_paths(
+    paths: &[PathBuf],
+    LintOptions {
+        fix,
+        baseline: baseline_path,
+        update_baseline,
+    }: LintOptions,
+) -> Result<bool> {
+    for key in unknown_rules(&CONFIG) {
+        tracing::warn!("Unknown rule '{key}' in the config");
+    }
+    // The project is indexed first, so included files and modules are known
+    if let Some(project_root) = paths
+        .iter()
+        .find(|path| path.is_dir() && path.join("CMakeLists.txt").is_file())
+    {
+        scansubs::scan_all(std::path::absolute(project_root)?, true).await;
+    }
+    // The baseline is written from every diagnostic, so none may be hidden meanwhile
+    let mut new_baseline = None;
+    if let Some(path) = &baseline_path {
+        if update_baseline || !path.exists() {
+            baseline::set_baseline(None);
+            new_baseline = Some(Baseline::default());
+        } else {
+            baseline::set_baseline(Some(Baseline::load(path)?));
+        }
+    }
+    let mut has_error = false;
+    let mut count = 0;
+    let (mut fixed_files, mut fixes) = (0, 0);
+    for path in collect_files(paths) {
+        let mut source = std::fs::read_to_string(&path)?;
+        let local_path = std::path::absolute(&path)?;
+        // The vendored code is left as it is
+        if fix && !external::is_external(&local_path) {
+            let (fixed, applied) = fix_source(Some(&local_path), &source);
+            if applied > 0 {
+                std::fs::write(&path, &fixed)?;
+                eprintln!("Fixed {} ({applied} fix(es))", path.display());
+                source = fixed;
+                fixed_files += 1;
+                fixes += applied;
+            }
+        }
+        let lint_info = LintConfigInfo {
+            use_lint: true,
+            use_extra_cmake_lint: CONFIG.enable_external_cmake_lint,
+            detached: false,
+        };
+        let Some(errors) = checkerror(&local_path, &source, lint_info) else {
+            continue;
+        };
+        if let Some(new_baseline) = new_baseline.as_mut() {
+            new_baseline.record(&local_path, &source, &errors);
+            continue;
+        }
+        for error in errors.iter() {
+            has_error |= matches!(error.severity, None | Some(DiagnosticSeverity::ERROR));
+            count += 1;
+            println!("{}", format_error(&path, error));
+        }
+    }
+    if fix {
+        eprintln!("{fixes} fix(es) applied to {fixed_files} file(s)");
+    }
+    if let (Some(new_baseline), Some(path)) = (new_baseline, baseline_path) {
+        new_baseline.save(&path)?;
+        eprintln!(
+            "Baseline of {} problem(s) written to {}",
+            new_baseline.entries.len(),
+            path.display()
+        );
+        return Ok(false);
+    }
+    if count > 0 {
+        eprintln!("{count} problem(s) found");
+    }
+    Ok(has_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::Point;
+
+    use super::*;
+
+    #[test]
+    fn test_format_error() {
+        let error = ErrorInformation {
+            start_point: Point { row: 2, column: 4 },
+            end_point: Point { row: 2, column: 10 },
+            message: "Argument is empty".to_string(),
+            severity: Some(DiagnosticSeverity::ERROR),
+            rule: Some(&rules::EMPTY_ARGUMENT),
+        };
+        assert_eq!(
+            format_error(Path::new("CMakeLists.txt"), &error),
+            "CMakeLists.txt:3:5: error: Argument is empty [N102 empty-argument, correctness]"
+        );
+    }
+
+    #[test]
+    fn test_baselined_issues_are_kept() {
+        let local_path = Path::new("/project/CMakeLists.txt");
+        let source = "if(${A} STREQUAL b)\nendif()\nif(${B} STREQUAL c)\nendif()\n";
+        let lines: Vec<&str> = source.lines().collect();
+        let issues = gammar::fixable_issues(None, source);
+        assert_eq!(issues.len(), 2);
+        let mut baseline = Baseline::default();
+        baseline.record(
+            local_path,
+            source,
+            &[ErrorInformation::from(issues[0].clone())],
+        );
+        let kept = kept_issues(issues.clone(), |errors| {
+            baseline.filter(local_path, &lines, errors);
+        });
+        assert_eq!(kept, vec![issues[1].clone()]);
+    }
+
+    #[test]
+    fn test_unknown_rules() {
+        let config: Config = toml::from_str(indoc::indoc! { r#"
+            [rules]
+            line-too-long = "off"
+            Command-Case = "warn"
+            n101 = "error"
+            no-such-rule = "off"
+        "#})
+        .unwrap();
+        assert_eq!(unknown_rules(&config), vec!["NO-SUCH-RULE"]);
+    }
+
+    #[test]
+    fn test_fix_source() {
+        let source = "if(${MODE} STREQUAL Release)\nendif()\nset(DIR /opt/my\\ sdk)\ninclude_directories(C:/Program Files/Sdk)\n";
+        let (fixed, applied) = fix_source(None, source);
+        assert_eq!(applied, 2);
+        // Joining the split path changes the call, it is left to the user
+        assert_eq!(
+            fixed,
+            "if(\"${MODE}\" STREQUAL Release)\nendif()\nset(DIR \"/opt/my sdk\")\ninclude_directories(C:/Program Files/Sdk)\n"
+        );
+    }
+
+    #[test]
+    fn test_collect_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("CMakeLists.txt"), "project(demo)\n").unwrap();
+        std::fs::create_dir(dir.path().join("cmake")).unwrap();
+        std::fs::write(dir.path().join("cmake").join("utils.cmake"), "").unwrap();
+        std::fs::write(dir.path().join("main.cpp"), "").unwrap();
+        let mut files = collect_files(&[dir.path().to_path_buf()]);
+        files.sort();
+        assert_eq!(
+            files,
+            vec![
+                dir.path().join("CMakeLists.txt"),
+                dir.path().join("cmake").join("utils.cmake"),
+            ]
+        );
+    }
+}
@@ -1,48 +1,81 @@
+use std::collections::HashMap;
 use std::net::Ipv4Addr;
+use std::ops::Deref;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
 use clap::{CommandFactory, Parser};
-use dashmap::DashMap;
+use dashmap::{DashMap, DashSet};
 use ignore::Walk;
 use ini::Ini;
-use tower_lsp::{Client, LspService, Server};
+use tower_lsp::Client;
 mod treesitter_nodetypes;
 
 use tokio::net::TcpListener;
+use tokio::task::JoinSet;
 use treesitter_nodetypes as CMakeNodeKinds;
 mod ast;
+mod auto_close;
+mod baseline;
+mod bench;
+mod capabilities;
 mod cli;
 mod complete;
 mod config;
+mod configure_file;
 mod consts;
+mod dependencies;
+mod doctor;
 mod document_link;
+mod eval;
+mod extract;
 mod fileapi;
 mod filewatcher;
+mod folding;
 mod formatting;
 mod gammar;
 mod hover;
+mod indexed;
+mod inlay_hint;
 mod jump;
 mod languageserver;
+mod lint;
+mod presets;
 mod quick_fix;
 mod rename;
+mod rules;
+mod scaffold;
 mod scanner;
 mod scansubs;
 mod search;
 mod semantic_token;
 mod signature_help;
 mod utils;
-use std::sync::OnceLock;
+mod vcpkg;
+use std::sync::atomic::{AtomicBool, AtomicU64};
+use std::sync::{Arc, Mutex, OnceLock};
 
-use tower_lsp::lsp_types::Uri;
+use tower_lsp::lsp_types::{Diagnostic, Uri};
 
 use crate::cli::{Cli, Command};
-use crate::formatting::format_file;
+use crate::formatting::{format_file, format_stdin, workspace};
+use crate::languageserver::config::LintRun;
+use crate::utils::document::Document;
+use crate::utils::line_index::LineIndex;
 
 #[derive(Debug)]
 struct BackendInitInfo {
     pub scan_cmake_in_package: bool,
     pub enable_lint: bool,
+    pub lint_run: LintRun,
+    pub lint_debounce: Duration,
+    pub lint_closed_files: bool,
+    pub persist_session: bool,
+    /// The client asks for the inlay hints again when told to
+    pub inlay_hint_refresh: bool,
+    /// Trim the caches past this many bytes
+    pub memory_budget: Option<usize>,
 }
 
 impl Default for BackendInitInfo {
@@ -50,27 +83,90 @@ impl Default for BackendInitInfo {
         Self {
             scan_cmake_in_package: true,
             enable_lint: true,
+            lint_run: LintRun::default(),
+            lint_debounce: Duration::ZERO,
+            lint_closed_files: false,
+            persist_session: false,
+            inlay_hint_refresh: false,
+            memory_budget: None,
         }
     }
 }
 
+/// The language server, cheap to clone so the work delayed past a handler can
+/// keep it
+#[derive(Debug, Clone)]
+struct Backend(Arc<BackendState>);
+
+impl Deref for Backend {
+    type Target = BackendState;
+
+    fn deref(&self) -> &BackendState {
+        &self.0
+    }
+}
+
 #[derive(Debug)]
-struct Backend {
+struct BackendState {
     client: Client,
-    documents: DashMap<Uri, String>,
+    documents: DashMap<Uri, Document>,
+    /// The line offsets of the open documents, computed again after a change
+    line_indexes: DashMap<Uri, Arc<LineIndex>>,
+    /// The syntax trees of the open documents, updated with them
+    trees: DashMap<Uri, tree_sitter::Tree>,
+    /// Number of changes of each document, so a debounced check knows if it is outdated
+    changes: DashMap<Uri, u64>,
+    /// Number of changes of the CMake caches, so a debounced check knows if it is outdated
+    cache_changes: AtomicU64,
+    /// The source directories of the projects whose cache changed, checked again together
+    changed_projects: DashSet<PathBuf>,
+    /// Number of diagnostics published for each document
+    diagnostics: DashMap<Uri, usize>,
+    /// The diagnostics published for each document, kept with the `session`
+    /// init option only, to be saved
+    published: DashMap<Uri, Vec<Diagnostic>>,
+    /// The documents open in the editor
+    open_documents: DashSet<Uri>,
+    /// The files whose diagnostics were restored from the last session, not
+    /// checked again by the first check of the closed files
+    restored: DashSet<Uri>,
+    /// When each document was last used, to trim the least recently used first
+    accessed: DashMap<Uri, Instant>,
     /// Storage the message of buffers
     init_info: OnceLock<BackendInitInfo>,
     root_path: OnceLock<Option<PathBuf>>,
+    /// A trimming of the caches waits for the documents opened and closed to settle
+    trim_pending: AtomicBool,
+    /// A save of the session waits for the changes to settle
+    session_pending: AtomicBool,
+    /// Set by the `shutdown` request, so the background work stops
+    shutting_down: Arc<AtomicBool>,
+    /// The work spawned in the background, aborted by the `shutdown` request
+    background: Mutex<JoinSet<()>>,
 }
 
 impl Backend {
     fn new(client: Client) -> Self {
-        Self {
+        Self(Arc::new(BackendState {
             client,
             documents: DashMap::new(),
+            line_indexes: DashMap::new(),
+            trees: DashMap::new(),
+            changes: DashMap::new(),
+            cache_changes: AtomicU64::new(0),
+            changed_projects: DashSet::new(),
+            diagnostics: DashMap::new(),
+            published: DashMap::new(),
+            open_documents: DashSet::new(),
+            restored: DashSet::new(),
+            accessed: DashMap::new(),
             init_info: OnceLock::new(),
             root_path: OnceLock::new(),
-        }
+            trim_pending: AtomicBool::new(false),
+            session_pending: AtomicBool::new(false),
+            shutting_down: Arc::new(AtomicBool::new(false)),
+            background: Mutex::new(JoinSet::new()),
+        }))
     }
 }
 
@@ -124,6 +220,37 @@ fn editconfig_setting_read<P: AsRef<Path>>(editconfig_path: P) -> Option<EditCon
     })
 }
 
+/// Format `files` in parallel and write them, a file failing does not stop
+/// the others. Returns whether every file was formatted
+fn format_in_place(
+    files: &[PathBuf],
+    use_space: bool,
+    indent_size: u32,
+    insert_final_newline: bool,
+) -> bool {
+    let mut succeeded = true;
+    for (path, result) in workspace::format_files(
+        files,
+        &HashMap::new(),
+        indent_size,
+        use_space,
+        insert_final_newline,
+        &AtomicBool::new(false),
+    ) {
+        let written = result.and_then(|(source, formatted)| {
+            if source != formatted {
+                std::fs::write(&path, formatted)?;
+            }
+            Ok(())
+        });
+        if let Err(err) = written {
+            tracing::error!("Failed to format '{}': {err:#}", path.display());
+            succeeded = false;
+        }
+    }
+    succeeded
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     clap_complete::CompleteEnv::with_factory(Cli::command)
@@ -144,53 +271,143 @@ async fn main() -> Result<()> {
     match args.command {
         Command::Stdio => {
             let (stdin, stdout) = (tokio::io::stdin(), tokio::io::stdout());
-            let (service, socket) = LspService::new(Backend::new);
-            Server::new(stdin, stdout, socket).serve(service).await;
+            let code = languageserver::serve(stdin, stdout).await;
+            // The runtime would wait for the blocking reader of stdin, which the
+            // editor may keep open
+            std::process::exit(code);
         }
         Command::Tcp { port } => {
             let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port)).await?;
             let (stream, _) = listener.accept().await?;
             let (read, write) = tokio::io::split(stream);
-            let (service, socket) = LspService::new(Backend::new);
-            Server::new(read, write, socket).serve(service).await;
+            let code = languageserver::serve(read, write).await;
+            // Do not wait for the background work still running
+            std::process::exit(code);
         }
         Command::Format {
             files: paths,
             inplace,
+            stdin,
+            stdin_filename,
         } => {
             let EditConfigSetting {
                 use_space,
                 indent_size,
                 insert_final_newline,
-            } = editconfig_setting().unwrap_or_default();
-
+            } = editconfig_setting()
+                .or_else(|| {
+                    let (indent_size, use_space) = formatting::foreign_indent()?;
+                    Some(EditConfigSetting {
+                        use_space,
+                        indent_size,
+                        ..Default::default()
+                    })
+                })
+                .unwrap_or_default();
+
+            if stdin {
+                format_stdin(
+                    stdin_filename.as_deref(),
+                    use_space,
+                    indent_size,
+                    insert_final_newline,
+                )?;
+            }
+            let mut files = vec![];
             for path in paths {
                 if !path.exists() {
                     tracing::warn!("Failed to format '{}': path doesn't exist", path.display());
                     continue;
                 }
                 if path.is_file() {
-                    format_file(&path, inplace, use_space, indent_size, insert_final_newline)?;
+                    files.push(path);
                 } else if path.is_dir() {
-                    for entry in Walk::new(path).flatten() {
-                        let path = entry.path();
-                        if path.is_file()
-                            && (path
-                                .file_name()
-                                .is_some_and(|name| name == "CMakeLists.txt")
-                                || path.extension().is_some_and(|ext| ext == "cmake"))
-                        {
-                            format_file(
-                                path,
-                                inplace,
-                                use_space,
-                                indent_size,
-                                insert_final_newline,
-                            )?;
-                        }
-                        // FIXME: Does this ignore recursive directories??
-                    }
+                    files.extend(
+                        Walk::new(path)
+                            .flatten()
+                            .map(|entry| entry.into_path())
+                            .filter(|path| {
+                                path.is_file()
+                                    && (path
+                                        .file_name()
+                                        .is_some_and(|name| name == "CMakeLists.txt")
+                                        || path.extension().is_some_and(|ext| ext == "cmake"))
+                            }),
+                    );
+                }
+            }
+            if !inplace {
+                for path in files {
+                    format_file(&path, use_space, indent_size, insert_final_newline)?;
                 }
+            } else if !format_in_place(&files, use_space, indent_size, insert_final_newline) {
+                std::process::exit(1);
+            }
+        }
+        Command::Lint {
+            paths,
+            fix,
+            baseline,
+            update_baseline,
+        } => {
+            let options = lint::LintOptions {
+                fix,
+                baseline,
+                update_baseline,
+            };
+            if lint::lint_paths(&paths, options).await? {
+                std::process::exit(1);
+            }
+        }
+        Command::Bench {
+            path,
+            iterations,
+            json,
+            baseline,
+            update_baseline,
+            threshold,
+        } => {
+            let options = bench::BenchOptions {
+                iterations,
+                json,
+                baseline,
+                update_baseline,
+                threshold,
+            };
+            if bench::bench_path(&path, options).await? {
+                std::process::exit(1);
+            }
+        }
+        Command::New {
+            path,
+            name,
+            language,
+            standard,
+            tests,
+            presets,
+        } => {
+            let options = scaffold::ProjectOptions {
+                name,
+                language,
+                standard,
+                tests,
+                presets,
+            };
+            for file in scaffold::new_project(&path, &options)? {
+                println!("Created {}", file.display());
+            }
+        }
+        Command::Capabilities { json } => {
+            let report = capabilities::capability_report();
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print!("{report}");
+            }
+        }
+        Command::Doctor => {
+            if doctor::doctor() {
+                std::process::exit(1);
             }
         }
         Command::Search { module, json } => {
@@ -0,0 +1,640 @@
+//! Support for `CMakePresets.json` and `CMakeUserPresets.json`.
+//!
+//! The presets are JSON, so the usual handlers do not apply to them. They get
+//! the completion of their fields, of the values cmake checks against its own
+//! lists (the `generator` and the `architecture` of the configure presets), of
+//! the cache variables of the project and of the presets they refer to, the
+//! hover of the fields, and diagnostics for what cmake refuses to load.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex, PoisonError};
+use std::time::SystemTime;
+
+use tower_lsp::lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionResponse, Diagnostic, DiagnosticSeverity,
+    Documentation, Hover, HoverContents, MarkedString, Position, Range,
+};
+
+use crate::complete::generator::{generator_items, platform_items};
+use crate::utils::json_scan::{self, Entry, Typed, offset_of, position_of, scan};
+use crate::{fileapi, inlay_hint, lint};
+
+const PRESETS_FILES: &[&str] = &["CMakePresets.json", "CMakeUserPresets.json"];
+
+const CONFIGURE: &str = "configurePresets";
+const BUILD: &str = "buildPresets";
+const TEST: &str = "testPresets";
+const PACKAGE: &str = "packagePresets";
+const WORKFLOW: &str = "workflowPresets";
+
+/// The arrays of presets, a name is unique in its array
+const PRESET_KINDS: &[&str] = &[CONFIGURE, BUILD, TEST, PACKAGE, WORKFLOW];
+
+/// The presets which can inherit others, of their kind
+const INHERITING: &[&str] = &[CONFIGURE, BUILD, TEST, PACKAGE];
+
+/// The fields of the presets, with the arrays of presets having them, the
+/// top level ones with none
+const PRESET_FIELDS: &[(&str, &[&str], &str)] = &[
+    (
+        "version",
+        &[],
+        "The version of the presets schema, which decides the fields cmake accepts",
+    ),
+    (
+        "cmakeMinimumRequired",
+        &[],
+        "The oldest cmake able to build the project",
+    ),
+    (
+        "include",
+        &[],
+        "Other presets files, whose presets can be inherited",
+    ),
+    (CONFIGURE, &[], "The presets of `cmake --preset`"),
+    (BUILD, &[], "The presets of `cmake --build --preset`"),
+    (TEST, &[], "The presets of `ctest --preset`"),
+    (PACKAGE, &[], "The presets of `cpack --preset`"),
+    (
+        WORKFLOW,
+        &[],
+        "The presets of `cmake --workflow --preset`, running a configure preset then build, test and package presets",
+    ),
+    (
+        "name",
+        PRESET_KINDS,
+        "The name of the preset, given to `--preset`",
+    ),
+    (
+        "displayName",
+        PRESET_KINDS,
+        "The name shown by `--list-presets`",
+    ),
+    ("description", PRESET_KINDS, "The description of the preset"),
+    (
+        "hidden",
+        INHERITING,
+        "Only for the other presets to inherit, not listed by `--list-presets`",
+    ),
+    (
+        "inherits",
+        INHERITING,
+        "The presets this one takes its fields from, the first ones first",
+    ),
+    (
+        "condition",
+        INHERITING,
+        "Whether the preset is enabled, like on one host system",
+    ),
+    (
+        "environment",
+        INHERITING,
+        "The environment variables, `$env{NAME}` and `$penv{NAME}` referring to the others",
+    ),
+    (
+        "vendor",
+        INHERITING,
+        "Data of other tools, ignored by cmake",
+    ),
+    (
+        "generator",
+        &[CONFIGURE],
+        "The generator of the build directory",
+    ),
+    (
+        "architecture",
+        &[CONFIGURE],
+        "The platform of the generators supporting it, like `x64` for Visual Studio",
+    ),
+    (
+        "toolset",
+        &[CONFIGURE],
+        "The toolset of the generators supporting it",
+    ),
+    (
+        "toolchainFile",
+        &[CONFIGURE],
+        "The toolchain file, `CMAKE_TOOLCHAIN_FILE`",
+    ),
+    (
+        "binaryDir",
+        &[CONFIGURE],
+        "The build directory, where `${sourceDir}` and the other macros are expanded",
+    ),
+    (
+        "installDir",
+        &[CONFIGURE],
+        "The install prefix, `CMAKE_INSTALL_PREFIX`",
+    ),
+    (
+        "cacheVariables",
+        &[CONFIGURE],
+        "The cache variables set with `-D`, a value or an object with `type` and `value`",
+    ),
+    (
+        "cmakeExecutable",
+        &[CONFIGURE],
+        "The cmake used by the IDEs for this preset",
+    ),
+    (
+        "warnings",
+        &[CONFIGURE],
+        "The warnings enabled, like `dev` or `deprecated`",
+    ),
+    ("errors", &[CONFIGURE], "The warnings turned into errors"),
+    (
+        "debug",
+        &[CONFIGURE],
+        "The debug output of cmake, like `find`",
+    ),
+    (
+        "configurePreset",
+        &[BUILD, TEST, PACKAGE],
+        "The configure preset whose build directory is used",
+    ),
+    (
+        "inheritConfigureEnvironment",
+        &[BUILD, TEST, PACKAGE],
+        "Take the environment of the configure preset",
+    ),
+    ("jobs", &[BUILD], "The number of parallel jobs"),
+    ("targets", &[BUILD], "The targets to build"),
+    (
+        "configuration",
+        &[BUILD, TEST],
+        "The configuration of the multi configuration generators",
+    ),
+    (
+        "cleanFirst",
+        &[BUILD],
+        "Clean the build directory before building",
+    ),
+    ("verbose", &[BUILD], "Print the commands run by the build"),
+    (
+        "nativeToolOptions",
+        &[BUILD],
+        "The options passed to the build tool",
+    ),
+    (
+        "output",
+        &[TEST, PACKAGE],
+        "The output of ctest or cpack, like `outputOnFailure`",
+    ),
+    ("filter", &[TEST], "The tests to run or exclude"),
+    (
+        "execution",
+        &[TEST],
+        "How the tests are run, like `stopOnFailure` or `timeout`",
+    ),
+    ("generators", &[PACKAGE], "The cpack generators"),
+    ("configurations", &[PACKAGE], "The configurations packaged"),
+    ("packageName", &[PACKAGE], "The name of the package"),
+    ("packageVersion", &[PACKAGE], "The version of the package"),
+    (
+        "steps",
+        &[WORKFLOW],
+        "The presets run by the workflow, in order",
+    ),
+];
+
+pub fn is_presets<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref()
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| PRESETS_FILES.contains(&name))
+}
+
+/// The JSON files other than the presets and the vcpkg manifest, which the
+/// server leaves alone when the editor sends them
+pub fn is_ignored_json<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref()
+        .extension()
+        .is_some_and(|extension| extension == "json")
+        && !is_presets(&path)
+        && !crate::vcpkg::is_manifest(&path)
+}
+
+/// The presets declared by `entries`, with the array they are in
+fn preset_names<'a, 'b>(entries: &'b [Entry<'a>]) -> Vec<(&'a str, &'b Entry<'a>)> {
+    entries
+        .iter()
+        .filter(|entry| entry.key == "name")
+        .filter_map(|entry| match entry.parents.as_slice() {
+            [kind] if PRESET_KINDS.contains(kind) => Some((*kind, entry)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The files whose presets `local_path` can refer to: its includes, and the
+/// project presets for the user presets
+fn included_files(local_path: &Path, entries: &[Entry]) -> Vec<PathBuf> {
+    let Some(dir) = local_path.parent() else {
+        return vec![];
+    };
+    let mut files: Vec<PathBuf> = entries
+        .iter()
+        .filter(|entry| entry.key == "include" && entry.parents.is_empty())
+        .map(|entry| dir.join(entry.value))
+        .collect();
+    if local_path
+        .file_name()
+        .is_some_and(|name| name == "CMakeUserPresets.json")
+    {
+        files.push(dir.join("CMakePresets.json"));
+    }
+    files
+}
+
+/// The presets of the files included by `local_path`, recursively
+fn external_presets(
+    local_path: &Path,
+    entries: &[Entry],
+    visited: &mut HashSet<PathBuf>,
+    names: &mut Vec<(String, String)>,
+) {
+    for file in included_files(local_path, entries) {
+        if !visited.insert(file.clone()) {
+            continue;
+        }
+        let Ok(source) = std::fs::read_to_string(&file) else {
+            continue;
+        };
+        let (entries, _) = scan(&source);
+        names.extend(
+            preset_names(&entries)
+                .into_iter()
+                .map(|(kind, entry)| (kind.to_string(), entry.value.to_string())),
+        );
+        external_presets(&file, &entries, visited, names);
+    }
+}
+
+/// The presets `local_path` can refer to, as `(array, name)`
+fn known_presets(local_path: &Path, entries: &[Entry]) -> Vec<(String, String)> {
+    let mut names: Vec<(String, String)> = preset_names(entries)
+        .into_iter()
+        .map(|(kind, entry)| (kind.to_string(), entry.value.to_string()))
+        .collect();
+    let mut visited = HashSet::from([local_path.to_path_buf()]);
+    external_presets(local_path, entries, &mut visited, &mut names);
+    names
+}
+
+type Declarations = (SystemTime, Vec<(String, String)>);
+
+/// The cache variables declared by each file, while it is not modified
+static DECLARATIONS: LazyLock<Mutex<HashMap<PathBuf, Declarations>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// The cache variables declared by `file`, parsed again only when it changes
+fn file_declarations(file: &Path) -> Vec<(String, String)> {
+    let Ok(modified) = std::fs::metadata(file).and_then(|metadata| metadata.modified()) else {
+        return vec![];
+    };
+    if let Some((cached, declarations)) = DECLARATIONS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(file)
+        && *cached == modified
+    {
+        return declarations.clone();
+    }
+    let Ok(source) = std::fs::read_to_string(file) else {
+        return vec![];
+    };
+    let declarations = inlay_hint::cache_declarations(&source);
+    DECLARATIONS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(file.to_path_buf(), (modified, declarations.clone()));
+    declarations
+}
+
+/// The cache variables declared by the project of the presets, with their
+/// default, and the ones of the CMake cache
+fn cache_variables(local_path: &Path) -> BTreeMap<String, String> {
+    let mut variables = BTreeMap::new();
+    if let Some(dir) = local_path.parent() {
+        for file in lint::collect_files(&[dir.to_path_buf()]) {
+            variables.extend(file_declarations(&file));
+        }
+    }
+    for (variable, value) in fileapi::get_entries_data().unwrap_or_default() {
+        variables.entry(variable).or_insert(value);
+    }
+    variables
+}
+
+fn field_items(parents: &[&str]) -> Vec<CompletionItem> {
+    let in_preset = match parents {
+        [] => None,
+        [kind] if PRESET_KINDS.contains(kind) => Some(*kind),
+        _ => return vec![],
+    };
+    PRESET_FIELDS
+        .iter()
+        .filter(|(_, kinds, _)| match in_preset {
+            Some(kind) => kinds.contains(&kind),
+            None => kinds.is_empty(),
+        })
+        .map(|(field, _, doc)| CompletionItem {
+            label: field.to_string(),
+            kind: Some(CompletionItemKind::PROPERTY),
+            detail: Some("Preset field".to_string()),
+            documentation: Some(Documentation::String(doc.to_string())),
+            ..Default::default()
+        })
+        .collect()
+}
+
+fn preset_items(names: Vec<(String, String)>, kind: &str) -> Vec<CompletionItem> {
+    let mut labels: Vec<String> = names
+        .into_iter()
+        .filter(|(preset_kind, _)| preset_kind == kind)
+        .map(|(_, name)| name)
+        .collect();
+    labels.sort();
+    labels.dedup();
+    labels
+        .into_iter()
+        .map(|name| CompletionItem {
+            label: name,
+            kind: Some(CompletionItemKind::REFERENCE),
+            detail: Some(format!("Preset of {kind}")),
+            ..Default::default()
+        })
+        .collect()
+}
+
+pub fn complete(local_path: &Path, source: &str, location: Position) -> Option<CompletionResponse> {
+    let offset = offset_of(source, location)?;
+    let line = source
+        .lines()
+        .nth(location.line as usize)
+        .unwrap_or_default();
+    let (_, typed) = scan(&source[..offset]);
+    let items = match typed? {
+        Typed::Key { parents, .. } if parents.last() == Some(&"cacheVariables") => {
+            cache_variables(local_path)
+                .into_iter()
+                .map(|(variable, default)| CompletionItem {
+                    label: variable,
+                    kind: Some(CompletionItemKind::VARIABLE),
+                    detail: Some(format!("Cache variable, default {default}")),
+                    ..Default::default()
+                })
+                .collect()
+        }
+        Typed::Key { parents, .. } => field_items(&parents),
+        Typed::Value(entry) => match (entry.key, entry.parents.as_slice()) {
+            ("generator", [CONFIGURE]) => generator_items(line, location, true),
+            ("architecture", [CONFIGURE]) | ("value", [CONFIGURE, "architecture"]) => {
+                platform_items(line, location, true)
+            }
+            ("inherits", [kind]) if INHERITING.contains(kind) => {
+                let (entries, _) = scan(source);
+                preset_items(known_presets(local_path, &entries), kind)
+            }
+            ("configurePreset", [_]) => {
+                let (entries, _) = scan(source);
+                preset_items(known_presets(local_path, &entries), CONFIGURE)
+            }
+            _ => return None,
+        },
+    };
+    if items.is_empty() {
+        return None;
+    }
+    Some(CompletionResponse::Array(items))
+}
+
+/// The documentation of the field, or of the cache variable, under `location`
+pub fn hover(local_path: &Path, source: &str, location: Position) -> Option<Hover> {
+    let offset = offset_of(source, location)?;
+    let Some(Typed::Key { parents, offset }) = scan(&source[..offset]).1 else {
+        return None;
+    };
+    let key = source[offset..].split(['"', '\n']).next()?;
+    let contents = if parents.last() == Some(&"cacheVariables") {
+        let default = cache_variables(local_path).remove(key)?;
+        format!("{key}\n\nCache variable of the project, default {default}")
+    } else {
+        let (_, _, doc) = PRESET_FIELDS.iter().find(|(field, _, _)| *field == key)?;
+        format!("{key}\n\n{doc}")
+    };
+    Some(Hover {
+        contents: HoverContents::Scalar(MarkedString::String(contents)),
+        range: Some(Range::new(
+            position_of(source, offset),
+            position_of(source, offset + key.len()),
+        )),
+    })
+}
+
+fn error(source: &str, entry: &Entry, message: String) -> Diagnostic {
+    Diagnostic {
+        range: Range::new(
+            position_of(source, entry.offset),
+            position_of(source, entry.offset + entry.value.len()),
+        ),
+        severity: Some(DiagnosticSeverity::ERROR),
+        source: Some("neocmakelsp".to_string()),
+        message,
+        ..Default::default()
+    }
+}
+
+/// The problems making cmake refuse the presets: invalid JSON, no version,
+/// presets declared twice and references to unknown presets
+pub fn check(local_path: &Path, source: &str) -> Vec<Diagnostic> {
+    let json = match json_scan::parse(source) {
+        Ok(json) => json,
+        Err(error) => return vec![*error],
+    };
+    let mut output = vec![];
+    if json.get("version").is_none() {
+        output.push(Diagnostic {
+            range: Range::default(),
+            severity: Some(DiagnosticSeverity::ERROR),
+            source: Some("neocmakelsp".to_string()),
+            message: "The presets have no version, cmake requires one".to_string(),
+            ..Default::default()
+        });
+    }
+    let (entries, _) = scan(source);
+    let mut declared = HashSet::new();
+    for (kind, entry) in preset_names(&entries) {
+        if !declared.insert((kind, entry.value)) {
+            output.push(error(
+                source,
+                entry,
+                format!("The preset {} is already declared in {kind}", entry.value),
+            ));
+        }
+    }
+    let known = known_presets(local_path, &entries);
+    let is_known = |kind: &str, name: &str| {
+        known
+            .iter()
+            .any(|(known_kind, known_name)| known_kind == kind && known_name == name)
+    };
+    for entry in &entries {
+        let kind = match (entry.key, entry.parents.as_slice()) {
+            ("inherits", [kind]) if INHERITING.contains(kind) => *kind,
+            ("configurePreset", [kind]) if PRESET_KINDS.contains(kind) => CONFIGURE,
+            _ => continue,
+        };
+        if !is_known(kind, entry.value) {
+            output.push(error(
+                source,
+                entry,
+                format!("No preset named {} in {kind}", entry.value),
+            ));
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets_completion() {
+        assert!(is_presets("/project/CMakeUserPresets.json"));
+        assert!(!is_presets("/project/package.json"));
+        assert!(is_ignored_json("/project/package.json"));
+        assert!(!is_ignored_json("/project/CMakeLists.txt"));
+        assert!(!is_ignored_json("/project/vcpkg.json"));
+
+        let path = Path::new("/tmp/presets/CMakePresets.json");
+        let source = r#"{
+  "configurePresets": [
+    {
+      "name": "vs",
+      "architecture": { "value": "x
+      "cacheVariables": { "value": "
+    }
+  ]
+}"#;
+        let Some(CompletionResponse::Array(items)) = complete(path, source, Position::new(4, 35))
+        else {
+            panic!("no platforms");
+        };
+        assert!(items.iter().any(|item| item.label == "ARM64"));
+        assert!(complete(path, source, Position::new(5, 36)).is_none());
+        assert!(complete(path, source, Position::new(3, 18)).is_none());
+
+        let source = r#"{
+  "configurePresets": [
+    { "name": "vs", "generator": "Ninja" },
+    { "name": "ninja", "inherits": [ "vs", "" ] },
+    {  }
+  ],
+  "buildPresets": [ { "name": "release", "configurePreset": "" } ]
+}"#;
+        let Some(CompletionResponse::Array(items)) = complete(path, source, Position::new(3, 44))
+        else {
+            panic!("no presets");
+        };
+        let labels: Vec<&str> = items.iter().map(|item| item.label.as_str()).collect();
+        assert_eq!(labels, vec!["ninja", "vs"]);
+        let Some(CompletionResponse::Array(items)) = complete(path, source, Position::new(6, 61))
+        else {
+            panic!("no configure presets");
+        };
+        assert_eq!(items.len(), 2);
+
+        let source = "{\n  \"buildPresets\": [ { \"";
+        let Some(CompletionResponse::Array(items)) = complete(path, source, Position::new(1, 23))
+        else {
+            panic!("no fields");
+        };
+        assert!(items.iter().any(|item| item.label == "jobs"));
+        assert!(!items.iter().any(|item| item.label == "binaryDir"));
+    }
+
+    #[test]
+    fn test_presets_hover() {
+        let path = Path::new("/tmp/presets/CMakePresets.json");
+        let source =
+            "{\n  \"configurePresets\": [ { \"name\": \"a\", \"binaryDir\": \"build\" } ]\n}\n";
+        let hover = hover(path, source, Position::new(1, 42)).unwrap();
+        let HoverContents::Scalar(MarkedString::String(contents)) = hover.contents else {
+            panic!("no documentation");
+        };
+        assert!(contents.starts_with("binaryDir\n\nThe build directory"));
+        assert_eq!(
+            hover.range,
+            Some(Range::new(Position::new(1, 40), Position::new(1, 49)))
+        );
+        // A value
+        assert!(super::hover(path, source, Position::new(1, 53)).is_none());
+
+        // The characters count UTF-16 code units
+        let source =
+            "{\n  \"configurePresets\": [ { \"name\": \"é😀\", \"binaryDir\": \"b\" } ]\n}\n";
+        let hover = super::hover(path, source, Position::new(1, 44)).unwrap();
+        assert_eq!(
+            hover.range,
+            Some(Range::new(Position::new(1, 42), Position::new(1, 51)))
+        );
+    }
+
+    #[test]
+    fn test_cache_variables_follow_the_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let presets = dir.path().join("CMakePresets.json");
+        let lists = dir.path().join("CMakeLists.txt");
+        std::fs::write(&lists, "option(WITH_A \"a\" ON)\n").unwrap();
+        assert_eq!(cache_variables(&presets).get("WITH_A").unwrap(), "ON");
+
+        std::fs::write(&lists, "option(WITH_B \"b\" OFF)\n").unwrap();
+        let file = std::fs::File::options().append(true).open(&lists).unwrap();
+        file.set_modified(SystemTime::now() + std::time::Duration::from_secs(10))
+            .unwrap();
+        let variables = cache_variables(&presets);
+        assert!(!variables.contains_key("WITH_A"));
+        assert_eq!(variables.get("WITH_B").unwrap(), "OFF");
+    }
+
+    #[test]
+    fn test_presets_check() {
+        let path = Path::new("/tmp/presets/CMakePresets.json");
+        let errors = check(path, "{\n  \"version\": 6,\n  \"configurePresets\": [\n");
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].message.starts_with("Invalid JSON"));
+
+        let source = r#"{
+  "configurePresets": [
+    { "name": "base", "hidden": true },
+    { "name": "dev", "inherits": ["base", "missing"] },
+    { "name": "dev" }
+  ],
+  "buildPresets": [ { "name": "dev", "configurePreset": "dev" } ],
+  "workflowPresets": [
+    { "name": "all", "steps": [ { "type": "configure", "name": "dev" } ] }
+  ]
+}"#;
+        let messages: Vec<(u32, String)> = check(path, source)
+            .into_iter()
+            .map(|error| (error.range.start.line, error.message))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                (
+                    0,
+                    "The presets have no version, cmake requires one".to_string()
+                ),
+                (
+                    4,
+                    "The preset dev is already declared in configurePresets".to_string()
+                ),
+                (3, "No preset named missing in configurePresets".to_string()),
+            ]
+        );
+    }
+}
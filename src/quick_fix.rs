@@ -3,13 +3,17 @@ use std::sync::LazyLock;
 use regex::Regex;
 use tower_lsp::lsp_types::{
     CodeAction, CodeActionKind, CodeActionOrCommand, CodeActionResponse, Diagnostic,
-    DocumentChanges, OneOf, OptionalVersionedTextDocumentIdentifier, Range, TextDocumentEdit,
-    TextEdit, WorkspaceEdit,
+    DocumentChanges, OneOf, OptionalVersionedTextDocumentIdentifier, Position, Range,
+    TextDocumentEdit, TextEdit, WorkspaceEdit,
 };
+use tree_sitter::{Node, Point};
 
-use crate::CMakeNodeKinds;
+use crate::config;
 use crate::consts::TREESITTER_CMAKE_LANGUAGE;
-use crate::utils::treehelper::ToPosition;
+use crate::formatting::whitespace::{WhitespaceStyle, whitespace_fixes};
+use crate::gammar::{self, Fix, line_length};
+use crate::utils::module_path;
+use crate::utils::treehelper::{ToPosition, command_at};
 
 static LINT_REGEX: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r#"((?<length>\d+)/(?<max>\d+))"#).unwrap());
@@ -25,100 +29,260 @@ pub fn lint_fix_action(
     let mut parse = tree_sitter::Parser::new();
     parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
     let tree = parse.parse(context, None)?;
-    let root = tree.root_node();
-    sub_lint_fix_action(
-        root,
-        &context.lines().collect(),
-        line as usize,
-        diagnose,
-        longest,
-        &uri,
-    )
+    let lines: Vec<&str> = context.lines().collect();
+    let fix = line_length::wrap_fix(tree.root_node(), &lines, line as usize, longest)?;
+    Some(vec![fix_action(fix, diagnose, &uri)])
 }
 
-fn sub_lint_fix_action(
-    input: tree_sitter::Node,
-    source: &Vec<&str>,
-    line: usize,
+fn fix_action(
+    fix: Fix,
     diagnose: &Diagnostic,
-    longest: usize,
     uri: &tower_lsp::lsp_types::Uri,
+) -> CodeActionOrCommand {
+    edit_action(fix, Some(diagnose), uri)
+}
+
+/// A quick fix applying `fix`, resolving `diagnose` when there is one
+fn edit_action(
+    fix: Fix,
+    diagnose: Option<&Diagnostic>,
+    uri: &tower_lsp::lsp_types::Uri,
+) -> CodeActionOrCommand {
+    let edit = TextEdit {
+        range: Range {
+            start: fix.start.to_position(),
+            end: fix.end.to_position(),
+        },
+        new_text: fix.new_text,
+    };
+    CodeActionOrCommand::CodeAction(CodeAction {
+        title: fix.title,
+        kind: Some(CodeActionKind::QUICKFIX),
+        diagnostics: diagnose.map(|diagnose| vec![diagnose.clone()]),
+        edit: Some(WorkspaceEdit {
+            changes: None,
+            change_annotations: None,
+            document_changes: Some(DocumentChanges::Edits(vec![TextDocumentEdit {
+                text_document: OptionalVersionedTextDocumentIdentifier {
+                    uri: uri.clone(),
+                    version: None,
+                },
+                edits: vec![OneOf::Left(edit)],
+            }])),
+        }),
+        command: None,
+        is_preferred: diagnose.map(|_| true),
+        disabled: None,
+        data: None,
+    })
+}
+
+/// Apply the fix of a diagnostic found by [`gammar::fixable_issues`]
+pub fn issue_fix_action(
+    context: &str,
+    diagnose: &Diagnostic,
+    uri: tower_lsp::lsp_types::Uri,
 ) -> Option<CodeActionResponse> {
-    let mut cursor = input.walk();
-    for child in input.children(&mut cursor) {
-        if child.end_position().row < line {
-            continue;
-        }
-        if child.start_position().row > line {
-            break;
+    let local_path = uri.to_file_path().ok();
+    let issue = gammar::fixable_issues(local_path.as_deref(), context)
+        .into_iter()
+        .find(|issue| {
+            issue.message == diagnose.message
+                && issue.start_point.to_position() == diagnose.range.start
+        })?;
+    Some(vec![fix_action(issue.fix, diagnose, &uri)])
+}
+
+/// Add the `cmake_minimum_required()` and `project()` calls missing from the
+/// top level CMakeLists.txt
+pub fn preamble_action(
+    context: &str,
+    uri: tower_lsp::lsp_types::Uri,
+) -> Option<CodeActionOrCommand> {
+    let file_path = uri.to_file_path().ok()?;
+    if module_path::source_dir().is_none_or(|dir| file_path != dir.join("CMakeLists.txt")) {
+        return None;
+    }
+    let fix = gammar::preamble_fix(file_path, context)?;
+    Some(edit_action(fix, None, &uri))
+}
+
+/// Strip the trailing whitespace, end the file with a newline and collapse the
+/// blank lines past `max_blank_lines`, touching only the lines which change
+pub fn whitespace_action(
+    context: &str,
+    uri: tower_lsp::lsp_types::Uri,
+) -> Option<CodeActionOrCommand> {
+    let mut parse = tree_sitter::Parser::new();
+    parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+    let tree = parse.parse(context, None)?;
+    let format = config::format_config(uri.to_file_path().ok().as_deref());
+    let style = WhitespaceStyle {
+        trim_trailing_whitespace: true,
+        ..WhitespaceStyle::configured(&format, true)
+    };
+    let fixes = whitespace_fixes(context, tree.root_node(), &style);
+    if fixes.is_empty() {
+        return None;
+    }
+    let edits = fixes
+        .into_iter()
+        .map(|fix| {
+            OneOf::Left(TextEdit {
+                range: Range {
+                    start: fix.start.to_position(),
+                    end: fix.end.to_position(),
+                },
+                new_text: fix.new_text,
+            })
+        })
+        .collect();
+    Some(CodeActionOrCommand::CodeAction(CodeAction {
+        title: "Normalize whitespace".to_string(),
+        kind: Some(CodeActionKind::SOURCE),
+        diagnostics: None,
+        edit: Some(WorkspaceEdit {
+            changes: None,
+            change_annotations: None,
+            document_changes: Some(DocumentChanges::Edits(vec![TextDocumentEdit {
+                text_document: OptionalVersionedTextDocumentIdentifier { uri, version: None },
+                edits,
+            }])),
+        }),
+        command: None,
+        is_preferred: None,
+        disabled: None,
+        data: None,
+    }))
+}
+
+fn is_variable_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '+' | '-'))
+}
+
+/// The variable selected, or the one referenced or set under the cursor
+fn selected_variable(source: &str, root: Node, range: Range) -> Option<String> {
+    let line = source.lines().nth(range.start.line as usize)?;
+    if range.start != range.end {
+        if range.start.line != range.end.line {
+            return None;
         }
-        match child.kind() {
-            CMakeNodeKinds::ARGUMENT_LIST => {
-                let start = child.start_position().to_position();
-                let end = child.end_position().to_position();
-                let range = Range { start, end };
-                let mut arg_cursor = child.walk();
-                let mut start_row = start.character as usize;
-                let start_space_len = start.character as usize;
-                let start_space: String = vec![' '; start_space_len].iter().collect();
-                let mut new_text = "".to_string();
-                for arg in child.children(&mut arg_cursor) {
-                    let current_row = arg.start_position().row;
-                    // I mean I cannot fix this problem
-                    if current_row != arg.end_position().row {
-                        return None;
-                    }
-                    let start_col = arg.start_position().column;
-                    let end_col = arg.end_position().column;
-                    let len = arg.end_position().column - arg.start_position().column;
-                    let arg = &source[current_row][start_col..end_col];
-                    if start_row + len + 1 > longest {
-                        start_row = start_space_len + len + 1;
-                        new_text.push('\n');
-                        new_text.push_str(&start_space);
-                    } else {
-                        start_row += len + 1;
-                        if !new_text.is_empty() {
-                            new_text.push(' ');
-                        }
-                    }
-                    new_text.push_str(arg);
-                }
-                return Some(vec![CodeActionOrCommand::CodeAction(CodeAction {
-                    title: "too long lint fix".to_string(),
-                    kind: Some(CodeActionKind::QUICKFIX),
-                    diagnostics: Some(vec![diagnose.clone()]),
-                    edit: Some(WorkspaceEdit {
-                        changes: None,
-                        change_annotations: None,
-                        document_changes: Some(DocumentChanges::Edits(vec![TextDocumentEdit {
-                            text_document: OptionalVersionedTextDocumentIdentifier {
-                                uri: uri.clone(),
-                                version: None,
-                            },
-                            edits: vec![OneOf::Left(TextEdit { range, new_text })],
-                        }])),
-                    }),
-                    command: None,
-                    is_preferred: None,
-                    disabled: None,
-                    data: None,
-                })]);
-            }
-            _ => {
-                let try_data = sub_lint_fix_action(child, source, line, diagnose, longest, uri);
-                if try_data.is_some() {
-                    return try_data;
-                }
-            }
+        let text = line
+            .get(range.start.character as usize..range.end.character as usize)?
+            .trim();
+        let name = text
+            .strip_prefix("${")
+            .and_then(|text| text.strip_suffix('}'))
+            .unwrap_or(text);
+        return is_variable_name(name).then(|| name.to_string());
+    }
+    let column = range.start.character as usize;
+    if let Some(start) = line.get(..column).and_then(|before| before.rfind("${")) {
+        let name_start = start + 2;
+        if let Some(len) = line[name_start..].find('}')
+            && name_start + len >= column
+            && is_variable_name(&line[name_start..name_start + len])
+        {
+            return Some(line[name_start..name_start + len].to_string());
         }
     }
-    None
+    let point = Point {
+        row: range.start.line as usize,
+        column,
+    };
+    let command = command_at(root, point, source)?;
+    if !matches!(command.name.as_str(), "set" | "option" | "unset") || command.argument_index != 0 {
+        return None;
+    }
+    // The arguments only go up to the cursor, which may be at the start of the name
+    let is_name_char = |c: char| !c.is_whitespace() && !matches!(c, '(' | ')' | '"' | '#');
+    let start = line
+        .get(..column)?
+        .rfind(|c| !is_name_char(c))
+        .map_or(0, |index| index + 1);
+    let end = line
+        .get(column..)?
+        .find(|c| !is_name_char(c))
+        .map_or(line.len(), |index| column + index);
+    let name = &line[start..end];
+    is_variable_name(name).then(|| name.to_string())
+}
+
+/// The edit printing the selected variable with `message(STATUS)` after the
+/// command it is in, with the name of the variable
+pub fn print_variable_edit(source: &str, range: Range) -> Option<(String, TextEdit)> {
+    let mut parse = tree_sitter::Parser::new();
+    parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+    let tree = parse.parse(source, None)?;
+    let name = selected_variable(source, tree.root_node(), range)?;
+
+    let point = Point {
+        row: range.end.line as usize,
+        column: range.end.character as usize,
+    };
+    let mut command = tree.root_node().descendant_for_point_range(point, point);
+    while let Some(node) = command
+        && !node.kind().ends_with("_command")
+    {
+        command = node.parent();
+    }
+    let (first_row, last_row) = command.map_or((point.row, point.row), |node| {
+        (node.start_position().row, node.end_position().row)
+    });
+    let lines: Vec<&str> = source.lines().collect();
+    let first_line = lines.get(first_row).copied().unwrap_or_default();
+    let indent = &first_line[..first_line.len() - first_line.trim_start().len()];
+    let message = format!("{indent}message(STATUS \"{name}=${{{name}}}\")");
+    let edit = if last_row + 1 < lines.len() || source.ends_with('\n') {
+        let start = Position::new(last_row as u32 + 1, 0);
+        TextEdit {
+            range: Range::new(start, start),
+            new_text: format!("{message}\n"),
+        }
+    } else {
+        let last_line = lines.get(last_row).copied().unwrap_or_default();
+        let end = Position::new(last_row as u32, last_line.encode_utf16().count() as u32);
+        TextEdit {
+            range: Range::new(end, end),
+            new_text: format!("\n{message}"),
+        }
+    };
+    Some((name, edit))
+}
+
+/// Print the selected variable with `message(STATUS "VAR=${VAR}")`
+pub fn print_variable_action(
+    context: &str,
+    range: Range,
+    uri: tower_lsp::lsp_types::Uri,
+) -> Option<CodeActionOrCommand> {
+    let (name, edit) = print_variable_edit(context, range)?;
+    Some(CodeActionOrCommand::CodeAction(CodeAction {
+        title: format!("Print {name} with message()"),
+        kind: Some(CodeActionKind::REFACTOR),
+        diagnostics: None,
+        edit: Some(WorkspaceEdit {
+            changes: None,
+            change_annotations: None,
+            document_changes: Some(DocumentChanges::Edits(vec![TextDocumentEdit {
+                text_document: OptionalVersionedTextDocumentIdentifier { uri, version: None },
+                edits: vec![OneOf::Left(edit)],
+            }])),
+        }),
+        command: None,
+        is_preferred: None,
+        disabled: None,
+        data: None,
+    }))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::gammar::balance;
 
     #[test]
     fn lint_regex_text() {
@@ -127,4 +291,69 @@ mod tests {
         assert_eq!(&caps["length"], "92");
         assert_eq!(&caps["max"], "80");
     }
+
+    #[test]
+    fn issue_fix() {
+        let context = "message(\"hello)\nset(A b)\n";
+        let diagnose = Diagnostic {
+            range: Range {
+                start: tower_lsp::lsp_types::Position {
+                    line: 0,
+                    character: 8,
+                },
+                end: tower_lsp::lsp_types::Position {
+                    line: 0,
+                    character: 15,
+                },
+            },
+            message: balance::UNTERMINATED_QUOTE.to_string(),
+            ..Default::default()
+        };
+        let uri: tower_lsp::lsp_types::Uri = "file:///tmp/CMakeLists.txt".parse().unwrap();
+        let actions = issue_fix_action(context, &diagnose, uri).unwrap();
+        let CodeActionOrCommand::CodeAction(action) = &actions[0] else {
+            panic!("expected a code action");
+        };
+        let Some(DocumentChanges::Edits(edits)) =
+            action.edit.as_ref().unwrap().document_changes.as_ref()
+        else {
+            panic!("expected text edits");
+        };
+        let OneOf::Left(edit) = &edits[0].edits[0] else {
+            panic!("expected a text edit");
+        };
+        assert_eq!(edit.new_text, "\"");
+        assert_eq!(edit.range.start.character, 14);
+    }
+
+    #[test]
+    fn test_print_variable_edit() {
+        let source = "if(ON)\n  set(OUTPUT_DIR ${BASE}/out\n      CACHE PATH \"\")\nendif()\n";
+        let at = |line, start, end| {
+            print_variable_edit(
+                source,
+                Range::new(Position::new(line, start), Position::new(line, end)),
+            )
+        };
+        // The reference under the cursor, printed after the whole command
+        let (name, edit) = at(1, 22, 22).unwrap();
+        assert_eq!(name, "BASE");
+        assert_eq!(edit.range.start, Position::new(3, 0));
+        assert_eq!(edit.new_text, "  message(STATUS \"BASE=${BASE}\")\n");
+        // The variable set under the cursor
+        assert_eq!(at(1, 8, 8).unwrap().0, "OUTPUT_DIR");
+        // A selected name
+        assert_eq!(at(1, 6, 16).unwrap().0, "OUTPUT_DIR");
+        assert_eq!(at(1, 17, 24).unwrap().0, "BASE");
+        assert!(at(2, 13, 13).is_none());
+        assert!(at(2, 6, 16).is_none());
+
+        let (_, edit) = print_variable_edit(
+            "set(A 1)",
+            Range::new(Position::new(0, 4), Position::new(0, 4)),
+        )
+        .unwrap();
+        assert_eq!(edit.range.start, Position::new(0, 8));
+        assert_eq!(edit.new_text, "\nmessage(STATUS \"A=${A}\")");
+    }
 }
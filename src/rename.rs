@@ -4,7 +4,48 @@ use std::path::Path;
 use dashmap::DashMap;
 use tower_lsp::lsp_types::{Location, Position, TextEdit, Uri, WorkspaceEdit};
 
+use crate::consts::TREESITTER_CMAKE_LANGUAGE;
 use crate::jump;
+use crate::jump::target::{project_references, target_at};
+use crate::utils::document::Document;
+use crate::utils::targets;
+
+/// Rename the target under `location` in every CMake file of the project,
+/// `source` being the content of `local_path` in the editor
+async fn rename_target(
+    edited: &str,
+    location: Position,
+    local_path: &Path,
+    source: &str,
+    root_path: Option<&Path>,
+    documents: &DashMap<Uri, Document>,
+) -> Option<HashMap<Uri, Vec<TextEdit>>> {
+    let name = {
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).ok()?;
+        let tree = parse.parse(source, None)?;
+        let lines: Vec<&str> = source.lines().collect();
+        let project = targets::project_targets(local_path, &lines, tree.root_node());
+        target_at(location, source, tree.root_node(), &project)?
+    };
+
+    let references = project_references(local_path, source, root_path, documents, vec![name]).await;
+    Some(
+        references
+            .into_iter()
+            .map(|(uri, ranges)| {
+                let edits = ranges
+                    .into_iter()
+                    .map(|range| TextEdit {
+                        range,
+                        new_text: edited.to_string(),
+                    })
+                    .collect();
+                (uri, edits)
+            })
+            .collect(),
+    )
+}
 
 pub async fn rename<P: AsRef<Path>>(
     edited: &str,
@@ -12,8 +53,25 @@ pub async fn rename<P: AsRef<Path>>(
     originuri: P,
     client: &tower_lsp::Client,
     source: &str,
-    documents: &DashMap<Uri, String>,
+    root_path: Option<&Path>,
+    documents: &DashMap<Uri, Document>,
 ) -> Option<WorkspaceEdit> {
+    if let Some(changes) = rename_target(
+        edited,
+        location,
+        originuri.as_ref(),
+        source,
+        root_path,
+        documents,
+    )
+    .await
+    {
+        return Some(WorkspaceEdit {
+            changes: Some(changes),
+            ..Default::default()
+        });
+    }
+
     let mut changes: HashMap<Uri, Vec<TextEdit>> = HashMap::new();
     let defs = jump::godef(location, source, originuri, client, false, true, documents).await?;
 
@@ -29,3 +87,57 @@ pub async fn rename<P: AsRef<Path>>(
         ..Default::default()
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::Range;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rename_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let top = dir.path().join("CMakeLists.txt");
+        let tests = dir.path().join("tests");
+        std::fs::create_dir(&tests).unwrap();
+        std::fs::write(
+            tests.join("CMakeLists.txt"),
+            "add_test(NAME core COMMAND $<TARGET_FILE:core>)\n",
+        )
+        .unwrap();
+        let source = "add_executable(core main.cpp)\nadd_subdirectory(tests)\n";
+        std::fs::write(&top, source).unwrap();
+
+        let changes = rename_target(
+            "engine",
+            Position::new(0, 17),
+            &top,
+            source,
+            Some(dir.path()),
+            &DashMap::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(
+            changes[&Uri::from_file_path(&top).unwrap()][0].range,
+            Range::new(Position::new(0, 15), Position::new(0, 19))
+        );
+        assert_eq!(
+            changes[&Uri::from_file_path(tests.join("CMakeLists.txt")).unwrap()][0].range,
+            Range::new(Position::new(0, 41), Position::new(0, 45))
+        );
+        assert!(
+            rename_target(
+                "engine",
+                Position::new(1, 19),
+                &top,
+                source,
+                Some(dir.path()),
+                &DashMap::new(),
+            )
+            .await
+            .is_none()
+        );
+    }
+}
@@ -0,0 +1,164 @@
+//! Registry of the lint rules.
+//!
+//! Every diagnostic of the server belongs to a rule, which gives it a stable id
+//! shown as the diagnostic code, a default severity, a category and the page
+//! documenting it. The severity of a rule can be changed, or the rule turned
+//! off, from the `[rules]` table of the config file, or for a subtree from the
+//! one of a `neocmake.toml` in its directory.
+
+use std::path::Path;
+
+use tower_lsp::lsp_types::DiagnosticSeverity;
+
+use crate::config;
+
+const DOCS_URL: &str = "https://github.com/NikitolProject/neocmakelsp-fast/blob/main/docs/rules.md";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Correctness,
+    Style,
+    Modernization,
+    Performance,
+}
+
+impl std::fmt::Display for Category {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Correctness => write!(f, "correctness"),
+            Self::Style => write!(f, "style"),
+            Self::Modernization => write!(f, "modernization"),
+            Self::Performance => write!(f, "performance"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Rule {
+    /// Stable id, used as the diagnostic code
+    pub id: &'static str,
+    pub name: &'static str,
+    pub severity: DiagnosticSeverity,
+    pub category: Category,
+}
+
+impl Rule {
+    /// The documentation of the rule
+    pub fn url(&self) -> String {
+        format!("{DOCS_URL}#{}-{}", self.id.to_lowercase(), self.name)
+    }
+
+    /// The severity set for the files of `path` by the `neocmake.toml` of
+    /// their directories or by the config, or the default one.
+    /// `None` when the rule is turned off.
+    pub fn configured_severity(&self, path: Option<&Path>) -> Option<DiagnosticSeverity> {
+        match config::rule_level(path, self.id, self.name) {
+            None => Some(self.severity),
+            Some(level) => level.severity(),
+        }
+    }
+}
+
+macro_rules! rules {
+    ($($constant:ident = $id:literal, $name:literal, $severity:ident, $category:ident;)*) => {
+        $(
+            pub static $constant: Rule = Rule {
+                id: $id,
+                name: $name,
+                severity: DiagnosticSeverity::$severity,
+                category: Category::$category,
+            };
+        )*
+
+        /// Every rule, ordered by id
+        pub static RULES: &[&Rule] = &[$(&$constant),*];
+    };
+}
+
+rules! {
+    SYNTAX_ERROR = "N001", "syntax-error", ERROR, Correctness;
+    UNTERMINATED_QUOTE = "N002", "unterminated-quote", ERROR, Correctness;
+    MISSING_PAREN = "N003", "missing-paren", ERROR, Correctness;
+    UNMATCHED_PAREN = "N004", "unmatched-paren", ERROR, Correctness;
+    PACKAGE_NOT_FOUND = "N101", "package-not-found", ERROR, Correctness;
+    EMPTY_ARGUMENT = "N102", "empty-argument", ERROR, Correctness;
+    INCLUDE_ERROR = "N103", "include-error", ERROR, Correctness;
+    INCLUDE_DIRECTORY = "N104", "include-directory", ERROR, Correctness;
+    MISSING_PATH = "N105", "missing-path", WARNING, Correctness;
+    DUPLICATE_TARGET = "N106", "duplicate-target", WARNING, Correctness;
+    REMOVED_COMMAND = "N107", "removed-command", ERROR, Correctness;
+    UNKNOWN_VARIABLE = "N108", "unknown-variable", WARNING, Correctness;
+    DUPLICATE_ARGUMENT = "N109", "duplicate-argument", WARNING, Correctness;
+    INSTALL_UNKNOWN_TARGET = "N110", "install-unknown-target", WARNING, Correctness;
+    INSTALL_MISSING_FILE = "N111", "install-missing-file", WARNING, Correctness;
+    INSTALL_DESTINATION = "N112", "install-destination", WARNING, Correctness;
+    INSTALL_UNKNOWN_EXPORT = "N113", "install-unknown-export", WARNING, Correctness;
+    DOWNLOAD_MISSING_HASH = "N114", "download-missing-hash", WARNING, Correctness;
+    MALFORMED_GIT_URL = "N115", "malformed-git-url", ERROR, Correctness;
+    UNPINNED_GIT_TAG = "N116", "unpinned-git-tag", WARNING, Correctness;
+    CPACK_AFTER_INCLUDE = "N117", "cpack-after-include", WARNING, Correctness;
+    MISSING_PKG_CONFIG = "N118", "missing-pkg-config", WARNING, Correctness;
+    QT_AUTOMOC_OFF = "N119", "qt-automoc-off", WARNING, Correctness;
+    LANGUAGE_NOT_ENABLED = "N120", "language-not-enabled", WARNING, Correctness;
+    GPU_ARCHITECTURES_UNSET = "N121", "gpu-architectures-unset", WARNING, Correctness;
+    VCPKG_UNDECLARED_PORT = "N122", "vcpkg-undeclared-port", WARNING, Correctness;
+    TARGET_BEFORE_DECLARATION = "N123", "target-before-declaration", ERROR, Correctness;
+    INSTALL_DIRS_BEFORE_INCLUDE = "N124", "install-dirs-before-include", WARNING, Correctness;
+    VARIABLE_BEFORE_SET = "N125", "variable-before-set", WARNING, Correctness;
+    LOOP_VARIABLE_AFTER_LOOP = "N126", "loop-variable-after-loop", WARNING, Correctness;
+    BREAK_OUTSIDE_LOOP = "N127", "break-outside-loop", ERROR, Correctness;
+    RETURN_ARGUMENTS = "N128", "return-arguments", WARNING, Correctness;
+    MATH_EXPRESSION = "N129", "math-expression", ERROR, Correctness;
+    INVALID_REGEX = "N130", "invalid-regex", ERROR, Correctness;
+    UNTERMINATED_REFERENCE = "N131", "unterminated-reference", ERROR, Correctness;
+    STRAY_DOLLAR = "N132", "stray-dollar", WARNING, Correctness;
+    UNKNOWN_PLATFORM = "N133", "unknown-platform", WARNING, Correctness;
+    SOURCE_GROUP = "N134", "source-group", ERROR, Correctness;
+    EXPORT_NOT_INSTALLED = "N135", "export-not-installed", WARNING, Correctness;
+    NOT_SCRIPTABLE = "N201", "not-scriptable", WARNING, Correctness;
+    // Reports nothing anymore, kept so the configs naming it stay valid
+    TOP_LEVEL_PROJECT = "N202", "top-level-project", WARNING, Correctness;
+    TOOLCHAIN_COMMAND = "N203", "toolchain-command", WARNING, Correctness;
+    UNQUOTED_COMPARISON = "N301", "unquoted-comparison", WARNING, Correctness;
+    UNQUOTED_SPACES = "N302", "unquoted-spaces", WARNING, Correctness;
+    COMMAND_CASE = "N401", "command-case", HINT, Style;
+    LINE_TOO_LONG = "N402", "line-too-long", WARNING, Style;
+    DEPRECATED_COMMAND = "N501", "deprecated-command", WARNING, Modernization;
+    UNUSED_PACKAGE = "N601", "unused-package", HINT, Performance;
+    VCPKG_UNUSED_PORT = "N602", "vcpkg-unused-port", WARNING, Performance;
+}
+
+/// Find a rule by id or by name, in any case
+pub fn find_rule(key: &str) -> Option<&'static Rule> {
+    RULES
+        .iter()
+        .find(|rule| rule.id.eq_ignore_ascii_case(key) || rule.name.eq_ignore_ascii_case(key))
+        .copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rules_are_unique() {
+        for (index, rule) in RULES.iter().enumerate() {
+            assert!(
+                RULES[index + 1..]
+                    .iter()
+                    .all(|other| other.id != rule.id && other.name != rule.name),
+                "{} is registered twice",
+                rule.id
+            );
+        }
+    }
+
+    #[test]
+    fn test_find_rule() {
+        assert_eq!(find_rule("N002"), Some(&UNTERMINATED_QUOTE));
+        assert_eq!(find_rule("missing-path"), Some(&MISSING_PATH));
+        assert_eq!(find_rule("N999"), None);
+        assert_eq!(find_rule("top-level-project"), Some(&TOP_LEVEL_PROJECT));
+        assert_eq!(MISSING_PATH.url(), format!("{DOCS_URL}#n105-missing-path"));
+    }
+}
@@ -0,0 +1,332 @@
+//! The `new` subcommand: a starter project written from built-in templates.
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+
+/// The version the templates need, for `CMakePresets.json` version 3
+const MINIMUM_VERSION: &str = "3.21";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Language {
+    C,
+    Cxx,
+}
+
+impl Language {
+    /// The name given to `project(LANGUAGES)`
+    fn cmake_name(self) -> &'static str {
+        match self {
+            Self::C => "C",
+            Self::Cxx => "CXX",
+        }
+    }
+
+    fn source_extension(self) -> &'static str {
+        match self {
+            Self::C => "c",
+            Self::Cxx => "cpp",
+        }
+    }
+
+    fn header_extension(self) -> &'static str {
+        match self {
+            Self::C => "h",
+            Self::Cxx => "hpp",
+        }
+    }
+
+    fn standards(self) -> &'static [u32] {
+        match self {
+            Self::C => &[90, 99, 11, 17, 23],
+            Self::Cxx => &[98, 11, 14, 17, 20, 23, 26],
+        }
+    }
+
+    fn default_standard(self) -> u32 {
+        match self {
+            Self::C => 11,
+            Self::Cxx => 17,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProjectOptions {
+    /// Defaults to the name of the directory
+    pub name: Option<String>,
+    pub language: Language,
+    /// Defaults to C11 or C++17
+    pub standard: Option<u32>,
+    pub tests: bool,
+    pub presets: bool,
+}
+
+/// The project name as a C identifier, for the function of the library
+fn identifier(name: &str) -> String {
+    let mut identifier: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if identifier.starts_with(|c: char| c.is_ascii_digit()) {
+        identifier.insert(0, '_');
+    }
+    identifier
+}
+
+fn root_lists(name: &str, options: &ProjectOptions, standard: u32) -> String {
+    let lang = options.language.cmake_name();
+    let source = options.language.source_extension();
+    let mut lists = format!(
+        r#"cmake_minimum_required(VERSION {MINIMUM_VERSION})
+
+project({name} VERSION 0.1.0 LANGUAGES {lang})
+
+set(CMAKE_{lang}_STANDARD {standard})
+set(CMAKE_{lang}_STANDARD_REQUIRED ON)
+set(CMAKE_{lang}_EXTENSIONS OFF)
+
+add_library({name}_lib src/{name}.{source})
+target_include_directories({name}_lib PUBLIC include)
+
+add_executable({name} src/main.{source})
+target_link_libraries({name} PRIVATE {name}_lib)
+"#
+    );
+    if options.tests {
+        lists.push_str(
+            r#"
+include(CTest)
+if(BUILD_TESTING)
+  add_subdirectory(tests)
+endif()
+"#,
+        );
+    }
+    lists
+}
+
+fn tests_lists(name: &str, language: Language) -> String {
+    let source = language.source_extension();
+    format!(
+        r#"add_executable({name}_test test_{name}.{source})
+target_link_libraries({name}_test PRIVATE {name}_lib)
+
+add_test(NAME {name}_test COMMAND {name}_test)
+"#
+    )
+}
+
+fn presets(tests: bool) -> String {
+    let mut presets = String::from(
+        r#"{
+  "version": 3,
+  "configurePresets": [
+    {
+      "name": "debug",
+      "binaryDir": "${sourceDir}/build/${presetName}",
+      "cacheVariables": { "CMAKE_BUILD_TYPE": "Debug" }
+    },
+    {
+      "name": "release",
+      "binaryDir": "${sourceDir}/build/${presetName}",
+      "cacheVariables": { "CMAKE_BUILD_TYPE": "Release" }
+    }
+  ],
+  "buildPresets": [
+    { "name": "debug", "configurePreset": "debug" },
+    { "name": "release", "configurePreset": "release" }
+  ]"#,
+    );
+    if tests {
+        presets.push_str(
+            r#",
+  "testPresets": [
+    {
+      "name": "debug",
+      "configurePreset": "debug",
+      "output": { "outputOnFailure": true }
+    }
+  ]"#,
+        );
+    }
+    presets.push_str("\n}\n");
+    presets
+}
+
+/// The files of the project, relative to its directory
+fn project_files(name: &str, options: &ProjectOptions, standard: u32) -> Vec<(PathBuf, String)> {
+    let language = options.language;
+    let source = language.source_extension();
+    let header = language.header_extension();
+    let function = format!("{}_answer", identifier(name));
+    let (include, print) = match language {
+        Language::C => ("#include <stdio.h>", "printf(\"%d\\n\", "),
+        Language::Cxx => ("#include <iostream>", "std::cout << "),
+    };
+    let end_print = match language {
+        Language::C => ");",
+        Language::Cxx => " << '\\n';",
+    };
+
+    let mut files = vec![
+        (
+            PathBuf::from("CMakeLists.txt"),
+            root_lists(name, options, standard),
+        ),
+        (
+            PathBuf::from(format!("include/{name}.{header}")),
+            format!("#pragma once\n\nint {function}(void);\n"),
+        ),
+        (
+            PathBuf::from(format!("src/{name}.{source}")),
+            format!("#include \"{name}.{header}\"\n\nint {function}(void) {{ return 42; }}\n"),
+        ),
+        (
+            PathBuf::from(format!("src/main.{source}")),
+            format!(
+                "{include}\n\n#include \"{name}.{header}\"\n\nint main(void) {{\n  {print}{function}(){end_print}\n  return 0;\n}}\n"
+            ),
+        ),
+    ];
+    if options.tests {
+        files.push((
+            PathBuf::from("tests/CMakeLists.txt"),
+            tests_lists(name, language),
+        ));
+        files.push((
+            PathBuf::from(format!("tests/test_{name}.{source}")),
+            format!(
+                "#include \"{name}.{header}\"\n\nint main(void) {{ return {function}() == 42 ? 0 : 1; }}\n"
+            ),
+        ));
+    }
+    if options.presets {
+        files.push((PathBuf::from("CMakePresets.json"), presets(options.tests)));
+    }
+    files
+}
+
+/// Write a new project in `path`, returning the files written
+pub fn new_project(path: &Path, options: &ProjectOptions) -> Result<Vec<PathBuf>> {
+    let name = match &options.name {
+        Some(name) => name.clone(),
+        None => std::fs::canonicalize(path)
+            .unwrap_or_else(|_| path.to_path_buf())
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map(str::to_string)
+            .context("Cannot name the project after its directory, give --name")?,
+    };
+    if name.is_empty() || name.contains(|c: char| c.is_whitespace() || c == '/') {
+        bail!("\"{name}\" cannot be used as a project name");
+    }
+    let standard = options
+        .standard
+        .unwrap_or(options.language.default_standard());
+    if !options.language.standards().contains(&standard) {
+        let known: Vec<String> = options
+            .language
+            .standards()
+            .iter()
+            .map(u32::to_string)
+            .collect();
+        bail!(
+            "{standard} is not a standard of {}, use one of {}",
+            options.language.cmake_name(),
+            known.join(", ")
+        );
+    }
+
+    let files = project_files(&name, options, standard);
+    // Never over the files already there, not only an existing project
+    if let Some((file, _)) = files.iter().find(|(file, _)| path.join(file).exists()) {
+        bail!("{} already exists", path.join(file).display());
+    }
+
+    let mut written = vec![];
+    for (file, content) in files {
+        let file = path.join(file);
+        if let Some(parent) = file.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&file)
+            .and_then(|mut output| output.write_all(content.as_bytes()))
+            .with_context(|| format!("Failed to write {}", file.display()))?;
+        written.push(file);
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    #[test]
+    fn test_new_project() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("demo-app");
+        let options = ProjectOptions {
+            name: None,
+            language: Language::Cxx,
+            standard: Some(20),
+            tests: true,
+            presets: true,
+        };
+        let written = new_project(&path, &options).unwrap();
+        assert_eq!(written.len(), 7);
+        assert!(path.join("include/demo-app.hpp").exists());
+
+        let lists = std::fs::read_to_string(path.join("CMakeLists.txt")).unwrap();
+        assert!(lists.contains("project(demo-app VERSION 0.1.0 LANGUAGES CXX)"));
+        assert!(lists.contains("set(CMAKE_CXX_STANDARD 20)"));
+        assert!(lists.contains("add_subdirectory(tests)"));
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        assert!(!parse.parse(&lists, None).unwrap().root_node().has_error());
+
+        let presets = std::fs::read_to_string(path.join("CMakePresets.json")).unwrap();
+        let presets: serde_json::Value = serde_json::from_str(&presets).unwrap();
+        assert_eq!(presets["testPresets"][0]["name"], "debug");
+
+        // Never over an existing project
+        assert!(new_project(&path, &options).is_err());
+        let c = ProjectOptions {
+            language: Language::C,
+            standard: Some(20),
+            ..options
+        };
+        assert!(new_project(&dir.path().join("c"), &c).is_err());
+    }
+
+    #[test]
+    fn test_new_project_keeps_existing_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("src/main.cpp");
+        std::fs::create_dir_all(main.parent().unwrap()).unwrap();
+        std::fs::write(&main, "int main() { return 1; }\n").unwrap();
+        let options = ProjectOptions {
+            name: Some("demo".to_string()),
+            language: Language::Cxx,
+            standard: None,
+            tests: false,
+            presets: true,
+        };
+        let err = new_project(dir.path(), &options).unwrap_err();
+        assert!(err.to_string().ends_with("main.cpp already exists"));
+        // Nothing is written, not even the files which were not there
+        assert!(!dir.path().join("CMakeLists.txt").exists());
+        assert_eq!(
+            std::fs::read_to_string(&main).unwrap(),
+            "int main() { return 1; }\n"
+        );
+    }
+}
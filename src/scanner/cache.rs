@@ -60,6 +60,12 @@ impl DirectoryCache {
         Some(entry.entries.clone())
     }
 
+    /// The entries of `path` even when they are expired, better than nothing
+    /// while it is scanned again
+    pub fn get_stale(&self, path: &PathBuf) -> Option<Vec<CachedEntry>> {
+        self.cache.get(path).map(|entry| entry.entries.clone())
+    }
+
     pub fn insert(&self, path: PathBuf, entries: Vec<CachedEntry>) {
         if self.cache.len() >= MAX_CACHE_SIZE {
             self.evict_oldest();
@@ -81,11 +87,30 @@ impl DirectoryCache {
         self.cache.retain(|path, _| !path.starts_with(root));
     }
 
-    #[allow(dead_code)]
     pub fn clear(&self) {
         self.cache.clear();
     }
 
+    /// The estimated bytes taken by the entries
+    pub fn memory_usage(&self) -> usize {
+        self.cache
+            .iter()
+            .map(|directory| {
+                let path = directory.key().as_os_str().len();
+                let entries: usize = directory
+                    .entries
+                    .iter()
+                    .map(|entry| {
+                        size_of::<CachedEntry>()
+                            + entry.name.len()
+                            + entry.extension.as_ref().map_or(0, String::len)
+                    })
+                    .sum();
+                path + entries
+            })
+            .sum()
+    }
+
     #[allow(dead_code)]
     pub fn cleanup_expired(&self) {
         self.cache.retain(|_, entry| !entry.is_expired(self.ttl));
@@ -0,0 +1,225 @@
+//! Filesystem abstraction used by the scanner and path completion.
+//!
+//! The real filesystem is the default, while [`MemoryFileSystem`] makes it
+//! possible to describe files that only exist in the editor (or on a remote
+//! machine) and to test completion without touching the disk.
+
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{PoisonError, RwLock};
+use std::time::Duration;
+
+use super::cache::CachedEntry;
+use super::parallel::{ScanOptions, scan_directory_with, scan_directory_within};
+
+/// How long a request waits for a directory to be scanned
+const SCAN_BUDGET: Duration = Duration::from_millis(50);
+
+/// A single child of a directory, as reported by [`FileSystem::read_dir`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FsEntry {
+    pub name: String,
+    pub is_dir: bool,
+}
+
+pub trait FileSystem: Send + Sync {
+    fn is_dir(&self, path: &Path) -> bool;
+
+    fn is_file(&self, path: &Path) -> bool;
+
+    /// List the direct children of `path`. Missing directories yield no entries.
+    fn read_dir(&self, path: &Path) -> Vec<FsEntry>;
+
+    /// Whether `dir` contains a `CMakeLists.txt`.
+    fn has_cmake(&self, dir: &Path) -> bool {
+        self.is_file(&dir.join("CMakeLists.txt"))
+    }
+
+    /// Scan `dir` with the given options.
+    fn scan(&self, dir: &Path, options: &ScanOptions) -> Vec<CachedEntry> {
+        scan_directory_with(self, dir, options)
+    }
+}
+
+/// The filesystem of the machine the server runs on.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RealFileSystem;
+
+impl FileSystem for RealFileSystem {
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    fn read_dir(&self, path: &Path) -> Vec<FsEntry> {
+        let Ok(read_dir) = std::fs::read_dir(path) else {
+            return Vec::new();
+        };
+        read_dir
+            .flatten()
+            .filter_map(|entry| {
+                let path = entry.path();
+                let name = path.file_name()?.to_str()?.to_string();
+                Some(FsEntry {
+                    name,
+                    is_dir: path.is_dir(),
+                })
+            })
+            .collect()
+    }
+
+    // Use the cached, gitignore-aware scanner for the real filesystem, without
+    // letting a slow one hold the requests
+    fn scan(&self, dir: &Path, options: &ScanOptions) -> Vec<CachedEntry> {
+        scan_directory_within(dir, options, SCAN_BUDGET)
+    }
+}
+
+/// An in-memory filesystem. Parent directories are created implicitly.
+#[allow(dead_code)]
+#[derive(Debug, Default)]
+pub struct MemoryFileSystem {
+    // path -> is_dir
+    nodes: RwLock<BTreeMap<PathBuf, bool>>,
+}
+
+#[allow(dead_code)]
+impl MemoryFileSystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_file<P: AsRef<Path>>(&self, path: P) {
+        let path = normalize(path.as_ref());
+        let mut nodes = self.nodes.write().unwrap_or_else(PoisonError::into_inner);
+        insert_parents(&mut nodes, &path);
+        nodes.insert(path, false);
+    }
+
+    pub fn add_dir<P: AsRef<Path>>(&self, path: P) {
+        let path = normalize(path.as_ref());
+        let mut nodes = self.nodes.write().unwrap_or_else(PoisonError::into_inner);
+        insert_parents(&mut nodes, &path);
+        nodes.insert(path, true);
+    }
+
+    /// Remove `path` and everything below it.
+    pub fn remove<P: AsRef<Path>>(&self, path: P) {
+        let path = normalize(path.as_ref());
+        self.nodes
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|node, _| !node.starts_with(&path));
+    }
+}
+
+fn insert_parents(nodes: &mut BTreeMap<PathBuf, bool>, path: &Path) {
+    for parent in path.ancestors().skip(1) {
+        if parent.as_os_str().is_empty() {
+            break;
+        }
+        nodes.insert(parent.to_path_buf(), true);
+    }
+}
+
+/// Resolve `.` and `..` lexically, so `src/../cmake` and `cmake` are the same node.
+fn normalize(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                result.pop();
+            }
+            other => result.push(other),
+        }
+    }
+    result
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn is_dir(&self, path: &Path) -> bool {
+        self.nodes
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&normalize(path))
+            .is_some_and(|is_dir| *is_dir)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.nodes
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&normalize(path))
+            .is_some_and(|is_dir| !*is_dir)
+    }
+
+    fn read_dir(&self, path: &Path) -> Vec<FsEntry> {
+        let path = normalize(path);
+        self.nodes
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .filter(|(node, _)| node.parent() == Some(path.as_path()))
+            .filter_map(|(node, is_dir)| {
+                Some(FsEntry {
+                    name: node.file_name()?.to_str()?.to_string(),
+                    is_dir: *is_dir,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_fs_implicit_parents() {
+        let fs = MemoryFileSystem::new();
+        fs.add_file("/project/src/CMakeLists.txt");
+        fs.add_file("/project/src/main.cpp");
+        fs.add_dir("/project/docs");
+
+        assert!(fs.is_dir(Path::new("/project")));
+        assert!(fs.is_dir(Path::new("/project/src")));
+        assert!(fs.is_file(Path::new("/project/src/main.cpp")));
+        assert!(fs.is_dir(Path::new("/project/src/../docs")));
+        assert!(!fs.is_dir(Path::new("/project/build")));
+        assert!(!fs.is_file(Path::new("/project/build")));
+        assert!(fs.has_cmake(Path::new("/project/src")));
+        assert!(!fs.has_cmake(Path::new("/project/docs")));
+
+        let mut names: Vec<String> = fs
+            .read_dir(Path::new("/project"))
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["docs", "src"]);
+    }
+
+    #[test]
+    fn test_memory_fs_scan() {
+        let fs = MemoryFileSystem::new();
+        fs.add_file("/project/src/CMakeLists.txt");
+        fs.add_dir("/project/tests");
+        fs.add_file("/project/.hidden/CMakeLists.txt");
+        fs.add_file("/project/main.cpp");
+
+        let entries = fs.scan(Path::new("/project"), &ScanOptions::for_subdirectory());
+        assert_eq!(entries.len(), 2);
+        let src = entries.iter().find(|e| e.name == "src").unwrap();
+        assert!(src.has_cmake);
+        let tests = entries.iter().find(|e| e.name == "tests").unwrap();
+        assert!(!tests.has_cmake);
+
+        fs.remove("/project/src");
+        let entries = fs.scan(Path::new("/project"), &ScanOptions::for_subdirectory());
+        assert_eq!(entries.len(), 1);
+    }
+}
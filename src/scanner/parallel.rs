@@ -1,9 +1,21 @@
 use std::path::{Path, PathBuf};
-use std::sync::mpsc;
+use std::sync::{Arc, LazyLock, OnceLock, mpsc};
+use std::time::Duration;
 
+use dashmap::DashMap;
+use dashmap::mapref::entry::Entry;
 use ignore::WalkBuilder;
 
 use super::cache::{CachedEntry, DIRECTORY_CACHE};
+use super::fs::{FileSystem, RealFileSystem};
+use crate::config::{self, CONFIG};
+use crate::utils::{languages, qt};
+
+/// The source files offered before the languages of the project are known
+const DEFAULT_SOURCE_EXTENSIONS: &[&str] = &[
+    "c", "cc", "cpp", "cxx", "c++", "h", "hh", "hpp", "hxx", "h++", "m", "mm", "cu", "cuh", "asm",
+    "s", "f", "f90", "f95", "for", "rc",
+];
 
 #[derive(Debug, Clone, Default)]
 pub struct ScanOptions {
@@ -38,32 +50,34 @@ impl ScanOptions {
         }
     }
 
+    /// The sources of the languages the project enables, or of all the
+    /// languages before it is indexed, and the extensions of the settings
     pub fn for_source_files() -> Self {
+        let mut extensions = languages::enabled_languages()
+            .map(|languages| languages::source_extensions(&languages))
+            .unwrap_or_else(|| {
+                DEFAULT_SOURCE_EXTENSIONS
+                    .iter()
+                    .map(|extension| extension.to_string())
+                    .collect()
+            });
+        // The forms, resources and translations are sources of Qt targets
+        let qt_extensions: &[&str] = if qt::uses_qt() {
+            qt::QT_SOURCE_EXTENSIONS
+        } else {
+            &[]
+        };
+        for extension in CONFIG
+            .source_extensions()
+            .chain(qt_extensions.iter().copied())
+        {
+            if !extensions.iter().any(|known| known == extension) {
+                extensions.push(extension.to_string());
+            }
+        }
         Self {
             dirs_only: false,
-            extensions: Some(vec![
-                "c".to_string(),
-                "cc".to_string(),
-                "cpp".to_string(),
-                "cxx".to_string(),
-                "c++".to_string(),
-                "h".to_string(),
-                "hh".to_string(),
-                "hpp".to_string(),
-                "hxx".to_string(),
-                "h++".to_string(),
-                "m".to_string(),
-                "mm".to_string(),
-                "cu".to_string(),
-                "cuh".to_string(),
-                "asm".to_string(),
-                "s".to_string(),
-                "f".to_string(),
-                "f90".to_string(),
-                "f95".to_string(),
-                "for".to_string(),
-                "rc".to_string(),
-            ]),
+            extensions: Some(extensions),
             include_hidden: false,
             check_cmake: false,
             max_depth: Some(1),
@@ -94,20 +108,81 @@ impl ScanOptions {
     }
 }
 
+/// The scans of the directories not cached yet. The requests racing on the
+/// same directory share one scan instead of each reading it
+static SCANS_IN_FLIGHT: LazyLock<DashMap<PathBuf, Arc<OnceLock<Vec<CachedEntry>>>>> =
+    LazyLock::new(DashMap::new);
+
 pub fn scan_directory<P: AsRef<Path>>(dir: P, options: &ScanOptions) -> Vec<CachedEntry> {
     let dir = dir.as_ref();
     let dir_path = dir.to_path_buf();
 
     if let Some(cached) = DIRECTORY_CACHE.get(&dir_path) {
-        return filter_entries(cached, options);
+        return filter_entries(dir, cached, options);
+    }
+
+    // The guard of the map is released before scanning, the other requests
+    // wait on the scan itself
+    let (scan, leader) = match SCANS_IN_FLIGHT.entry(dir_path.clone()) {
+        Entry::Occupied(entry) => (entry.get().clone(), false),
+        Entry::Vacant(entry) => (entry.insert(Arc::default()).value().clone(), true),
+    };
+    if !leader {
+        let full_entries = scan.get_or_init(|| scan_directory_full(dir));
+        return filter_entries(dir, full_entries.clone(), options);
     }
 
+    // Cached by a scan finishing between the lookup and the entry
+    if let Some(cached) = DIRECTORY_CACHE.get(&dir_path) {
+        SCANS_IN_FLIGHT.remove(&dir_path);
+        return filter_entries(dir, cached, options);
+    }
     let entries = scan_directory_internal(dir, options);
-    let full_entries = scan_directory_full(dir);
-    DIRECTORY_CACHE.insert(dir_path, full_entries);
+    let full_entries = scan.get_or_init(|| scan_directory_full(dir)).clone();
+    DIRECTORY_CACHE.insert(dir_path.clone(), full_entries);
+    SCANS_IN_FLIGHT.remove(&dir_path);
     entries
 }
 
+/// Scan `dir` off the async executor, waiting at most `budget` for it.
+///
+/// On a slow filesystem the scan goes on in the background and fills the
+/// cache for the next request, meanwhile the expired entries of the cache are
+/// returned, or none. Outside of a tokio runtime, the scan is done in place.
+pub fn scan_directory_within<P: AsRef<Path>>(
+    dir: P,
+    options: &ScanOptions,
+    budget: Duration,
+) -> Vec<CachedEntry> {
+    let dir_path = dir.as_ref().to_path_buf();
+    if let Some(cached) = DIRECTORY_CACHE.get(&dir_path) {
+        return filter_entries(&dir_path, cached, options);
+    }
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+        return scan_directory(&dir_path, options);
+    };
+    let stale = DIRECTORY_CACHE.get_stale(&dir_path);
+    let (sender, receiver) = mpsc::channel();
+    let scan_options = options.clone();
+    let scan_path = dir_path.clone();
+    runtime.spawn_blocking(move || {
+        // The receiver is gone when the budget is exceeded
+        let _ = sender.send(scan_directory(&scan_path, &scan_options));
+    });
+    match receiver.recv_timeout(budget) {
+        Ok(entries) => entries,
+        Err(_) => {
+            tracing::debug!(
+                "Scanning {} takes more than {budget:?}, answering from the cache",
+                dir_path.display()
+            );
+            stale
+                .map(|entries| filter_entries(&dir_path, entries, options))
+                .unwrap_or_default()
+        }
+    }
+}
+
 fn scan_directory_internal<P: AsRef<Path>>(dir: P, options: &ScanOptions) -> Vec<CachedEntry> {
     let dir = dir.as_ref();
     if !dir.exists() || !dir.is_dir() {
@@ -144,6 +219,10 @@ fn scan_directory_internal<P: AsRef<Path>>(dir: P, options: &ScanOptions) -> Vec
             continue;
         }
 
+        if config::is_excluded(path) {
+            continue;
+        }
+
         let extension = path
             .extension()
             .and_then(|e| e.to_str())
@@ -157,11 +236,7 @@ fn scan_directory_internal<P: AsRef<Path>>(dir: P, options: &ScanOptions) -> Vec
             }
         }
 
-        let has_cmake = if is_dir && options.check_cmake {
-            path.join("CMakeLists.txt").exists()
-        } else {
-            false
-        };
+        let has_cmake = is_dir && options.check_cmake && RealFileSystem.has_cmake(path);
 
         entries.push(CachedEntry {
             name: name.to_string(),
@@ -176,45 +251,56 @@ fn scan_directory_internal<P: AsRef<Path>>(dir: P, options: &ScanOptions) -> Vec
 }
 
 fn scan_directory_full<P: AsRef<Path>>(dir: P) -> Vec<CachedEntry> {
-    let dir = dir.as_ref();
-    if !dir.exists() || !dir.is_dir() {
+    read_entries(&RealFileSystem, dir.as_ref(), true)
+}
+
+/// Read every child of `dir` through `fs`, without any filtering.
+fn read_entries<F: FileSystem + ?Sized>(fs: &F, dir: &Path, check_cmake: bool) -> Vec<CachedEntry> {
+    if !fs.is_dir(dir) {
         return Vec::new();
     }
 
-    let mut entries = Vec::new();
-    if let Ok(read_dir) = std::fs::read_dir(dir) {
-        for entry in read_dir.flatten() {
-            let path = entry.path();
-            let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
-                continue;
-            };
-
-            let is_dir = path.is_dir();
-            let is_hidden = name.starts_with('.');
+    fs.read_dir(dir)
+        .into_iter()
+        .map(|entry| {
+            let path = dir.join(&entry.name);
+            let is_hidden = entry.name.starts_with('.');
             let extension = path
                 .extension()
                 .and_then(|e| e.to_str())
                 .map(|s| s.to_string());
-            let has_cmake = if is_dir {
-                path.join("CMakeLists.txt").exists()
-            } else {
-                false
-            };
+            let has_cmake = entry.is_dir && check_cmake && fs.has_cmake(&path);
 
-            entries.push(CachedEntry {
-                name: name.to_string(),
-                is_dir,
+            CachedEntry {
+                name: entry.name,
+                is_dir: entry.is_dir,
                 is_hidden,
                 has_cmake,
                 extension,
-            });
-        }
-    }
+            }
+        })
+        .collect()
+}
 
-    entries
+/// Scan `dir` through an arbitrary [`FileSystem`].
+///
+/// Unlike [`scan_directory`] this neither consults the directory cache nor
+/// applies gitignore rules, as neither makes sense for virtual filesystems.
+pub fn scan_directory_with<F: FileSystem + ?Sized, P: AsRef<Path>>(
+    fs: &F,
+    dir: P,
+    options: &ScanOptions,
+) -> Vec<CachedEntry> {
+    let entries = read_entries(fs, dir.as_ref(), options.check_cmake);
+    filter_entries(dir.as_ref(), entries, options)
 }
 
-fn filter_entries(entries: Vec<CachedEntry>, options: &ScanOptions) -> Vec<CachedEntry> {
+/// The `entries` of `dir` matching `options`, and not excluded by a `neocmake.toml`
+fn filter_entries(
+    dir: &Path,
+    entries: Vec<CachedEntry>,
+    options: &ScanOptions,
+) -> Vec<CachedEntry> {
     entries
         .into_iter()
         .filter(|entry| {
@@ -235,7 +321,7 @@ fn filter_entries(entries: Vec<CachedEntry>, options: &ScanOptions) -> Vec<Cache
                 }
             }
 
-            true
+            !config::is_excluded(&dir.join(&entry.name))
         })
         .collect()
 }
@@ -298,11 +384,7 @@ pub fn scan_directory_recursive<P: AsRef<Path>>(
                 }
             }
 
-            let has_cmake = if is_dir && options.check_cmake {
-                path.join("CMakeLists.txt").exists()
-            } else {
-                false
-            };
+            let has_cmake = is_dir && options.check_cmake && RealFileSystem.has_cmake(path);
 
             let cached_entry = CachedEntry {
                 name: name.to_string(),
@@ -379,4 +461,41 @@ mod tests {
         let entries = scan_directory(dir.path(), &ScanOptions::for_source_files());
         assert_eq!(entries.len(), 2);
     }
+
+    #[test]
+    fn test_scan_directory_concurrent() {
+        let dir = tempdir().unwrap();
+        for index in 0..20 {
+            File::create(dir.path().join(format!("source{index}.cpp"))).unwrap();
+        }
+        fs::create_dir(dir.path().join("subdir")).unwrap();
+
+        let counts: Vec<usize> = std::thread::scope(|scope| {
+            let scans: Vec<_> = (0..8)
+                .map(|_| scope.spawn(|| scan_directory(dir.path(), &ScanOptions::default()).len()))
+                .collect();
+            scans.into_iter().map(|scan| scan.join().unwrap()).collect()
+        });
+        assert!(counts.iter().all(|count| *count == 21));
+        assert!(!SCANS_IN_FLIGHT.contains_key(dir.path()));
+        assert!(DIRECTORY_CACHE.get(&dir.path().to_path_buf()).is_some());
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_scan_directory_within() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("source.cpp")).unwrap();
+
+        // Over budget, the scan goes on in the background
+        scan_directory_within(dir.path(), &ScanOptions::default(), Duration::ZERO);
+        let path = dir.path().to_path_buf();
+        for _ in 0..100 {
+            if DIRECTORY_CACHE.get(&path).is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let entries = scan_directory_within(dir.path(), &ScanOptions::default(), Duration::ZERO);
+        assert_eq!(entries.len(), 1);
+    }
 }
@@ -1,5 +1,5 @@
 use std::path::PathBuf;
-use std::sync::OnceLock;
+use std::sync::{Mutex, OnceLock, PoisonError};
 use std::time::Duration;
 
 use notify::{
@@ -7,6 +7,7 @@ use notify::{
     event::{CreateKind, ModifyKind, RemoveKind, RenameMode},
 };
 use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
 use tracing::{debug, error, info, warn};
 
 use super::cache::DIRECTORY_CACHE;
@@ -15,6 +16,7 @@ static FILE_WATCHER: OnceLock<FileWatcherHandle> = OnceLock::new();
 
 pub struct FileWatcherHandle {
     watch_tx: mpsc::UnboundedSender<WatchCommand>,
+    task: Mutex<Option<JoinHandle<()>>>,
 }
 
 enum WatchCommand {
@@ -38,17 +40,29 @@ impl FileWatcherHandle {
         }
     }
 
-    pub fn shutdown(&self) {
+    /// Stop watching, and wait for the task of the watcher to end
+    pub async fn shutdown(&self) {
         let _ = self.watch_tx.send(WatchCommand::Shutdown);
+        let task = self
+            .task
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if let Some(task) = task {
+            let _ = task.await;
+        }
     }
 }
 
 pub fn init_file_watcher() -> Option<&'static FileWatcherHandle> {
     FILE_WATCHER.get_or_init(|| {
         let (watch_tx, watch_rx) = mpsc::unbounded_channel();
-        tokio::spawn(run_watcher(watch_rx));
+        let task = tokio::spawn(run_watcher(watch_rx));
         info!("File watcher initialized");
-        FileWatcherHandle { watch_tx }
+        FileWatcherHandle {
+            watch_tx,
+            task: Mutex::new(Some(task)),
+        }
     });
     FILE_WATCHER.get()
 }
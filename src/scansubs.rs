@@ -1,14 +1,17 @@
 use std::collections::HashMap;
 use std::fmt;
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, LazyLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, PoisonError, RwLock};
 
 use serde::{Deserialize, Serialize};
 use tokio::sync::Mutex;
 
 use crate::consts::TREESITTER_CMAKE_LANGUAGE;
-use crate::utils::remove_quotation_and_replace_placeholders;
-use crate::{CMakeNodeKinds, complete, jump};
+use crate::utils::{
+    languages, module_path, packages, qt, remove_quotation_and_replace_placeholders, targets,
+};
+use crate::{CMakeNodeKinds, complete, configure_file, jump};
 
 /// NOTE: key is be included path, value is the top CMakeLists
 /// This is used to find who is on the top of the CMakeLists
@@ -26,27 +29,128 @@ pub static TREE_MAP: LazyLock<Arc<Mutex<TreeKey>>> =
 pub static TREE_CMAKE_MAP: LazyLock<Arc<Mutex<TreeCMakeKey>>> =
     LazyLock::new(|| Arc::new(Mutex::new(HashMap::new())));
 
-pub async fn scan_all<P: AsRef<Path>>(project_root: P, is_first: bool) {
-    let root_cmake = project_root.as_ref().join("CMakeLists.txt");
+/// NOTE: key is a scanned file, value is the top CMakeLists of the project
+/// reaching it through `add_subdirectory()` or `include()`
+static PROJECT_ROOTS: LazyLock<RwLock<HashMap<PathBuf, PathBuf>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// The top CMakeLists of the project `path` is part of, or `path` itself when
+/// no scanned file reaches it
+pub fn project_root<P: AsRef<Path>>(path: P) -> PathBuf {
+    let path = path.as_ref();
+    PROJECT_ROOTS
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(path)
+        .cloned()
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// NOTE: key is the path of a document, value is its content not saved yet
+/// The scans read it instead of the disk, where an untitled document is not
+static OVERLAY: LazyLock<RwLock<HashMap<PathBuf, String>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// The content of the document at `path` changed in the editor
+pub fn set_overlay<P: AsRef<Path>>(path: P, source: &str) {
+    OVERLAY
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(path.as_ref().to_path_buf(), source.to_string());
+}
+
+/// The document at `path` was saved or closed, the scans read the disk again
+pub fn remove_overlay<P: AsRef<Path>>(path: P) {
+    OVERLAY
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .remove(path.as_ref());
+}
+
+/// The content of `path`, the one in the editor when it is not saved
+async fn read_source(path: &Path) -> Option<String> {
+    let unsaved = OVERLAY
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(path)
+        .cloned();
+    match unsaved {
+        Some(source) => Some(source),
+        None => tokio::fs::read_to_string(path).await.ok(),
+    }
+}
+
+/// Scan the project from its top level CMakeLists.txt, returning the number of
+/// files scanned
+/// The number of scans of a whole project running
+static SCANS_RUNNING: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether a project is being indexed, so its targets are not all known yet
+pub fn is_indexing() -> bool {
+    SCANS_RUNNING.load(Ordering::Relaxed) > 0
+}
+
+/// Counts a scan as running until dropped, even if the scan is cancelled
+struct RunningScan;
+
+impl RunningScan {
+    fn start() -> Self {
+        SCANS_RUNNING.fetch_add(1, Ordering::Relaxed);
+        Self
+    }
+}
+
+impl Drop for RunningScan {
+    fn drop(&mut self) {
+        SCANS_RUNNING.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+pub async fn scan_all<P: AsRef<Path>>(project_root: P, is_first: bool) -> usize {
+    let _running = RunningScan::start();
+    scan_project(project_root.as_ref(), is_first).await
+}
+
+async fn scan_project(project_root: &Path, is_first: bool) -> usize {
+    module_path::set_source_dir(project_root);
+    let root_cmake = project_root.join("CMakeLists.txt");
     let mut to_scan: Vec<PathBuf> = vec![root_cmake];
+    let mut scanned = 0;
     while !to_scan.is_empty() {
         let mut next_to_scan = Vec::new();
         for scan_cmake in to_scan.iter() {
+            if scan_cmake.is_file()
+                || OVERLAY
+                    .read()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .contains_key(scan_cmake)
+            {
+                scanned += 1;
+            }
             let mut out = scan_dir(scan_cmake, is_first).await;
             next_to_scan.append(&mut out);
         }
         to_scan = next_to_scan;
     }
+    scanned
 }
 
 pub async fn scan_dir<P: AsRef<Path>>(path: P, is_first: bool) -> Vec<PathBuf> {
     let (bufs, cmakebufs) = scan_dir_inner(path.as_ref(), is_first).await;
+    let root = project_root(path.as_ref());
     let mut tree = TREE_MAP.lock().await;
     let mut includetree = TREE_CMAKE_MAP.lock().await;
+    let mut roots = PROJECT_ROOTS
+        .write()
+        .unwrap_or_else(PoisonError::into_inner);
     for subpath in bufs.iter() {
         tree.insert(subpath.to_path_buf(), path.as_ref().into());
+        roots.insert(subpath.to_path_buf(), root.clone());
     }
     for cmakepath in cmakebufs {
+        roots
+            .entry(cmakepath.clone())
+            .or_insert_with(|| root.clone());
         let include_key = includetree.entry(cmakepath).or_default();
         let toaddpath = path.as_ref().into();
         if !include_key.contains(&toaddpath) {
@@ -57,7 +161,7 @@ pub async fn scan_dir<P: AsRef<Path>>(path: P, is_first: bool) -> Vec<PathBuf> {
 }
 
 async fn scan_dir_inner<P: AsRef<Path>>(path: P, is_first: bool) -> (Vec<PathBuf>, Vec<PathBuf>) {
-    let Ok(source) = tokio::fs::read_to_string(path.as_ref()).await else {
+    let Some(source) = read_source(path.as_ref()).await else {
         return (Vec::new(), Vec::new());
     };
 
@@ -73,6 +177,12 @@ async fn scan_dir_inner<P: AsRef<Path>>(path: P, is_first: bool) -> (Vec<PathBuf
     if tree.is_error() {
         return (Vec::new(), Vec::new());
     }
+    module_path::update_module_paths(path.as_ref(), &newsource, tree);
+    languages::update_languages(path.as_ref(), &newsource, tree);
+    qt::update_qt(path.as_ref(), &source);
+    packages::update_words(path.as_ref(), &source);
+    configure_file::update_templates(path.as_ref(), &newsource, tree);
+    targets::update_targets(path.as_ref(), &newsource, tree);
 
     scan_node(&newsource, tree, path)
 }
@@ -122,6 +232,10 @@ fn scan_node<P: AsRef<Path>>(
                             continue;
                         };
                         if !name.ends_with(".cmake") {
+                            // Modules of the project are tracked like included files
+                            if let Some(module) = module_path::find_project_module(&name) {
+                                cmake_bufs.push(module);
+                            }
                             continue;
                         }
                         let mut cmake_buf_path = PathBuf::from(name);
@@ -258,8 +372,34 @@ mod tests {
         File::create_new(&subdir_file).unwrap();
         let bufs = scan_dir(&top_cmake, false).await;
         assert_eq!(bufs, vec![subdir_file.clone()]);
+        // The other tests scan projects of their own into the same map
         let cache_data = TREE_MAP.lock().await;
-        assert_eq!(*cache_data, HashMap::from_iter([(subdir_file, top_cmake)]));
+        assert_eq!(cache_data.get(&subdir_file), Some(&top_cmake));
+    }
+
+    #[tokio::test]
+    async fn test_read_overlay() {
+        let dir = tempdir().unwrap();
+        let saved = dir.path().join("CMakeLists.txt");
+        fs::write(&saved, "project(saved)\n").unwrap();
+        let untitled = dir.path().join("Untitled-1");
+        set_overlay(&saved, "project(unsaved)\n");
+        set_overlay(&untitled, "add_subdirectory(src)\n");
+        assert_eq!(
+            read_source(&saved).await.as_deref(),
+            Some("project(unsaved)\n")
+        );
+        assert_eq!(
+            read_source(&untitled).await.as_deref(),
+            Some("add_subdirectory(src)\n")
+        );
+        remove_overlay(&saved);
+        remove_overlay(&untitled);
+        assert_eq!(
+            read_source(&saved).await.as_deref(),
+            Some("project(saved)\n")
+        );
+        assert_eq!(read_source(&untitled).await, None);
     }
 
     #[test]
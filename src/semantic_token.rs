@@ -1,12 +1,15 @@
+use std::collections::HashSet;
 use std::sync::LazyLock;
 
 use tower_lsp::Client;
 use tower_lsp::lsp_types::{
-    SemanticToken, SemanticTokenType, SemanticTokens, SemanticTokensResult,
+    SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokens, SemanticTokensResult,
 };
 
 use crate::CMakeNodeKinds;
+use crate::complete::builtin::BUILTIN_COMMAND;
 use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+use crate::utils::deprecated::is_deprecated_command;
 static NUMBERREGEX: LazyLock<regex::Regex> =
     LazyLock::new(|| regex::Regex::new(r"^\d+(?:\.+\d*)?").unwrap());
 
@@ -25,6 +28,42 @@ pub const LEGEND_TYPE: &[SemanticTokenType] = &[
     SemanticTokenType::PARAMETER,
 ];
 
+pub const LEGEND_MODIFIER: &[SemanticTokenModifier] = &[
+    SemanticTokenModifier::MODIFICATION,
+    SemanticTokenModifier::DEPRECATED,
+    SemanticTokenModifier::DEFAULT_LIBRARY,
+];
+
+/// Commands whose first argument is the variable they write
+const SET_COMMANDS: &[&str] = &["set", "unset", "option"];
+
+static BUILTIN_COMMAND_NAMES: LazyLock<HashSet<String>> = LazyLock::new(|| {
+    BUILTIN_COMMAND
+        .as_ref()
+        .map(|items| items.iter().map(|item| item.label.to_lowercase()).collect())
+        .unwrap_or_default()
+});
+
+fn get_modifier_bit(modifier: SemanticTokenModifier) -> u32 {
+    1 << LEGEND_MODIFIER
+        .iter()
+        .position(|data| *data == modifier)
+        .unwrap()
+}
+
+/// The modifiers of the name of a command
+fn command_modifiers(name: &str) -> u32 {
+    let name = name.to_lowercase();
+    let mut modifiers = 0;
+    if is_deprecated_command(&name) {
+        modifiers |= get_modifier_bit(SemanticTokenModifier::DEPRECATED);
+    }
+    if BUILTIN_COMMAND_NAMES.contains(&name) {
+        modifiers |= get_modifier_bit(SemanticTokenModifier::DEFAULT_LIBRARY);
+    }
+    modifiers
+}
+
 fn get_token_position(tokentype: SemanticTokenType) -> u32 {
     LEGEND_TYPE
         .iter()
@@ -45,16 +84,55 @@ pub async fn semantic_token(_client: &Client, context: &str) -> Option<SemanticT
             &mut 0,
             &mut 0,
             false,
+            0,
         ),
     }))
 }
 
+/// The string tokens of `node`, one for each of its lines as a token cannot
+/// span several
+fn string_tokens(
+    node: tree_sitter::Node,
+    source: &[&str],
+    preline: &mut u32,
+    prestart: &mut u32,
+    res: &mut Vec<SemanticToken>,
+) {
+    let start = node.start_position();
+    let end = node.end_position();
+    for row in start.row..=end.row {
+        let line = source.get(row).copied().unwrap_or_default();
+        let first = if row == start.row { start.column } else { 0 };
+        let last = if row == end.row {
+            end.column
+        } else {
+            line.len()
+        };
+        if last <= first {
+            continue;
+        }
+        if row as u32 != *preline {
+            *prestart = 0;
+        }
+        res.push(SemanticToken {
+            delta_line: row as u32 - *preline,
+            delta_start: first as u32 - *prestart,
+            length: (last - first) as u32,
+            token_type: get_token_position(SemanticTokenType::STRING),
+            token_modifiers_bitset: 0,
+        });
+        *preline = row as u32;
+        *prestart = first as u32;
+    }
+}
+
 fn sub_tokens(
     input: tree_sitter::Node,
     source: &Vec<&str>,
     preline: &mut u32,
     prestart: &mut u32,
     is_if: bool,
+    first_modifiers: u32,
 ) -> Vec<SemanticToken> {
     let mut res = vec![];
 
@@ -110,17 +188,30 @@ fn sub_tokens(
                     *prestart = 0;
                 }
 
+                let name = &source[h][x..y];
                 res.push(SemanticToken {
                     delta_line: h as u32 - *preline,
                     delta_start: x as u32 - *prestart,
                     length: (y - x) as u32,
                     token_type: get_token_position(SemanticTokenType::METHOD),
-                    token_modifiers_bitset: 0,
+                    token_modifiers_bitset: command_modifiers(name),
                 });
                 *preline = h as u32;
                 *prestart = x as u32;
 
-                res.append(&mut sub_tokens(child, source, preline, prestart, false));
+                let first_modifiers = if SET_COMMANDS.contains(&name.to_lowercase().as_str()) {
+                    get_modifier_bit(SemanticTokenModifier::MODIFICATION)
+                } else {
+                    0
+                };
+                res.append(&mut sub_tokens(
+                    child,
+                    source,
+                    preline,
+                    prestart,
+                    false,
+                    first_modifiers,
+                ));
             }
 
             CMakeNodeKinds::LINE_COMMENT => {
@@ -194,15 +285,7 @@ fn sub_tokens(
                     {
                         let quoted_argument = argument.child(0).unwrap();
                         if quoted_argument.child_count() == 1 {
-                            res.push(SemanticToken {
-                                delta_line: h as u32 - *preline,
-                                delta_start: x as u32 - *prestart,
-                                length: (y - x) as u32,
-                                token_type: get_token_position(SemanticTokenType::STRING),
-                                token_modifiers_bitset: 0,
-                            });
-                            *prestart = x as u32;
-                            *preline = h as u32;
+                            string_tokens(quoted_argument, source, preline, prestart, &mut res);
                         } else {
                             // TODO: very base implement, but it is enough for me,
                             // if you do not very satisfied with this
@@ -250,53 +333,18 @@ fn sub_tokens(
                         is_first_val = false;
                         continue;
                     }
+                    // Nothing is expanded in a bracket argument, it is a string as a whole
                     if argument
                         .child(0)
                         .is_some_and(|child| child.kind() == CMakeNodeKinds::BRACKET_ARGUMENT)
                     {
-                        let bracket_argument = argument.child(0).unwrap();
-                        let h = bracket_argument.start_position().row;
-                        let h2 = bracket_argument.end_position().row;
-                        let x = bracket_argument.start_position().column;
-                        let y = bracket_argument.end_position().column;
-                        for column in h..=h2 {
-                            if column == h {
-                                let content = &source[h][x..];
-                                res.push(SemanticToken {
-                                    delta_line: h as u32 - *preline,
-                                    delta_start: x as u32 - *prestart,
-                                    length: content.len() as u32,
-                                    token_type: get_token_position(SemanticTokenType::STRING),
-                                    token_modifiers_bitset: 0,
-                                });
-                                *prestart = x as u32;
-                                *preline = h as u32;
-                                continue;
-                            }
-                            if column == h2 {
-                                let content = &source[h2][..y];
-                                res.push(SemanticToken {
-                                    delta_line: h2 as u32 - *preline,
-                                    delta_start: 0,
-                                    length: content.len() as u32,
-                                    token_type: get_token_position(SemanticTokenType::STRING),
-                                    token_modifiers_bitset: 0,
-                                });
-                                *prestart = 0;
-                                *preline = h2 as u32;
-                                continue;
-                            }
-                            let content = &source[column];
-                            res.push(SemanticToken {
-                                delta_line: column as u32 - *preline,
-                                delta_start: 0,
-                                length: content.len() as u32,
-                                token_type: get_token_position(SemanticTokenType::STRING),
-                                token_modifiers_bitset: 0,
-                            });
-                            *prestart = 0;
-                            *preline = column as u32;
-                        }
+                        string_tokens(
+                            argument.child(0).unwrap(),
+                            source,
+                            preline,
+                            prestart,
+                            &mut res,
+                        );
                         is_first_val = false;
                         continue;
                     }
@@ -310,11 +358,26 @@ fn sub_tokens(
                             preline,
                             prestart,
                             false,
+                            0,
                         ));
                         is_first_val = false;
                         continue;
                     }
                     let name = &source[h][x..y];
+                    // The variable a set() writes, whatever its case
+                    if is_first_val && first_modifiers != 0 {
+                        res.push(SemanticToken {
+                            delta_line: h as u32 - *preline,
+                            delta_start: x as u32 - *prestart,
+                            length: (y - x) as u32,
+                            token_type: get_token_position(SemanticTokenType::VARIABLE),
+                            token_modifiers_bitset: first_modifiers,
+                        });
+                        *prestart = x as u32;
+                        *preline = h as u32;
+                        is_first_val = false;
+                        continue;
+                    }
                     if BOOL_VAL.contains(&name) {
                         res.push(SemanticToken {
                             delta_line: h as u32 - *preline,
@@ -372,7 +435,7 @@ fn sub_tokens(
                             delta_start: x as u32 - *prestart,
                             length: (y - x) as u32,
                             token_type: get_token_position(SemanticTokenType::VARIABLE),
-                            token_modifiers_bitset: 0,
+                            token_modifiers_bitset: first_modifiers,
                         });
                         *prestart = x as u32;
                         *preline = h as u32;
@@ -400,7 +463,7 @@ fn sub_tokens(
                 });
                 *preline = h as u32;
                 *prestart = x as u32;
-                res.append(&mut sub_tokens(child, source, preline, prestart, false));
+                res.append(&mut sub_tokens(child, source, preline, prestart, false, 0));
             }
             CMakeNodeKinds::BODY
             | CMakeNodeKinds::MACRO_DEF
@@ -421,6 +484,7 @@ fn sub_tokens(
                     preline,
                     prestart,
                     child.kind() == CMakeNodeKinds::IF_COMMAND,
+                    0,
                 ));
             }
             _ => {}
@@ -453,10 +517,50 @@ mod tests {
                 &mut 0,
                 &mut 0,
                 false,
+                0,
             ),
         }))
     }
 
+    #[test]
+    fn test_modifiers() {
+        let Some(SemanticTokensResult::Tokens(tokens)) =
+            semantic_token_test("set(MY_VAR 1)\nexec_program(ls)\nmy_function(MY_VAR)\n")
+        else {
+            panic!("no tokens");
+        };
+        let modification = get_modifier_bit(SemanticTokenModifier::MODIFICATION);
+        let deprecated = get_modifier_bit(SemanticTokenModifier::DEPRECATED);
+        let data = &tokens.data;
+        // set, MY_VAR, 1, exec_program, ls, my_function, MY_VAR
+        assert_eq!(
+            data[1].token_type,
+            get_token_position(SemanticTokenType::VARIABLE)
+        );
+        assert_eq!(data[1].token_modifiers_bitset, modification);
+        assert_ne!(data[3].token_modifiers_bitset & deprecated, 0);
+        assert_eq!(data[5].token_modifiers_bitset & deprecated, 0);
+        assert_eq!(data[6].token_modifiers_bitset & modification, 0);
+    }
+
+    #[test]
+    fn test_bracket_argument() {
+        let Some(SemanticTokensResult::Tokens(tokens)) =
+            semantic_token_test("set(A [[x ${B}]] C)\nset(D [=[\n  ${E}\n]=])\n")
+        else {
+            panic!("no tokens");
+        };
+        let string = get_token_position(SemanticTokenType::STRING);
+        let strings: Vec<(u32, u32, u32)> = tokens
+            .data
+            .iter()
+            .filter(|token| token.token_type == string)
+            .map(|token| (token.delta_line, token.delta_start, token.length))
+            .collect();
+        // The line of the first bracket, then the three of the second one
+        assert_eq!(strings, vec![(0, 2, 10), (0, 2, 3), (1, 0, 6), (1, 0, 3)]);
+    }
+
     #[test]
     fn test_hl() {
         semantic_token_test(include_str!(
@@ -1,52 +1,56 @@
 use std::collections::HashMap;
-use std::process::Command;
-use std::sync::LazyLock;
+use std::sync::{Arc, LazyLock, PoisonError};
 
 use tower_lsp::lsp_types::{
     Documentation, MarkupContent, MarkupKind, ParameterInformation, ParameterLabel, Position,
-    SignatureHelp, SignatureInformation,
+    SignatureHelp, SignatureHelpContext, SignatureInformation,
 };
-use tree_sitter::{Node, Point};
+use tree_sitter::{Node, Tree};
 
-use crate::CMakeNodeKinds;
-use crate::consts::TREESITTER_CMAKE_LANGUAGE;
-use crate::utils::treehelper::ToPoint;
+use crate::config::{CONFIG, KnownCommand};
+use crate::utils::command_help::{COMMAND_HELP, parse_command_help};
+use crate::utils::function_doc::FUNCTION_DOCS;
+use crate::utils::help_markdown::help_to_markdown;
+use crate::utils::input::{self, InputError};
+use crate::utils::qt;
+use crate::utils::treehelper::{ToPoint, command_at};
+
+/// Every signature carries the whole help section, so cap them to keep the
+/// memory used by pathological input linear in its size.
+const MAX_SIGNATURES_PER_COMMAND: usize = 64;
 
 /// Parsed signature information for a CMake command
 #[derive(Debug, Clone)]
 pub struct CMakeSignature {
     pub label: String,
-    pub documentation: String,
+    /// The help of the command, shared by its signatures
+    pub documentation: Arc<str>,
     pub parameters: Vec<String>,
 }
 
 /// Extract signatures from cmake --help-commands output
 fn parse_signatures_from_help(raw_info: &str) -> HashMap<String, Vec<CMakeSignature>> {
-    let mut signatures: HashMap<String, Vec<CMakeSignature>> = HashMap::new();
+    signatures_from_help(&parse_command_help(raw_info))
+}
 
-    // Split by command headers (command_name followed by dashes)
-    let re = regex::Regex::new(r"([a-zA-Z_][a-zA-Z0-9_]*)\n-+").unwrap();
-    let keys: Vec<_> = re
-        .find_iter(raw_info)
-        .map(|message| {
-            let temp: Vec<&str> = message.as_str().split('\n').collect();
-            temp[0].to_lowercase()
-        })
-        .collect();
-    let contents: Vec<_> = re.split(raw_info).collect();
-    let contents = &contents[1..];
+/// The signatures of each command, sharing the help of the command
+fn signatures_from_help(help: &HashMap<String, Arc<str>>) -> HashMap<String, Vec<CMakeSignature>> {
+    let mut signatures: HashMap<String, Vec<CMakeSignature>> = HashMap::new();
 
     // Fallback regex for when the command-specific pattern fails
     let fallback_re = regex::Regex::new(r"^\s*\w+\s*\(([^)]*)\)").unwrap();
 
-    for (key, content) in keys.iter().zip(contents) {
+    for (key, content) in help {
         // Find all signature patterns like: command_name(<args>)
         let sig_pattern = format!(r"(?m)^\s*{}\s*\(([^)]*)\)", regex::escape(key));
         let sig_re = regex::Regex::new(&sig_pattern).unwrap_or_else(|_| fallback_re.clone());
 
         let mut cmd_signatures = Vec::new();
 
-        for caps in sig_re.captures_iter(content) {
+        for caps in sig_re
+            .captures_iter(content)
+            .take(MAX_SIGNATURES_PER_COMMAND)
+        {
             if let Some(args_match) = caps.get(1) {
                 let args_str = args_match.as_str().trim();
                 let full_sig = format!("{}({})", key, args_str);
@@ -56,7 +60,7 @@ fn parse_signatures_from_help(raw_info: &str) -> HashMap<String, Vec<CMakeSignat
 
                 cmd_signatures.push(CMakeSignature {
                     label: full_sig,
-                    documentation: content.trim().to_string(),
+                    documentation: content.clone(),
                     parameters,
                 });
             }
@@ -66,7 +70,7 @@ fn parse_signatures_from_help(raw_info: &str) -> HashMap<String, Vec<CMakeSignat
         if cmd_signatures.is_empty() {
             cmd_signatures.push(CMakeSignature {
                 label: format!("{}(...)", key),
-                documentation: content.trim().to_string(),
+                documentation: content.clone(),
                 parameters: vec![],
             });
         }
@@ -77,6 +81,14 @@ fn parse_signatures_from_help(raw_info: &str) -> HashMap<String, Vec<CMakeSignat
     signatures
 }
 
+/// Checked variant of [`parse_signatures_from_help`] for arbitrary bytes
+#[allow(dead_code)]
+pub fn try_parse_signatures_from_help(
+    raw_info: &[u8],
+) -> Result<HashMap<String, Vec<CMakeSignature>>, InputError> {
+    Ok(parse_signatures_from_help(input::decode(raw_info)?))
+}
+
 /// Parse parameters from a signature argument string
 fn parse_parameters(args_str: &str) -> Vec<String> {
     let mut parameters = Vec::new();
@@ -133,15 +145,63 @@ fn parse_parameters(args_str: &str) -> Vec<String> {
     parameters
 }
 
+/// Signatures of a command from the `known_commands` setting
+fn known_command_signatures(command: &KnownCommand) -> Vec<CMakeSignature> {
+    let documentation: Arc<str> = Arc::from(command.description.as_deref().unwrap_or_default());
+    let name = command.name.to_lowercase();
+    let sig_re = regex::Regex::new(r"(?s)^\s*[a-zA-Z_][a-zA-Z0-9_]*\s*\((.*)\)\s*$").unwrap();
+
+    let mut signatures: Vec<CMakeSignature> = command
+        .signatures
+        .iter()
+        .map(|signature| {
+            // Accept both `name(<args>)` and bare `<args>`
+            let args_str = sig_re
+                .captures(signature)
+                .and_then(|caps| caps.get(1))
+                .map_or(signature.as_str(), |args| args.as_str())
+                .trim();
+            CMakeSignature {
+                label: format!("{}({})", name, args_str),
+                documentation: documentation.clone(),
+                parameters: parse_parameters(args_str),
+            }
+        })
+        .collect();
+
+    if signatures.is_empty() {
+        signatures.push(CMakeSignature {
+            label: format!("{}(...)", name),
+            documentation,
+            parameters: vec![],
+        });
+    }
+    signatures
+}
+
+/// Signature of a function or macro of the project, documented by the comment
+/// above its definition
+fn user_command_signature(name: &str) -> Option<CMakeSignature> {
+    let docs = FUNCTION_DOCS.lock().unwrap_or_else(PoisonError::into_inner);
+    let doc = docs.get(name)?;
+    Some(CMakeSignature {
+        label: doc.label.clone(),
+        documentation: Arc::from(doc.documentation.as_str()),
+        parameters: doc.parameters.clone(),
+    })
+}
+
 /// Lazy-loaded signature storage
 pub static COMMAND_SIGNATURES: LazyLock<HashMap<String, Vec<CMakeSignature>>> =
     LazyLock::new(|| {
-        if let Ok(output) = Command::new("cmake").arg("--help-commands").output() {
-            let temp = String::from_utf8_lossy(&output.stdout);
-            parse_signatures_from_help(&temp)
-        } else {
-            HashMap::new()
+        let mut signatures = signatures_from_help(&COMMAND_HELP);
+        // Builtin commands win over user declared ones
+        for command in CONFIG.known_commands.iter().chain(qt::QT_COMMANDS.iter()) {
+            signatures
+                .entry(command.name.to_lowercase())
+                .or_insert_with(|| known_command_signatures(command));
         }
+        signatures
     });
 
 /// Initialize signature data (called at startup)
@@ -149,102 +209,63 @@ pub fn init_signatures() {
     let _ = &*COMMAND_SIGNATURES;
 }
 
-/// Find the command name at the current position
-fn find_command_at_position(source: &str, position: Position) -> Option<(String, u32)> {
-    let mut parser = tree_sitter::Parser::new();
-    parser.set_language(&TREESITTER_CMAKE_LANGUAGE).ok()?;
-    let tree = parser.parse(source, None)?;
-    let point = position.to_point();
-
-    find_command_in_tree(tree.root_node(), point, &source.lines().collect())
+/// Find the command name at the current position, and the index of the
+/// argument there
+fn find_command_at_position(root: Node, source: &str, position: Position) -> Option<(String, u32)> {
+    let command = command_at(root, position.to_point(), source)?;
+    Some((command.name, command.argument_index as u32))
 }
 
-/// Recursively find command at the given point
-fn find_command_in_tree<'a>(
-    node: Node<'a>,
-    point: Point,
-    source: &Vec<&str>,
-) -> Option<(String, u32)> {
-    let mut cursor = node.walk();
-
-    for child in node.children(&mut cursor) {
-        // Check if point is within this node's range
-        let start = child.start_position();
-        let end = child.end_position();
-
-        if point.row < start.row || point.row > end.row {
-            continue;
-        }
-        if point.row == start.row && point.column < start.column {
-            continue;
-        }
-        if point.row == end.row && point.column > end.column {
-            continue;
-        }
-
-        // If this is a normal_command, extract the command name and argument index
-        if child.kind() == CMakeNodeKinds::NORMAL_COMMAND {
-            // First child is the identifier (command name)
-            if let Some(id_node) = child.child(0) {
-                let row = id_node.start_position().row;
-                let start_col = id_node.start_position().column;
-                let end_col = id_node.end_position().column;
-
-                if row < source.len() && end_col <= source[row].len() {
-                    let cmd_name = source[row][start_col..end_col].to_lowercase();
-
-                    // Find argument index based on cursor position
-                    let arg_index = find_argument_index(child, point, source);
-
-                    return Some((cmd_name, arg_index));
-                }
-            }
-        }
-
-        // Recurse into children
-        if let Some(result) = find_command_in_tree(child, point, source) {
-            return Some(result);
-        }
+/// The signature to show first: the one shown before while retriggered by
+/// typing in the same call, else the first taking `active_param`
+fn active_signature(
+    signatures: &[SignatureInformation],
+    active_param: u32,
+    context: Option<&SignatureHelpContext>,
+) -> u32 {
+    if let Some(SignatureHelpContext {
+        is_retrigger: true,
+        active_signature_help: Some(previous),
+        ..
+    }) = context
+        && let Some(index) = previous.active_signature
+        && previous.signatures.len() == signatures.len()
+        && previous
+            .signatures
+            .iter()
+            .zip(signatures)
+            .all(|(previous, signature)| previous.label == signature.label)
+    {
+        return index;
     }
-
-    None
-}
-
-/// Find which argument index the cursor is at
-fn find_argument_index(command_node: Node, point: Point, _source: &Vec<&str>) -> u32 {
-    let mut cursor = command_node.walk();
-    let mut arg_index = 0u32;
-
-    for child in command_node.children(&mut cursor) {
-        if child.kind() == CMakeNodeKinds::ARGUMENT_LIST {
-            let mut arg_cursor = child.walk();
-            for arg_child in child.children(&mut arg_cursor) {
-                if arg_child.kind() == CMakeNodeKinds::ARGUMENT
-                    || arg_child.kind() == CMakeNodeKinds::UNQUOTED_ARGUMENT
-                    || arg_child.kind() == CMakeNodeKinds::QUOTED_ARGUMENT
-                {
-                    let arg_end = arg_child.end_position();
-
-                    // If cursor is before or at this argument's end, we're at this index
-                    if point.row < arg_end.row
-                        || (point.row == arg_end.row && point.column <= arg_end.column)
-                    {
-                        return arg_index;
-                    }
-                    arg_index += 1;
-                }
-            }
-        }
-    }
-
-    arg_index
+    signatures
+        .iter()
+        .position(|signature| {
+            signature
+                .parameters
+                .as_ref()
+                .is_some_and(|parameters| parameters.len() > active_param as usize)
+        })
+        .unwrap_or(0) as u32
 }
 
 /// Get signature help for a position in the document
-pub fn get_signature_help(source: &str, position: Position) -> Option<SignatureHelp> {
-    let (cmd_name, active_param) = find_command_at_position(source, position)?;
-
-    let signatures = COMMAND_SIGNATURES.get(&cmd_name)?;
+pub fn get_signature_help(
+    source: &str,
+    tree: &Tree,
+    position: Position,
+    context: Option<&SignatureHelpContext>,
+) -> Option<SignatureHelp> {
+    let (cmd_name, active_param) = find_command_at_position(tree.root_node(), source, position)?;
+
+    let user_signatures;
+    let signatures = match COMMAND_SIGNATURES.get(&cmd_name) {
+        Some(signatures) => signatures,
+        None => {
+            user_signatures = vec![user_command_signature(&cmd_name)?];
+            &user_signatures
+        }
+    };
     if signatures.is_empty() {
         return None;
     }
@@ -265,7 +286,7 @@ pub fn get_signature_help(source: &str, position: Position) -> Option<SignatureH
                 label: sig.label.clone(),
                 documentation: Some(Documentation::MarkupContent(MarkupContent {
                     kind: MarkupKind::Markdown,
-                    value: sig.documentation.clone(),
+                    value: help_to_markdown(&sig.documentation),
                 })),
                 parameters: if params.is_empty() {
                     None
@@ -278,8 +299,8 @@ pub fn get_signature_help(source: &str, position: Position) -> Option<SignatureH
         .collect();
 
     Some(SignatureHelp {
+        active_signature: Some(active_signature(&sig_infos, active_param, context)),
         signatures: sig_infos,
-        active_signature: Some(0),
         active_parameter: Some(active_param),
     })
 }
@@ -287,6 +308,13 @@ pub fn get_signature_help(source: &str, position: Position) -> Option<SignatureH
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    fn parse(source: &str) -> Tree {
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        parser.parse(source, None).unwrap()
+    }
 
     #[test]
     fn test_parse_parameters() {
@@ -297,6 +325,35 @@ mod tests {
         assert_eq!(params2, vec!["<variable>", "[<value>...]"]);
     }
 
+    #[test]
+    fn test_user_command_signature_help() {
+        use crate::utils::function_doc::FunctionDoc;
+
+        FUNCTION_DOCS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(
+                "signature_help_plugin".to_string(),
+                FunctionDoc {
+                    label: "Signature_Help_Plugin(NAME [STATIC])".to_string(),
+                    parameters: vec!["NAME".to_string(), "[STATIC]".to_string()],
+                    documentation: "Add a plugin.".to_string(),
+                    path: std::path::PathBuf::from("plugins.cmake"),
+                },
+            );
+        let source = "Signature_Help_Plugin(demo STATIC)\n";
+        let pos = Position {
+            line: 0,
+            character: 30,
+        };
+        let help = get_signature_help(source, &parse(source), pos, None).unwrap();
+        assert_eq!(
+            help.signatures[0].label,
+            "Signature_Help_Plugin(NAME [STATIC])"
+        );
+        assert_eq!(help.active_parameter, Some(1));
+    }
+
     #[test]
     fn test_signature_help() {
         let source = r#"
@@ -307,9 +364,9 @@ set(MY_VAR "value")
             line: 2,
             character: 8,
         };
-        let help = get_signature_help(source, pos);
-        // Should find "set" command
-        assert!(help.is_some() || COMMAND_SIGNATURES.is_empty());
+        let help = get_signature_help(source, &parse(source), pos, None);
+        // Should find "set" command, when cmake is there to describe it
+        assert!(help.is_some() || !COMMAND_SIGNATURES.contains_key("set"));
     }
 
     #[test]
@@ -342,6 +399,41 @@ set(MY_VAR "value")
         }
     }
 
+    #[test]
+    fn test_known_command_signatures() {
+        let command = KnownCommand {
+            name: "Catch_Discover_Tests".to_string(),
+            signatures: vec![
+                "catch_discover_tests(<target> [TEST_SPEC <spec>])".to_string(),
+                "<target> EXTRA_ARGS <args>...".to_string(),
+            ],
+            description: Some("Discover Catch2 tests".to_string()),
+        };
+        let signatures = known_command_signatures(&command);
+        assert_eq!(signatures.len(), 2);
+        assert_eq!(
+            signatures[0].label,
+            "catch_discover_tests(<target> [TEST_SPEC <spec>])"
+        );
+        assert_eq!(
+            signatures[0].parameters,
+            vec!["<target>", "[TEST_SPEC <spec>]"]
+        );
+        assert_eq!(
+            signatures[1].label,
+            "catch_discover_tests(<target> EXTRA_ARGS <args>...)"
+        );
+        assert_eq!(&*signatures[1].documentation, "Discover Catch2 tests");
+
+        let command = KnownCommand {
+            name: "sdk_add_module".to_string(),
+            signatures: vec![],
+            description: None,
+        };
+        let signatures = known_command_signatures(&command);
+        assert_eq!(signatures[0].label, "sdk_add_module(...)");
+    }
+
     #[test]
     fn test_find_command_at_position() {
         let source = "set(MY_VAR \"value\")";
@@ -350,11 +442,119 @@ set(MY_VAR "value")
             line: 0,
             character: 5,
         };
-        let result = find_command_at_position(source, pos);
+        let tree = parse(source);
+        let result = find_command_at_position(tree.root_node(), source, pos);
         println!("find_command_at_position result: {:?}", result);
         assert!(result.is_some(), "Should find command at position");
         let (cmd_name, arg_idx) = result.unwrap();
         assert_eq!(cmd_name, "set", "Should find 'set' command");
         println!("Command: {}, arg_index: {}", cmd_name, arg_idx);
     }
+
+    #[test]
+    fn test_find_command_in_comment() {
+        let source = "set(MY_VAR [[ value ]]) # set(";
+        let tree = parse(source);
+        let in_bracket = Position {
+            line: 0,
+            character: 15,
+        };
+        assert!(find_command_at_position(tree.root_node(), source, in_bracket).is_none());
+        let in_comment = Position {
+            line: 0,
+            character: 30,
+        };
+        assert!(find_command_at_position(tree.root_node(), source, in_comment).is_none());
+        let in_argument = Position {
+            line: 0,
+            character: 6,
+        };
+        assert!(find_command_at_position(tree.root_node(), source, in_argument).is_some());
+    }
+
+    #[test]
+    fn test_find_command_outside_parentheses() {
+        let source = "set(A 1)\nmessage(";
+        let tree = parse(source);
+        let at = |line, character| {
+            find_command_at_position(tree.root_node(), source, Position { line, character })
+        };
+        assert!(at(0, 1).is_none());
+        assert!(at(0, 3).is_none());
+        assert_eq!(at(0, 4), Some(("set".to_string(), 0)));
+        assert_eq!(at(0, 7), Some(("set".to_string(), 1)));
+        assert!(at(0, 8).is_none());
+        // Still typing the arguments of a call without its `)`
+        assert_eq!(at(1, 8), Some(("message".to_string(), 0)));
+    }
+
+    #[test]
+    fn test_find_command_unclosed() {
+        let source = "project(demo)\nmessage(STATUS \"a\" \nset(A 1)\n";
+        let tree = parse(source);
+        let at = |line, character| {
+            find_command_at_position(tree.root_node(), source, Position { line, character })
+        };
+        assert_eq!(at(1, 12), Some(("message".to_string(), 0)));
+        assert_eq!(at(1, 19), Some(("message".to_string(), 2)));
+        assert!(at(0, 13).is_none());
+    }
+
+    #[test]
+    fn test_active_signature() {
+        use tower_lsp::lsp_types::SignatureHelpTriggerKind;
+
+        let signature = |label: &str, count: usize| SignatureInformation {
+            label: label.to_string(),
+            documentation: None,
+            parameters: Some(
+                (0..count)
+                    .map(|index| ParameterInformation {
+                        label: ParameterLabel::Simple(index.to_string()),
+                        documentation: None,
+                    })
+                    .collect(),
+            ),
+            active_parameter: None,
+        };
+        let signatures = vec![signature("f(a)", 1), signature("f(a b c)", 3)];
+        assert_eq!(active_signature(&signatures, 0, None), 0);
+        assert_eq!(active_signature(&signatures, 2, None), 1);
+        let context = |previous: Vec<SignatureInformation>| SignatureHelpContext {
+            trigger_kind: SignatureHelpTriggerKind::CONTENT_CHANGE,
+            trigger_character: None,
+            is_retrigger: true,
+            active_signature_help: Some(SignatureHelp {
+                signatures: previous,
+                active_signature: Some(0),
+                active_parameter: Some(0),
+            }),
+        };
+        // The signature shown while typing in the same call stays
+        assert_eq!(
+            active_signature(&signatures, 2, Some(&context(signatures.clone()))),
+            0
+        );
+        assert_eq!(
+            active_signature(&signatures, 2, Some(&context(vec![signature("g()", 0)]))),
+            1
+        );
+    }
+
+    #[test]
+    fn test_try_parse_signatures_from_help() {
+        let help = b"set\n---\n\n set(<variable> <value>...)\n";
+        let signatures = try_parse_signatures_from_help(help).unwrap();
+        assert_eq!(
+            signatures["set"][0].parameters,
+            vec!["<variable>", "<value>..."]
+        );
+
+        assert!(try_parse_signatures_from_help(b"set\n---\n\xff").is_err());
+
+        // Arbitrary bytes shaped like help output
+        for input in input::arbitrary_inputs(0x2545_f491, b"ab_\n-()<>[] \t", 200, 256) {
+            assert!(try_parse_signatures_from_help(&input).is_ok());
+        }
+    }
 }
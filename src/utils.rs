@@ -1,9 +1,34 @@
+pub mod cmake_docs;
+pub mod cmake_modules;
+pub mod command_help;
+pub mod cpack;
+pub mod deprecated;
+pub mod document;
+pub mod external;
 mod findpackage;
+pub mod function_doc;
+pub mod generators;
+pub mod help_markdown;
+pub mod help_rst;
+pub mod input;
+pub mod json_scan;
+pub mod languages;
+pub mod line_index;
+pub mod loops;
+pub mod module_path;
+pub mod packages;
+pub mod parse_arguments;
+pub mod platforms;
+pub mod qt;
+pub mod script_mode;
+pub mod targets;
+pub mod toolchain;
 pub mod treehelper;
+pub mod variable_families;
 
 use std::collections::HashMap;
-use std::path::PathBuf;
-use std::sync::{LazyLock, Mutex};
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex, PoisonError};
 
 use serde::{Deserialize, Serialize};
 use tower_lsp::lsp_types::Uri;
@@ -94,7 +119,27 @@ pub fn get_node_content<'a>(source: &[&'a str], node: &Node) -> Vec<&'a str> {
     content
 }
 
+/// The content of a bracket argument like `[=[...]=]`, without the newline
+/// right after its opening bracket which cmake drops
+pub fn bracket_content(argument: &str) -> Option<&str> {
+    let rest = argument.strip_prefix('[')?;
+    let equals = rest.len() - rest.trim_start_matches('=').len();
+    let rest = rest[equals..].strip_prefix('[')?;
+    let content = rest.strip_suffix(format!("]{}]", "=".repeat(equals)).as_str())?;
+    Some(
+        content
+            .strip_prefix("\r\n")
+            .or_else(|| content.strip_prefix('\n'))
+            .unwrap_or(content),
+    )
+}
+
+/// The value of an argument: a bracket argument is taken as it is, else the
+/// quotes are removed and the variables replaced
 pub fn remove_quotation_and_replace_placeholders(origin_template: &str) -> Option<String> {
+    if let Some(content) = bracket_content(origin_template) {
+        return Some(content.to_string());
+    }
     replace_placeholders(origin_template.trim_matches('"'))
 }
 
@@ -115,7 +160,9 @@ static CACHE_ENV_DATA: LazyLock<Mutex<HashMap<String, String>>> =
 fn replace_placeholders_with_env_map(template: &str) -> Option<String> {
     let mut result = template.to_string();
 
-    let mut cache = CACHE_ENV_DATA.lock().unwrap();
+    let mut cache = CACHE_ENV_DATA
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
     for caps in PLACE_ENV_HODER_REGEX.captures_iter(template) {
         let key = &caps[1];
         match cache.get(key) {
@@ -176,6 +223,32 @@ pub fn gen_module_pattern(subpath: &str) -> Option<String> {
     }
 }
 
+/// Find the file of `include(<name>)` for a module name, looking at the
+/// tracked `CMAKE_MODULE_PATH` first. The flag tells if it is shipped with CMake.
+pub fn find_module_file(name: &str) -> Option<(PathBuf, bool)> {
+    if let Some(path) = module_path::find_project_module(name) {
+        return Some((path, false));
+    }
+    if let Some(path) = cmake_modules::builtin_module(name) {
+        return Some((path, true));
+    }
+    let glob_pattern = gen_module_pattern(name)?;
+    let path = glob::glob(&glob_pattern)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .next()?;
+    Some((path, true))
+}
+
+/// Resolve `include(<name>)` relative to `current_dir`, or as a module.
+pub fn resolve_include<P: AsRef<Path>>(name: &str, current_dir: P) -> Option<(PathBuf, bool)> {
+    if !include_is_module(name) {
+        return Some((current_dir.as_ref().join(name), false));
+    }
+    find_module_file(name)
+}
+
 #[derive(Debug)]
 pub struct LineCommentTmp<'a> {
     pub end_y: usize,
@@ -212,6 +285,51 @@ pub fn get_the_packagename(package: &str) -> &str {
     package
 }
 
+fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        path.metadata()
+            .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+    }
+    #[cfg(not(unix))]
+    {
+        path.is_file()
+            && path.extension().is_some_and(|extension| {
+                extension == "exe" || extension == "bat" || extension == "cmd"
+            })
+    }
+}
+
+static PATH_EXECUTABLES: tokio::sync::OnceCell<Vec<String>> = tokio::sync::OnceCell::const_new();
+
+/// Names of the executables found in `PATH`, sorted and without duplicates.
+/// The directories are read once, on a blocking thread.
+pub async fn path_executables() -> &'static [String] {
+    PATH_EXECUTABLES
+        .get_or_init(|| async {
+            tokio::task::spawn_blocking(scan_path_executables)
+                .await
+                .unwrap_or_default()
+        })
+        .await
+}
+
+fn scan_path_executables() -> Vec<String> {
+    let Some(paths) = std::env::var_os("PATH") else {
+        return Vec::new();
+    };
+    let mut executables: Vec<String> = std::env::split_paths(&paths)
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flat_map(|read_dir| read_dir.flatten())
+        .filter(|entry| is_executable(&entry.path()))
+        .filter_map(|entry| entry.file_name().to_str().map(str::to_string))
+        .collect();
+    executables.sort();
+    executables.dedup();
+    executables
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -288,6 +406,18 @@ Core Gui Widget
         );
     }
 
+    #[test]
+    fn test_bracket_content() {
+        assert_eq!(bracket_content("[[a ${B}]]"), Some("a ${B}"));
+        assert_eq!(bracket_content("[==[\n]] ]=]\n]==]"), Some("]] ]=]\n"));
+        assert_eq!(bracket_content("[=[a]]"), None);
+        assert_eq!(bracket_content("\"[[a]]\""), None);
+        assert_eq!(
+            remove_quotation_and_replace_placeholders("[[${ROOT_DIR}/abc]]"),
+            Some("${ROOT_DIR}/abc".to_string())
+        );
+    }
+
     #[test]
     fn test_comment() {
         let linecomment = LineCommentTmp {
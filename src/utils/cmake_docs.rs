@@ -0,0 +1,140 @@
+//! Links to the online documentation of cmake, for the installed version.
+
+use std::collections::HashMap;
+use std::process::Command;
+use std::sync::LazyLock;
+
+use tower_lsp::lsp_types::CompletionItem;
+
+use crate::complete::builtin::{BUILTIN_COMMAND, BUILTIN_MODULE, BUILTIN_VARIABLE};
+
+const DOCS_URL: &str = "https://cmake.org/cmake/help";
+
+/// The major and minor version of the installed cmake
+pub static CMAKE_VERSION: LazyLock<Option<(u32, u32)>> = LazyLock::new(|| {
+    Command::new("cmake")
+        .arg("--version")
+        .output()
+        .ok()
+        .and_then(|output| cmake_version(&String::from_utf8_lossy(&output.stdout)))
+});
+
+/// The version of the documentation, such as `v3.28`, or `latest` without cmake
+pub static CMAKE_DOCS_VERSION: LazyLock<String> = LazyLock::new(|| {
+    CMAKE_VERSION
+        .map(|(major, minor)| format!("v{major}.{minor}"))
+        .unwrap_or_else(|| "latest".to_string())
+});
+
+/// The major and minor version from the output of `cmake --version`
+fn cmake_version(output: &str) -> Option<(u32, u32)> {
+    let version = output.lines().next()?.strip_prefix("cmake version ")?;
+    let mut parts = version.trim().split(['.', '-']);
+    let major: u32 = parts.next()?.parse().ok()?;
+    let minor: u32 = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocKind {
+    Command,
+    Variable,
+    Module,
+}
+
+impl DocKind {
+    /// The directory of its pages, both online and in the `Help` sources
+    pub fn directory(self) -> &'static str {
+        match self {
+            Self::Command => "command",
+            Self::Variable => "variable",
+            Self::Module => "module",
+        }
+    }
+}
+
+/// Commands which are documented by the module defining them
+const MODULE_COMMANDS: &[(&str, &str)] = &[
+    ("pkg_check_modules", "FindPkgConfig"),
+    ("pkg_search_module", "FindPkgConfig"),
+];
+
+/// The name of the page documenting `name`, `CMAKE_<LANG>_FLAGS` being
+/// documented as `CMAKE_LANG_FLAGS`
+pub fn page_name(name: &str) -> String {
+    name.chars().filter(|c| !matches!(c, '<' | '>')).collect()
+}
+
+/// The page documenting `name` in the documentation of `version`
+fn page_url(version: &str, kind: DocKind, name: &str) -> String {
+    format!(
+        "{DOCS_URL}/{version}/{}/{}.html",
+        kind.directory(),
+        page_name(name)
+    )
+}
+
+/// The page documenting `name`, in the documentation of the installed cmake
+pub fn doc_url(kind: DocKind, name: &str) -> String {
+    page_url(&CMAKE_DOCS_VERSION, kind, name)
+}
+
+fn labels(items: &anyhow::Result<Vec<CompletionItem>>) -> impl Iterator<Item = &str> {
+    items.iter().flatten().map(|item| item.label.as_str())
+}
+
+static BUILTIN_PAGES: LazyLock<HashMap<String, (DocKind, String)>> = LazyLock::new(|| {
+    let mut pages = HashMap::new();
+    for name in labels(&BUILTIN_MODULE) {
+        pages.insert(name.to_string(), (DocKind::Module, name.to_string()));
+    }
+    for name in labels(&BUILTIN_VARIABLE) {
+        pages.insert(name.to_string(), (DocKind::Variable, name.to_string()));
+    }
+    for name in labels(&BUILTIN_COMMAND) {
+        let name = name.to_lowercase();
+        let page = match MODULE_COMMANDS.iter().find(|(command, _)| *command == name) {
+            Some((_, module)) => (DocKind::Module, module.to_string()),
+            None => (DocKind::Command, name.clone()),
+        };
+        pages.insert(name, page);
+    }
+    pages
+});
+
+/// The kind and page of a builtin command, variable or module
+pub fn builtin_page(name: &str) -> Option<(DocKind, &'static str)> {
+    let (kind, page) = BUILTIN_PAGES
+        .get(name)
+        .or_else(|| BUILTIN_PAGES.get(&name.to_lowercase()))?;
+    Some((*kind, page.as_str()))
+}
+
+/// The online documentation of a builtin command, variable or module
+pub fn builtin_doc_url(name: &str) -> Option<String> {
+    let (kind, page) = builtin_page(name)?;
+    Some(doc_url(kind, page))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_doc_urls() {
+        assert_eq!(
+            cmake_version("cmake version 3.28.3\n\nCMake suite maintained"),
+            Some((3, 28))
+        );
+        assert_eq!(cmake_version("cmake version 4.0.0-rc2\n"), Some((4, 0)));
+        assert_eq!(cmake_version("not cmake"), None);
+        assert_eq!(
+            page_url("v3.28", DocKind::Command, "add_library"),
+            "https://cmake.org/cmake/help/v3.28/command/add_library.html"
+        );
+        assert_eq!(
+            page_url("latest", DocKind::Variable, "CMAKE_<LANG>_FLAGS"),
+            "https://cmake.org/cmake/help/latest/variable/CMAKE_LANG_FLAGS.html"
+        );
+    }
+}
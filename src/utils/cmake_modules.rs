@@ -0,0 +1,88 @@
+//! The `Modules` directory of the installed cmake.
+//!
+//! `include(FetchContent)` and `find_package(Threads)` use the modules shipped
+//! with cmake when the project has none of this name, so going to their
+//! definition opens the file of the installation, to read what it does.
+
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use crate::fileapi;
+
+/// The installation prefix of the cmake found in `PATH`, like `/usr`
+pub static CMAKE_PREFIX: LazyLock<Option<PathBuf>> = LazyLock::new(|| {
+    let executable = if cfg!(windows) { "cmake.exe" } else { "cmake" };
+    let paths = std::env::var_os("PATH")?;
+    let binary = std::env::split_paths(&paths)
+        .map(|dir| dir.join(executable))
+        .find(|path| path.is_file())?;
+    let binary = binary.canonicalize().unwrap_or(binary);
+    Some(binary.parent()?.parent()?.to_path_buf())
+});
+
+static MODULES_DIR: LazyLock<Option<PathBuf>> =
+    LazyLock::new(|| modules_dir_in(CMAKE_PREFIX.as_deref()?));
+
+/// The `Modules` directory of the cmake installed in `prefix`
+fn modules_dir_in(prefix: &Path) -> Option<PathBuf> {
+    let pattern = prefix.join("share").join("cmake*").join("Modules");
+    glob::glob(pattern.to_str()?)
+        .ok()?
+        .flatten()
+        .find(|dir| dir.join("FindPackageHandleStandardArgs.cmake").is_file())
+}
+
+/// The `Modules` directory of the cmake configuring the project, from
+/// `CMAKE_ROOT` in its cache, else the one of the cmake found in `PATH`
+pub fn modules_dir() -> Option<PathBuf> {
+    fileapi::get_entries_data()
+        .and_then(|entries| entries.get("CMAKE_ROOT").cloned())
+        .map(|root| Path::new(&root).join("Modules"))
+        .filter(|dir| dir.is_dir())
+        .or_else(|| MODULES_DIR.clone())
+}
+
+/// The file of a module in `modules_dir`, like `FetchContent.cmake`
+pub fn module_in(modules_dir: &Path, name: &str) -> Option<PathBuf> {
+    let path = modules_dir.join(format!("{name}.cmake"));
+    path.is_file().then_some(path)
+}
+
+/// The file of the module `name` shipped with cmake, for `include(<name>)`
+pub fn builtin_module(name: &str) -> Option<PathBuf> {
+    module_in(&modules_dir()?, name)
+}
+
+/// The find module `Find<package>.cmake` shipped with cmake
+pub fn builtin_find_module(package: &str) -> Option<PathBuf> {
+    module_in(&modules_dir()?, &format!("Find{package}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_modules_dir() {
+        let prefix = tempfile::tempdir().unwrap();
+        let modules = prefix.path().join("share/cmake-3.30/Modules");
+        std::fs::create_dir_all(&modules).unwrap();
+        std::fs::create_dir_all(prefix.path().join("share/cmake-3.30/Help")).unwrap();
+        assert_eq!(modules_dir_in(prefix.path()), None);
+        std::fs::write(modules.join("FindPackageHandleStandardArgs.cmake"), "").unwrap();
+        std::fs::write(modules.join("FindThreads.cmake"), "").unwrap();
+        std::fs::write(modules.join("FetchContent.cmake"), "").unwrap();
+
+        let dir = modules_dir_in(prefix.path()).unwrap();
+        assert_eq!(dir, modules);
+        assert_eq!(
+            module_in(&dir, "FetchContent"),
+            Some(modules.join("FetchContent.cmake"))
+        );
+        assert_eq!(
+            module_in(&dir, "FindThreads"),
+            Some(modules.join("FindThreads.cmake"))
+        );
+        assert_eq!(module_in(&dir, "FindFoo"), None);
+    }
+}
@@ -0,0 +1,51 @@
+//! The help of the builtin commands, read once from `cmake --help-commands`.
+//!
+//! The help of a command takes several KB. It is kept once, in an `Arc<str>`
+//! shared by the completion of its lowercase and uppercase spellings, its
+//! signatures and the hover.
+
+use std::collections::HashMap;
+use std::process::Command;
+use std::sync::{Arc, LazyLock};
+
+/// The help of each command, by lowercase name
+pub static COMMAND_HELP: LazyLock<HashMap<String, Arc<str>>> = LazyLock::new(|| {
+    let Ok(output) = Command::new("cmake").arg("--help-commands").output() else {
+        return HashMap::new();
+    };
+    parse_command_help(&String::from_utf8_lossy(&output.stdout))
+});
+
+/// Split the output of `cmake --help-commands` by command, each page starting
+/// with the name of the command underlined with dashes
+pub fn parse_command_help(raw_info: &str) -> HashMap<String, Arc<str>> {
+    let re = regex::Regex::new(r"([a-zA-Z_][a-zA-Z0-9_]*)\n-+").unwrap();
+    let keys = re
+        .captures_iter(raw_info)
+        .filter_map(|caps| caps.get(1))
+        .map(|name| name.as_str().to_lowercase());
+    let contents = re.split(raw_info).skip(1);
+    keys.zip(contents)
+        .map(|(key, content)| (key, Arc::from(content.trim())))
+        .collect()
+}
+
+/// The help of `name`, whatever its case
+pub fn command_help(name: &str) -> Option<Arc<str>> {
+    COMMAND_HELP.get(&name.to_lowercase()).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_command_help() {
+        let help = parse_command_help(
+            "add_test\n--------\n\nAdd a test to the project.\n\nbreak\n-----\n\nBreak from a loop.\n",
+        );
+        assert_eq!(help.len(), 2);
+        assert_eq!(&*help["add_test"], "Add a test to the project.");
+        assert_eq!(&*help["break"], "Break from a loop.");
+    }
+}
@@ -0,0 +1,258 @@
+//! The variables read by `include(CPack)` and the generators it knows.
+//!
+//! `cmake --help-variables` only lists a few of the `CPACK_*` variables, the
+//! others are documented with the CPack module and its generators.
+
+/// Variables of the CPack module and of its common generators
+pub const CPACK_VARIABLES: &[(&str, &str)] = &[
+    (
+        "CPACK_PACKAGE_NAME",
+        "Name of the package, defaults to the project name",
+    ),
+    ("CPACK_PACKAGE_VENDOR", "Name of the package vendor"),
+    (
+        "CPACK_PACKAGE_DIRECTORY",
+        "Directory where the packages are written",
+    ),
+    (
+        "CPACK_PACKAGE_VERSION",
+        "Version of the package, defaults to the project version",
+    ),
+    (
+        "CPACK_PACKAGE_VERSION_MAJOR",
+        "Major version of the package",
+    ),
+    (
+        "CPACK_PACKAGE_VERSION_MINOR",
+        "Minor version of the package",
+    ),
+    (
+        "CPACK_PACKAGE_VERSION_PATCH",
+        "Patch version of the package",
+    ),
+    ("CPACK_PACKAGE_DESCRIPTION", "Description of the package"),
+    (
+        "CPACK_PACKAGE_DESCRIPTION_SUMMARY",
+        "Short description of the package",
+    ),
+    (
+        "CPACK_PACKAGE_DESCRIPTION_FILE",
+        "File with the description of the package",
+    ),
+    ("CPACK_PACKAGE_HOMEPAGE_URL", "Project homepage"),
+    (
+        "CPACK_PACKAGE_CONTACT",
+        "Contact of the maintainer, used by the DEB generator",
+    ),
+    (
+        "CPACK_PACKAGE_FILE_NAME",
+        "Name of the package file, without its extension",
+    ),
+    (
+        "CPACK_PACKAGE_INSTALL_DIRECTORY",
+        "Installation directory on the target system",
+    ),
+    ("CPACK_PACKAGE_ICON", "Icon shown by the installer"),
+    (
+        "CPACK_PACKAGE_CHECKSUM",
+        "Algorithm of the checksum file written next to the package",
+    ),
+    (
+        "CPACK_PACKAGE_EXECUTABLES",
+        "Pairs of executables and labels to create shortcuts for",
+    ),
+    (
+        "CPACK_PACKAGING_INSTALL_PREFIX",
+        "Prefix the files are installed under in the package",
+    ),
+    (
+        "CPACK_PROJECT_CONFIG_FILE",
+        "File included by cpack for each generator, to change the variables per generator",
+    ),
+    (
+        "CPACK_RESOURCE_FILE_LICENSE",
+        "License shown by the installers",
+    ),
+    (
+        "CPACK_RESOURCE_FILE_README",
+        "Readme shown by the installers",
+    ),
+    (
+        "CPACK_RESOURCE_FILE_WELCOME",
+        "Welcome text shown by the installers",
+    ),
+    (
+        "CPACK_MONOLITHIC_INSTALL",
+        "Package all the components together",
+    ),
+    (
+        "CPACK_GENERATOR",
+        "Generators used by cpack to build the binary packages",
+    ),
+    (
+        "CPACK_OUTPUT_CONFIG_FILE",
+        "Name of the configuration file of the binary packages",
+    ),
+    ("CPACK_STRIP_FILES", "Strip the installed files"),
+    (
+        "CPACK_VERBATIM_VARIABLES",
+        "Escape the values of the variables written in the configuration files",
+    ),
+    (
+        "CPACK_THREADS",
+        "Number of threads used to compress the packages",
+    ),
+    (
+        "CPACK_SET_DESTDIR",
+        "Install with DESTDIR instead of a prefix",
+    ),
+    (
+        "CPACK_INCLUDE_TOPLEVEL_DIRECTORY",
+        "Put the files of an archive in a top level directory",
+    ),
+    (
+        "CPACK_INSTALL_CMAKE_PROJECTS",
+        "Quadruplets of build directory, project, component and directory to install",
+    ),
+    (
+        "CPACK_SYSTEM_NAME",
+        "System name used in the package file name",
+    ),
+    (
+        "CPACK_SOURCE_GENERATOR",
+        "Generators used by cpack to build the source packages",
+    ),
+    (
+        "CPACK_SOURCE_PACKAGE_FILE_NAME",
+        "Name of the source package file, without its extension",
+    ),
+    (
+        "CPACK_SOURCE_IGNORE_FILES",
+        "Regular expressions of the files left out of the source packages",
+    ),
+    (
+        "CPACK_SOURCE_OUTPUT_CONFIG_FILE",
+        "Name of the configuration file of the source packages",
+    ),
+    (
+        "CPACK_SOURCE_STRIP_FILES",
+        "Strip the files of the source packages",
+    ),
+    (
+        "CPACK_COMPONENTS_ALL",
+        "Components to package, all the installed ones by default",
+    ),
+    (
+        "CPACK_COMPONENTS_GROUPING",
+        "How the components are split in packages: ONE_PER_GROUP, IGNORE or ALL_COMPONENTS_IN_ONE",
+    ),
+    (
+        "CPACK_ARCHIVE_COMPONENT_INSTALL",
+        "Make one archive per component",
+    ),
+    ("CPACK_ARCHIVE_FILE_NAME", "Name of the archive file"),
+    (
+        "CPACK_DEB_COMPONENT_INSTALL",
+        "Make one DEB package per component",
+    ),
+    (
+        "CPACK_DEBIAN_PACKAGE_MAINTAINER",
+        "Maintainer of the DEB package",
+    ),
+    (
+        "CPACK_DEBIAN_PACKAGE_DEPENDS",
+        "Dependencies of the DEB package",
+    ),
+    ("CPACK_DEBIAN_PACKAGE_SECTION", "Section of the DEB package"),
+    (
+        "CPACK_DEBIAN_PACKAGE_SHLIBDEPS",
+        "Compute the dependencies of the DEB package with dpkg-shlibdeps",
+    ),
+    (
+        "CPACK_DEBIAN_FILE_NAME",
+        "Name of the DEB file, DEB-DEFAULT for the Debian convention",
+    ),
+    (
+        "CPACK_RPM_COMPONENT_INSTALL",
+        "Make one RPM package per component",
+    ),
+    ("CPACK_RPM_PACKAGE_LICENSE", "License of the RPM package"),
+    (
+        "CPACK_RPM_PACKAGE_REQUIRES",
+        "Dependencies of the RPM package",
+    ),
+    ("CPACK_RPM_PACKAGE_GROUP", "Group of the RPM package"),
+    (
+        "CPACK_RPM_FILE_NAME",
+        "Name of the RPM file, RPM-DEFAULT for the rpmbuild convention",
+    ),
+    (
+        "CPACK_NSIS_DISPLAY_NAME",
+        "Name shown by the NSIS installer",
+    ),
+    ("CPACK_NSIS_PACKAGE_NAME", "Title of the NSIS installer"),
+    (
+        "CPACK_NSIS_MODIFY_PATH",
+        "Offer to add the install directory to PATH",
+    ),
+    (
+        "CPACK_NSIS_ENABLE_UNINSTALL_BEFORE_INSTALL",
+        "Uninstall the previous version first",
+    ),
+    (
+        "CPACK_WIX_UPGRADE_GUID",
+        "GUID identifying the product across its versions",
+    ),
+    (
+        "CPACK_WIX_PRODUCT_ICON",
+        "Icon of the product in the programs list",
+    ),
+    ("CPACK_DMG_VOLUME_NAME", "Name of the mounted disk image"),
+];
+
+/// The generators of `CPACK_GENERATOR` and `CPACK_SOURCE_GENERATOR`
+pub const CPACK_GENERATORS: &[(&str, &str)] = &[
+    ("7Z", "7-Zip archive"),
+    ("TBZ2", "Tar archive compressed with bzip2"),
+    ("TGZ", "Tar archive compressed with gzip"),
+    ("TXZ", "Tar archive compressed with xz"),
+    ("TZ", "Tar archive compressed with compress"),
+    ("TZST", "Tar archive compressed with zstd"),
+    ("ZIP", "Zip archive"),
+    ("STGZ", "Self extracting tar gzip archive"),
+    ("DEB", "Debian package"),
+    ("RPM", "RPM package"),
+    ("FREEBSD", "FreeBSD pkg package"),
+    ("NSIS", "NSIS installer for Windows"),
+    ("NSIS64", "64 bit NSIS installer for Windows"),
+    ("WIX", "MSI installer made with the WiX toolset"),
+    ("INNOSETUP", "Inno Setup installer for Windows"),
+    ("NuGet", "NuGet package"),
+    ("DragNDrop", "macOS disk image"),
+    ("Bundle", "macOS application bundle in a disk image"),
+    ("productbuild", "macOS flat package"),
+    ("IFW", "Qt Installer Framework installer"),
+    ("Cygwin", "Cygwin package"),
+    ("AppImage", "AppImage for Linux"),
+    ("External", "JSON file for an external packaging tool"),
+];
+
+/// The variables whose values are generators
+pub const GENERATOR_VARIABLES: &[&str] = &["CPACK_GENERATOR", "CPACK_SOURCE_GENERATOR"];
+
+fn find(table: &[(&'static str, &'static str)], name: &str) -> Option<&'static str> {
+    table
+        .iter()
+        .find(|(key, _)| *key == name)
+        .map(|(_, doc)| *doc)
+}
+
+/// The description of a CPack variable
+pub fn variable_doc(name: &str) -> Option<&'static str> {
+    find(CPACK_VARIABLES, name)
+}
+
+/// The description of a CPack generator
+pub fn generator_doc(name: &str) -> Option<&'static str> {
+    find(CPACK_GENERATORS, name)
+}
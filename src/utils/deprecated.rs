@@ -0,0 +1,89 @@
+//! Commands CMake deprecates, kept only for compatibility with old projects,
+//! and the ones it has removed since.
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct DeprecatedCommand {
+    /// Lower case name
+    pub name: &'static str,
+    /// What to use instead, when something replaces it
+    pub replacement: Option<&'static str>,
+    /// The version of CMake calling it is an error since
+    pub removed_in: Option<&'static str>,
+}
+
+macro_rules! deprecated {
+    ($($name:literal => $replacement:expr, $removed_in:expr;)*) => {
+        /// Deprecated commands, by lower case name
+        pub const DEPRECATED_COMMANDS: &[DeprecatedCommand] = &[$(
+            DeprecatedCommand {
+                name: $name,
+                replacement: $replacement,
+                removed_in: $removed_in,
+            }
+        ),*];
+    };
+}
+
+deprecated! {
+    "build_name" => Some("the CMAKE_SYSTEM and CMAKE_<LANG>_COMPILER variables"), Some("4.0");
+    "exec_program" => Some("execute_process()"), None;
+    "export_library_dependencies" => Some("install(EXPORT) or export()"), Some("4.0");
+    "install_files" => Some("install(FILES)"), None;
+    "install_programs" => Some("install(PROGRAMS)"), None;
+    "install_targets" => Some("install(TARGETS)"), None;
+    "load_command" => None, Some("4.0");
+    "make_directory" => Some("file(MAKE_DIRECTORY)"), None;
+    "output_required_files" => None, Some("4.0");
+    "qt_wrap_cpp" => Some("the AUTOMOC target property"), None;
+    "qt_wrap_ui" => Some("the AUTOUIC target property"), None;
+    "remove" => Some("list(REMOVE_ITEM)"), None;
+    "subdir_depends" => None, Some("4.0");
+    "subdirs" => Some("add_subdirectory()"), None;
+    "use_mangled_mesa" => None, Some("4.0");
+    "utility_source" => None, Some("4.0");
+    "variable_requires" => None, Some("4.0");
+    "write_file" => Some("file(WRITE)"), None;
+}
+
+pub fn find_deprecated_command(name: &str) -> Option<&'static DeprecatedCommand> {
+    let name = name.to_lowercase();
+    DEPRECATED_COMMANDS
+        .iter()
+        .find(|command| command.name == name)
+}
+
+pub fn is_deprecated_command(name: &str) -> bool {
+    find_deprecated_command(name).is_some()
+}
+
+impl DeprecatedCommand {
+    /// What is wrong with calling it, and what to do instead
+    pub fn note(&self) -> String {
+        let status = match self.removed_in {
+            Some(version) => format!("{}() was removed in CMake {version}", self.name),
+            None => format!("{}() is deprecated", self.name),
+        };
+        match self.replacement {
+            Some(replacement) => format!("{status}, use {replacement} instead"),
+            None => status,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deprecated_notes() {
+        assert_eq!(
+            find_deprecated_command("EXEC_PROGRAM").unwrap().note(),
+            "exec_program() is deprecated, use execute_process() instead"
+        );
+        assert_eq!(
+            find_deprecated_command("load_command").unwrap().note(),
+            "load_command() was removed in CMake 4.0"
+        );
+        assert!(!is_deprecated_command("execute_process"));
+    }
+}
@@ -0,0 +1,275 @@
+//! The text of a document, edited in place by the changes of the editor.
+//!
+//! The editor sends the ranges it changed rather than the whole document. The
+//! text is kept in a rope, so applying a change and mapping its positions cost
+//! O(log n) even on files of tens of thousands of lines, and the syntax tree is
+//! parsed again from the chunks of the rope, reusing the unchanged nodes. The
+//! text as one string, which the features read, is built once per version, when
+//! a request needs it.
+
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use ropey::Rope;
+use tower_lsp::lsp_types::{Position, Range, Uri};
+use tree_sitter::{InputEdit, Parser, Point, Tree};
+
+use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+#[derive(Debug, Clone, Default)]
+pub struct Document {
+    rope: Rope,
+    /// The text of the rope, built when it is read
+    text: OnceLock<String>,
+}
+
+impl Document {
+    pub fn new(text: String) -> Self {
+        Self {
+            rope: Rope::from_str(&text),
+            text: OnceLock::from(text),
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        self.text.get_or_init(|| self.rope.to_string())
+    }
+
+    /// The length in bytes
+    pub fn len(&self) -> usize {
+        self.rope.len_bytes()
+    }
+
+    /// The number of lines, counting the empty one after a final line break
+    pub fn line_count(&self) -> usize {
+        self.rope.len_lines()
+    }
+
+    /// The index of the char at `position`, whose character is counted in
+    /// UTF-16 code units. Past the end of a line, the end of the line.
+    fn char_index(&self, position: Position) -> usize {
+        let row = position.line as usize;
+        if row >= self.rope.len_lines() {
+            return self.rope.len_chars();
+        }
+        let line = self.rope.line(row);
+        // The end of the line, before its line break
+        let mut end = line.len_chars();
+        if end > 0 && line.char(end - 1) == '\n' {
+            end -= 1;
+        }
+        if end > 0 && line.char(end - 1) == '\r' {
+            end -= 1;
+        }
+        let column = (position.character as usize).min(line.char_to_utf16_cu(end));
+        self.rope.line_to_char(row) + line.utf16_cu_to_char(column)
+    }
+
+    /// The point of the char at `index`, its column in bytes as for tree-sitter
+    fn point(&self, index: usize) -> Point {
+        let row = self.rope.char_to_line(index);
+        let column = self.rope.char_to_byte(index) - self.rope.line_to_byte(row);
+        Point { row, column }
+    }
+
+    /// Replace `range` with `text`, returns the edit to apply to the syntax
+    /// tree of the document before parsing it again
+    pub fn edit(&mut self, range: Range, text: &str) -> InputEdit {
+        let start = self.char_index(range.start);
+        let old_end = self.char_index(range.end).max(start);
+        let start_byte = self.rope.char_to_byte(start);
+        let old_end_byte = self.rope.char_to_byte(old_end);
+        let start_position = self.point(start);
+        let old_end_position = self.point(old_end);
+
+        self.rope.remove(start..old_end);
+        self.rope.insert(start, text);
+        self.text.take();
+
+        InputEdit {
+            start_byte,
+            old_end_byte,
+            new_end_byte: start_byte + text.len(),
+            start_position,
+            old_end_position,
+            new_end_position: self.point(start + text.chars().count()),
+        }
+    }
+
+    /// Parse the document, reusing the nodes of `old_tree`, the tree of the
+    /// previous version with the edits applied
+    pub fn parse(&self, old_tree: Option<&Tree>) -> Tree {
+        let mut parser = Parser::new();
+        parser.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let len = self.rope.len_bytes();
+        parser
+            .parse_with_options(
+                &mut |byte, _| {
+                    if byte >= len {
+                        return &[][..];
+                    }
+                    let (chunk, chunk_start, _, _) = self.rope.chunk_at_byte(byte);
+                    &chunk.as_bytes()[byte - chunk_start..]
+                },
+                old_tree,
+                None,
+            )
+            .unwrap()
+    }
+}
+
+impl Deref for Document {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.text()
+    }
+}
+
+/// The scheme of the documents not saved yet, like `untitled:Untitled-1`
+const UNTITLED_SCHEME: &str = "untitled:";
+
+/// Whether the document at `uri` was never saved
+pub fn is_untitled(uri: &Uri) -> bool {
+    uri.as_str().starts_with(UNTITLED_SCHEME)
+}
+
+/// `text` with its `%XX` escapes decoded
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%'
+            && let Some(byte) = text
+                .get(index + 1..index + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            decoded.push(byte);
+            index += 3;
+            continue;
+        }
+        decoded.push(bytes[index]);
+        index += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// The path of the document at `uri`. An untitled document is read as a file
+/// of the workspace root, `root`, or of the current directory without one,
+/// unless the editor tells the path it is going to be saved at. Its name keeps
+/// the `untitled:` scheme, so it never stands for a file on the disk, like the
+/// CMakeLists.txt of the root for `untitled:CMakeLists.txt`.
+pub fn document_path(uri: &Uri, root: Option<&Path>) -> Option<PathBuf> {
+    if uri.scheme().as_str() == "file" {
+        return uri.to_file_path().ok().map(|path| path.to_path_buf());
+    }
+    let name = percent_decode(uri.as_str().strip_prefix(UNTITLED_SCHEME)?);
+    let path = Path::new(&name);
+    let base = match path.parent() {
+        Some(parent) if path.is_absolute() => parent.to_path_buf(),
+        _ => match root {
+            Some(root) => root.to_path_buf(),
+            None => std::env::current_dir().ok()?,
+        },
+    };
+    let file_name = path.file_name()?.to_string_lossy();
+    Some(base.join(format!("{UNTITLED_SCHEME}{file_name}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: (u32, u32), end: (u32, u32)) -> Range {
+        Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1))
+    }
+
+    fn parse(text: &str) -> Tree {
+        let mut parser = Parser::new();
+        parser.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        parser.parse(text, None).unwrap()
+    }
+
+    #[test]
+    fn test_edit() {
+        let mut document = Document::new("project(demo)\r\nset(A \"é😀\")\n".to_string());
+        assert_eq!(document.line_count(), 3);
+        // After the emoji, two UTF-16 code units
+        document.edit(range((1, 10), (1, 10)), "b");
+        assert_eq!(document.text(), "project(demo)\r\nset(A \"é😀b\")\n");
+        // Past the end of the line, before its line break
+        document.edit(range((0, 8), (0, 40)), "other)");
+        assert_eq!(document.text(), "project(other)\r\nset(A \"é😀b\")\n");
+        // Past the last line
+        document.edit(range((5, 0), (5, 0)), "add_subdirectory(src)\n");
+        assert_eq!(
+            &*document,
+            "project(other)\r\nset(A \"é😀b\")\nadd_subdirectory(src)\n"
+        );
+        document.edit(range((1, 0), (2, 0)), "");
+        assert_eq!(document.text(), "project(other)\r\nadd_subdirectory(src)\n");
+        assert_eq!(document.len(), document.text().len());
+    }
+
+    #[test]
+    fn test_parse_after_edits() {
+        let mut document = Document::new("project(demo)\nset(A 1)\n".to_string());
+        let mut tree = document.parse(None);
+        for (changed, text) in [
+            (range((1, 6), (1, 7)), "${B}"),
+            (range((2, 0), (2, 0)), "if(A)\n  message(é)\nendif()\n"),
+            (range((0, 8), (0, 12)), "changed"),
+            (range((3, 11), (4, 0)), ")\n"),
+        ] {
+            let edit = document.edit(changed, text);
+            tree.edit(&edit);
+            tree = document.parse(Some(&tree));
+            assert_eq!(
+                tree.root_node().to_sexp(),
+                parse(document.text()).root_node().to_sexp(),
+                "{}",
+                document.text()
+            );
+        }
+        assert_eq!(
+            document.text(),
+            "project(changed)\nset(A ${B})\nif(A)\n  message(é)\nendif()\n"
+        );
+    }
+
+    #[test]
+    fn test_document_path() {
+        let root = Path::new("/tmp/project");
+        let untitled: Uri = "untitled:Untitled-1".parse().unwrap();
+        assert!(is_untitled(&untitled));
+        assert_eq!(
+            document_path(&untitled, Some(root)),
+            Some(root.join("untitled:Untitled-1"))
+        );
+        // Not the CMakeLists.txt of the root
+        let named: Uri = "untitled:CMakeLists.txt".parse().unwrap();
+        assert_eq!(
+            document_path(&named, Some(root)),
+            Some(root.join("untitled:CMakeLists.txt"))
+        );
+        let proposed: Uri = "untitled:/tmp/other/My%20Project/CMakeLists.txt"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            document_path(&proposed, Some(root)),
+            Some(PathBuf::from(
+                "/tmp/other/My Project/untitled:CMakeLists.txt"
+            ))
+        );
+        let file = Uri::from_file_path(root.join("CMakeLists.txt")).unwrap();
+        assert!(!is_untitled(&file));
+        assert_eq!(
+            document_path(&file, None),
+            Some(root.join("CMakeLists.txt"))
+        );
+        let other: Uri = "git:/tmp/project/CMakeLists.txt".parse().unwrap();
+        assert_eq!(document_path(&other, Some(root)), None);
+    }
+}
@@ -0,0 +1,120 @@
+//! Detection of the vendored code of the project.
+//!
+//! The CMake files under `third_party/`, `extern/`, `_deps/`... and the sources
+//! downloaded by `FetchContent` are indexed like the others, so their targets
+//! are completed, but their diagnostics are lowered or dropped as the user
+//! cannot fix them.
+
+use std::path::{Path, PathBuf};
+
+use crate::config::{self, CONFIG};
+use crate::fileapi;
+
+/// The names of the directories holding vendored code
+pub const EXTERNAL_DIRS: &[&str] = &[
+    "third_party",
+    "third-party",
+    "thirdparty",
+    "3rdparty",
+    "extern",
+    "_deps",
+];
+
+/// The names which hold vendored code only at the top of the project, deeper
+/// down they are as likely the project's own code
+pub const ROOT_EXTERNAL_DIRS: &[&str] = &["external", "vendor"];
+
+/// The cache entries giving where `FetchContent` puts the sources
+const FETCHCONTENT_BASE_DIR: &str = "FETCHCONTENT_BASE_DIR";
+const FETCHCONTENT_SOURCE_DIR: &str = "FETCHCONTENT_SOURCE_DIR_";
+
+/// The directories of the sources of `FetchContent`, from the CMake cache
+fn fetched_dirs() -> Vec<PathBuf> {
+    fileapi::with_cache_data(|cache| {
+        cache
+            .entries
+            .iter()
+            .filter(|entry| {
+                !entry.value().is_empty()
+                    && (entry.name() == FETCHCONTENT_BASE_DIR
+                        || entry.name().starts_with(FETCHCONTENT_SOURCE_DIR))
+            })
+            .map(|entry| PathBuf::from(entry.value()))
+            .collect()
+    })
+    .unwrap_or_default()
+}
+
+/// Whether `path` is under a vendored directory of the project at `root`, one
+/// of [`EXTERNAL_DIRS`], one of [`ROOT_EXTERNAL_DIRS`] at the top, matching
+/// one of `patterns` or one of `fetched`
+pub fn is_external_in(path: &Path, root: &Path, patterns: &[String], fetched: &[PathBuf]) -> bool {
+    if fetched.iter().any(|dir| path.starts_with(dir)) {
+        return true;
+    }
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let Some(parent) = relative.parent() else {
+        return false;
+    };
+    if parent.components().any(|component| {
+        EXTERNAL_DIRS
+            .iter()
+            .any(|dir| component.as_os_str() == *dir)
+    }) {
+        return true;
+    }
+    if parent
+        .components()
+        .next()
+        .is_some_and(|top| ROOT_EXTERNAL_DIRS.iter().any(|dir| top.as_os_str() == *dir))
+    {
+        return true;
+    }
+    patterns.iter().any(|pattern| {
+        let Ok(pattern) = glob::Pattern::new(pattern.trim_end_matches('/')) else {
+            return false;
+        };
+        parent
+            .ancestors()
+            .filter(|part| !part.as_os_str().is_empty())
+            .any(|part| pattern.matches_path(part))
+    })
+}
+
+/// Whether the file at `path` is vendored code, by its directory or the config
+pub fn is_external(path: &Path) -> bool {
+    let (Some(root), Ok(path)) = (config::project_root(), std::path::absolute(path)) else {
+        return false;
+    };
+    is_external_in(&path, &root, &CONFIG.external_dirs, &fetched_dirs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_external() {
+        let root = Path::new("/project");
+        let patterns = vec!["libs/*/upstream/".to_string()];
+        let fetched = vec![PathBuf::from("/build/fetched")];
+        let external = |path: &str| is_external_in(Path::new(path), root, &patterns, &fetched);
+        assert!(external("/project/third_party/zlib/CMakeLists.txt"));
+        assert!(external("/project/build/_deps/fmt-src/CMakeLists.txt"));
+        assert!(external("/project/libs/json/upstream/cmake/config.cmake"));
+        assert!(external("/build/fetched/googletest-src/CMakeLists.txt"));
+        assert!(external("/project/vendor/glm/CMakeLists.txt"));
+        assert!(!external("/project/src/vendor/CMakeLists.txt"));
+        assert!(!external("/project/plugins/external/CMakeLists.txt"));
+        assert!(!external("/project/CMakeLists.txt"));
+        assert!(!external("/project/libs/json/CMakeLists.txt"));
+        assert!(!external("/project/cmake/external.cmake"));
+        // The project itself may be checked out in such a directory
+        assert!(!is_external_in(
+            Path::new("/home/user/extern/project/CMakeLists.txt"),
+            Path::new("/home/user/extern/project"),
+            &[],
+            &[]
+        ));
+    }
+}
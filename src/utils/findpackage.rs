@@ -303,7 +303,7 @@ fn get_version(source: &str) -> Option<String> {
 #[cfg(unix)]
 pub mod packagepkgconfig {
     use std::collections::HashMap;
-    use std::sync::{Arc, LazyLock, Mutex};
+    use std::sync::{Arc, LazyLock, Mutex, PoisonError};
 
     use super::{FindPackageFunsFake, FindPackageFunsReal, FindPackageFunsTrait};
     use crate::Uri;
@@ -322,7 +322,11 @@ pub mod packagepkgconfig {
     pub(super) fn get_pkg_messages() -> HashMap<String, PkgConfig> {
         let mut packages: HashMap<String, PkgConfig> = HashMap::new();
         let mut generatepackage = || -> anyhow::Result<()> {
-            for path in QUERYSRULES.lock().unwrap().iter() {
+            for path in QUERYSRULES
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .iter()
+            {
                 for entry in glob::glob(path)?.flatten() {
                     let Some(file_name) =
                         entry.file_name().and_then(|file_name| file_name.to_str())
@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, LazyLock, Mutex};
+use std::sync::{Arc, LazyLock, Mutex, PoisonError};
 
 use super::{
     CMAKECONFIG, CMAKECONFIGVERSION, CMAKEREGEX, SPECIAL_PACKAGE_PATTERN, get_version,
@@ -34,8 +34,8 @@ fn safe_canonicalize(path: &Path) -> std::io::Result<PathBuf> {
 
 fn get_available_libs() -> Vec<PathBuf> {
     let mut ava: Vec<PathBuf> = vec![];
-    let vcpkg_prefix = VCPKG_PREFIX.lock().unwrap();
-    let vcpkg_libs = VCPKG_LIBS.lock().unwrap();
+    let vcpkg_prefix = VCPKG_PREFIX.lock().unwrap_or_else(PoisonError::into_inner);
+    let vcpkg_libs = VCPKG_LIBS.lock().unwrap_or_else(PoisonError::into_inner);
     for prefix in vcpkg_prefix.iter() {
         for lib in vcpkg_libs.iter() {
             let p = Path::new(prefix).join(lib);
@@ -49,7 +49,7 @@ fn get_available_libs() -> Vec<PathBuf> {
 
 fn get_cmake_message() -> HashMap<String, CMakePackage> {
     let mut packages: HashMap<String, CMakePackage> = HashMap::new();
-    let vcpkg_prefix = VCPKG_PREFIX.lock().unwrap();
+    let vcpkg_prefix = VCPKG_PREFIX.lock().unwrap_or_else(PoisonError::into_inner);
     for lib in vcpkg_prefix.iter() {
         let Ok(paths) = glob::glob(&format!("{lib}/share/*/cmake/")) else {
             continue;
@@ -228,12 +228,12 @@ mod tests {
             .to_str()
             .unwrap()
             .to_string();
-        let mut prefix = VCPKG_PREFIX.lock().unwrap();
+        let mut prefix = VCPKG_PREFIX.lock().unwrap_or_else(PoisonError::into_inner);
 
         prefix.push(Box::leak(prefix_dir.into_boxed_str()));
         drop(prefix);
 
-        let mut libs = VCPKG_LIBS.lock().unwrap();
+        let mut libs = VCPKG_LIBS.lock().unwrap_or_else(PoisonError::into_inner);
         libs.push("x64-linux");
         libs.push("share/cmake");
         drop(libs);
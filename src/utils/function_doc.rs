@@ -0,0 +1,297 @@
+//! The documentation of the functions and macros of the project, read from the
+//! comment right above their definition, either line comments:
+//!
+//! ```cmake
+//! # Add a plugin library.
+//! #   NAME  the name of the target
+//! function(add_plugin NAME)
+//! ```
+//!
+//! or a bracket comment, as the modules of cmake do:
+//!
+//! ```cmake
+//! #[=[.rst:
+//! Add a plugin library.
+//! #]=]
+//! function(add_plugin NAME)
+//! ```
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex, PoisonError};
+
+use tree_sitter::Node;
+
+use super::parse_arguments::{definition_name, node_text, parse_arguments_of};
+use crate::CMakeNodeKinds;
+
+/// The signature and the documentation of a function or macro
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionDoc {
+    /// Like `add_plugin(NAME [STATIC] [SOURCES <value>...])`
+    pub label: String,
+    pub parameters: Vec<String>,
+    pub documentation: String,
+    /// The file defining it
+    pub path: PathBuf,
+}
+
+/// The functions and macros seen while indexing, keyed by lowercase name.
+pub static FUNCTION_DOCS: LazyLock<Mutex<HashMap<String, FunctionDoc>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// A line drawing a banner, like `#------`
+fn is_banner(line: &str) -> bool {
+    !line.is_empty()
+        && line
+            .chars()
+            .all(|c| matches!(c, '#' | '-' | '=' | '*' | '~'))
+}
+
+/// The text of a line comment, without its `#` and the space after it
+fn line_comment_text(line: &str) -> &str {
+    let text = line.trim().trim_start_matches('#');
+    text.strip_prefix(' ').unwrap_or(text).trim_end()
+}
+
+/// The text inside a bracket comment, like `#[=[ ... #]=]`, dedented
+fn bracket_comment_text(text: &str) -> String {
+    let text = text.strip_prefix('#').unwrap_or(text);
+    // `[==[` is closed by `]==]` only, the brackets inside are text
+    let equals = text
+        .strip_prefix('[')
+        .map_or(0, |rest| rest.chars().take_while(|c| *c == '=').count());
+    let open = format!("[{}[", "=".repeat(equals));
+    let close = format!("]{}]", "=".repeat(equals));
+    let text = text.strip_prefix(open.as_str()).unwrap_or(text);
+    let text = text.strip_suffix(close.as_str()).unwrap_or(text);
+    // The `#` commenting out the closing bracket, like `#]=]`
+    let text = text.strip_suffix('#').unwrap_or(text);
+    let text = text.strip_prefix(".rst:").unwrap_or(text);
+    let lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+    let indent = lines
+        .iter()
+        .filter(|line| !line.is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    lines
+        .iter()
+        .map(|line| line.get(indent..).unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim_matches('\n')
+        .to_string()
+}
+
+/// The documentation of `definition`, a `function_def` or `macro_def`: the line
+/// comments on the lines right above it, or the bracket comment ending there
+pub fn leading_doc(source: &[&str], definition: Node) -> Option<String> {
+    let mut row = definition.start_position().row;
+    let mut lines = vec![];
+    let mut previous = definition.prev_sibling();
+    while let Some(comment) = previous {
+        if comment.end_position().row + 1 != row {
+            break;
+        }
+        let start = comment.start_position();
+        // A comment after a command documents the command
+        if !source[start.row][..start.column].trim().is_empty() {
+            break;
+        }
+        match comment.kind() {
+            CMakeNodeKinds::LINE_COMMENT => {
+                lines.push(line_comment_text(
+                    source.get(start.row)?.get(start.column..)?,
+                ));
+            }
+            CMakeNodeKinds::BRACKET_COMMENT if lines.is_empty() => {
+                let text = bracket_comment_text(&node_text(source, comment)?);
+                return (!text.is_empty()).then_some(text);
+            }
+            _ => break,
+        }
+        row = start.row;
+        previous = comment.prev_sibling();
+    }
+    lines.reverse();
+    let lines: Vec<&str> = lines
+        .into_iter()
+        .skip_while(|line| line.is_empty() || is_banner(line))
+        .collect();
+    let end = lines
+        .iter()
+        .rposition(|line| !line.is_empty() && !is_banner(line))?;
+    Some(lines[..=end].join("\n"))
+}
+
+/// The parameters of `definition`: its named arguments, then the keywords of
+/// its `cmake_parse_arguments()`
+fn parameters_of(source: &[&str], definition: Node) -> Option<(String, Vec<String>)> {
+    let arguments = definition.child(0)?.child(2)?;
+    let mut cursor = arguments.walk();
+    let mut names = arguments
+        .children(&mut cursor)
+        .filter(|argument| argument.kind() == CMakeNodeKinds::ARGUMENT)
+        .filter_map(|argument| node_text(source, argument));
+    let name = names.next()?;
+    let mut parameters: Vec<String> = names.collect();
+    if let Some(parsed) = parse_arguments_of(source, definition) {
+        parameters.extend(parsed.options.iter().map(|option| format!("[{option}]")));
+        parameters.extend(
+            parsed
+                .one_value_keywords
+                .iter()
+                .map(|keyword| format!("[{keyword} <value>]")),
+        );
+        parameters.extend(
+            parsed
+                .multi_value_keywords
+                .iter()
+                .map(|keyword| format!("[{keyword} <value>...]")),
+        );
+    }
+    Some((name, parameters))
+}
+
+/// The signature and the documentation of `definition`, in the file at `path`
+pub fn function_doc(source: &[&str], definition: Node, path: &Path) -> Option<FunctionDoc> {
+    let (name, parameters) = parameters_of(source, definition)?;
+    Some(FunctionDoc {
+        label: format!("{name}({})", parameters.join(" ")),
+        parameters,
+        documentation: leading_doc(source, definition).unwrap_or_default(),
+        path: path.to_path_buf(),
+    })
+}
+
+/// Record the documentation of a definition of the file at `path` for the
+/// signature help
+pub fn update_function_doc(source: &[&str], definition: Node, path: &Path) {
+    let Some(name) = definition_name(source, definition) else {
+        return;
+    };
+    let mut docs = FUNCTION_DOCS.lock().unwrap_or_else(PoisonError::into_inner);
+    match function_doc(source, definition, path) {
+        Some(doc) => {
+            docs.insert(name, doc);
+        }
+        None => {
+            docs.remove(&name);
+        }
+    }
+}
+
+/// Forget the definitions of the file at `path`, before it is read again or
+/// once it is gone
+pub fn forget_file(path: &Path) {
+    FUNCTION_DOCS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .retain(|_, doc| doc.path != path);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    fn definition_docs(source: &str) -> Vec<Option<FunctionDoc>> {
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let root = thetree.root_node();
+        let mut cursor = root.walk();
+        root.children(&mut cursor)
+            .filter(|child| {
+                matches!(
+                    child.kind(),
+                    CMakeNodeKinds::FUNCTION_DEF | CMakeNodeKinds::MACRO_DEF
+                )
+            })
+            .map(|definition| function_doc(&lines, definition, Path::new("plugins.cmake")))
+            .collect()
+    }
+
+    #[test]
+    fn test_function_docs() {
+        let source = r#"# ---------------------
+# Add a plugin library.
+#   NAME  the name of the target
+# ---------------------
+function(add_plugin NAME)
+  cmake_parse_arguments(ARG "STATIC" "" "SOURCES" ${ARGN})
+endfunction()
+
+#[=[.rst:
+  Print the
+  ``MESSAGE``.
+#]=]
+macro(say MESSAGE)
+endmacro()
+
+set(A 1) # Not about the function
+
+function(undocumented)
+endfunction()
+"#;
+        let docs = definition_docs(source);
+        assert_eq!(
+            docs[0],
+            Some(FunctionDoc {
+                label: "add_plugin(NAME [STATIC] [SOURCES <value>...])".to_string(),
+                parameters: vec![
+                    "NAME".to_string(),
+                    "[STATIC]".to_string(),
+                    "[SOURCES <value>...]".to_string()
+                ],
+                documentation: "Add a plugin library.\n  NAME  the name of the target".to_string(),
+                path: PathBuf::from("plugins.cmake"),
+            })
+        );
+        let say = docs[1].as_ref().unwrap();
+        assert_eq!(say.label, "say(MESSAGE)");
+        assert_eq!(say.documentation, "Print the\n``MESSAGE``.");
+        assert_eq!(docs[2].as_ref().unwrap().documentation, "");
+    }
+
+    #[test]
+    fn test_comment_texts() {
+        assert_eq!(line_comment_text("  #  indented"), " indented");
+        assert_eq!(line_comment_text("## Title "), "Title");
+        assert!(is_banner("#====="));
+        assert_eq!(
+            bracket_comment_text("#[[\nText\n  More\n]]"),
+            "Text\n  More"
+        );
+        // The brackets of the text are kept, only the closing one goes
+        assert_eq!(
+            bracket_comment_text("#[==[\nIndex list[0]\n]]\n#]==]"),
+            "Index list[0]\n]]"
+        );
+    }
+
+    #[test]
+    fn test_forget_file() {
+        let source = "function(forget_file_demo)\nendfunction()\n";
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let path = Path::new("/forget/demo.cmake");
+        let definition = thetree.root_node().child(0).unwrap();
+        update_function_doc(&lines, definition, path);
+        let known = || {
+            FUNCTION_DOCS
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .contains_key("forget_file_demo")
+        };
+        assert!(known());
+        forget_file(Path::new("/forget/other.cmake"));
+        assert!(known());
+        forget_file(path);
+        assert!(!known());
+    }
+}
@@ -0,0 +1,105 @@
+//! The generators of the installed cmake, and the platforms given to them.
+
+use std::process::Command;
+use std::sync::LazyLock;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Generator {
+    pub name: String,
+    pub description: String,
+    /// Marked with `*` by `cmake --help`, used without `-G`
+    pub default: bool,
+}
+
+/// The generators listed by `cmake --help`
+pub static CMAKE_GENERATORS: LazyLock<Vec<Generator>> = LazyLock::new(|| {
+    Command::new("cmake")
+        .arg("--help")
+        .output()
+        .map(|output| parse_generators(&String::from_utf8_lossy(&output.stdout)))
+        .unwrap_or_default()
+});
+
+/// The values of `CMAKE_GENERATOR_PLATFORM`, `-A` or the `architecture` of a
+/// preset, for the Visual Studio generators
+pub const GENERATOR_PLATFORMS: &[(&str, &str)] = &[
+    ("x64", "64 bit x86"),
+    ("Win32", "32 bit x86"),
+    ("ARM64", "64 bit ARM"),
+    ("ARM64EC", "64 bit ARM, compatible with x64 code"),
+    ("ARM", "32 bit ARM"),
+];
+
+/// The variables whose values are generators, and platforms
+pub const GENERATOR_VARIABLE: &str = "CMAKE_GENERATOR";
+pub const PLATFORM_VARIABLE: &str = "CMAKE_GENERATOR_PLATFORM";
+
+/// The `Generators` section of `cmake --help`, where each generator is
+/// followed by `=` and a description, which may go on over the next lines
+fn parse_generators(help: &str) -> Vec<Generator> {
+    let mut generators: Vec<Generator> = vec![];
+    let Some(start) = help.find("The following generators are available") else {
+        return generators;
+    };
+    for line in help[start..].lines().skip(1) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match line.split_once('=') {
+            Some((name, description)) if !name.trim().is_empty() => {
+                let default = name.trim_start().starts_with('*');
+                let name = name.trim_start_matches([' ', '*']).trim();
+                // `Visual Studio 9 2008 [arch]` takes the platform in its name
+                let name = name.strip_suffix("[arch]").unwrap_or(name).trim();
+                generators.push(Generator {
+                    name: name.to_string(),
+                    description: description.trim().to_string(),
+                    default,
+                });
+            }
+            _ => {
+                let Some(last) = generators.last_mut() else {
+                    break;
+                };
+                if !line.starts_with(' ') {
+                    break;
+                }
+                last.description.push(' ');
+                last.description.push_str(line.trim());
+            }
+        }
+    }
+    generators
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_generators() {
+        let help = r#"Usage
+
+  cmake [options] <path-to-source>
+
+Generators
+
+The following generators are available on this platform (* marks default):
+* Unix Makefiles               = Generates standard UNIX makefiles.
+  Ninja                        = Generates build.ninja files.
+  Visual Studio 17 2022        = Generates Visual Studio 2022 project files.
+                                 Use -A option to specify architecture.
+  Visual Studio 9 2008 [arch]  = Generates Visual Studio 2008 project files.
+"#;
+        let generators = parse_generators(help);
+        assert_eq!(generators.len(), 4);
+        assert!(generators[0].default);
+        assert_eq!(generators[0].name, "Unix Makefiles");
+        assert!(!generators[1].default);
+        assert_eq!(
+            generators[2].description,
+            "Generates Visual Studio 2022 project files. Use -A option to specify architecture."
+        );
+        assert_eq!(generators[3].name, "Visual Studio 9 2008");
+    }
+}
@@ -0,0 +1,431 @@
+//! The reStructuredText printed by `cmake --help-*`, as Markdown.
+//!
+//! cmake prints the sources of its manual with the literal blocks indented one
+//! space more than the text around them, and keeps the directives, roles and
+//! definition lists as they are. They become code fences, emphasized notes,
+//! inline code and list items.
+
+use std::sync::LazyLock;
+
+static DIRECTIVE_REGEX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"^\.\.\s+(?<name>[a-z_-]+)::\s*(?<argument>.*)$").unwrap());
+
+/// An option of a directive, such as `:target: EVAL`
+static DIRECTIVE_OPTION_REGEX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"^:[a-z-]+:(\s|$)").unwrap());
+
+static LIST_ITEM_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(r"^(?<marker>[*+-]|\d+\.|#\.|[a-z]\.)\s+(?<text>.*)$").unwrap()
+});
+
+static SIGNATURE_REGEX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"^([A-Za-z_][A-Za-z0-9_]*\s*\(|#)").unwrap());
+
+static LINK_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(r"`(?<text>[^`<]+?)\s*<(?<url>[a-z]+://[^`>]+)>`__?").unwrap()
+});
+
+static ROLE_REGEX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r":(?<role>[a-z_]+(:[a-z_]+)?):`(?<text>[^`]+)`").unwrap());
+
+static LITERAL_REGEX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"(?s)``(?<code>.+?)``").unwrap());
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// Whether `text` only underlines a title
+fn is_underline(text: &str) -> bool {
+    text.len() >= 3
+        && text
+            .chars()
+            .next()
+            .is_some_and(|first| "=-^\"*~#+".contains(first) && text.chars().all(|c| c == first))
+}
+
+#[derive(Default)]
+struct Markdown {
+    /// The lines, and whether they are part of a code block
+    lines: Vec<(bool, String)>,
+    /// The indentation of the content of the open list items
+    items: Vec<usize>,
+}
+
+impl Markdown {
+    /// The number of list items the text at `indent` is nested in
+    fn depth(&mut self, indent: usize) -> usize {
+        while self.items.last().is_some_and(|item| *item > indent) {
+            self.items.pop();
+        }
+        self.items.len()
+    }
+
+    fn blank(&mut self) {
+        if self.lines.last().is_some_and(|(_, line)| !line.is_empty()) {
+            self.lines.push((false, String::new()));
+        }
+    }
+
+    fn text(&mut self, indent: usize, text: &str) {
+        let depth = self.depth(indent);
+        self.lines
+            .push((false, format!("{}{text}", "  ".repeat(depth))));
+    }
+
+    fn item(&mut self, indent: usize, marker: &str, text: &str, content_indent: usize) {
+        let depth = self.depth(indent);
+        self.lines
+            .push((false, format!("{}{marker} {text}", "  ".repeat(depth))));
+        self.items.push(content_indent);
+    }
+
+    fn code(&mut self, indent: usize, language: &str, code: &[&str]) {
+        let prefix = "  ".repeat(self.depth(indent));
+        self.blank();
+        self.lines.push((true, format!("{prefix}```{language}")));
+        for line in code {
+            let line = line.get(indent..).unwrap_or(line.trim_start());
+            self.lines.push((true, format!("{prefix}{line}")));
+        }
+        self.lines.push((true, format!("{prefix}```")));
+        self.lines.push((false, String::new()));
+    }
+
+    fn finish(self) -> String {
+        let mut output = String::new();
+        let mut prose = String::new();
+        for (is_code, line) in self.lines {
+            if is_code {
+                output.push_str(&inline_markdown(&prose));
+                prose.clear();
+                output.push_str(&line);
+                output.push('\n');
+            } else {
+                prose.push_str(&line);
+                prose.push('\n');
+            }
+        }
+        output.push_str(&inline_markdown(&prose));
+        output.trim().to_string()
+    }
+}
+
+/// Escape the `<` outside of inline code, which would start an html tag
+fn escape_angles(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut in_code = false;
+    for c in text.chars() {
+        match c {
+            '`' => in_code = !in_code,
+            '<' if !in_code => output.push('\\'),
+            _ => {}
+        }
+        output.push(c);
+    }
+    output
+}
+
+/// The links, roles and inline literals of `text` as Markdown
+fn inline_markdown(text: &str) -> String {
+    let text = LINK_REGEX.replace_all(text, "[$text]($url)");
+    let text = ROLE_REGEX.replace_all(&text, |caps: &regex::Captures| {
+        let content = &caps["text"];
+        let (shown, has_target) = match content.rsplit_once(" <") {
+            Some((shown, target)) if target.ends_with('>') => (shown.trim(), true),
+            _ => (content, false),
+        };
+        match &caps["role"] {
+            "ref" | "doc" | "manual" if has_target => shown.to_string(),
+            _ => format!("`{shown}`"),
+        }
+    });
+    let text = LITERAL_REGEX.replace_all(&text, "`$code`");
+    escape_angles(&text)
+}
+
+/// The lines from `start` indented at least `indent`, up to the next line
+/// indented less, without the blank lines at their end
+fn indented_block<'a>(lines: &[&'a str], start: usize, indent: usize) -> (Vec<&'a str>, usize) {
+    let mut end = start;
+    while end < lines.len() && (lines[end].trim().is_empty() || indent_of(lines[end]) >= indent) {
+        end += 1;
+    }
+    let mut block = lines[start..end].to_vec();
+    while block.last().is_some_and(|line| line.trim().is_empty()) {
+        block.pop();
+    }
+    (block, end)
+}
+
+fn admonition(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "note" => "Note",
+        "warning" => "Warning",
+        "important" => "Important",
+        "hint" => "Hint",
+        "tip" => "Tip",
+        "caution" => "Caution",
+        "attention" => "Attention",
+        "danger" => "Danger",
+        "seealso" => "See also",
+        _ => return None,
+    })
+}
+
+/// Render a directive starting at line `index`, returning the next line to read
+fn directive(
+    output: &mut Markdown,
+    lines: &[&str],
+    index: usize,
+    name: &str,
+    argument: &str,
+) -> usize {
+    let indent = indent_of(lines[index]);
+    let mut next = index + 1;
+    // The options of the directive
+    while next < lines.len()
+        && indent_of(lines[next]) > indent
+        && DIRECTIVE_OPTION_REGEX.is_match(lines[next].trim())
+    {
+        next += 1;
+    }
+    match name {
+        "code-block" | "code" | "parsed-literal" | "productionlist" => {
+            let Some(first) = (next..lines.len()).find(|row| !lines[*row].trim().is_empty()) else {
+                return next;
+            };
+            let code_indent = indent_of(lines[first]);
+            if code_indent <= indent {
+                return next;
+            }
+            let (block, end) = indented_block(lines, first, code_indent);
+            let language = match (name, argument) {
+                ("parsed-literal", _) => "cmake",
+                (_, "") => "",
+                (_, language) => language,
+            };
+            output.code(code_indent, language, &block);
+            end
+        }
+        "signature" => {
+            let mut signature = vec![];
+            if !argument.is_empty() {
+                signature.push(argument);
+            }
+            while next < lines.len() && indent_of(lines[next]) > indent {
+                let line = lines[next].trim();
+                if line.is_empty() {
+                    break;
+                }
+                if !DIRECTIVE_OPTION_REGEX.is_match(line) {
+                    signature.push(line);
+                }
+                next += 1;
+            }
+            output.code(0, "cmake", &signature);
+            next
+        }
+        "versionadded" => {
+            output.text(indent, &format!("*New in version {argument}.*"));
+            next
+        }
+        "versionchanged" => {
+            output.text(indent, &format!("*Changed in version {argument}.*"));
+            next
+        }
+        "deprecated" => {
+            output.text(indent, &format!("*Deprecated since version {argument}.*"));
+            next
+        }
+        "command" | "variable" | "envvar" | "genex" | "module" | "policy" => {
+            output.text(indent, &format!("**`{argument}`**"));
+            next
+        }
+        _ => {
+            if let Some(title) = admonition(name) {
+                let text = if argument.is_empty() {
+                    format!("**{title}:**")
+                } else {
+                    format!("**{title}:** {argument}")
+                };
+                output.text(indent, &text);
+            }
+            next
+        }
+    }
+}
+
+/// The help of a command, variable or module as Markdown
+pub fn help_to_markdown(help: &str) -> String {
+    let lines: Vec<&str> = help.lines().collect();
+    let mut output = Markdown::default();
+    // The indentation of the text the next lines are part of
+    let mut context = 0;
+    let mut after_blank = true;
+    let mut literal_next = false;
+    let mut index = 0;
+    while index < lines.len() {
+        let line = lines[index].trim_end();
+        let text = line.trim_start();
+        let indent = indent_of(line);
+        if text.is_empty() {
+            output.blank();
+            after_blank = true;
+            index += 1;
+            continue;
+        }
+        if after_blank && (literal_next || (indent % 2 == 1 && indent > context)) {
+            let (block, end) = indented_block(&lines, index, indent);
+            let language = if SIGNATURE_REGEX.is_match(text) {
+                "cmake"
+            } else {
+                ""
+            };
+            output.code(indent, language, &block);
+            literal_next = false;
+            index = end;
+            continue;
+        }
+        literal_next = false;
+        after_blank = false;
+
+        let next = lines.get(index + 1).map(|next| next.trim_end());
+        if indent == 0 && next.is_some_and(|next| is_underline(next) && next.len() >= text.len()) {
+            output.blank();
+            output.text(0, &format!("### {text}"));
+            output.blank();
+            context = 0;
+            index += 2;
+            continue;
+        }
+        if is_underline(text) {
+            index += 1;
+            continue;
+        }
+        if let Some(caps) = DIRECTIVE_REGEX.captures(text) {
+            index = directive(&mut output, &lines, index, &caps["name"], &caps["argument"]);
+            context = indent + 2;
+            continue;
+        }
+
+        let text = match text.strip_suffix("::") {
+            Some(rest) => {
+                literal_next = true;
+                if rest.trim().is_empty() {
+                    index += 1;
+                    continue;
+                }
+                format!("{}:", rest.trim_end())
+            }
+            None => text.to_string(),
+        };
+        if let Some(caps) = LIST_ITEM_REGEX.captures(&text) {
+            let marker = &caps["marker"];
+            let content_indent = indent + marker.len() + 1;
+            let marker = if marker.starts_with(|c: char| c.is_ascii_digit() || c == '#') {
+                "1."
+            } else {
+                "-"
+            };
+            output.item(indent, marker, &caps["text"], content_indent);
+            context = content_indent;
+        } else if let Some(next) = next
+            && !next.trim().is_empty()
+            && indent_of(next) > indent
+        {
+            // The term of a definition list, its definition being indented below it
+            output.item(indent, "-", &text, indent_of(next));
+            context = indent_of(next);
+        } else {
+            output.text(indent, &text);
+            context = indent;
+        }
+        index += 1;
+    }
+    output.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_help_to_markdown() {
+        let help = r#"
+.. versionadded:: 3.25
+
+Evaluate a group of commands with a dedicated :ref:`scope <Scopes>`.
+
+ block([SCOPE_FOR [POLICIES] [VARIABLES] ] [PROPAGATE <var-name>...])
+   <commands>
+ endblock()
+
+All commands between ``block()`` and the matching :command:`endblock`
+are recorded.
+
+``SCOPE_FOR``
+  Specify which scopes must be created.
+
+  .. code-block:: cmake
+
+    block(SCOPE_FOR VARIABLES POLICIES)
+
+* one
+* two
+
+Output::
+
+ -- done
+"#;
+        assert_eq!(
+            help_to_markdown(help),
+            r#"*New in version 3.25.*
+
+Evaluate a group of commands with a dedicated scope.
+
+```cmake
+block([SCOPE_FOR [POLICIES] [VARIABLES] ] [PROPAGATE <var-name>...])
+  <commands>
+endblock()
+```
+
+All commands between `block()` and the matching `endblock`
+are recorded.
+
+- `SCOPE_FOR`
+  Specify which scopes must be created.
+
+  ```cmake
+  block(SCOPE_FOR VARIABLES POLICIES)
+  ```
+
+- one
+- two
+
+Output:
+
+```
+-- done
+```"#
+        );
+    }
+
+    #[test]
+    fn test_signature_and_title() {
+        let help = r#"
+Synopsis
+^^^^^^^^
+
+.. signature::
+  cmake_language(EVAL CODE <code>...)
+  :target: EVAL
+
+  Evaluates the ``<code>...`` as CMake code, see `the docs <https://cmake.org>`_.
+"#;
+        assert_eq!(
+            help_to_markdown(help),
+            "### Synopsis\n\n```cmake\ncmake_language(EVAL CODE <code>...)\n```\n\nEvaluates the `<code>...` as CMake code, see [the docs](https://cmake.org)."
+        );
+    }
+}
@@ -0,0 +1,172 @@
+//! The documentation of cmake read from the reStructuredText sources of its
+//! manual, when the installation ships them in `share/cmake-<version>/Help`.
+//!
+//! They hold the whole pages, with their examples, where `--help-*` can leave
+//! parts out. The output of `--help-*` is used when they are missing.
+
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+use super::cmake_docs::{DocKind, page_name};
+use super::cmake_modules::CMAKE_PREFIX;
+
+/// How deep nested `.. include::` are followed, in case pages include each other
+const MAX_INCLUDE_DEPTH: usize = 4;
+
+static INCLUDE_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(r"^(?<indent>\s*)\.\.\s+(include|cmake-module)::\s*(?<path>\S+)\s*$").unwrap()
+});
+
+/// The `Help` directory of the cmake found in `PATH`
+pub static HELP_DIR: LazyLock<Option<PathBuf>> =
+    LazyLock::new(|| help_dir_in(CMAKE_PREFIX.as_deref()?));
+
+/// The `Help` directory of the cmake installed in `prefix`
+fn help_dir_in(prefix: &Path) -> Option<PathBuf> {
+    let pattern = prefix.join("share").join("cmake*").join("Help");
+    glob::glob(pattern.to_str()?)
+        .ok()?
+        .flatten()
+        .find(|dir| dir.join("command").is_dir())
+}
+
+/// The documentation block of a module, between `#[=[.rst:` and `#]=]`
+fn module_rst(source: &str) -> Option<String> {
+    let mut lines = source
+        .lines()
+        .skip_while(|line| !(line.starts_with("#[") && line.contains("[.rst:")));
+    lines.next()?;
+    let block: Vec<&str> = lines.take_while(|line| !line.starts_with("#]")).collect();
+    Some(block.join("\n"))
+}
+
+/// The text of `path`, with its includes expanded
+fn expand(path: &Path, depth: usize) -> Option<String> {
+    let text = std::fs::read_to_string(path).ok()?;
+    let text = if path
+        .extension()
+        .is_some_and(|extension| extension == "cmake")
+    {
+        module_rst(&text)?
+    } else {
+        text
+    };
+    let dir = path.parent()?;
+    let mut output = String::new();
+    for line in text.lines() {
+        if depth < MAX_INCLUDE_DEPTH
+            && let Some(caps) = INCLUDE_REGEX.captures(line)
+            && let Some(included) = expand(&dir.join(&caps["path"]), depth + 1)
+        {
+            for included_line in included.lines() {
+                if !included_line.is_empty() {
+                    output.push_str(&caps["indent"]);
+                    output.push_str(included_line);
+                }
+                output.push('\n');
+            }
+            continue;
+        }
+        output.push_str(line);
+        output.push('\n');
+    }
+    Some(output)
+}
+
+/// The page without its title, its labels, and the parts only shown in html
+fn page_body(text: &str) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut output = String::new();
+    let mut index = 0;
+    let mut title_seen = false;
+    while index < lines.len() {
+        let line = lines[index];
+        let trimmed = line.trim();
+        if !title_seen
+            && !trimmed.is_empty()
+            && lines.get(index + 1).is_some_and(|next| {
+                let next = next.trim();
+                next.len() >= trimmed.len() && next.chars().all(|c| "-=*^~".contains(c))
+            })
+        {
+            title_seen = true;
+            index += 2;
+            continue;
+        }
+        // `.. _label:` targets
+        if trimmed.starts_with(".. _") && trimmed.ends_with(':') {
+            index += 1;
+            continue;
+        }
+        if trimmed.starts_with(".. only:: html") || trimmed.starts_with(".. contents::") {
+            let indent = line.len() - line.trim_start().len();
+            index += 1;
+            while lines.get(index).is_some_and(|next| {
+                next.trim().is_empty() || next.len() - next.trim_start().len() > indent
+            }) {
+                index += 1;
+            }
+            continue;
+        }
+        title_seen |= !trimmed.is_empty();
+        output.push_str(line);
+        output.push('\n');
+        index += 1;
+    }
+    output.trim_start_matches('\n').to_string()
+}
+
+/// The documentation of `name` in the sources of `help_dir`
+fn read_page(help_dir: &Path, kind: DocKind, name: &str) -> Option<String> {
+    let path = help_dir
+        .join(kind.directory())
+        .join(format!("{}.rst", page_name(name)));
+    let text = expand(&path, 0)?;
+    Some(page_body(&text))
+}
+
+/// The reStructuredText documentation of `name`, when the sources are installed
+pub fn read_help(kind: DocKind, name: &str) -> Option<String> {
+    read_page(HELP_DIR.as_deref()?, kind, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_page() {
+        let prefix = tempfile::tempdir().unwrap();
+        let help = prefix.path().join("share/cmake-3.28/Help");
+        std::fs::create_dir_all(help.join("command/include")).unwrap();
+        std::fs::create_dir_all(help.join("module")).unwrap();
+        std::fs::create_dir_all(prefix.path().join("share/cmake-3.28/Modules")).unwrap();
+        std::fs::write(
+            help.join("command/block.rst"),
+            "block\n-----\n\n.. only:: html\n\n   .. contents::\n\nEvaluate a group.\n\n  .. include:: include/NOTE.rst\n",
+        )
+        .unwrap();
+        std::fs::write(help.join("command/include/NOTE.rst"), "A note.\n\nMore.\n").unwrap();
+        std::fs::write(
+            help.join("module/CTest.rst"),
+            ".. cmake-module:: ../../Modules/CTest.cmake\n",
+        )
+        .unwrap();
+        std::fs::write(
+            prefix.path().join("share/cmake-3.28/Modules/CTest.cmake"),
+            "#[=======================================================================[.rst:\nCTest\n-----\n\nConfigure a project for testing.\n#]=======================================================================]\n\ninclude(Foo)\n",
+        )
+        .unwrap();
+
+        assert_eq!(help_dir_in(prefix.path()).as_deref(), Some(help.as_path()));
+        assert_eq!(
+            read_page(&help, DocKind::Command, "block").as_deref(),
+            Some("Evaluate a group.\n\n  A note.\n\n  More.\n")
+        );
+        assert_eq!(
+            read_page(&help, DocKind::Module, "CTest").as_deref(),
+            Some("Configure a project for testing.\n")
+        );
+        assert_eq!(read_page(&help, DocKind::Variable, "CMAKE_FOO"), None);
+    }
+}
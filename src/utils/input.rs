@@ -0,0 +1,73 @@
+//! Validation for untrusted input handed to the parsing entry points.
+//!
+//! The `try_*` functions built on top of this refuse input larger than
+//! [`MAX_INPUT_LEN`] or not utf-8, which makes them usable from fuzz targets
+//! and property tests fed with arbitrary bytes.
+
+/// Upper bound for a single document or help dump, in bytes.
+pub const MAX_INPUT_LEN: usize = 8 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputError {
+    TooLarge { len: usize, max: usize },
+    InvalidUtf8 { valid_up_to: usize },
+}
+
+impl std::fmt::Display for InputError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InputError::TooLarge { len, max } => {
+                write!(f, "input is too large ({len} bytes, max {max})")
+            }
+            InputError::InvalidUtf8 { valid_up_to } => {
+                write!(f, "input is not valid utf-8 after byte {valid_up_to}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for InputError {}
+
+/// Check the size of `input` and decode it as utf-8.
+pub fn decode(input: &[u8]) -> Result<&str, InputError> {
+    if input.len() > MAX_INPUT_LEN {
+        return Err(InputError::TooLarge {
+            len: input.len(),
+            max: MAX_INPUT_LEN,
+        });
+    }
+    std::str::from_utf8(input).map_err(|err| InputError::InvalidUtf8 {
+        valid_up_to: err.valid_up_to(),
+    })
+}
+
+/// `count` pseudo-random inputs of `len` bytes of `alphabet`, the same ones
+/// for the same `seed`, for the tests of the `try_*` functions
+#[cfg(test)]
+pub fn arbitrary_inputs(mut seed: u32, alphabet: &[u8], count: usize, len: usize) -> Vec<Vec<u8>> {
+    let mut next = || {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        alphabet[seed as usize % alphabet.len()]
+    };
+    (0..count)
+        .map(|_| (0..len).map(|_| next()).collect())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode(b"set(A 1)"), Ok("set(A 1)"));
+        assert_eq!(
+            decode(b"set(\xff)"),
+            Err(InputError::InvalidUtf8 { valid_up_to: 4 })
+        );
+        let large = vec![b'a'; MAX_INPUT_LEN + 1];
+        assert!(matches!(decode(&large), Err(InputError::TooLarge { .. })));
+    }
+}
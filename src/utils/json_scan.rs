@@ -0,0 +1,217 @@
+//! A scan of the string values of JSON documents, with their keys and offsets.
+//!
+//! The files of the presets and of the vcpkg manifest are read while they are
+//! being written, so they are rarely valid JSON. The scan goes on whatever it
+//! meets, which is enough to know where the cursor is and which names the
+//! document declares.
+
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range};
+
+/// A string value of the document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry<'a> {
+    /// The keys of the objects and arrays around the entry
+    pub parents: Vec<&'a str>,
+    /// The key of the value, or of the array the value is an element of
+    pub key: &'a str,
+    pub value: &'a str,
+    /// The byte offset of the value, after its quote
+    pub offset: usize,
+}
+
+/// The string the scan ends in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Typed<'a> {
+    /// The key of an object
+    Key {
+        parents: Vec<&'a str>,
+        offset: usize,
+    },
+    Value(Entry<'a>),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Container<'a> {
+    array: bool,
+    key: Option<&'a str>,
+}
+
+fn parents<'a>(containers: &[Container<'a>]) -> Vec<&'a str> {
+    containers
+        .iter()
+        .filter_map(|container| container.key)
+        .collect()
+}
+
+/// The string values of `source`, and the string it ends in, if any.
+/// The files being written are not valid JSON, so the scan goes on whatever it
+/// meets, and a string ends with its line
+pub fn scan(source: &str) -> (Vec<Entry<'_>>, Option<Typed<'_>>) {
+    let bytes = source.as_bytes();
+    let mut containers: Vec<Container> = vec![];
+    let mut pending_key = None;
+    let mut entries = vec![];
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'"' => {
+                let start = index + 1;
+                let mut end = start;
+                while end < bytes.len() && !matches!(bytes[end], b'"' | b'\n') {
+                    end += if bytes[end] == b'\\' { 2 } else { 1 };
+                }
+                let end = end.min(bytes.len());
+                let in_object = containers.last().is_some_and(|container| !container.array);
+                let is_key = in_object && pending_key.is_none();
+                if end == bytes.len() {
+                    let typed = if is_key {
+                        Some(Typed::Key {
+                            parents: parents(&containers),
+                            offset: start,
+                        })
+                    } else {
+                        value_entry(&containers, pending_key, &source[start..], start)
+                            .map(Typed::Value)
+                    };
+                    return (entries, typed);
+                }
+                let text = &source[start..end];
+                index = end;
+                if is_key {
+                    if source[end + 1..].trim_start().starts_with(':') {
+                        pending_key = Some(text);
+                    }
+                } else {
+                    entries.extend(value_entry(&containers, pending_key.take(), text, start));
+                }
+            }
+            b'{' | b'[' => containers.push(Container {
+                array: bytes[index] == b'[',
+                key: pending_key.take(),
+            }),
+            b'}' | b']' => {
+                containers.pop();
+                pending_key = None;
+            }
+            b',' => pending_key = None,
+            _ => {}
+        }
+        index += 1;
+    }
+    (entries, None)
+}
+
+fn value_entry<'a>(
+    containers: &[Container<'a>],
+    pending_key: Option<&'a str>,
+    value: &'a str,
+    offset: usize,
+) -> Option<Entry<'a>> {
+    let (around, key) = match containers.split_last()? {
+        (array, around) if array.array => (around, array.key?),
+        _ => (containers, pending_key?),
+    };
+    Some(Entry {
+        parents: parents(around),
+        key,
+        value,
+        offset,
+    })
+}
+
+/// The byte offset of `location`, whose character counts UTF-16 code units
+/// as the editors do
+pub fn offset_of(source: &str, location: Position) -> Option<usize> {
+    let mut offset = 0;
+    for (row, line) in source.split('\n').enumerate() {
+        if row == location.line as usize {
+            let mut units = 0;
+            for (index, c) in line.char_indices() {
+                if units >= location.character as usize {
+                    return Some(offset + index);
+                }
+                units += c.len_utf16();
+            }
+            return (units >= location.character as usize).then_some(offset + line.len());
+        }
+        offset += line.len() + 1;
+    }
+    None
+}
+
+/// The position of the byte `offset`, in UTF-16 code units
+pub fn position_of(source: &str, offset: usize) -> Position {
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    Position::new(
+        before.matches('\n').count() as u32,
+        before[line_start..].encode_utf16().count() as u32,
+    )
+}
+
+/// The JSON of `source`, or the diagnostic of its syntax error
+pub fn parse(source: &str) -> Result<serde_json::Value, Box<Diagnostic>> {
+    serde_json::from_str(source).map_err(|err| {
+        let position = Position::new(
+            err.line().saturating_sub(1) as u32,
+            err.column().saturating_sub(1) as u32,
+        );
+        Box::new(Diagnostic {
+            range: Range::new(position, position),
+            severity: Some(DiagnosticSeverity::ERROR),
+            source: Some("neocmakelsp".to_string()),
+            message: format!("Invalid JSON: {err}"),
+            ..Default::default()
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan() {
+        let source = r#"{ "a": "x", "b": { "c": ["y", "z"] }, "d": 1, "e": "w"#;
+        let (entries, typed) = scan(source);
+        let values: Vec<(&[&str], &str, &str)> = entries
+            .iter()
+            .map(|entry| (entry.parents.as_slice(), entry.key, entry.value))
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                (&[][..], "a", "x"),
+                (&["b"][..], "c", "y"),
+                (&["b"][..], "c", "z"),
+            ]
+        );
+        assert_eq!(entries[0].offset, 8);
+        let Some(Typed::Value(entry)) = typed else {
+            panic!("no value typed");
+        };
+        assert_eq!((entry.key, entry.value), ("e", "w"));
+        assert_eq!(position_of(source, entry.offset), Position::new(0, 52));
+        assert_eq!(offset_of("{\n  \"a\"", Position::new(1, 3)), Some(5));
+        assert_eq!(offset_of("{\n", Position::new(1, 1)), None);
+    }
+
+    #[test]
+    fn test_non_ascii_positions() {
+        let source = "{ \"é\": \"😀\", \"b\": \"x";
+        let (entries, typed) = scan(source);
+        assert_eq!(entries[0].value, "😀");
+        assert_eq!(position_of(source, entries[0].offset), Position::new(0, 8));
+        let Some(Typed::Value(entry)) = typed else {
+            panic!("no value typed");
+        };
+        assert_eq!(position_of(source, entry.offset), Position::new(0, 19));
+        assert_eq!(offset_of(source, Position::new(0, 19)), Some(entry.offset));
+        assert_eq!(
+            offset_of(source, Position::new(0, 11)),
+            Some(source.find(',').unwrap())
+        );
+        assert_eq!(offset_of(source, Position::new(0, 20)), Some(source.len()));
+        assert_eq!(offset_of(source, Position::new(0, 21)), None);
+    }
+}
@@ -0,0 +1,217 @@
+//! Static tracking of the languages a project enables.
+//!
+//! While indexing, the `LANGUAGES` of `project()` and the `enable_language()`
+//! calls are collected per file, so the source files offered by completion
+//! are only the ones of a language cmake would compile.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, PoisonError, RwLock};
+
+use tree_sitter::Node;
+
+use crate::CMakeNodeKinds;
+
+/// Key is the file enabling the languages
+static LANGUAGES: LazyLock<RwLock<BTreeMap<PathBuf, Vec<String>>>> =
+    LazyLock::new(|| RwLock::new(BTreeMap::new()));
+
+/// The languages of `project()` without `LANGUAGES`
+pub const DEFAULT_LANGUAGES: &[&str] = &["C", "CXX"];
+
+/// The other keywords of `project()`, each followed by its value
+const PROJECT_KEYWORDS: &[&str] = &["VERSION", "DESCRIPTION", "HOMEPAGE_URL"];
+
+/// The extensions of the sources and headers of each language
+const LANGUAGE_EXTENSIONS: &[(&str, &[&str])] = &[
+    ("C", &["c", "h"]),
+    (
+        "CXX",
+        &[
+            "cc", "cpp", "cxx", "c++", "C", "ixx", "cppm", "h", "hh", "hpp", "hxx", "h++",
+        ],
+    ),
+    ("OBJC", &["m", "h"]),
+    ("OBJCXX", &["mm", "h", "hh", "hpp"]),
+    ("CUDA", &["cu", "cuh"]),
+    ("HIP", &["hip"]),
+    ("ASM", &["s", "S", "asm"]),
+    ("ASM_NASM", &["asm", "nasm"]),
+    ("ASM_MASM", &["asm"]),
+    ("ASM-ATT", &["s"]),
+    (
+        "Fortran",
+        &[
+            "f", "F", "for", "FOR", "f77", "F77", "f90", "F90", "f95", "F95", "f03", "F03", "f08",
+            "F08", "fpp", "FPP",
+        ],
+    ),
+    // Enabled by C and C++ on Windows, for the resource files
+    ("RC", &["rc"]),
+    ("ISPC", &["ispc"]),
+    ("Swift", &["swift"]),
+    ("CSharp", &["cs"]),
+    ("Java", &["java"]),
+];
+
+fn text<'a>(node: Node, source: &[&'a str]) -> Option<&'a str> {
+    let row = node.start_position().row;
+    if row != node.end_position().row {
+        return None;
+    }
+    source[row].get(node.start_position().column..node.end_position().column)
+}
+
+fn arguments<'a>(command: Node, source: &[&'a str]) -> Vec<&'a str> {
+    let Some(argument_list) = command
+        .child(2)
+        .filter(|node| node.kind() == CMakeNodeKinds::ARGUMENT_LIST)
+    else {
+        return vec![];
+    };
+    let mut walk = argument_list.walk();
+    argument_list
+        .children(&mut walk)
+        .filter(|node| node.kind() == CMakeNodeKinds::ARGUMENT)
+        .filter_map(|node| text(node, source))
+        .map(|text| text.trim_matches('"'))
+        .collect()
+}
+
+/// The languages of `project(<name> [LANGUAGES] <lang>...)`
+fn project_languages(arguments: &[&str]) -> Vec<String> {
+    let mut languages = vec![];
+    let mut in_languages = true;
+    for argument in arguments.iter().skip(1) {
+        if *argument == "LANGUAGES" {
+            in_languages = true;
+        } else if PROJECT_KEYWORDS.contains(argument) {
+            in_languages = false;
+        } else if in_languages && !argument.contains("${") {
+            languages.push(argument.to_string());
+        }
+    }
+    if languages.is_empty() {
+        return DEFAULT_LANGUAGES
+            .iter()
+            .map(|lang| lang.to_string())
+            .collect();
+    }
+    languages.retain(|lang| lang != "NONE");
+    languages
+}
+
+fn collect_languages(source: &[&str], node: Node, output: &mut Vec<String>) {
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if child.kind() != CMakeNodeKinds::NORMAL_COMMAND {
+            collect_languages(source, child, output);
+            continue;
+        }
+        let Some(name) = child.child(0).and_then(|ids| text(ids, source)) else {
+            continue;
+        };
+        let arguments = arguments(child, source);
+        let languages = match name.to_lowercase().as_str() {
+            "project" => project_languages(&arguments),
+            "enable_language" => arguments
+                .iter()
+                .filter(|arg| **arg != "OPTIONAL" && !arg.contains("${"))
+                .map(|arg| arg.to_string())
+                .collect(),
+            _ => continue,
+        };
+        for language in languages {
+            if !output.contains(&language) {
+                output.push(language);
+            }
+        }
+    }
+}
+
+/// The languages enabled by `source`
+pub fn languages_of(source: &[&str], root: Node) -> Vec<String> {
+    let mut languages = vec![];
+    collect_languages(source, root, &mut languages);
+    languages
+}
+
+/// Record the languages enabled by `local_path`
+pub fn update_languages<P: AsRef<Path>>(local_path: P, source: &[&str], root: Node) {
+    let languages = languages_of(source, root);
+    let mut tracked = LANGUAGES.write().unwrap_or_else(PoisonError::into_inner);
+    if languages.is_empty() {
+        tracked.remove(local_path.as_ref());
+    } else {
+        tracked.insert(local_path.as_ref().to_path_buf(), languages);
+    }
+}
+
+/// The languages enabled by the project, `None` before it is indexed
+pub fn enabled_languages() -> Option<Vec<String>> {
+    let tracked = LANGUAGES.read().unwrap_or_else(PoisonError::into_inner);
+    let mut languages: Vec<String> = vec![];
+    for language in tracked.values().flatten() {
+        if !languages.contains(language) {
+            languages.push(language.clone());
+        }
+    }
+    (!languages.is_empty()).then_some(languages)
+}
+
+/// The languages cmake knows of
+pub fn known_languages() -> impl Iterator<Item = &'static str> {
+    LANGUAGE_EXTENSIONS.iter().map(|(language, _)| *language)
+}
+
+/// The extensions of the source files of `languages`
+pub fn source_extensions(languages: &[String]) -> Vec<String> {
+    let mut extensions: Vec<String> = vec![];
+    let resources = languages.iter().any(|lang| lang == "C" || lang == "CXX");
+    for (language, language_extensions) in LANGUAGE_EXTENSIONS {
+        if !(languages.iter().any(|lang| lang == language) || (resources && *language == "RC")) {
+            continue;
+        }
+        for extension in *language_extensions {
+            if !extensions.iter().any(|known| known == extension) {
+                extensions.push(extension.to_string());
+            }
+        }
+    }
+    extensions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    fn languages(source: &str) -> Vec<String> {
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        languages_of(&lines, thetree.root_node())
+    }
+
+    #[test]
+    fn test_languages() {
+        assert_eq!(languages("project(demo)"), vec!["C", "CXX"]);
+        assert_eq!(
+            languages(
+                "project(demo VERSION 1.0 LANGUAGES CXX)\nif(GPU)\n  enable_language(CUDA)\nendif()"
+            ),
+            vec!["CXX", "CUDA"]
+        );
+        assert_eq!(languages("project(demo Fortran)"), vec!["Fortran"]);
+        assert!(languages("project(demo NONE)").is_empty());
+
+        let extensions = source_extensions(&["CXX".to_string()]);
+        assert!(extensions.contains(&"cpp".to_string()));
+        assert!(extensions.contains(&"rc".to_string()));
+        assert!(!extensions.contains(&"f90".to_string()));
+        assert!(!extensions.contains(&"cu".to_string()));
+        let extensions = source_extensions(&["CUDA".to_string()]);
+        assert!(extensions.contains(&"cu".to_string()));
+    }
+}
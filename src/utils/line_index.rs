@@ -0,0 +1,61 @@
+//! The offsets of the lines of a document, computed when it changes.
+//!
+//! The requests only need the line under the cursor. Splitting the whole
+//! document again on each completion or signature help is slow on large
+//! files, the index finds a line without reading the ones before.
+
+/// The start offset of each line of a text
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineIndex {
+    starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(text: &str) -> Self {
+        if text.is_empty() {
+            return Self::default();
+        }
+        let mut starts = vec![0];
+        starts.extend(
+            text.match_indices('\n')
+                .map(|(offset, _)| offset + 1)
+                .filter(|start| *start < text.len()),
+        );
+        Self { starts }
+    }
+
+    /// The number of lines, as counted by [`str::lines`]
+    pub fn line_count(&self) -> usize {
+        self.starts.len()
+    }
+
+    /// The line `row` of `text`, the text the index was computed from, without
+    /// its line ending like with [`str::lines`]
+    pub fn line<'a>(&self, text: &'a str, row: usize) -> Option<&'a str> {
+        let start = *self.starts.get(row)?;
+        let end = self.starts.get(row + 1).map_or(text.len(), |next| *next);
+        let line = text.get(start..end)?;
+        let Some(line) = line.strip_suffix('\n') else {
+            return Some(line);
+        };
+        Some(line.strip_suffix('\r').unwrap_or(line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_index() {
+        for text in ["", "\n", "a", "a\n", "a\r\nbc\n\nd", "a\n\n", "é\nà\r\n"] {
+            let index = LineIndex::new(text);
+            let lines: Vec<&str> = text.lines().collect();
+            assert_eq!(index.line_count(), lines.len(), "{text:?}");
+            for (row, line) in lines.iter().enumerate() {
+                assert_eq!(index.line(text, row), Some(*line), "{text:?}");
+            }
+            assert_eq!(index.line(text, lines.len()), None);
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! The variables of the `foreach()` loops.
+//!
+//! cmake sets them in the body of the loop only: once the loop ends, they get
+//! back the value they had before it, which is usually none.
+
+use tree_sitter::Node;
+
+use crate::CMakeNodeKinds;
+
+fn text<'a>(node: Node, source: &[&'a str]) -> Option<&'a str> {
+    let row = node.start_position().row;
+    if row != node.end_position().row {
+        return None;
+    }
+    source[row].get(node.start_position().column..node.end_position().column)
+}
+
+fn child_of_kind<'a>(node: Node<'a>, kind: &str) -> Option<Node<'a>> {
+    let mut cursor = node.walk();
+    node.children(&mut cursor)
+        .find(|child| child.kind() == kind)
+}
+
+/// The variables set by `foreach_loop`: the names before `IN`, or the first
+/// argument, and `<var>_<N>` for each list of `IN ZIP_LISTS` when it is given
+/// a single name
+pub fn loop_variables(foreach_loop: Node, source: &[&str]) -> Vec<String> {
+    let Some(arguments) = child_of_kind(foreach_loop, CMakeNodeKinds::FOREACH_COMMAND)
+        .and_then(|command| child_of_kind(command, CMakeNodeKinds::ARGUMENT_LIST))
+    else {
+        return vec![];
+    };
+    let mut walk = arguments.walk();
+    let arguments: Vec<&str> = arguments
+        .children(&mut walk)
+        .filter(|node| node.kind() == CMakeNodeKinds::ARGUMENT)
+        .filter_map(|node| text(node, source))
+        .collect();
+    let Some(keyword) = arguments.iter().position(|argument| *argument == "IN") else {
+        return arguments
+            .first()
+            .filter(|name| !name.contains("${"))
+            .map(|name| vec![name.to_string()])
+            .unwrap_or_default();
+    };
+    let names: Vec<&str> = arguments[..keyword]
+        .iter()
+        .copied()
+        .filter(|name| !name.contains("${"))
+        .collect();
+    if let [name] = names.as_slice()
+        && arguments.get(keyword + 1) == Some(&"ZIP_LISTS")
+    {
+        return (0..arguments.len() - keyword - 2)
+            .map(|index| format!("{name}_{index}"))
+            .collect();
+    }
+    names.into_iter().map(str::to_string).collect()
+}
+
+/// The body of `foreach_loop`, where its variables are set
+pub fn loop_body(foreach_loop: Node) -> Option<Node> {
+    child_of_kind(foreach_loop, CMakeNodeKinds::BODY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    fn variables(source: &str) -> Vec<String> {
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let foreach_loop = thetree.root_node().child(0).unwrap();
+        loop_variables(foreach_loop, &lines)
+    }
+
+    #[test]
+    fn test_loop_variables() {
+        assert_eq!(variables("foreach(item a b c)\nendforeach()"), vec!["item"]);
+        assert_eq!(
+            variables("foreach(source IN LISTS SOURCES ITEMS main.c)\nendforeach()"),
+            vec!["source"]
+        );
+        assert_eq!(
+            variables("foreach(name path IN ZIP_LISTS NAMES PATHS)\nendforeach()"),
+            vec!["name", "path"]
+        );
+        assert_eq!(
+            variables("foreach(pair IN ZIP_LISTS NAMES PATHS)\nendforeach()"),
+            vec!["pair_0", "pair_1"]
+        );
+    }
+}
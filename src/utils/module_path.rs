@@ -0,0 +1,316 @@
+//! Static tracking of `CMAKE_MODULE_PATH`.
+//!
+//! While indexing, `list(APPEND CMAKE_MODULE_PATH ...)`, `list(PREPEND ...)` and
+//! `set(CMAKE_MODULE_PATH ...)` are collected per file, so `include(Foo)` and
+//! `find_package(Foo MODULE)` can be resolved against the project's own modules
+//! before falling back to the ones shipped with CMake.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, PoisonError, RwLock};
+
+use tree_sitter::Node;
+
+use super::replace_placeholders;
+use crate::CMakeNodeKinds;
+
+/// Key is the file doing the assignment, value is the directories it adds
+static MODULE_PATHS: LazyLock<RwLock<BTreeMap<PathBuf, Vec<PathBuf>>>> =
+    LazyLock::new(|| RwLock::new(BTreeMap::new()));
+
+/// The top level source directory, used for `${CMAKE_SOURCE_DIR}`
+static SOURCE_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+pub fn set_source_dir<P: AsRef<Path>>(dir: P) {
+    *SOURCE_DIR.write().unwrap_or_else(PoisonError::into_inner) = Some(dir.as_ref().to_path_buf());
+}
+
+/// The top level source directory, once the project has been scanned
+pub fn source_dir() -> Option<PathBuf> {
+    SOURCE_DIR
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+fn resolve_entry(entry: &str, current_dir: &Path, source_dir: Option<&Path>) -> Option<PathBuf> {
+    let current = current_dir.to_str()?;
+    let mut entry = entry
+        .replace("${CMAKE_CURRENT_SOURCE_DIR}", current)
+        .replace("${CMAKE_CURRENT_LIST_DIR}", current);
+    if let Some(source_dir) = source_dir.and_then(Path::to_str) {
+        entry = entry
+            .replace("${CMAKE_SOURCE_DIR}", source_dir)
+            .replace("${PROJECT_SOURCE_DIR}", source_dir);
+    }
+    let entry = replace_placeholders(&entry)?;
+    if entry.is_empty() || entry.contains("${") {
+        return None;
+    }
+    let entry = PathBuf::from(entry);
+    if entry.is_absolute() {
+        Some(entry)
+    } else {
+        Some(current_dir.join(entry))
+    }
+}
+
+/// Resolve a path argument of a command written in a file of `current_dir`
+pub fn resolve_path(argument: &str, current_dir: &Path) -> Option<PathBuf> {
+    let source_dir = SOURCE_DIR
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+    resolve_entry(
+        argument.trim_matches('"'),
+        current_dir,
+        source_dir.as_deref(),
+    )
+}
+
+fn collect_entries(
+    source: &[&str],
+    node: Node,
+    current_dir: &Path,
+    source_dir: Option<&Path>,
+    entries: &mut Vec<PathBuf>,
+) {
+    let mut course = node.walk();
+    for child in node.children(&mut course) {
+        match child.kind() {
+            CMakeNodeKinds::NORMAL_COMMAND => {
+                let Some(ids) = child.child(0) else {
+                    continue;
+                };
+                let h = ids.start_position().row;
+                let Some(command_name) = source
+                    .get(h)
+                    .and_then(|line| {
+                        line.get(ids.start_position().column..ids.end_position().column)
+                    })
+                    .map(str::to_lowercase)
+                else {
+                    continue;
+                };
+                let Some(argument_list) = child.child(2) else {
+                    continue;
+                };
+                if argument_list.kind() != CMakeNodeKinds::ARGUMENT_LIST {
+                    continue;
+                }
+                let mut walk = argument_list.walk();
+                let arguments: Vec<&str> = argument_list
+                    .children(&mut walk)
+                    .filter(|argument| {
+                        argument.kind() == CMakeNodeKinds::ARGUMENT
+                            && argument.start_position().row == argument.end_position().row
+                    })
+                    .filter_map(|argument| {
+                        let h = argument.start_position().row;
+                        source
+                            .get(h)?
+                            .get(argument.start_position().column..argument.end_position().column)
+                    })
+                    .collect();
+                let values = match (command_name.as_str(), arguments.as_slice()) {
+                    ("list", [operation, "CMAKE_MODULE_PATH", values @ ..])
+                        if *operation == "APPEND" || *operation == "PREPEND" =>
+                    {
+                        values
+                    }
+                    ("set", ["CMAKE_MODULE_PATH", values @ ..]) => values,
+                    _ => continue,
+                };
+                entries.extend(
+                    values
+                        .iter()
+                        .flat_map(|value| value.trim_matches('"').split(';'))
+                        .filter(|value| *value != "${CMAKE_MODULE_PATH}")
+                        .filter_map(|value| resolve_entry(value, current_dir, source_dir)),
+                );
+            }
+            CMakeNodeKinds::IF_CONDITION
+            | CMakeNodeKinds::FOREACH_LOOP
+            | CMakeNodeKinds::WHILE_LOOP
+            | CMakeNodeKinds::BLOCK_DEF
+            | CMakeNodeKinds::BODY => {
+                collect_entries(source, child, current_dir, source_dir, entries);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The directories `local_path` adds to `CMAKE_MODULE_PATH`
+pub fn module_path_entries<P: AsRef<Path>>(
+    local_path: P,
+    source: &[&str],
+    root: Node,
+    source_dir: Option<&Path>,
+) -> Vec<PathBuf> {
+    let Some(current_dir) = local_path.as_ref().parent() else {
+        return Vec::new();
+    };
+    let mut entries = Vec::new();
+    collect_entries(source, root, current_dir, source_dir, &mut entries);
+    entries.dedup();
+    entries
+}
+
+/// Record the `CMAKE_MODULE_PATH` assignments of `local_path`
+pub fn update_module_paths<P: AsRef<Path>>(local_path: P, source: &[&str], root: Node) {
+    let source_dir = SOURCE_DIR
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+    let entries = module_path_entries(local_path.as_ref(), source, root, source_dir.as_deref());
+    let mut paths = MODULE_PATHS.write().unwrap_or_else(PoisonError::into_inner);
+    if entries.is_empty() {
+        paths.remove(local_path.as_ref());
+    } else {
+        paths.insert(local_path.as_ref().to_path_buf(), entries);
+    }
+}
+
+/// All the tracked module directories, without duplicates
+pub fn module_paths() -> Vec<PathBuf> {
+    let mut result: Vec<PathBuf> = Vec::new();
+    for entry in MODULE_PATHS
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .values()
+        .flatten()
+    {
+        if !result.contains(entry) {
+            result.push(entry.clone());
+        }
+    }
+    result
+}
+
+fn find_in(dirs: &[PathBuf], file_name: &str) -> Option<PathBuf> {
+    dirs.iter()
+        .map(|dir| dir.join(file_name))
+        .find(|path| path.is_file())
+}
+
+/// Resolve `include(<name>)` against the tracked module directories
+pub fn find_project_module(name: &str) -> Option<PathBuf> {
+    find_in(&module_paths(), &format!("{name}.cmake"))
+}
+
+/// Resolve `find_package(<name> MODULE)` against the tracked module directories
+pub fn find_project_package(name: &str) -> Option<PathBuf> {
+    find_in(&module_paths(), &format!("Find{name}.cmake"))
+}
+
+fn list_modules(prefix: &str) -> Vec<(String, PathBuf)> {
+    let mut modules: Vec<(String, PathBuf)> = Vec::new();
+    for dir in module_paths() {
+        let Ok(read_dir) = std::fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in read_dir.flatten() {
+            let path = entry.path();
+            let Some(name) = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix(prefix))
+                .and_then(|name| name.strip_suffix(".cmake"))
+            else {
+                continue;
+            };
+            if !name.is_empty() && !modules.iter().any(|(module, _)| module == name) {
+                modules.push((name.to_string(), path.clone()));
+            }
+        }
+    }
+    modules
+}
+
+/// Modules in the tracked directories, usable with `include(<name>)`
+pub fn project_modules() -> Vec<(String, PathBuf)> {
+    list_modules("")
+}
+
+/// Packages provided by `Find<name>.cmake` files in the tracked directories
+pub fn project_packages() -> Vec<(String, PathBuf)> {
+    list_modules("Find")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    #[test]
+    fn test_module_path_entries() {
+        let source = r#"list(APPEND CMAKE_MODULE_PATH "${CMAKE_CURRENT_SOURCE_DIR}/cmake")
+if(WIN32)
+    list(PREPEND CMAKE_MODULE_PATH ${CMAKE_SOURCE_DIR}/cmake/win32 extra)
+endif()
+set(CMAKE_MODULE_PATH "${CMAKE_MODULE_PATH};/opt/sdk/cmake")
+list(APPEND OTHER_PATH ignored)
+list(APPEND CMAKE_MODULE_PATH ${UNKNOWN_VARIABLE_FOR_TEST}/cmake)
+"#;
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let entries = module_path_entries(
+            "/project/sub/CMakeLists.txt",
+            &lines,
+            thetree.root_node(),
+            Some(Path::new("/project")),
+        );
+        assert_eq!(
+            entries,
+            vec![
+                PathBuf::from("/project/sub/cmake"),
+                PathBuf::from("/project/cmake/win32"),
+                PathBuf::from("/project/sub/extra"),
+                PathBuf::from("/opt/sdk/cmake"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_project_module() {
+        let dir = tempfile::tempdir().unwrap();
+        let cmake_dir = dir.path().join("cmake");
+        std::fs::create_dir(&cmake_dir).unwrap();
+        std::fs::write(cmake_dir.join("ProjectWarnings.cmake"), "").unwrap();
+        std::fs::write(cmake_dir.join("FindProjectSdk.cmake"), "").unwrap();
+
+        let source = "list(APPEND CMAKE_MODULE_PATH ${CMAKE_CURRENT_LIST_DIR}/cmake)\n";
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let local_path = dir.path().join("CMakeLists.txt");
+        update_module_paths(&local_path, &lines, thetree.root_node());
+
+        assert_eq!(
+            find_project_module("ProjectWarnings"),
+            Some(cmake_dir.join("ProjectWarnings.cmake"))
+        );
+        assert_eq!(
+            find_project_package("ProjectSdk"),
+            Some(cmake_dir.join("FindProjectSdk.cmake"))
+        );
+        assert!(
+            project_packages()
+                .iter()
+                .any(|(name, _)| name == "ProjectSdk")
+        );
+        assert!(
+            project_modules()
+                .iter()
+                .any(|(name, _)| name == "ProjectWarnings")
+        );
+
+        let empty = parse.parse("", None).unwrap();
+        update_module_paths(&local_path, &[], empty.root_node());
+        assert_eq!(find_project_module("ProjectWarnings"), None);
+    }
+}
@@ -0,0 +1,143 @@
+//! Static tracking of the names the project references, to know which of the
+//! packages it finds are used.
+//!
+//! While indexing, the words of each file are collected in lowercase, so a
+//! package is used when a file references one of its imported targets, like
+//! `Foo::Foo`, `foo` or `foo-core`, one of its variables, like
+//! `${Foo_LIBRARIES}` or `Foo_FOUND`, or one of its commands, like
+//! `foo_add_thing()`. The version in the name of a package, like in `glfw3`,
+//! is left out of its targets. The names in the `find_package()` calls
+//! themselves are not words of the file.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, PoisonError, RwLock};
+
+/// Key is the indexed file, value is its words
+static WORDS: LazyLock<RwLock<BTreeMap<PathBuf, BTreeSet<String>>>> =
+    LazyLock::new(|| RwLock::new(BTreeMap::new()));
+
+static WORD_REGEX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"[A-Za-z0-9_:]+").unwrap());
+
+static FIND_PACKAGE_REGEX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"(?i)\bfind_package\s*\([^)]*\)").unwrap());
+
+/// The packages used by names without their prefix
+const OTHER_USES: &[(&str, &[&str])] = &[
+    (
+        "pkgconfig",
+        &[
+            "pkg_check_modules",
+            "pkg_search_module",
+            "pkg_get_variable",
+            "pkg_config_",
+        ],
+    ),
+    (
+        "threads",
+        &[
+            "cmake_thread_libs_init",
+            "cmake_use_pthreads_init",
+            "cmake_use_win32_threads_init",
+        ],
+    ),
+    ("qt5", &["qt_"]),
+    ("qt6", &["qt_"]),
+    ("catch2", &["catch_discover_tests"]),
+    ("pythoninterp", &["python_"]),
+    ("pythonlibs", &["python_"]),
+    ("cudatoolkit", &["cuda::"]),
+    ("ecm", &["kde"]),
+];
+
+/// The words of `source`, in lowercase, leaving out the comment lines and the
+/// `find_package()` calls
+pub fn words_of(source: &str) -> BTreeSet<String> {
+    let code: Vec<&str> = source
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .collect();
+    let code = code.join("\n");
+    let code = FIND_PACKAGE_REGEX.replace_all(&code, "");
+    WORD_REGEX
+        .find_iter(&code)
+        .map(|word| word.as_str().to_lowercase())
+        .collect()
+}
+
+/// Record the words of `local_path`
+pub fn update_words<P: AsRef<Path>>(local_path: P, source: &str) {
+    WORDS
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(local_path.as_ref().to_path_buf(), words_of(source));
+}
+
+/// Whether one of `words` references the package `name`
+pub fn uses_package(words: &BTreeSet<String>, name: &str) -> bool {
+    let name = name.to_lowercase();
+    let unversioned = name.trim_end_matches(|c: char| c.is_ascii_digit());
+    let mut targets = vec![name.as_str()];
+    if !unversioned.is_empty() && unversioned != name {
+        targets.push(unversioned);
+    }
+    let mut prefixes = vec![format!("{name}::"), format!("{name}_")];
+    if let Some((_, others)) = OTHER_USES.iter().find(|(package, _)| *package == name) {
+        prefixes.extend(others.iter().map(|other| other.to_string()));
+    }
+    words.iter().any(|word| {
+        targets.contains(&word.as_str())
+            || prefixes
+                .iter()
+                .any(|prefix| word.starts_with(prefix.as_str()))
+    })
+}
+
+/// Whether an indexed file other than `local_path` references the package
+/// `name`, `None` before the project is indexed
+pub fn used_elsewhere(local_path: &Path, name: &str) -> Option<bool> {
+    let words = WORDS.read().unwrap_or_else(PoisonError::into_inner);
+    if words.is_empty() {
+        return None;
+    }
+    Some(
+        words
+            .iter()
+            .filter(|(path, _)| path.as_path() != local_path)
+            .any(|(_, words)| uses_package(words, name)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uses_package() {
+        let words = words_of(
+            "find_package(Threads)\nfind_package(PkgConfig)\n# target_link_libraries(app ZLIB::ZLIB)\npkg_check_modules(GLIB glib-2.0)\nif(Foo_FOUND)\nendif()\n",
+        );
+        assert!(uses_package(&words, "Foo"));
+        assert!(uses_package(&words, "PkgConfig"));
+        assert!(!uses_package(&words, "Threads"));
+        assert!(!uses_package(&words, "ZLIB"));
+        assert!(uses_package(
+            &words_of("target_link_libraries(app Threads::Threads)"),
+            "Threads"
+        ));
+
+        // The targets not named after the package
+        let words = words_of(
+            "find_package(glfw3 3.3 REQUIRED)\nfind_package(SFML 2.6 COMPONENTS graphics)\nfind_package(Eigen3)\ntarget_link_libraries(app glfw sfml-graphics)\n",
+        );
+        assert!(uses_package(&words, "glfw3"));
+        assert!(uses_package(&words, "SFML"));
+        assert!(!uses_package(&words, "Eigen3"));
+        assert!(uses_package(
+            &words_of("target_link_libraries(app SFML::Graphics)"),
+            "SFML"
+        ));
+        assert!(!uses_package(&words_of("find_package(ZLIB)"), "ZLIB"));
+    }
+}
@@ -0,0 +1,261 @@
+//! Model of `cmake_parse_arguments` calls inside function and macro bodies.
+//!
+//! A function doing
+//!
+//! ```cmake
+//! function(my_add NAME)
+//!     cmake_parse_arguments(ARG "STATIC" "DESTINATION" "SOURCES" ${ARGN})
+//! endfunction()
+//! ```
+//!
+//! accepts the keywords `STATIC`, `DESTINATION` and `SOURCES` at call sites,
+//! and defines `ARG_STATIC`, `ARG_DESTINATION`, `ARG_SOURCES`, ... in its body.
+
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex, PoisonError};
+
+use tree_sitter::Node;
+
+use crate::CMakeNodeKinds;
+
+/// Keywords of the functions seen while indexing, keyed by lowercase name.
+pub static FUNCTION_KEYWORDS: LazyLock<Mutex<HashMap<String, ParsedArguments>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParsedArguments {
+    pub prefix: String,
+    pub options: Vec<String>,
+    pub one_value_keywords: Vec<String>,
+    pub multi_value_keywords: Vec<String>,
+}
+
+impl ParsedArguments {
+    pub fn keywords(&self) -> impl Iterator<Item = &String> {
+        self.options
+            .iter()
+            .chain(self.one_value_keywords.iter())
+            .chain(self.multi_value_keywords.iter())
+    }
+
+    /// The variables `cmake_parse_arguments` defines in the function body.
+    pub fn variables(&self) -> Vec<String> {
+        self.keywords()
+            .map(String::as_str)
+            .chain(["UNPARSED_ARGUMENTS", "KEYWORDS_MISSING_VALUES"])
+            .map(|keyword| format!("{}_{keyword}", self.prefix))
+            .collect()
+    }
+}
+
+pub(super) fn node_text(source: &[&str], node: Node) -> Option<String> {
+    let start = node.start_position();
+    let end = node.end_position();
+    if start.row == end.row {
+        return source
+            .get(start.row)?
+            .get(start.column..end.column)
+            .map(str::to_string);
+    }
+    let mut lines = vec![source.get(start.row)?.get(start.column..)?];
+    lines.extend_from_slice(source.get(start.row + 1..end.row)?);
+    lines.push(source.get(end.row)?.get(..end.column)?);
+    Some(lines.join("\n"))
+}
+
+fn command_name(source: &[&str], command: Node) -> Option<String> {
+    let identifier = command.child(0)?;
+    Some(node_text(source, identifier)?.to_lowercase())
+}
+
+fn command_arguments(source: &[&str], command: Node) -> Vec<String> {
+    let Some(argument_list) = command.child(2) else {
+        return vec![];
+    };
+    if argument_list.kind() != CMakeNodeKinds::ARGUMENT_LIST {
+        return vec![];
+    }
+    let mut walk = argument_list.walk();
+    argument_list
+        .children(&mut walk)
+        .filter(|argument| argument.kind() == CMakeNodeKinds::ARGUMENT)
+        .filter_map(|argument| node_text(source, argument))
+        .collect()
+}
+
+/// Expand a list argument such as `"A;B"`, `A` or `"${options}"` into its items
+fn expand_list(argument: &str, lists: &HashMap<String, Vec<String>>) -> Vec<String> {
+    argument
+        .trim_matches('"')
+        .split([';', ' ', '\t', '\n'])
+        .filter(|item| !item.is_empty())
+        .flat_map(|item| {
+            match item
+                .strip_prefix("${")
+                .and_then(|item| item.strip_suffix('}'))
+            {
+                Some(variable) => lists.get(variable).cloned().unwrap_or_default(),
+                None => vec![item.to_string()],
+            }
+        })
+        .collect()
+}
+
+fn parse_call(arguments: &[String], lists: &HashMap<String, Vec<String>>) -> ParsedArguments {
+    let arguments = match arguments.first() {
+        Some(first) if first == "PARSE_ARGV" => arguments.get(2..).unwrap_or_default(),
+        _ => arguments,
+    };
+    let list_at = |index: usize| {
+        arguments
+            .get(index)
+            .map(|argument| expand_list(argument, lists))
+            .unwrap_or_default()
+    };
+    ParsedArguments {
+        prefix: arguments
+            .first()
+            .map(|prefix| prefix.trim_matches('"').to_string())
+            .unwrap_or_default(),
+        options: list_at(1),
+        one_value_keywords: list_at(2),
+        multi_value_keywords: list_at(3),
+    }
+}
+
+fn find_in_body(
+    source: &[&str],
+    node: Node,
+    lists: &mut HashMap<String, Vec<String>>,
+) -> Option<ParsedArguments> {
+    let mut walk = node.walk();
+    for child in node.children(&mut walk) {
+        match child.kind() {
+            CMakeNodeKinds::NORMAL_COMMAND => {
+                let Some(name) = command_name(source, child) else {
+                    continue;
+                };
+                let arguments = command_arguments(source, child);
+                if name == "set" && !arguments.is_empty() {
+                    let values = arguments[1..]
+                        .iter()
+                        .flat_map(|argument| expand_list(argument, lists))
+                        .collect();
+                    lists.insert(arguments[0].clone(), values);
+                } else if name == "cmake_parse_arguments" && !arguments.is_empty() {
+                    return Some(parse_call(&arguments, lists));
+                }
+            }
+            CMakeNodeKinds::BODY
+            | CMakeNodeKinds::IF_CONDITION
+            | CMakeNodeKinds::FOREACH_LOOP
+            | CMakeNodeKinds::WHILE_LOOP
+            | CMakeNodeKinds::BLOCK_DEF => {
+                if let Some(parsed) = find_in_body(source, child, lists) {
+                    return Some(parsed);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Parse the `cmake_parse_arguments` call in a `function_def` or `macro_def` node.
+pub fn parse_arguments_of(source: &[&str], definition: Node) -> Option<ParsedArguments> {
+    find_in_body(source, definition, &mut HashMap::new())
+}
+
+/// The name of a `function_def` or `macro_def` node, lowercased.
+pub fn definition_name(source: &[&str], definition: Node) -> Option<String> {
+    let command = definition.child(0)?;
+    command_arguments(source, command)
+        .first()
+        .map(|name| name.to_lowercase())
+}
+
+/// Record the keywords of a definition so call sites can complete them.
+pub fn update_function_keywords(source: &[&str], definition: Node) {
+    let Some(name) = definition_name(source, definition) else {
+        return;
+    };
+    let mut keywords = FUNCTION_KEYWORDS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    match parse_arguments_of(source, definition) {
+        Some(parsed) => {
+            keywords.insert(name, parsed);
+        }
+        None => {
+            keywords.remove(&name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    fn parse_first_definition(source: &str) -> (Option<String>, Option<ParsedArguments>) {
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let definition = thetree.root_node().child(0).unwrap();
+        (
+            definition_name(&lines, definition),
+            parse_arguments_of(&lines, definition),
+        )
+    }
+
+    #[test]
+    fn test_parse_literal_lists() {
+        let source = r#"function(My_Add NAME)
+    if(TRUE)
+        cmake_parse_arguments(ARG "STATIC;SHARED" DESTINATION "SOURCES;HEADERS" ${ARGN})
+    endif()
+endfunction()
+"#;
+        let (name, parsed) = parse_first_definition(source);
+        assert_eq!(name.as_deref(), Some("my_add"));
+        let parsed = parsed.unwrap();
+        assert_eq!(
+            parsed,
+            ParsedArguments {
+                prefix: "ARG".to_string(),
+                options: vec!["STATIC".to_string(), "SHARED".to_string()],
+                one_value_keywords: vec!["DESTINATION".to_string()],
+                multi_value_keywords: vec!["SOURCES".to_string(), "HEADERS".to_string()],
+            }
+        );
+        let variables = parsed.variables();
+        assert_eq!(variables.len(), 7);
+        assert!(variables.contains(&"ARG_SOURCES".to_string()));
+        assert!(variables.contains(&"ARG_UNPARSED_ARGUMENTS".to_string()));
+    }
+
+    #[test]
+    fn test_parse_argv_with_variables() {
+        let source = r#"macro(my_macro)
+    set(options QUIET)
+    set(one_value NAME VERSION)
+    set(multi_value "TARGETS;FILES")
+    cmake_parse_arguments(PARSE_ARGV 0 MY "${options}" "${one_value}" "${multi_value}")
+endmacro()
+"#;
+        let (_, parsed) = parse_first_definition(source);
+        let parsed = parsed.unwrap();
+        assert_eq!(parsed.prefix, "MY");
+        assert_eq!(parsed.options, vec!["QUIET"]);
+        assert_eq!(parsed.one_value_keywords, vec!["NAME", "VERSION"]);
+        assert_eq!(parsed.multi_value_keywords, vec!["TARGETS", "FILES"]);
+    }
+
+    #[test]
+    fn test_no_parse_arguments() {
+        let (name, parsed) = parse_first_definition("function(plain A B)\nendfunction()\n");
+        assert_eq!(name.as_deref(), Some("plain"));
+        assert_eq!(parsed, None);
+    }
+}
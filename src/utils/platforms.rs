@@ -0,0 +1,84 @@
+//! The variables cmake sets to tell the platforms apart in `if()`, and the
+//! names people write for them which cmake never sets.
+
+/// The platform variables and when each is true
+pub const PLATFORM_VARIABLES: &[(&str, &str)] = &[
+    (
+        "WIN32",
+        "True when the target system is Windows, 64-bit included, and on Cygwin only before CMake 2.8.4",
+    ),
+    (
+        "APPLE",
+        "True when the target system is an Apple platform: macOS, iOS, tvOS, visionOS or watchOS",
+    ),
+    (
+        "UNIX",
+        "True when the target system is a UNIX or UNIX-like system, macOS and Cygwin included",
+    ),
+    (
+        "MSVC",
+        "True when the compiler is Microsoft Visual C++ or a compiler simulating it, like clang-cl",
+    ),
+    (
+        "MINGW",
+        "True when the compiler is a MinGW GCC, native or cross-compiling to Windows",
+    ),
+    (
+        "ANDROID",
+        "True when cross-compiling for Android, set by the toolchain of `CMAKE_SYSTEM_NAME` `Android`",
+    ),
+    (
+        "EMSCRIPTEN",
+        "True when compiling to WebAssembly with the Emscripten toolchain file",
+    ),
+    (
+        "CYGWIN",
+        "True when the target system is Cygwin, where `WIN32` is false",
+    ),
+    ("MSYS", "True when the generator is MSYS Makefiles"),
+    (
+        "IOS",
+        "True when the target system is iOS, since CMake 3.14",
+    ),
+    (
+        "LINUX",
+        "True when the target system is Linux, since CMake 3.25",
+    ),
+    (
+        "BSD",
+        "True when the target system is a BSD, set to its name like `FreeBSD`, since CMake 3.25",
+    ),
+];
+
+/// Names which look like platform variables but are never set by cmake, and
+/// the variable meant
+pub const MISTAKEN_PLATFORMS: &[(&str, &str)] = &[
+    ("WINDOWS", "WIN32"),
+    ("WIN64", "WIN32"),
+    ("_WIN32", "WIN32"),
+    ("MACOS", "APPLE"),
+    ("MACOSX", "APPLE"),
+    ("OSX", "APPLE"),
+    ("DARWIN", "APPLE"),
+    ("MAC", "APPLE"),
+    ("POSIX", "UNIX"),
+    ("IPHONE", "IOS"),
+    ("WASM", "EMSCRIPTEN"),
+    ("WEBASSEMBLY", "EMSCRIPTEN"),
+];
+
+/// When the platform variable `name` is true
+pub fn platform_doc(name: &str) -> Option<&'static str> {
+    PLATFORM_VARIABLES
+        .iter()
+        .find(|(known, _)| *known == name)
+        .map(|(_, doc)| *doc)
+}
+
+/// The platform variable meant by `name`, which cmake never sets
+pub fn mistaken_platform(name: &str) -> Option<&'static str> {
+    MISTAKEN_PLATFORMS
+        .iter()
+        .find(|(mistaken, _)| *mistaken == name)
+        .map(|(_, meant)| *meant)
+}
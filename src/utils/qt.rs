@@ -0,0 +1,179 @@
+//! Static tracking of the projects using Qt, and the commands Qt defines.
+//!
+//! While indexing, the files calling `find_package(Qt6)` or `find_package(Qt5)`
+//! are recorded, so the Qt files and commands are only offered to the
+//! projects using it.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, PoisonError, RwLock};
+
+use crate::config::KnownCommand;
+
+/// The files finding Qt
+static QT_FILES: LazyLock<RwLock<BTreeSet<PathBuf>>> =
+    LazyLock::new(|| RwLock::new(BTreeSet::new()));
+
+/// `find_package(Qt6 ...)`, `find_package(Qt5Widgets)` or `find_package(QT NAMES Qt6 Qt5)`
+pub static FIND_QT_REGEX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"(?i:find_package)\s*\(\s*(Qt[56]\w*|QT)\b").unwrap());
+
+/// The files of Qt given as sources: forms, resources and translations
+pub const QT_SOURCE_EXTENSIONS: &[&str] = &["ui", "qrc", "ts"];
+
+/// The commands taking source files, forms and resources
+pub const QT_SOURCE_COMMANDS: &[&str] = &[
+    "qt_add_executable",
+    "qt_add_library",
+    "qt_add_resources",
+    "qt_wrap_cpp",
+    "qt_wrap_ui",
+    "qt5_add_resources",
+    "qt5_wrap_cpp",
+    "qt5_wrap_ui",
+    "qt6_add_executable",
+    "qt6_add_library",
+    "qt6_add_resources",
+    "qt6_wrap_cpp",
+    "qt6_wrap_ui",
+];
+
+/// The commands defined by Qt, with their signatures, also named with a
+/// `qt6_` prefix
+const QT_COMMANDS_DOCS: &[(&str, &[&str], &str)] = &[
+    (
+        "qt_add_executable",
+        &["qt_add_executable(<target> [WIN32] [MACOSX_BUNDLE] [MANUAL_FINALIZATION] <sources>...)"],
+        "Create an executable, with AUTOMOC, AUTOUIC and AUTORCC enabled",
+    ),
+    (
+        "qt_add_library",
+        &[
+            "qt_add_library(<target> [STATIC | SHARED | MODULE | INTERFACE | OBJECT] [MANUAL_FINALIZATION] <sources>...)",
+        ],
+        "Create a library, with AUTOMOC, AUTOUIC and AUTORCC enabled",
+    ),
+    (
+        "qt_add_resources",
+        &[
+            "qt_add_resources(<VAR> <file.qrc>... [OPTIONS <options>...])",
+            "qt_add_resources(<target> <resource_name> [PREFIX <path>] [LANG <language>] [BASE <path>] [BIG_RESOURCES] [OUTPUT_TARGETS <variable>] [FILES <files>...] [OPTIONS <options>...])",
+        ],
+        "Compile resources into the sources, from .qrc files or from a list of files",
+    ),
+    (
+        "qt_wrap_cpp",
+        &[
+            "qt_wrap_cpp(<VAR> <sources>... [TARGET <target>] [OPTIONS <options>...] [DEPENDS <files>...])",
+        ],
+        "Run moc on the sources, without AUTOMOC",
+    ),
+    (
+        "qt_wrap_ui",
+        &["qt_wrap_ui(<VAR> <file.ui>... [OPTIONS <options>...])"],
+        "Run uic on the forms, without AUTOUIC",
+    ),
+    (
+        "qt_add_translations",
+        &[
+            "qt_add_translations(<target> [TS_FILES <file.ts>...] [RESOURCE_PREFIX <prefix>] [LUPDATE_OPTIONS <options>...] [LRELEASE_OPTIONS <options>...])",
+        ],
+        "Update the .ts files with lupdate and compile them into the target with lrelease",
+    ),
+    (
+        "qt_standard_project_setup",
+        &[
+            "qt_standard_project_setup([REQUIRES <version>] [SUPPORTS_UP_TO <version>] [I18N_SOURCE_LANGUAGE <language>] [I18N_TRANSLATED_LANGUAGES <languages>...])",
+        ],
+        "Enable AUTOMOC and AUTOUIC, and set the defaults recommended by Qt",
+    ),
+    (
+        "qt_add_qml_module",
+        &[
+            "qt_add_qml_module(<target> URI <uri> [VERSION <version>] [QML_FILES <files>...] [RESOURCES <files>...] [SOURCES <files>...])",
+        ],
+        "Define a QML module",
+    ),
+    (
+        "qt_finalize_target",
+        &["qt_finalize_target(<target>)"],
+        "Finish a target created with MANUAL_FINALIZATION",
+    ),
+];
+
+/// The commands with a `qt5_` name, the others only have a `qt6_` one
+const QT5_COMMANDS: &[&str] = &["qt_add_resources", "qt_wrap_cpp", "qt_wrap_ui"];
+
+/// The commands defined by Qt, in the form of the `known_commands` setting
+pub static QT_COMMANDS: LazyLock<Vec<KnownCommand>> = LazyLock::new(|| {
+    let mut commands = vec![];
+    for (name, signatures, description) in QT_COMMANDS_DOCS {
+        let suffix = name.trim_start_matches("qt_");
+        let mut versions = vec!["qt_".to_string(), "qt6_".to_string()];
+        if QT5_COMMANDS.contains(name) {
+            versions.push("qt5_".to_string());
+        }
+        for version in versions {
+            let versioned = format!("{version}{suffix}");
+            commands.push(KnownCommand {
+                signatures: signatures
+                    .iter()
+                    .map(|signature| signature.replacen(name, &versioned, 1))
+                    .collect(),
+                name: versioned,
+                description: Some(description.to_string()),
+            });
+        }
+    }
+    commands
+});
+
+/// Record whether `local_path` finds Qt
+pub fn update_qt<P: AsRef<Path>>(local_path: P, source: &str) {
+    let mut files = QT_FILES.write().unwrap_or_else(PoisonError::into_inner);
+    if FIND_QT_REGEX.is_match(source) {
+        files.insert(local_path.as_ref().to_path_buf());
+    } else {
+        files.remove(local_path.as_ref());
+    }
+}
+
+/// Whether the project finds Qt
+pub fn uses_qt() -> bool {
+    !QT_FILES
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_qt_commands() {
+        assert!(FIND_QT_REGEX.is_match("find_package(Qt6 REQUIRED COMPONENTS Widgets)"));
+        assert!(FIND_QT_REGEX.is_match("FIND_PACKAGE( Qt5Widgets )"));
+        assert!(FIND_QT_REGEX.is_match("find_package(QT NAMES Qt6 Qt5)"));
+        assert!(!FIND_QT_REGEX.is_match("find_package(QtKeychain)"));
+
+        let wrap_ui = QT_COMMANDS
+            .iter()
+            .find(|command| command.name == "qt5_wrap_ui")
+            .unwrap();
+        assert_eq!(
+            wrap_ui.signatures,
+            vec!["qt5_wrap_ui(<VAR> <file.ui>... [OPTIONS <options>...])"]
+        );
+        assert!(
+            QT_COMMANDS
+                .iter()
+                .any(|command| command.name == "qt6_add_qml_module")
+        );
+        assert!(
+            !QT_COMMANDS
+                .iter()
+                .any(|command| command.name == "qt5_add_qml_module")
+        );
+    }
+}
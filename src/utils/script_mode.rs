@@ -0,0 +1,194 @@
+//! Detection of standalone scripts, run with `cmake -P`.
+//!
+//! Scripts have no project: commands like `add_executable` are refused there,
+//! and the project variables are never set.
+
+use std::path::Path;
+
+use tree_sitter::Node;
+
+use crate::CMakeNodeKinds;
+
+/// The commands listed as project commands in cmake-commands(7),
+/// which fail with "not scriptable" in script mode
+const PROJECT_COMMANDS: &[&str] = &[
+    "add_compile_definitions",
+    "add_compile_options",
+    "add_custom_command",
+    "add_custom_target",
+    "add_definitions",
+    "add_dependencies",
+    "add_executable",
+    "add_library",
+    "add_link_options",
+    "add_subdirectory",
+    "add_test",
+    "aux_source_directory",
+    "build_command",
+    "create_test_sourcelist",
+    "define_property",
+    "enable_language",
+    "enable_testing",
+    "export",
+    "fltk_wrap_ui",
+    "get_source_file_property",
+    "get_target_property",
+    "get_test_property",
+    "include_directories",
+    "include_external_msproject",
+    "include_regular_expression",
+    "install",
+    "link_directories",
+    "link_libraries",
+    "load_cache",
+    "project",
+    "remove_definitions",
+    "set_source_files_properties",
+    "set_target_properties",
+    "set_tests_properties",
+    "source_group",
+    "target_compile_definitions",
+    "target_compile_features",
+    "target_compile_options",
+    "target_include_directories",
+    "target_link_directories",
+    "target_link_libraries",
+    "target_link_options",
+    "target_precompile_headers",
+    "target_sources",
+    "try_compile",
+    "try_run",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileMode {
+    Project,
+    Script,
+}
+
+pub fn is_project_command(name: &str) -> bool {
+    PROJECT_COMMANDS.contains(&name.to_lowercase().as_str())
+}
+
+/// Variables only set once `project()` has run
+pub fn is_project_variable(name: &str) -> bool {
+    name.starts_with("PROJECT_")
+        || name.starts_with("CMAKE_PROJECT_")
+        || name.contains("<PROJECT-NAME>")
+}
+
+fn collect_commands<'a>(source: &[&str], node: Node<'a>, output: &mut Vec<(String, Node<'a>)>) {
+    let mut course = node.walk();
+    for child in node.children(&mut course) {
+        if child.kind() != CMakeNodeKinds::NORMAL_COMMAND {
+            collect_commands(source, child, output);
+            continue;
+        }
+        let Some(ids) = child.child(0) else {
+            continue;
+        };
+        let h = ids.start_position().row;
+        let name = source[h][ids.start_position().column..ids.end_position().column].to_lowercase();
+        output.push((name, ids));
+    }
+}
+
+/// Every command of the file, lowercased, with its identifier node
+pub fn commands<'a>(source: &[&str], root: Node<'a>) -> Vec<(String, Node<'a>)> {
+    let mut output = vec![];
+    collect_commands(source, root, &mut output);
+    output
+}
+
+fn calls_project(source: &[&str], root: Node) -> bool {
+    let mut course = root.walk();
+    root.children(&mut course)
+        .filter(|node| node.kind() == CMakeNodeKinds::NORMAL_COMMAND)
+        .filter_map(|node| node.child(0))
+        .any(|ids| {
+            let h = ids.start_position().row;
+            source[h][ids.start_position().column..ids.end_position().column]
+                .eq_ignore_ascii_case("project")
+        })
+}
+
+/// Guess if `local_path` is a project file or a script run with `cmake -P`.
+/// A file is a project file unless it says it is a script, as modules and
+/// files included in ways the index cannot follow look like scripts too.
+pub fn detect_mode<P: AsRef<Path>>(local_path: P, source: &[&str], root: Node) -> FileMode {
+    let local_path = local_path.as_ref();
+    if local_path
+        .file_name()
+        .is_some_and(|name| name == "CMakeLists.txt")
+        || calls_project(source, root)
+    {
+        return FileMode::Project;
+    }
+    // `#!/usr/bin/env -S cmake -P`, or a script checking how it is run
+    let has_shebang = source.first().is_some_and(|line| {
+        line.starts_with("#!") && line.contains("cmake") && line.contains("-P")
+    });
+    if has_shebang
+        || source
+            .iter()
+            .any(|line| line.contains("CMAKE_SCRIPT_MODE_FILE"))
+    {
+        return FileMode::Script;
+    }
+    FileMode::Project
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    fn mode_of(path: &str, source: &str) -> FileMode {
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        detect_mode(path, &lines, thetree.root_node())
+    }
+
+    #[test]
+    fn test_detect_mode() {
+        assert_eq!(
+            mode_of("/project/CMakeLists.txt", "message(hello)\n"),
+            FileMode::Project
+        );
+        assert_eq!(
+            mode_of("/project/tools/gen.cmake", "project(gen)\nmessage(hello)\n"),
+            FileMode::Project
+        );
+        assert_eq!(
+            mode_of(
+                "/somewhere/gen.cmake",
+                "#!/usr/bin/env -S cmake -P\nmessage(hello)\n"
+            ),
+            FileMode::Script
+        );
+        assert_eq!(
+            mode_of(
+                "/somewhere/gen.cmake",
+                "if(CMAKE_SCRIPT_MODE_FILE)\nendif()\n"
+            ),
+            FileMode::Script
+        );
+        // Nothing says it is run on its own
+        assert_eq!(
+            mode_of("/project/cmake/helpers.cmake", "message(hello)\n"),
+            FileMode::Project
+        );
+    }
+
+    #[test]
+    fn test_project_names() {
+        assert!(is_project_command("add_executable"));
+        assert!(is_project_command("Target_Link_Libraries"));
+        assert!(!is_project_command("file"));
+        assert!(is_project_variable("PROJECT_SOURCE_DIR"));
+        assert!(is_project_variable("CMAKE_PROJECT_NAME"));
+        assert!(!is_project_variable("CMAKE_SOURCE_DIR"));
+    }
+}
@@ -0,0 +1,545 @@
+//! Index of the targets declared by the project.
+//!
+//! While indexing, `add_executable`, `add_library` and `add_custom_target`
+//! calls are collected per file, so target names can be offered where
+//! CMake accepts them. The export sets filled by `install(TARGETS ... EXPORT)`,
+//! the ones installed by `install(EXPORT)` or `export(EXPORT)`, and what the
+//! `target_*()` calls add to each target are collected alongside.
+//!
+//! An alias, like `add_library(demo::core ALIAS core)`, is a target of its
+//! own which knows the target it stands for, so the alias chain can be
+//! followed to the declaration of the real target.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, PoisonError, RwLock};
+
+use tree_sitter::{Node, Point};
+
+use crate::CMakeNodeKinds;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetKind {
+    Executable,
+    Library,
+    /// `add_library(<name> INTERFACE)`, a library without sources of its own
+    Interface,
+    Custom,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub name: String,
+    pub kind: TargetKind,
+    /// Start of the target name in the declaring file
+    pub position: Point,
+    /// Declared in a branch of `if()`, so another declaration may be the other branch
+    pub conditional: bool,
+    /// The target this one is an alias of
+    pub alias_of: Option<String>,
+    /// `IMPORTED` without `GLOBAL`, only visible in its directory and below
+    pub local_import: bool,
+}
+
+/// Key is the declaring file, value is the targets it declares
+static TARGETS: LazyLock<RwLock<BTreeMap<PathBuf, Vec<Target>>>> =
+    LazyLock::new(|| RwLock::new(BTreeMap::new()));
+
+/// Key is the declaring file, value is the export sets it installs targets in
+static EXPORTS: LazyLock<RwLock<BTreeMap<PathBuf, Vec<String>>>> =
+    LazyLock::new(|| RwLock::new(BTreeMap::new()));
+
+/// Key is the declaring file, value is the export sets it installs or exports
+static INSTALLED_EXPORTS: LazyLock<RwLock<BTreeMap<PathBuf, Vec<String>>>> =
+    LazyLock::new(|| RwLock::new(BTreeMap::new()));
+
+/// The text of a single line argument, without its quotes
+fn argument_text<'a>(source: &[&'a str], argument: Node) -> Option<&'a str> {
+    if argument.start_position().row != argument.end_position().row {
+        return None;
+    }
+    let h = argument.start_position().row;
+    Some(
+        source[h]
+            .get(argument.start_position().column..argument.end_position().column)?
+            .trim_matches('"'),
+    )
+}
+
+fn collect_targets(source: &[&str], node: Node, conditional: bool, output: &mut Vec<Target>) {
+    let mut course = node.walk();
+    for child in node.children(&mut course) {
+        match child.kind() {
+            CMakeNodeKinds::NORMAL_COMMAND => {
+                let Some(ids) = child.child(0) else {
+                    continue;
+                };
+                let h = ids.start_position().row;
+                let kind = match source[h][ids.start_position().column..ids.end_position().column]
+                    .to_lowercase()
+                    .as_str()
+                {
+                    "add_executable" => TargetKind::Executable,
+                    "add_library" => TargetKind::Library,
+                    "add_custom_target" => TargetKind::Custom,
+                    _ => continue,
+                };
+                let Some(arguments) = child
+                    .child(2)
+                    .filter(|arguments| arguments.kind() == CMakeNodeKinds::ARGUMENT_LIST)
+                else {
+                    continue;
+                };
+                let mut walk = arguments.walk();
+                let arguments: Vec<Node> = arguments
+                    .children(&mut walk)
+                    .filter(|argument| argument.kind() == CMakeNodeKinds::ARGUMENT)
+                    .collect();
+                let Some((name, text)) = arguments
+                    .first()
+                    .and_then(|name| Some((name, argument_text(source, *name)?)))
+                else {
+                    continue;
+                };
+                // Names computed at configure time cannot be offered
+                if text.is_empty() || text.contains("${") {
+                    continue;
+                }
+                let second = arguments
+                    .get(1)
+                    .and_then(|argument| argument_text(source, *argument));
+                let kind = match second {
+                    Some("INTERFACE") if kind == TargetKind::Library => TargetKind::Interface,
+                    _ => kind,
+                };
+                let alias_of = arguments
+                    .get(2)
+                    .filter(|_| second == Some("ALIAS"))
+                    .and_then(|target| argument_text(source, *target))
+                    .map(str::to_string);
+                let is_keyword = |keyword: &str| {
+                    arguments[1..]
+                        .iter()
+                        .any(|argument| argument_text(source, *argument) == Some(keyword))
+                };
+                let local_import = is_keyword("IMPORTED") && !is_keyword("GLOBAL");
+                output.push(Target {
+                    name: text.to_string(),
+                    kind,
+                    position: name.start_position(),
+                    conditional,
+                    alias_of,
+                    local_import,
+                });
+            }
+            CMakeNodeKinds::IF_CONDITION => {
+                collect_targets(source, child, true, output);
+            }
+            CMakeNodeKinds::FOREACH_LOOP
+            | CMakeNodeKinds::WHILE_LOOP
+            | CMakeNodeKinds::BLOCK_DEF
+            | CMakeNodeKinds::BODY => {
+                collect_targets(source, child, conditional, output);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The targets declared in `source`
+pub fn declared_targets(source: &[&str], root: Node) -> Vec<Target> {
+    let mut output = Vec::new();
+    collect_targets(source, root, false, &mut output);
+    output
+}
+
+/// The targets of the project, the ones declared in `source`, the content of
+/// `local_path`, rather than the indexed ones which may be out of date
+pub fn project_targets(local_path: &Path, source: &[&str], root: Node) -> Vec<(PathBuf, Target)> {
+    declared_targets(source, root)
+        .into_iter()
+        .map(|target| (local_path.to_path_buf(), target))
+        .chain(
+            all_targets()
+                .into_iter()
+                .filter(|(path, _)| path != local_path),
+        )
+        .collect()
+}
+
+/// The real target `name` stands for, following the aliases of `targets`
+pub fn resolve_alias<'a>(name: &'a str, targets: &'a [(PathBuf, Target)]) -> &'a str {
+    let mut name = name;
+    // cmake refuses an alias of an alias, but the index may be in the middle
+    // of an edit making a loop
+    for _ in 0..targets.len() {
+        let Some(target) = targets
+            .iter()
+            .find_map(|(_, target)| (target.name == name).then_some(target))
+            .and_then(|target| target.alias_of.as_deref())
+        else {
+            break;
+        };
+        name = target;
+    }
+    name
+}
+
+/// The names of `targets` standing for the same real target as `name`, with
+/// the real target first
+pub fn alias_family<'a>(name: &'a str, targets: &'a [(PathBuf, Target)]) -> Vec<&'a str> {
+    let real = resolve_alias(name, targets);
+    let mut family = vec![real];
+    for (_, target) in targets {
+        if target.alias_of.is_some()
+            && resolve_alias(&target.name, targets) == real
+            && !family.contains(&target.name.as_str())
+        {
+            family.push(&target.name);
+        }
+    }
+    family
+}
+
+/// A `target_*()` call, or the sources given to `add_executable()` or `add_library()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetUsage {
+    pub target: String,
+    pub property: TargetProperty,
+    pub items: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetProperty {
+    Sources,
+    LinkLibraries,
+    IncludeDirectories,
+    CompileDefinitions,
+}
+
+/// Key is the calling file, value is the usages of targets it has
+static USAGES: LazyLock<RwLock<BTreeMap<PathBuf, Vec<TargetUsage>>>> =
+    LazyLock::new(|| RwLock::new(BTreeMap::new()));
+
+/// The arguments of the `target_*()` calls which are not items
+const USAGE_KEYWORDS: &[&str] = &[
+    "INTERFACE",
+    "PUBLIC",
+    "PRIVATE",
+    "SYSTEM",
+    "BEFORE",
+    "AFTER",
+    "LINK_PUBLIC",
+    "LINK_PRIVATE",
+    "LINK_INTERFACE_LIBRARIES",
+    "STATIC",
+    "SHARED",
+    "MODULE",
+    "OBJECT",
+    "UNKNOWN",
+    "EXCLUDE_FROM_ALL",
+    "WIN32",
+    "MACOSX_BUNDLE",
+    "FILES",
+];
+
+/// Keywords of `target_sources` followed by a name, and not a source
+const NAMING_KEYWORDS: &[&str] = &["FILE_SET", "TYPE"];
+
+/// The name and the single line arguments of the calls in `node`, leaving out
+/// the bodies of functions and macros
+fn collect_calls<'a>(source: &[&'a str], node: Node, output: &mut Vec<(String, Vec<&'a str>)>) {
+    let mut course = node.walk();
+    for child in node.children(&mut course) {
+        match child.kind() {
+            CMakeNodeKinds::NORMAL_COMMAND => {
+                let Some(ids) = child.child(0) else {
+                    continue;
+                };
+                let h = ids.start_position().row;
+                let name = source[h][ids.start_position().column..ids.end_position().column]
+                    .to_lowercase();
+                let Some(arguments) = child
+                    .child(2)
+                    .filter(|arguments| arguments.kind() == CMakeNodeKinds::ARGUMENT_LIST)
+                else {
+                    continue;
+                };
+                let mut walk = arguments.walk();
+                let words: Vec<&str> = arguments
+                    .children(&mut walk)
+                    .filter(|argument| {
+                        argument.kind() == CMakeNodeKinds::ARGUMENT
+                            && argument.start_position().row == argument.end_position().row
+                    })
+                    .map(|argument| {
+                        let h = argument.start_position().row;
+                        &source[h][argument.start_position().column..argument.end_position().column]
+                    })
+                    .collect();
+                output.push((name, words));
+            }
+            CMakeNodeKinds::IF_CONDITION
+            | CMakeNodeKinds::FOREACH_LOOP
+            | CMakeNodeKinds::WHILE_LOOP
+            | CMakeNodeKinds::BLOCK_DEF
+            | CMakeNodeKinds::BODY => {
+                collect_calls(source, child, output);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn calls<'a>(source: &[&'a str], root: Node) -> Vec<(String, Vec<&'a str>)> {
+    let mut output = Vec::new();
+    collect_calls(source, root, &mut output);
+    output
+}
+
+/// The export sets `source` installs targets in
+pub fn declared_exports(source: &[&str], root: Node) -> Vec<String> {
+    calls(source, root)
+        .into_iter()
+        .filter(|(name, words)| name == "install" && words.first() == Some(&"TARGETS"))
+        .filter_map(|(_, words)| {
+            let index = words.iter().position(|word| *word == "EXPORT")?;
+            Some(words.get(index + 1)?.trim_matches('"').to_string())
+        })
+        .collect()
+}
+
+/// The export sets `source` installs with `install(EXPORT)`, or exports to the
+/// build tree with `export(EXPORT)`
+pub fn declared_installed_exports(source: &[&str], root: Node) -> Vec<String> {
+    calls(source, root)
+        .into_iter()
+        .filter(|(name, words)| {
+            matches!(name.as_str(), "install" | "export") && words.first() == Some(&"EXPORT")
+        })
+        .filter_map(|(_, words)| Some(words.get(1)?.trim_matches('"').to_string()))
+        .collect()
+}
+
+/// What the calls of `source` add to the targets
+pub fn declared_usages(source: &[&str], root: Node) -> Vec<TargetUsage> {
+    let mut output = Vec::new();
+    for (name, words) in calls(source, root) {
+        let property = match name.as_str() {
+            "add_executable" | "add_library" | "target_sources" => TargetProperty::Sources,
+            "target_link_libraries" => TargetProperty::LinkLibraries,
+            "target_include_directories" => TargetProperty::IncludeDirectories,
+            "target_compile_definitions" => TargetProperty::CompileDefinitions,
+            _ => continue,
+        };
+        let Some((target, arguments)) = words.split_first() else {
+            continue;
+        };
+        if arguments
+            .iter()
+            .any(|argument| matches!(*argument, "ALIAS" | "IMPORTED"))
+        {
+            continue;
+        }
+        let mut items = Vec::new();
+        let mut skip_next = false;
+        let mut in_base_dirs = false;
+        for argument in arguments {
+            if std::mem::take(&mut skip_next) {
+                continue;
+            }
+            match *argument {
+                keyword if NAMING_KEYWORDS.contains(&keyword) => skip_next = true,
+                "BASE_DIRS" => in_base_dirs = true,
+                keyword if USAGE_KEYWORDS.contains(&keyword) => in_base_dirs = false,
+                _ if in_base_dirs => {}
+                item => items.push(item.trim_matches('"').to_string()),
+            }
+        }
+        if !items.is_empty() {
+            output.push(TargetUsage {
+                target: target.trim_matches('"').to_string(),
+                property,
+                items,
+            });
+        }
+    }
+    output
+}
+
+fn store<T, P: AsRef<Path>>(map: &RwLock<BTreeMap<PathBuf, Vec<T>>>, local_path: P, items: Vec<T>) {
+    let mut map = map.write().unwrap_or_else(PoisonError::into_inner);
+    if items.is_empty() {
+        map.remove(local_path.as_ref());
+    } else {
+        map.insert(local_path.as_ref().to_path_buf(), items);
+    }
+}
+
+fn all<T: Clone>(map: &RwLock<BTreeMap<PathBuf, Vec<T>>>) -> Vec<(PathBuf, T)> {
+    map.read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .flat_map(|(path, items)| items.iter().map(move |item| (path.clone(), item.clone())))
+        .collect()
+}
+
+/// Record the targets declared by `local_path`, its export sets, and what it
+/// adds to the targets
+pub fn update_targets<P: AsRef<Path>>(local_path: P, source: &[&str], root: Node) {
+    store(&TARGETS, &local_path, declared_targets(source, root));
+    store(&EXPORTS, &local_path, declared_exports(source, root));
+    store(
+        &INSTALLED_EXPORTS,
+        &local_path,
+        declared_installed_exports(source, root),
+    );
+    store(&USAGES, &local_path, declared_usages(source, root));
+}
+
+/// Every indexed target, with the file declaring it
+pub fn all_targets() -> Vec<(PathBuf, Target)> {
+    all(&TARGETS)
+}
+
+/// Every indexed export set, with the file installing targets in it
+pub fn all_exports() -> Vec<(PathBuf, String)> {
+    all(&EXPORTS)
+}
+
+/// Every indexed export set installed or exported, with the file doing it
+pub fn all_installed_exports() -> Vec<(PathBuf, String)> {
+    all(&INSTALLED_EXPORTS)
+}
+
+/// Every indexed usage of a target, with the calling file
+pub fn all_usages() -> Vec<(PathBuf, TargetUsage)> {
+    all(&USAGES)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+
+    #[test]
+    fn test_declared_targets() {
+        let source = r#"add_executable(app main.cpp)
+if(BUILD_TOOLS)
+    add_executable("codegen" codegen.cpp)
+endif()
+add_library(core STATIC core.cpp)
+add_custom_target(docs COMMAND doxygen)
+add_library(${PROJECT_NAME} lib.cpp)
+"#;
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let targets = declared_targets(&lines, thetree.root_node());
+        let names: Vec<(&str, TargetKind)> = targets
+            .iter()
+            .map(|target| (target.name.as_str(), target.kind))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("app", TargetKind::Executable),
+                ("codegen", TargetKind::Executable),
+                ("core", TargetKind::Library),
+                ("docs", TargetKind::Custom),
+            ]
+        );
+        assert_eq!(targets[1].position, Point { row: 2, column: 19 });
+        assert!(targets[1].conditional);
+        assert!(!targets[2].conditional);
+        assert_eq!(targets[2].alias_of, None);
+        assert!(!targets[2].local_import);
+
+        update_targets(
+            "/project/targets/CMakeLists.txt",
+            &lines,
+            thetree.root_node(),
+        );
+        assert!(
+            all_targets()
+                .iter()
+                .any(|(path, target)| target.name == "core"
+                    && path == Path::new("/project/targets/CMakeLists.txt"))
+        );
+    }
+
+    #[test]
+    fn test_aliases() {
+        let source = r#"add_library(core INTERFACE)
+add_library(demo::core ALIAS core)
+add_library(demo::all ALIAS demo::core)
+add_library(loop ALIAS loop)
+add_executable(tool tool.cpp)
+"#;
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let targets = project_targets(
+            Path::new("/project/aliases/CMakeLists.txt"),
+            &lines,
+            thetree.root_node(),
+        );
+        assert_eq!(targets[0].1.kind, TargetKind::Interface);
+        assert_eq!(targets[1].1.kind, TargetKind::Library);
+        assert_eq!(targets[1].1.alias_of.as_deref(), Some("core"));
+        assert_eq!(resolve_alias("demo::all", &targets), "core");
+        assert_eq!(resolve_alias("tool", &targets), "tool");
+        assert_eq!(resolve_alias("loop", &targets), "loop");
+        assert_eq!(
+            alias_family("demo::core", &targets),
+            vec!["core", "demo::core", "demo::all"]
+        );
+    }
+
+    #[test]
+    fn test_declared_usages() {
+        let source = r#"add_library(core STATIC core.cpp "util.cpp")
+target_sources(core PRIVATE FILE_SET HEADERS BASE_DIRS include FILES include/core.h)
+target_link_libraries(core PUBLIC Threads::Threads PRIVATE m)
+target_compile_definitions(core PRIVATE CORE_BUILD)
+add_library(alias ALIAS core)
+install(TARGETS core EXPORT CoreTargets)
+install(EXPORT CoreTargets DESTINATION lib/cmake/core)
+export(EXPORT "CoreTargets" FILE CoreTargets.cmake)
+"#;
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let lines: Vec<&str> = source.lines().collect();
+        let usages = declared_usages(&lines, thetree.root_node());
+        let found: Vec<(TargetProperty, Vec<&str>)> = usages
+            .iter()
+            .map(|usage| {
+                assert_eq!(usage.target, "core");
+                (
+                    usage.property,
+                    usage.items.iter().map(String::as_str).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (TargetProperty::Sources, vec!["core.cpp", "util.cpp"]),
+                (TargetProperty::Sources, vec!["include/core.h"]),
+                (TargetProperty::LinkLibraries, vec!["Threads::Threads", "m"]),
+                (TargetProperty::CompileDefinitions, vec!["CORE_BUILD"]),
+            ]
+        );
+        assert_eq!(
+            declared_exports(&lines, thetree.root_node()),
+            vec!["CoreTargets".to_string()]
+        );
+        assert_eq!(
+            declared_installed_exports(&lines, thetree.root_node()),
+            vec!["CoreTargets".to_string(), "CoreTargets".to_string()]
+        );
+    }
+}
@@ -0,0 +1,101 @@
+//! Recognition of toolchain files, as passed with `CMAKE_TOOLCHAIN_FILE`.
+//!
+//! A file is a toolchain file when it matches the `toolchain_files` setting, is
+//! the `CMAKE_TOOLCHAIN_FILE` of the build directory, or looks like one: its
+//! name contains "toolchain", or it sets `CMAKE_SYSTEM_NAME`.
+
+use std::path::Path;
+
+use super::module_path;
+use crate::config::CONFIG;
+use crate::fileapi;
+
+/// Commands a toolchain file should not call: it is read by `project()`
+/// and `enable_language()`, possibly several times
+const FORBIDDEN_COMMANDS: &[&str] = &[
+    "project",
+    "enable_language",
+    "add_subdirectory",
+    "add_executable",
+    "add_library",
+];
+
+fn matches_setting(path: &Path) -> bool {
+    let relative = module_path::source_dir()
+        .and_then(|dir| path.strip_prefix(dir).ok().map(Path::to_path_buf));
+    CONFIG.toolchain_files.iter().any(|pattern| {
+        let Ok(pattern) = glob::Pattern::new(pattern) else {
+            return false;
+        };
+        pattern.matches_path(path)
+            || relative
+                .as_deref()
+                .is_some_and(|relative| pattern.matches_path(relative))
+            || path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| pattern.matches(name))
+    })
+}
+
+fn is_cache_toolchain(path: &Path) -> bool {
+    fileapi::get_entries_data()
+        .and_then(|entries| entries.get("CMAKE_TOOLCHAIN_FILE").cloned())
+        .is_some_and(|toolchain| Path::new(&toolchain) == path)
+}
+
+pub fn is_toolchain_file<P: AsRef<Path>>(path: P, source: &[&str]) -> bool {
+    let path = path.as_ref();
+    if path
+        .file_name()
+        .is_some_and(|name| name == "CMakeLists.txt")
+    {
+        return false;
+    }
+    let name_hint = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.to_lowercase().contains("toolchain") && name.ends_with(".cmake"));
+    let sets_system_name = source.iter().any(|line| {
+        let line = line.trim_start().to_lowercase();
+        line.starts_with("set(") && line[4..].trim_start().starts_with("cmake_system_name")
+    });
+    name_hint || sets_system_name || matches_setting(path) || is_cache_toolchain(path)
+}
+
+pub fn is_forbidden_command(name: &str) -> bool {
+    FORBIDDEN_COMMANDS.contains(&name.to_lowercase().as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_toolchain_file() {
+        assert!(is_toolchain_file("/project/cmake/arm-Toolchain.cmake", &[]));
+        assert!(is_toolchain_file(
+            "/project/cmake/rpi.cmake",
+            &["set(CMAKE_SYSTEM_NAME Linux)"]
+        ));
+        assert!(is_toolchain_file(
+            "/project/cmake/rpi.cmake",
+            &["  SET( CMAKE_SYSTEM_NAME Linux)"]
+        ));
+        assert!(!is_toolchain_file(
+            "/project/cmake/warnings.cmake",
+            &["set(WARNINGS -Wall)"]
+        ));
+        assert!(!is_toolchain_file(
+            "/project/CMakeLists.txt",
+            &["set(CMAKE_SYSTEM_NAME Linux)"]
+        ));
+    }
+
+    #[test]
+    fn test_forbidden_command() {
+        assert!(is_forbidden_command("project"));
+        assert!(is_forbidden_command("ADD_EXECUTABLE"));
+        assert!(!is_forbidden_command("set"));
+    }
+}
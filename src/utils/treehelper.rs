@@ -1,15 +1,17 @@
 use std::collections::HashMap;
 use std::iter::zip;
 use std::process::Command;
-use std::sync::LazyLock;
+use std::sync::{Arc, LazyLock};
 
 use lsp_types::{Position, Range};
 /// Some tools for treesitter  to lsp_types
 use tower_lsp::lsp_types;
 use tree_sitter::{Node, Point};
 
-use super::get_node_content;
+use super::command_help::COMMAND_HELP;
+use super::{get_node_content, qt};
 use crate::CMakeNodeKinds;
+use crate::config::CONFIG;
 
 const BLACK_POS_STRING: [&str; 5] = ["(", ")", "{", "}", "$"];
 
@@ -124,25 +126,11 @@ pub fn get_position_range(location: Position, root: Node) -> Option<Range> {
     None
 }
 
-pub static MESSAGE_STORAGE: LazyLock<HashMap<String, String>> = LazyLock::new(|| {
-    let mut storage: HashMap<String, String> = HashMap::new();
+/// The help of the builtin commands, variables and modules, for the hover
+pub static MESSAGE_STORAGE: LazyLock<HashMap<String, Arc<str>>> = LazyLock::new(|| {
+    // The help of the commands is shared with the completion and the signatures
+    let mut storage: HashMap<String, Arc<str>> = COMMAND_HELP.clone();
     let re = regex::Regex::new(r"[z-zA-z]+\n-+").unwrap();
-    if let Ok(output) = Command::new("cmake").arg("--help-commands").output() {
-        let output = output.stdout;
-        let temp = String::from_utf8_lossy(&output);
-        let key: Vec<_> = re
-            .find_iter(&temp)
-            .map(|message| {
-                let temp: Vec<&str> = message.as_str().split('\n').collect();
-                temp[0]
-            })
-            .collect();
-        let content: Vec<_> = re.split(&temp).collect();
-        let context = &content[1..];
-        for (akey, message) in zip(key, context) {
-            storage.insert(akey.to_string(), message.to_string());
-        }
-    }
     if let Ok(output) = Command::new("cmake").arg("--help-variables").output() {
         let output = output.stdout;
         let temp = String::from_utf8_lossy(&output);
@@ -156,7 +144,7 @@ pub static MESSAGE_STORAGE: LazyLock<HashMap<String, String>> = LazyLock::new(||
         let content: Vec<_> = re.split(&temp).collect();
         let context = &content[1..];
         for (akey, message) in zip(key, context) {
-            storage.insert(akey.to_string(), message.to_string());
+            storage.insert(akey.to_string(), Arc::from(*message));
         }
     }
     if let Ok(output) = Command::new("cmake").arg("--help-modules").output() {
@@ -172,14 +160,19 @@ pub static MESSAGE_STORAGE: LazyLock<HashMap<String, String>> = LazyLock::new(||
         let content: Vec<_> = re.split(&temp).collect();
         let context = &content[1..];
         for (akey, message) in zip(key, context) {
-            storage.insert(akey.to_string(), message.to_string());
+            storage.insert(akey.to_string(), Arc::from(*message));
         }
     }
     #[cfg(unix)]
     storage.insert(
         "pkg_check_modules".to_string(),
-        "please FindPackage PkgConfig first".to_string(),
+        Arc::from("please FindPackage PkgConfig first"),
     );
+    for command in CONFIG.known_commands.iter().chain(qt::QT_COMMANDS.iter()) {
+        storage
+            .entry(command.name.clone())
+            .or_insert_with(|| Arc::from(command.documentation()));
+    }
     storage
 });
 
@@ -247,6 +240,208 @@ pub fn contain_comment(location: Point, root: Node) -> bool {
     false
 }
 
+/// The chain of nodes containing `location`, from the outermost to the innermost
+pub fn get_enclosing_nodes<'a>(location: Point, root: Node<'a>) -> Vec<Node<'a>> {
+    let mut nodes = vec![];
+    let mut current = root;
+    'outer: loop {
+        let mut cursor = current.walk();
+        for child in current.children(&mut cursor) {
+            if location_range_contain(location, child) {
+                nodes.push(child);
+                current = child;
+                continue 'outer;
+            }
+        }
+        break;
+    }
+    nodes
+}
+
+/// The innermost node of one of `kinds` containing `location`
+pub fn innermost_node<'a>(location: Point, root: Node<'a>, kinds: &[&str]) -> Option<Node<'a>> {
+    get_enclosing_nodes(location, root)
+        .into_iter()
+        .rev()
+        .find(|node| kinds.contains(&node.kind()))
+}
+
+/// Whether `location` is inside a comment or a bracket argument `[[...]]`,
+/// where neither commands, variables nor paths make sense.
+pub fn is_in_comment_or_bracket(location: Point, root: Node) -> bool {
+    get_enclosing_nodes(location, root)
+        .iter()
+        .find_map(|node| match node.kind() {
+            // A line comment runs to the end of the line, so its end is still inside
+            CMakeNodeKinds::LINE_COMMENT => Some(location != node.start_position()),
+            CMakeNodeKinds::BRACKET_COMMENT | CMakeNodeKinds::BRACKET_ARGUMENT => {
+                Some(location != node.start_position() && location != node.end_position())
+            }
+            _ => None,
+        })
+        .unwrap_or(false)
+}
+
+/// A command call around a position, parsed or still being typed, like
+/// `message(STATUS ` which tree-sitter leaves in an ERROR without its `)`
+#[derive(Debug)]
+pub struct CommandAt<'a> {
+    /// The name of the command, lowercase
+    pub name: String,
+    /// The arguments before the position, and the one under it
+    pub arguments: Vec<Node<'a>>,
+    /// The index of the argument under the position
+    pub argument_index: usize,
+}
+
+enum CallToken<'a> {
+    Name(Node<'a>),
+    Open,
+    Close,
+    Argument(Node<'a>),
+}
+
+/// Whether `node` is a whole argument, in an argument list or left loose in an
+/// ERROR
+fn is_argument(node: Node) -> bool {
+    matches!(
+        node.kind(),
+        CMakeNodeKinds::ARGUMENT
+            | CMakeNodeKinds::QUOTED_ARGUMENT
+            | CMakeNodeKinds::UNQUOTED_ARGUMENT
+            | CMakeNodeKinds::BRACKET_ARGUMENT
+    )
+}
+
+/// The arguments of a parsed command call
+pub fn command_arguments<'a>(command: Node<'a>) -> Vec<Node<'a>> {
+    let Some(argument_list) = command
+        .child(2)
+        .filter(|node| node.kind() == CMakeNodeKinds::ARGUMENT_LIST)
+    else {
+        return vec![];
+    };
+    let mut cursor = argument_list.walk();
+    argument_list
+        .children(&mut cursor)
+        .filter(|node| is_argument(*node))
+        .collect()
+}
+
+/// The names, parentheses and arguments before `location`. The statements
+/// ending before it open and close as many parentheses, so they are skipped
+/// unless they hold an error, but not the arguments of the call being typed.
+fn call_tokens<'a>(node: Node<'a>, location: Point, tokens: &mut Vec<CallToken<'a>>) {
+    let is_command = node.kind().ends_with("_command");
+    let mut cursor = node.walk();
+    for (index, child) in node.children(&mut cursor).enumerate() {
+        if child.start_position() >= location {
+            break;
+        }
+        if is_argument(child) {
+            tokens.push(CallToken::Argument(child));
+            continue;
+        }
+        match child.kind() {
+            "(" if !child.is_missing() => tokens.push(CallToken::Open),
+            ")" if !child.is_missing() => tokens.push(CallToken::Close),
+            CMakeNodeKinds::IDENTIFIER => tokens.push(CallToken::Name(child)),
+            // `if`, `foreach`... naming the commands of the blocks
+            _ if is_command && index == 0 => tokens.push(CallToken::Name(child)),
+            CMakeNodeKinds::LINE_COMMENT | CMakeNodeKinds::BRACKET_COMMENT => {}
+            CMakeNodeKinds::ARGUMENT_LIST => call_tokens(child, location, tokens),
+            _ if child.end_position() < location && !child.has_error() => {}
+            _ => call_tokens(child, location, tokens),
+        }
+    }
+}
+
+/// The command call whose parentheses hold `location`, even when its `)` or
+/// its arguments are missing, with the index of the argument there
+pub fn command_at<'a>(root: Node<'a>, location: Point, source: &str) -> Option<CommandAt<'a>> {
+    if is_in_comment_or_bracket(location, root) {
+        return None;
+    }
+    let mut tokens = vec![];
+    call_tokens(root, location, &mut tokens);
+
+    let mut depth = 0;
+    let mut groups = 0;
+    let mut arguments = vec![];
+    let mut open = None;
+    for (index, token) in tokens.iter().enumerate().rev() {
+        match token {
+            CallToken::Close => depth += 1,
+            CallToken::Open if depth > 0 => {
+                depth -= 1;
+                // A parenthesized group, like `(A OR B)` in a condition
+                if depth == 0 {
+                    groups += 1;
+                }
+            }
+            CallToken::Open => {
+                open = Some(index);
+                break;
+            }
+            CallToken::Name(node) | CallToken::Argument(node) if depth == 0 => {
+                arguments.push(*node);
+            }
+            _ => {}
+        }
+    }
+    let CallToken::Name(identifier) = tokens.get(open?.checked_sub(1)?)? else {
+        return None;
+    };
+    arguments.reverse();
+    let name = source.get(identifier.byte_range())?.to_lowercase();
+    let argument_index = groups
+        + arguments
+            .iter()
+            .filter(|argument| argument.end_position() < location)
+            .count();
+    Some(CommandAt {
+        name,
+        arguments,
+        argument_index,
+    })
+}
+
+/// If the cursor is on the value of `set(<VAR> ...)`, return `VAR`
+pub fn set_variable_at(location: Point, root: Node, source: &[&str]) -> Option<String> {
+    let command = get_enclosing_nodes(location, root)
+        .into_iter()
+        .rev()
+        .find(|node| node.kind() == CMakeNodeKinds::NORMAL_COMMAND)?;
+    let ids = command.child(0)?;
+    let h = ids.start_position().row;
+    if !source
+        .get(h)?
+        .get(ids.start_position().column..ids.end_position().column)?
+        .eq_ignore_ascii_case("set")
+    {
+        return None;
+    }
+    let argument_list = command.child(2)?;
+    if argument_list.kind() != CMakeNodeKinds::ARGUMENT_LIST {
+        return None;
+    }
+    let mut walk = argument_list.walk();
+    let arguments: Vec<_> = argument_list
+        .children(&mut walk)
+        .filter(|argument| argument.kind() == CMakeNodeKinds::ARGUMENT)
+        .collect();
+    let variable = arguments.first()?;
+    // The cursor must be after the variable name
+    if location <= variable.end_position() {
+        return None;
+    }
+    let h = variable.start_position().row;
+    source
+        .get(h)?
+        .get(variable.start_position().column..variable.end_position().column)
+        .map(str::to_string)
+}
+
 #[inline]
 pub fn get_pos_type<'a>(location: Point, root: Node, source: &'a str) -> PositionType<'a> {
     get_pos_type_inner(
@@ -286,6 +481,7 @@ fn get_pos_type_inner<'a>(
                     "target_link_libraries" => PositionType::TargetLink,
                     // Source file commands
                     "add_executable" | "add_library" | "target_sources" => PositionType::SourceFile,
+                    name if qt::QT_SOURCE_COMMANDS.contains(&name) => PositionType::SourceFile,
                     // Any file commands
                     "configure_file"
                     | "source_group"
@@ -671,4 +867,71 @@ endmacro()
             print_tree_debug(child, source, indent + 1);
         }
     }
+
+    #[test]
+    fn test_in_comment_or_bracket() {
+        let source = r#"# line comment
+#[[ bracket
+comment ]]
+set(A [=[ bracket argument ]=] B) # trailing
+"#;
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let root = thetree.root_node();
+        let in_comment = |row, column| is_in_comment_or_bracket(Point { row, column }, root);
+
+        assert!(!in_comment(0, 0));
+        assert!(in_comment(0, 5));
+        assert!(in_comment(0, 14));
+        assert!(in_comment(1, 5));
+        assert!(in_comment(2, 3));
+        assert!(!in_comment(3, 2));
+        assert!(!in_comment(3, 4));
+        assert!(in_comment(3, 12));
+        assert!(!in_comment(3, 32));
+        assert!(in_comment(3, 40));
+    }
+
+    #[test]
+    fn test_innermost_node() {
+        let source = "function(demo)\n  message(STATUS \"${A}\")\nendfunction()\n";
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let root = thetree.root_node();
+
+        let at = Point { row: 1, column: 12 };
+        let command = innermost_node(at, root, &[CMakeNodeKinds::NORMAL_COMMAND]).unwrap();
+        assert_eq!(command.start_position(), Point { row: 1, column: 2 });
+        assert!(innermost_node(at, root, &[CMakeNodeKinds::FUNCTION_DEF]).is_some());
+        assert!(!is_in_comment_or_bracket(at, root));
+
+        let outside = Point { row: 4, column: 0 };
+        assert!(innermost_node(outside, root, &[CMakeNodeKinds::NORMAL_COMMAND]).is_none());
+    }
+
+    #[test]
+    fn test_command_at() {
+        let source = r#"set(A 1)
+if((A OR B) AND )
+message(STATUS "unclosed" ${A} 
+"#;
+        let mut parse = tree_sitter::Parser::new();
+        parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+        let thetree = parse.parse(source, None).unwrap();
+        let root = thetree.root_node();
+        let at = |row, column| {
+            command_at(root, Point { row, column }, source)
+                .map(|command| (command.name, command.argument_index))
+        };
+
+        assert_eq!(at(0, 2), None);
+        assert_eq!(at(0, 4), Some(("set".to_string(), 0)));
+        assert_eq!(at(0, 7), Some(("set".to_string(), 1)));
+        assert_eq!(at(0, 8), None);
+        assert_eq!(at(1, 16), Some(("if".to_string(), 2)));
+        assert_eq!(at(2, 10), Some(("message".to_string(), 0)));
+        assert_eq!(at(2, 31), Some(("message".to_string(), 3)));
+    }
 }
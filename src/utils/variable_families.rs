@@ -0,0 +1,215 @@
+//! The families of variables like `CMAKE_<LANG>_FLAGS_<CONFIG>`.
+//!
+//! `cmake --help-variables` only documents the patterns, these are their
+//! instances for the languages and the configurations, such as
+//! `CMAKE_CXX_FLAGS_RELEASE`.
+
+use std::collections::HashSet;
+use std::sync::LazyLock;
+
+use crate::complete::builtin::BUILTIN_VARIABLE;
+use crate::utils::languages;
+
+/// The configurations every generator knows, as they appear in variable names
+pub const STANDARD_CONFIGS: &[&str] = &["DEBUG", "RELEASE", "RELWITHDEBINFO", "MINSIZEREL"];
+
+const PLACEHOLDERS: &[&str] = &["<LANG>", "<CONFIG>"];
+
+/// The builtin variables with a `<LANG>` or `<CONFIG>` placeholder, and no other
+pub static VARIABLE_PATTERNS: LazyLock<Vec<String>> = LazyLock::new(|| {
+    BUILTIN_VARIABLE
+        .iter()
+        .flatten()
+        .map(|item| item.label.clone())
+        .filter(|label| is_pattern(label))
+        .collect()
+});
+
+fn is_pattern(label: &str) -> bool {
+    let mut rest = label.to_string();
+    for placeholder in PLACEHOLDERS {
+        rest = rest.replace(placeholder, "");
+    }
+    rest.len() < label.len() && !rest.contains('<')
+}
+
+/// A variable of a family, with the values of its placeholders
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instance {
+    pub name: String,
+    pub pattern: String,
+    pub bindings: Vec<(&'static str, String)>,
+}
+
+impl Instance {
+    /// Like "`<LANG>` = `CXX` and `<CONFIG>` = `RELEASE`"
+    pub fn describe_bindings(&self) -> String {
+        self.bindings
+            .iter()
+            .map(|(placeholder, value)| format!("`{placeholder}` = `{value}`"))
+            .collect::<Vec<_>>()
+            .join(" and ")
+    }
+}
+
+fn expand(pattern: &str, languages: &[String], output: &mut Vec<Instance>) {
+    let mut instances = vec![Instance {
+        name: pattern.to_string(),
+        pattern: pattern.to_string(),
+        bindings: vec![],
+    }];
+    for placeholder in PLACEHOLDERS {
+        if !pattern.contains(placeholder) {
+            continue;
+        }
+        let values: Vec<&str> = if *placeholder == "<LANG>" {
+            languages.iter().map(String::as_str).collect()
+        } else {
+            STANDARD_CONFIGS.to_vec()
+        };
+        instances = instances
+            .into_iter()
+            .flat_map(|instance| {
+                values.iter().map(move |value| {
+                    let mut bindings = instance.bindings.clone();
+                    bindings.push((*placeholder, value.to_string()));
+                    Instance {
+                        name: instance.name.replace(placeholder, value),
+                        pattern: instance.pattern.clone(),
+                        bindings,
+                    }
+                })
+            })
+            .collect();
+    }
+    output.append(&mut instances);
+}
+
+fn placeholder_count(pattern: &str) -> usize {
+    PLACEHOLDERS
+        .iter()
+        .map(|placeholder| pattern.matches(placeholder).count())
+        .sum()
+}
+
+/// The instances of `patterns` for `languages` and the standard
+/// configurations, the most specific pattern of a name first, like
+/// `CMAKE_<LANG>_FLAGS_DEBUG` before `CMAKE_<LANG>_FLAGS_<CONFIG>`
+pub fn instances_of(patterns: &[String], languages: &[String]) -> Vec<Instance> {
+    let mut patterns: Vec<&String> = patterns.iter().collect();
+    patterns.sort_by_key(|pattern| placeholder_count(pattern));
+    let mut instances = vec![];
+    for pattern in patterns {
+        expand(pattern, languages, &mut instances);
+    }
+    let mut seen = HashSet::new();
+    instances.retain(|instance| seen.insert(instance.name.clone()));
+    instances
+}
+
+/// The instances for the languages of the project, C and C++ before it is indexed
+pub fn project_instances() -> Vec<Instance> {
+    let languages = languages::enabled_languages().unwrap_or_else(|| {
+        languages::DEFAULT_LANGUAGES
+            .iter()
+            .map(|language| language.to_string())
+            .collect()
+    });
+    instances_of(&VARIABLE_PATTERNS, &languages)
+}
+
+/// The family of `name` among `patterns`, for any language and configuration
+pub fn family_of(patterns: &[String], name: &str) -> Option<Instance> {
+    let languages: Vec<String> = languages::known_languages().map(regex::escape).collect();
+    patterns
+        .iter()
+        .filter(|pattern| {
+            let mut segments = pattern.split(['<', '>']);
+            let first = segments.next().unwrap_or_default();
+            let last = segments.next_back().unwrap_or_default();
+            name.starts_with(first) && name.ends_with(last)
+        })
+        .filter_map(|pattern| {
+            let mut regex = regex::escape(pattern);
+            for placeholder in PLACEHOLDERS {
+                let value = if *placeholder == "<LANG>" {
+                    format!("({})", languages.join("|"))
+                } else {
+                    "([A-Z][A-Z0-9]*)".to_string()
+                };
+                regex = regex.replace(placeholder, &value);
+            }
+            let captures = regex::Regex::new(&format!("^{regex}$"))
+                .ok()?
+                .captures(name)?;
+            let mut order: Vec<(usize, &'static str)> = PLACEHOLDERS
+                .iter()
+                .flat_map(|placeholder| {
+                    pattern
+                        .match_indices(placeholder)
+                        .map(|(index, _)| (index, *placeholder))
+                })
+                .collect();
+            order.sort();
+            let bindings = order
+                .into_iter()
+                .zip(captures.iter().skip(1).flatten())
+                .map(|((_, placeholder), value)| (placeholder, value.as_str().to_string()))
+                .collect();
+            Some(Instance {
+                name: name.to_string(),
+                pattern: pattern.clone(),
+                bindings,
+            })
+        })
+        .min_by_key(|instance| placeholder_count(&instance.pattern))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_variable_families() {
+        let patterns: Vec<String> = [
+            "CMAKE_<LANG>_FLAGS_<CONFIG>",
+            "CMAKE_<LANG>_FLAGS_DEBUG",
+            "CMAKE_<LANG>_STANDARD",
+            "CMAKE_<CONFIG>_POSTFIX",
+        ]
+        .iter()
+        .map(|pattern| pattern.to_string())
+        .collect();
+        assert!(is_pattern("CMAKE_<LANG>_FLAGS_<CONFIG>_INIT"));
+        assert!(!is_pattern("CMAKE_<LANG>_LINK_LIBRARY_USING_<FEATURE>"));
+        assert!(!is_pattern("CMAKE_BUILD_TYPE"));
+
+        let instances = instances_of(&patterns, &["C".to_string(), "CUDA".to_string()]);
+        let names: Vec<&str> = instances
+            .iter()
+            .map(|instance| instance.name.as_str())
+            .collect();
+        assert_eq!(names.len(), 2 + 2 + 4 + 2 * 3);
+        assert!(names.contains(&"CMAKE_CUDA_FLAGS_RELWITHDEBINFO"));
+        assert!(names.contains(&"CMAKE_MINSIZEREL_POSTFIX"));
+        let debug = instances
+            .iter()
+            .find(|instance| instance.name == "CMAKE_C_FLAGS_DEBUG")
+            .unwrap();
+        assert_eq!(debug.pattern, "CMAKE_<LANG>_FLAGS_DEBUG");
+
+        let family = family_of(&patterns, "CMAKE_CXX_FLAGS_RELEASE").unwrap();
+        assert_eq!(family.pattern, "CMAKE_<LANG>_FLAGS_<CONFIG>");
+        assert_eq!(
+            family.describe_bindings(),
+            "`<LANG>` = `CXX` and `<CONFIG>` = `RELEASE`"
+        );
+        assert_eq!(
+            family_of(&patterns, "CMAKE_Fortran_FLAGS_DEBUG")
+                .unwrap()
+                .pattern,
+            "CMAKE_<LANG>_FLAGS_DEBUG"
+        );
+        assert_eq!(family_of(&patterns, "CMAKE_COBOL_STANDARD"), None);
+    }
+}
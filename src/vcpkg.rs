@@ -0,0 +1,668 @@
+//! The vcpkg manifest, `vcpkg.json`, checked against the `find_package()` calls
+//! of the project.
+//!
+//! A package found from a port the manifest does not declare only builds while
+//! another port pulls it in, and a declared port no `find_package()` uses is
+//! built for nothing. The problems are reported in the CMake files, in the
+//! manifest, and by the `neocmake/vcpkgReport` request for the whole project.
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex, PoisonError, RwLock};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::{
+    CodeDescription, CompletionItem, CompletionItemKind, CompletionResponse, Diagnostic, Location,
+    NumberOrString, Position, Range, Uri,
+};
+use tree_sitter::{Node, Point};
+
+use crate::consts::TREESITTER_CMAKE_LANGUAGE;
+use crate::utils::cmake_modules;
+use crate::utils::json_scan::{self, Entry, Typed, offset_of, position_of, scan};
+use crate::utils::treehelper::ToPosition;
+use crate::{CMakeNodeKinds, lint, rules};
+
+const MANIFEST: &str = "vcpkg.json";
+
+pub const VCPKG_REPORT_METHOD: &str = "neocmake/vcpkgReport";
+
+/// The port installed with each CMake package, by lowercase package name
+type Installed = HashMap<String, String>;
+
+static INSTALLED_PACKAGES: LazyLock<RwLock<Installed>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// What was read from each file, with its modification time
+type Cache<T> = Mutex<HashMap<PathBuf, (SystemTime, T)>>;
+
+/// The ports declared by each manifest
+static MANIFESTS: LazyLock<Cache<Vec<String>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// The `find_package()` calls of each CMake file
+static PACKAGE_USES: LazyLock<Cache<Vec<PackageUse>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// The keywords of `find_package()` ending its list of components, with the
+/// ones starting with `NO_`
+const FIND_PACKAGE_KEYWORDS: &[&str] = &[
+    "EXACT",
+    "QUIET",
+    "MODULE",
+    "CONFIG",
+    "GLOBAL",
+    "NAMES",
+    "CONFIGS",
+    "HINTS",
+    "PATHS",
+    "PATH_SUFFIXES",
+    "REGISTRY_VIEW",
+    "BYPASS_PROVIDER",
+    "CMAKE_FIND_ROOT_PATH_BOTH",
+    "ONLY_CMAKE_FIND_ROOT_PATH",
+];
+
+pub fn is_manifest<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref()
+        .file_name()
+        .is_some_and(|name| name == MANIFEST)
+}
+
+/// The package of a config file, like `fmt` for `fmt-config.cmake`
+fn config_package(file_name: &str) -> Option<&str> {
+    file_name
+        .strip_suffix("Config.cmake")
+        .or_else(|| file_name.strip_suffix("-config.cmake"))
+        .filter(|package| !package.is_empty())
+}
+
+/// The packages of the ports installed in `installed`, the `vcpkg_installed`
+/// directory of the project
+fn read_installed_packages(installed: &Path) -> Installed {
+    let pattern = format!("{}/*/share/*/*.cmake", installed.display());
+    let mut packages = HashMap::new();
+    for file in glob::glob(&pattern).into_iter().flatten().flatten() {
+        let Some(package) = file
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(config_package)
+        else {
+            continue;
+        };
+        let Some(port) = file
+            .parent()
+            .and_then(|dir| dir.file_name())
+            .and_then(|name| name.to_str())
+        else {
+            continue;
+        };
+        packages.insert(package.to_lowercase(), port.to_string());
+    }
+    packages
+}
+
+/// Read the packages of the ports installed in `installed`, the
+/// `vcpkg_installed` directory of the project
+pub fn update_installed_packages(installed: &Path) {
+    *INSTALLED_PACKAGES
+        .write()
+        .unwrap_or_else(PoisonError::into_inner) = read_installed_packages(installed);
+}
+
+/// The installed port providing `package`
+fn installed_port(installed: &Installed, package: &str) -> Option<String> {
+    installed.get(&package.to_lowercase()).cloned()
+}
+
+/// The name vcpkg gives most ports, like `nlohmann-json` for `nlohmann_json`
+fn port_name(package: &str) -> String {
+    package.to_lowercase().replace('_', "-")
+}
+
+/// The components asked by the arguments of `find_package()` after the name
+/// of the package, following `COMPONENTS`, `OPTIONAL_COMPONENTS` or `REQUIRED`
+pub fn components<'a>(arguments: &[&'a str]) -> Vec<&'a str> {
+    let mut listing = false;
+    let mut output = vec![];
+    for argument in arguments {
+        match *argument {
+            "COMPONENTS" | "OPTIONAL_COMPONENTS" | "REQUIRED" => listing = true,
+            keyword if keyword.starts_with("NO_") || FIND_PACKAGE_KEYWORDS.contains(&keyword) => {
+                listing = false;
+            }
+            component if listing => output.push(component.trim_matches('"')),
+            _ => {}
+        }
+    }
+    output
+}
+
+/// The ports of the components of a package vcpkg splits in one port per
+/// component, like `boost-filesystem` for `find_package(Boost COMPONENTS filesystem)`
+fn component_ports(package: &PackageUse) -> Vec<String> {
+    if !package.name.eq_ignore_ascii_case("boost") {
+        return vec![];
+    }
+    package
+        .components
+        .iter()
+        .map(|component| {
+            let library = match component.as_str() {
+                "unit_test_framework" | "prg_exec_monitor" | "test_exec_monitor" => "test",
+                "numpy" => "python",
+                component if component.starts_with("python") => "python",
+                component => component,
+            };
+            format!("boost-{}", port_name(library))
+        })
+        .collect()
+}
+
+/// Whether `package` comes from `port`, by the installed files, by the name
+/// of the port, or by one of its components
+fn provides(installed: &Installed, port: &str, package: &PackageUse) -> bool {
+    installed_port(installed, &package.name).is_some_and(|installed| installed == port)
+        || port_name(&package.name) == port
+        || component_ports(package)
+            .iter()
+            .any(|component| component == port)
+}
+
+/// The dependencies of the manifest, as strings or as the `name` of objects
+fn dependencies<'a, 'b>(entries: &'b [Entry<'a>]) -> Vec<&'b Entry<'a>> {
+    entries
+        .iter()
+        .filter(|entry| {
+            matches!(
+                (entry.key, entry.parents.as_slice()),
+                ("dependencies", []) | ("name", ["dependencies"])
+            )
+        })
+        .collect()
+}
+
+/// The ports declared by the manifest of the project in `root`, `None`
+/// without a manifest. The manifest is parsed again only when it changes.
+pub fn declared_ports(root: &Path) -> Option<Vec<String>> {
+    let manifest = root.join(MANIFEST);
+    let modified = std::fs::metadata(&manifest)
+        .and_then(|metadata| metadata.modified())
+        .ok()?;
+    if let Some((cached, ports)) = MANIFESTS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(&manifest)
+        && *cached == modified
+    {
+        return Some(ports.clone());
+    }
+    let source = std::fs::read_to_string(&manifest).ok()?;
+    let (entries, _) = scan(&source);
+    let ports: Vec<String> = dependencies(&entries)
+        .into_iter()
+        .map(|entry| entry.value.to_string())
+        .collect();
+    MANIFESTS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(manifest, (modified, ports.clone()));
+    Some(ports)
+}
+
+fn undeclared_port_in(
+    installed: &Installed,
+    package: &PackageUse,
+    declared: &[String],
+) -> Option<String> {
+    let port = installed_port(installed, &package.name)?;
+    if declared
+        .iter()
+        .any(|declared| provides(installed, declared, package))
+    {
+        return None;
+    }
+    Some(port)
+}
+
+/// The installed port providing `package` when the manifest does not declare
+/// it
+pub fn undeclared_port(package: &PackageUse, declared: &[String]) -> Option<String> {
+    undeclared_port_in(
+        &INSTALLED_PACKAGES
+            .read()
+            .unwrap_or_else(PoisonError::into_inner),
+        package,
+        declared,
+    )
+}
+
+/// A `find_package()` of the project
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageUse {
+    pub name: String,
+    pub components: Vec<String>,
+    pub start: Point,
+    pub end: Point,
+}
+
+fn find_packages_inner(node: Node, source: &str, packages: &mut Vec<PackageUse>) {
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if child.kind() != CMakeNodeKinds::NORMAL_COMMAND {
+            find_packages_inner(child, source, packages);
+            continue;
+        }
+        let is_find_package = child
+            .child(0)
+            .is_some_and(|name| source[name.byte_range()].eq_ignore_ascii_case("find_package"));
+        if !is_find_package {
+            continue;
+        }
+        let Some(arguments) = child
+            .child(2)
+            .filter(|arguments| arguments.kind() == CMakeNodeKinds::ARGUMENT_LIST)
+        else {
+            continue;
+        };
+        let mut walk = arguments.walk();
+        let arguments: Vec<Node> = arguments
+            .children(&mut walk)
+            .filter(|argument| argument.kind() == CMakeNodeKinds::ARGUMENT)
+            .collect();
+        let Some((package, rest)) = arguments.split_first() else {
+            continue;
+        };
+        let rest: Vec<&str> = rest
+            .iter()
+            .map(|argument| &source[argument.byte_range()])
+            .collect();
+        packages.push(PackageUse {
+            name: source[package.byte_range()].trim_matches('"').to_string(),
+            components: components(&rest).into_iter().map(String::from).collect(),
+            start: package.start_position(),
+            end: package.end_position(),
+        });
+    }
+}
+
+/// The `find_package()` calls of `source`
+pub fn find_packages(source: &str) -> Vec<PackageUse> {
+    let mut parse = tree_sitter::Parser::new();
+    parse.set_language(&TREESITTER_CMAKE_LANGUAGE).unwrap();
+    let Some(tree) = parse.parse(source, None) else {
+        return vec![];
+    };
+    let mut packages = vec![];
+    find_packages_inner(tree.root_node(), source, &mut packages);
+    packages
+}
+
+/// The `find_package()` calls of `file`, parsed again only when it changes
+fn file_packages(file: &Path) -> Option<Vec<PackageUse>> {
+    let modified = std::fs::metadata(file)
+        .and_then(|metadata| metadata.modified())
+        .ok()?;
+    if let Some((cached, packages)) = PACKAGE_USES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(file)
+        && *cached == modified
+    {
+        return Some(packages.clone());
+    }
+    let source = std::fs::read_to_string(file).ok()?;
+    let packages = find_packages(&source);
+    PACKAGE_USES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(file.to_path_buf(), (modified, packages.clone()));
+    Some(packages)
+}
+
+/// The `find_package()` calls of the project in `root`, with their file
+fn project_packages(root: &Path) -> Vec<(PathBuf, PackageUse)> {
+    lint::collect_files(&[root.to_path_buf()])
+        .into_iter()
+        .filter_map(|file| {
+            let packages = file_packages(&file)?;
+            Some((file, packages))
+        })
+        .flat_map(|(file, packages)| {
+            packages
+                .into_iter()
+                .map(move |package| (file.clone(), package))
+        })
+        .collect()
+}
+
+/// Whether no package of `packages` comes from `port`. The ports of the vcpkg
+/// helpers, like `vcpkg-cmake`, are only used by the other ports
+fn is_unused(installed: &Installed, port: &str, packages: &[PackageUse]) -> bool {
+    !port.starts_with("vcpkg-")
+        && !packages
+            .iter()
+            .any(|package| provides(installed, port, package))
+}
+
+fn check_in(installed: &Installed, local_path: &Path, source: &str) -> Vec<Diagnostic> {
+    if let Err(error) = json_scan::parse(source) {
+        return vec![*error];
+    }
+    let Some(root) = local_path.parent() else {
+        return vec![];
+    };
+    let rule = &rules::VCPKG_UNUSED_PORT;
+    let Some(severity) = rule.configured_severity(Some(local_path)) else {
+        return vec![];
+    };
+    let packages: Vec<PackageUse> = project_packages(root)
+        .into_iter()
+        .map(|(_, package)| package)
+        .collect();
+    let (entries, _) = scan(source);
+    dependencies(&entries)
+        .into_iter()
+        .filter(|entry| is_unused(installed, entry.value, &packages))
+        .map(|entry| Diagnostic {
+            range: Range::new(
+                position_of(source, entry.offset),
+                position_of(source, entry.offset + entry.value.len()),
+            ),
+            severity: Some(severity),
+            code: Some(NumberOrString::String(rule.id.to_string())),
+            code_description: rule.url().parse().ok().map(|href| CodeDescription { href }),
+            source: Some("neocmakelsp".to_string()),
+            message: format!(
+                "No find_package() of the project uses the port {}",
+                entry.value
+            ),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// The problems of the manifest: invalid JSON, and the ports no
+/// `find_package()` of the project uses
+pub fn check(local_path: &Path, source: &str) -> Vec<Diagnostic> {
+    check_in(
+        &INSTALLED_PACKAGES
+            .read()
+            .unwrap_or_else(PoisonError::into_inner),
+        local_path,
+        source,
+    )
+}
+
+/// The ports of the vcpkg checkout in `VCPKG_ROOT`
+fn available_ports() -> Vec<String> {
+    let Some(root) = std::env::var_os("VCPKG_ROOT") else {
+        return vec![];
+    };
+    let Ok(ports) = std::fs::read_dir(Path::new(&root).join("ports")) else {
+        return vec![];
+    };
+    ports
+        .flatten()
+        .filter_map(|port| port.file_name().into_string().ok())
+        .collect()
+}
+
+/// The ports the project needs for `package`. A package found by a find module
+/// of cmake, in `modules_dir`, needs none unless vcpkg installed it.
+fn needed_ports(
+    installed: &Installed,
+    modules_dir: Option<&Path>,
+    package: &PackageUse,
+) -> Vec<String> {
+    let mut ports = component_ports(package);
+    if let Some(port) = installed_port(installed, &package.name) {
+        ports.push(port);
+    } else if !modules_dir.is_some_and(|dir| {
+        cmake_modules::module_in(dir, &format!("Find{}", package.name)).is_some()
+    }) {
+        ports.push(port_name(&package.name));
+    }
+    ports
+}
+
+fn complete_in(
+    installed: &Installed,
+    modules_dir: Option<&Path>,
+    local_path: &Path,
+    source: &str,
+    location: Position,
+) -> Option<CompletionResponse> {
+    let offset = offset_of(source, location)?;
+    let Some(Typed::Value(entry)) = scan(&source[..offset]).1 else {
+        return None;
+    };
+    if dependencies(std::slice::from_ref(&entry)).is_empty() {
+        return None;
+    }
+    let (entries, _) = scan(source);
+    let declared: Vec<&str> = dependencies(&entries)
+        .into_iter()
+        .map(|entry| entry.value)
+        .collect();
+    let mut used = BTreeSet::new();
+    if let Some(root) = local_path.parent() {
+        for (_, package) in project_packages(root) {
+            used.extend(needed_ports(installed, modules_dir, &package));
+        }
+    }
+    let mut others: BTreeSet<String> = available_ports().into_iter().collect();
+    others.extend(installed.values().cloned());
+    let items: Vec<CompletionItem> = used
+        .iter()
+        .map(|port| (port, "Port of a find_package() of the project"))
+        .chain(
+            others
+                .iter()
+                .filter(|port| !used.contains(*port))
+                .map(|port| (port, "vcpkg port")),
+        )
+        .filter(|(port, _)| !declared.contains(&port.as_str()))
+        .map(|(port, detail)| CompletionItem {
+            label: port.clone(),
+            kind: Some(CompletionItemKind::MODULE),
+            detail: Some(detail.to_string()),
+            ..Default::default()
+        })
+        .collect();
+    if items.is_empty() {
+        return None;
+    }
+    Some(CompletionResponse::Array(items))
+}
+
+/// Completion of the dependencies of the manifest: first the ports of the
+/// packages the project finds, then the installed and available ports
+pub fn complete(local_path: &Path, source: &str, location: Position) -> Option<CompletionResponse> {
+    complete_in(
+        &INSTALLED_PACKAGES
+            .read()
+            .unwrap_or_else(PoisonError::into_inner),
+        cmake_modules::modules_dir().as_deref(),
+        local_path,
+        source,
+        location,
+    )
+}
+
+/// A `find_package()` of a port the manifest does not declare
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UndeclaredPackage {
+    pub package: String,
+    pub port: String,
+    pub location: Location,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VcpkgReport {
+    pub undeclared: Vec<UndeclaredPackage>,
+    /// The declared ports no `find_package()` uses
+    pub unused: Vec<String>,
+}
+
+fn report_in(installed: &Installed, root: &Path) -> Option<VcpkgReport> {
+    let declared = declared_ports(root)?;
+    let uses = project_packages(root);
+    let undeclared = uses
+        .iter()
+        .filter_map(|(file, package)| {
+            let port = undeclared_port_in(installed, package, &declared)?;
+            Some(UndeclaredPackage {
+                package: package.name.clone(),
+                port,
+                location: Location {
+                    uri: Uri::from_file_path(file).unwrap(),
+                    range: Range::new(package.start.to_position(), package.end.to_position()),
+                },
+            })
+        })
+        .collect();
+    let packages: Vec<PackageUse> = uses.into_iter().map(|(_, package)| package).collect();
+    let unused = declared
+        .into_iter()
+        .filter(|port| is_unused(installed, port, &packages))
+        .collect();
+    Some(VcpkgReport { undeclared, unused })
+}
+
+/// The report of the project in `root`, `None` without a manifest
+pub fn report(root: &Path) -> Option<VcpkgReport> {
+    report_in(
+        &INSTALLED_PACKAGES
+            .read()
+            .unwrap_or_else(PoisonError::into_inner),
+        root,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vcpkg_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let installed = dir.path().join("vcpkg_installed");
+        for (port, config) in [
+            ("fmt", "fmt-config.cmake"),
+            ("nlohmann-json", "nlohmann_jsonConfig.cmake"),
+            ("spdlog", "spdlogConfig.cmake"),
+        ] {
+            let share = installed.join("x64-linux").join("share").join(port);
+            std::fs::create_dir_all(&share).unwrap();
+            std::fs::write(share.join(config), "").unwrap();
+        }
+        let installed = read_installed_packages(&installed);
+        let modules = dir.path().join("Modules");
+        std::fs::create_dir(&modules).unwrap();
+        std::fs::write(modules.join("FindThreads.cmake"), "").unwrap();
+
+        let manifest = r#"{
+  "name": "demo",
+  "dependencies": [
+    "fmt",
+    { "name": "nlohmann-json", "features": ["diagnostics"] },
+    "vcpkg-cmake",
+    "zlib"
+  ]
+}"#;
+        std::fs::write(dir.path().join(MANIFEST), manifest).unwrap();
+        std::fs::write(
+            dir.path().join("CMakeLists.txt"),
+            "find_package(fmt CONFIG REQUIRED)\nfind_package(nlohmann_json)\nfind_package(spdlog)\nfind_package(Threads)\n",
+        )
+        .unwrap();
+
+        let report = report_in(&installed, dir.path()).unwrap();
+        assert_eq!(report.undeclared.len(), 1);
+        assert_eq!(report.undeclared[0].package, "spdlog");
+        assert_eq!(report.undeclared[0].port, "spdlog");
+        assert_eq!(
+            report.undeclared[0].location.range.start,
+            Position::new(2, 13)
+        );
+        assert_eq!(report.unused, vec!["zlib"]);
+
+        let diagnostics = check_in(&installed, &dir.path().join(MANIFEST), manifest);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range.start, Position::new(6, 5));
+        assert_eq!(
+            diagnostics[0].code,
+            Some(NumberOrString::String("N602".to_string()))
+        );
+
+        let typing = "{\n  \"dependencies\": [ \"fmt\", \"";
+        let Some(CompletionResponse::Array(items)) = complete_in(
+            &installed,
+            Some(&modules),
+            &dir.path().join(MANIFEST),
+            typing,
+            Position::new(1, 28),
+        ) else {
+            panic!("no ports");
+        };
+        let labels: Vec<&str> = items.iter().map(|item| item.label.as_str()).collect();
+        assert_eq!(labels[..2], ["nlohmann-json", "spdlog"]);
+        assert!(!labels.contains(&"fmt"));
+        assert!(!labels.contains(&"threads"));
+    }
+
+    #[test]
+    fn test_boost_components() {
+        let dir = tempfile::tempdir().unwrap();
+        let installed = HashMap::from([("boost".to_string(), "boost-cmake".to_string())]);
+        std::fs::write(
+            dir.path().join(MANIFEST),
+            r#"{ "dependencies": ["boost-filesystem", "boost-regex"] }"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("CMakeLists.txt"),
+            "find_package(Boost 1.80 REQUIRED COMPONENTS filesystem unit_test_framework CONFIG)\n",
+        )
+        .unwrap();
+
+        let report = report_in(&installed, dir.path()).unwrap();
+        assert!(report.undeclared.is_empty());
+        assert_eq!(report.unused, vec!["boost-regex"]);
+
+        let packages = project_packages(dir.path());
+        assert_eq!(
+            needed_ports(&installed, None, &packages[0].1),
+            vec!["boost-filesystem", "boost-test", "boost-cmake"]
+        );
+    }
+
+    #[test]
+    fn test_components() {
+        assert_eq!(
+            components(&[
+                "COMPONENTS",
+                "Core",
+                "Widgets",
+                "OPTIONAL_COMPONENTS",
+                "Svg"
+            ]),
+            vec!["Core", "Widgets", "Svg"]
+        );
+        assert_eq!(
+            components(&[
+                "1.80",
+                "REQUIRED",
+                "system",
+                "NO_DEFAULT_PATH",
+                "PATHS",
+                "/opt"
+            ]),
+            vec!["system"]
+        );
+        assert!(components(&["CONFIG", "HINTS", "/opt"]).is_empty());
+    }
+}
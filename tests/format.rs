@@ -0,0 +1,18 @@
+use assert_cmd::cargo::cargo_bin_cmd;
+
+#[test]
+fn format_stdin() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut command = cargo_bin_cmd!();
+    command
+        .current_dir(dir.path())
+        .args(["format", "--stdin", "--stdin-filename", "CMakeLists.txt"])
+        .write_stdin("if(A)\nset(B 1)\nendif()\n");
+
+    let output = command.output().unwrap();
+    assert!(output.status.success(), "Failed to call neocmakelsp");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "if(A)\n  set(B 1)\nendif()\n"
+    );
+}